@@ -0,0 +1,380 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Baseline benchmarks for the hot paths of reading and writing Redshirt streams.
+//!
+//! Run with `cargo bench --features bench --bench hot_paths`. All inputs are generated from a
+//! fixed seed in memory, so results depend only on the machine. Benchmark IDs are stable; rename
+//! one only if what it measures changes, so that Criterion's saved baselines stay comparable.
+//!
+//! The `read_to_end` group measures decoding into memory that is not initialised first. To compare
+//! it with decoding into zeroed memory, run it once as above and once with a nightly compiler and
+//! `RUSTFLAGS="--cfg redshirt_unstable"`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use redshirt::{__bench::xor_bytes, v1, v2};
+use std::{
+    convert::TryFrom,
+    env,
+    fs::{self, File},
+    io::{self, BufReader, Cursor, Read, Seek, SeekFrom, Write},
+    process,
+};
+
+const MIB: usize = 1024 * 1024;
+const LARGE_LEN: usize = 64 * MIB;
+const COPY_LEN: usize = 256 * MIB;
+const DECODE_LEN: usize = 512 * MIB;
+
+/// Generates `len` bytes from a fixed-seed linear congruential generator.
+fn data(len: usize) -> Vec<u8> {
+    let mut state = 0x5EED_u32;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            state.to_be_bytes()[0]
+        })
+        .collect()
+}
+
+fn xor(c: &mut Criterion) {
+    let mut group = c.benchmark_group("xor_bytes");
+    for len in [4096, 16384, MIB] {
+        let mut buf = data(len);
+        let _ = group.throughput(Throughput::Bytes(u64::try_from(len).unwrap()));
+        let _ = group.bench_with_input(BenchmarkId::from_parameter(len), &len, |b, _| {
+            b.iter(|| xor_bytes(black_box(&mut buf)));
+        });
+    }
+    group.finish();
+}
+
+fn large(c: &mut Criterion) {
+    let payload = data(LARGE_LEN);
+    let v1_encoded = v1::encode(&payload);
+    let v2_encoded = v2::encode(&payload);
+    let mut group = c.benchmark_group("large");
+    let _ = group
+        .sample_size(10)
+        .throughput(Throughput::Bytes(u64::try_from(LARGE_LEN).unwrap()));
+    let _ = group.bench_function("v1_reader_decode_64mib", |b| {
+        b.iter(|| {
+            let mut reader = v1::Reader::new(Cursor::new(&v1_encoded)).unwrap();
+            io::copy(&mut reader, &mut io::sink()).unwrap()
+        });
+    });
+    let _ = group.bench_function("v2_reader_new_verify_64mib", |b| {
+        b.iter(|| v2::Reader::new(Cursor::new(&v2_encoded)).unwrap());
+    });
+    let mut output = Vec::with_capacity(v2_encoded.len());
+    let _ = group.bench_function("v2_writer_encode_64mib", |b| {
+        b.iter(|| {
+            output.clear();
+            let mut writer = v2::Writer::new(Cursor::new(&mut output)).unwrap();
+            writer.write_all(&payload).unwrap();
+            let _ = writer.into_inner().unwrap();
+        });
+    });
+    group.finish();
+}
+
+fn seek_small_reads(c: &mut Criterion) {
+    const READS: usize = 1000;
+    const READ_LEN: usize = 16;
+    let encoded = v1::encode(&data(MIB));
+    let offsets: Vec<u64> = data(READS * 4)
+        .chunks(4)
+        .map(|bytes| {
+            let offset = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            u64::from(offset) % u64::try_from(MIB - READ_LEN).unwrap()
+        })
+        .collect();
+    let mut group = c.benchmark_group("seek_small_reads");
+    let _ = group.throughput(Throughput::Elements(u64::try_from(READS).unwrap()));
+    let _ = group.bench_function("v1_reader_1000x16b", |b| {
+        let mut reader = v1::Reader::new(Cursor::new(&encoded)).unwrap();
+        let mut buf = [0; READ_LEN];
+        b.iter(|| {
+            for &offset in &offsets {
+                let _ = reader.seek(SeekFrom::Start(offset)).unwrap();
+                reader.read_exact(&mut buf).unwrap();
+            }
+        });
+    });
+    group.finish();
+}
+
+fn buffered_seeks(c: &mut Criterion) {
+    const READS: usize = 1000;
+    const READ_LEN: usize = 4;
+    const WINDOW: usize = 4096;
+    const CAPACITY: usize = 8192;
+    let encoded = v1::encode(&data(MIB));
+    // Clusters of 16 reads at random positions within a window, as when parsing a record
+    // structure that points back and forth within itself.
+    let offsets: Vec<u64> = data(READS * 4)
+        .chunks(4)
+        .enumerate()
+        .map(|(i, bytes)| {
+            let cluster = u64::try_from(i / 16 * WINDOW).unwrap() % u64::try_from(MIB).unwrap();
+            let offset = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            cluster + u64::from(offset) % u64::try_from(WINDOW - READ_LEN).unwrap()
+        })
+        .collect();
+    let mut group = c.benchmark_group("buffered_seeks");
+    let _ = group.throughput(Throughput::Elements(u64::try_from(READS).unwrap()));
+    let _ = group.bench_function("v1_buffered_reader_1000x4b", |b| {
+        let mut reader = v1::Reader::new(Cursor::new(&encoded))
+            .unwrap()
+            .buffered(CAPACITY);
+        let mut buf = [0; READ_LEN];
+        b.iter(|| {
+            for &offset in &offsets {
+                let _ = reader.seek(SeekFrom::Start(offset)).unwrap();
+                reader.read_exact(&mut buf).unwrap();
+            }
+        });
+    });
+    let _ = group.bench_function("v1_std_bufreader_1000x4b", |b| {
+        let mut reader =
+            BufReader::with_capacity(CAPACITY, v1::Reader::new(Cursor::new(&encoded)).unwrap());
+        let mut buf = [0; READ_LEN];
+        b.iter(|| {
+            for &offset in &offsets {
+                let _ = reader.seek(SeekFrom::Start(offset)).unwrap();
+                reader.read_exact(&mut buf).unwrap();
+            }
+        });
+    });
+    group.finish();
+}
+
+fn writes(c: &mut Criterion) {
+    const SMALL_WRITES: usize = 100_000;
+    const SMALL_LEN: usize = 16;
+    const TINY_LEN: usize = 4;
+    const LARGE_WRITES: usize = 16;
+    let small = data(SMALL_LEN);
+    let large = data(MIB);
+    let mut group = c.benchmark_group("writes");
+    let _ = group.sample_size(20).throughput(Throughput::Bytes(
+        u64::try_from(SMALL_WRITES * SMALL_LEN).unwrap(),
+    ));
+    let mut output = Vec::with_capacity(SMALL_WRITES * SMALL_LEN + MIB);
+    let _ = group.bench_function("v1_writer_100000x16b", |b| {
+        b.iter(|| {
+            output.clear();
+            let mut writer = v1::Writer::new(&mut output).unwrap();
+            for _ in 0..SMALL_WRITES {
+                writer.write_all(black_box(&small)).unwrap();
+            }
+        });
+    });
+    let _ = group.bench_function("v2_multi_writer_100000x16b", |b| {
+        b.iter(|| {
+            let mut writer = v2::MultiWriter::new(io::sink(), io::sink()).unwrap();
+            for _ in 0..SMALL_WRITES {
+                writer.write_all(black_box(&small)).unwrap();
+            }
+        });
+    });
+    // Each write to a file is a system call, unless small writes are coalesced.
+    let _ = group.throughput(Throughput::Bytes(
+        u64::try_from(SMALL_WRITES * TINY_LEN).unwrap(),
+    ));
+    let path = env::temp_dir().join(format!("redshirt-bench-{}", process::id()));
+    for (id, coalesce) in [
+        ("v2_writer_file_100000x4b", false),
+        ("v2_writer_file_coalesced_100000x4b", true),
+    ] {
+        let _ = group.bench_function(id, |b| {
+            b.iter(|| {
+                let mut writer = v2::WriterOptions::new()
+                    .coalesce_writes(coalesce)
+                    .from_writer(File::create(&path).unwrap())
+                    .unwrap();
+                for _ in 0..SMALL_WRITES {
+                    writer.write_all(black_box(&small[..TINY_LEN])).unwrap();
+                }
+                writer.into_inner().unwrap()
+            });
+        });
+    }
+    fs::remove_file(&path).unwrap();
+    let _ = group.throughput(Throughput::Bytes(
+        u64::try_from(LARGE_WRITES * MIB).unwrap(),
+    ));
+    let mut output = Vec::with_capacity((LARGE_WRITES + 1) * MIB);
+    let _ = group.bench_function("v1_writer_16x1mib", |b| {
+        b.iter(|| {
+            output.clear();
+            let mut writer = v1::Writer::new(&mut output).unwrap();
+            for _ in 0..LARGE_WRITES {
+                writer.write_all(black_box(&large)).unwrap();
+            }
+        });
+    });
+    let _ = group.bench_function("v2_multi_writer_16x1mib", |b| {
+        b.iter(|| {
+            let mut writer = v2::MultiWriter::new(io::sink(), io::sink()).unwrap();
+            for _ in 0..LARGE_WRITES {
+                writer.write_all(black_box(&large)).unwrap();
+            }
+        });
+    });
+    group.finish();
+}
+
+fn copy(c: &mut Criterion) {
+    let payload = data(COPY_LEN);
+    let v1_encoded = v1::encode(&payload);
+    let mut group = c.benchmark_group("copy");
+    let _ = group
+        .sample_size(10)
+        .throughput(Throughput::Bytes(u64::try_from(COPY_LEN).unwrap()));
+    let _ = group.bench_function("v1_reader_io_copy_256mib", |b| {
+        b.iter(|| {
+            let mut reader = v1::Reader::new(&v1_encoded[..]).unwrap();
+            io::copy(&mut reader, &mut io::sink()).unwrap()
+        });
+    });
+    let _ = group.bench_function("v1_reader_pipe_to_256mib", |b| {
+        b.iter(|| {
+            let mut reader = v1::Reader::new(&v1_encoded[..]).unwrap();
+            reader.pipe_to(&mut io::sink()).unwrap()
+        });
+    });
+    let _ = group.bench_function("v1_copy_raw_256mib", |b| {
+        b.iter(|| {
+            let mut reader = v1::Reader::new(&v1_encoded[..]).unwrap();
+            let mut writer = v1::Writer::new(io::sink()).unwrap();
+            v1::copy_raw(&mut reader, &mut writer).unwrap()
+        });
+    });
+    drop(v1_encoded);
+    let _ = group.bench_function("v1_writer_io_copy_256mib", |b| {
+        b.iter(|| {
+            let mut writer = v1::Writer::new(io::sink()).unwrap();
+            io::copy(&mut &payload[..], &mut writer).unwrap()
+        });
+    });
+    let _ = group.bench_function("v1_writer_write_reader_256mib", |b| {
+        b.iter(|| {
+            let mut writer = v1::Writer::new(io::sink()).unwrap();
+            writer.write_reader(&mut &payload[..]).unwrap()
+        });
+    });
+    let mut output = Vec::with_capacity(COPY_LEN + MIB);
+    let _ = group.bench_function("v2_writer_io_copy_256mib", |b| {
+        b.iter(|| {
+            output.clear();
+            let mut writer = v2::Writer::new(Cursor::new(&mut output)).unwrap();
+            let _ = io::copy(&mut &payload[..], &mut writer).unwrap();
+            let _ = writer.into_inner().unwrap();
+        });
+    });
+    let _ = group.bench_function("v2_writer_write_reader_256mib", |b| {
+        b.iter(|| {
+            output.clear();
+            let mut writer = v2::Writer::new(Cursor::new(&mut output)).unwrap();
+            let _ = writer.write_reader(&mut &payload[..]).unwrap();
+            let _ = writer.into_inner().unwrap();
+        });
+    });
+    // Copying between Redshirt 2 streams, verifying the source lazily, either by decoding and
+    // re-encoding every byte or by moving the encoded bytes as they are.
+    let v2_encoded = v2::encode(&payload);
+    drop(payload);
+    let lazy = |encoded| {
+        v2::ReaderOptions::new()
+            .verification(v2::Verification::Lazy)
+            .from_stream(encoded)
+            .unwrap()
+    };
+    let _ = group.bench_function("v2_reader_writer_io_copy_256mib", |b| {
+        b.iter(|| {
+            output.clear();
+            let mut reader = lazy(&v2_encoded[..]);
+            let mut writer = v2::Writer::new(Cursor::new(&mut output)).unwrap();
+            let _ = io::copy(&mut reader, &mut writer).unwrap();
+            let _ = writer.into_inner().unwrap();
+        });
+    });
+    let _ = group.bench_function("v2_copy_raw_256mib", |b| {
+        b.iter(|| {
+            output.clear();
+            let mut reader = lazy(&v2_encoded[..]);
+            let mut writer = v2::Writer::new(Cursor::new(&mut output)).unwrap();
+            let _ = v2::copy_raw(&mut reader, &mut writer).unwrap();
+            let _ = writer.into_inner().unwrap();
+        });
+    });
+    group.finish();
+}
+
+fn read_to_end(c: &mut Criterion) {
+    let payload = data(DECODE_LEN);
+    let v1_encoded = v1::encode(&payload);
+    let v2_encoded = v2::encode(&payload);
+    drop(payload);
+    let mut group = c.benchmark_group("read_to_end");
+    let _ = group
+        .sample_size(10)
+        .throughput(Throughput::Bytes(u64::try_from(DECODE_LEN).unwrap()));
+    let _ = group.bench_function("v1_reader_512mib", |b| {
+        b.iter(|| {
+            let mut decoded = Vec::with_capacity(DECODE_LEN);
+            let mut reader = v1::Reader::new(&v1_encoded[..]).unwrap();
+            let _ = reader.read_to_end(&mut decoded).unwrap();
+            decoded
+        });
+    });
+    let _ = group.bench_function("v2_reader_unverified_512mib", |b| {
+        b.iter(|| {
+            let mut decoded = Vec::with_capacity(DECODE_LEN);
+            let mut reader = v2::ReaderOptions::new()
+                .verification(v2::Verification::Skip)
+                .from_stream(&v2_encoded[..])
+                .unwrap();
+            let _ = reader.read_to_end(&mut decoded).unwrap();
+            decoded
+        });
+    });
+    // Reusing the output buffer leaves only the cost of zeroing it, as the memory is already
+    // paged in; `read_to_end` cannot know that its spare capacity was initialised before.
+    let mut decoded = Vec::with_capacity(DECODE_LEN);
+    let _ = group.bench_function("v1_reader_reused_512mib", |b| {
+        b.iter(|| {
+            decoded.clear();
+            let mut reader = v1::Reader::new(&v1_encoded[..]).unwrap();
+            reader.read_to_end(&mut decoded).unwrap()
+        });
+    });
+    let _ = group.bench_function("v2_reader_unverified_reused_512mib", |b| {
+        b.iter(|| {
+            decoded.clear();
+            let mut reader = v2::ReaderOptions::new()
+                .verification(v2::Verification::Skip)
+                .from_stream(&v2_encoded[..])
+                .unwrap();
+            reader.read_to_end(&mut decoded).unwrap()
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    xor,
+    large,
+    seek_small_reads,
+    buffered_seeks,
+    writes,
+    copy,
+    read_to_end
+);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Compares serial and parallel encoding of in-memory buffers of increasing size, to find the
+//! point from which `codec::par_encode_in_place` is faster.
+//!
+//! Run with `cargo bench --features rayon --bench par_xor`.
+
+use redshirt::codec;
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+/// The serial transform used by `v1::encode` and `v2::encode` below the parallel threshold.
+fn encode_in_place(data: &mut [u8]) {
+    for n in data {
+        *n ^= 0x80;
+    }
+}
+
+/// Returns the fastest of several timed runs of `f` over `data`, to reduce scheduling noise.
+fn time<F: FnMut(&mut [u8])>(data: &mut [u8], mut f: F) -> Duration {
+    let runs = (256 * 1024 * 1024 / data.len()).clamp(5, 1000);
+    (0..runs)
+        .map(|_| {
+            let start = Instant::now();
+            f(black_box(&mut *data));
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    println!(
+        "{:>10}  {:>12}  {:>12}  {:>8}",
+        "size", "serial", "parallel", "speedup"
+    );
+    let mut crossover = None;
+    for shift in 12..=26 {
+        let len = 1_usize << shift;
+        let mut data = vec![0x42; len];
+        let serial = time(&mut data, encode_in_place);
+        let parallel = time(&mut data, codec::par_encode_in_place);
+        let speedup = serial.as_secs_f64() / parallel.as_secs_f64();
+        if speedup > 1.0 && crossover.is_none() {
+            crossover = Some(len);
+        }
+        println!(
+            "{:>7} KiB  {:>12?}  {:>12?}  {:>7.2}x",
+            len / 1024,
+            serial,
+            parallel,
+            speedup
+        );
+    }
+    match crossover {
+        Some(len) => println!("parallel encoding is faster from {} KiB", len / 1024),
+        None => println!("parallel encoding was never faster"),
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+//! Corrupts valid Redshirt 2 streams, checking that every corruption is reported as the right
+//! error.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use redshirt::{v2, Error};
+
+const MARKER_LEN: usize = 9;
+const HEADER_LEN: usize = 29;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    payload: Vec<u8>,
+    /// Bytes to flip, as an index into the encoded stream and a mask to XOR it with.
+    mutations: Vec<(u16, u8)>,
+}
+
+fuzz_target!(|input: Input| {
+    let encoded = v2::encode(&input.payload);
+    let mut corrupt = encoded.clone();
+    for (index, mask) in input.mutations {
+        let index = usize::from(index) % corrupt.len();
+        corrupt[index] ^= mask;
+    }
+    let result = v2::decode(&corrupt);
+    if corrupt == encoded {
+        assert_eq!(result.unwrap(), input.payload);
+    } else if corrupt[..MARKER_LEN] != encoded[..MARKER_LEN] {
+        assert!(matches!(result, Err(Error::BadHeader)), "{:?}", result);
+    } else if corrupt[MARKER_LEN..HEADER_LEN].iter().all(|&b| b == 0) {
+        assert!(matches!(result, Err(Error::Unfinalized)), "{:?}", result);
+    } else {
+        assert!(
+            matches!(result, Err(Error::BadChecksum { .. })),
+            "{:?}",
+            result
+        );
+    }
+});
@@ -0,0 +1,46 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+//! Decodes arbitrary bytes with every decoder, checking that they agree with each other.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use redshirt::{codec, v1, v2, Error};
+use std::io::{Cursor, Read};
+
+const V1_HEADER_LEN: usize = 9;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(decoded) = v1::decode(data) {
+        let mut reader = v1::Reader::new(Cursor::new(data)).unwrap();
+        let mut streamed = Vec::new();
+        let _ = reader.read_to_end(&mut streamed).unwrap();
+        assert_eq!(streamed, decoded);
+        let mut in_place = data[V1_HEADER_LEN..].to_vec();
+        codec::par_decode_in_place(&mut in_place);
+        assert_eq!(in_place, decoded);
+        assert_eq!(v1::encode(&decoded), data);
+    }
+    let lazy = v2::ReaderOptions::new()
+        .verification(v2::Verification::Lazy)
+        .from_reader(Cursor::new(data));
+    match (v2::decode(data), lazy) {
+        (Ok(decoded), Ok(mut reader)) => {
+            let mut streamed = Vec::new();
+            let _ = reader.read_to_end(&mut streamed).unwrap();
+            assert_eq!(streamed, decoded);
+            assert_eq!(v2::encode(&decoded), data);
+        }
+        (Err(Error::BadChecksum { .. }), Ok(mut reader)) => {
+            let e = reader.read_to_end(&mut Vec::new()).unwrap_err();
+            let inner = e.get_ref().and_then(|inner| inner.downcast_ref::<Error>());
+            assert!(matches!(inner, Some(Error::BadChecksum { .. })), "{:?}", e);
+        }
+        (Err(_), Err(_)) => (),
+        (decoded, reader) => panic!("{:?} but {:?}", decoded, reader.map(|_| ())),
+    }
+});
@@ -0,0 +1,60 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Feeds arbitrary bytes to every function that parses a header, checking that each either
+//! succeeds or fails with one of its documented errors.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use redshirt::{v1, v2, Error, Version};
+use std::io::{Cursor, ErrorKind};
+
+const V1_HEADER_LEN: usize = 9;
+
+fn is_eof(e: &Error) -> bool {
+    matches!(e, Error::Io(e) if e.kind() == ErrorKind::UnexpectedEof)
+}
+
+fuzz_target!(|data: &[u8]| {
+    let version = Version::detect(data);
+    if let Err(e) = v1::Reader::new(Cursor::new(data)) {
+        assert!(matches!(e, Error::BadHeader) || is_eof(&e), "{:?}", e);
+        assert_ne!(version, Some(Version::V1));
+    }
+    match v2::Reader::new(Cursor::new(data)) {
+        Ok(_) => assert_eq!(version, Some(Version::V2)),
+        Err(e) => assert!(
+            matches!(
+                e,
+                Error::BadHeader | Error::Unfinalized | Error::BadChecksum { .. }
+            ) || is_eof(&e),
+            "{:?}",
+            e
+        ),
+    }
+    match v1::decode(data) {
+        Ok(decoded) => assert_eq!(decoded.len() + V1_HEADER_LEN, data.len()),
+        Err(e) => assert!(matches!(e, Error::BadHeader), "{:?}", e),
+    }
+    if let Err(e) = v2::decode(data) {
+        assert!(
+            matches!(
+                e,
+                Error::BadHeader | Error::Unfinalized | Error::BadChecksum { .. }
+            ),
+            "{:?}",
+            e
+        );
+    }
+    for verify in [false, true] {
+        match redshirt::inspect_reader(Cursor::new(data), verify) {
+            Ok(info) => assert_eq!(Some(info.version), version),
+            Err(e) => assert!(matches!(e, Error::BadHeader), "{:?}", e),
+        }
+    }
+});
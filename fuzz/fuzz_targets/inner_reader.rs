@@ -0,0 +1,98 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+//! Decodes valid streams through an underlying reader that returns short reads, interruptions and
+//! errors as scripted by the fuzzer, checking that every reader either returns the original data
+//! or the injected error.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use redshirt::{v1, v2, Error};
+use std::io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom};
+
+#[derive(Arbitrary, Clone, Copy, Debug)]
+enum Op {
+    /// Reads at most this many bytes, or one byte if zero.
+    Short(u8),
+    /// Fails with `ErrorKind::Interrupted` without reading anything.
+    Interrupted,
+    /// Fails with the injected error without reading anything.
+    Fail,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    payload: Vec<u8>,
+    /// The result of each read; reads past the end of the script are not limited.
+    ops: Vec<Op>,
+}
+
+/// Wraps a stream, following a script for each read while still honouring the `Read` contract.
+struct Scripted<'a> {
+    inner: Cursor<Vec<u8>>,
+    ops: &'a [Op],
+}
+
+fn injected() -> io::Error {
+    io::Error::other("injected")
+}
+
+fn is_injected(e: &io::Error) -> bool {
+    e.kind() == ErrorKind::Other && e.to_string() == "injected"
+}
+
+impl Read for Scripted<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some((&op, rest)) = self.ops.split_first() else {
+            return self.inner.read(buf);
+        };
+        self.ops = rest;
+        match op {
+            Op::Short(n) => {
+                let len = buf.len().min(usize::from(n.max(1)));
+                self.inner.read(&mut buf[..len])
+            }
+            Op::Interrupted => Err(ErrorKind::Interrupted.into()),
+            Op::Fail => Err(injected()),
+        }
+    }
+}
+
+impl Seek for Scripted<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+fn check<T: Read>(reader: Result<T, Error>, payload: &[u8]) {
+    let mut reader = match reader {
+        Ok(reader) => reader,
+        Err(Error::Io(e)) if is_injected(&e) => return,
+        Err(e) => panic!("{:?}", e),
+    };
+    let mut decoded = Vec::new();
+    match reader.read_to_end(&mut decoded) {
+        Ok(_) => assert_eq!(decoded, payload),
+        Err(e) => assert!(is_injected(&e), "{:?}", e),
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let scripted = |encoded: Vec<u8>| Scripted {
+        inner: Cursor::new(encoded),
+        ops: &input.ops,
+    };
+    let v1 = v1::encode(&input.payload);
+    check(v1::Reader::new(scripted(v1)), &input.payload);
+    let v2 = v2::encode(&input.payload);
+    check(v2::Reader::new(scripted(v2.clone())), &input.payload);
+    let lazy = v2::ReaderOptions::new()
+        .verification(v2::Verification::Lazy)
+        .from_stream(scripted(v2));
+    check(lazy, &input.payload);
+});
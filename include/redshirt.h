@@ -0,0 +1,80 @@
+/*
+ * Copyright (c) 2019 FaultyRAM
+ *
+ * Licensed under the Apache License, Version 2.0
+ * <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+ * modified, or distributed except according to those terms.
+ */
+
+/*
+ * C API for the redshirt crate, available when it is built with the `capi` feature.
+ *
+ * Every function that can fail returns a `redshirt_status` code, where REDSHIRT_OK (zero)
+ * indicates success. After a failure, redshirt_last_error_message() returns a description of the
+ * error, which remains valid until the next call into this API on the same thread.
+ *
+ * Paths are NUL-terminated, UTF-8 encoded strings.
+ *
+ * Buffers returned by redshirt_decode_file() are owned by the caller, and must be released with
+ * redshirt_free_buffer(); they must not be passed to free(). All other pointers are borrowed for
+ * the duration of the call, and are never retained or freed by the library.
+ */
+
+#ifndef REDSHIRT_H
+#define REDSHIRT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define REDSHIRT_DIGEST_LEN 20
+
+typedef enum redshirt_status {
+    REDSHIRT_OK = 0,
+    REDSHIRT_ERROR_IO = 1,
+    REDSHIRT_ERROR_BAD_HEADER = 2,
+    REDSHIRT_ERROR_BAD_CHECKSUM = 3,
+    REDSHIRT_ERROR_UNFINALIZED = 4,
+    REDSHIRT_ERROR_INVALID_ARGUMENT = 5,
+    REDSHIRT_ERROR_INVALID_TEXT = 6,
+    REDSHIRT_ERROR_PAYLOAD_TOO_LARGE = 7,
+    REDSHIRT_ERROR_CANCELLED = 8
+} redshirt_status;
+
+/*
+ * Reads and decodes the Redshirt 1 or Redshirt 2 file at `path`, detecting the version from its
+ * header. On success, `*out_buf` receives the decoded data (or NULL if it is empty), which must be
+ * released with redshirt_free_buffer(), and `*out_len` receives its length.
+ */
+int redshirt_decode_file(const char *path, uint8_t **out_buf, size_t *out_len);
+
+/*
+ * Encodes `len` bytes from `data` and writes them to the file at `path`, replacing its contents
+ * if it exists. `version` must be 1 or 2. `data` may be NULL if `len` is zero.
+ */
+int redshirt_encode_file(const char *path, const uint8_t *data, size_t len, int version);
+
+/*
+ * Verifies the SHA-1 hash of the Redshirt 2 file at `path` without decoding it. On success, the
+ * hash is written to `out_digest`, which must have room for REDSHIRT_DIGEST_LEN bytes.
+ */
+int redshirt_verify(const char *path, uint8_t *out_digest);
+
+/* Releases a buffer returned by redshirt_decode_file(). Passing NULL does nothing. */
+void redshirt_free_buffer(uint8_t *buf, size_t len);
+
+/*
+ * Returns a description of the last error that occurred on this thread, or NULL if no error has
+ * occurred.
+ */
+const char *redshirt_last_error_message(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* REDSHIRT_H */
@@ -0,0 +1,91 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Access pattern hints for files, passed to the operating system with `posix_fadvise`.
+//!
+//! This requires the `fadvise` feature, and Android, FreeBSD or Linux, which provide
+//! `posix_fadvise`. Elsewhere, every hint succeeds without doing anything.
+
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+use std::{convert::TryFrom, io::Read, path::Path};
+use std::{fs::File, io};
+
+#[derive(Clone, Copy, Debug)]
+/// How a file is expected to be accessed.
+enum Advice {
+    /// The file will be read sequentially, so the operating system should read ahead more
+    /// aggressively.
+    Sequential,
+    #[cfg(feature = "fadvise")]
+    /// The cached contents of the file will not be needed again, so they can be evicted from the
+    /// page cache.
+    DontNeed,
+}
+
+#[inline]
+/// Tells the operating system that `file` will be read sequentially.
+pub(crate) fn sequential(file: &File) -> io::Result<()> {
+    sys::advise(file, Advice::Sequential)
+}
+
+#[cfg(feature = "fadvise")]
+#[inline]
+/// Tells the operating system that the cached contents of `file` will not be needed again.
+pub(crate) fn dont_need(file: &File) -> io::Result<()> {
+    sys::advise(file, Advice::DontNeed)
+}
+
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+/// Reads the whole file at `path`, as `fs::read` does, hinting that it is read sequentially.
+pub(crate) fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    // The hint is only an optimisation, so failing to give it is not an error.
+    let _ = sequential(&file);
+    let len = file.metadata().map_or(0, |metadata| metadata.len());
+    let mut data = Vec::with_capacity(usize::try_from(len).unwrap_or(0));
+    let _ = file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(all(
+    feature = "fadvise",
+    any(target_os = "android", target_os = "freebsd", target_os = "linux")
+))]
+#[allow(unsafe_code)]
+mod sys {
+    use super::Advice;
+    use std::{fs::File, io, os::unix::io::AsRawFd};
+
+    /// Applies `advice` to the whole of `file`.
+    pub(super) fn advise(file: &File, advice: Advice) -> io::Result<()> {
+        let advice = match advice {
+            Advice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+            Advice::DontNeed => libc::POSIX_FADV_DONTNEED,
+        };
+        // SAFETY: The file descriptor is owned by `file`, so it stays open for the duration of the
+        // call, and `posix_fadvise` does not access memory.
+        match unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice) } {
+            0 => Ok(()),
+            e => Err(io::Error::from_raw_os_error(e)),
+        }
+    }
+}
+
+#[cfg(not(all(
+    feature = "fadvise",
+    any(target_os = "android", target_os = "freebsd", target_os = "linux")
+)))]
+mod sys {
+    use super::Advice;
+    use std::{fs::File, io};
+
+    #[allow(clippy::unnecessary_wraps)]
+    /// Accepts a hint without passing it on.
+    pub(super) fn advise(_file: &File, _advice: Advice) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{
+    async_io::{RawRead, RawSeek, RawWrite},
+    cursor::{add_len, base_of, overflow_error, translate_seek, Staging, BUFFER_LEN},
+    xor_bytes,
+};
+use std::{
+    io::{self, SeekFrom},
+    task::{ready, Context, Poll},
+};
+
+#[derive(Debug)]
+pub(crate) struct AsyncCursor<T> {
+    inner: T,
+    base: Option<u64>,
+    offset: u64,
+    seek: SeekState,
+    staging: Staging,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SeekState {
+    Idle,
+    FindingBase(SeekFrom),
+    /// Seeking the underlying stream, whose encoded data starts at the given position.
+    Seeking(u64),
+    Restoring,
+}
+
+impl<T> AsyncCursor<T> {
+    #[inline]
+    pub(crate) const fn new(inner: T) -> Self {
+        Self {
+            inner,
+            base: None,
+            offset: 0,
+            seek: SeekState::Idle,
+            staging: Staging::new(BUFFER_LEN),
+        }
+    }
+
+    #[cfg(feature = "redshirt2")]
+    #[inline]
+    pub(crate) const fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    #[inline]
+    pub(crate) fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: RawRead> AsyncCursor<T> {
+    #[inline]
+    pub(crate) fn poll_read_encoded(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let len = ready!(self.inner.poll_read(cx, buf))?;
+        self.offset = add_len(self.offset, len)?;
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<T: RawRead> RawRead for AsyncCursor<T> {
+    #[inline]
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let len = ready!(self.poll_read_encoded(cx, buf))?;
+        xor_bytes(&mut buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<T: RawWrite> AsyncCursor<T> {
+    #[inline]
+    /// Encodes and writes up to one chunk of `buf`, passing the encoded bytes accepted by the
+    /// underlying writer to `accepted`.
+    pub(crate) fn poll_write_with<F: FnOnce(&[u8])>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        accepted: F,
+    ) -> Poll<io::Result<usize>> {
+        let used = self.staging.encode(buf);
+        let len = ready!(self.inner.poll_write(cx, &used))?;
+        accepted(&used[..len]);
+        self.offset = add_len(self.offset, len)?;
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<T: RawWrite> RawWrite for AsyncCursor<T> {
+    #[inline]
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.poll_write_with(cx, buf, |_| ())
+    }
+
+    #[inline]
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.inner.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.inner.poll_close(cx)
+    }
+}
+
+impl<T: RawSeek> AsyncCursor<T> {
+    #[cfg(feature = "futures-io")]
+    #[inline]
+    /// Seeks in a single step, as `futures-io` expects, starting a new seek only if none is
+    /// already in progress.
+    pub(crate) fn poll_seek(
+        &mut self,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        if self.seek == SeekState::Idle {
+            self.start_seek(pos)?;
+        }
+        self.poll_complete(cx)
+    }
+}
+
+impl<T: RawSeek> RawSeek for AsyncCursor<T> {
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.seek == SeekState::Idle {
+            self.inner.poll_ready(cx)
+        } else {
+            self.poll_complete(cx).map_ok(|_| ())
+        }
+    }
+
+    #[inline]
+    fn start_seek(&mut self, pos: SeekFrom) -> io::Result<()> {
+        if self.seek != SeekState::Idle {
+            return Err(io::Error::other("other seek operation is pending"));
+        }
+        if let Some(base) = self.base {
+            let target = translate_seek(base, self.offset, pos)?;
+            self.inner.start_seek(target)?;
+            self.seek = SeekState::Seeking(base);
+        } else {
+            self.inner.start_seek(SeekFrom::Current(0))?;
+            self.seek = SeekState::FindingBase(pos);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn poll_complete(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        loop {
+            let res = match self.seek {
+                SeekState::Idle => {
+                    ready!(self.inner.poll_ready(cx))?;
+                    return Poll::Ready(Ok(self.offset));
+                }
+                SeekState::FindingBase(pos) => ready!(self.inner.poll_complete(cx)).and_then(|v| {
+                    let base = base_of(v, self.offset)?;
+                    self.base = Some(base);
+                    let target = translate_seek(base, self.offset, pos)?;
+                    self.inner.start_seek(target)?;
+                    self.seek = SeekState::Seeking(base);
+                    Ok(None)
+                }),
+                SeekState::Seeking(base) => ready!(self.inner.poll_complete(cx)).and_then(|v| {
+                    if let Some(offset) = v.checked_sub(base) {
+                        self.offset = offset;
+                        self.seek = SeekState::Idle;
+                        Ok(Some(offset))
+                    } else {
+                        let restore = base.checked_add(self.offset).ok_or_else(overflow_error)?;
+                        self.inner.start_seek(SeekFrom::Start(restore))?;
+                        self.seek = SeekState::Restoring;
+                        Ok(None)
+                    }
+                }),
+                SeekState::Restoring => {
+                    ready!(self.inner.poll_complete(cx)).and_then(|_| Err(overflow_error()))
+                }
+            };
+            match res {
+                Ok(Some(offset)) => return Poll::Ready(Ok(offset)),
+                Ok(None) => (),
+                Err(e) => {
+                    self.seek = SeekState::Idle;
+                    return Poll::Ready(Err(e));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,273 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! A runtime-agnostic view of asynchronous I/O.
+//!
+//! Tokio and `futures-io` define incompatible `AsyncRead`, `AsyncWrite` and `AsyncSeek` traits.
+//! The encoding and verification logic is written once against the `Raw*` traits below, and each
+//! runtime gets a thin adapter type implementing them.
+
+use std::{
+    future,
+    io::{self, SeekFrom},
+    task::{Context, Poll},
+};
+
+/// A source of bytes that can be polled for reading.
+pub(crate) trait RawRead {
+    /// Attempts to read into `buf`, returning the number of bytes read.
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>>;
+}
+
+/// A sink for bytes that can be polled for writing.
+pub(crate) trait RawWrite {
+    /// Attempts to write from `buf`, returning the number of bytes written.
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>>;
+
+    /// Attempts to flush any buffered data.
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    /// Attempts to flush any buffered data and shut down the sink.
+    fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+}
+
+/// A stream that supports seeking, using Tokio's two-phase model.
+pub(crate) trait RawSeek {
+    /// Waits for any pending operation to complete, so that a new seek can be started.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    /// Begins seeking to `pos`.
+    fn start_seek(&mut self, pos: SeekFrom) -> io::Result<()>;
+
+    /// Waits for a seek started by `start_seek` to complete, returning the new position.
+    fn poll_complete(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u64>>;
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) use self::tokio::{poll_read_buf, TokioIo};
+
+#[cfg(feature = "futures-io")]
+pub(crate) use self::futures::FuturesIo;
+
+#[cfg(feature = "tokio")]
+mod tokio {
+    use super::{RawRead, RawSeek, RawWrite};
+    use crate::cursor::check_len;
+    use std::{
+        io::{self, SeekFrom},
+        pin::Pin,
+        task::{ready, Context, Poll},
+    };
+    use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+    #[derive(Debug)]
+    /// Adapts a Tokio I/O object to the `Raw*` traits.
+    pub(crate) struct TokioIo<T>(T);
+
+    impl<T> TokioIo<T> {
+        #[inline]
+        pub(crate) const fn new(inner: T) -> Self {
+            Self(inner)
+        }
+
+        #[inline]
+        pub(crate) fn into_inner(self) -> T {
+            self.0
+        }
+    }
+
+    #[inline]
+    /// Reads from `src` into the unfilled part of a Tokio `ReadBuf`.
+    pub(crate) fn poll_read_buf<T: RawRead>(
+        src: &mut T,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let len = ready!(src.poll_read(cx, buf.initialize_unfilled()))?;
+        buf.advance(len);
+        Poll::Ready(Ok(()))
+    }
+
+    impl<T: AsyncRead + Unpin> RawRead for TokioIo<T> {
+        #[inline]
+        fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            let mut buf = ReadBuf::new(buf);
+            ready!(Pin::new(&mut self.0).poll_read(cx, &mut buf))?;
+            Poll::Ready(Ok(buf.filled().len()))
+        }
+    }
+
+    impl<T: AsyncWrite + Unpin> RawWrite for TokioIo<T> {
+        #[inline]
+        fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.0)
+                .poll_write(cx, buf)
+                .map(|res| res.and_then(|len| check_len(len, buf.len())))
+        }
+
+        #[inline]
+        fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_flush(cx)
+        }
+
+        #[inline]
+        fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_shutdown(cx)
+        }
+    }
+
+    impl<T: AsyncSeek + Unpin> RawSeek for TokioIo<T> {
+        #[inline]
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            // Some types, such as `tokio::fs::File`, reject `start_seek` while a write is still in
+            // flight. Tokio's own `seek` future calls `poll_complete` first for this reason.
+            Pin::new(&mut self.0).poll_complete(cx).map_ok(|_| ())
+        }
+
+        #[inline]
+        fn start_seek(&mut self, pos: SeekFrom) -> io::Result<()> {
+            Pin::new(&mut self.0).start_seek(pos)
+        }
+
+        #[inline]
+        fn poll_complete(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+            Pin::new(&mut self.0).poll_complete(cx)
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+mod futures {
+    use super::{RawRead, RawSeek, RawWrite};
+    use crate::cursor::check_len;
+    use futures_io::{AsyncRead, AsyncSeek, AsyncWrite};
+    use std::{
+        io::{self, SeekFrom},
+        pin::Pin,
+        task::{ready, Context, Poll},
+    };
+
+    #[derive(Debug)]
+    /// Adapts a `futures-io` I/O object to the `Raw*` traits.
+    pub(crate) struct FuturesIo<T> {
+        inner: T,
+        seek: Option<SeekFrom>,
+    }
+
+    impl<T> FuturesIo<T> {
+        #[inline]
+        pub(crate) const fn new(inner: T) -> Self {
+            Self { inner, seek: None }
+        }
+
+        #[inline]
+        pub(crate) fn into_inner(self) -> T {
+            self.inner
+        }
+    }
+
+    impl<T: AsyncRead + Unpin> RawRead for FuturesIo<T> {
+        #[inline]
+        fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner)
+                .poll_read(cx, buf)
+                .map(|res| res.and_then(|len| check_len(len, buf.len())))
+        }
+    }
+
+    impl<T: AsyncWrite + Unpin> RawWrite for FuturesIo<T> {
+        #[inline]
+        fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner)
+                .poll_write(cx, buf)
+                .map(|res| res.and_then(|len| check_len(len, buf.len())))
+        }
+
+        #[inline]
+        fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        #[inline]
+        fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_close(cx)
+        }
+    }
+
+    impl<T: AsyncSeek + Unpin> RawSeek for FuturesIo<T> {
+        #[inline]
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        #[inline]
+        fn start_seek(&mut self, pos: SeekFrom) -> io::Result<()> {
+            self.seek = Some(pos);
+            Ok(())
+        }
+
+        #[inline]
+        fn poll_complete(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+            // `futures-io` seeks in one step, so replay the position given to `start_seek` until
+            // the seek completes.
+            let pos = self.seek.unwrap_or(SeekFrom::Current(0));
+            let res = ready!(Pin::new(&mut self.inner).poll_seek(cx, pos));
+            self.seek = None;
+            Poll::Ready(res)
+        }
+    }
+}
+
+#[inline]
+/// Reads exactly enough bytes to fill `buf`.
+pub(crate) async fn read_exact<T: RawRead>(src: &mut T, mut buf: &mut [u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match read(src, buf).await {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(len) => buf = &mut buf[len..],
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+/// Reads some bytes into `buf`, returning the number of bytes read.
+pub(crate) async fn read<T: RawRead>(src: &mut T, buf: &mut [u8]) -> io::Result<usize> {
+    future::poll_fn(|cx| src.poll_read(cx, buf)).await
+}
+
+#[inline]
+/// Writes the entirety of `buf`.
+pub(crate) async fn write_all<T: RawWrite>(dst: &mut T, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match future::poll_fn(|cx| dst.poll_write(cx, buf)).await {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(len) => buf = &buf[len..],
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "redshirt2")]
+#[inline]
+/// Flushes any buffered data.
+pub(crate) async fn flush<T: RawWrite>(dst: &mut T) -> io::Result<()> {
+    future::poll_fn(|cx| dst.poll_flush(cx)).await
+}
+
+#[cfg(feature = "redshirt2")]
+#[inline]
+/// Seeks to `pos`, returning the new position.
+pub(crate) async fn seek<T: RawSeek>(stream: &mut T, pos: SeekFrom) -> io::Result<u64> {
+    future::poll_fn(|cx| stream.poll_ready(cx)).await?;
+    stream.start_seek(pos)?;
+    future::poll_fn(|cx| stream.poll_complete(cx)).await
+}
@@ -0,0 +1,714 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Processing whole directory trees of Redshirt files.
+//!
+//! `decode_dir`, `encode_dir` and `verify_dir` walk a directory tree in a deterministic order,
+//! handling each regular file independently. Rather than stopping at the first error, they return
+//! a `BatchReport` recording what happened to each file. `decode_dir` and `encode_dir` mirror the
+//! structure of the input tree in the output tree.
+//!
+//! When decoding or verifying, files that do not begin with a Redshirt 1 or Redshirt 2 marker are
+//! skipped, unless `BatchOptions::strict` or `BatchOptions::copy_through` is enabled. Symbolic
+//! links are not followed.
+//!
+//! If `BatchOptions::game` is set, the report notes each decoded or verified save file whose
+//! encoding scheme differs from the one that game is expected to use.
+
+use crate::{
+    advice,
+    cancel::{self, CancelToken},
+    digest::Digest,
+    error::Error,
+    game::{Game, UnexpectedVersion},
+    v1, v2,
+    version::Version,
+};
+use std::{
+    fmt::{self, Debug, Formatter},
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// A predicate deciding which files are processed.
+type Filter = Arc<dyn Fn(&Path) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+/// Options which can be used to configure how a directory tree is processed.
+pub struct BatchOptions {
+    strict: bool,
+    fail_fast: bool,
+    copy_through: bool,
+    overwrite: Overwrite,
+    filter: Option<Filter>,
+    game: Option<Game>,
+    cancel: Option<CancelToken>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// What to do when an output file already exists.
+pub enum Overwrite {
+    /// Replace the existing file.
+    Always,
+    /// Keep the existing file, and report the input file as skipped.
+    Never,
+    /// Keep the existing file, and report the input file as failed.
+    Fail,
+}
+
+#[derive(Debug, Default)]
+/// The outcome of processing a directory tree.
+pub struct BatchReport {
+    files: Vec<FileReport>,
+}
+
+#[derive(Debug)]
+/// The outcome of processing a single file.
+pub struct FileReport {
+    path: PathBuf,
+    outcome: Outcome,
+    unexpected: Option<UnexpectedVersion>,
+}
+
+#[derive(Debug)]
+/// What happened to a single file.
+pub enum Outcome {
+    /// The file was decoded and written to the output tree.
+    Decoded(Version),
+    /// The file was encoded and written to the output tree.
+    Encoded(Version),
+    /// The file does not begin with a Redshirt marker, and was copied to the output tree as-is.
+    Copied,
+    /// The file was verified.
+    Verified {
+        /// The encoding scheme of the file.
+        version: Version,
+        /// The SHA-1 hash of the encoded data, for Redshirt 2 files.
+        digest: Option<Digest>,
+    },
+    /// The file does not begin with a Redshirt marker, or its output already exists and
+    /// `Overwrite::Never` is in effect, and was skipped.
+    Skipped,
+    /// Processing the file failed.
+    Failed(Error),
+}
+
+impl BatchOptions {
+    #[inline]
+    #[must_use]
+    /// Creates a new set of options with the default configuration.
+    ///
+    /// By default, every file is processed regardless of earlier failures, files without a
+    /// Redshirt marker are skipped, and existing output files are overwritten.
+    pub const fn new() -> Self {
+        Self {
+            strict: false,
+            fail_fast: false,
+            copy_through: false,
+            overwrite: Overwrite::Always,
+            filter: None,
+            game: None,
+            cancel: None,
+        }
+    }
+
+    #[inline]
+    /// Sets whether files without a Redshirt marker are reported as failures with
+    /// `Error::BadHeader`, instead of being skipped.
+    pub fn strict(&mut self, strict: bool) -> &mut Self {
+        self.strict = strict;
+        self
+    }
+
+    #[inline]
+    /// Sets whether processing stops after the first failure.
+    pub fn fail_fast(&mut self, fail_fast: bool) -> &mut Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    #[inline]
+    /// Sets whether `decode_dir` copies files without a Redshirt marker to the output tree
+    /// unchanged, instead of skipping them.
+    ///
+    /// This has no effect if `strict` is enabled.
+    pub fn copy_through(&mut self, copy_through: bool) -> &mut Self {
+        self.copy_through = copy_through;
+        self
+    }
+
+    #[inline]
+    /// Sets what happens when an output file already exists.
+    pub fn overwrite(&mut self, overwrite: Overwrite) -> &mut Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    #[inline]
+    /// Sets a predicate deciding which files are processed.
+    ///
+    /// The predicate receives the path of each regular file relative to the root of the tree.
+    /// Files for which it returns `false` are ignored, and do not appear in the `BatchReport`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::batch::BatchOptions;
+    ///
+    /// let mut options = BatchOptions::new();
+    /// let _ = options.filter(|path| path.extension().is_some_and(|ext| ext == "usr"));
+    /// ```
+    pub fn filter<F>(&mut self, filter: F) -> &mut Self
+    where
+        F: Fn(&Path) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    #[inline]
+    /// Sets the game whose files are being processed.
+    ///
+    /// Decoded or verified files that `Game::check` reports as using an unexpected encoding
+    /// scheme are noted in `FileReport::unexpected_version`. They are still processed as usual.
+    pub fn game(&mut self, game: Game) -> &mut Self {
+        self.game = Some(game);
+        self
+    }
+
+    #[inline]
+    /// Sets a token that stops processing once it is cancelled.
+    ///
+    /// The token is checked before each file is read, and by `verify_dir` before each chunk of up
+    /// to 16 KiB of a Redshirt 2 file is hashed. Once it is cancelled, the operation returns
+    /// `Error::Cancelled`, and the outcomes of the files processed so far are discarded; their
+    /// output files are left in place.
+    pub fn cancel_token(&mut self, token: CancelToken) -> &mut Self {
+        self.cancel = Some(token);
+        self
+    }
+}
+
+impl Debug for BatchOptions {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchOptions")
+            .field("strict", &self.strict)
+            .field("fail_fast", &self.fail_fast)
+            .field("copy_through", &self.copy_through)
+            .field("overwrite", &self.overwrite)
+            .field("filter", &self.filter.as_ref().map(|_| ".."))
+            .field("game", &self.game)
+            .field("cancel", &self.cancel)
+            .finish()
+    }
+}
+
+impl Default for BatchOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatchReport {
+    #[inline]
+    #[must_use]
+    /// Returns the outcome for each file, in the order in which the files were processed.
+    pub fn files(&self) -> &[FileReport] {
+        &self.files
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the number of files that were processed successfully.
+    pub fn ok(&self) -> usize {
+        self.count(|outcome| {
+            matches!(
+                outcome,
+                Outcome::Decoded(_)
+                    | Outcome::Encoded(_)
+                    | Outcome::Copied
+                    | Outcome::Verified { .. }
+            )
+        })
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the number of files that could not be processed.
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Failed(_)))
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the number of files that were skipped.
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Skipped))
+    }
+
+    #[inline]
+    fn count<F: Fn(&Outcome) -> bool>(&self, f: F) -> usize {
+        self.files.iter().filter(|file| f(&file.outcome)).count()
+    }
+}
+
+impl FileReport {
+    #[inline]
+    #[must_use]
+    /// Returns the path of the file, relative to the root of the tree.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns what happened to the file.
+    pub fn outcome(&self) -> &Outcome {
+        &self.outcome
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns how the file's encoding scheme differs from the one expected by
+    /// `BatchOptions::game`, if it does.
+    pub fn unexpected_version(&self) -> Option<UnexpectedVersion> {
+        self.unexpected
+    }
+}
+
+#[inline]
+/// Decodes every Redshirt file in the tree rooted at `src`, writing the decoded data to the same
+/// relative path under `dst`.
+///
+/// Directories under `dst` are created as needed. Existing files are handled according to
+/// `BatchOptions::overwrite`.
+///
+/// # Errors
+///
+/// Returns an `Err` if `src` cannot be read, or `Error::Cancelled` if the token set by
+/// `BatchOptions::cancel_token` is cancelled. Errors affecting individual files or subdirectories
+/// are recorded in the returned `BatchReport` instead.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::batch::{self, BatchOptions};
+///
+/// let report = batch::decode_dir("users".as_ref(), "decoded".as_ref(), &BatchOptions::new())
+///     .unwrap();
+/// println!("{} decoded, {} failed", report.ok(), report.failed());
+/// ```
+pub fn decode_dir(src: &Path, dst: &Path, options: &BatchOptions) -> Result<BatchReport, Error> {
+    walk(src, options, |path, encoded| {
+        let (decoded, outcome) = match Version::detect(encoded) {
+            Some(Version::V1) => (v1::decode(encoded)?, Outcome::Decoded(Version::V1)),
+            Some(Version::V2) => (v2::decode(encoded)?, Outcome::Decoded(Version::V2)),
+            None if options.copy_through && !options.strict => (encoded.to_vec(), Outcome::Copied),
+            None => return Ok(None),
+        };
+        write_output(&dst.join(path), &decoded, options.overwrite)
+            .map(|written| Some(if written { outcome } else { Outcome::Skipped }))
+    })
+}
+
+#[inline]
+/// Encodes every file in the tree rooted at `src` using the given encoding scheme, writing the
+/// encoded data to the same relative path under `dst`.
+///
+/// Every file is encoded, including files that are already Redshirt-encoded; use
+/// `BatchOptions::filter` to exclude them. Directories under `dst` are created as needed.
+/// Existing files are handled according to `BatchOptions::overwrite`.
+///
+/// # Errors
+///
+/// Returns an `Err` if `src` cannot be read, or `Error::Cancelled` if the token set by
+/// `BatchOptions::cancel_token` is cancelled. Errors affecting individual files or subdirectories
+/// are recorded in the returned `BatchReport` instead.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::{batch::{self, BatchOptions, Overwrite}, Version};
+///
+/// let mut options = BatchOptions::new();
+/// let _ = options.overwrite(Overwrite::Never);
+/// let report = batch::encode_dir("src".as_ref(), "users".as_ref(), Version::V2, &options)
+///     .unwrap();
+/// println!("{} encoded, {} failed", report.ok(), report.failed());
+/// ```
+pub fn encode_dir(
+    src: &Path,
+    dst: &Path,
+    version: Version,
+    options: &BatchOptions,
+) -> Result<BatchReport, Error> {
+    walk(src, options, |path, data| {
+        let encoded = match version {
+            Version::V1 => v1::encode(data),
+            Version::V2 => v2::encode(data),
+        };
+        write_output(&dst.join(path), &encoded, options.overwrite).map(|written| {
+            Some(if written {
+                Outcome::Encoded(version)
+            } else {
+                Outcome::Skipped
+            })
+        })
+    })
+}
+
+#[inline]
+/// Verifies every Redshirt file in the tree rooted at `root`.
+///
+/// Redshirt 1 files have no hash, so only their marker is checked.
+///
+/// # Errors
+///
+/// Returns an `Err` if `root` cannot be read, or `Error::Cancelled` if the token set by
+/// `BatchOptions::cancel_token` is cancelled. Errors affecting individual files or subdirectories
+/// are recorded in the returned `BatchReport` instead.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::batch::{self, BatchOptions};
+///
+/// let report = batch::verify_dir("users".as_ref(), &BatchOptions::new()).unwrap();
+/// assert_eq!(report.failed(), 0);
+/// ```
+pub fn verify_dir(root: &Path, options: &BatchOptions) -> Result<BatchReport, Error> {
+    walk(root, options, |_, encoded| {
+        Ok(match Version::detect(encoded) {
+            Some(Version::V1) => Some(Outcome::Verified {
+                version: Version::V1,
+                digest: None,
+            }),
+            Some(Version::V2) => Some(Outcome::Verified {
+                version: Version::V2,
+                digest: Some(
+                    v2::verify_with(encoded, options.cancel.as_ref()).map_err(truncated_header)?,
+                ),
+            }),
+            None => None,
+        })
+    })
+}
+
+/// Calls `f` on the contents of every regular file under `root`, recording the outcome.
+///
+/// `f` receives the path of the file relative to `root`, and returns `Ok(None)` if the file
+/// should be skipped. Files rejected by `BatchOptions::filter` are not passed to `f`.
+fn walk<F>(root: &Path, options: &BatchOptions, mut f: F) -> Result<BatchReport, Error>
+where
+    F: FnMut(&Path, &[u8]) -> Result<Option<Outcome>, Error>,
+{
+    let mut report = BatchReport::default();
+    for file in Files::new(root).map_err(Error::Io)? {
+        cancel::check(options.cancel.as_ref())?;
+        let (path, outcome) = match file {
+            Ok(path) if options.filter.as_ref().is_some_and(|filter| !filter(&path)) => continue,
+            Ok(path) => {
+                let outcome = match advice::read(&root.join(&path)) {
+                    Ok(encoded) => match f(&path, &encoded) {
+                        Err(Error::Cancelled) => return Err(Error::Cancelled),
+                        Ok(Some(outcome)) => outcome,
+                        Ok(None) if options.strict => Outcome::Failed(Error::BadHeader),
+                        Ok(None) => Outcome::Skipped,
+                        Err(e) => Outcome::Failed(e),
+                    },
+                    Err(e) => Outcome::Failed(Error::Io(e)),
+                };
+                (path, outcome)
+            }
+            Err((path, e)) => (path, Outcome::Failed(Error::Io(e))),
+        };
+        let failed = matches!(outcome, Outcome::Failed(_));
+        let unexpected = match (options.game, &outcome) {
+            (Some(game), Outcome::Decoded(version) | Outcome::Verified { version, .. }) => {
+                game.check(&path, *version)
+            }
+            _ => None,
+        };
+        report.files.push(FileReport {
+            path,
+            outcome,
+            unexpected,
+        });
+        if failed && options.fail_fast {
+            break;
+        }
+    }
+    Ok(report)
+}
+
+#[derive(Debug)]
+/// An iterator over the regular files in a directory tree, depth-first and in sorted order.
+///
+/// Each item is the path of a file relative to the root, or the relative path of an entry that
+/// could not be read along with the error. Symbolic links are not followed.
+pub(crate) struct Files {
+    root: PathBuf,
+    pending: Vec<Vec<PathBuf>>,
+}
+
+impl Files {
+    pub(crate) fn new(root: &Path) -> io::Result<Self> {
+        Ok(Self {
+            root: root.to_owned(),
+            pending: vec![read_dir(root)?],
+        })
+    }
+}
+
+impl Iterator for Files {
+    type Item = Result<PathBuf, (PathBuf, io::Error)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entries = self.pending.last_mut()?;
+            let Some(path) = entries.pop() else {
+                let _ = self.pending.pop();
+                continue;
+            };
+            let full = self.root.join(&path);
+            match fs::symlink_metadata(&full) {
+                Ok(metadata) if metadata.is_dir() => match read_dir(&full) {
+                    Ok(children) => self
+                        .pending
+                        .push(children.into_iter().map(|c| path.join(c)).collect()),
+                    Err(e) => return Some(Err((path, e))),
+                },
+                Ok(metadata) if metadata.is_file() => return Some(Ok(path)),
+                Ok(_) => (),
+                Err(e) => return Some(Err((path, e))),
+            }
+        }
+    }
+}
+
+/// Returns the names of the entries in `dir`, in reverse order so that they can be popped off
+/// the end in sorted order.
+fn read_dir(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut names = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| PathBuf::from(entry.file_name())))
+        .collect::<Result<Vec<_>, _>>()?;
+    names.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(names)
+}
+
+/// Writes `data` to `path`, creating its parent directories, and returns whether it was written.
+fn write_output(path: &Path, data: &[u8], overwrite: Overwrite) -> Result<bool, Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(Error::Io)?;
+    }
+    let mut open_options = OpenOptions::new();
+    let _ = match overwrite {
+        Overwrite::Always => open_options.write(true).create(true).truncate(true),
+        Overwrite::Never | Overwrite::Fail => open_options.write(true).create_new(true),
+    };
+    match open_options.open(path) {
+        Ok(mut file) => file.write_all(data).map(|()| true).map_err(Error::Io),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists && overwrite == Overwrite::Never => {
+            Ok(false)
+        }
+        Err(e) => Err(Error::Io(e)),
+    }
+}
+
+/// Reports a Redshirt 2 file that is too short to hold a header as a bad header.
+fn truncated_header(e: Error) -> Error {
+    match e {
+        Error::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof => Error::BadHeader,
+        e => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_dir, encode_dir, verify_dir, BatchOptions, Outcome, Overwrite};
+    use crate::{
+        cancel::CancelToken, error::Error, game::Game, testdata, v1, v2, version::Version,
+    };
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+        process,
+    };
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("redshirt-batch-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::write(dir.join("a/one.dat"), v1::encode(MSG_DEC)).unwrap();
+        fs::write(dir.join("a/b/two.usr"), v2::encode(MSG_DEC)).unwrap();
+        fs::write(dir.join("plain.txt"), MSG_DEC).unwrap();
+        dir
+    }
+
+    fn paths(report: &super::BatchReport) -> Vec<&Path> {
+        report.files().iter().map(super::FileReport::path).collect()
+    }
+
+    #[test]
+    fn decode_tree() {
+        let src = temp_dir("decode-src");
+        let dst = src.with_file_name(format!("redshirt-batch-{}-decode-dst", process::id()));
+        let report = decode_dir(&src, &dst, &BatchOptions::new()).unwrap();
+        assert_eq!(
+            paths(&report),
+            [
+                Path::new("a/b/two.usr"),
+                Path::new("a/one.dat"),
+                Path::new("plain.txt")
+            ]
+        );
+        assert!(matches!(
+            report.files()[0].outcome(),
+            Outcome::Decoded(Version::V2)
+        ));
+        assert_eq!((report.ok(), report.failed(), report.skipped()), (2, 0, 1));
+        assert_eq!(fs::read(dst.join("a/one.dat")).unwrap(), MSG_DEC);
+        assert_eq!(fs::read(dst.join("a/b/two.usr")).unwrap(), MSG_DEC);
+        assert!(!dst.join("plain.txt").exists());
+        fs::remove_dir_all(src).unwrap();
+        fs::remove_dir_all(dst).unwrap();
+    }
+
+    #[test]
+    fn encode_tree() {
+        let src = temp_dir("encode-src");
+        let dst = src.with_file_name(format!("redshirt-batch-{}-encode-dst", process::id()));
+        let _ = fs::remove_dir_all(&dst);
+        let mut options = BatchOptions::new();
+        let _ = options.filter(|path| path.extension().is_some_and(|ext| ext == "txt"));
+        let report = encode_dir(&src, &dst, Version::V2, &options).unwrap();
+        assert_eq!(paths(&report), [Path::new("plain.txt")]);
+        assert!(matches!(
+            report.files()[0].outcome(),
+            Outcome::Encoded(Version::V2)
+        ));
+        assert_eq!(
+            fs::read(dst.join("plain.txt")).unwrap(),
+            v2::encode(MSG_DEC)
+        );
+        fs::remove_dir_all(src).unwrap();
+        fs::remove_dir_all(dst).unwrap();
+    }
+
+    #[test]
+    fn decode_tree_copy_through() {
+        let src = temp_dir("copy-src");
+        let dst = src.with_file_name(format!("redshirt-batch-{}-copy-dst", process::id()));
+        let _ = fs::remove_dir_all(&dst);
+        let report = decode_dir(&src, &dst, BatchOptions::new().copy_through(true)).unwrap();
+        assert_eq!((report.ok(), report.failed(), report.skipped()), (3, 0, 0));
+        assert!(matches!(report.files()[2].outcome(), Outcome::Copied));
+        assert_eq!(fs::read(dst.join("plain.txt")).unwrap(), MSG_DEC);
+        fs::remove_dir_all(src).unwrap();
+        fs::remove_dir_all(dst).unwrap();
+    }
+
+    #[test]
+    fn decode_tree_overwrite() {
+        let src = temp_dir("overwrite-src");
+        let dst = src.with_file_name(format!("redshirt-batch-{}-overwrite-dst", process::id()));
+        let _ = fs::remove_dir_all(&dst);
+        fs::create_dir_all(dst.join("a")).unwrap();
+        fs::write(dst.join("a/one.dat"), b"existing").unwrap();
+        let report =
+            decode_dir(&src, &dst, BatchOptions::new().overwrite(Overwrite::Never)).unwrap();
+        assert_eq!((report.ok(), report.failed(), report.skipped()), (1, 0, 2));
+        assert_eq!(fs::read(dst.join("a/one.dat")).unwrap(), b"existing");
+        let report =
+            decode_dir(&src, &dst, BatchOptions::new().overwrite(Overwrite::Fail)).unwrap();
+        assert!(matches!(
+            report.files()[1].outcome(),
+            Outcome::Failed(Error::Io(e)) if e.kind() == std::io::ErrorKind::AlreadyExists
+        ));
+        assert_eq!((report.ok(), report.failed(), report.skipped()), (0, 2, 1));
+        let report = decode_dir(&src, &dst, &BatchOptions::new()).unwrap();
+        assert_eq!((report.ok(), report.failed(), report.skipped()), (2, 0, 1));
+        assert_eq!(fs::read(dst.join("a/one.dat")).unwrap(), MSG_DEC);
+        fs::remove_dir_all(src).unwrap();
+        fs::remove_dir_all(dst).unwrap();
+    }
+
+    #[test]
+    fn verify_tree_strict() {
+        let root = temp_dir("verify-strict");
+        let report = verify_dir(&root, BatchOptions::new().strict(true)).unwrap();
+        assert_eq!((report.ok(), report.failed(), report.skipped()), (2, 1, 0));
+        assert!(matches!(
+            report.files()[2].outcome(),
+            Outcome::Failed(Error::BadHeader)
+        ));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn verify_tree_continues_after_failure() {
+        let root = temp_dir("verify-continue");
+        let corrupt = testdata::flip_digest_bit(MSG_DEC, 0).data;
+        fs::write(root.join("a/b/two.usr"), corrupt).unwrap();
+        let report = verify_dir(&root, &BatchOptions::new()).unwrap();
+        assert!(matches!(
+            report.files()[0].outcome(),
+            Outcome::Failed(Error::BadChecksum { .. })
+        ));
+        assert_eq!((report.ok(), report.failed(), report.skipped()), (1, 1, 1));
+        let report = verify_dir(&root, BatchOptions::new().fail_fast(true)).unwrap();
+        assert_eq!(report.files().len(), 1);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn verify_tree_cancelled() {
+        let root = temp_dir("verify-cancelled");
+        let token = CancelToken::new();
+        token.cancel();
+        let report = verify_dir(&root, BatchOptions::new().cancel_token(token));
+        assert!(matches!(report, Err(Error::Cancelled)));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn verify_tree_game() {
+        let root = temp_dir("verify-game");
+        fs::create_dir_all(root.join("users")).unwrap();
+        fs::write(root.join("users/old.usr"), v1::encode(MSG_DEC)).unwrap();
+        let report = verify_dir(&root, BatchOptions::new().game(Game::Uplink)).unwrap();
+        let unexpected = report
+            .files()
+            .iter()
+            .filter_map(|file| Some((file.path(), file.unexpected_version()?)))
+            .collect::<Vec<_>>();
+        assert_eq!(unexpected.len(), 1);
+        assert_eq!(unexpected[0].0, Path::new("users/old.usr"));
+        assert_eq!(unexpected[0].1.found, Version::V1);
+        let report = verify_dir(&root, &BatchOptions::new()).unwrap();
+        assert!(report
+            .files()
+            .iter()
+            .all(|file| file.unexpected_version().is_none()));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn verify_missing_root() {
+        let root = env::temp_dir().join(format!("redshirt-batch-{}-missing", process::id()));
+        assert!(matches!(
+            verify_dir(&root, &BatchOptions::new()),
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,801 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! A command-line tool for encoding, decoding and verifying Redshirt files.
+
+#![deny(
+    warnings,
+    future_incompatible,
+    rust_2018_idioms,
+    rustdoc::all,
+    unused,
+    missing_copy_implementations,
+    missing_debug_implementations,
+    missing_docs,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unused_results,
+    clippy::all,
+    clippy::pedantic
+)]
+
+use redshirt::{
+    batch::{self, BatchOptions, BatchReport, Outcome},
+    manifest::{Discrepancy, Manifest, ManifestOptions},
+    text, v1, v2, Comparison, Error, Info, ScanOptions, Version,
+};
+use std::{
+    env,
+    ffi::OsString,
+    fmt::Write as _,
+    fs,
+    fs::File,
+    io::{self, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+const USAGE: &str = "\
+Usage:
+    redshirt encode <in> <out> (--v1 | --v2)
+    redshirt encode --recursive [--fail-fast] (--v1 | --v2) <in-dir> <out-dir>
+    redshirt decode <in> <out>
+    redshirt decode --recursive [--strict] [--fail-fast] <in-dir> <out-dir>
+    redshirt verify <in>
+    redshirt verify --recursive [--strict] [--fail-fast] <in-dir>
+    redshirt salvage <in> <out>
+    redshirt info [--json] [--no-verify] <in>
+    redshirt strings [--min-len <n>] <in>
+    redshirt scan [--no-verify] <in>
+    redshirt diff <a> <b>
+    redshirt manifest create [--rehash] <dir> <manifest>
+    redshirt manifest check [--rehash] <dir> <manifest>
+
+A path of `-` reads from standard input or writes to standard output.
+When both paths given to `encode` or `decode` are `-`, data is streamed, and encoded data is
+never read from or written to a terminal.
+
+With --recursive, every file under the input directory is processed, and encoded or decoded
+files are written to the same relative path under the output directory. When decoding or
+verifying, files without a Redshirt marker are skipped, or reported as errors with --strict. Processing continues after a failure, unless
+--fail-fast is given.
+
+`salvage` decodes as much of a truncated or corrupt Redshirt 2 file as is present, whatever its
+SHA-1 hash, and reports how much was recovered and whether the hash matches.
+
+`strings` prints each run of at least 4 (or <n>) printable characters in the decoded data of a
+Redshirt file, with its offset in hexadecimal. The SHA-1 hash of a Redshirt 2 file is not
+checked, so corrupt files can be examined.
+
+`scan` lists the offset and version of every Redshirt marker in a file of any kind, such as a
+memory dump, and whether the SHA-1 hash following each Redshirt 2 marker matches the data up to
+the next marker or the end of the file. Data that merely contains a marker is listed too.
+
+`diff` compares the decoded data of two Redshirt files, which may use different encoding schemes,
+and exits with status 1 if they differ.
+
+`manifest create` records the SHA-1 hash of every Redshirt file under a directory, and `manifest
+check` reports files that have since been removed, added or modified. Redshirt 2 hashes are read
+from file headers, unless --rehash is given.
+
+`info` exits with status 3 if the input is not a Redshirt file, and 4 if it is a Redshirt 2 file
+whose SHA-1 hash does not match its data.";
+
+/// The number of header bytes shown when a file has an invalid header.
+const FOUND_LEN: usize = 9;
+
+/// The exit status of `info` for input that is not a Redshirt file.
+const EXIT_NOT_REDSHIRT: u8 = 3;
+
+/// The exit status of `info` for a Redshirt 2 file with a mismatched hash.
+const EXIT_CORRUPT: u8 = 4;
+
+#[derive(Debug)]
+enum Command {
+    Encode {
+        input: PathBuf,
+        output: PathBuf,
+        version: u8,
+    },
+    EncodeDir {
+        input: PathBuf,
+        output: PathBuf,
+        version: Version,
+        options: BatchOptions,
+    },
+    Decode {
+        input: PathBuf,
+        output: PathBuf,
+    },
+    DecodeDir {
+        input: PathBuf,
+        output: PathBuf,
+        options: BatchOptions,
+    },
+    Verify {
+        input: PathBuf,
+    },
+    VerifyDir {
+        input: PathBuf,
+        options: BatchOptions,
+    },
+    Salvage {
+        input: PathBuf,
+        output: PathBuf,
+    },
+    Info {
+        input: PathBuf,
+        json: bool,
+        verify: bool,
+    },
+    Strings {
+        input: PathBuf,
+        min_len: usize,
+    },
+    Scan {
+        input: PathBuf,
+        verify: bool,
+    },
+    Diff {
+        a: PathBuf,
+        b: PathBuf,
+    },
+    ManifestCreate {
+        root: PathBuf,
+        manifest: PathBuf,
+        options: ManifestOptions,
+    },
+    ManifestCheck {
+        root: PathBuf,
+        manifest: PathBuf,
+        options: ManifestOptions,
+    },
+    Help,
+}
+
+fn main() -> ExitCode {
+    let command = match parse_args(env::args_os().skip(1)) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    if let Command::Info {
+        input,
+        json,
+        verify,
+    } = command
+    {
+        return info(&input, json, verify);
+    }
+    match run(command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The options given on the command line, before they are checked against the subcommand.
+// Each option is an independent switch, so there is no state machine to refactor these into.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug)]
+struct Flags {
+    help: bool,
+    version: Option<u8>,
+    recursive: bool,
+    strict: bool,
+    fail_fast: bool,
+    json: bool,
+    verify: bool,
+    rehash: bool,
+    min_len: Option<usize>,
+}
+
+/// Separates the options in `args` from the positional arguments.
+fn parse_flags<I: Iterator<Item = OsString>>(
+    mut args: I,
+) -> Result<(Flags, Vec<OsString>), String> {
+    let mut flags = Flags {
+        help: false,
+        version: None,
+        recursive: false,
+        strict: false,
+        fail_fast: false,
+        json: false,
+        verify: true,
+        rehash: false,
+        min_len: None,
+    };
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-h" | "--help") => flags.help = true,
+            Some("--v1") => flags.version = Some(1),
+            Some("--v2") => flags.version = Some(2),
+            Some("-r" | "--recursive") => flags.recursive = true,
+            Some("--strict") => flags.strict = true,
+            Some("--fail-fast") => flags.fail_fast = true,
+            Some("--json") => flags.json = true,
+            Some("--no-verify") => flags.verify = false,
+            Some("--rehash") => flags.rehash = true,
+            Some("-n" | "--min-len") => {
+                let value = args.next().ok_or("--min-len requires a value")?;
+                flags.min_len = Some(
+                    value
+                        .to_str()
+                        .and_then(|value| value.parse().ok())
+                        .ok_or("--min-len requires a number")?,
+                );
+            }
+            Some(flag) if flag.starts_with("--") => return Err(format!("unknown option `{flag}`")),
+            _ => positional.push(arg),
+        }
+    }
+    Ok((flags, positional))
+}
+
+fn parse_args<I: Iterator<Item = OsString>>(args: I) -> Result<Command, String> {
+    let (flags, positional) = parse_flags(args)?;
+    if flags.help {
+        return Ok(Command::Help);
+    }
+    let mut positional = positional.into_iter();
+    let subcommand = positional.next().ok_or("no subcommand given")?;
+    check_flags(&flags, subcommand.to_str())?;
+    let Flags {
+        version,
+        recursive,
+        strict,
+        fail_fast,
+        json,
+        verify,
+        rehash,
+        min_len,
+        ..
+    } = flags;
+    let mut path = |name: &str| {
+        positional
+            .next()
+            .map(PathBuf::from)
+            .ok_or(format!("missing <{name}> argument"))
+    };
+    let mut manifest_options = ManifestOptions::new();
+    let _ = manifest_options.rehash(rehash);
+    let mut options = BatchOptions::new();
+    let _ = options.strict(strict).fail_fast(fail_fast);
+    let command = match subcommand.to_str() {
+        Some(name @ ("salvage" | "scan" | "diff" | "manifest")) if recursive => {
+            return Err(format!("`{name}` does not support --recursive"))
+        }
+        Some("encode") if strict => return Err("`encode` does not support --strict".to_owned()),
+        Some("encode") if recursive => Command::EncodeDir {
+            input: path("in-dir")?,
+            output: path("out-dir")?,
+            version: match version.ok_or("`encode` requires either --v1 or --v2")? {
+                1 => Version::V1,
+                _ => Version::V2,
+            },
+            options,
+        },
+        Some("encode") => Command::Encode {
+            input: path("in")?,
+            output: path("out")?,
+            version: version.ok_or("`encode` requires either --v1 or --v2")?,
+        },
+        Some("decode") if recursive => Command::DecodeDir {
+            input: path("in-dir")?,
+            output: path("out-dir")?,
+            options,
+        },
+        Some("decode") => Command::Decode {
+            input: path("in")?,
+            output: path("out")?,
+        },
+        Some("verify") if recursive => Command::VerifyDir {
+            input: path("in-dir")?,
+            options,
+        },
+        Some("verify") => Command::Verify { input: path("in")? },
+        Some("salvage") => Command::Salvage {
+            input: path("in")?,
+            output: path("out")?,
+        },
+        Some("info") => Command::Info {
+            input: path("in")?,
+            json,
+            verify,
+        },
+        Some("strings") => Command::Strings {
+            input: path("in")?,
+            min_len: min_len.unwrap_or(4),
+        },
+        Some("scan") => Command::Scan {
+            input: path("in")?,
+            verify,
+        },
+        Some("diff") => match (path("a")?, path("b")?) {
+            (a, b) if a == Path::new("-") && b == Path::new("-") => {
+                return Err("only one input to `diff` can be `-`".to_owned())
+            }
+            (a, b) => Command::Diff { a, b },
+        },
+        Some("manifest") => parse_manifest(&mut positional, manifest_options)?,
+        _ => {
+            return Err(format!(
+                "unknown subcommand `{}`",
+                subcommand.to_string_lossy()
+            ))
+        }
+    };
+    match positional.next() {
+        Some(extra) => Err(format!("unexpected argument `{}`", extra.to_string_lossy())),
+        None => Ok(command),
+    }
+}
+
+/// Checks that each option in `flags` applies to `subcommand`.
+fn check_flags(flags: &Flags, subcommand: Option<&str>) -> Result<(), String> {
+    if (flags.strict || flags.fail_fast) && !flags.recursive {
+        return Err("--strict and --fail-fast require --recursive".to_owned());
+    }
+    if flags.json && subcommand != Some("info") {
+        return Err("--json only applies to `info`".to_owned());
+    }
+    if !flags.verify && !matches!(subcommand, Some("info" | "scan")) {
+        return Err("--no-verify only applies to `info` and `scan`".to_owned());
+    }
+    if flags.rehash && subcommand != Some("manifest") {
+        return Err("--rehash only applies to `manifest`".to_owned());
+    }
+    if flags.min_len.is_some() && subcommand != Some("strings") {
+        return Err("--min-len only applies to `strings`".to_owned());
+    }
+    Ok(())
+}
+
+/// Parses the arguments following `manifest`.
+fn parse_manifest<I: Iterator<Item = OsString>>(
+    args: &mut I,
+    options: ManifestOptions,
+) -> Result<Command, String> {
+    let action = args.next().ok_or("missing `create` or `check` action")?;
+    let mut path = |name: &str| {
+        args.next()
+            .map(PathBuf::from)
+            .ok_or(format!("missing <{name}> argument"))
+    };
+    match action.to_str() {
+        Some("create") => Ok(Command::ManifestCreate {
+            root: path("dir")?,
+            manifest: path("manifest")?,
+            options,
+        }),
+        Some("check") => Ok(Command::ManifestCheck {
+            root: path("dir")?,
+            manifest: path("manifest")?,
+            options,
+        }),
+        _ => Err(format!(
+            "unknown manifest action `{}`",
+            action.to_string_lossy()
+        )),
+    }
+}
+
+fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Encode {
+            input,
+            output,
+            version,
+        } => encode(&input, &output, version),
+        Command::Decode { input, output } => decode(&input, &output),
+        Command::Verify { input } => {
+            let encoded = read_input(&input)?;
+            if v1::decode(&encoded).is_ok() {
+                println!("{}: OK (Redshirt 1, no checksum)", display(&input));
+                return Ok(());
+            }
+            match v2::verify(&encoded[..]) {
+                Ok(digest) => {
+                    println!("{}: OK (Redshirt 2, SHA-1 {digest})", display(&input));
+                    Ok(())
+                }
+                // The whole input is in memory, so running out of data means the header is short.
+                Err(Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    Err(describe(&input, &Error::BadHeader, &encoded))
+                }
+                Err(e) => Err(describe(&input, &e, &encoded)),
+            }
+        }
+        Command::EncodeDir {
+            input,
+            output,
+            version,
+            options,
+        } => batch::encode_dir(&input, &output, version, &options)
+            .map_err(|e| format!("{}: {e}", input.display()))
+            .and_then(|report| summarize(&input, &report)),
+        Command::DecodeDir {
+            input,
+            output,
+            options,
+        } => batch::decode_dir(&input, &output, &options)
+            .map_err(|e| format!("{}: {e}", input.display()))
+            .and_then(|report| summarize(&input, &report)),
+        Command::VerifyDir { input, options } => batch::verify_dir(&input, &options)
+            .map_err(|e| format!("{}: {e}", input.display()))
+            .and_then(|report| summarize(&input, &report)),
+        Command::Salvage { input, output } => salvage(&input, &output),
+        Command::Info { .. } => unreachable!("handled by `info`"),
+        Command::Strings { input, min_len } => {
+            if input == Path::new("-") {
+                print_strings(&input, Cursor::new(read_input(&input)?), min_len)
+            } else {
+                let file = File::open(&input).map_err(|e| format!("{}: {e}", input.display()))?;
+                print_strings(&input, file, min_len)
+            }
+        }
+        Command::Scan { input, verify } => scan(&input, verify),
+        Command::Diff { a, b } => diff(&a, &b),
+        Command::ManifestCreate {
+            root,
+            manifest,
+            options,
+        } => {
+            let scanned =
+                Manifest::scan(&root, &options).map_err(|e| format!("{}: {e}", root.display()))?;
+            let mut text = Vec::new();
+            scanned
+                .write_to(&mut text)
+                .map_err(|e| format!("{}: {e}", display(&manifest)))?;
+            write_output(&manifest, &text)
+        }
+        Command::ManifestCheck {
+            root,
+            manifest,
+            options,
+        } => {
+            let text = read_input(&manifest)?;
+            let expected = Manifest::read_from(&text[..])
+                .map_err(|e| format!("{}: {e}", display(&manifest)))?;
+            expected
+                .verify(&root, &options)
+                .map_err(|e| format!("{}: {e}", root.display()))
+                .and_then(|discrepancies| report_discrepancies(&root, &discrepancies))
+        }
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    }
+}
+
+/// Prints metadata describing `input`, returning the exit status.
+fn info(input: &Path, json: bool, verify: bool) -> ExitCode {
+    let res = if input == Path::new("-") {
+        match read_input(input) {
+            Ok(data) => redshirt::inspect_reader(Cursor::new(data), verify),
+            Err(message) => {
+                eprintln!("error: {message}");
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        redshirt::inspect(input, verify)
+    };
+    match res {
+        Ok(info) => {
+            print_info(&info, json);
+            if info.verified == Some(false) {
+                let e = match info.stored_digest {
+                    Some(digest) if digest.is_zero() => "unfinalized checksum",
+                    _ => "bad checksum",
+                };
+                eprintln!("error: {}: {e}", display(input));
+                ExitCode::from(EXIT_CORRUPT)
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Err(e) => {
+            eprintln!("error: {}: {e}", display(input));
+            if let Error::BadHeader = e {
+                ExitCode::from(EXIT_NOT_REDSHIRT)
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+/// Prints the fields of `info`, either aligned for reading or as a JSON object.
+fn print_info(info: &Info, json: bool) {
+    let version = match info.version {
+        Version::V1 => "v1",
+        Version::V2 => "v2",
+    };
+    let digest = info.stored_digest.map(|digest| digest.to_string());
+    let checksum_ok = info.verified.map(|ok| ok.to_string());
+    if json {
+        let digest = digest.map_or_else(|| "null".to_owned(), |digest| format!("\"{digest}\""));
+        println!(
+            "{{ \"version\": \"{version}\", \"payload_len\": {}, \"digest\": {digest}, \"checksum_ok\": {} }}",
+            info.payload_len,
+            checksum_ok.as_deref().unwrap_or("null"),
+        );
+    } else {
+        println!("version      {version}");
+        println!("payload_len  {}", info.payload_len);
+        println!("digest       {}", digest.as_deref().unwrap_or("-"));
+        println!("checksum_ok  {}", checksum_ok.as_deref().unwrap_or("-"));
+    }
+}
+
+/// Prints the outcome for each file in `report`, followed by a summary.
+fn summarize(root: &Path, report: &BatchReport) -> Result<(), String> {
+    for file in report.files() {
+        let path = root.join(file.path());
+        match file.outcome() {
+            Outcome::Decoded(version) => println!("{}: decoded ({version})", path.display()),
+            Outcome::Encoded(version) => println!("{}: encoded ({version})", path.display()),
+            Outcome::Copied => println!("{}: copied (no Redshirt marker)", path.display()),
+            Outcome::Verified {
+                version: Version::V1,
+                ..
+            } => println!("{}: OK (Redshirt 1, no checksum)", path.display()),
+            Outcome::Verified {
+                version,
+                digest: Some(digest),
+            } => println!("{}: OK ({version}, SHA-1 {digest})", path.display()),
+            Outcome::Verified { version, .. } => println!("{}: OK ({version})", path.display()),
+            Outcome::Skipped => println!("{}: skipped (no Redshirt marker)", path.display()),
+            Outcome::Failed(e) => eprintln!("error: {}: {e}", path.display()),
+        }
+    }
+    println!(
+        "{} ok, {} failed, {} skipped",
+        report.ok(),
+        report.failed(),
+        report.skipped()
+    );
+    match report.failed() {
+        0 => Ok(()),
+        1 => Err("1 file failed".to_owned()),
+        n => Err(format!("{n} files failed")),
+    }
+}
+
+/// Prints the strings in the decoded data of the Redshirt stream `src`, without verifying it.
+fn print_strings<R: Read + Seek>(input: &Path, mut src: R, min_len: usize) -> Result<(), String> {
+    let mut marker = Vec::new();
+    let _ = src
+        .by_ref()
+        .take(9)
+        .read_to_end(&mut marker)
+        .and_then(|_| src.seek(SeekFrom::Start(0)))
+        .map_err(|e| format!("{}: {e}", display(input)))?;
+    let res = match Version::detect(&marker) {
+        Some(Version::V1) => v1::Reader::new(src).and_then(|reader| write_strings(reader, min_len)),
+        Some(Version::V2) => v2::ReaderOptions::new()
+            .accept_unfinalized(true)
+            .verification(v2::Verification::Skip)
+            .from_reader(src)
+            .and_then(|reader| write_strings(reader, min_len)),
+        None => Err(Error::BadHeader),
+    };
+    res.map_err(|e| describe(input, &e, &marker))
+}
+
+fn write_strings<R: Read>(src: R, min_len: usize) -> Result<(), Error> {
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    for item in text::extract_strings(src, min_len) {
+        let (offset, string) = item.map_err(Error::Io)?;
+        writeln!(stdout, "{offset:08x}  {string}").map_err(Error::Io)?;
+    }
+    stdout.flush().map_err(Error::Io)
+}
+
+/// Prints the offset and version of each Redshirt marker in `input`.
+fn scan(input: &Path, verify: bool) -> Result<(), String> {
+    let mut options = ScanOptions::new();
+    let _ = options.verify(verify);
+    if input == Path::new("-") {
+        print_findings(input, io::stdin().lock(), options)
+    } else {
+        let file = File::open(input).map_err(|e| format!("{}: {e}", input.display()))?;
+        print_findings(input, file, options)
+    }
+}
+
+fn print_findings<R: Read>(input: &Path, src: R, options: ScanOptions) -> Result<(), String> {
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    for finding in redshirt::scan(src, &options) {
+        let finding = finding.map_err(|e| format!("{}: {e}", display(input)))?;
+        let version = match finding.version {
+            Version::V1 => "v1",
+            Version::V2 => "v2",
+        };
+        let status = match finding.verified {
+            Some(true) => "  ok",
+            Some(false) => "  bad checksum",
+            None => "",
+        };
+        writeln!(stdout, "{:08x}  {version}{status}", finding.offset).map_err(|e| e.to_string())?;
+    }
+    stdout.flush().map_err(|e| e.to_string())
+}
+
+/// Compares the decoded data of `a` and `b`, printing where they differ.
+fn diff(a: &Path, b: &Path) -> Result<(), String> {
+    let open = |path: &Path| File::open(path).map_err(|e| format!("{}: {e}", path.display()));
+    let res = if a == Path::new("-") {
+        redshirt::compare_decoded(Cursor::new(read_input(a)?), open(b)?)
+    } else if b == Path::new("-") {
+        redshirt::compare_decoded(open(a)?, Cursor::new(read_input(b)?))
+    } else {
+        redshirt::compare_decoded(open(a)?, open(b)?)
+    };
+    let (a, b) = (display(a), display(b));
+    match res.map_err(|e| format!("{a}, {b}: {e}"))? {
+        Comparison::Identical => return Ok(()),
+        Comparison::DifferAt { offset } => println!("{a} and {b} differ at offset {offset}"),
+        Comparison::LengthMismatch { a: a_len, b: b_len } => {
+            println!("{a} and {b} differ in length ({a_len} and {b_len} bytes)");
+        }
+    }
+    Err("decoded data differs".to_owned())
+}
+
+/// Prints each discrepancy found by `manifest check`, followed by a summary.
+fn report_discrepancies(root: &Path, discrepancies: &[Discrepancy]) -> Result<(), String> {
+    let (mut missing, mut added, mut modified) = (0, 0, 0);
+    for discrepancy in discrepancies {
+        let path = root.join(discrepancy.path());
+        match discrepancy {
+            Discrepancy::Missing(_) => {
+                missing += 1;
+                println!("{}: missing", path.display());
+            }
+            Discrepancy::Added(_) => {
+                added += 1;
+                println!("{}: added", path.display());
+            }
+            Discrepancy::Modified { expected, actual } => {
+                modified += 1;
+                println!(
+                    "{}: modified (expected SHA-1 {}, found {})",
+                    path.display(),
+                    expected.digest,
+                    actual.digest
+                );
+            }
+        }
+    }
+    println!("{missing} missing, {added} added, {modified} modified");
+    if discrepancies.is_empty() {
+        Ok(())
+    } else {
+        Err("manifest does not match".to_owned())
+    }
+}
+
+fn encode(input: &Path, output: &Path, version: u8) -> Result<(), String> {
+    if input == Path::new("-") && output == Path::new("-") {
+        let version = if version == 1 {
+            Version::V1
+        } else {
+            Version::V2
+        };
+        return redshirt::encode_stdio(version).map_err(|e| format!("<stdout>: {e}"));
+    }
+    let data = read_input(input)?;
+    let encoded = if version == 1 {
+        v1::encode(&data)
+    } else {
+        v2::encode(&data)
+    };
+    write_output(output, &encoded)
+}
+
+fn decode(input: &Path, output: &Path) -> Result<(), String> {
+    if input == Path::new("-") && output == Path::new("-") {
+        // Streamed without seeking; a bad Redshirt 2 checksum is reported after the output.
+        let _ = redshirt::decode_stdio().map_err(|e| format!("<stdin>: {e}"))?;
+        return Ok(());
+    }
+    let encoded = read_input(input)?;
+    let decoded = match v1::decode(&encoded) {
+        Err(Error::BadHeader) => v2::decode(&encoded),
+        res => res,
+    }
+    .map_err(|e| describe(input, &e, &encoded))?;
+    write_output(output, &decoded)
+}
+
+/// Recovers what it can of the Redshirt 2 file `input` into `output`, reporting the outcome on
+/// standard error so that it is kept apart from data written to standard output.
+fn salvage(input: &Path, output: &Path) -> Result<(), String> {
+    let report = if input == Path::new("-") || output == Path::new("-") {
+        let encoded = read_input(input)?;
+        let mut decoded = Vec::new();
+        let report = v2::salvage(&encoded[..], &mut decoded)
+            .map_err(|e| format!("{}: {e}", display(input)))?;
+        write_output(output, &decoded)?;
+        report
+    } else {
+        v2::salvage_path(input, output).map_err(|e| format!("{}: {e}", input.display()))?
+    };
+    let hash = match report.stored {
+        None => "the file ends within its header".to_owned(),
+        Some(_) if report.is_unfinalized() => "the SHA-1 hash was never finalised".to_owned(),
+        Some(stored) if report.is_mismatch() => format!(
+            "the SHA-1 hash does not match (expected {stored}, found {})",
+            report.actual
+        ),
+        Some(_) => "the SHA-1 hash matches".to_owned(),
+    };
+    eprintln!(
+        "{}: recovered {} bytes; {hash}",
+        display(input),
+        report.recovered
+    );
+    Ok(())
+}
+
+fn read_input(path: &Path) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    if path == Path::new("-") {
+        let _ = io::stdin()
+            .lock()
+            .read_to_end(&mut data)
+            .map_err(|e| format!("<stdin>: {e}"))?;
+    } else {
+        data = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+    Ok(data)
+}
+
+fn write_output(path: &Path, data: &[u8]) -> Result<(), String> {
+    if path == Path::new("-") {
+        let mut stdout = io::stdout().lock();
+        stdout
+            .write_all(data)
+            .and_then(|()| stdout.flush())
+            .map_err(|e| format!("<stdout>: {e}"))
+    } else {
+        fs::write(path, data).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+fn display(path: &Path) -> String {
+    if path == Path::new("-") {
+        "<stdin>".to_owned()
+    } else {
+        path.display().to_string()
+    }
+}
+
+/// Formats an error for `path`, including the bytes found in place of a valid header.
+fn describe(path: &Path, e: &Error, data: &[u8]) -> String {
+    let mut message = format!("{}: {e}", display(path));
+    if let Error::BadHeader = e {
+        message.push_str(" (found");
+        if data.is_empty() {
+            message.push_str(" no data");
+        }
+        for n in data.iter().take(FOUND_LEN) {
+            let _ = write!(message, " {n:02x}");
+        }
+        message.push_str(", expected a Redshirt 1 or Redshirt 2 marker)");
+    }
+    message
+}
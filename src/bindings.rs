@@ -0,0 +1,74 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! In-memory helpers shared by the foreign-language bindings.
+
+use crate::{error::Error, v1, v2};
+use std::{
+    error,
+    fmt::{self, Display, Formatter},
+    io,
+};
+
+#[derive(Debug)]
+/// Marks an error as caused by an invalid argument passed to a binding, rather than by I/O.
+struct InvalidArgument(String);
+
+#[inline]
+/// Decodes `encoded` as the given Redshirt version, or detects the version from the header if
+/// `version` is `None`.
+pub(crate) fn decode(encoded: &[u8], version: Option<u8>) -> Result<Vec<u8>, Error> {
+    match version {
+        Some(1) => v1::decode(encoded),
+        Some(2) => v2::decode(encoded),
+        Some(_) => Err(bad_version()),
+        None => match v1::decode(encoded) {
+            Err(Error::BadHeader) => v2::decode(encoded),
+            res => res,
+        },
+    }
+}
+
+#[inline]
+/// Encodes `data` as the given Redshirt version.
+pub(crate) fn encode(data: &[u8], version: u8) -> Result<Vec<u8>, Error> {
+    match version {
+        1 => Ok(v1::encode(data)),
+        2 => Ok(v2::encode(data)),
+        _ => Err(bad_version()),
+    }
+}
+
+#[inline]
+/// Returns the error reported for an unsupported Redshirt version.
+pub(crate) fn bad_version() -> Error {
+    invalid_argument("version must be 1 or 2")
+}
+
+#[inline]
+/// Returns an error reporting an invalid argument, which `is_invalid_argument` recognises.
+pub(crate) fn invalid_argument<M: Into<String>>(message: M) -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        InvalidArgument(message.into()),
+    ))
+}
+
+#[inline]
+/// Returns whether `e` was returned by `invalid_argument`, as opposed to by an underlying stream.
+pub(crate) fn is_invalid_argument(e: &io::Error) -> bool {
+    matches!(e.get_ref(), Some(inner) if inner.is::<InvalidArgument>())
+}
+
+impl Display for InvalidArgument {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl error::Error for InvalidArgument {}
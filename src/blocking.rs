@@ -0,0 +1,399 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{cursor::BUFFER_LEN, error::Error};
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+use crate::{game::open_auto, v1, v2, version::Version};
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+use std::{fs, path::Path};
+use std::{
+    io::{self, Read},
+    panic,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    task::{self, JoinError},
+};
+
+/// The number of chunks a `BlockingReader` buffers by default.
+const DEFAULT_CHUNKS: usize = 4;
+
+#[derive(Debug)]
+/// Reads from a synchronous reader, such as a `v1::Reader<File>`, on one of Tokio's blocking
+/// threads, and exposes the data it produces through `AsyncRead`.
+///
+/// The blocking thread reads the data in chunks and sends them over a bounded channel, so no more
+/// than `chunks + 2` chunks are held in memory at once, however far the blocking thread is ahead.
+///
+/// If the synchronous reader fails, including because lazy verification detected a bad checksum
+/// at the end of a Redshirt 2 stream, the `io::Error` it returned is passed on unchanged, so that
+/// an `Error` wrapped in it can still be recovered with `get_ref` and `downcast_ref`. Later reads
+/// fail with an error of the same kind.
+///
+/// Dropping a `BlockingReader` closes the channel, after which the blocking thread stops as soon
+/// as it next tries to send a chunk, and drops the synchronous reader.
+pub struct BlockingReader {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    current: Vec<u8>,
+    pos: usize,
+    failed: Option<io::ErrorKind>,
+}
+
+impl BlockingReader {
+    #[inline]
+    /// Moves `src` onto a blocking thread and starts reading from it.
+    ///
+    /// This is equivalent to `BlockingReader::with_capacity(src, 16384, 4)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::{self, tokio::BlockingReader};
+    /// use std::fs::File;
+    /// use tokio::io::AsyncReadExt;
+    ///
+    /// # async fn example() {
+    /// let reader = v1::Reader::new(File::open("data.dat").unwrap()).unwrap();
+    /// let mut data = Vec::new();
+    /// let _ = BlockingReader::new(reader)
+    ///     .read_to_end(&mut data)
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn new<R: Read + Send + 'static>(src: R) -> Self {
+        Self::with_capacity(src, BUFFER_LEN, DEFAULT_CHUNKS)
+    }
+
+    #[inline]
+    /// Moves `src` onto a blocking thread and starts reading from it in chunks of up to
+    /// `chunk_size` bytes, buffering up to `chunks` chunks that have not yet been read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` or `chunks` is zero, or if called outside of a Tokio runtime.
+    pub fn with_capacity<R: Read + Send + 'static>(
+        src: R,
+        chunk_size: usize,
+        chunks: usize,
+    ) -> Self {
+        assert!(chunk_size != 0, "chunk size must be non-zero");
+        let (tx, rx) = mpsc::channel(chunks);
+        drop(task::spawn_blocking(move || pump(src, &tx, chunk_size)));
+        Self {
+            chunks: rx,
+            current: Vec::new(),
+            pos: 0,
+            failed: None,
+        }
+    }
+
+    #[inline]
+    /// Calls `open` on a blocking thread to create a synchronous reader, then reads from it as by
+    /// `BlockingReader::new`.
+    ///
+    /// This allows readers that read or verify data when they are created, such as `v2::Reader`,
+    /// to be created without blocking the asynchronous runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if `open` does.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime, or if `open` panics.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::{
+    ///     v2::{self, tokio::BlockingReader},
+    ///     Error,
+    /// };
+    /// use std::fs::File;
+    ///
+    /// # async fn example() {
+    /// let reader = BlockingReader::open_with(|| {
+    ///     v2::Reader::new(File::open("User.usr").map_err(Error::Io)?)
+    /// })
+    /// .await
+    /// .unwrap();
+    /// # }
+    /// ```
+    pub async fn open_with<R, F>(open: F) -> Result<Self, Error>
+    where
+        R: Read + Send + 'static,
+        F: FnOnce() -> Result<R, Error> + Send + 'static,
+    {
+        let src = join(task::spawn_blocking(open).await)?;
+        Ok(Self::new(src))
+    }
+}
+
+impl AsyncRead for BlockingReader {
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(kind) = this.failed {
+            return Poll::Ready(Err(io::Error::new(kind, "a previous read failed")));
+        }
+        while this.pos == this.current.len() {
+            match this.chunks.poll_recv(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Ready(Some(Ok(chunk))) => {
+                    this.current = chunk;
+                    this.pos = 0;
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    this.failed = Some(e.kind());
+                    return Poll::Ready(Err(e));
+                }
+            }
+        }
+        let len = buf.remaining().min(this.current.len() - this.pos);
+        buf.put_slice(&this.current[this.pos..this.pos + len]);
+        this.pos += len;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reads chunks from `src` and sends them over `tx` until `src` reaches its end, fails, or the
+/// receiving `BlockingReader` is dropped.
+fn pump<R: Read>(mut src: R, tx: &Sender<io::Result<Vec<u8>>>, chunk_size: usize) {
+    loop {
+        let mut chunk = vec![0; chunk_size];
+        match src.read(&mut chunk) {
+            Ok(0) => return,
+            Ok(len) => {
+                chunk.truncate(len);
+                if tx.blocking_send(Ok(chunk)).is_err() {
+                    return;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => {
+                let _ = tx.blocking_send(Err(e));
+                return;
+            }
+        }
+    }
+}
+
+/// Returns the result of a blocking task, resuming its panic if it panicked.
+fn join<T>(res: Result<Result<T, Error>, JoinError>) -> Result<T, Error> {
+    match res {
+        Ok(res) => res,
+        Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(Error::Io(io::Error::other(e))),
+    }
+}
+
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+#[inline]
+/// Reads and decodes the entire contents of the file at `path` on a blocking thread, detecting
+/// its encoding scheme from its marker.
+///
+/// Redshirt 2 files are verified before any data is returned.
+///
+/// # Errors
+///
+/// Returns an `Err` under the same conditions as `open_auto`, or if the file cannot be read.
+///
+/// # Panics
+///
+/// Panics if called outside of a Tokio runtime.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::v2::tokio;
+///
+/// # async fn example() {
+/// let data = tokio::decode_file_blocking("User.usr").await.unwrap();
+/// # }
+/// ```
+pub async fn decode_file_blocking<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, Error> {
+    let path = path.as_ref().to_owned();
+    join(
+        task::spawn_blocking(move || {
+            let mut reader = open_auto(path, None)?;
+            let mut data = Vec::new();
+            let _ = reader.read_to_end(&mut data).map_err(Error::from_io)?;
+            Ok(data)
+        })
+        .await,
+    )
+}
+
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+#[inline]
+/// Encodes `data` with the given encoding scheme and writes it to the file at `path` on a blocking
+/// thread, replacing its contents if it exists.
+///
+/// # Errors
+///
+/// Returns an `Err` if the file cannot be written.
+///
+/// # Panics
+///
+/// Panics if called outside of a Tokio runtime.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::{v2::tokio, Version};
+///
+/// # async fn example() {
+/// tokio::encode_file_blocking("User.usr", b"foobar".to_vec(), Version::V2)
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub async fn encode_file_blocking<P: AsRef<Path>>(
+    path: P,
+    data: Vec<u8>,
+    version: Version,
+) -> Result<(), Error> {
+    let path = path.as_ref().to_owned();
+    join(
+        task::spawn_blocking(move || {
+            let encoded = match version {
+                Version::V1 => v1::encode(&data),
+                Version::V2 => v2::encode(&data),
+            };
+            drop(data);
+            fs::write(path, encoded).map_err(Error::Io)
+        })
+        .await,
+    )
+}
+
+#[cfg(all(test, feature = "redshirt1", feature = "redshirt2"))]
+mod tests {
+    use super::BlockingReader;
+    use crate::{error::Error, v1};
+    use std::{
+        io::{self, Cursor, Read},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+    use tokio::io::AsyncReadExt;
+
+    /// A reader that produces an endless stream of bytes and records when it is dropped.
+    struct Endless(Arc<AtomicBool>);
+
+    impl Read for Endless {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            buf.fill(0x55);
+            Ok(buf.len())
+        }
+    }
+
+    impl Drop for Endless {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn read_v1() {
+        let data = (0..=255_u8).cycle().take(100_000).collect::<Vec<_>>();
+        let reader = v1::Reader::new(Cursor::new(v1::encode(&data))).unwrap();
+        let mut decoded = Vec::new();
+        let _ = BlockingReader::with_capacity(reader, 1000, 2)
+            .read_to_end(&mut decoded)
+            .await
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[tokio::test]
+    async fn bad_checksum() {
+        use crate::v2::{self, ReaderOptions, Verification, HEADER_LEN};
+
+        let mut encoded = v2::encode(&[0x55; 100]);
+        encoded[HEADER_LEN] ^= 1;
+        let mut options = ReaderOptions::new();
+        let _ = options.verification(Verification::Lazy);
+        let lazy = encoded.clone();
+        let mut reader = BlockingReader::open_with(move || options.from_reader(Cursor::new(lazy)))
+            .await
+            .unwrap();
+        let mut decoded = Vec::new();
+        let e = reader.read_to_end(&mut decoded).await.unwrap_err();
+        assert!(matches!(
+            e.get_ref().and_then(|e| e.downcast_ref::<Error>()),
+            Some(Error::BadChecksum { .. })
+        ));
+        assert_eq!(
+            reader.read_u8().await.unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(matches!(
+            BlockingReader::open_with(move || v2::Reader::new(Cursor::new(encoded))).await,
+            Err(Error::BadChecksum { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn drop_stops_thread() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let mut reader = BlockingReader::with_capacity(Endless(Arc::clone(&dropped)), 16, 1);
+        let mut buf = [0; 64];
+        let _ = reader.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [0x55; 64]);
+        drop(reader);
+        let stopped = tokio::task::spawn_blocking(move || {
+            (0..500).any(|_| {
+                thread::sleep(Duration::from_millis(10));
+                dropped.load(Ordering::SeqCst)
+            })
+        });
+        assert!(stopped.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn file_round_trip() {
+        use crate::version::Version;
+
+        for version in [Version::V1, Version::V2] {
+            let path = std::env::temp_dir().join(format!(
+                "redshirt-blocking-{}-file_round_trip-{:?}",
+                std::process::id(),
+                version
+            ));
+            super::encode_file_blocking(&path, b"foobar".to_vec(), version)
+                .await
+                .unwrap();
+            let encoded = std::fs::read(&path).unwrap();
+            let decoded = super::decode_file_blocking(&path).await.unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(Version::detect(&encoded), Some(version));
+            assert_eq!(decoded, b"foobar");
+        }
+        assert!(matches!(
+            super::decode_file_blocking(std::env::temp_dir().join("redshirt-missing")).await,
+            Err(Error::Io(_))
+        ));
+    }
+}
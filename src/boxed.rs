@@ -0,0 +1,23 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::io::{Read, Seek, Write};
+
+/// A stream that supports reading and seeking, for use as a trait object.
+///
+/// A trait object can only name one trait other than auto traits, so `dyn Read + Seek` is not a
+/// valid type; `dyn ReadSeek` is used instead. It is implemented for every `Read + Seek` type.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek + ?Sized> ReadSeek for T {}
+
+/// A stream that supports writing and seeking, for use as a trait object.
+///
+/// This is the `Write` counterpart of `ReadSeek`, and is implemented for every `Write + Seek` type.
+pub trait WriteSeek: Write + Seek {}
+
+impl<T: Write + Seek + ?Sized> WriteSeek for T {}
@@ -0,0 +1,282 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::cursor::{self, overflow_error};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Debug, Formatter},
+    io::{self, BufRead, Read, Seek, SeekFrom},
+};
+
+/// Buffers the decoded data read from a `Reader`, keeping the buffer when seeking within it.
+///
+/// Unlike `std::io::BufReader`, which discards its buffer on every seek, seeking to a position
+/// within the buffered data only moves the read position, so many small seeks and reads over the
+/// same region of a stream do not each reach the underlying reader.
+///
+/// Created by `v1::Reader::buffered` or `v2::Reader::buffered`.
+pub struct BufferedReader<R> {
+    inner: R,
+    buffer: Box<[u8]>,
+    pos: usize,
+    filled: usize,
+    /// The position of `inner`, which is the position of the end of the buffered data, if known.
+    inner_pos: Option<u64>,
+}
+
+impl<R> BufferedReader<R> {
+    #[inline]
+    pub(crate) fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buffer: vec![0; capacity.max(1)].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+            inner_pos: None,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns a reference to the underlying reader.
+    pub const fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the capacity of the internal buffer.
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    #[inline]
+    /// Unwraps a `BufferedReader`, returning the underlying reader.
+    ///
+    /// Any buffered data is lost, and the underlying reader is positioned after it.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn discard(&mut self) {
+        self.pos = 0;
+        self.filled = 0;
+    }
+
+    fn advance(&mut self, len: usize) -> io::Result<()> {
+        if let Some(inner_pos) = self.inner_pos.as_mut() {
+            *inner_pos = cursor::add_len(*inner_pos, len)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Seek> BufferedReader<R> {
+    /// Returns the position of the end of the buffered data, querying the underlying reader once.
+    fn inner_pos(&mut self) -> io::Result<u64> {
+        if let Some(inner_pos) = self.inner_pos {
+            return Ok(inner_pos);
+        }
+        let inner_pos = self.inner.stream_position()?;
+        self.inner_pos = Some(inner_pos);
+        Ok(inner_pos)
+    }
+}
+
+impl<R: Read> Read for BufferedReader<R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.filled && buf.len() >= self.buffer.len() {
+            // The buffer no longer ends where the underlying reader is positioned after this.
+            self.discard();
+            let len = self.inner.read(buf)?;
+            self.advance(len)?;
+            return Ok(len);
+        }
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: Read> BufRead for BufferedReader<R> {
+    #[inline]
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            let len = self.inner.read(&mut self.buffer)?;
+            self.advance(len)?;
+            self.pos = 0;
+            self.filled = len;
+        }
+        Ok(&self.buffer[self.pos..self.filled])
+    }
+
+    #[inline]
+    fn consume(&mut self, amt: usize) {
+        self.pos = self.pos.saturating_add(amt).min(self.filled);
+    }
+}
+
+impl<R: Seek> Seek for BufferedReader<R> {
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let end = self.inner_pos()?;
+        let start = end
+            .checked_sub(cursor::len_u64(self.filled))
+            .ok_or_else(overflow_error)?;
+        let current = start
+            .checked_add(cursor::len_u64(self.pos))
+            .ok_or_else(overflow_error)?;
+        // The underlying reader is positioned after the buffered data, so seeks relative to the
+        // current position are made absolute.
+        let pos = match pos {
+            SeekFrom::Current(n) => current
+                .checked_add_signed(n)
+                .map(SeekFrom::Start)
+                .ok_or_else(overflow_error)?,
+            _ => pos,
+        };
+        if let SeekFrom::Start(target) = pos {
+            if start <= target && target <= end {
+                self.pos = usize::try_from(target - start).map_err(|_| overflow_error())?;
+                return Ok(target);
+            }
+        }
+        self.discard();
+        self.inner_pos = None;
+        let inner_pos = self.inner.seek(pos)?;
+        self.inner_pos = Some(inner_pos);
+        Ok(inner_pos)
+    }
+}
+
+impl<R: Debug> Debug for BufferedReader<R> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferedReader")
+            .field("inner", &self.inner)
+            .field("buffered", &(self.filled - self.pos))
+            .field("capacity", &self.buffer.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferedReader;
+    use std::io::{BufRead, Cursor, Read, Seek, SeekFrom};
+
+    /// Counts the seeks and reads that reach the underlying reader.
+    struct Counting<T> {
+        inner: T,
+        reads: usize,
+        seeks: usize,
+    }
+
+    impl<T: Read> Read for Counting<T> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.reads += 1;
+            self.inner.read(buf)
+        }
+    }
+
+    impl<T: Seek> Seek for Counting<T> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.seeks += 1;
+            self.inner.seek(pos)
+        }
+    }
+
+    /// Encodes `data` with whichever encoding scheme is enabled, and opens a reader for it.
+    #[cfg(feature = "redshirt1")]
+    fn decoder(data: &[u8]) -> impl Read + Seek {
+        crate::v1::Reader::new(Cursor::new(crate::v1::encode(data))).unwrap()
+    }
+
+    /// Encodes `data` with whichever encoding scheme is enabled, and opens a reader for it.
+    #[cfg(not(feature = "redshirt1"))]
+    fn decoder(data: &[u8]) -> impl Read + Seek {
+        crate::v2::Reader::new(Cursor::new(crate::v2::encode(data))).unwrap()
+    }
+
+    fn reader(data: &[u8], capacity: usize) -> BufferedReader<Counting<impl Read + Seek>> {
+        let inner = decoder(data);
+        BufferedReader::with_capacity(
+            capacity,
+            Counting {
+                inner,
+                reads: 0,
+                seeks: 0,
+            },
+        )
+    }
+
+    #[test]
+    fn seek_within_buffer() {
+        let data: Vec<u8> = (0..=255).collect();
+        let mut reader = reader(&data, 64);
+        let mut byte = [0];
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(reader.seek(SeekFrom::Current(9)).unwrap(), 10);
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], 10);
+        assert_eq!(reader.seek(SeekFrom::Start(2)).unwrap(), 2);
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], 2);
+        assert_eq!(reader.stream_position().unwrap(), 3);
+        assert_eq!(reader.get_ref().reads, 1);
+        assert_eq!(reader.get_ref().seeks, 1);
+    }
+
+    #[test]
+    fn seek_outside_buffer() {
+        let data: Vec<u8> = (0..=255).collect();
+        let mut reader = reader(&data, 64);
+        let mut byte = [0];
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(reader.seek(SeekFrom::Current(99)).unwrap(), 100);
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], 100);
+        assert_eq!(reader.seek(SeekFrom::End(-1)).unwrap(), 255);
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], 255);
+        assert_eq!(reader.seek(SeekFrom::Current(-200)).unwrap(), 56);
+        assert_eq!(reader.fill_buf().unwrap()[0], 56);
+        assert!(reader.seek(SeekFrom::Current(-100)).is_err());
+    }
+
+    #[test]
+    fn seek_after_large_read() {
+        let data: Vec<u8> = (0..=255).collect();
+        let mut reader = reader(&data, 16);
+        let mut byte = [0];
+        reader.read_exact(&mut byte).unwrap();
+        reader.consume(15);
+        let mut large = [0; 16];
+        reader.read_exact(&mut large).unwrap();
+        assert_eq!(large[0], 16);
+        assert_eq!(reader.seek(SeekFrom::Start(31)).unwrap(), 31);
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], 31);
+        assert_eq!(reader.seek(SeekFrom::Start(15)).unwrap(), 15);
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], 15);
+    }
+
+    #[test]
+    fn read_large_and_lines() {
+        let mut reader = reader(b"first\nsecond\n", 4);
+        let lines: Vec<String> = reader.by_ref().lines().map(Result::unwrap).collect();
+        assert_eq!(lines, ["first", "second"]);
+        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+        let mut all = [0; 13];
+        reader.read_exact(&mut all).unwrap();
+        assert_eq!(&all, b"first\nsecond\n");
+    }
+}
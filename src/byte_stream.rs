@@ -0,0 +1,79 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use bytes::{Bytes, BytesMut};
+use futures_core::Stream;
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, ReadBuf};
+
+#[derive(Debug)]
+/// A stream of decoded data, read from an asynchronous reader in fixed-size chunks.
+///
+/// Every chunk yielded by a `ByteStream` is exactly the requested size, except for the final chunk,
+/// which may be shorter. If reading fails, including because lazy verification detected a bad
+/// checksum at the end of the stream, the error is yielded and the stream terminates.
+///
+/// This type is returned by `into_byte_stream` on the Tokio readers.
+pub struct ByteStream<R> {
+    reader: Option<R>,
+    buffer: BytesMut,
+    chunk_size: usize,
+}
+
+impl<R> ByteStream<R> {
+    #[inline]
+    pub(crate) fn new(reader: R, chunk_size: usize) -> Self {
+        assert!(chunk_size != 0, "chunk size must be non-zero");
+        Self {
+            reader: Some(reader),
+            buffer: BytesMut::with_capacity(chunk_size),
+            chunk_size,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for ByteStream<R> {
+    type Item = io::Result<Bytes>;
+
+    #[inline]
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(reader) = this.reader.as_mut() else {
+            return Poll::Ready(None);
+        };
+        while this.buffer.len() < this.chunk_size {
+            let filled = this.buffer.len();
+            this.buffer.resize(this.chunk_size, 0);
+            let mut buf = ReadBuf::new(&mut this.buffer[filled..]);
+            let res = Pin::new(&mut *reader).poll_read(cx, &mut buf);
+            let len = buf.filled().len();
+            this.buffer.truncate(filled + len);
+            match res {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(())) if len == 0 => {
+                    this.reader = None;
+                    return Poll::Ready(if this.buffer.is_empty() {
+                        None
+                    } else {
+                        Some(Ok(this.buffer.split().freeze()))
+                    });
+                }
+                Poll::Ready(Ok(())) => (),
+                Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::Interrupted => (),
+                Poll::Ready(Err(e)) => {
+                    this.reader = None;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+        }
+        Poll::Ready(Some(Ok(this.buffer.split().freeze())))
+    }
+}
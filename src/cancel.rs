@@ -0,0 +1,169 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+#[cfg(feature = "redshirt2")]
+use crate::error::Error;
+#[cfg(feature = "redshirt2")]
+use std::io;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+#[derive(Clone, Debug, Default)]
+/// A flag that stops a long-running operation, such as verifying a large file or processing a
+/// directory tree, from another thread.
+///
+/// Clones share the same flag, so a clone can be handed to the operation while the original is
+/// kept to cancel it. Operations check the flag once per chunk of data, or once per file, and
+/// return `Error::Cancelled` once it is set. A cancelled token stays cancelled.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::{v2, CancelToken, Error};
+///
+/// let token = CancelToken::new();
+/// token.cancel();
+/// let encoded = v2::encode(b"foo");
+/// assert!(matches!(
+///     v2::verify_cancellable(&encoded[..], &token),
+///     Err(Error::Cancelled)
+/// ));
+/// ```
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    #[inline]
+    #[must_use]
+    /// Creates a new token that has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    /// Cancels every operation using this token or one of its clones.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns whether this token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "redshirt2")]
+#[inline]
+/// Fails with `Error::Cancelled` if `token` is present and has been cancelled.
+pub(crate) fn check(token: Option<&CancelToken>) -> Result<(), Error> {
+    match token {
+        Some(token) if token.is_cancelled() => Err(Error::Cancelled),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(feature = "redshirt2")]
+#[inline]
+/// Fails with an `io::Error` wrapping `Error::Cancelled` if `token` is present and has been
+/// cancelled, for use inside I/O loops.
+pub(crate) fn check_io(token: Option<&CancelToken>) -> io::Result<()> {
+    check(token).map_err(io::Error::from)
+}
+
+#[cfg(all(test, feature = "redshirt2"))]
+mod tests {
+    use super::CancelToken;
+    use crate::{cursor::BUFFER_LEN, error::Error, v2};
+    use std::{
+        io::{self, Cursor, Read, Seek, SeekFrom},
+        sync::mpsc::{self, Receiver, Sender},
+        thread,
+    };
+
+    /// A reader that asks another thread to cancel a token once it has been read from `after`
+    /// times, and waits until it has.
+    struct Cancelling<R> {
+        inner: R,
+        after: usize,
+        request: Sender<()>,
+        done: Receiver<()>,
+    }
+
+    impl<R: Read> Read for Cancelling<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.after == 0 {
+                self.request.send(()).unwrap();
+                self.done.recv().unwrap();
+            }
+            self.after = self.after.wrapping_sub(1);
+            self.inner.read(buf)
+        }
+    }
+
+    impl<R: Seek> Seek for Cancelling<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    fn cancel_after<R>(inner: R, after: usize, token: &CancelToken) -> Cancelling<R> {
+        let (request, requested) = mpsc::channel();
+        let (cancelled, done) = mpsc::channel();
+        let token = token.clone();
+        let _ = thread::spawn(move || {
+            requested.recv().unwrap();
+            token.cancel();
+            cancelled.send(()).unwrap();
+        });
+        Cancelling {
+            inner,
+            after,
+            request,
+            done,
+        }
+    }
+
+    #[test]
+    fn verify_cancelled_from_thread() {
+        let encoded = v2::encode(&vec![0x55; BUFFER_LEN * 64]);
+        let token = CancelToken::new();
+        assert!(v2::verify_cancellable(&encoded[..], &token).is_ok());
+        let src = cancel_after(&encoded[..], 4, &token);
+        assert!(matches!(
+            v2::verify_cancellable(src, &token),
+            Err(Error::Cancelled)
+        ));
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn open_cancelled_from_thread() {
+        let encoded = v2::encode(&vec![0x55; BUFFER_LEN * 64]);
+        let token = CancelToken::new();
+        let src = cancel_after(Cursor::new(&encoded[..]), 4, &token);
+        assert!(matches!(
+            v2::ReaderOptions::new().from_reader_cancellable(src, &token),
+            Err(Error::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn copy_cancelled_from_thread() {
+        let encoded = v2::encode(&vec![0x55; BUFFER_LEN * 64]);
+        let token = CancelToken::new();
+        let src = cancel_after(&encoded[..], 4, &token);
+        let mut copy = Vec::new();
+        assert!(matches!(
+            v2::CopyOptions::new().copy_cancellable(src, &mut copy, &token),
+            Err(Error::Cancelled)
+        ));
+        assert!(copy.len() < encoded.len());
+    }
+}
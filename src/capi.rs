@@ -0,0 +1,240 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! A C API for decoding, encoding and verifying Redshirt-encoded files.
+//!
+//! The corresponding C header is `include/redshirt.h` in the source distribution.
+//!
+//! # Conventions
+//!
+//! Every function that can fail returns a `RedshirtStatus` code, where `REDSHIRT_OK` (zero)
+//! indicates success. After a failure, `redshirt_last_error_message` returns a description of the
+//! error, which remains valid until the next call into this API on the same thread.
+//!
+//! Paths are NUL-terminated, UTF-8 encoded strings.
+//!
+//! # Memory ownership
+//!
+//! Buffers returned by `redshirt_decode_file` are allocated by this library, and must be released
+//! with `redshirt_free_buffer`; they must not be passed to `free`. All other pointers are borrowed
+//! for the duration of the call, and are never retained or freed by this library.
+
+#![allow(unsafe_code)]
+
+use crate::{bindings, error::Error, v2};
+use std::{
+    cell::RefCell,
+    convert::TryFrom,
+    ffi::{CStr, CString},
+    fs::{self, File},
+    os::raw::{c_char, c_int},
+    ptr, slice,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+/// Status codes returned by the C API, mirroring the variants of `Error`.
+pub enum RedshirtStatus {
+    /// The operation succeeded.
+    Ok = 0,
+    /// An I/O error occurred; corresponds to `Error::Io`.
+    Io = 1,
+    /// The header contains invalid data; corresponds to `Error::BadHeader`.
+    BadHeader = 2,
+    /// The SHA-1 hash does not match; corresponds to `Error::BadChecksum`.
+    BadChecksum = 3,
+    /// The SHA-1 hash is all zeroes; corresponds to `Error::Unfinalized`.
+    Unfinalized = 4,
+    /// A null pointer, non-UTF-8 path or unsupported version was passed.
+    InvalidArgument = 5,
+    /// Decoded data is not valid UTF-8; corresponds to `Error::InvalidText`.
+    InvalidText = 6,
+    /// The data exceeds the maximum length; corresponds to `Error::PayloadTooLarge`.
+    PayloadTooLarge = 7,
+    /// The operation was cancelled; corresponds to `Error::Cancelled`.
+    Cancelled = 8,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+#[no_mangle]
+/// Reads and decodes the Redshirt 1 or Redshirt 2 file at `path`, detecting the version from its
+/// header.
+///
+/// On success, `*out_buf` receives a buffer containing the decoded data, which must be released
+/// with `redshirt_free_buffer`, and `*out_len` receives its length. If the decoded data is empty,
+/// `*out_buf` is set to `NULL`.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string, and `out_buf` and `out_len` must be valid for
+/// writes.
+pub unsafe extern "C" fn redshirt_decode_file(
+    path: *const c_char,
+    out_buf: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    if out_buf.is_null() || out_len.is_null() {
+        return invalid_argument("output pointers must not be null");
+    }
+    let res = path_arg(path).and_then(|path| {
+        let encoded = fs::read(path).map_err(Error::Io)?;
+        bindings::decode(&encoded, None)
+    });
+    report(res.map(|decoded| {
+        let len = decoded.len();
+        let buf = if len == 0 {
+            ptr::null_mut()
+        } else {
+            Box::into_raw(decoded.into_boxed_slice()).cast::<u8>()
+        };
+        out_buf.write(buf);
+        out_len.write(len);
+    }))
+}
+
+#[no_mangle]
+/// Encodes `len` bytes from `data` and writes them to the file at `path`, replacing its contents
+/// if it exists.
+///
+/// `version` must be `1` or `2`.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string, and `data` must be valid for reads of `len`
+/// bytes. `data` may be `NULL` if `len` is zero.
+pub unsafe extern "C" fn redshirt_encode_file(
+    path: *const c_char,
+    data: *const u8,
+    len: usize,
+    version: c_int,
+) -> c_int {
+    let data = if len == 0 {
+        &[]
+    } else if data.is_null() {
+        return invalid_argument("data must not be null");
+    } else {
+        slice::from_raw_parts(data, len)
+    };
+    report(path_arg(path).and_then(|path| {
+        let version = u8::try_from(version).map_err(|_| bindings::bad_version())?;
+        let encoded = bindings::encode(data, version)?;
+        fs::write(path, encoded).map_err(Error::Io)
+    }))
+}
+
+#[no_mangle]
+/// Verifies the SHA-1 hash of the Redshirt 2 file at `path`, without decoding it.
+///
+/// On success, the 20-byte hash is written to `out_digest`.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string, and `out_digest` must be valid for writes of 20
+/// bytes.
+pub unsafe extern "C" fn redshirt_verify(path: *const c_char, out_digest: *mut u8) -> c_int {
+    if out_digest.is_null() {
+        return invalid_argument("output pointer must not be null");
+    }
+    report(path_arg(path).and_then(|path| {
+        let file = File::open(path).map_err(Error::Io)?;
+        v2::verify(file).map(|digest| {
+            let bytes = digest.to_bytes();
+            ptr::copy_nonoverlapping(bytes.as_ptr(), out_digest, bytes.len());
+        })
+    }))
+}
+
+#[no_mangle]
+/// Releases a buffer returned by `redshirt_decode_file`.
+///
+/// Passing `NULL` does nothing.
+///
+/// # Safety
+///
+/// `buf` must be `NULL`, or a buffer returned by `redshirt_decode_file` that has not already been
+/// released, and `len` must be the length returned alongside it.
+pub unsafe extern "C" fn redshirt_free_buffer(buf: *mut u8, len: usize) {
+    if !buf.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buf, len)));
+    }
+}
+
+#[no_mangle]
+/// Returns a description of the last error that occurred on this thread, or `NULL` if no error
+/// has occurred.
+///
+/// The returned string is owned by this library, and remains valid until the next call into this
+/// API on the same thread.
+pub extern "C" fn redshirt_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+#[inline]
+unsafe fn path_arg<'a>(path: *const c_char) -> Result<&'a str, Error> {
+    if path.is_null() {
+        return Err(bindings::invalid_argument("path must not be null"));
+    }
+    CStr::from_ptr(path)
+        .to_str()
+        .map_err(|e| bindings::invalid_argument(e.to_string()))
+}
+
+#[inline]
+fn report(res: Result<(), Error>) -> c_int {
+    let status = match &res {
+        Ok(()) => RedshirtStatus::Ok,
+        Err(Error::Io(e)) if bindings::is_invalid_argument(e) => RedshirtStatus::InvalidArgument,
+        Err(Error::Io(_)) => RedshirtStatus::Io,
+        Err(Error::BadHeader) => RedshirtStatus::BadHeader,
+        Err(Error::BadChecksum { .. }) => RedshirtStatus::BadChecksum,
+        Err(Error::Unfinalized) => RedshirtStatus::Unfinalized,
+        Err(Error::InvalidText { .. }) => RedshirtStatus::InvalidText,
+        Err(Error::PayloadTooLarge { .. }) => RedshirtStatus::PayloadTooLarge,
+        Err(Error::Cancelled) => RedshirtStatus::Cancelled,
+    };
+    if let Err(e) = res {
+        set_last_error(&e.to_string());
+    }
+    status as c_int
+}
+
+#[inline]
+fn invalid_argument(message: &str) -> c_int {
+    set_last_error(message);
+    RedshirtStatus::InvalidArgument as c_int
+}
+
+#[inline]
+fn set_last_error(message: &str) {
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{report, RedshirtStatus};
+    use crate::{bindings, error::Error};
+    use std::{io, os::raw::c_int};
+
+    #[test]
+    fn report_invalid_argument() {
+        assert_eq!(
+            report(Err(bindings::invalid_argument("bad"))),
+            RedshirtStatus::InvalidArgument as c_int
+        );
+        assert_eq!(
+            report(Err(bindings::bad_version())),
+            RedshirtStatus::InvalidArgument as c_int
+        );
+        // Errors from the file system are reported as I/O errors, whatever their kind.
+        let e = io::Error::new(io::ErrorKind::InvalidInput, "bad");
+        assert_eq!(report(Err(Error::Io(e))), RedshirtStatus::Io as c_int);
+    }
+}
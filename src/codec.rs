@@ -0,0 +1,407 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Codecs for framed I/O, for encoding large in-memory buffers in parallel, and for recognising
+//! encoded data without a header.
+//!
+//! # Framed I/O
+//!
+//! If the `tokio-codec` feature is enabled, this module provides [`tokio_util::codec`]
+//! implementations. `V1Codec` and `V2Codec` implement `Encoder<Bytes>` and `Decoder`, so that
+//! Redshirt encoding can be dropped into an existing `Framed`, `FramedRead` or `FramedWrite`
+//! pipeline. Each codec treats the entire stream as a single Redshirt-encoded document: the header
+//! is written before the first encoded item, and is read and validated before the first decoded
+//! item. The decoders yield whatever data is available, so frame boundaries are not preserved.
+//!
+//! Errors are reported as `io::Error`s; errors specific to Redshirt (such as a bad header) can be
+//! recovered as an `Error` in the same way as for lazy verification.
+//!
+//! # Parallel encoding
+//!
+//! If the `rayon` feature is enabled, `par_encode_in_place` and `par_decode_in_place` transform a
+//! buffer of payload data across Rayon's global thread pool, and `v1::encode`, `v1::decode`,
+//! `v2::encode` and `v2::decode` use them for payloads of at least 1 MiB. Below that, the cost of
+//! distributing the work outweighs the gain. The SHA-1 hash of a Redshirt 2 stream can only be
+//! computed serially, so for Redshirt 2 only the encoding itself runs in parallel.
+//!
+//! # Buffers
+//!
+//! If the `bytes` feature is enabled, `encode_into_buf` and `decode_from_buf` transform payload
+//! data from any [`bytes::Buf`] into any [`bytes::BufMut`], one chunk at a time, so that data held
+//! in several separate buffers (such as a chain of `Bytes`) is never gathered into one. As with the
+//! parallel functions, the header is neither written nor read.
+//!
+//! # Headerless payloads
+//!
+//! Some tools strip the marker from Redshirt files and store only the encoded payload, which
+//! `Version::detect` cannot recognise. `is_probably_encoded` and `is_probably_encoded_reader`
+//! estimate from a sample whether such data is encoded, returning a graded `Confidence`.
+//!
+//! [`tokio_util::codec`]: https://docs.rs/tokio-util/latest/tokio_util/codec/index.html
+//! [`bytes::Buf`]: https://docs.rs/bytes/latest/bytes/trait.Buf.html
+//! [`bytes::BufMut`]: https://docs.rs/bytes/latest/bytes/trait.BufMut.html
+
+#[cfg(feature = "bytes")]
+use crate::cursor::{Scratch, BUFFER_LEN};
+#[cfg(any(feature = "rayon", feature = "bytes"))]
+use crate::xor_bytes;
+#[cfg(feature = "bytes")]
+use bytes::{Buf, BufMut};
+#[cfg(feature = "rayon")]
+use rayon::{iter::ParallelIterator, slice::ParallelSliceMut};
+use std::{
+    convert::TryFrom,
+    io::{self, Read},
+};
+
+#[cfg(all(feature = "tokio-codec", feature = "redshirt1"))]
+pub use crate::v1::codec::V1Codec;
+#[cfg(all(feature = "tokio-codec", feature = "redshirt2"))]
+pub use crate::v2::codec::V2Codec;
+
+/// The length of the shortest sample from which `is_probably_encoded` reports `Likely`.
+const MIN_SAMPLE_LEN: usize = 64;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+/// How confident `is_probably_encoded` is that a sample of data is Redshirt-encoded.
+///
+/// Variants are ordered from least to most confident, so they can be compared with `>=`.
+pub enum Confidence {
+    /// The sample does not look encoded. This includes empty samples.
+    Unlikely,
+    /// The sample may be encoded, but looks similar to ordinary binary data.
+    Possible,
+    /// The sample looks encoded.
+    Likely,
+}
+
+#[cfg(feature = "rayon")]
+/// The length of the chunks that a buffer is split into, small enough to fit in a core's L2 cache.
+const PAR_CHUNK_LEN: usize = 64 * 1024;
+
+#[cfg(feature = "rayon")]
+/// The payload length from which the in-memory encoding functions transform data in parallel.
+pub(crate) const PAR_THRESHOLD: usize = 1024 * 1024;
+
+#[cfg(feature = "rayon")]
+#[inline]
+/// Encodes a buffer of payload data in place, splitting it into chunks across Rayon's global
+/// thread pool.
+///
+/// The header is not written; to encode a complete stream, use `v1::encode` or `v2::encode`.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::codec;
+///
+/// let mut data = b"foo".to_vec();
+/// codec::par_encode_in_place(&mut data);
+/// assert_eq!(data, b"\xE6\xEF\xEF");
+/// ```
+pub fn par_encode_in_place(data: &mut [u8]) {
+    data.par_chunks_mut(PAR_CHUNK_LEN).for_each(xor_bytes);
+}
+
+#[cfg(feature = "rayon")]
+#[inline]
+/// Decodes a buffer of payload data in place, splitting it into chunks across Rayon's global
+/// thread pool.
+///
+/// The data must not include the header; to decode a complete stream, use `v1::decode` or
+/// `v2::decode`.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::codec;
+///
+/// let mut data = b"\xE6\xEF\xEF".to_vec();
+/// codec::par_decode_in_place(&mut data);
+/// assert_eq!(data, b"foo");
+/// ```
+pub fn par_decode_in_place(data: &mut [u8]) {
+    par_encode_in_place(data);
+}
+
+#[cfg(feature = "bytes")]
+#[inline]
+/// Encodes payload data from `src` into `dst`, one chunk at a time, returning the number of bytes
+/// encoded.
+///
+/// Encoding stops when `src` is exhausted or `dst` is full. The header is not written; to produce
+/// a complete Redshirt 1 stream, put `v1::encode(&[])` into `dst` first.
+///
+/// # Examples
+///
+/// ```
+/// use bytes::{Buf, BytesMut};
+/// use redshirt::codec;
+///
+/// let mut src = &b"foo"[..];
+/// let mut dst = BytesMut::new();
+/// assert_eq!(codec::encode_into_buf(&mut src, &mut dst), 3);
+/// assert!(!src.has_remaining());
+/// assert_eq!(dst, &b"\xE6\xEF\xEF"[..]);
+/// ```
+pub fn encode_into_buf<S: Buf + ?Sized, D: BufMut + ?Sized>(src: &mut S, dst: &mut D) -> usize {
+    let mut scratch = Scratch::new();
+    let mut total = 0;
+    loop {
+        let len = src.chunk().len().min(dst.remaining_mut()).min(BUFFER_LEN);
+        if len == 0 {
+            break total;
+        }
+        let chunk = &mut scratch[..len];
+        chunk.copy_from_slice(&src.chunk()[..len]);
+        xor_bytes(chunk);
+        dst.put_slice(chunk);
+        src.advance(len);
+        total += len;
+    }
+}
+
+#[cfg(feature = "bytes")]
+#[inline]
+/// Decodes payload data from `src` into `dst`, one chunk at a time, returning the number of bytes
+/// decoded.
+///
+/// Decoding stops when `src` is exhausted or `dst` is full. The data must not include the header;
+/// skip it with `Buf::advance` first.
+///
+/// # Examples
+///
+/// ```
+/// use bytes::{Buf, Bytes, BytesMut};
+/// use redshirt::{codec, v1};
+///
+/// let encoded = Bytes::from(v1::encode(b"Hello world!"));
+/// let mut src = encoded.slice(9..13).chain(encoded.slice(13..));
+/// let mut dst = BytesMut::new();
+/// assert_eq!(codec::decode_from_buf(&mut src, &mut dst), 12);
+/// assert_eq!(dst, &b"Hello world!"[..]);
+/// ```
+pub fn decode_from_buf<S: Buf + ?Sized, D: BufMut + ?Sized>(src: &mut S, dst: &mut D) -> usize {
+    encode_into_buf(src, dst)
+}
+
+#[inline]
+#[must_use]
+/// Estimates whether `sample`, which has no header, is Redshirt-encoded payload data.
+///
+/// Encoding sets the high bit of every ASCII character and of the zero bytes that pad small
+/// integers, so most bytes of typical encoded data, such as an *Uplink* save, have the high bit
+/// set, while most bytes of the same data before encoding do not. The sample is also compared
+/// against the result of decoding it: encoded data becomes mostly printable text or zero bytes.
+///
+/// The result is at most `Possible` for samples shorter than 64 bytes.
+///
+/// # False positives and negatives
+///
+/// Data that is already binary, such as compressed or encrypted data, has the high bit set in
+/// about half of its bytes whether or not it is encoded, and is reported as `Unlikely` or
+/// `Possible`. Decoding such data gives meaningless results either way. Binary data in which most
+/// bytes have the high bit set, such as UTF-8 text in a non-Latin script, may be reported as
+/// `Possible`. Only data that also becomes mostly printable or zero when decoded is reported as
+/// `Likely`, but the heuristic can still be fooled by data that happens to resemble encoded text.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::{codec::{self, Confidence}, v1};
+///
+/// let text = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor.";
+/// let encoded = v1::encode(text);
+/// assert_eq!(codec::is_probably_encoded(&encoded[9..]), Confidence::Likely);
+/// assert_eq!(codec::is_probably_encoded(text), Confidence::Unlikely);
+/// ```
+pub fn is_probably_encoded(sample: &[u8]) -> Confidence {
+    let len = sample.len();
+    let high = sample.iter().filter(|&&b| b >= 0x80).count();
+    let text_before = sample.iter().filter(|&&b| is_text(b)).count();
+    let plain_after = sample
+        .iter()
+        .filter(|&&b| b == 0x80 || is_text(b ^ 0x80))
+        .count();
+    if len >= MIN_SAMPLE_LEN && high * 4 >= len * 3 && plain_after * 2 >= len {
+        Confidence::Likely
+    } else if len > 0 && (high * 5 >= len * 3 || plain_after >= text_before + len / 4) {
+        Confidence::Possible
+    } else {
+        Confidence::Unlikely
+    }
+}
+
+#[inline]
+/// Estimates whether the data read from `src`, which has no header, is Redshirt-encoded payload
+/// data, as by `is_probably_encoded`.
+///
+/// At most `sample_len` bytes are read from `src`; a few KiB is usually enough.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::codec::{self, Confidence};
+/// use std::fs::File;
+///
+/// let file = File::open("mystery.bin").unwrap();
+/// if codec::is_probably_encoded_reader(file, 4096).unwrap() == Confidence::Likely {
+///     println!("mystery.bin looks like a headerless Redshirt file");
+/// }
+/// ```
+pub fn is_probably_encoded_reader<R: Read>(src: R, sample_len: usize) -> io::Result<Confidence> {
+    let mut sample = Vec::new();
+    let limit = u64::try_from(sample_len).unwrap_or(u64::MAX);
+    let _ = src.take(limit).read_to_end(&mut sample)?;
+    Ok(is_probably_encoded(&sample))
+}
+
+/// Returns whether `byte` is printable ASCII or common whitespace.
+const fn is_text(byte: u8) -> bool {
+    matches!(byte, b' '..=b'~' | b'\t' | b'\n' | b'\r')
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    #[cfg(feature = "bytes")]
+    use super::{decode_from_buf, encode_into_buf};
+    use super::{is_probably_encoded, is_probably_encoded_reader, Confidence};
+    #[cfg(feature = "rayon")]
+    use super::{par_decode_in_place, par_encode_in_place, PAR_CHUNK_LEN, PAR_THRESHOLD};
+    #[cfg(any(feature = "rayon", feature = "bytes"))]
+    use crate::xor_bytes;
+    #[cfg(feature = "bytes")]
+    use bytes::{Buf, BufMut, Bytes, BytesMut};
+    #[cfg(feature = "rayon")]
+    use proptest::{collection::vec, num::u8, prop_assert_eq, proptest, test_runner::Config};
+    #[cfg(feature = "bytes")]
+    use std::collections::VecDeque;
+
+    #[cfg(feature = "bytes")]
+    /// A `Buf` made of separately allocated segments, as received from a network.
+    struct Segments(VecDeque<Bytes>);
+
+    #[cfg(feature = "bytes")]
+    impl Segments {
+        fn new(data: &[u8], len: usize) -> Self {
+            Self(
+                data.chunks(len)
+                    .map(|chunk| BytesMut::from(chunk).freeze())
+                    .collect(),
+            )
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    impl Buf for Segments {
+        fn remaining(&self) -> usize {
+            self.0.iter().map(Bytes::len).sum()
+        }
+
+        fn chunk(&self) -> &[u8] {
+            self.0.front().map_or(&[], |segment| segment)
+        }
+
+        fn advance(&mut self, mut cnt: usize) {
+            while cnt > 0 {
+                let front = self.0.front_mut().unwrap();
+                let len = cnt.min(front.len());
+                front.advance(len);
+                cnt -= len;
+                if front.is_empty() {
+                    let _ = self.0.pop_front();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn probably_encoded() {
+        let mut save = Vec::new();
+        for i in 0..32_i32 {
+            save.extend_from_slice(&i.to_le_bytes());
+            save.extend_from_slice(b"Agent\0");
+        }
+        let mut encoded = save.clone();
+        crate::xor_bytes(&mut encoded);
+        assert_eq!(is_probably_encoded(&encoded), Confidence::Likely);
+        assert_eq!(is_probably_encoded(&save), Confidence::Unlikely);
+        assert_eq!(is_probably_encoded(&encoded[..16]), Confidence::Possible);
+        assert_eq!(is_probably_encoded(b""), Confidence::Unlikely);
+        assert_eq!(is_probably_encoded(&[0xFF; 256]), Confidence::Possible);
+        let noise = (0..=255).cycle().take(1024).collect::<Vec<u8>>();
+        assert!(is_probably_encoded(&noise) < Confidence::Likely);
+        let mut src = &encoded[..];
+        assert_eq!(
+            is_probably_encoded_reader(&mut src, 100).unwrap(),
+            Confidence::Likely
+        );
+        assert_eq!(src.len(), encoded.len() - 100);
+    }
+
+    #[cfg(feature = "rayon")]
+    proptest! {
+        #![proptest_config(Config::with_cases(64))]
+
+        #[test]
+        fn par_matches_serial(data in vec(u8::ANY, 0..PAR_CHUNK_LEN * 4)) {
+            let mut serial = data.clone();
+            xor_bytes(&mut serial);
+            let mut parallel = data.clone();
+            par_encode_in_place(&mut parallel);
+            prop_assert_eq!(&parallel, &serial);
+            par_decode_in_place(&mut parallel);
+            prop_assert_eq!(parallel, data);
+        }
+    }
+
+    #[cfg(all(feature = "rayon", feature = "redshirt1"))]
+    #[test]
+    fn v1_above_threshold() {
+        let data: Vec<u8> = (0..=255).cycle().take(PAR_THRESHOLD + 3).collect();
+        let encoded = crate::v1::encode(&data);
+        assert!(encoded[crate::v1::MARKER_LEN..]
+            .iter()
+            .zip(&data)
+            .all(|(e, d)| *e == d ^ 0x80));
+        assert_eq!(crate::v1::decode(&encoded).unwrap(), data);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn segmented_bufs() {
+        let data: Vec<u8> = (0..=255).cycle().take(40_000).collect();
+        let mut encoded = data.clone();
+        xor_bytes(&mut encoded);
+        let mut src = Segments::new(&data, 7);
+        let mut dst = BytesMut::new();
+        assert_eq!(encode_into_buf(&mut src, &mut dst), data.len());
+        assert!(!src.has_remaining());
+        assert_eq!(dst, encoded);
+        let mut src = Segments::new(&encoded, 5000);
+        let mut left = vec![0; 100];
+        let mut right = vec![0; data.len()];
+        let mut dst = (&mut left[..]).chain_mut(&mut right[..]);
+        assert_eq!(decode_from_buf(&mut src, &mut dst), data.len());
+        assert_eq!(left, data[..100]);
+        assert_eq!(right[..data.len() - 100], data[100..]);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn full_dst() {
+        let mut src = Segments::new(b"Hello world!", 3);
+        let mut buffer = [0; 5];
+        assert_eq!(decode_from_buf(&mut src, &mut &mut buffer[..]), 5);
+        assert_eq!(src.remaining(), 7);
+        assert_eq!(&buffer, b"\xC8\xE5\xEC\xEC\xEF");
+    }
+}
@@ -0,0 +1,263 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{
+    cursor::{self, overflow_error, read_up_to, Scratch},
+    error::Error,
+    v1, v2,
+    version::Version,
+};
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// The result of comparing the decoded contents of two Redshirt streams.
+pub enum Comparison {
+    /// The decoded contents are identical.
+    Identical,
+    /// The decoded contents first differ at the given offset.
+    DifferAt {
+        /// The offset of the first differing byte, from the start of the decoded data.
+        offset: u64,
+    },
+    /// One stream's decoded contents are a prefix of the other's.
+    LengthMismatch {
+        /// The length of the first stream's decoded contents.
+        a: u64,
+        /// The length of the second stream's decoded contents.
+        b: u64,
+    },
+}
+
+/// A reader for either Redshirt encoding scheme.
+enum Decoder<R: Read + Seek> {
+    V1(v1::Reader<R>),
+    V2(Box<v2::Reader<R>>),
+}
+
+/// Decoded data read from one of the two streams being compared.
+struct Side<R: Read + Seek> {
+    decoder: Decoder<R>,
+    buffer: Scratch,
+    start: usize,
+    end: usize,
+}
+
+#[inline]
+/// Compares the decoded contents of two Redshirt streams, which may use different encoding
+/// schemes.
+///
+/// Both streams are read from their current positions, and decoded in lockstep a chunk at a time,
+/// so neither is read into memory in full. The SHA-1 hash of a Redshirt 2 stream is verified
+/// before it is compared.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, either stream does not begin with a valid Redshirt 1
+/// or Redshirt 2 header, or the SHA-1 hash of a Redshirt 2 stream is invalid.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::{v1, v2, Comparison};
+/// use std::io::Cursor;
+///
+/// let a = Cursor::new(v1::encode(b"foobar"));
+/// let b = Cursor::new(v2::encode(b"foobaz"));
+/// let comparison = redshirt::compare_decoded(a, b).unwrap();
+/// assert_eq!(comparison, Comparison::DifferAt { offset: 5 });
+/// ```
+pub fn compare_decoded<A, B>(a: A, b: B) -> Result<Comparison, Error>
+where
+    A: Read + Seek,
+    B: Read + Seek,
+{
+    compare_sides(&mut Side::new(a)?, &mut Side::new(b)?)
+}
+
+#[inline]
+/// Compares the decoded contents of the Redshirt files at `a` and `b`.
+///
+/// See `compare_decoded` for details.
+///
+/// # Errors
+///
+/// Returns an `Err` if either file cannot be opened, or `compare_decoded` fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::Comparison;
+///
+/// let comparison = redshirt::compare_paths("User.usr", "User.usr.bak").unwrap();
+/// assert_eq!(comparison, Comparison::Identical);
+/// ```
+pub fn compare_paths<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q) -> Result<Comparison, Error> {
+    let a = File::open(a).map_err(Error::Io)?;
+    let b = File::open(b).map_err(Error::Io)?;
+    compare_decoded(a, b)
+}
+
+impl<R: Read + Seek> Side<R> {
+    fn new(mut src: R) -> Result<Self, Error> {
+        let start = src.stream_position().map_err(Error::Io)?;
+        let mut marker = array!(v1::MARKER_LEN);
+        let len = read_up_to(&mut src, &mut marker).map_err(Error::Io)?;
+        let _ = src.seek(SeekFrom::Start(start)).map_err(Error::Io)?;
+        let decoder = match Version::detect(&marker[..len]) {
+            Some(Version::V1) => Decoder::V1(v1::Reader::new(src)?),
+            Some(Version::V2) => Decoder::V2(Box::new(v2::Reader::new(src)?)),
+            None => return Err(Error::BadHeader),
+        };
+        Ok(Self {
+            decoder,
+            buffer: Scratch::new(),
+            start: 0,
+            end: 0,
+        })
+    }
+
+    /// Returns the decoded data that has not yet been compared, reading more if none is left. An
+    /// empty slice indicates the end of the stream.
+    fn fill(&mut self) -> Result<&[u8], Error> {
+        if self.start == self.end {
+            self.start = 0;
+            self.end = read_up_to(&mut self.decoder, &mut self.buffer).map_err(Error::Io)?;
+        }
+        Ok(&self.buffer[self.start..self.end])
+    }
+
+    /// Returns `offset` plus the length of the decoded data that has not yet been compared,
+    /// reading it to the end.
+    fn count_rest(&mut self, offset: u64) -> Result<u64, Error> {
+        let offset = cursor::add_len(offset, self.end - self.start).map_err(Error::Io)?;
+        io::copy(&mut self.decoder, &mut io::sink())
+            .and_then(|len| offset.checked_add(len).ok_or_else(overflow_error))
+            .map_err(Error::Io)
+    }
+}
+
+impl<R: Read + Seek> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Decoder::V1(reader) => reader.read(buf),
+            Decoder::V2(reader) => reader.read(buf),
+        }
+    }
+}
+
+fn compare_sides<A, B>(a: &mut Side<A>, b: &mut Side<B>) -> Result<Comparison, Error>
+where
+    A: Read + Seek,
+    B: Read + Seek,
+{
+    let mut offset = 0;
+    loop {
+        let (chunk_a, chunk_b) = (a.fill()?, b.fill()?);
+        let len = chunk_a.len().min(chunk_b.len());
+        if let Some(i) = chunk_a[..len]
+            .iter()
+            .zip(&chunk_b[..len])
+            .position(|(x, y)| x != y)
+        {
+            return Ok(Comparison::DifferAt {
+                offset: cursor::add_len(offset, i).map_err(Error::Io)?,
+            });
+        }
+        if len == 0 {
+            return if chunk_a.is_empty() && chunk_b.is_empty() {
+                Ok(Comparison::Identical)
+            } else {
+                Ok(Comparison::LengthMismatch {
+                    a: a.count_rest(offset)?,
+                    b: b.count_rest(offset)?,
+                })
+            };
+        }
+        a.start += len;
+        b.start += len;
+        offset = cursor::add_len(offset, len).map_err(Error::Io)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_decoded, compare_paths, Comparison};
+    use crate::{cursor::BUFFER_LEN, error::Error, testdata, v1, v2};
+    use std::{env, fs, io::Cursor, process};
+
+    fn compare(a: &[u8], b: &[u8]) -> Result<Comparison, Error> {
+        compare_decoded(Cursor::new(a), Cursor::new(b))
+    }
+
+    #[test]
+    fn compare_across_versions() {
+        let data = vec![0x42; BUFFER_LEN * 2 + 7];
+        assert_eq!(
+            compare(&v1::encode(&data), &v2::encode(&data)).unwrap(),
+            Comparison::Identical
+        );
+        let mut other = data.clone();
+        other[BUFFER_LEN + 3] = 0;
+        assert_eq!(
+            compare(&v2::encode(&data), &v1::encode(&other)).unwrap(),
+            Comparison::DifferAt {
+                offset: BUFFER_LEN as u64 + 3
+            }
+        );
+        assert_eq!(
+            compare(&v1::encode(b""), &v2::encode(b"")).unwrap(),
+            Comparison::Identical
+        );
+    }
+
+    #[test]
+    fn compare_lengths() {
+        let data = vec![0x42; BUFFER_LEN * 3];
+        assert_eq!(
+            compare(&v1::encode(&data[..BUFFER_LEN]), &v2::encode(&data)).unwrap(),
+            Comparison::LengthMismatch {
+                a: BUFFER_LEN as u64,
+                b: BUFFER_LEN as u64 * 3
+            }
+        );
+        assert_eq!(
+            compare(&v2::encode(&data), &v2::encode(b"")).unwrap(),
+            Comparison::LengthMismatch {
+                a: BUFFER_LEN as u64 * 3,
+                b: 0
+            }
+        );
+    }
+
+    #[test]
+    fn compare_errors() {
+        assert!(matches!(
+            compare(b"Hello world!", &v1::encode(b"Hello world!")),
+            Err(Error::BadHeader)
+        ));
+        let corrupt = testdata::flip_digest_bit(b"Hello world!", 0).data;
+        assert!(matches!(
+            compare(&v1::encode(b"Hello world!"), &corrupt),
+            Err(Error::BadChecksum { .. })
+        ));
+    }
+
+    #[test]
+    fn compare_files() {
+        let a = env::temp_dir().join(format!("redshirt-compare-{}-a", process::id()));
+        let b = env::temp_dir().join(format!("redshirt-compare-{}-b", process::id()));
+        fs::write(&a, v1::encode(b"Hello world!")).unwrap();
+        fs::write(&b, v2::encode(b"Hello world!")).unwrap();
+        assert_eq!(compare_paths(&a, &b).unwrap(), Comparison::Identical);
+        fs::remove_file(a).unwrap();
+        fs::remove_file(b).unwrap();
+    }
+}
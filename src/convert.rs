@@ -0,0 +1,315 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{error::Error, v1, v2, version::Version};
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+    process,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The action taken by `ensure_decoded` or `ensure_encoded`.
+pub enum Conversion {
+    /// The file was already in the requested state, and was left unchanged.
+    Unchanged,
+    /// The file was decoded from the given encoding scheme.
+    Decoded(Version),
+    /// The file was not Redshirt-encoded, and was encoded with the given encoding scheme.
+    Encoded(Version),
+    /// The file was decoded from one encoding scheme and encoded with another.
+    Reencoded {
+        /// The encoding scheme the file was decoded from.
+        from: Version,
+        /// The encoding scheme the file was encoded with.
+        to: Version,
+    },
+}
+
+#[inline]
+/// Returns the encoding scheme of the file at `path`, or `None` if it is not Redshirt-encoded.
+///
+/// Only the marker at the start of the file is read; the rest of the header and the encoded data
+/// are not checked. An empty file is not Redshirt-encoded.
+///
+/// # Errors
+///
+/// Returns an `Err` if the file cannot be opened or read.
+///
+/// # Examples
+///
+/// ```no_run
+/// if let Some(version) = redshirt::is_encoded("User.usr").unwrap() {
+///     println!("encoded with {version}");
+/// }
+/// ```
+pub fn is_encoded<P: AsRef<Path>>(path: P) -> io::Result<Option<Version>> {
+    let mut marker = Vec::with_capacity(v1::MARKER_LEN);
+    let _ = File::open(path)?
+        .take(v1::MARKER_LEN as u64)
+        .read_to_end(&mut marker)?;
+    Ok(Version::detect(&marker))
+}
+
+#[inline]
+/// Decodes the file at `path` in place, unless it is not Redshirt-encoded.
+///
+/// The decoded data is written to a temporary file in the same directory, which then replaces the
+/// original, so the file is never left partially decoded.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, or the file begins with a Redshirt marker but cannot
+/// be decoded.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::Conversion;
+///
+/// match redshirt::ensure_decoded("User.usr").unwrap() {
+///     Conversion::Unchanged => println!("already plain"),
+///     action => println!("{action:?}"),
+/// }
+/// ```
+pub fn ensure_decoded<P: AsRef<Path>>(path: P) -> Result<Conversion, Error> {
+    let path = path.as_ref();
+    let data = fs::read(path).map_err(Error::Io)?;
+    let Some(version) = Version::detect(&data) else {
+        return Ok(Conversion::Unchanged);
+    };
+    replace(path, &decode(version, &data)?).map(|()| Conversion::Decoded(version))
+}
+
+#[inline]
+/// Encodes the file at `path` in place using the given encoding scheme, unless it is already
+/// encoded with that scheme.
+///
+/// A file encoded with the other scheme is decoded and re-encoded. The encoded data is written to
+/// a temporary file in the same directory, which then replaces the original, so the file is never
+/// left partially encoded.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, or the file must be re-encoded but cannot be decoded.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::{Conversion, Version};
+///
+/// let action = redshirt::ensure_encoded("User.usr", Version::V2).unwrap();
+/// assert_ne!(action, Conversion::Decoded(Version::V2));
+/// ```
+pub fn ensure_encoded<P: AsRef<Path>>(path: P, version: Version) -> Result<Conversion, Error> {
+    let path = path.as_ref();
+    let data = fs::read(path).map_err(Error::Io)?;
+    let (plain, action) = match Version::detect(&data) {
+        Some(from) if from == version => return Ok(Conversion::Unchanged),
+        Some(from) => (
+            decode(from, &data)?,
+            Conversion::Reencoded { from, to: version },
+        ),
+        None => (data, Conversion::Encoded(version)),
+    };
+    replace(path, &encode(version, &plain)).map(|()| action)
+}
+
+#[inline]
+/// Returns whether decoding `src` and encoding the result with the same scheme reproduces `src`
+/// exactly.
+///
+/// Encoding is deterministic: the output of `v1::encode` and `v2::encode`, and of the writers, is
+/// fully determined by the payload, with no timestamps, padding or other variable data, and the
+/// SHA-1 hash in a Redshirt 2 header is the hash of the encoded payload. So this returns `true`
+/// for every well-formed Redshirt file.
+///
+/// Files that can only be read leniently are not well-formed, and are reported as errors here.
+/// A Redshirt 2 file read with `v2::ReaderOptions::accept_unfinalized` or with verification
+/// skipped may have an all-zero or mismatched hash, which re-encoding replaces with the correct
+/// one, so the result is not byte-identical.
+///
+/// # Errors
+///
+/// Returns `Error::BadHeader` if `src` does not begin with a Redshirt marker, or any error that
+/// `v1::decode` or `v2::decode` would return.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v2;
+///
+/// let encoded = v2::encode(b"Hello world!");
+/// assert!(redshirt::roundtrip_identity(&encoded).unwrap());
+/// ```
+pub fn roundtrip_identity(src: &[u8]) -> Result<bool, Error> {
+    let version = Version::detect(src).ok_or(Error::BadHeader)?;
+    let decoded = decode(version, src)?;
+    Ok(encode(version, &decoded) == src)
+}
+
+/// Decodes a whole Redshirt file of the given version.
+pub(crate) fn decode(version: Version, data: &[u8]) -> Result<Vec<u8>, Error> {
+    match version {
+        Version::V1 => v1::decode(data),
+        Version::V2 => v2::decode(data),
+    }
+}
+
+/// Encodes a whole payload with the given version.
+pub(crate) fn encode(version: Version, data: &[u8]) -> Vec<u8> {
+    match version {
+        Version::V1 => v1::encode(data),
+        Version::V2 => v2::encode(data),
+    }
+}
+
+/// Atomically replaces the contents of the file at `path` with `data`, keeping its permissions. The
+/// file is created if it does not exist.
+pub(crate) fn replace(path: &Path, data: &[u8]) -> Result<(), Error> {
+    let permissions = match fs::metadata(path) {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(Error::Io(e)),
+    };
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.tmp", process::id()));
+    let temp = path.with_file_name(name);
+    let res = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()?;
+            if let Some(permissions) = permissions {
+                fs::set_permissions(&temp, permissions)?;
+            }
+            fs::rename(&temp, path)
+        });
+    if res.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    res.map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ensure_decoded, ensure_encoded, is_encoded, roundtrip_identity, Conversion};
+    use crate::{error::Error, testdata, v1, v2, version::Version};
+    use std::{env, fs, path::PathBuf, process};
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = env::temp_dir().join(format!("redshirt-convert-{}-{name}", process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn is_encoded_sniffs_marker() {
+        let path = temp_file("sniff", &v1::encode(MSG_DEC));
+        assert_eq!(is_encoded(&path).unwrap(), Some(Version::V1));
+        fs::write(&path, v2::encode(MSG_DEC)).unwrap();
+        assert_eq!(is_encoded(&path).unwrap(), Some(Version::V2));
+        fs::write(&path, MSG_DEC).unwrap();
+        assert_eq!(is_encoded(&path).unwrap(), None);
+        fs::write(&path, b"").unwrap();
+        assert_eq!(is_encoded(&path).unwrap(), None);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn ensure_decoded_plain_and_encoded() {
+        let path = temp_file("decode", MSG_DEC);
+        assert_eq!(ensure_decoded(&path).unwrap(), Conversion::Unchanged);
+        assert_eq!(fs::read(&path).unwrap(), MSG_DEC);
+        fs::write(&path, v2::encode(MSG_DEC)).unwrap();
+        assert_eq!(
+            ensure_decoded(&path).unwrap(),
+            Conversion::Decoded(Version::V2)
+        );
+        assert_eq!(fs::read(&path).unwrap(), MSG_DEC);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn ensure_encoded_plain_and_encoded() {
+        let path = temp_file("encode", MSG_DEC);
+        assert_eq!(
+            ensure_encoded(&path, Version::V1).unwrap(),
+            Conversion::Encoded(Version::V1)
+        );
+        assert_eq!(fs::read(&path).unwrap(), v1::encode(MSG_DEC));
+        assert_eq!(
+            ensure_encoded(&path, Version::V1).unwrap(),
+            Conversion::Unchanged
+        );
+        assert_eq!(
+            ensure_encoded(&path, Version::V2).unwrap(),
+            Conversion::Reencoded {
+                from: Version::V1,
+                to: Version::V2
+            }
+        );
+        assert_eq!(fs::read(&path).unwrap(), v2::encode(MSG_DEC));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn ensure_empty_file() {
+        let path = temp_file("empty", b"");
+        assert_eq!(ensure_decoded(&path).unwrap(), Conversion::Unchanged);
+        assert_eq!(fs::read(&path).unwrap(), b"");
+        assert_eq!(
+            ensure_encoded(&path, Version::V2).unwrap(),
+            Conversion::Encoded(Version::V2)
+        );
+        assert_eq!(fs::read(&path).unwrap(), v2::encode(b""));
+        assert_eq!(
+            ensure_decoded(&path).unwrap(),
+            Conversion::Decoded(Version::V2)
+        );
+        assert_eq!(fs::read(&path).unwrap(), b"");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn ensure_decoded_corrupt() {
+        let corrupt = testdata::flip_digest_bit(MSG_DEC, 0).data;
+        let path = temp_file("corrupt", &corrupt);
+        assert!(matches!(
+            ensure_decoded(&path),
+            Err(Error::BadChecksum { .. })
+        ));
+        assert_eq!(fs::read(&path).unwrap(), corrupt);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn roundtrip_identity_edge_cases() {
+        assert!(roundtrip_identity(&v1::MARKER).unwrap());
+        assert!(roundtrip_identity(&v2::encode(b"")).unwrap());
+        assert!(roundtrip_identity(b"REDSHIRT\x00\x00\xFF\x7F").unwrap());
+        assert!(matches!(roundtrip_identity(MSG_DEC), Err(Error::BadHeader)));
+        let corrupt = testdata::flip_digest_bit(MSG_DEC, 0).data;
+        assert!(matches!(
+            roundtrip_identity(&corrupt),
+            Err(Error::BadChecksum { .. })
+        ));
+        let mut unfinalized = v2::encode(MSG_DEC);
+        unfinalized[9..29].fill(0);
+        assert!(matches!(
+            roundtrip_identity(&unfinalized),
+            Err(Error::Unfinalized)
+        ));
+    }
+}
@@ -5,138 +5,529 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
 // modified, or distributed except according to those terms.
 
+#[cfg(redshirt_unstable)]
+use crate::read_buf;
+#[cfg(feature = "redshirt1")]
+use crate::resize::Resize;
 use crate::xor_bytes;
+#[cfg(redshirt_unstable)]
+use std::io::BorrowedCursor;
 use std::{
     convert::TryFrom,
+    fmt::{self, Debug, Formatter},
+    fs::File,
     io::{self, Read, Seek, SeekFrom, Write},
-    ops::Deref,
+    ops::{Deref, DerefMut},
 };
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, Zeroizing};
 
-const BUFFER_LEN: usize = 16384;
+pub(crate) const BUFFER_LEN: usize = 16384;
 
-#[derive(Debug)]
-pub(crate) struct Cursor<T> {
+// Buffer lengths are converted to `u64` positions and counts throughout, which is lossless only
+// where `usize` has at most 64 bits; this is true of every target Rust supports.
+const _: () = assert!(usize::BITS <= u64::BITS);
+
+/// An encoding or decoding position in an underlying stream.
+///
+/// Data written is encoded in a heap buffer if `BUF` is `0`, or otherwise in an inline buffer of
+/// `BUF` bytes.
+pub(crate) struct Cursor<T, const BUF: usize = 0> {
     inner: T,
     base: Option<u64>,
     offset: u64,
+    /// The number of bytes read or written, regardless of seeking.
+    transferred: u64,
+    staging: Staging<BUF>,
+    peeked: Peeked,
+}
+
+/// The encoded bytes accepted by the underlying writer in a call to `Cursor::write_chunk`.
+///
+/// The chunk's bytes are scrubbed from the `Cursor`'s buffer when it is dropped.
+pub(crate) struct Chunk<'a> {
+    used: Staged<'a>,
+    len: usize,
 }
 
-pub(crate) struct Chunk {
-    bytes: [u8; BUFFER_LEN],
+/// A buffer reused to hold each chunk of data while it is encoded and written.
+///
+/// If `INLINE` is `0`, the buffer is allocated on the heap on first use; otherwise, it is an array
+/// of `INLINE` bytes held in the `Staging` itself.
+pub(crate) struct Staging<const INLINE: usize = 0> {
+    inline: [u8; INLINE],
+    buffer: Option<Box<[u8]>>,
     len: usize,
+    /// The number of encoded bytes at the start of the buffer that are waiting to be written.
+    pending: usize,
 }
 
+/// A chunk of encoded data held in a `Staging` buffer, which is scrubbed when dropped.
+pub(crate) struct Staged<'a>(&'a mut [u8]);
+
+/// A temporary buffer for data being encoded, decoded or copied, which is scrubbed when dropped.
+pub(crate) struct Scratch([u8; BUFFER_LEN]);
+
+/// A `Read` implementation that calls a closure, for passing a reader's encoded data to functions
+/// that take a `Read`.
+pub(crate) struct ReadFn<F>(pub(crate) F);
+
+/// Encoded bytes read from the underlying stream ahead of the position by `Cursor::peek_encoded`,
+/// which are scrubbed when dropped.
+///
+/// While any are held, the underlying stream is positioned after them rather than at the position.
+struct Peeked(Vec<u8>);
+
 impl<T> Cursor<T> {
     #[inline]
     pub(crate) const fn new(inner: T) -> Self {
+        Self::with_buffer_len(inner, BUFFER_LEN)
+    }
+
+    #[inline]
+    /// Creates a `Cursor` that encodes data written to it in chunks of up to `len` bytes.
+    pub(crate) const fn with_buffer_len(inner: T, len: usize) -> Self {
+        Self::with_staging(inner, Staging::new(len))
+    }
+}
+
+impl<T, const BUF: usize> Cursor<T, BUF> {
+    #[inline]
+    /// Creates a `Cursor` that encodes data written to it in chunks of up to `BUF` bytes, in an
+    /// inline buffer.
+    pub(crate) const fn with_inline_buffer(inner: T) -> Self {
+        Self::with_staging(inner, Staging::inline())
+    }
+
+    #[inline]
+    const fn with_staging(inner: T, staging: Staging<BUF>) -> Self {
         Self {
             inner,
             base: None,
             offset: 0,
+            transferred: 0,
+            staging,
+            peeked: Peeked(Vec::new()),
         }
     }
 
-    #[cfg(feature = "redshirt2")]
     #[inline]
     pub(crate) const fn offset(&self) -> u64 {
         self.offset
     }
 
+    #[inline]
+    pub(crate) const fn transferred(&self) -> u64 {
+        self.transferred
+    }
+
     #[cfg(feature = "redshirt2")]
     #[inline]
-    pub(crate) fn inner_mut(&mut self) -> &mut T {
-        &mut self.inner
+    /// Returns the length of the buffer in which data written is encoded.
+    pub(crate) const fn buffer_len(&self) -> usize {
+        self.staging.len
+    }
+
+    #[cfg(feature = "redshirt2")]
+    #[inline]
+    /// Returns the encoded data held by `coalesce` that has not been written yet.
+    pub(crate) fn pending(&self) -> &[u8] {
+        self.staging.pending()
+    }
+
+    #[cfg(feature = "redshirt2")]
+    #[inline]
+    /// Records `base` as the position of the start of the data in the underlying stream.
+    pub(crate) fn pinned_at(mut self, base: u64) -> Self {
+        self.base = Some(base);
+        self
     }
 
     #[inline]
     pub(crate) fn into_inner(self) -> T {
         self.inner
     }
+
+    #[inline]
+    pub(crate) const fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    #[inline]
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    #[cfg(feature = "redshirt2")]
+    #[inline]
+    /// Wraps the underlying stream with `f`, keeping the current position.
+    pub(crate) fn map_inner<U, F: FnOnce(T) -> U>(self, f: F) -> Cursor<U, BUF> {
+        Cursor {
+            inner: f(self.inner),
+            base: self.base,
+            offset: self.offset,
+            transferred: self.transferred,
+            staging: self.staging,
+            peeked: self.peeked,
+        }
+    }
+
+    #[inline]
+    /// Moves up to `buf.len()` peeked bytes into `buf`, advancing the position past them, and
+    /// returns how many were moved.
+    fn take_peeked(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.peeked.0.len());
+        buf[..len].copy_from_slice(&self.peeked.0[..len]);
+        advance(&mut self.offset, &mut self.transferred, len)?;
+        scrub(&mut self.peeked.0[..len]);
+        let _ = self.peeked.0.drain(..len);
+        Ok(len)
+    }
+
+    #[inline]
+    /// Discards any peeked bytes, once the underlying stream has been moved away from them.
+    pub(crate) fn clear_peeked(&mut self) {
+        scrub(&mut self.peeked.0);
+        self.peeked.0.clear();
+    }
 }
 
-impl<T: Write> Cursor<T> {
+impl Cursor<File> {
     #[inline]
-    pub(crate) fn write_chunk(&mut self, buf: &[u8]) -> io::Result<Chunk> {
-        let mut temp = array!(BUFFER_LEN);
-        if let Some(chunk) = buf.chunks(temp.len()).next() {
-            let used = &mut temp[..chunk.len()];
-            used.copy_from_slice(chunk);
-            xor_bytes(used);
-            self.inner.write(used).map(|len| {
-                self.offset += u64::try_from(len).unwrap();
-                Chunk::new(temp, len)
-            })
-        } else {
-            Ok(Chunk::new(temp, 0))
+    /// Duplicates the underlying file handle, returning a `Cursor` at the same position.
+    ///
+    /// The position of the encoded data in the file is recorded in both cursors, so that either
+    /// can restore its own position after the other has moved the shared file offset.
+    pub(crate) fn try_clone(&mut self) -> io::Result<Self> {
+        let base = self.pin_base()?;
+        if !self.peeked.0.is_empty() {
+            // The clone shares the file offset, so it must not be left past the position.
+            let _ = self.seek(SeekFrom::Start(self.offset))?;
         }
+        Ok(Self {
+            inner: self.inner.try_clone()?,
+            base: Some(base),
+            offset: self.offset,
+            transferred: self.transferred,
+            staging: Staging::new(self.staging.len),
+            peeked: Peeked(Vec::new()),
+        })
     }
 }
 
-impl<T: Read> Read for Cursor<T> {
+impl<T: Write, const BUF: usize> Cursor<T, BUF> {
     #[inline]
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        match self.inner.read(buf) {
-            Ok(len) => {
-                xor_bytes(&mut buf[..len]);
-                self.offset += u64::try_from(len).unwrap();
-                Ok(len)
+    pub(crate) fn write_chunk(&mut self, buf: &[u8]) -> io::Result<Chunk<'_>> {
+        let used = self.staging.encode(buf);
+        let len = write_checked(&mut self.inner, &used)?;
+        advance(&mut self.offset, &mut self.transferred, len)?;
+        Ok(Chunk { used, len })
+    }
+
+    #[cfg(feature = "redshirt2")]
+    #[inline]
+    /// Encodes `buf` into the staging buffer after any data already waiting there, to be written
+    /// by `write_pending`, if it fits, returning whether it did.
+    ///
+    /// The position advances as though `buf` had been written.
+    pub(crate) fn coalesce(&mut self, buf: &[u8]) -> io::Result<bool> {
+        if buf.len() > self.staging.len - self.staging.pending {
+            return Ok(false);
+        }
+        advance(&mut self.offset, &mut self.transferred, buf.len())?;
+        self.staging.push(buf);
+        Ok(true)
+    }
+
+    #[cfg(feature = "redshirt2")]
+    #[inline]
+    /// Writes the data waiting in the staging buffer, retrying interrupted writes, and passes each
+    /// part of it accepted by the underlying writer to `f`, in order.
+    ///
+    /// If a write fails, the data not yet accepted is kept, to be written by the next call.
+    pub(crate) fn write_pending<F: FnMut(&[u8])>(&mut self, mut f: F) -> io::Result<()> {
+        let mut written = 0;
+        let res = loop {
+            let pending = &self.staging.pending()[written..];
+            if pending.is_empty() {
+                break Ok(());
+            }
+            match write_checked(&mut self.inner, pending) {
+                Ok(0) => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write whole buffer",
+                    ))
+                }
+                Ok(len) => {
+                    f(&pending[..len]);
+                    written += len;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => break Err(e),
+            }
+        };
+        self.staging.consume(written);
+        res
+    }
+
+    #[inline]
+    /// Reads up to one chunk, and at most `max` bytes, from `src` into the staging buffer, then
+    /// encodes it if `encode` is set and writes all of it, retrying interrupted reads and writes.
+    ///
+    /// This saves copying the data from a caller's buffer. The chunk is empty at the end of `src`.
+    /// Data that `src` produces already encoded is copied as-is by passing `false`.
+    pub(crate) fn write_from<R: Read + ?Sized>(
+        &mut self,
+        src: &mut R,
+        max: usize,
+        encode: bool,
+    ) -> io::Result<Chunk<'_>> {
+        let used = self.staging.read_from(src, max, encode)?;
+        write_all(&mut self.inner, &used)?;
+        let len = used.len();
+        advance(&mut self.offset, &mut self.transferred, len)?;
+        Ok(Chunk { used, len })
+    }
+}
+
+impl<T: Read, const BUF: usize> Cursor<T, BUF> {
+    #[inline]
+    pub(crate) fn read_encoded(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.peeked.0.is_empty() {
+            return self.take_peeked(buf);
+        }
+        let len = read_checked(&mut self.inner, buf)?;
+        advance(&mut self.offset, &mut self.transferred, len).map(|()| len)
+    }
+
+    #[cfg(redshirt_unstable)]
+    #[inline]
+    /// Reads encoded data into the unfilled part of `cursor` without initialising it first,
+    /// passing the bytes read to `f`, which may modify them, before they are marked as filled.
+    pub(crate) fn read_encoded_buf<F: FnOnce(&mut [u8]) -> io::Result<()>>(
+        &mut self,
+        cursor: BorrowedCursor<'_>,
+        f: F,
+    ) -> io::Result<()> {
+        if !self.peeked.0.is_empty() {
+            return read_buf::fill_init(cursor, |buf| {
+                let len = self.take_peeked(buf)?;
+                f(&mut buf[..len]).map(|()| len)
+            });
+        }
+        let Self {
+            inner,
+            offset,
+            transferred,
+            ..
+        } = self;
+        read_buf::fill(
+            cursor,
+            |unfilled| inner.read_buf(unfilled),
+            |filled| {
+                advance(offset, transferred, filled.len())?;
+                f(filled)
+            },
+        )
+    }
+
+    /// Returns up to `len` encoded bytes from the position onwards without advancing the position,
+    /// reading from the underlying stream, and retrying interrupted reads, until there are `len`
+    /// bytes or it reaches its end.
+    ///
+    /// The bytes are returned again by later reads, until the cursor is seeked or resynced.
+    pub(crate) fn peek_encoded(&mut self, len: usize) -> io::Result<&[u8]> {
+        let peeked = &mut self.peeked.0;
+        while peeked.len() < len {
+            let filled = peeked.len();
+            peeked.resize(len, 0);
+            match read_checked(&mut self.inner, &mut peeked[filled..]) {
+                Ok(0) => {
+                    peeked.truncate(filled);
+                    break;
+                }
+                Ok(len) => peeked.truncate(filled + len),
+                Err(e) => {
+                    peeked.truncate(filled);
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                }
             }
-            Err(e) => Err(e),
         }
+        Ok(&peeked[..len.min(peeked.len())])
     }
 }
 
-impl<T: Seek> Seek for Cursor<T> {
+impl<T: Read, const BUF: usize> Read for Cursor<T, BUF> {
     #[inline]
-    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        #[inline]
-        fn overflow_error() -> io::Error {
-            io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "invalid seek to a negative or overflowing position",
-            )
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.read_encoded(buf)?;
+        xor_bytes(&mut buf[..len]);
+        Ok(len)
+    }
+
+    #[cfg(redshirt_unstable)]
+    #[inline]
+    fn read_buf(&mut self, cursor: BorrowedCursor<'_>) -> io::Result<()> {
+        self.read_encoded_buf(cursor, |filled| {
+            xor_bytes(filled);
+            Ok(())
+        })
+    }
+}
+
+impl<T: Seek> Cursor<T> {
+    #[cfg(feature = "redshirt2")]
+    #[inline]
+    /// Creates a `Cursor` whose data starts at the current position of `inner`.
+    ///
+    /// The position is recorded now, rather than by the first seek, so that seeking never
+    /// depends on where `inner` was left in the meantime.
+    pub(crate) fn new_seekable(mut inner: T) -> io::Result<Self> {
+        let base = inner.stream_position()?;
+        Ok(Self::new(inner).pinned_at(base))
+    }
+}
+
+impl<T: Seek, const BUF: usize> Cursor<T, BUF> {
+    #[inline]
+    /// Recomputes the position in the data from that of the underlying stream, after the stream
+    /// has been moved directly.
+    ///
+    /// This requires the position of the start of the data to have been recorded, either on
+    /// creation or by a previous seek.
+    pub(crate) fn resync(&mut self) -> io::Result<()> {
+        let base = self.base.ok_or_else(|| {
+            io::Error::other("the position of the data in the underlying stream is unknown")
+        })?;
+        self.offset = self
+            .inner
+            .stream_position()?
+            .checked_sub(base)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the underlying stream is positioned before the start of the data",
+                )
+            })?;
+        self.clear_peeked();
+        Ok(())
+    }
+
+    #[inline]
+    /// Returns the position of the start of the data in the underlying stream, recording it first
+    /// if it is not yet known.
+    pub(crate) fn pin_base(&mut self) -> io::Result<u64> {
+        if let Some(base) = self.base {
+            return Ok(base);
         }
+        // Only cursors over streams that need not be seekable are created without a base.
+        let ahead = add_len(self.offset, self.peeked.0.len())?;
+        let base = base_of(self.inner.stream_position()?, ahead)?;
+        Ok(*self.base.insert(base))
+    }
 
-        let base = if let Some(v) = self.base {
-            v
+    fn seek_to(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let base = self.pin_base()?;
+        let v = self.inner.seek(translate_seek(base, self.offset, pos)?)?;
+        self.clear_peeked();
+        if let Some(offset) = v.checked_sub(base) {
+            self.offset = offset;
+            Ok(offset)
         } else {
-            let v = self.inner.seek(SeekFrom::Current(0))? - self.offset;
-            self.base = Some(v);
-            v
+            if let Some(restore) = base.checked_add(self.offset) {
+                let _ = self.inner.seek(SeekFrom::Start(restore))?;
+            }
+            Err(overflow_error())
+        }
+    }
+
+    #[cfg(feature = "redshirt2")]
+    /// Seeks as `seek` does, except that a position before the start of the data is clamped to
+    /// the start rather than being an error.
+    ///
+    /// The clamped position is always reached by seeking from the start of the data, so the
+    /// offset and the position of the underlying stream stay paired as they do for `seek`.
+    pub(crate) fn seek_clamped(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let offset = match pos {
+            SeekFrom::Start(_) | SeekFrom::End(0..) => return self.seek(pos),
+            SeekFrom::Current(n) => match self.offset.checked_add_signed(n) {
+                Some(offset) => offset,
+                None if n < 0 => 0,
+                None => return self.seek(pos),
+            },
+            SeekFrom::End(n) => {
+                let base = self.pin_base()?;
+                let end = self.inner.seek(SeekFrom::End(0));
+                // The end is only measured, so move back to where the data says the stream is
+                // before reporting a failure.
+                let end = match end {
+                    Ok(end) => end,
+                    Err(e) => {
+                        self.clear_peeked();
+                        if let Some(restore) = base.checked_add(self.offset) {
+                            let _ = self.inner.seek(SeekFrom::Start(restore))?;
+                        }
+                        return Err(e);
+                    }
+                };
+                end.checked_sub(base)
+                    .and_then(|len| len.checked_add_signed(n))
+                    .unwrap_or(0)
+            }
         };
+        self.seek(SeekFrom::Start(offset))
+    }
+}
 
-        match pos {
-            SeekFrom::Start(n) => n
-                .checked_add(base)
-                .ok_or_else(overflow_error)
-                .and_then(|v| self.inner.seek(SeekFrom::Start(v))),
-            SeekFrom::Current(n) => {
-                let offset_big = i128::from(self.offset);
-                let n_big = i128::from(n);
-                let rel = offset_big + n_big;
-                if rel >= 0 {
-                    self.inner.seek(SeekFrom::Current(n))
-                } else {
-                    Err(overflow_error())
-                }
+#[cfg(feature = "redshirt1")]
+impl<T: Write + Seek + Resize, const BUF: usize> Cursor<T, BUF> {
+    /// Truncates or extends the data to `len` bytes, filling any bytes added with encoded zeroes,
+    /// and moves the position to the new end if it was past it.
+    ///
+    /// The underlying stream is left at the position in the data, as a seek would leave it.
+    pub(crate) fn set_len(&mut self, len: u64) -> io::Result<()> {
+        let base = self.pin_base()?;
+        let target = base.checked_add(len).ok_or_else(overflow_error)?;
+        let end = self.inner.seek(SeekFrom::End(0))?;
+        if let Some(mut remaining) = target.checked_sub(end) {
+            let mut padding = Scratch::new();
+            xor_bytes(&mut padding);
+            while remaining > 0 {
+                let n = usize::try_from(remaining).map_or(BUFFER_LEN, |n| n.min(BUFFER_LEN));
+                write_all(&mut self.inner, &padding[..n])?;
+                remaining -= len_u64(n);
             }
-            SeekFrom::End(n) => self.inner.seek(SeekFrom::End(n)).and_then(|v| {
-                if v >= base {
-                    Ok(v)
-                } else {
-                    let _ = self.inner.seek(SeekFrom::Start(self.offset)).unwrap();
-                    Err(overflow_error())
-                }
-            }),
+        } else {
+            self.inner.set_len(target)?;
         }
-        .map(|v| {
-            self.offset = v - base;
-            self.offset
-        })
+        self.offset = self.offset.min(len);
+        let _ = self.inner.seek(SeekFrom::Start(base + self.offset))?;
+        Ok(())
+    }
+}
+
+impl<T: Seek, const BUF: usize> Seek for Cursor<T, BUF> {
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let res = self.seek_to(pos);
+        #[cfg(feature = "tracing")]
+        if let Err(e) = &res {
+            tracing::debug!(
+                ?pos,
+                offset = self.offset,
+                underlying = self.base.and_then(|base| base.checked_add(self.offset)),
+                error = %e,
+                "seek failed",
+            );
+        }
+        res
     }
 }
 
-impl<T: Write> Write for Cursor<T> {
+impl<T: Write, const BUF: usize> Write for Cursor<T, BUF> {
     #[inline]
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.write_chunk(buf).map(|chunk| chunk.len())
@@ -148,21 +539,26 @@ impl<T: Write> Write for Cursor<T> {
     }
 }
 
-impl Chunk {
+impl<T: Debug, const BUF: usize> Debug for Cursor<T, BUF> {
     #[inline]
-    pub(self) const fn new(bytes: [u8; BUFFER_LEN], len: usize) -> Self {
-        Self { bytes, len }
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cursor")
+            .field("inner", &self.inner)
+            .field("base", &self.base)
+            .field("offset", &self.offset)
+            .field("transferred", &self.transferred)
+            .finish_non_exhaustive()
     }
 }
 
-impl AsRef<[u8]> for Chunk {
+impl AsRef<[u8]> for Chunk<'_> {
     #[inline]
     fn as_ref(&self) -> &[u8] {
-        &self.bytes[..self.len]
+        &self.used[..self.len]
     }
 }
 
-impl Deref for Chunk {
+impl Deref for Chunk<'_> {
     type Target = [u8];
 
     #[inline]
@@ -170,3 +566,735 @@ impl Deref for Chunk {
         self.as_ref()
     }
 }
+
+impl Staging {
+    #[inline]
+    /// Creates a buffer for chunks of up to `len` bytes, or one byte if `len` is `0`.
+    pub(crate) const fn new(len: usize) -> Self {
+        Self {
+            inline: [],
+            buffer: None,
+            len: if len == 0 { 1 } else { len },
+            pending: 0,
+        }
+    }
+}
+
+impl<const INLINE: usize> Staging<INLINE> {
+    #[inline]
+    /// Creates an inline buffer for chunks of up to `INLINE` bytes, or a heap buffer of the default
+    /// length if `INLINE` is `0`.
+    pub(crate) const fn inline() -> Self {
+        Self {
+            inline: [0; INLINE],
+            buffer: None,
+            len: if INLINE == 0 { BUFFER_LEN } else { INLINE },
+            pending: 0,
+        }
+    }
+
+    #[inline]
+    /// Copies up to one chunk of `buf` into the buffer and encodes it.
+    pub(crate) fn encode(&mut self, buf: &[u8]) -> Staged<'_> {
+        debug_assert_eq!(self.pending, 0);
+        let used_len = buf.len().min(self.len);
+        let used = if used_len == 0 {
+            &mut []
+        } else {
+            &mut self.buffer()[..used_len]
+        };
+        used.copy_from_slice(&buf[..used_len]);
+        xor_bytes(used);
+        Staged(used)
+    }
+
+    #[inline]
+    /// Reads up to one chunk, and at most `max` bytes, from `src` directly into the buffer and
+    /// encodes it if `encode` is set, retrying interrupted reads. The chunk is empty at the end of
+    /// `src`.
+    pub(crate) fn read_from<R: Read + ?Sized>(
+        &mut self,
+        src: &mut R,
+        max: usize,
+        encode: bool,
+    ) -> io::Result<Staged<'_>> {
+        debug_assert_eq!(self.pending, 0);
+        let len = max.min(self.len);
+        let buffer = &mut self.buffer()[..len];
+        let len = loop {
+            match read_checked(src, buffer) {
+                Ok(len) => break len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    scrub(buffer);
+                    return Err(e);
+                }
+            }
+        };
+        let used = &mut buffer[..len];
+        if encode {
+            xor_bytes(used);
+        }
+        Ok(Staged(used))
+    }
+
+    #[cfg(feature = "redshirt2")]
+    #[inline]
+    /// Returns the encoded data waiting to be written.
+    pub(crate) fn pending(&self) -> &[u8] {
+        let buffer = if INLINE == 0 {
+            self.buffer.as_deref().unwrap_or_default()
+        } else {
+            &self.inline
+        };
+        &buffer[..self.pending]
+    }
+
+    #[cfg(feature = "redshirt2")]
+    /// Copies `buf`, which must fit, into the buffer after the data waiting to be written, and
+    /// encodes it.
+    fn push(&mut self, buf: &[u8]) {
+        if !buf.is_empty() {
+            let start = self.pending;
+            let used = &mut self.buffer()[start..start + buf.len()];
+            used.copy_from_slice(buf);
+            xor_bytes(used);
+            self.pending += buf.len();
+        }
+    }
+
+    #[cfg(feature = "redshirt2")]
+    /// Discards the first `len` bytes of the data waiting to be written, scrubbing them.
+    fn consume(&mut self, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let pending = self.pending;
+        let buffer = self.buffer();
+        buffer.copy_within(len..pending, 0);
+        scrub(&mut buffer[pending - len..pending]);
+        self.pending -= len;
+    }
+
+    /// Returns the buffer, allocating it if it is on the heap and this is its first use.
+    fn buffer(&mut self) -> &mut [u8] {
+        if INLINE != 0 {
+            return &mut self.inline;
+        }
+        let len = self.len;
+        self.buffer
+            .get_or_insert_with(|| vec![0; len].into_boxed_slice())
+    }
+}
+
+impl<const INLINE: usize> Debug for Staging<INLINE> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Staging")
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Deref for Staged<'_> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl Drop for Staged<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        scrub(self.0);
+    }
+}
+
+impl Scratch {
+    #[inline]
+    pub(crate) const fn new() -> Self {
+        Self([0; BUFFER_LEN])
+    }
+}
+
+impl Deref for Scratch {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Scratch {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for Scratch {
+    #[inline]
+    fn drop(&mut self) {
+        scrub(&mut self.0);
+    }
+}
+
+impl<F: FnMut(&mut [u8]) -> io::Result<usize>> Read for ReadFn<F> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (self.0)(buf)
+    }
+}
+
+impl Drop for Peeked {
+    #[inline]
+    fn drop(&mut self) {
+        scrub(&mut self.0);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+#[inline]
+/// Overwrites `bytes` with zeroes, in a way that the compiler will not optimise away.
+pub(crate) fn scrub(bytes: &mut [u8]) {
+    bytes.zeroize();
+}
+
+#[cfg(not(feature = "zeroize"))]
+#[inline]
+/// Does nothing, as the `zeroize` feature is disabled.
+pub(crate) fn scrub(_: &mut [u8]) {}
+
+#[cfg(feature = "zeroize")]
+#[inline]
+/// Reads `src` to the end into a buffer that is scrubbed when dropped.
+///
+/// The buffer is grown by copying into a new buffer and scrubbing the old one, so that no copy of
+/// the data is left behind in freed memory.
+pub(crate) fn read_zeroizing<R: Read>(mut src: R) -> io::Result<Zeroizing<Vec<u8>>> {
+    let mut data = Zeroizing::new(Vec::with_capacity(BUFFER_LEN));
+    loop {
+        if data.len() == data.capacity() {
+            let mut grown = Zeroizing::new(Vec::with_capacity(data.capacity() * 2));
+            grown.extend_from_slice(&data);
+            data = grown;
+        }
+        let len = data.len();
+        let capacity = data.capacity();
+        data.resize(capacity, 0);
+        match read_checked(&mut src, &mut data[len..]) {
+            Ok(0) => {
+                data.truncate(len);
+                break Ok(data);
+            }
+            Ok(read) => data.truncate(len + read),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => data.truncate(len),
+            Err(e) => break Err(e),
+        }
+    }
+}
+
+#[inline]
+/// Reads `src` to the end, or until more than `max` bytes have been read, reserving space for
+/// `expected` bytes up front.
+///
+/// Memory is reserved with `Vec::try_reserve`, so an allocation failure is returned as an error of
+/// kind `OutOfMemory` instead of aborting. At most `max + 1` bytes are read, so the caller can
+/// tell whether the data is longer than `max` from the length of the result.
+pub(crate) fn read_capped<R: Read>(mut src: R, expected: u64, max: u64) -> io::Result<Vec<u8>> {
+    let to_usize = |n: u64| usize::try_from(n).unwrap_or(usize::MAX);
+    let cap = max.saturating_add(1);
+    let mut data = Vec::new();
+    reserve(&mut data, to_usize(expected.min(cap)))?;
+    loop {
+        let len = data.len();
+        let left = to_usize(cap - len_u64(len));
+        if len == data.capacity() {
+            reserve(&mut data, len.max(BUFFER_LEN).min(left))?;
+        }
+        let end = data.capacity().min(len.saturating_add(left));
+        data.resize(end, 0);
+        match read_checked(&mut src, &mut data[len..]) {
+            Ok(0) => {
+                data.truncate(len);
+                break Ok(data);
+            }
+            Ok(read) if len_u64(len + read) > max => {
+                data.truncate(len + read);
+                break Ok(data);
+            }
+            Ok(read) => data.truncate(len + read),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => data.truncate(len),
+            Err(e) => break Err(e),
+        }
+    }
+}
+
+/// Reserves space for at least `additional` more bytes in `data`, returning an error of kind
+/// `OutOfMemory` if the allocation fails.
+fn reserve(data: &mut Vec<u8>, additional: usize) -> io::Result<()> {
+    data.try_reserve(additional)
+        .map_err(|e| io::Error::new(io::ErrorKind::OutOfMemory, e))
+}
+
+#[inline]
+/// Copies `src` to `dst` in chunks of up to `BUFFER_LEN` bytes, retrying interrupted reads and
+/// writes, and returns the number of bytes copied.
+pub(crate) fn pump<R: Read + ?Sized, W: Write + ?Sized>(
+    src: &mut R,
+    dst: &mut W,
+) -> io::Result<u64> {
+    let mut buffer = Scratch::new();
+    let mut total = 0;
+    loop {
+        let len = match read_checked(src, &mut buffer) {
+            Ok(0) => break Ok(total),
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => break Err(e),
+        };
+        write_all(dst, &buffer[..len])?;
+        total = add_len(total, len)?;
+    }
+}
+
+#[inline]
+/// Calls `step` until it returns `0`, and returns the sum of the values it returned.
+pub(crate) fn pump_with<F>(mut step: F) -> io::Result<u64>
+where
+    F: FnMut() -> io::Result<usize>,
+{
+    let mut total = 0;
+    loop {
+        match step()? {
+            0 => return Ok(total),
+            len => total = add_len(total, len)?,
+        }
+    }
+}
+
+#[inline]
+/// Reads and discards up to `n` bytes using `read`, retrying interrupted reads, and returns the
+/// number of bytes discarded, which is less than `n` only if the end of the stream was reached.
+pub(crate) fn skip<F>(n: u64, mut read: F) -> io::Result<u64>
+where
+    F: FnMut(&mut [u8]) -> io::Result<usize>,
+{
+    let mut buffer = Scratch::new();
+    let mut skipped = 0;
+    while skipped < n {
+        let len = usize::try_from(n - skipped).map_or(BUFFER_LEN, |left| left.min(BUFFER_LEN));
+        match read(&mut buffer[..len]) {
+            Ok(0) => break,
+            Ok(len) => skipped = add_len(skipped, len)?,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(skipped)
+}
+
+#[inline]
+/// Checks the number of bytes that an underlying stream claims to have transferred from or to a
+/// buffer of `requested` bytes, returning an error if the stream claims to have transferred more.
+pub(crate) fn check_len(len: usize, requested: usize) -> io::Result<usize> {
+    if len <= requested {
+        Ok(len)
+    } else {
+        Err(io::Error::other(format!(
+            "underlying stream claimed to transfer {len} bytes, but only {requested} were requested"
+        )))
+    }
+}
+
+#[inline]
+/// Reads from `src` into `buf`, without trusting `src` to return a valid length.
+pub(crate) fn read_checked<R: Read + ?Sized>(src: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    src.read(buf).and_then(|len| check_len(len, buf.len()))
+}
+
+#[inline]
+/// Writes from `buf` to `dst`, without trusting `dst` to return a valid length.
+pub(crate) fn write_checked<W: Write + ?Sized>(dst: &mut W, buf: &[u8]) -> io::Result<usize> {
+    dst.write(buf).and_then(|len| check_len(len, buf.len()))
+}
+
+#[inline]
+/// Reads exactly enough bytes from `src` to fill `buf`, as `Read::read_exact` does, without
+/// trusting `src` to return a valid length.
+pub(crate) fn read_exact<R: Read + ?Sized>(src: &mut R, mut buf: &mut [u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match read_checked(src, buf) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ))
+            }
+            Ok(len) => buf = &mut buf[len..],
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(any(feature = "redshirt2", feature = "uplink"))]
+#[inline]
+/// Reads into `buf` until it is full or the end of the stream is reached, returning the number of
+/// bytes read.
+pub(crate) fn read_up_to<R: Read + ?Sized>(src: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match read_checked(src, &mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+#[inline]
+/// Writes the whole of `buf` to `dst`, as `Write::write_all` does, without trusting `dst` to return
+/// a valid length.
+pub(crate) fn write_all<W: Write + ?Sized>(dst: &mut W, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match write_checked(dst, buf) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
+            }
+            Ok(len) => buf = &buf[len..],
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+/// Converts a buffer length to a `u64`, which is lossless; see the assertion on `usize::BITS`.
+pub(crate) const fn len_u64(len: usize) -> u64 {
+    len as u64
+}
+
+#[inline]
+/// Adds a buffer length to a position or byte count, returning an error instead of overflowing.
+pub(crate) fn add_len(n: u64, len: usize) -> io::Result<u64> {
+    n.checked_add(len_u64(len)).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream position or length overflowed a u64",
+        )
+    })
+}
+
+/// Advances a `Cursor`'s position and transfer count by `len` bytes, changing neither if either
+/// would overflow.
+fn advance(offset: &mut u64, transferred: &mut u64, len: usize) -> io::Result<()> {
+    let new_offset = add_len(*offset, len)?;
+    *transferred = add_len(*transferred, len)?;
+    *offset = new_offset;
+    Ok(())
+}
+
+#[inline]
+/// Returns the position of the start of the encoded data in an underlying stream, given the
+/// stream's position and the position within the encoded data, or an error if the stream's
+/// position is before the start of the encoded data, as it might be if the stream misreports it.
+pub(crate) fn base_of(underlying: u64, offset: u64) -> io::Result<u64> {
+    underlying.checked_sub(offset).ok_or_else(overflow_error)
+}
+
+#[inline]
+pub(crate) fn overflow_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "invalid seek to a negative or overflowing position",
+    )
+}
+
+#[inline]
+/// Translates a seek relative to the start of the encoded data into a seek relative to the start
+/// of the underlying stream, given the position of the encoded data in the underlying stream
+/// (`base`) and the current position within the encoded data (`offset`).
+///
+/// Seeks relative to the current position are resolved against `offset` rather than the position
+/// of the underlying stream, so they remain correct if another handle to the same file has moved
+/// it. Seeks relative to the end of the stream are passed through unchanged; the caller must check
+/// that the resulting position does not precede `base`.
+pub(crate) fn translate_seek(base: u64, offset: u64, pos: SeekFrom) -> io::Result<SeekFrom> {
+    match pos {
+        SeekFrom::Start(n) => n
+            .checked_add(base)
+            .map(SeekFrom::Start)
+            .ok_or_else(overflow_error),
+        SeekFrom::Current(n) => offset
+            .checked_add_signed(n)
+            .and_then(|n| n.checked_add(base))
+            .map(SeekFrom::Start)
+            .ok_or_else(overflow_error),
+        SeekFrom::End(_) => Ok(pos),
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+/// A stream that is interrupted before every transfer, and then transfers at most three bytes,
+/// for testing short reads and writes.
+pub(crate) struct Trickle<T> {
+    inner: T,
+    interrupt: bool,
+}
+
+#[cfg(test)]
+impl<T> Trickle<T> {
+    pub(crate) const fn new(inner: T) -> Self {
+        Self {
+            inner,
+            interrupt: false,
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn interrupt(&mut self) -> io::Result<()> {
+        self.interrupt = !self.interrupt;
+        if self.interrupt {
+            Err(io::ErrorKind::Interrupted.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+impl<T: Read> Read for Trickle<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.interrupt()?;
+        let len = buf.len().min(3);
+        self.inner.read(&mut buf[..len])
+    }
+}
+
+#[cfg(test)]
+impl<T: Write> Write for Trickle<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.interrupt()?;
+        self.inner.write(&buf[..buf.len().min(3)])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+impl<T: Seek> Seek for Trickle<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+/// A stream that transfers bytes honestly until `honest` bytes have been transferred, and then
+/// claims to transfer one byte more than each buffer holds, for testing misbehaving streams.
+pub(crate) struct Overclaim<T> {
+    inner: T,
+    honest: usize,
+}
+
+#[cfg(test)]
+impl<T> Overclaim<T> {
+    pub(crate) const fn new(inner: T, honest: usize) -> Self {
+        Self { inner, honest }
+    }
+}
+
+#[cfg(test)]
+impl<T: Read> Read for Overclaim<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.honest == 0 {
+            let _ = self.inner.read(buf)?;
+            return Ok(buf.len() + 1);
+        }
+        let len = buf.len().min(self.honest);
+        let len = self.inner.read(&mut buf[..len])?;
+        self.honest -= len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+impl<T: Write> Write for Overclaim<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.honest == 0 {
+            let _ = self.inner.write(buf)?;
+            return Ok(buf.len() + 1);
+        }
+        let len = self.inner.write(&buf[..buf.len().min(self.honest)])?;
+        self.honest -= len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+impl<T: Seek> Seek for Overclaim<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_len, Cursor};
+    #[cfg(feature = "zeroize")]
+    use super::{read_zeroizing, Trickle, BUFFER_LEN};
+    #[cfg(feature = "zeroize")]
+    use std::io::Write;
+    use std::io::{self, Read, Seek, SeekFrom};
+
+    /// Reports whatever position it is told to, wherever it is asked to seek.
+    struct Lying {
+        pos: u64,
+    }
+
+    impl Read for Lying {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            buf.fill(0);
+            Ok(buf.len())
+        }
+    }
+
+    impl Seek for Lying {
+        fn seek(&mut self, _: SeekFrom) -> io::Result<u64> {
+            Ok(self.pos)
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    /// Accepts up to four bytes in the first write, then fails.
+    struct Failing(Vec<u8>);
+
+    #[cfg(feature = "zeroize")]
+    impl Write for Failing {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                let len = buf.len().min(4);
+                self.0.extend_from_slice(&buf[..len]);
+                Ok(len)
+            } else {
+                Err(io::Error::other("full"))
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn write_chunk_scrubs() {
+        let mut cursor = Cursor::new(Failing(Vec::new()));
+        assert_eq!(
+            &cursor.write_chunk(b"secret").unwrap()[..],
+            b"\xF3\xE5\xE3\xF2"
+        );
+        let staged = cursor.staging.buffer.as_deref().unwrap();
+        assert_eq!(staged.len(), BUFFER_LEN);
+        assert!(staged.iter().all(|&b| b == 0));
+        assert!(cursor.write_chunk(b"secret").is_err());
+        assert!(cursor
+            .staging
+            .buffer
+            .iter()
+            .flat_map(|b| b.iter())
+            .all(|&b| b == 0));
+    }
+
+    #[test]
+    fn write_chunk_buffer_len() {
+        let mut cursor = Cursor::with_buffer_len(Vec::new(), 4);
+        assert_eq!(cursor.write_chunk(b"secret").unwrap().len(), 4);
+        assert_eq!(cursor.write_chunk(b"et").unwrap().len(), 2);
+        let buffer = cursor.staging.buffer.as_deref().unwrap();
+        assert_eq!(buffer.len(), 4);
+        assert_eq!(cursor.into_inner(), b"\xF3\xE5\xE3\xF2\xE5\xF4");
+        assert_eq!(Cursor::with_buffer_len((), 0).staging.len, 1);
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn read_zeroizing_grows() {
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let decoded = read_zeroizing(Trickle::new(&data[..])).unwrap();
+        assert_eq!(&decoded[..], &data[..]);
+    }
+
+    #[test]
+    fn seek_overflow() {
+        let mut inner = io::Cursor::new(vec![0; 8]);
+        inner.set_position(2);
+        let mut cursor = Cursor::new(inner);
+        assert_eq!(cursor.seek(SeekFrom::Start(4)).unwrap(), 4);
+        assert!(cursor.seek(SeekFrom::Start(u64::MAX)).is_err());
+        assert!(cursor.seek(SeekFrom::Current(i64::MIN)).is_err());
+        assert_eq!(cursor.stream_position().unwrap(), 4);
+    }
+
+    #[cfg(feature = "redshirt2")]
+    #[test]
+    fn seek_clamped() {
+        let mut inner = io::Cursor::new(vec![0; 8]);
+        inner.set_position(2);
+        let mut cursor = Cursor::new_seekable(inner).unwrap();
+        assert_eq!(cursor.seek_clamped(SeekFrom::Start(4)).unwrap(), 4);
+        assert_eq!(cursor.seek_clamped(SeekFrom::Current(-1)).unwrap(), 3);
+        assert_eq!(cursor.seek_clamped(SeekFrom::Current(-4)).unwrap(), 0);
+        assert_eq!(cursor.get_ref().position(), 2);
+        assert_eq!(cursor.seek_clamped(SeekFrom::End(-1)).unwrap(), 5);
+        // Past the start of the data, and past the start of the underlying stream.
+        for n in [-7, -9, i64::MIN] {
+            assert_eq!(cursor.seek_clamped(SeekFrom::End(n)).unwrap(), 0);
+            assert_eq!((cursor.offset(), cursor.get_ref().position()), (0, 2));
+        }
+        assert!(cursor.seek_clamped(SeekFrom::Start(u64::MAX)).is_err());
+        assert_eq!((cursor.offset(), cursor.get_ref().position()), (0, 2));
+    }
+
+    #[test]
+    fn seek_misreported_position() {
+        let mut cursor = Cursor::new(Lying { pos: 0 });
+        assert_eq!(cursor.read(&mut [0; 4]).unwrap(), 4);
+        // The stream claims to be before the data that was just read from it.
+        assert!(cursor.seek(SeekFrom::Start(0)).is_err());
+        cursor.get_mut().pos = 8;
+        assert_eq!(cursor.seek(SeekFrom::End(0)).unwrap(), 4);
+        cursor.get_mut().pos = u64::MAX;
+        assert_eq!(cursor.seek(SeekFrom::End(0)).unwrap(), u64::MAX - 4);
+        assert_eq!(cursor.read(&mut [0; 3]).unwrap(), 3);
+        assert!(cursor.read(&mut [0; 2]).is_err());
+        assert_eq!(cursor.offset, u64::MAX - 1);
+    }
+
+    #[test]
+    fn add_len_overflow() {
+        assert_eq!(add_len(u64::MAX - 1, 1).unwrap(), u64::MAX);
+        assert!(add_len(u64::MAX, 1).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+#[cfg(feature = "serde")]
+use serde::{
+    de::{self, Unexpected, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::fmt::{self, Display, Formatter, LowerHex, UpperHex};
+
+pub(crate) const DIGEST_LEN: usize = 20;
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+/// A SHA-1 hash, in the byte order used by Redshirt 2 headers.
+pub struct Digest([u8; DIGEST_LEN]);
+
+impl Digest {
+    #[inline]
+    #[must_use]
+    /// Creates a new `Digest` from its raw bytes.
+    pub const fn from_bytes(bytes: [u8; DIGEST_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the raw bytes of this `Digest`.
+    pub const fn to_bytes(self) -> [u8; DIGEST_LEN] {
+        self.0
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns `true` if every byte of this `Digest` is zero.
+    ///
+    /// Redshirt 2 writers fill the header with zeroes until the real hash is known, so an all-zero
+    /// digest usually means that the writer never finalised the stream.
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|&n| n == 0)
+    }
+
+    #[cfg(any(feature = "manifest", feature = "serde"))]
+    /// Parses a `Digest` from exactly 40 hexadecimal digits, in either case.
+    pub(crate) fn parse_hex(hex: &str) -> Option<Self> {
+        if hex.len() != DIGEST_LEN * 2 || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let mut bytes = array!(DIGEST_LEN);
+        for (byte, i) in bytes.iter_mut().zip((0..hex.len()).step_by(2)) {
+            *byte = u8::from_str_radix(&hex[i..i + 2], 16).ok()?;
+        }
+        Some(Self(bytes))
+    }
+}
+
+impl Display for Digest {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        LowerHex::fmt(self, f)
+    }
+}
+
+impl LowerHex for Digest {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|n| write!(f, "{n:02x}"))
+    }
+}
+
+impl UpperHex for Digest {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|n| write!(f, "{n:02X}"))
+    }
+}
+
+impl AsRef<[u8]> for Digest {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<[u8; DIGEST_LEN]> for Digest {
+    #[inline]
+    fn from(bytes: [u8; DIGEST_LEN]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<Digest> for [u8; DIGEST_LEN] {
+    #[inline]
+    fn from(digest: Digest) -> Self {
+        digest.0
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Digest {
+    #[inline]
+    /// Serializes a `Digest` as a string of 40 lowercase hexadecimal digits.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Digest {
+    #[inline]
+    /// Deserializes a `Digest` from a string of exactly 40 hexadecimal digits, in either case.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HexVisitor;
+
+        impl Visitor<'_> for HexVisitor {
+            type Value = Digest;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                f.write_str("a SHA-1 hash as 40 hexadecimal digits")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Digest, E> {
+                Digest::parse_hex(v).ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_str(HexVisitor)
+    }
+}
@@ -5,6 +5,7 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
 // modified, or distributed except according to those terms.
 
+use crate::digest::Digest;
 use std::{
     error,
     fmt::{self, Display, Formatter},
@@ -12,7 +13,11 @@ use std::{
 };
 
 #[derive(Debug)]
+#[non_exhaustive]
 /// Represents errors that may occur when working with Redshirt-encoded data.
+///
+/// New variants may be added in minor releases, so matches on an `Error` must include a wildcard
+/// arm.
 pub enum Error {
     /// An I/O error occurred.
     Io(io::Error),
@@ -20,7 +25,44 @@ pub enum Error {
     BadHeader,
     /// The checksum specified in the Redshirt 2 header does not match the checksum of the encoded
     /// data.
-    BadChecksum,
+    BadChecksum {
+        /// The checksum specified in the Redshirt 2 header.
+        expected: Digest,
+        /// The checksum of the encoded data.
+        actual: Digest,
+    },
+    /// The checksum specified in the Redshirt 2 header is all zeroes, indicating that the writer
+    /// never finalised the stream.
+    Unfinalized,
+    /// The decoded data is not valid UTF-8.
+    InvalidText {
+        /// The length of the longest prefix of the decoded data that is valid UTF-8.
+        valid_up_to: usize,
+    },
+    /// The data is, or a write would have made it, longer than a limit such as the one set by
+    /// `v2::WriterOptions::max_payload_len` or `v2::ReaderOptions::max_payload_len`.
+    PayloadTooLarge {
+        /// The maximum length of the data, in bytes, not counting the header.
+        limit: u64,
+        /// The length the data would have had if the write had been accepted in full, or, when
+        /// reading, the length of the data as far as it is known.
+        attempted: u64,
+    },
+    /// The operation was stopped by a `CancelToken` before it completed.
+    Cancelled,
+}
+
+impl Error {
+    #[inline]
+    /// Converts an `io::Error` into an `Error`, recovering an `Error` that was converted into an
+    /// `io::Error` (e.g. by a `Read` implementation).
+    pub(crate) fn from_io(e: io::Error) -> Self {
+        if matches!(e.get_ref(), Some(inner) if inner.is::<Self>()) {
+            *e.into_inner().unwrap().downcast::<Self>().unwrap()
+        } else {
+            Error::Io(e)
+        }
+    }
 }
 
 impl Display for Error {
@@ -29,7 +71,17 @@ impl Display for Error {
         match self {
             Error::Io(inner) => Display::fmt(inner, f),
             Error::BadHeader => f.write_str("bad header"),
-            Error::BadChecksum => f.write_str("bad checksum"),
+            Error::BadChecksum { expected, actual } => {
+                write!(f, "bad checksum (expected {expected}, found {actual})")
+            }
+            Error::Unfinalized => f.write_str("unfinalized checksum"),
+            Error::InvalidText { valid_up_to } => {
+                write!(f, "invalid UTF-8 after {valid_up_to} bytes")
+            }
+            Error::PayloadTooLarge { limit, attempted } => {
+                write!(f, "payload too large ({attempted} bytes, limit {limit})")
+            }
+            Error::Cancelled => f.write_str("cancelled"),
         }
     }
 }
@@ -43,3 +95,88 @@ impl error::Error for Error {
         }
     }
 }
+
+impl From<Error> for io::Error {
+    #[inline]
+    /// Converts an `Error` into an `io::Error`.
+    ///
+    /// `Error::Io` is unwrapped; any other variant becomes an `io::Error` of kind `InvalidData`,
+    /// from which the original `Error` can be recovered via `io::Error::get_ref` and
+    /// `downcast_ref`.
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(inner) => inner,
+            _ => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// Identifies one of the output streams of a `MultiWriter`.
+pub enum Sink {
+    /// The first output stream.
+    Primary,
+    /// The second output stream.
+    Secondary,
+}
+
+#[derive(Debug)]
+/// An I/O error that occurred while writing to one of the output streams of a `MultiWriter`.
+pub struct SinkError {
+    sink: Sink,
+    inner: io::Error,
+}
+
+impl Display for Sink {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Sink::Primary => f.write_str("primary"),
+            Sink::Secondary => f.write_str("secondary"),
+        }
+    }
+}
+
+impl SinkError {
+    #[inline]
+    pub(crate) const fn new(sink: Sink, inner: io::Error) -> Self {
+        Self { sink, inner }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the output stream on which the error occurred.
+    pub const fn sink(&self) -> Sink {
+        self.sink
+    }
+
+    #[inline]
+    #[must_use]
+    /// Consumes this `SinkError`, returning the underlying I/O error.
+    pub fn into_inner(self) -> io::Error {
+        self.inner
+    }
+}
+
+impl Display for SinkError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} sink: {}", self.sink, self.inner)
+    }
+}
+
+impl error::Error for SinkError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+impl From<SinkError> for io::Error {
+    #[inline]
+    /// Converts a `SinkError` into an `io::Error` of the same kind, whose message names the output
+    /// stream on which the error occurred.
+    fn from(e: SinkError) -> Self {
+        io::Error::new(e.inner.kind(), e)
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::cursor::{self, overflow_error};
+use std::io::{self, Read, Seek, SeekFrom};
+
+#[derive(Debug)]
+/// Implements `Seek` for a reader that cannot seek, by reading and discarding data to move
+/// forwards.
+///
+/// This lets code written against `Read + Seek`, such as a parser that skips over fields it does
+/// not need, work over a pipe or standard input. `SeekFrom::Start` and `SeekFrom::Current` seeks
+/// to the current position or later are emulated by reading up to the new position. Seeks
+/// backwards, and seeks relative to the end, fail with an `io::Error` of kind `Unsupported`,
+/// leaving the position unchanged.
+///
+/// Skipped data is read through the wrapped reader, so a `v2::Reader` that verifies its hash
+/// lazily still hashes it. A seek past the end of the stream stops at the end, and returns that
+/// position rather than the one requested.
+///
+/// Created by `ForwardOnly::new`, `v1::Reader::forward_only` or `v2::Reader::forward_only`.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::{v2, ForwardOnly};
+/// use std::io::{Read, Seek, SeekFrom};
+///
+/// let encoded = v2::encode(b"foobar");
+/// let mut reader = v2::ReaderOptions::new()
+///     .from_stream(&encoded[..])
+///     .unwrap()
+///     .forward_only();
+/// assert_eq!(reader.seek(SeekFrom::Current(3)).unwrap(), 3);
+/// let mut rest = String::new();
+/// let _ = reader.read_to_string(&mut rest).unwrap();
+/// assert_eq!(rest, "bar");
+/// assert!(reader.seek(SeekFrom::Start(0)).is_err());
+/// ```
+pub struct ForwardOnly<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R> ForwardOnly<R> {
+    #[inline]
+    /// Wraps a reader, counting positions from the data it reads next.
+    pub const fn new(inner: R) -> Self {
+        Self::at(inner, 0)
+    }
+
+    #[inline]
+    /// Wraps a reader that is already at `position`.
+    pub(crate) const fn at(inner: R, position: u64) -> Self {
+        Self { inner, position }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the current position.
+    pub const fn position(&self) -> u64 {
+        self.position
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns a reference to the wrapped reader.
+    pub const fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    #[inline]
+    /// Returns a mutable reference to the wrapped reader.
+    ///
+    /// Reading from the wrapped reader directly leaves the position stale.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    #[inline]
+    /// Unwraps a `ForwardOnly`, returning the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for ForwardOnly<R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.position = cursor::add_len(self.position, len)?;
+        Ok(len)
+    }
+}
+
+impl<R: Read> Seek for ForwardOnly<R> {
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => n,
+            SeekFrom::Current(n) if n >= 0 => self
+                .position
+                .checked_add_signed(n)
+                .ok_or_else(overflow_error)?,
+            SeekFrom::Current(_) | SeekFrom::End(_) => return Err(unsupported(pos)),
+        };
+        let n = target
+            .checked_sub(self.position)
+            .ok_or_else(|| unsupported(pos))?;
+        let inner = &mut self.inner;
+        let skipped = cursor::skip(n, |buf| inner.read(buf))?;
+        self.position += skipped;
+        Ok(self.position)
+    }
+
+    #[inline]
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.position)
+    }
+}
+
+/// Returns the error for a seek that cannot be emulated by reading forwards.
+fn unsupported(pos: SeekFrom) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{pos:?} would move backwards or relative to the end of a forward-only stream"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ForwardOnly;
+    use crate::cursor::Trickle;
+    use std::io::{self, Read, Seek, SeekFrom};
+
+    #[test]
+    fn seek_forwards() {
+        let data: Vec<u8> = (0..=255).collect();
+        let mut reader = ForwardOnly::new(Trickle::new(&data[..]));
+        assert_eq!(reader.stream_position().unwrap(), 0);
+        assert_eq!(reader.seek(SeekFrom::Start(10)).unwrap(), 10);
+        assert_eq!(reader.seek(SeekFrom::Start(10)).unwrap(), 10);
+        assert_eq!(reader.seek(SeekFrom::Current(5)).unwrap(), 15);
+        let mut byte = [0];
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!((byte[0], reader.stream_position().unwrap()), (15, 16));
+        assert_eq!(reader.seek(SeekFrom::Start(1000)).unwrap(), 256);
+        assert_eq!(reader.position(), 256);
+    }
+
+    #[test]
+    fn seek_unsupported() {
+        let mut reader = ForwardOnly::new(&b"foobar"[..]);
+        assert_eq!(reader.seek(SeekFrom::Start(3)).unwrap(), 3);
+        for pos in [
+            SeekFrom::Start(2),
+            SeekFrom::Current(-1),
+            SeekFrom::End(0),
+            SeekFrom::End(-1),
+        ] {
+            let e = reader.seek(pos).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::Unsupported, "{pos:?}");
+            assert_eq!(reader.position(), 3);
+        }
+        assert_eq!(reader.seek(SeekFrom::Current(i64::MAX)).unwrap(), 6);
+        assert!(reader.seek(SeekFrom::Current(i64::MAX)).is_ok());
+    }
+
+    #[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+    #[test]
+    fn lazy_verification() {
+        use crate::{error::Error, testdata, v2};
+
+        let broken = testdata::flip_digest_bit(b"Hello world!", 0);
+        let mut reader = v2::ReaderOptions::new()
+            .from_stream(&broken.data[..])
+            .unwrap()
+            .forward_only();
+        let e = reader.seek(SeekFrom::Start(100)).unwrap_err();
+        let e = e.get_ref().and_then(|e| e.downcast_ref::<Error>()).unwrap();
+        assert!(broken.expects(e), "{}: {:?}", broken, e);
+
+        let encoded = v2::encode(b"Hello world!");
+        let mut reader = v2::ReaderOptions::new().from_stream(&encoded[..]).unwrap();
+        let mut buf = [0; 6];
+        reader.read_exact(&mut buf).unwrap();
+        let mut reader = reader.forward_only();
+        assert_eq!(reader.seek(SeekFrom::Start(100)).unwrap(), 12);
+        assert_eq!(
+            reader.get_ref().digest_computed(),
+            Some(v2::verify(&encoded[..]).unwrap())
+        );
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Generation of valid and corrupted Redshirt files for fuzz targets and property tests.
+//!
+//! Enabled by the `arbitrary` feature, which also implements `arbitrary::Arbitrary` for `Version`,
+//! `Digest` and `Info`.
+//!
+//! # Examples
+//!
+//! ```
+//! use arbitrary::Unstructured;
+//! use redshirt::{
+//!     fuzzing::{arbitrary_encoded_file, Corruption},
+//!     v1, v2, Version,
+//! };
+//!
+//! let mut u = Unstructured::new(&[0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF0]);
+//! let file = arbitrary_encoded_file(&mut u).unwrap();
+//! let decoded = match file.version {
+//!     Version::V1 => v1::decode(&file.data),
+//!     Version::V2 => v2::decode(&file.data),
+//! };
+//! assert_eq!(decoded.is_ok(), file.corruption == Corruption::None);
+//! ```
+
+use crate::{digest::DIGEST_LEN, v1, v2, version::Version};
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// Describes how `arbitrary_encoded_file` altered an otherwise valid file.
+pub enum Corruption {
+    /// The file is valid.
+    None,
+    /// The file was cut short partway through its header. Readers fail with `Error::BadHeader`
+    /// or an I/O error of kind `UnexpectedEof`.
+    TruncatedHeader {
+        /// The length of the truncated file, which is less than the length of the header.
+        len: usize,
+    },
+    /// One bit of the SHA-1 hash in a Redshirt 2 header was flipped. Readers that verify the hash
+    /// fail with `Error::BadChecksum`.
+    FlippedDigestBit {
+        /// The index of the flipped bit, counting from the least significant bit of the first byte
+        /// of the hash.
+        bit: usize,
+    },
+    /// One byte of the marker at the start of the file was changed. Readers fail with
+    /// `Error::BadHeader`.
+    WrongMarkerByte {
+        /// The index of the changed byte.
+        index: usize,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A Redshirt file generated by `arbitrary_encoded_file`.
+pub struct EncodedFile {
+    /// The encoding scheme of the file before it was corrupted.
+    pub version: Version,
+    /// The decoded data.
+    pub payload: Vec<u8>,
+    /// How the file was corrupted, if at all.
+    pub corruption: Corruption,
+    /// The contents of the file.
+    pub data: Vec<u8>,
+}
+
+#[inline]
+/// Generates a Redshirt file containing an arbitrary payload, which is either valid or corrupted
+/// in one of the ways described by `Corruption`.
+///
+/// # Errors
+///
+/// Returns an `Err` if `u` cannot provide the data needed, as for any `Arbitrary` implementation.
+pub fn arbitrary_encoded_file(u: &mut Unstructured<'_>) -> Result<EncodedFile> {
+    let version = u.arbitrary()?;
+    let payload: Vec<u8> = u.arbitrary()?;
+    let (mut data, header_len, marker_len) = match version {
+        Version::V1 => (v1::encode(&payload), v1::MARKER_LEN, v1::MARKER_LEN),
+        Version::V2 => (
+            v2::encode(&payload),
+            v2::HEADER_LEN,
+            v2::HEADER_LEN - DIGEST_LEN,
+        ),
+    };
+    let kinds = if version == Version::V2 { 3 } else { 2 };
+    let corruption = match u.int_in_range(0..=kinds)? {
+        0 => Corruption::None,
+        1 => {
+            let len = u.int_in_range(0..=header_len - 1)?;
+            data.truncate(len);
+            Corruption::TruncatedHeader { len }
+        }
+        2 => {
+            let index = u.int_in_range(0..=marker_len - 1)?;
+            data[index] ^= u.int_in_range(1..=u8::MAX)?;
+            Corruption::WrongMarkerByte { index }
+        }
+        _ => {
+            let bit = u.int_in_range(0..=DIGEST_LEN * 8 - 1)?;
+            data[marker_len + bit / 8] ^= 1 << (bit % 8);
+            Corruption::FlippedDigestBit { bit }
+        }
+    };
+    Ok(EncodedFile {
+        version,
+        payload,
+        corruption,
+        data,
+    })
+}
+
+impl<'a> Arbitrary<'a> for EncodedFile {
+    #[inline]
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_encoded_file(u)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{arbitrary_encoded_file, Corruption};
+    use crate::{v1, v2, version::Version, Error};
+    use arbitrary::Unstructured;
+
+    #[test]
+    fn corruptions_are_detected() {
+        let input: Vec<u8> = (0..4096_u32)
+            .map(|n| n.wrapping_mul(2_654_435_761).to_le_bytes()[3])
+            .collect();
+        let mut u = Unstructured::new(&input);
+        let mut seen = [false; 4];
+        while !u.is_empty() {
+            let file = arbitrary_encoded_file(&mut u).unwrap();
+            let res = match file.version {
+                Version::V1 => v1::decode(&file.data),
+                Version::V2 => v2::decode(&file.data),
+            };
+            match file.corruption {
+                Corruption::None => {
+                    seen[0] = true;
+                    assert_eq!(res.unwrap(), file.payload);
+                }
+                Corruption::TruncatedHeader { len } => {
+                    seen[1] = true;
+                    assert_eq!(file.data.len(), len);
+                    assert!(matches!(res, Err(Error::BadHeader)), "{:?}", res);
+                }
+                Corruption::WrongMarkerByte { .. } => {
+                    seen[2] = true;
+                    assert!(matches!(res, Err(Error::BadHeader)), "{:?}", res);
+                }
+                Corruption::FlippedDigestBit { .. } => {
+                    seen[3] = true;
+                    assert_eq!(file.version, Version::V2);
+                    assert!(matches!(res, Err(Error::BadChecksum { .. })), "{:?}", res);
+                }
+            }
+        }
+        assert_eq!(seen, [true; 4]);
+    }
+}
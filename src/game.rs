@@ -0,0 +1,407 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{cursor::read_up_to, error::Error, v1, v2, version::Version};
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Component, Path},
+};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+/// A game known to use the Redshirt encoding schemes, used as a hint when opening files.
+pub enum Game {
+    /// *Uplink*.
+    Uplink,
+    /// *Darwinia*.
+    Darwinia,
+    /// *DEFCON*.
+    Defcon,
+    /// A game whose conventions are not known.
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// A Redshirt file whose encoding scheme differs from the one its game is expected to use, as
+/// returned by `Game::check`.
+pub struct UnexpectedVersion {
+    /// The game whose files were being examined.
+    pub game: Game,
+    /// The encoding scheme the game is expected to use.
+    pub expected: Version,
+    /// The encoding scheme of the file.
+    pub found: Version,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+/// A reader for either encoding scheme, as returned by `open_auto`.
+pub enum AnyReader<R> {
+    /// A Redshirt 1 stream.
+    V1(v1::Reader<R>),
+    /// A Redshirt 2 stream.
+    V2(v2::Reader<R>),
+}
+
+impl Game {
+    #[inline]
+    #[must_use]
+    /// Returns the encoding scheme that this game's save files are expected to use, if known.
+    ///
+    /// Only *Uplink* has a known convention: its current releases write user files with Redshirt
+    /// 2, although the earliest releases used Redshirt 1.
+    pub const fn expected_version(self) -> Option<Version> {
+        match self {
+            Game::Uplink => Some(Version::V2),
+            Game::Darwinia | Game::Defcon | Game::Unknown => None,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns glob patterns matching this game's save files, relative to its installation
+    /// directory.
+    ///
+    /// Patterns use `/` as a separator, and `*` matches any run of characters within a single
+    /// path component. The list is empty for games whose layout is not known.
+    pub const fn save_file_globs(self) -> &'static [&'static str] {
+        match self {
+            Game::Uplink => &["users/*.usr", "users/*.usr.tmp"],
+            Game::Darwinia | Game::Defcon | Game::Unknown => &[],
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns whether `path` matches one of `save_file_globs`.
+    ///
+    /// Patterns are matched against the end of the path, so both absolute paths and paths
+    /// relative to a subdirectory of the installation directory match. A path with fewer
+    /// components than a pattern is matched against the end of that pattern.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::Game;
+    ///
+    /// assert!(Game::Uplink.is_save_file("/games/uplink/users/agent.usr".as_ref()));
+    /// assert!(Game::Uplink.is_save_file("agent.usr".as_ref()));
+    /// assert!(!Game::Uplink.is_save_file("data/agent.usr".as_ref()));
+    /// ```
+    pub fn is_save_file(self, path: &Path) -> bool {
+        self.save_file_globs()
+            .iter()
+            .any(|pattern| matches_glob(pattern, path))
+    }
+
+    #[inline]
+    #[must_use]
+    /// Checks the encoding scheme of the file at `path` against the one this game is expected to
+    /// use, returning the mismatch if `path` is one of its save files and has the wrong scheme.
+    pub fn check(self, path: &Path, found: Version) -> Option<UnexpectedVersion> {
+        match self.expected_version() {
+            Some(expected) if expected != found && self.is_save_file(path) => {
+                Some(UnexpectedVersion {
+                    game: self,
+                    expected,
+                    found,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for Game {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Game::Uplink => "Uplink",
+            Game::Darwinia => "Darwinia",
+            Game::Defcon => "DEFCON",
+            Game::Unknown => "unknown game",
+        })
+    }
+}
+
+impl Display for UnexpectedVersion {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let found = match self.found {
+            Version::V1 => "v1",
+            Version::V2 => "v2",
+        };
+        write!(f, "unexpected {found} file in an {} directory", self.game)
+    }
+}
+
+impl<R> AnyReader<R> {
+    #[inline]
+    #[must_use]
+    /// Returns the encoding scheme of the stream.
+    pub const fn version(&self) -> Version {
+        match self {
+            AnyReader::V1(_) => Version::V1,
+            AnyReader::V2(_) => Version::V2,
+        }
+    }
+}
+
+impl<R: Read> AnyReader<R> {
+    #[inline]
+    /// Creates a new reader from an input stream that cannot be rewound, such as a member of an
+    /// archive or a socket, detecting its encoding scheme from its marker.
+    ///
+    /// The marker is read only once, so `src` does not need to support seeking. A Redshirt 2
+    /// stream is read as by `v2::ReaderOptions::from_stream` with the default options, so its
+    /// SHA-1 hash is verified lazily: a mismatch is reported by the read that reaches the end of
+    /// the stream, as described in the [`v2` module documentation](v2/index.html#lazy-verification).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, `src` does not begin with a valid Redshirt header,
+    /// or it is a Redshirt 2 stream whose header contains an all-zero SHA-1 hash.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{v2, AnyReader, Version};
+    /// use std::io::Read;
+    ///
+    /// let encoded = v2::encode(b"foo");
+    /// let mut reader = AnyReader::from_stream(&encoded[..]).unwrap();
+    /// assert_eq!(reader.version(), Version::V2);
+    /// let mut decoded = String::new();
+    /// let _ = reader.read_to_string(&mut decoded).unwrap();
+    /// assert_eq!(decoded, "foo");
+    /// ```
+    pub fn from_stream(mut src: R) -> Result<Self, Error> {
+        let mut marker = [0; v1::MARKER_LEN];
+        let len = read_up_to(&mut src, &mut marker).map_err(Error::Io)?;
+        match Version::detect(&marker[..len]).ok_or(Error::BadHeader)? {
+            Version::V1 => Ok(AnyReader::V1(v1::Reader::from_raw(src))),
+            Version::V2 => v2::ReaderOptions::new()
+                .open_after_marker(src)
+                .map(AnyReader::V2),
+        }
+    }
+
+    #[inline]
+    /// Consumes this reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        match self {
+            AnyReader::V1(reader) => reader.into_inner(),
+            AnyReader::V2(reader) => reader.into_inner(),
+        }
+    }
+}
+
+impl<R: Read> Read for AnyReader<R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            AnyReader::V1(reader) => reader.read(buf),
+            AnyReader::V2(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl<R: Seek> Seek for AnyReader<R> {
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            AnyReader::V1(reader) => reader.seek(pos),
+            AnyReader::V2(reader) => reader.seek(pos),
+        }
+    }
+}
+
+#[inline]
+/// Opens the file at `path` for reading, detecting its encoding scheme from its marker.
+///
+/// If `game` is given and `path` is one of its save files, the file must use the encoding scheme
+/// the game is expected to use; for example, *Uplink* user files must be Redshirt 2 files. Other
+/// files, and files for games without a known convention, may use either scheme.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, the file does not begin with a valid Redshirt header,
+/// or it uses a different encoding scheme from the one `game` expects, which is reported as
+/// `Error::BadHeader`. Redshirt 2 files are verified as by `v2::Reader::new`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::{open_auto, Game};
+/// use std::io::Read;
+///
+/// let mut reader = open_auto("users/agent.usr", Some(Game::Uplink)).unwrap();
+/// let mut data = Vec::new();
+/// let _ = reader.read_to_end(&mut data).unwrap();
+/// ```
+pub fn open_auto<P: AsRef<Path>>(path: P, game: Option<Game>) -> Result<AnyReader<File>, Error> {
+    let path = path.as_ref();
+    let mut file = File::open(path).map_err(Error::Io)?;
+    let mut marker = [0; v1::MARKER_LEN];
+    let len = read_up_to(&mut file, &mut marker).map_err(Error::Io)?;
+    let version = Version::detect(&marker[..len]).ok_or(Error::BadHeader)?;
+    if game.and_then(|game| game.check(path, version)).is_some() {
+        return Err(Error::BadHeader);
+    }
+    let _ = file.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
+    match version {
+        Version::V1 => v1::Reader::new(file).map(AnyReader::V1),
+        Version::V2 => v2::Reader::new(file).map(AnyReader::V2),
+    }
+}
+
+/// Returns whether the trailing components of `path` match those of the glob `pattern`.
+fn matches_glob(pattern: &str, path: &Path) -> bool {
+    let mut components = path.components().rev();
+    let mut matched = false;
+    for part in pattern.rsplit('/') {
+        match components.next() {
+            Some(Component::Normal(name)) => match name.to_str() {
+                Some(name) if matches_component(part, name) => matched = true,
+                _ => return false,
+            },
+            Some(_) => return false,
+            None => break,
+        }
+    }
+    matched
+}
+
+/// Returns whether `name` matches the glob `pattern`, in which `*` matches any run of characters.
+fn matches_component(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect::<Vec<_>>();
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches_component, open_auto, AnyReader, Game, UnexpectedVersion};
+    use crate::{cursor::Trickle, error::Error, testdata, v1, v2, version::Version};
+    use std::{env, fs, io::Read, path::Path, process};
+
+    #[test]
+    fn globs() {
+        assert!(matches_component("*.usr", "agent.usr"));
+        assert!(matches_component("*.usr", ".usr"));
+        assert!(matches_component("a*b*c", "abc"));
+        assert!(matches_component("users", "users"));
+        assert!(!matches_component("*.usr", "agent.usr.tmp"));
+        assert!(!matches_component("a*b*c", "acb"));
+        assert!(Game::Uplink.is_save_file(Path::new("users/agent.usr.tmp")));
+        assert!(!Game::Uplink.is_save_file(Path::new("users/options")));
+        assert!(!Game::Darwinia.is_save_file(Path::new("users/agent.usr")));
+    }
+
+    #[test]
+    fn check() {
+        let path = Path::new("users/agent.usr");
+        let unexpected = Game::Uplink.check(path, Version::V1).unwrap();
+        assert_eq!(
+            unexpected,
+            UnexpectedVersion {
+                game: Game::Uplink,
+                expected: Version::V2,
+                found: Version::V1,
+            }
+        );
+        assert_eq!(
+            unexpected.to_string(),
+            "unexpected v1 file in an Uplink directory"
+        );
+        assert_eq!(Game::Uplink.check(path, Version::V2), None);
+        assert_eq!(Game::Uplink.check(Path::new("data.dat"), Version::V1), None);
+        assert_eq!(Game::Unknown.check(path, Version::V1), None);
+    }
+
+    #[test]
+    fn open() {
+        let dir = env::temp_dir().join(format!("redshirt-game-{}/users", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("old.usr"), v1::encode(b"old")).unwrap();
+        fs::write(dir.join("new.usr"), v2::encode(b"new")).unwrap();
+        fs::write(dir.join("plain.usr"), b"plain").unwrap();
+
+        let mut data = String::new();
+        let mut reader = open_auto(dir.join("new.usr"), Some(Game::Uplink)).unwrap();
+        assert_eq!(reader.version(), Version::V2);
+        let _ = reader.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "new");
+        let mut reader = open_auto(dir.join("old.usr"), None).unwrap();
+        assert_eq!(reader.version(), Version::V1);
+        data.clear();
+        let _ = reader.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "old");
+        assert!(matches!(
+            open_auto(dir.join("old.usr"), Some(Game::Uplink)),
+            Err(Error::BadHeader)
+        ));
+        assert!(open_auto(dir.join("old.usr"), Some(Game::Defcon)).is_ok());
+        assert!(matches!(
+            open_auto(dir.join("plain.usr"), None),
+            Err(Error::BadHeader)
+        ));
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+    #[test]
+    fn stream() {
+        let mut data = String::new();
+        let encoded = v1::encode(b"old");
+        let mut reader = AnyReader::from_stream(Trickle::new(&encoded[..])).unwrap();
+        assert_eq!(reader.version(), Version::V1);
+        let _ = reader.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "old");
+        let encoded = v2::encode(b"new");
+        let mut reader = AnyReader::from_stream(Trickle::new(&encoded[..])).unwrap();
+        assert_eq!(reader.version(), Version::V2);
+        data.clear();
+        let _ = reader.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "new");
+
+        let corrupt = testdata::flip_digest_bit(b"new", 0).data;
+        let mut reader = AnyReader::from_stream(&corrupt[..]).unwrap();
+        let e = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(
+            e.get_ref().and_then(|e| e.downcast_ref::<Error>()),
+            Some(Error::BadChecksum { .. })
+        ));
+        let mut unfinalized = encoded;
+        unfinalized[v1::MARKER_LEN..v2::HEADER_LEN].fill(0);
+        assert!(matches!(
+            AnyReader::from_stream(&unfinalized[..]),
+            Err(Error::Unfinalized)
+        ));
+        assert!(matches!(
+            AnyReader::from_stream(&b"plain"[..]),
+            Err(Error::BadHeader)
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{
+    advice,
+    cursor::{self, read_up_to},
+    digest::{Digest, DIGEST_LEN},
+    error::Error,
+    v1, v2,
+    version::Version,
+};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Metadata describing a Redshirt-encoded file, as returned by `inspect`.
+pub struct Info {
+    /// The encoding scheme of the file.
+    pub version: Version,
+    /// The length of the header, in bytes.
+    pub header_len: u64,
+    /// The length of the encoded data following the header, in bytes.
+    pub payload_len: u64,
+    /// The SHA-1 hash stored in the header, for Redshirt 2 files.
+    pub stored_digest: Option<Digest>,
+    /// Whether the stored SHA-1 hash matches that of the encoded data, if it was checked.
+    ///
+    /// This is `None` for Redshirt 1 files, which have no hash, and for Redshirt 2 files if
+    /// verification was skipped. A Redshirt 2 file whose stored hash is all zeroes is never valid.
+    pub verified: Option<bool>,
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Info {
+    #[inline]
+    /// Generates an `Info` that `inspect` could have returned: Redshirt 1 files have no stored hash,
+    /// and an all-zero hash is never verified.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let version = u.arbitrary()?;
+        let payload_len = u.arbitrary()?;
+        Ok(match version {
+            Version::V1 => Self {
+                version,
+                header_len: cursor::len_u64(v1::MARKER_LEN),
+                payload_len,
+                stored_digest: None,
+                verified: None,
+            },
+            Version::V2 => {
+                let digest: Digest = u.arbitrary()?;
+                let verified = u.arbitrary::<Option<bool>>()?;
+                Self {
+                    version,
+                    header_len: cursor::len_u64(v2::HEADER_LEN),
+                    payload_len,
+                    stored_digest: Some(digest),
+                    verified: verified.map(|verified| verified && !digest.is_zero()),
+                }
+            }
+        })
+    }
+}
+
+#[inline]
+/// Describes the Redshirt file at `path` without decoding it.
+///
+/// If `verify` is `true`, the SHA-1 hash of a Redshirt 2 file is checked, which requires reading
+/// the whole file. Otherwise only the header is read, and the length of the encoded data is taken
+/// from the file's metadata.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, or the file does not begin with a valid Redshirt 1 or
+/// Redshirt 2 header. A hash mismatch is not an error; it is reported via `Info::verified`.
+///
+/// # Examples
+///
+/// ```no_run
+/// let info = redshirt::inspect("User.usr", false).unwrap();
+/// println!("{} with {} bytes of data", info.version, info.payload_len);
+/// ```
+pub fn inspect<P: AsRef<Path>>(path: P, verify: bool) -> Result<Info, Error> {
+    let mut file = File::open(path).map_err(Error::Io)?;
+    if verify {
+        // Verifying reads the whole file. The hint is only an optimisation, so failing to give it
+        // is not an error.
+        let _ = advice::sequential(&file);
+    }
+    let len = file.metadata().map_err(Error::Io)?.len();
+    inspect_with_len(&mut file, len, verify)
+}
+
+#[inline]
+/// Describes a Redshirt stream without decoding it.
+///
+/// This behaves like `inspect`, but reads from `src`, starting at its current position. The
+/// length of the stream is found by seeking to its end.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, or the stream does not begin with a valid Redshirt 1
+/// or Redshirt 2 header.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::{v2, Version};
+/// use std::io::Cursor;
+///
+/// let encoded = v2::encode(b"foobar");
+/// let info = redshirt::inspect_reader(Cursor::new(encoded), true).unwrap();
+/// assert_eq!(info.version, Version::V2);
+/// assert_eq!(info.payload_len, 6);
+/// assert_eq!(info.verified, Some(true));
+/// ```
+pub fn inspect_reader<R: Read + Seek>(mut src: R, verify: bool) -> Result<Info, Error> {
+    let start = src.stream_position().map_err(Error::Io)?;
+    let end = src.seek(SeekFrom::End(0)).map_err(Error::Io)?;
+    let _ = src.seek(SeekFrom::Start(start)).map_err(Error::Io)?;
+    inspect_with_len(src, end.saturating_sub(start), verify)
+}
+
+fn inspect_with_len<R: Read + Seek>(mut src: R, len: u64, verify: bool) -> Result<Info, Error> {
+    let start = src.stream_position().map_err(Error::Io)?;
+    let mut header = array!(v2::HEADER_LEN);
+    let header_len = read_up_to(&mut src, &mut header).map_err(Error::Io)?;
+    let version = Version::detect(&header[..header_len]).ok_or(Error::BadHeader)?;
+    let header_len = match version {
+        Version::V1 => v1::MARKER_LEN,
+        Version::V2 if header_len < v2::HEADER_LEN => return Err(Error::BadHeader),
+        Version::V2 => v2::HEADER_LEN,
+    };
+    let header_len = cursor::len_u64(header_len);
+    let mut info = Info {
+        version,
+        header_len,
+        payload_len: len.saturating_sub(header_len),
+        stored_digest: None,
+        verified: None,
+    };
+    if version == Version::V2 {
+        let mut digest = array!(DIGEST_LEN);
+        digest.copy_from_slice(&header[v2::HEADER_LEN - DIGEST_LEN..]);
+        info.stored_digest = Some(Digest::from_bytes(digest));
+        if verify {
+            let _ = src.seek(SeekFrom::Start(start)).map_err(Error::Io)?;
+            info.verified = match v2::verify(src) {
+                Ok(_) => Some(true),
+                Err(Error::BadChecksum { .. } | Error::Unfinalized) => Some(false),
+                Err(e) => return Err(e),
+            };
+        }
+    }
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inspect, inspect_reader, Info};
+    use crate::{digest::Digest, error::Error, testdata, v1, v2, version::Version};
+    use std::{env, fs, io::Cursor, process};
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+
+    #[test]
+    fn inspect_v1() {
+        let info = inspect_reader(Cursor::new(v1::encode(MSG_DEC)), true).unwrap();
+        assert_eq!(
+            info,
+            Info {
+                version: Version::V1,
+                header_len: 9,
+                payload_len: 12,
+                stored_digest: None,
+                verified: None,
+            }
+        );
+    }
+
+    #[test]
+    fn inspect_v2() {
+        let encoded = v2::encode(MSG_DEC);
+        let info = inspect_reader(Cursor::new(&encoded), true).unwrap();
+        let mut digest = [u8::default(); 20];
+        digest.copy_from_slice(&encoded[9..29]);
+        assert_eq!(
+            info,
+            Info {
+                version: Version::V2,
+                header_len: 29,
+                payload_len: 12,
+                stored_digest: Some(Digest::from_bytes(digest)),
+                verified: Some(true),
+            }
+        );
+        let info = inspect_reader(Cursor::new(&encoded), false).unwrap();
+        assert_eq!(info.verified, None);
+    }
+
+    #[test]
+    fn inspect_v2_bad_checksum() {
+        let encoded = testdata::flip_digest_bit(MSG_DEC, 0).data;
+        let info = inspect_reader(Cursor::new(&encoded), true).unwrap();
+        assert_eq!(info.verified, Some(false));
+    }
+
+    #[test]
+    fn inspect_bad_header() {
+        assert!(matches!(
+            inspect_reader(Cursor::new(MSG_DEC), true),
+            Err(Error::BadHeader)
+        ));
+        assert!(matches!(
+            inspect_reader(Cursor::new(&v2::encode(MSG_DEC)[..20]), true),
+            Err(Error::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn inspect_file() {
+        let path = env::temp_dir().join(format!("redshirt-inspect-{}.usr", process::id()));
+        fs::write(&path, v2::encode(MSG_DEC)).unwrap();
+        let info = inspect(&path, false).unwrap();
+        assert_eq!((info.version, info.payload_len), (Version::V2, 12));
+        assert_eq!(info.verified, None);
+        assert_eq!(inspect(&path, true).unwrap().verified, Some(true));
+        let corrupt = testdata::flip_digest_bit(MSG_DEC, 0).data;
+        fs::write(&path, corrupt).unwrap();
+        assert_eq!(inspect(&path, true).unwrap().verified, Some(false));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -78,13 +78,195 @@
 //! }
 //! ```
 //!
+//! # Inspecting Redshirt files
+//!
+//! With both `redshirt1` and `redshirt2` enabled, `inspect` describes a file without decoding it,
+//! returning an `Info` with its encoding scheme, header and payload lengths, and stored SHA-1 hash.
+//! Verifying the hash of a Redshirt 2 file requires reading the whole file, so it is optional:
+//!
+//! ```no_run
+//! let info = redshirt::inspect("User.usr", true).unwrap();
+//! if info.verified == Some(false) {
+//!     println!("{} file is corrupt", info.version);
+//! }
+//! ```
+//!
+//! When verification is skipped, only the header is read, and the payload length comes from the
+//! file's metadata. `inspect_reader` does the same for any `Read + Seek` stream.
+//!
+//! Data whose header has been stripped cannot be identified from its marker, but
+//! `codec::is_probably_encoded` can estimate whether a sample of it is encoded. To find Redshirt
+//! files embedded in larger files, such as memory dumps, `scan` searches a stream for markers.
+//!
+//! # Opening files for a particular game
+//!
+//! `open_auto` opens a file of either encoding scheme, returning an `AnyReader`. It can be given a
+//! `Game` as a hint, in which case that game's save files must use the scheme it is expected to
+//! use; for example, a Redshirt 1 file in *Uplink*'s `users` directory is rejected. `Game` also
+//! describes where each game keeps its save files, and `BatchOptions::game` and
+//! `Manifest::unexpected_versions` use it to point out files with an unexpected scheme:
+//!
+//! ```no_run
+//! use redshirt::{open_auto, Game};
+//!
+//! let reader = open_auto("users/agent.usr", Some(Game::Uplink)).unwrap();
+//! println!("{}", reader.version());
+//! ```
+//!
+//! `AnyReader::from_stream` does the same for a stream that cannot be rewound, such as a member
+//! of a ZIP archive, without extracting it first. Redshirt 2 streams opened this way are verified
+//! lazily.
+//!
+//! For shell pipelines, `decode_stdio` and `encode_stdio` decode or encode standard input to
+//! standard output without seeking either. They refuse to read encoded data from, or write it to,
+//! a terminal.
+//!
+//! Readers over such streams do not implement `Seek`. `ForwardOnly`, created by
+//! `v1::Reader::forward_only` or `v2::Reader::forward_only`, implements it for seeks that only
+//! move forwards, by reading and discarding data, so that parsers written against `Read + Seek`
+//! can skip over data in a pipe.
+//!
+//! # Thread safety
+//!
+//! None of the readers, writers or other types in this crate use shared ownership or interior
+//! mutability, so each is `Send` if its underlying streams are `Send`, and `Sync` if they are
+//! `Sync`. For example, `v1::Reader<File>`, `v2::Reader<File>`, `v1::Writer<File>` and
+//! `v2::Writer<File>` are all `Send` and `Sync`, and can be moved into another thread or task.
+//! `Error`, `Digest`, `Version`, `Game` and `Info` are always `Send` and `Sync`. The asynchronous
+//! types follow the same rule. To write to one Redshirt 2 stream from several threads,
+//! `v2::SyncWriter` wraps a `v2::Writer` in a lock and implements `Write` for shared references.
+//! `CancelToken` is the one type meant to be shared: its clones set and read the same atomic flag,
+//! so that a long-running operation on one thread can be cancelled from another.
+//!
+//! To store readers or writers for different kinds of stream together, `v1` and `v2` provide
+//! `BoxedReader` and `BoxedWriter` aliases over boxed `Send` trait objects, with `from_boxed`
+//! constructors. These are `Send` but not `Sync`. `ReadSeek` and `WriteSeek` name the combined
+//! traits that the trait objects need.
+//!
+//! # Asynchronous I/O
+//!
+//! If the `tokio` feature is enabled, `v1::tokio` and `v2::tokio` provide types that implement
+//! Tokio's `AsyncRead`, `AsyncWrite` and `AsyncSeek` traits. Likewise, if the `futures-io`
+//! feature is enabled, `v1::futures_io` and `v2::futures_io` provide types that implement the
+//! `futures::io` equivalents, for use with runtimes such as smol and async-std. Both features may
+//! be enabled at once. The `async-std` feature additionally exposes these types as
+//! `v1::async_std` and `v2::async_std`.
+//!
+//! If the `tokio-codec` feature is enabled, the `codec` module provides `tokio_util` codecs for
+//! use with `Framed`, `FramedRead` and `FramedWrite`.
+//!
+//! # Parallel encoding
+//!
+//! If the `rayon` feature is enabled, the `codec` module provides `par_encode_in_place` and
+//! `par_decode_in_place`, which encode and decode large in-memory buffers across Rayon's thread
+//! pool, and the `encode` and `decode` functions in `v1` and `v2` use them for large payloads.
+//!
+//! # Compressed data
+//!
+//! Some files store gzip-compressed data inside the Redshirt encoding. If the `flate2` feature is
+//! enabled, `v1::Reader`, `v2::Reader`, `v1::Writer` and `v2::Writer` have `with_gz` methods that
+//! wrap them in a `GzDecoder` or `GzEncoder`, and `open_gz` and `create_gz` constructors that do
+//! the same for a file. The gzip layer is always innermost, so the SHA-1 hash in a Redshirt 2
+//! header covers the compressed data. `GzDecoder`, `GzEncoder` and `Compression` are re-exported
+//! from [`flate2`].
+//!
+//! # Scrubbing decoded data
+//!
+//! If the `zeroize` feature is enabled, the buffers that readers, writers and other helpers in
+//! this crate use internally to hold data while encoding, decoding, hashing or copying it are
+//! overwritten with zeroes once each chunk has been processed, or when they are dropped. Since the
+//! Redshirt encoding schemes are trivially reversible, encoded data is treated as sensitive too.
+//! `v1::decode_to_vec_zeroizing` and `v2::decode_to_vec_zeroizing` decode a stream into a
+//! `Zeroizing<Vec<u8>>`, which is scrubbed when dropped, without leaving copies behind as the
+//! buffer grows.
+//!
+//! This does not cover buffers owned by the caller, or returned from functions such as
+//! `v1::decode` and `v2::decode`, the buffer of a `BufferedReader`, strings returned by the `text`
+//! module, buffers inside the underlying readers and writers (such as a `BufWriter`), or copies
+//! made by the operating system, such as the page cache or swap.
+//!
+//! # Diagnostics
+//!
+//! If the `tracing` feature is enabled, this crate emits [`tracing`] events when a reader reads a
+//! header, when a SHA-1 hash does not match, when a `v2::Writer` writes the hash into the header
+//! (noting whether `into_inner` or `drop` did so), and when seeking fails. Eager verification runs
+//! within a `verify` span, whose duration a subscriber can record. Without a subscriber, these
+//! cost little more than a check of the global maximum level; without the feature, they are not
+//! compiled at all.
+//!
+//! If the `metrics` feature is enabled, verifying a Redshirt 2 stream also records counters and
+//! histograms through the [`metrics`] facade, for services that export them to a monitoring system.
+//! The `metrics` module documents their names. Without the feature, nothing is recorded or
+//! compiled.
+//!
+//! # C API
+//!
+//! If the `capi` feature is enabled, the `capi` module exports a small C API from the `cdylib`
+//! build of this crate. Its header is `include/redshirt.h`.
+//!
+//! # Python bindings
+//!
+//! If the `python` feature is enabled, the `cdylib` build of this crate is also a Python extension
+//! module named `redshirt`, which can be built with [maturin] using the `pyproject.toml` in the
+//! source distribution. It provides `decode`, `encode`, `decode_file`, `encode_file` and `verify`
+//! functions.
+//!
+//! # Features
+//!
+//! | Feature       | Enables                                                  | `wasm32-unknown-unknown` |
+//! |---------------|----------------------------------------------------------|--------------------------|
+//! | `redshirt1`   | The `v1` module (default)                                | Yes                      |
+//! | `redshirt2`   | The `v2` module (default)                                | Yes                      |
+//! | `ring`        | Hashing with `ring` instead of `sha1_smol` (default)     | No                       |
+//! | `tokio`       | `v1::tokio` and `v2::tokio`                              | Yes, except file helpers |
+//! | `futures-io`  | `v1::futures_io` and `v2::futures_io`                    | Yes                      |
+//! | `async-std`   | `v1::async_std` and `v2::async_std`                      | Yes                      |
+//! | `tokio-codec` | `codec::V1Codec` and `codec::V2Codec`                    | Yes                      |
+//! | `bytes`       | `Buf` and `BufMut` support in `codec`, `v1` and `v2`     | Yes                      |
+//! | `rayon`       | `codec::par_encode_in_place` and `par_decode_in_place`   | Yes, without threads     |
+//! | `manifest`    | The `manifest` module                                    | No                       |
+//! | `capi`        | The `capi` module                                        | No                       |
+//! | `cli`         | The `redshirt` command-line tool                         | No                       |
+//! | `python`      | The Python extension module                              | No                       |
+//! | `zeroize`     | Scrubbing of internal buffers, and `Zeroizing` results   | Yes                      |
+//! | `flate2`      | `with_gz`, `open_gz`, `create_gz` and re-exports         | Yes                      |
+//! | `fadvise`     | `advise_sequential` and `advise_dontneed` on readers     | Yes, as no-ops           |
+//! | `fallocate`   | Disk allocation by `WriterOptions::preallocate` on Linux | Yes, as no-ops           |
+//! | `mmap`        | `create_mmap` on writers, on Unix-like systems           | No                       |
+//! | `tracing`     | Diagnostic events for `tracing` subscribers              | Yes                      |
+//! | `metrics`     | The `metrics` module, and metrics for exporters          | No                       |
+//! | `arbitrary`   | The `fuzzing` module, and `Arbitrary` for value types    | Yes                      |
+//! | `testdata`    | The `testdata` module, for building files in tests       | Yes                      |
+//! | `serde`       | `Serialize` and `Deserialize` for value types            | Yes                      |
+//! | `tempfile`    | `v2::SpillOptions`, to spill `BufferedWriter` to disk    | No                       |
+//! | `uplink`      | The `uplink` module                                      | Yes                      |
+//!
+//! To build for `wasm32-unknown-unknown`, disable the default features and enable `redshirt1`
+//! and/or `redshirt2` explicitly. On that target, the Tokio `open`, `create`, `decode_file` and
+//! `encode_file` helpers are unavailable, since Tokio does not support file I/O there; use
+//! `v1::decode`, `v1::encode`, `v2::decode`, `v2::encode` and `v2::verify` to work with data that
+//! is already in memory.
+//!
+//! With a nightly compiler, building with `RUSTFLAGS="--cfg redshirt_unstable"` implements
+//! `Read::read_buf` for `v1::Reader` and `v2::Reader`, so that callers such as `read_to_end` can
+//! decode into memory without zeroing it first. This is a `cfg` rather than a feature so that
+//! `--all-features` builds keep working on stable compilers.
+//!
 //! [SHA-1]: https://en.wikipedia.org/wiki/SHA-1
+//! [maturin]: https://www.maturin.rs
+//! [`flate2`]: https://docs.rs/flate2
+//! [`tracing`]: https://docs.rs/tracing
+//! [`metrics`]: https://docs.rs/metrics
 
+#![cfg_attr(
+    redshirt_unstable,
+    feature(borrowed_buf_init, core_io_borrowed_buf, read_buf)
+)]
 #![deny(
     warnings,
     future_incompatible,
     rust_2018_idioms,
-    rustdoc,
+    rustdoc::all,
     unused,
     missing_copy_implementations,
     missing_debug_implementations,
@@ -115,13 +297,156 @@ pub(crate) fn xor_bytes(bytes: &mut [u8]) {
     }
 }
 
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+#[inline]
+/// Encodes or decodes a whole in-memory payload, in parallel if it is large enough and the
+/// `rayon` feature is enabled.
+pub(crate) fn xor_payload(bytes: &mut [u8]) {
+    #[cfg(feature = "rayon")]
+    if bytes.len() >= codec::PAR_THRESHOLD {
+        codec::par_encode_in_place(bytes);
+        return;
+    }
+    xor_bytes(bytes);
+}
+
+#[cfg(all(
+    any(feature = "redshirt1", feature = "redshirt2"),
+    any(feature = "fadvise", all(feature = "redshirt1", feature = "redshirt2"))
+))]
+mod advice;
+#[cfg(all(
+    any(feature = "tokio", feature = "futures-io"),
+    any(feature = "redshirt1", feature = "redshirt2")
+))]
+mod async_cursor;
+#[cfg(all(
+    any(feature = "tokio", feature = "futures-io"),
+    any(feature = "redshirt1", feature = "redshirt2")
+))]
+mod async_io;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+pub mod batch;
+#[cfg(any(feature = "capi", feature = "python"))]
+mod bindings;
+#[cfg(all(
+    feature = "tokio",
+    not(target_arch = "wasm32"),
+    any(feature = "redshirt1", feature = "redshirt2")
+))]
+mod blocking;
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+mod boxed;
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+mod buffered;
+#[cfg(all(feature = "tokio", any(feature = "redshirt1", feature = "redshirt2")))]
+mod byte_stream;
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+mod cancel;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+pub mod codec;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+mod compare;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+mod convert;
 #[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
 mod cursor;
 #[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+mod digest;
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
 mod error;
 #[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
-pub use error::Error;
+mod forward;
+#[cfg(all(feature = "arbitrary", feature = "redshirt1", feature = "redshirt2"))]
+pub mod fuzzing;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+mod game;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+mod inspect;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+#[cfg(all(feature = "metrics", feature = "redshirt2"))]
+pub mod metrics;
+#[cfg(all(
+    feature = "mmap",
+    unix,
+    any(feature = "redshirt1", feature = "redshirt2")
+))]
+mod mmap;
+#[cfg(feature = "redshirt2")]
+mod prealloc;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(all(redshirt_unstable, any(feature = "redshirt1", feature = "redshirt2")))]
+mod read_buf;
+#[cfg(feature = "redshirt1")]
+mod resize;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+mod scan;
+#[cfg(feature = "redshirt2")]
+mod sha1;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+mod stdio;
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+mod tee;
+#[cfg(all(
+    any(feature = "testdata", test),
+    feature = "redshirt1",
+    feature = "redshirt2"
+))]
+pub mod testdata;
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+pub mod text;
+#[cfg(all(feature = "uplink", any(feature = "redshirt1", feature = "redshirt2")))]
+pub mod uplink;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+mod version;
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+pub use boxed::{ReadSeek, WriteSeek};
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+pub use buffered::BufferedReader;
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+pub use cancel::CancelToken;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+pub use compare::{compare_decoded, compare_paths, Comparison};
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+pub use convert::{ensure_decoded, ensure_encoded, is_encoded, roundtrip_identity, Conversion};
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+pub use digest::Digest;
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+pub use error::{Error, Sink, SinkError};
+#[cfg(all(feature = "flate2", any(feature = "redshirt1", feature = "redshirt2")))]
+pub use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+#[cfg(any(feature = "redshirt1", feature = "redshirt2"))]
+pub use forward::ForwardOnly;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+pub use game::{open_auto, AnyReader, Game, UnexpectedVersion};
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+pub use inspect::{inspect, inspect_reader, Info};
+#[cfg(feature = "redshirt1")]
+pub use resize::Resize;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+pub use scan::{scan, Finding, Scan, ScanOptions};
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+pub use stdio::{decode_stdio, encode_stdio};
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+pub use version::Version;
+#[cfg(all(feature = "zeroize", any(feature = "redshirt1", feature = "redshirt2")))]
+pub use zeroize::Zeroizing;
 #[cfg(feature = "redshirt1")]
 pub mod v1;
 #[cfg(feature = "redshirt2")]
 pub mod v2;
+
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+/// Internal functions exposed for the benchmarks in `benches/`. Not part of the public API.
+pub mod __bench {
+    #[inline]
+    /// Applies the Redshirt XOR transform to `bytes` in place, serially.
+    pub fn xor_bytes(bytes: &mut [u8]) {
+        crate::xor_bytes(bytes);
+    }
+}
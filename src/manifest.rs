@@ -0,0 +1,546 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Recording and checking the SHA-1 hashes of every Redshirt file in a directory tree.
+//!
+//! `Manifest::scan` walks a directory tree in the same order as the `batch` module, recording an
+//! `Entry` for each Redshirt file it finds. A manifest can be saved with `Manifest::write_to`,
+//! loaded again with `Manifest::read_from`, and compared against the tree with `Manifest::verify`
+//! to find files that have been removed, added or modified since. `Manifest::unexpected_versions`
+//! finds the entries whose encoding scheme differs from the one a given `Game` is expected to use.
+//!
+//! The digest recorded for each file is the SHA-1 hash of its encoded data, computed the same way
+//! as the hash in a Redshirt 2 header. Redshirt 1 files have no such hash, so their data is always
+//! hashed; for Redshirt 2 files, the hash is taken from the header unless
+//! `ManifestOptions::rehash` is enabled.
+//!
+//! # Format
+//!
+//! Manifests are UTF-8 text with one entry per line, giving the digest, the version (`v1` or
+//! `v2`), the length of the encoded data and the path relative to the root, separated by single
+//! spaces. Paths use `/` as a separator. Blank lines and lines beginning with `#` are ignored.
+//!
+//! ```text
+//! # redshirt manifest
+//! 3454262b4abf291d0b8e60d9a176e1147ddf05d4 v2 12 users/agent/two.usr
+//! ```
+
+use crate::{
+    advice,
+    batch::Files,
+    cancel::{self, CancelToken},
+    digest::Digest,
+    error::Error,
+    game::{Game, UnexpectedVersion},
+    inspect::inspect,
+    v2,
+    version::Version,
+};
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+#[derive(Clone, Debug)]
+/// Options which can be used to configure how a directory tree is scanned.
+pub struct ManifestOptions {
+    rehash: bool,
+    cancel: Option<CancelToken>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+/// A record of the Redshirt files in a directory tree.
+pub struct Manifest {
+    entries: Vec<Entry>,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+/// A single Redshirt file recorded in a `Manifest`.
+pub struct Entry {
+    /// The path of the file, relative to the root of the tree.
+    pub path: PathBuf,
+    /// The encoding scheme of the file.
+    pub version: Version,
+    /// The length of the encoded data following the header, in bytes.
+    pub payload_len: u64,
+    /// The SHA-1 hash of the encoded data.
+    pub digest: Digest,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A difference between a `Manifest` and the directory tree it describes.
+pub enum Discrepancy {
+    /// A file in the manifest no longer exists, or is no longer a Redshirt file.
+    Missing(Entry),
+    /// A Redshirt file exists that is not in the manifest.
+    Added(Entry),
+    /// A file's version, length or digest differs from the manifest.
+    Modified {
+        /// The entry recorded in the manifest.
+        expected: Entry,
+        /// The entry for the file as it is now.
+        actual: Entry,
+    },
+}
+
+impl ManifestOptions {
+    #[inline]
+    #[must_use]
+    /// Creates a new set of options with the default configuration.
+    ///
+    /// By default, the digests of Redshirt 2 files are taken from their headers.
+    pub const fn new() -> Self {
+        Self {
+            rehash: false,
+            cancel: None,
+        }
+    }
+
+    #[inline]
+    /// Sets whether the digests of Redshirt 2 files are computed from their data, rather than
+    /// taken from their headers.
+    ///
+    /// Taking the digest from the header only requires reading the header, but will not notice
+    /// changes to the data of a file whose header was left as it was.
+    pub fn rehash(&mut self, rehash: bool) -> &mut Self {
+        self.rehash = rehash;
+        self
+    }
+
+    #[inline]
+    /// Sets a token that stops scanning once it is cancelled.
+    ///
+    /// The token is checked before each file is inspected, and before each chunk of up to 16 KiB
+    /// of a file is hashed. Once it is cancelled, `Manifest::scan` and `Manifest::verify` return
+    /// `Error::Cancelled`.
+    pub fn cancel_token(&mut self, token: CancelToken) -> &mut Self {
+        self.cancel = Some(token);
+        self
+    }
+}
+
+impl Default for ManifestOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Manifest {
+    #[inline]
+    /// Records every Redshirt file in the tree rooted at `root`.
+    ///
+    /// Files that do not begin with a Redshirt 1 or Redshirt 2 header are ignored, and symbolic
+    /// links are not followed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any part of the tree cannot be read, or `Error::Cancelled` if the token
+    /// set by `ManifestOptions::cancel_token` is cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::manifest::{Manifest, ManifestOptions};
+    /// use std::fs::File;
+    ///
+    /// let manifest = Manifest::scan("uplink".as_ref(), &ManifestOptions::new()).unwrap();
+    /// manifest.write_to(File::create("uplink.manifest").unwrap()).unwrap();
+    /// ```
+    pub fn scan(root: &Path, options: &ManifestOptions) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+        for file in Files::new(root).map_err(Error::Io)? {
+            cancel::check(options.cancel.as_ref())?;
+            let path = file.map_err(|(_, e)| Error::Io(e))?;
+            if let Some(entry) = scan_file(root, path, options)? {
+                entries.push(entry);
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    #[inline]
+    /// Reads a manifest previously written by `Manifest::write_to`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or the manifest is malformed or lists the same
+    /// path more than once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::manifest::Manifest;
+    ///
+    /// let text = "3454262b4abf291d0b8e60d9a176e1147ddf05d4 v2 12 users/agent/two.usr\n";
+    /// let manifest = Manifest::read_from(text.as_bytes()).unwrap();
+    /// assert_eq!(manifest.entries()[0].payload_len, 12);
+    /// ```
+    pub fn read_from<R: Read>(src: R) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+        for (n, line) in BufReader::new(src).lines().enumerate() {
+            let line = line.map_err(Error::Io)?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = parse_entry(&line)
+                .ok_or_else(|| invalid_data(format!("line {}: malformed entry", n + 1)))?;
+            entries.push(entry);
+        }
+        entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0].path == pair[1].path) {
+            return Err(invalid_data(format!(
+                "duplicate entry for {}",
+                pair[0].path.display()
+            )));
+        }
+        Ok(Self { entries })
+    }
+
+    #[inline]
+    /// Writes this manifest to `dst`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or a path is not valid UTF-8 or contains a line
+    /// break.
+    pub fn write_to<W: Write>(&self, mut dst: W) -> Result<(), Error> {
+        writeln!(dst, "# redshirt manifest").map_err(Error::Io)?;
+        for entry in &self.entries {
+            let path = format_path(&entry.path).ok_or_else(|| {
+                invalid_data(format!("cannot record path {}", entry.path.display()))
+            })?;
+            let version = version_name(entry.version);
+            writeln!(
+                dst,
+                "{} {version} {} {path}",
+                entry.digest, entry.payload_len
+            )
+            .map_err(Error::Io)?;
+        }
+        dst.flush().map_err(Error::Io)
+    }
+
+    #[inline]
+    /// Compares this manifest against the tree rooted at `root`, returning the differences in
+    /// path order.
+    ///
+    /// The tree is scanned with `options`, so enabling `ManifestOptions::rehash` here detects
+    /// changes to the data of Redshirt 2 files even if their headers are unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any part of the tree cannot be read, or `Error::Cancelled` if the token
+    /// set by `ManifestOptions::cancel_token` is cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::manifest::{Manifest, ManifestOptions};
+    /// use std::fs::File;
+    ///
+    /// let manifest = Manifest::read_from(File::open("uplink.manifest").unwrap()).unwrap();
+    /// let mut options = ManifestOptions::new();
+    /// let _ = options.rehash(true);
+    /// for discrepancy in manifest.verify("uplink".as_ref(), &options).unwrap() {
+    ///     println!("{}", discrepancy.path().display());
+    /// }
+    /// ```
+    pub fn verify(
+        &self,
+        root: &Path,
+        options: &ManifestOptions,
+    ) -> Result<Vec<Discrepancy>, Error> {
+        let current = Self::scan(root, options)?;
+        let mut expected = self.entries.iter().peekable();
+        let mut actual = current.entries.into_iter().peekable();
+        let mut discrepancies = Vec::new();
+        loop {
+            let order = match (expected.peek(), actual.peek()) {
+                (Some(e), Some(a)) => e.path.cmp(&a.path),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+            match order {
+                Ordering::Less => {
+                    discrepancies.extend(expected.next().cloned().map(Discrepancy::Missing));
+                }
+                Ordering::Greater => discrepancies.extend(actual.next().map(Discrepancy::Added)),
+                Ordering::Equal => {
+                    let (Some(e), Some(a)) = (expected.next(), actual.next()) else {
+                        unreachable!();
+                    };
+                    if *e != a {
+                        discrepancies.push(Discrepancy::Modified {
+                            expected: e.clone(),
+                            actual: a,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(discrepancies)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the entries in this manifest, in path order.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the entries for `game`'s save files whose encoding scheme differs from the one it
+    /// is expected to use, as reported by `Game::check`, in path order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{manifest::Manifest, Game};
+    ///
+    /// let text = "3454262b4abf291d0b8e60d9a176e1147ddf05d4 v1 12 users/agent.usr\n";
+    /// let manifest = Manifest::read_from(text.as_bytes()).unwrap();
+    /// for (entry, unexpected) in manifest.unexpected_versions(Game::Uplink) {
+    ///     println!("{}: {unexpected}", entry.path.display());
+    /// }
+    /// ```
+    pub fn unexpected_versions(&self, game: Game) -> Vec<(&Entry, UnexpectedVersion)> {
+        self.entries
+            .iter()
+            .filter_map(|entry| Some((entry, game.check(&entry.path, entry.version)?)))
+            .collect()
+    }
+}
+
+impl Discrepancy {
+    #[inline]
+    #[must_use]
+    /// Returns the path of the affected file, relative to the root of the tree.
+    pub fn path(&self) -> &Path {
+        match self {
+            Discrepancy::Missing(entry)
+            | Discrepancy::Added(entry)
+            | Discrepancy::Modified {
+                expected: entry, ..
+            } => &entry.path,
+        }
+    }
+}
+
+/// Creates an `Entry` for the file at `path` under `root`, or returns `None` if it is not a
+/// Redshirt file.
+fn scan_file(
+    root: &Path,
+    path: PathBuf,
+    options: &ManifestOptions,
+) -> Result<Option<Entry>, Error> {
+    let full = root.join(&path);
+    let info = match inspect(&full, false) {
+        Ok(info) => info,
+        Err(Error::BadHeader) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let digest = match info.stored_digest {
+        Some(digest) if !options.rehash => digest,
+        _ => {
+            let mut file = File::open(&full).map_err(Error::Io)?;
+            let _ = advice::sequential(&file);
+            let _ = file
+                .seek(SeekFrom::Start(info.header_len))
+                .map_err(Error::Io)?;
+            v2::hash_payload_cancellable(&mut file, options.cancel.as_ref())
+                .map_err(Error::from_io)?
+        }
+    };
+    Ok(Some(Entry {
+        path,
+        version: info.version,
+        payload_len: info.payload_len,
+        digest,
+    }))
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut fields = line.splitn(4, ' ');
+    let digest = Digest::parse_hex(fields.next()?)?;
+    let version = match fields.next()? {
+        "v1" => Version::V1,
+        "v2" => Version::V2,
+        _ => return None,
+    };
+    let payload_len = fields.next()?.parse().ok()?;
+    let path = fields.next()?;
+    if path.is_empty() {
+        return None;
+    }
+    Some(Entry {
+        path: path.split('/').collect(),
+        version,
+        payload_len,
+        digest,
+    })
+}
+
+/// Formats a relative path with `/` separators, or returns `None` if it cannot be written to a
+/// manifest.
+fn format_path(path: &Path) -> Option<String> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => components.push(name.to_str()?),
+            _ => return None,
+        }
+    }
+    let formatted = components.join("/");
+    if formatted.is_empty() || formatted.contains(['\n', '\r']) {
+        None
+    } else {
+        Some(formatted)
+    }
+}
+
+fn version_name(version: Version) -> &'static str {
+    match version {
+        Version::V1 => "v1",
+        Version::V2 => "v2",
+    }
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Discrepancy, Manifest, ManifestOptions};
+    use crate::{cancel::CancelToken, error::Error, game::Game, v1, v2, version::Version};
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+        process,
+    };
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("redshirt-manifest-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::write(dir.join("a/one.dat"), v1::encode(MSG_DEC)).unwrap();
+        fs::write(dir.join("a/b/two.usr"), v2::encode(MSG_DEC)).unwrap();
+        fs::write(dir.join("plain.txt"), MSG_DEC).unwrap();
+        dir
+    }
+
+    #[test]
+    fn scan_tree() {
+        let root = temp_dir("scan");
+        let manifest = Manifest::scan(&root, &ManifestOptions::new()).unwrap();
+        let entries = manifest.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, Path::new("a/b/two.usr"));
+        assert_eq!(entries[0].version, Version::V2);
+        assert_eq!(entries[1].path, Path::new("a/one.dat"));
+        assert_eq!(entries[1].payload_len, 12);
+        // A Redshirt 1 file is hashed the same way as the equivalent Redshirt 2 file.
+        assert_eq!(entries[0].digest, entries[1].digest);
+        let rehashed = Manifest::scan(&root, ManifestOptions::new().rehash(true)).unwrap();
+        assert_eq!(rehashed, manifest);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn scan_cancelled() {
+        let root = temp_dir("cancelled");
+        let token = CancelToken::new();
+        let mut options = ManifestOptions::new();
+        let _ = options.rehash(true).cancel_token(token.clone());
+        assert_eq!(Manifest::scan(&root, &options).unwrap().entries().len(), 2);
+        token.cancel();
+        assert!(matches!(
+            Manifest::scan(&root, &options),
+            Err(Error::Cancelled)
+        ));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn unexpected_versions() {
+        let root = temp_dir("unexpected");
+        fs::create_dir_all(root.join("users")).unwrap();
+        fs::write(root.join("users/old.usr"), v1::encode(MSG_DEC)).unwrap();
+        let manifest = Manifest::scan(&root, &ManifestOptions::new()).unwrap();
+        let unexpected = manifest.unexpected_versions(Game::Uplink);
+        assert_eq!(unexpected.len(), 1);
+        assert_eq!(unexpected[0].0.path, Path::new("users/old.usr"));
+        assert_eq!(unexpected[0].1.expected, Version::V2);
+        assert!(manifest.unexpected_versions(Game::Darwinia).is_empty());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let root = temp_dir("round-trip");
+        let manifest = Manifest::scan(&root, &ManifestOptions::new()).unwrap();
+        let mut text = Vec::new();
+        manifest.write_to(&mut text).unwrap();
+        assert!(String::from_utf8_lossy(&text)
+            .contains("\n3454262b4abf291d0b8e60d9a176e1147ddf05d4 v2 12 a/b/two.usr\n"));
+        assert_eq!(Manifest::read_from(&text[..]).unwrap(), manifest);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn read_malformed() {
+        let digest = "3454262b4abf291d0b8e60d9a176e1147ddf05d4";
+        for text in [
+            format!("{digest} v3 12 a.dat"),
+            format!("{digest} v2 twelve a.dat"),
+            format!("{digest} v2 12"),
+            format!("{} v2 12 a.dat", &digest[1..]),
+            format!("{digest} v2 12 a.dat\n{digest} v1 12 a.dat"),
+        ] {
+            assert!(matches!(
+                Manifest::read_from(text.as_bytes()),
+                Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::InvalidData
+            ));
+        }
+    }
+
+    #[test]
+    fn verify_tree() {
+        let root = temp_dir("verify");
+        let manifest = Manifest::scan(&root, &ManifestOptions::new()).unwrap();
+        assert!(manifest
+            .verify(&root, &ManifestOptions::new())
+            .unwrap()
+            .is_empty());
+        fs::remove_file(root.join("a/one.dat")).unwrap();
+        fs::write(root.join("three.dat"), v1::encode(MSG_DEC)).unwrap();
+        let mut corrupt = v2::encode(MSG_DEC);
+        *corrupt.last_mut().unwrap() ^= 0xFF;
+        fs::write(root.join("a/b/two.usr"), corrupt).unwrap();
+        let discrepancies = manifest.verify(&root, &ManifestOptions::new()).unwrap();
+        let paths: Vec<_> = discrepancies.iter().map(Discrepancy::path).collect();
+        assert_eq!(paths, [Path::new("a/one.dat"), Path::new("three.dat")]);
+        assert!(matches!(discrepancies[0], Discrepancy::Missing(_)));
+        assert!(matches!(discrepancies[1], Discrepancy::Added(_)));
+        let discrepancies = manifest
+            .verify(&root, ManifestOptions::new().rehash(true))
+            .unwrap();
+        assert!(matches!(
+            &discrepancies[0],
+            Discrepancy::Modified { expected, .. } if expected.path == Path::new("a/b/two.usr")
+        ));
+        assert_eq!(discrepancies.len(), 3);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Names of the metrics recorded through the [`metrics`] facade.
+//!
+//! With the `metrics` feature enabled, verifying a Redshirt 2 stream records its outcome, the
+//! length of its encoded data and how long it took, and the asynchronous `BufferedWriter`s report
+//! how much encoded data they hold in memory. Nothing is recorded unless a recorder, such as a
+//! Prometheus exporter, is installed.
+//!
+//! Verification is recorded by `v2::verify`, `v2::verify_cancellable`, `v2::copy_verified`,
+//! `v2::CopyOptions::copy` and `copy_cancellable`, `batch::verify_dir`, and by `v2::Reader`s
+//! created with `v2::Verification::Eager`. Streams that fail before their data is hashed, for
+//! example because of a bad header, are still counted as failures.
+//!
+//! The names below, and the values of the `error` label, are part of this crate's public API, and
+//! do not change between releases except as a breaking change.
+//!
+//! [`metrics`]: https://docs.rs/metrics
+
+use crate::error::Error;
+use std::time::Instant;
+
+/// A counter of Redshirt 2 streams whose SHA-1 hash was verified successfully.
+pub const FILES_VERIFIED: &str = "redshirt_files_verified_total";
+
+/// A counter of Redshirt 2 streams that failed verification, labelled with `ERROR_LABEL`.
+pub const FILES_FAILED: &str = "redshirt_files_failed_total";
+
+/// The label of `FILES_FAILED` naming the kind of error.
+///
+/// Its value is one of `io`, `bad_header`, `bad_checksum`, `unfinalized`, `invalid_text`,
+/// `payload_too_large` or `cancelled`, after the variants of `Error`.
+pub const ERROR_LABEL: &str = "error";
+
+/// A histogram of the lengths, in bytes, of the encoded data of verified Redshirt 2 streams,
+/// whether or not their hashes matched.
+pub const PAYLOAD_BYTES: &str = "redshirt_payload_bytes";
+
+/// A histogram of how long verifying a Redshirt 2 stream took, in seconds, whatever the outcome.
+pub const VERIFICATION_SECONDS: &str = "redshirt_verification_duration_seconds";
+
+/// A gauge of the number of encoded bytes held in memory by asynchronous `BufferedWriter`s that
+/// have not yet been finished.
+///
+/// Data spilled to disk by `v2::SpillOptions` is not counted.
+pub const BUFFERED_BYTES: &str = "redshirt_buffered_bytes";
+
+#[derive(Debug)]
+/// Times the verification of a single stream.
+pub(crate) struct Verification {
+    start: Instant,
+}
+
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+#[derive(Debug, Default)]
+/// The share of `BUFFERED_BYTES` held by one writer, which is released when it is dropped.
+pub(crate) struct Buffered(u64);
+
+impl Verification {
+    #[inline]
+    pub(crate) fn start() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+
+    #[inline]
+    /// Records the outcome of the verification, and the length of the encoded data if it was
+    /// hashed.
+    pub(crate) fn finish<T>(self, res: &Result<T, Error>, payload_len: Option<u64>) {
+        ::metrics::histogram!(VERIFICATION_SECONDS).record(self.start.elapsed());
+        if let Some(len) = payload_len {
+            ::metrics::histogram!(PAYLOAD_BYTES).record(to_f64(len));
+        }
+        match res {
+            Ok(_) => ::metrics::counter!(FILES_VERIFIED).increment(1),
+            Err(e) => ::metrics::counter!(FILES_FAILED, ERROR_LABEL => error_kind(e)).increment(1),
+        }
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+impl Buffered {
+    #[inline]
+    pub(crate) fn add(&mut self, len: usize) {
+        let len = crate::cursor::len_u64(len);
+        self.0 += len;
+        ::metrics::gauge!(BUFFERED_BYTES).increment(to_f64(len));
+    }
+
+    #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+    #[inline]
+    pub(crate) fn sub(&mut self, len: usize) {
+        let len = crate::cursor::len_u64(len).min(self.0);
+        self.0 -= len;
+        ::metrics::gauge!(BUFFERED_BYTES).decrement(to_f64(len));
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+impl Drop for Buffered {
+    #[inline]
+    fn drop(&mut self) {
+        if self.0 > 0 {
+            ::metrics::gauge!(BUFFERED_BYTES).decrement(to_f64(self.0));
+        }
+    }
+}
+
+#[inline]
+/// Returns the value of `ERROR_LABEL` for an error.
+const fn error_kind(e: &Error) -> &'static str {
+    match e {
+        Error::Io(_) => "io",
+        Error::BadHeader => "bad_header",
+        Error::BadChecksum { .. } => "bad_checksum",
+        Error::Unfinalized => "unfinalized",
+        Error::InvalidText { .. } => "invalid_text",
+        Error::PayloadTooLarge { .. } => "payload_too_large",
+        Error::Cancelled => "cancelled",
+    }
+}
+
+#[inline]
+#[allow(clippy::cast_precision_loss)]
+/// Converts a length to the `f64` that metric values are recorded as. Lengths above 2^53 bytes lose
+/// precision, which no metric backend would preserve anyway.
+fn to_f64(len: u64) -> f64 {
+    len as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ERROR_LABEL, FILES_FAILED, FILES_VERIFIED, PAYLOAD_BYTES, VERIFICATION_SECONDS};
+    use crate::v2;
+    use metrics::{SharedString, Unit};
+    use metrics_util::{
+        debugging::{DebugValue, DebuggingRecorder},
+        CompositeKey,
+    };
+    use std::io::Cursor;
+
+    type Metric = (CompositeKey, Option<Unit>, Option<SharedString>, DebugValue);
+
+    /// Returns the value of the metric with the given name and labels in a snapshot.
+    ///
+    /// Taking a snapshot drains the values recorded by histograms, so every metric must be read
+    /// from the same snapshot.
+    fn value<'a>(snapshot: &'a [Metric], name: &str, labels: &[(&str, &str)]) -> &'a DebugValue {
+        let found = snapshot.iter().find(|(key, ..)| {
+            let key = key.key();
+            key.name() == name
+                && key
+                    .labels()
+                    .map(|label| (label.key(), label.value()))
+                    .eq(labels.iter().copied())
+        });
+        match found {
+            Some((.., value)) => value,
+            None => panic!("{} was not recorded", name),
+        }
+    }
+
+    #[test]
+    fn verification_recorded() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            let encoded = v2::encode(b"Hello world!");
+            let _ = v2::verify(&encoded[..]).unwrap();
+            let _ = v2::ReaderOptions::new()
+                .from_reader(Cursor::new(&encoded[..]))
+                .unwrap();
+            let _ = v2::ReaderOptions::new()
+                .verification(v2::Verification::Lazy)
+                .from_reader(Cursor::new(&encoded[..]))
+                .unwrap();
+            let mut broken = encoded.clone();
+            *broken.last_mut().unwrap() ^= 1;
+            assert!(v2::verify(&broken[..]).is_err());
+            assert!(v2::copy_verified(&b"REDSHIRT2"[..], Vec::new()).is_err());
+        });
+        let snapshot = snapshotter.snapshot().into_vec();
+        assert_eq!(
+            value(&snapshot, FILES_VERIFIED, &[]),
+            &DebugValue::Counter(2)
+        );
+        assert_eq!(
+            value(&snapshot, FILES_FAILED, &[(ERROR_LABEL, "bad_checksum")]),
+            &DebugValue::Counter(1)
+        );
+        assert_eq!(
+            value(&snapshot, FILES_FAILED, &[(ERROR_LABEL, "io")]),
+            &DebugValue::Counter(1)
+        );
+        match value(&snapshot, PAYLOAD_BYTES, &[]) {
+            DebugValue::Histogram(lens) => assert_eq!(lens, &[12.0; 3]),
+            other => panic!("unexpected {}: {:?}", PAYLOAD_BYTES, other),
+        }
+        match value(&snapshot, VERIFICATION_SECONDS, &[]) {
+            DebugValue::Histogram(durations) => assert_eq!(durations.len(), 4),
+            other => panic!("unexpected {}: {:?}", VERIFICATION_SECONDS, other),
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn buffered_bytes_recorded() {
+        use super::BUFFERED_BYTES;
+        use tokio::io::AsyncWriteExt;
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let gauge = || match value(&snapshotter.snapshot().into_vec(), BUFFERED_BYTES, &[]) {
+            // Gauges hold whole byte counts, so they can be compared exactly.
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            DebugValue::Gauge(bytes) => bytes.into_inner() as u64,
+            other => panic!("unexpected {}: {:?}", BUFFERED_BYTES, other),
+        };
+        metrics::with_local_recorder(&recorder, || {
+            futures::executor::block_on(async {
+                let mut finished = v2::tokio::BufferedWriter::new(Vec::new());
+                let mut dropped = v2::tokio::BufferedWriter::new(Vec::new());
+                finished.write_all(b"foo").await.unwrap();
+                dropped.write_all(b"foobar").await.unwrap();
+                assert_eq!(gauge(), 9);
+                let _ = finished.finish().await.unwrap();
+                assert_eq!(gauge(), 6);
+                drop(dropped);
+                assert_eq!(gauge(), 0);
+            });
+        });
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Files of a fixed size, mapped into memory with `mmap` so that they can be encoded into directly.
+//!
+//! This requires the `mmap` feature, and a Unix-like operating system. Like any shared mapping, a
+//! `Mapping` assumes that no other process truncates the file while it is mapped; if one does,
+//! writing to the missing pages raises `SIGBUS`.
+
+use crate::{cursor, error::Error};
+use std::{
+    convert::TryFrom,
+    fs::{File, OpenOptions},
+    io,
+    path::Path,
+};
+
+#[derive(Debug)]
+/// A file mapped into memory, into which a header and up to a fixed amount of data are written.
+pub(crate) struct Mapping {
+    region: sys::Region,
+    file: File,
+    header_len: usize,
+    pos: usize,
+}
+
+impl Mapping {
+    #[inline]
+    /// Creates the file at `path`, truncating it if it exists, sizes it to hold `header` and
+    /// `payload_len` bytes of data, maps it, and copies `header` to the start of it.
+    pub(crate) fn create(path: &Path, header: &[u8], payload_len: u64) -> Result<Self, Error> {
+        let len = cursor::add_len(payload_len, header.len())
+            .and_then(|len| usize::try_from(len).map_err(|_| cursor::overflow_error()))
+            .map_err(Error::Io)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(Error::Io)?;
+        file.set_len(cursor::len_u64(len)).map_err(Error::Io)?;
+        let mut region = sys::Region::map(&file, len).map_err(Error::Io)?;
+        region.as_mut_slice()[..header.len()].copy_from_slice(header);
+        Ok(Self {
+            region,
+            file,
+            header_len: header.len(),
+            pos: header.len(),
+        })
+    }
+
+    #[inline]
+    /// Returns the number of bytes of data written after the header.
+    pub(crate) fn position(&self) -> u64 {
+        cursor::len_u64(self.pos - self.header_len)
+    }
+
+    #[inline]
+    /// Returns the number of bytes of data the mapping was created to hold.
+    pub(crate) fn capacity(&self) -> u64 {
+        cursor::len_u64(self.region.len() - self.header_len)
+    }
+
+    #[inline]
+    /// Copies `buf` into the mapping after the data already written, returning the bytes it was
+    /// copied to so that they can be encoded in place.
+    ///
+    /// Nothing is copied if `buf` does not fit, in which case an `io::Error` wrapping
+    /// `Error::PayloadTooLarge` is returned.
+    pub(crate) fn append(&mut self, buf: &[u8]) -> io::Result<&mut [u8]> {
+        if buf.len() > self.region.len() - self.pos {
+            return Err(io::Error::from(Error::PayloadTooLarge {
+                limit: self.capacity(),
+                attempted: self.position().saturating_add(cursor::len_u64(buf.len())),
+            }));
+        }
+        let start = self.pos;
+        self.pos += buf.len();
+        let dst = &mut self.region.as_mut_slice()[start..self.pos];
+        dst.copy_from_slice(buf);
+        Ok(dst)
+    }
+
+    #[cfg(feature = "redshirt2")]
+    #[inline]
+    /// Returns the header and the data written so far.
+    pub(crate) fn split_mut(&mut self) -> (&mut [u8], &[u8]) {
+        let (header, rest) = self.region.as_mut_slice().split_at_mut(self.header_len);
+        (header, &rest[..self.pos - self.header_len])
+    }
+
+    #[inline]
+    /// Writes the mapping back to the file and unmaps it, then truncates the file to the data
+    /// written if less than its capacity was written, returning the file.
+    pub(crate) fn finish(self) -> io::Result<File> {
+        let Self {
+            region, file, pos, ..
+        } = self;
+        region.flush()?;
+        let len = region.len();
+        drop(region);
+        if pos < len {
+            file.set_len(cursor::len_u64(pos))?;
+        }
+        Ok(file)
+    }
+}
+
+#[allow(unsafe_code)]
+mod sys {
+    use std::{fs::File, io, os::unix::io::AsRawFd, ptr::NonNull, slice};
+
+    #[derive(Debug)]
+    /// A shared, writable mapping of the start of a file, which is unmapped when dropped.
+    pub(super) struct Region {
+        ptr: NonNull<u8>,
+        len: usize,
+    }
+
+    // SAFETY: The mapping is owned by the `Region`, which only hands out references to it through
+    // references to itself, so it can be used and shared across threads as a `Vec<u8>` can.
+    unsafe impl Send for Region {}
+    // SAFETY: See above.
+    unsafe impl Sync for Region {}
+
+    impl Region {
+        /// Maps the first `len` bytes of `file`, which must not be zero, for reading and writing.
+        pub(super) fn map(file: &File, len: usize) -> io::Result<Self> {
+            // SAFETY: A null address lets the kernel choose where to place the mapping, so no
+            // existing memory is affected, and the file descriptor is owned by `file`, which
+            // outlives the call.
+            let ptr = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            NonNull::new(ptr.cast())
+                .map(|ptr| Self { ptr, len })
+                .ok_or_else(|| io::Error::other("mmap returned a null pointer"))
+        }
+
+        #[inline]
+        pub(super) const fn len(&self) -> usize {
+            self.len
+        }
+
+        #[inline]
+        pub(super) fn as_mut_slice(&mut self) -> &mut [u8] {
+            // SAFETY: The mapping is `len` bytes long, readable and writable, and stays mapped
+            // until `self` is dropped, which the borrow prevents.
+            unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+        }
+
+        /// Writes the mapping back to the file, waiting for the write to complete.
+        pub(super) fn flush(&self) -> io::Result<()> {
+            // SAFETY: The address and length are those of a mapping that is still mapped.
+            match unsafe { libc::msync(self.ptr.as_ptr().cast(), self.len, libc::MS_SYNC) } {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error()),
+            }
+        }
+    }
+
+    impl Drop for Region {
+        fn drop(&mut self) {
+            // SAFETY: The address and length are those of a mapping that is still mapped, and no
+            // references to it outlive `self`. Unmapping can only fail for invalid arguments.
+            let _ = unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+        }
+    }
+}
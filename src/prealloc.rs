@@ -0,0 +1,83 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Reserving disk space for files before they are written, for `WriterOptions::preallocate`.
+//!
+//! With the `fallocate` feature on Linux, space is allocated with `fallocate`. Elsewhere, or if the
+//! file system does not support it, the file is only extended with `File::set_len`, which on most
+//! file systems creates a sparse file without reserving any space.
+
+use std::{fs::File, io};
+
+#[inline]
+/// Extends `file` to `len` bytes, allocating the space on disk where possible.
+pub(crate) fn reserve(file: &File, len: u64) -> io::Result<()> {
+    match sys::allocate(file, len) {
+        Ok(true) => Ok(()),
+        Ok(false) => file.set_len(len),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(all(feature = "fallocate", target_os = "linux"))]
+#[allow(unsafe_code)]
+mod sys {
+    use std::{convert::TryFrom, fs::File, io, os::unix::io::AsRawFd};
+
+    /// Allocates the first `len` bytes of `file`, extending it if necessary, returning `false` if
+    /// the file system does not support allocation or `len` does not fit in an `off_t`.
+    pub(super) fn allocate(file: &File, len: u64) -> io::Result<bool> {
+        let Ok(len) = libc::off_t::try_from(len) else {
+            return Ok(false);
+        };
+        // SAFETY: The file descriptor is owned by `file`, so it stays open for the duration of the
+        // call, and `fallocate` does not access memory.
+        if unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, len) } == 0 {
+            return Ok(true);
+        }
+        let e = io::Error::last_os_error();
+        match e.raw_os_error() {
+            Some(libc::EOPNOTSUPP) => Ok(false),
+            _ => Err(e),
+        }
+    }
+}
+
+#[cfg(not(all(feature = "fallocate", target_os = "linux")))]
+mod sys {
+    use std::{fs::File, io};
+
+    #[allow(clippy::unnecessary_wraps)]
+    /// Leaves the file to be extended without allocating any space.
+    pub(super) fn allocate(_file: &File, _len: u64) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reserve, sys};
+    use std::{env, fs, process};
+
+    #[test]
+    fn reserve_extends() {
+        let path = env::temp_dir().join(format!("redshirt-prealloc-{}", process::id()));
+        let file = fs::File::create(&path).unwrap();
+        reserve(&file, 8192).unwrap();
+        assert_eq!(file.metadata().unwrap().len(), 8192);
+        #[cfg(all(feature = "fallocate", target_os = "linux"))]
+        if sys::allocate(&file, 16384).unwrap() {
+            use std::os::unix::fs::MetadataExt;
+            let metadata = file.metadata().unwrap();
+            assert_eq!(metadata.len(), 16384);
+            assert!(metadata.blocks() * 512 >= 16384);
+        }
+        #[cfg(not(all(feature = "fallocate", target_os = "linux")))]
+        assert!(!sys::allocate(&file, 16384).unwrap());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Python bindings, built with [maturin].
+//!
+//! The `redshirt` Python module provides the following functions:
+//!
+//! * `decode(data, version=None) -> bytes`
+//! * `encode(data, version) -> bytes`
+//! * `decode_file(path, version=None) -> bytes`
+//! * `encode_file(path, data, version) -> None`
+//! * `verify(path) -> str`, returning the SHA-1 hash of a Redshirt 2 file as a hex string
+//!
+//! If `version` is `None`, it is detected from the header. `OSError` is raised for I/O errors, and
+//! `ValueError` is raised for invalid data or arguments. The GIL is released while data is being
+//! encoded, decoded or hashed.
+//!
+//! [maturin]: https://www.maturin.rs
+
+// The code generated by `#[pyfunction]` converts `PyErr` into itself.
+#![allow(clippy::useless_conversion)]
+
+use crate::{bindings, error::Error, v2};
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes};
+use std::{fs, path::PathBuf};
+
+#[pyfunction]
+#[pyo3(signature = (data, version = None))]
+/// Decodes Redshirt-encoded data.
+fn decode<'py>(py: Python<'py>, data: &[u8], version: Option<u8>) -> PyResult<Bound<'py, PyBytes>> {
+    py.allow_threads(|| bindings::decode(data, version))
+        .map(|decoded| PyBytes::new_bound(py, &decoded))
+        .map_err(to_py_err)
+}
+
+#[pyfunction]
+/// Encodes data using the given Redshirt version.
+fn encode<'py>(py: Python<'py>, data: &[u8], version: u8) -> PyResult<Bound<'py, PyBytes>> {
+    py.allow_threads(|| bindings::encode(data, version))
+        .map(|encoded| PyBytes::new_bound(py, &encoded))
+        .map_err(to_py_err)
+}
+
+#[pyfunction]
+#[pyo3(signature = (path, version = None))]
+/// Reads and decodes a Redshirt-encoded file.
+fn decode_file(py: Python<'_>, path: PathBuf, version: Option<u8>) -> PyResult<Bound<'_, PyBytes>> {
+    py.allow_threads(|| {
+        let encoded = fs::read(path).map_err(Error::Io)?;
+        bindings::decode(&encoded, version)
+    })
+    .map(|decoded| PyBytes::new_bound(py, &decoded))
+    .map_err(to_py_err)
+}
+
+#[pyfunction]
+/// Encodes data using the given Redshirt version and writes it to a file.
+fn encode_file(py: Python<'_>, path: PathBuf, data: &[u8], version: u8) -> PyResult<()> {
+    py.allow_threads(|| {
+        let encoded = bindings::encode(data, version)?;
+        fs::write(path, encoded).map_err(Error::Io)
+    })
+    .map_err(to_py_err)
+}
+
+#[pyfunction]
+/// Verifies a Redshirt 2 file, returning its SHA-1 hash as a hex string.
+fn verify(py: Python<'_>, path: PathBuf) -> PyResult<String> {
+    py.allow_threads(|| {
+        let file = fs::File::open(path).map_err(Error::Io)?;
+        v2::verify(file).map(|digest| digest.to_string())
+    })
+    .map_err(to_py_err)
+}
+
+#[pymodule]
+fn redshirt(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(decode, m)?)?;
+    m.add_function(wrap_pyfunction!(encode, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_file, m)?)?;
+    m.add_function(wrap_pyfunction!(encode_file, m)?)?;
+    m.add_function(wrap_pyfunction!(verify, m)?)
+}
+
+#[inline]
+fn to_py_err(e: Error) -> PyErr {
+    match e {
+        Error::Io(e) if bindings::is_invalid_argument(&e) => PyValueError::new_err(e.to_string()),
+        Error::Io(e) => e.into(),
+        e => PyValueError::new_err(e.to_string()),
+    }
+}
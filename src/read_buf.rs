@@ -0,0 +1,56 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Support for `Read::read_buf`, which reads into memory that need not be initialised first.
+//!
+//! This requires a nightly compiler, and is enabled with `--cfg redshirt_unstable`.
+
+#![allow(unsafe_code)]
+
+use std::io::{self, BorrowedBuf, BorrowedCursor};
+
+/// Fills the unfilled part of `cursor` using `read`, then passes the bytes read to `decode`, which
+/// may modify them in place, before marking them as filled.
+///
+/// If `decode` fails, the bytes are not marked as filled.
+pub(crate) fn fill<R, D>(mut cursor: BorrowedCursor<'_>, read: R, decode: D) -> io::Result<()>
+where
+    R: FnOnce(BorrowedCursor<'_>) -> io::Result<()>,
+    D: FnOnce(&mut [u8]) -> io::Result<()>,
+{
+    let init = cursor.is_init();
+    // SAFETY: The slice is only used through a `BorrowedBuf`, which never writes uninitialised
+    // bytes to it, so no initialised byte is de-initialised.
+    let mut buf = BorrowedBuf::from(unsafe { cursor.as_mut() });
+    if init {
+        // SAFETY: The whole unfilled part of `cursor`, and so the whole of `buf`, is initialised.
+        let _ = unsafe { buf.set_init() };
+    }
+    read(buf.unfilled())?;
+    let filled = buf.filled_mut();
+    decode(filled)?;
+    let len = filled.len();
+    // SAFETY: `read` filled, and so initialised, the first `len` bytes of the unfilled part of
+    // `cursor`.
+    let _ = unsafe { cursor.advance(len) };
+    Ok(())
+}
+
+/// Fills the unfilled part of `cursor` using `read`, which is given the unfilled part as an
+/// initialised slice, and returns how many bytes it read.
+///
+/// This is for readers that cannot read directly into a `BorrowedCursor`, such as those that only
+/// fill part of the buffer they are given.
+pub(crate) fn fill_init<R>(mut cursor: BorrowedCursor<'_>, read: R) -> io::Result<()>
+where
+    R: FnOnce(&mut [u8]) -> io::Result<usize>,
+{
+    let len = read(cursor.ensure_init())?;
+    // SAFETY: The whole unfilled part of `cursor` was initialised by `ensure_init`.
+    let _ = unsafe { cursor.advance(len) };
+    Ok(())
+}
@@ -0,0 +1,57 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{self, Cursor},
+};
+
+/// A stream whose length can be set directly, as `v1::Writer::set_payload_len` requires.
+///
+/// It is implemented for `File` and for `io::Cursor<Vec<u8>>`.
+pub trait Resize {
+    /// Truncates or extends the stream to `len` bytes, without moving its position.
+    ///
+    /// Bytes added by extending the stream are zeroes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the stream cannot be resized.
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+}
+
+impl Resize for File {
+    #[inline]
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        Self::set_len(self, len)
+    }
+}
+
+impl Resize for Cursor<Vec<u8>> {
+    #[inline]
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        let len = usize::try_from(len).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                "length does not fit in memory on this platform",
+            )
+        })?;
+        let data = self.get_mut();
+        data.try_reserve(len.saturating_sub(data.len()))
+            .map_err(|e| io::Error::new(io::ErrorKind::OutOfMemory, e))?;
+        data.resize(len, 0);
+        Ok(())
+    }
+}
+
+impl<T: Resize + ?Sized> Resize for &mut T {
+    #[inline]
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        (**self).set_len(len)
+    }
+}
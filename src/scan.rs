@@ -0,0 +1,350 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{
+    cursor::{self, BUFFER_LEN},
+    digest::{Digest, DIGEST_LEN},
+    error::Error,
+    v1,
+    v2::Verifier,
+    version::Version,
+};
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    io::{self, Read},
+};
+
+#[derive(Clone, Copy, Debug)]
+/// Options which can be used to configure how `scan` searches a stream.
+pub struct ScanOptions {
+    verify: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// A Redshirt marker found by `scan`.
+pub struct Finding {
+    /// The offset of the marker from the start of the stream.
+    pub offset: u64,
+    /// The encoding scheme named by the marker.
+    pub version: Version,
+    /// Whether the SHA-1 hash following a Redshirt 2 marker matches the data after it, up to the
+    /// next marker or the end of the stream.
+    ///
+    /// This is `None` for Redshirt 1 markers, which have no hash, and if verification is disabled.
+    pub verified: Option<bool>,
+}
+
+#[derive(Debug)]
+/// An iterator over the Redshirt markers in a stream, as returned by `scan`.
+pub struct Scan<R> {
+    src: R,
+    verify: bool,
+    buf: Vec<u8>,
+    base: u64,
+    pending: Option<Pending>,
+    found: VecDeque<Finding>,
+    done: bool,
+}
+
+#[derive(Debug)]
+/// A Redshirt 2 marker whose hash is still being checked.
+struct Pending {
+    offset: u64,
+    version: Version,
+    digest: Vec<u8>,
+    verifier: Option<Verifier>,
+}
+
+impl ScanOptions {
+    #[inline]
+    #[must_use]
+    /// Creates a new set of options with the default configuration.
+    ///
+    /// By default, the hashes of Redshirt 2 streams are not verified.
+    pub const fn new() -> Self {
+        Self { verify: false }
+    }
+
+    #[inline]
+    /// Sets whether the SHA-1 hash following each Redshirt 2 marker is checked against the data
+    /// after it, up to the next marker or the end of the stream.
+    ///
+    /// This requires hashing most of the stream, and delays each finding until the next marker or
+    /// the end of the stream is reached.
+    pub fn verify(&mut self, verify: bool) -> &mut Self {
+        self.verify = verify;
+        self
+    }
+}
+
+impl Default for ScanOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> Scan<R> {
+    #[inline]
+    /// Consumes this `Scan`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.src
+    }
+}
+
+impl<R: Read> Scan<R> {
+    /// Reads the next chunk of `src`, recording the markers it contains.
+    fn step(&mut self) -> io::Result<()> {
+        let kept = self.buf.len();
+        self.buf.resize(kept + BUFFER_LEN, 0);
+        let len = loop {
+            match cursor::read_checked(&mut self.src, &mut self.buf[kept..]) {
+                Ok(len) => break len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    self.buf.truncate(kept);
+                    return Err(e);
+                }
+            }
+        };
+        self.buf.truncate(kept + len);
+        let eof = len == 0;
+        // A marker can only start where there is room for all of it, unless no more data is coming.
+        let end = if eof {
+            self.buf.len()
+        } else {
+            self.buf.len().saturating_sub(v1::MARKER_LEN - 1)
+        };
+        let mut fed = 0;
+        let mut i = 0;
+        while let Some(start) = self
+            .buf
+            .get(i..end)
+            .and_then(|window| window.iter().position(|&b| b == b'R'))
+        {
+            let start = i + start;
+            let version = Version::detect(&self.buf[start..]);
+            i = start + 1;
+            if let Some(version) = version {
+                if let Some(pending) = &mut self.pending {
+                    pending.feed(&self.buf[fed..start]);
+                }
+                let offset =
+                    self.base + u64::try_from(start).map_err(|_| cursor::overflow_error())?;
+                self.found(offset, version);
+                fed = start + v1::MARKER_LEN;
+                i = fed;
+            }
+        }
+        let consumed = fed.max(end);
+        if let Some(pending) = &mut self.pending {
+            pending.feed(&self.buf[fed.min(consumed)..consumed]);
+        }
+        let _ = self.buf.drain(..consumed);
+        self.base += u64::try_from(consumed).map_err(|_| cursor::overflow_error())?;
+        if eof {
+            if let Some(pending) = self.pending.take() {
+                self.found.push_back(pending.finish());
+            }
+            self.done = true;
+        }
+        Ok(())
+    }
+
+    /// Records a marker, finishing the previous one if it is being verified.
+    fn found(&mut self, offset: u64, version: Version) {
+        if !self.verify {
+            self.found.push_back(Finding {
+                offset,
+                version,
+                verified: None,
+            });
+            return;
+        }
+        if let Some(pending) = self.pending.take() {
+            self.found.push_back(pending.finish());
+        }
+        self.pending = Some(Pending {
+            offset,
+            version,
+            digest: Vec::with_capacity(DIGEST_LEN),
+            verifier: None,
+        });
+    }
+}
+
+impl<R: Read> Iterator for Scan<R> {
+    type Item = Result<Finding, Error>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(finding) = self.found.pop_front() {
+                return Some(Ok(finding));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.step() {
+                self.done = true;
+                return Some(Err(Error::Io(e)));
+            }
+        }
+    }
+}
+
+impl Pending {
+    /// Adds data following the marker, first to the stored hash and then to the computed one.
+    fn feed(&mut self, mut data: &[u8]) {
+        if self.version != Version::V2 {
+            return;
+        }
+        if self.digest.len() < DIGEST_LEN {
+            let len = data.len().min(DIGEST_LEN - self.digest.len());
+            self.digest.extend_from_slice(&data[..len]);
+            data = &data[len..];
+        }
+        if self.digest.len() == DIGEST_LEN {
+            let digest = &self.digest;
+            self.verifier
+                .get_or_insert_with(|| {
+                    let mut bytes = [0; DIGEST_LEN];
+                    bytes.copy_from_slice(digest);
+                    Verifier::new(Digest::from_bytes(bytes))
+                })
+                .update(data);
+        }
+    }
+
+    /// Returns the finding for this marker.
+    fn finish(self) -> Finding {
+        Finding {
+            offset: self.offset,
+            version: self.version,
+            verified: match self.version {
+                Version::V1 => None,
+                Version::V2 => Some(self.verifier.is_some_and(|v| v.finish().is_ok())),
+            },
+        }
+    }
+}
+
+#[inline]
+/// Searches `src` for Redshirt markers, such as those of Redshirt files embedded in memory dumps
+/// or archives, returning an iterator over what was found.
+///
+/// Every occurrence of a marker is reported, in order, including markers split across reads from
+/// `src`. Since the markers are short, data that merely happens to contain one is reported too.
+/// The headers following the markers are not validated unless `ScanOptions::verify` is enabled,
+/// in which case each Redshirt 2 stream is assumed to end at the next marker or the end of
+/// `src`; a stream followed by unrelated data will fail verification. `src` is read only once,
+/// and does not need to be seekable.
+///
+/// The iterator yields an `Err` and then ends if an I/O error occurs.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::{scan, v1, v2, ScanOptions, Version};
+///
+/// let mut dump = b"junk".to_vec();
+/// dump.extend_from_slice(&v2::encode(b"Hello world!"));
+/// dump.extend_from_slice(&v1::encode(b"Hello again!"));
+/// let findings = scan(&dump[..], ScanOptions::new().verify(true))
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+/// assert_eq!(findings[0].offset, 4);
+/// assert_eq!(findings[0].verified, Some(true));
+/// assert_eq!(findings[1].offset, 45);
+/// assert_eq!(findings[1].version, Version::V1);
+/// ```
+pub fn scan<R: Read>(src: R, options: &ScanOptions) -> Scan<R> {
+    Scan {
+        src,
+        verify: options.verify,
+        buf: Vec::new(),
+        base: 0,
+        pending: None,
+        found: VecDeque::new(),
+        done: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scan, Finding, ScanOptions};
+    use crate::{cursor::Trickle, error::Error, v1, v2, version::Version};
+    use std::io;
+
+    fn findings<R: io::Read>(src: R, verify: bool) -> Vec<Finding> {
+        scan(src, ScanOptions::new().verify(verify))
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn dump() -> Vec<u8> {
+        let mut data = vec![b'R'; 20_000];
+        data.extend_from_slice(b"REDSHIR");
+        data.extend_from_slice(&v2::encode(b"Hello world!"));
+        data.extend_from_slice(&v1::encode(b"Hello again!"));
+        let mut corrupt = v2::encode(&vec![0; 30_000]);
+        *corrupt.last_mut().unwrap() ^= 1;
+        data.extend_from_slice(&corrupt);
+        data.extend_from_slice(&v2::MARKER);
+        data
+    }
+
+    #[test]
+    fn scan_dump() {
+        let data = dump();
+        let expected = [
+            (20_007, Version::V2, Some(true)),
+            (20_048, Version::V1, None),
+            (20_069, Version::V2, Some(false)),
+            (50_098, Version::V2, Some(false)),
+        ];
+        for verify in [false, true] {
+            let found = findings(&data[..], verify);
+            let found = found
+                .iter()
+                .map(|f| (f.offset, f.version, f.verified))
+                .collect::<Vec<_>>();
+            if verify {
+                assert_eq!(found, expected);
+            } else {
+                let unverified = expected.map(|(offset, version, _)| (offset, version, None));
+                assert_eq!(found, unverified);
+            }
+        }
+    }
+
+    #[test]
+    fn scan_split_reads() {
+        let data = dump();
+        assert_eq!(
+            findings(Trickle::new(&data[..]), true),
+            findings(&data[..], true)
+        );
+        let marker = findings(&v1::MARKER[..], false);
+        assert_eq!(marker.len(), 1);
+        assert!(findings(&v1::MARKER[..8], true).is_empty());
+    }
+
+    #[test]
+    fn scan_error() {
+        struct Failing;
+        impl io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("oops"))
+            }
+        }
+        let mut iter = scan(Failing, &ScanOptions::new());
+        assert!(matches!(iter.next(), Some(Err(Error::Io(_)))));
+        assert!(iter.next().is_none());
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The SHA-1 implementation used by Redshirt 2.
+//!
+//! If the `ring` feature is enabled, hashing is done by `ring`. Otherwise the portable `sha1_smol`
+//! crate is used, which builds for every target that `std` does, including
+//! `wasm32-unknown-unknown`.
+
+use crate::digest::DIGEST_LEN;
+
+#[cfg(feature = "ring")]
+use ring::digest::{Context, SHA1_FOR_LEGACY_USE_ONLY as SHA1};
+
+#[cfg(feature = "ring")]
+#[derive(Clone)]
+/// An in-progress SHA-1 computation.
+pub(crate) struct Sha1(Context);
+
+#[cfg(not(feature = "ring"))]
+#[derive(Clone)]
+/// An in-progress SHA-1 computation.
+pub(crate) struct Sha1(sha1_smol::Sha1);
+
+#[cfg(feature = "ring")]
+impl Sha1 {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self(Context::new(&SHA1))
+    }
+
+    #[inline]
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    #[inline]
+    /// Returns the hash of all data passed to `update`, in standard byte order.
+    pub(crate) fn finish(self) -> [u8; DIGEST_LEN] {
+        let mut out = array!(DIGEST_LEN);
+        out.copy_from_slice(self.0.finish().as_ref());
+        out
+    }
+}
+
+#[cfg(not(feature = "ring"))]
+impl Sha1 {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self(sha1_smol::Sha1::new())
+    }
+
+    #[inline]
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    #[inline]
+    /// Returns the hash of all data passed to `update`, in standard byte order.
+    pub(crate) fn finish(self) -> [u8; DIGEST_LEN] {
+        self.0.digest().bytes()
+    }
+}
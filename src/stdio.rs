@@ -0,0 +1,159 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{cursor, error::Error, game::AnyReader, v1, v2, version::Version};
+use std::io::{self, IsTerminal, Read, Write};
+
+#[inline]
+/// Decodes a Redshirt stream of either encoding scheme from standard input, writing the decoded
+/// data to standard output, and returns the encoding scheme it used.
+///
+/// Neither stream is seeked, so both may be pipes. The encoding scheme is detected from the
+/// marker, as by `AnyReader::from_stream`. A Redshirt 2 stream is verified lazily, so decoded data
+/// is written as it is read, and a hash mismatch is only reported once the whole stream has been
+/// written; a consumer of standard output should discard what it received if this fails.
+///
+/// # Errors
+///
+/// Returns an `Err` if any of the following occurs:
+///
+/// * Standard input is a terminal, in which case the error is `Error::Io` with kind
+///   `InvalidInput`;
+/// * An I/O error occurs;
+/// * Standard input does not begin with a valid Redshirt header;
+/// * The input is a Redshirt 2 stream whose SHA-1 hash is all zeroes or does not match that of the
+///   encoded data.
+///
+/// # Examples
+///
+/// ```no_run
+/// let version = redshirt::decode_stdio().unwrap();
+/// eprintln!("decoded {version} data");
+/// ```
+pub fn decode_stdio() -> Result<Version, Error> {
+    let stdin = io::stdin().lock();
+    if stdin.is_terminal() {
+        return Err(terminal("refusing to read encoded data from a terminal"));
+    }
+    decode_stream(stdin, io::stdout().lock())
+}
+
+#[inline]
+/// Encodes standard input with the given encoding scheme, writing the encoded data to standard
+/// output.
+///
+/// Neither stream is seeked, so both may be pipes. Redshirt 1 data is encoded as it is read.
+/// Redshirt 2 stores the SHA-1 hash of the encoded data before the data itself, so the whole of
+/// standard input is read into memory and encoded in place before anything is written.
+///
+/// # Errors
+///
+/// Returns an `Err` if standard output is a terminal, in which case the error is `Error::Io` with
+/// kind `InvalidInput`, or if an I/O error occurs.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::Version;
+///
+/// redshirt::encode_stdio(Version::V2).unwrap();
+/// ```
+pub fn encode_stdio(version: Version) -> Result<(), Error> {
+    let stdout = io::stdout().lock();
+    if stdout.is_terminal() {
+        return Err(terminal("refusing to write encoded data to a terminal"));
+    }
+    encode_stream(io::stdin().lock(), stdout, version)
+}
+
+/// Decodes `src` into `dst` without seeking either, returning the encoding scheme of `src`.
+pub(crate) fn decode_stream<R: Read, W: Write>(src: R, mut dst: W) -> Result<Version, Error> {
+    let mut reader = AnyReader::from_stream(src)?;
+    let _ = cursor::pump(&mut reader, &mut dst).map_err(Error::from_io)?;
+    dst.flush().map_err(Error::Io)?;
+    Ok(reader.version())
+}
+
+/// Encodes `src` into `dst` with the given encoding scheme without seeking either.
+pub(crate) fn encode_stream<R: Read, W: Write>(
+    mut src: R,
+    mut dst: W,
+    version: Version,
+) -> Result<(), Error> {
+    match version {
+        Version::V1 => {
+            let mut writer = v1::Writer::new(dst)?;
+            let _ = cursor::pump(&mut src, &mut writer).map_err(Error::Io)?;
+            writer.flush().map_err(Error::Io)
+        }
+        Version::V2 => {
+            let mut encoded = vec![u8::default(); v2::HEADER_LEN];
+            let _ = src.read_to_end(&mut encoded).map_err(Error::Io)?;
+            v2::encode_in_place(&mut encoded);
+            dst.write_all(&encoded)
+                .and_then(|()| dst.flush())
+                .map_err(Error::Io)
+        }
+    }
+}
+
+/// Returns the error reported when the encoded side of a pipe is a terminal.
+fn terminal(message: &str) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_stream, encode_stream};
+    use crate::{cursor::Trickle, error::Error, testdata, v1, v2, version::Version};
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+
+    #[test]
+    fn decode_both_versions() {
+        for (version, encoded) in [
+            (Version::V1, v1::encode(MSG_DEC)),
+            (Version::V2, v2::encode(MSG_DEC)),
+        ] {
+            let mut decoded = Vec::new();
+            assert_eq!(
+                decode_stream(Trickle::new(&encoded[..]), &mut decoded).unwrap(),
+                version
+            );
+            assert_eq!(decoded, MSG_DEC);
+        }
+    }
+
+    #[test]
+    fn decode_bad_checksum() {
+        let mut decoded = Vec::new();
+        for broken in [
+            testdata::flip_digest_bit(MSG_DEC, 0),
+            testdata::truncate_payload(MSG_DEC, 6),
+            testdata::wrong_marker(Version::V1, MSG_DEC),
+        ] {
+            let e = decode_stream(&broken.data[..], &mut decoded).unwrap_err();
+            assert!(broken.expects(&e), "{}: {:?}", broken, e);
+        }
+        assert!(matches!(
+            decode_stream(MSG_DEC, &mut decoded),
+            Err(Error::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn encode_both_versions() {
+        for (version, expected) in [
+            (Version::V1, v1::encode(MSG_DEC)),
+            (Version::V2, v2::encode(MSG_DEC)),
+        ] {
+            let mut encoded = Vec::new();
+            encode_stream(Trickle::new(MSG_DEC), &mut encoded, version).unwrap();
+            assert_eq!(encoded, expected);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{
+    cursor,
+    error::{Sink, SinkError},
+};
+use std::io::{self, Write};
+
+#[derive(Debug)]
+/// Writes the same bytes to two output streams, stopping at the first error.
+pub(crate) struct Tee<A, B> {
+    primary: A,
+    secondary: B,
+    failed: Option<Sink>,
+}
+
+impl<A: Write, B: Write> Tee<A, B> {
+    #[inline]
+    pub(crate) fn new(primary: A, secondary: B, header: &[u8]) -> Result<Self, SinkError> {
+        let mut tee = Self {
+            primary,
+            secondary,
+            failed: None,
+        };
+        tee.write_all(header).map(|()| tee)
+    }
+
+    #[inline]
+    pub(crate) fn write_all(&mut self, data: &[u8]) -> Result<(), SinkError> {
+        self.check()?;
+        let res = cursor::write_all(&mut self.primary, data);
+        self.record(Sink::Primary, res)?;
+        let res = cursor::write_all(&mut self.secondary, data);
+        self.record(Sink::Secondary, res)
+    }
+
+    #[inline]
+    pub(crate) fn flush(&mut self) -> Result<(), SinkError> {
+        self.check()?;
+        let res = self.primary.flush();
+        self.record(Sink::Primary, res)?;
+        let res = self.secondary.flush();
+        self.record(Sink::Secondary, res)
+    }
+}
+
+impl<A, B> Tee<A, B> {
+    #[inline]
+    pub(crate) const fn failed(&self) -> Option<Sink> {
+        self.failed
+    }
+
+    #[inline]
+    /// Returns an error if a previous write failed, as the output streams may no longer match.
+    pub(crate) fn check(&self) -> Result<(), SinkError> {
+        match self.failed {
+            Some(sink) => Err(SinkError::new(
+                sink,
+                io::Error::other("a previous write to this sink failed"),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn into_inner(self) -> (A, B) {
+        (self.primary, self.secondary)
+    }
+
+    fn record<T>(&mut self, sink: Sink, res: io::Result<T>) -> Result<T, SinkError> {
+        res.map_err(|e| {
+            self.failed = Some(sink);
+            SinkError::new(sink, e)
+        })
+    }
+}
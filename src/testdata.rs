@@ -0,0 +1,235 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Generators for valid and deliberately broken Redshirt files, for use in tests.
+//!
+//! Enabled by the `testdata` feature. Every file is built with `v1::encode` or `v2::encode` and
+//! then altered, so the generators always agree with the encoders, including the byte order of
+//! the SHA-1 hash in a Redshirt 2 header.
+//!
+//! # Examples
+//!
+//! ```
+//! use redshirt::{testdata, v2};
+//!
+//! let file = testdata::v2_file(b"Hello world!");
+//! assert_eq!(v2::decode(&file).unwrap(), b"Hello world!");
+//!
+//! let broken = testdata::flip_digest_bit(b"Hello world!", 7);
+//! let e = v2::decode(&broken.data).unwrap_err();
+//! assert!(broken.expects(&e), "{}: {}", broken, e);
+//! ```
+
+use crate::{digest::DIGEST_LEN, error::Error, v1, v2, version::Version};
+use std::fmt;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A Redshirt file that readers must reject, together with a description of what is wrong with
+/// it.
+pub struct Broken {
+    /// The encoding scheme of the file before it was broken.
+    pub version: Version,
+    /// The data that was encoded.
+    pub payload: Vec<u8>,
+    /// The contents of the broken file.
+    pub data: Vec<u8>,
+    /// A human-readable description of the file and how it was broken.
+    pub description: String,
+    /// The error that readers report.
+    expected: Expected,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The errors that a `Broken` file can cause.
+enum Expected {
+    /// `Error::BadHeader`.
+    BadHeader,
+    /// `Error::BadChecksum`.
+    BadChecksum,
+}
+
+impl Broken {
+    #[inline]
+    #[must_use]
+    /// Returns whether `error` is the error that readers report for this file.
+    ///
+    /// Readers that verify lazily report the error as an `io::Error` that wraps an `Error`, which
+    /// can be recovered with `get_ref` and `downcast_ref`.
+    pub fn expects(&self, error: &Error) -> bool {
+        match self.expected {
+            Expected::BadHeader => matches!(error, Error::BadHeader),
+            Expected::BadChecksum => matches!(error, Error::BadChecksum { .. }),
+        }
+    }
+}
+
+impl fmt::Display for Broken {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
+#[inline]
+#[must_use]
+/// Returns a valid Redshirt 1 file containing `payload`.
+///
+/// This is the same as `v1::encode`.
+pub fn v1_file(payload: &[u8]) -> Vec<u8> {
+    v1::encode(payload)
+}
+
+#[inline]
+#[must_use]
+/// Returns a valid Redshirt 2 file containing `payload`.
+///
+/// This is the same as `v2::encode`.
+pub fn v2_file(payload: &[u8]) -> Vec<u8> {
+    v2::encode(payload)
+}
+
+#[inline]
+#[must_use]
+/// Returns a Redshirt 2 file containing `payload`, with one bit of the SHA-1 hash in its header
+/// flipped. Readers that verify the hash fail with `Error::BadChecksum`.
+///
+/// `bit` counts from the least significant bit of the first byte of the hash, as stored in the
+/// file.
+///
+/// # Panics
+///
+/// Panics if `bit` is not less than 160, the number of bits in the hash.
+pub fn flip_digest_bit(payload: &[u8], bit: usize) -> Broken {
+    assert!(bit < DIGEST_LEN * 8, "bit {} is outside the hash", bit);
+    let mut data = v2::encode(payload);
+    data[v2::HEADER_LEN - DIGEST_LEN + bit / 8] ^= 1 << (bit % 8);
+    Broken {
+        version: Version::V2,
+        payload: payload.to_vec(),
+        data,
+        description: format!(
+            "Redshirt 2 file with a {}-byte payload and bit {} of its SHA-1 hash flipped",
+            payload.len(),
+            bit
+        ),
+        expected: Expected::BadChecksum,
+    }
+}
+
+#[inline]
+#[must_use]
+/// Returns a Redshirt 2 file containing `payload`, cut short so that only the first `len` bytes of
+/// the encoded payload remain. The header is left intact, so readers that verify the hash fail
+/// with `Error::BadChecksum`.
+///
+/// Redshirt 1 has no hash, so a truncated Redshirt 1 file is indistinguishable from a valid one
+/// with a shorter payload.
+///
+/// # Panics
+///
+/// Panics if `len` is not less than the length of `payload`.
+pub fn truncate_payload(payload: &[u8], len: usize) -> Broken {
+    assert!(
+        len < payload.len(),
+        "cannot truncate a {}-byte payload to {} bytes",
+        payload.len(),
+        len
+    );
+    let mut data = v2::encode(payload);
+    data.truncate(v2::HEADER_LEN + len);
+    Broken {
+        version: Version::V2,
+        payload: payload.to_vec(),
+        data,
+        description: format!(
+            "Redshirt 2 file with a {}-byte payload truncated to {} bytes",
+            payload.len(),
+            len
+        ),
+        expected: Expected::BadChecksum,
+    }
+}
+
+#[inline]
+#[must_use]
+/// Returns a file containing `payload` encoded with the given encoding scheme, with the first
+/// byte of its marker changed from `R` to `r`. Readers fail with `Error::BadHeader`.
+pub fn wrong_marker(version: Version, payload: &[u8]) -> Broken {
+    let mut data = match version {
+        Version::V1 => v1::encode(payload),
+        Version::V2 => v2::encode(payload),
+    };
+    data[0].make_ascii_lowercase();
+    Broken {
+        version,
+        payload: payload.to_vec(),
+        data,
+        description: format!(
+            "{} file with a {}-byte payload and a lowercase first marker byte",
+            version,
+            payload.len()
+        ),
+        expected: Expected::BadHeader,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{flip_digest_bit, truncate_payload, v1_file, v2_file, wrong_marker};
+    use crate::{v1, v2, version::Version};
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+
+    #[test]
+    fn valid_files() {
+        assert_eq!(v1::decode(&v1_file(MSG_DEC)).unwrap(), MSG_DEC);
+        assert_eq!(v2::decode(&v2_file(MSG_DEC)).unwrap(), MSG_DEC);
+        assert!(v2::verify(&v2_file(b"")[..]).is_ok());
+    }
+
+    #[test]
+    fn broken_files_are_rejected() {
+        let mut broken = vec![
+            truncate_payload(MSG_DEC, 0),
+            truncate_payload(MSG_DEC, 11),
+            wrong_marker(Version::V1, MSG_DEC),
+            wrong_marker(Version::V2, MSG_DEC),
+        ];
+        broken.extend((0..160).map(|bit| flip_digest_bit(MSG_DEC, bit)));
+        for file in broken {
+            let e = match file.version {
+                Version::V1 => v1::decode(&file.data),
+                Version::V2 => v2::decode(&file.data),
+            }
+            .unwrap_err();
+            assert!(file.expects(&e), "{}: {:?}", file, e);
+            assert_eq!(file.payload, MSG_DEC);
+        }
+    }
+
+    #[test]
+    fn descriptions() {
+        assert_eq!(
+            flip_digest_bit(MSG_DEC, 3).to_string(),
+            "Redshirt 2 file with a 12-byte payload and bit 3 of its SHA-1 hash flipped"
+        );
+        assert_eq!(
+            truncate_payload(MSG_DEC, 4).to_string(),
+            "Redshirt 2 file with a 12-byte payload truncated to 4 bytes"
+        );
+        assert_eq!(
+            wrong_marker(Version::V1, MSG_DEC).to_string(),
+            "Redshirt 1 file with a 12-byte payload and a lowercase first marker byte"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "outside the hash")]
+    fn flip_digest_bit_out_of_range() {
+        let _ = flip_digest_bit(MSG_DEC, 160);
+    }
+}
@@ -0,0 +1,443 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Helpers for examining the text in decoded data.
+
+use crate::{
+    cursor::{read_checked, Scratch},
+    error::Error,
+};
+use std::{
+    convert::TryFrom,
+    error,
+    fmt::{self, Debug, Display, Formatter},
+    io::{self, BufRead, BufReader, Read},
+    mem,
+};
+
+/// An iterator over the runs of printable characters in a stream, as returned by
+/// `extract_strings`.
+pub struct Strings<R: Read> {
+    src: R,
+    min_len: usize,
+    buffer: Scratch,
+    pos: usize,
+    len: usize,
+    offset: u64,
+    run_start: u64,
+    run: String,
+    done: bool,
+}
+
+/// An iterator over the lines of a text stream.
+///
+/// Lines may end with either LF or CRLF, and the line ending is not included in the returned
+/// `String`. A final line without a line ending is still returned.
+///
+/// Each line must be valid UTF-8. Optionally, lines can be limited to a maximum length, which
+/// guards against reading a whole binary file into memory as a single line. Any error is reported
+/// along with the number of the line on which it occurred, after which the iterator ends.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::text::LineReader;
+///
+/// let lines: Vec<_> = LineReader::new(&b"first\r\nsecond\n"[..])
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!(lines, ["first", "second"]);
+/// ```
+pub struct LineReader<R: Read> {
+    src: BufReader<R>,
+    max_len: Option<usize>,
+    line: u64,
+    done: bool,
+}
+
+#[derive(Debug)]
+/// An error reading a line from a `LineReader`.
+pub struct LineError {
+    line: u64,
+    inner: io::Error,
+}
+
+#[inline]
+/// Returns an iterator over the runs of at least `min_len` printable characters in `src`, along
+/// with the offset of each run from the position at which reading started.
+///
+/// Printable ASCII characters, tabs and printable Latin-1 characters (`U+00A0` to `U+00FF`) are
+/// considered printable; each byte is mapped to the character with the same code point. The input
+/// is read in fixed-size chunks, so only the current run is held in memory. A `min_len` of zero is
+/// treated as one.
+///
+/// Pass a `v1::Reader` or `v2::Reader` to extract strings from Redshirt-encoded data.
+///
+/// # Errors
+///
+/// If reading from `src` fails, the iterator yields the error and then ends.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::{text, v1};
+/// use std::io::Cursor;
+///
+/// let encoded = v1::encode(b"\x00\x01agent\x00\x02x\x03");
+/// let reader = v1::Reader::new(Cursor::new(encoded)).unwrap();
+/// let strings: Vec<_> = text::extract_strings(reader, 4)
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!(strings, [(2, "agent".to_owned())]);
+/// ```
+pub fn extract_strings<R: Read>(src: R, min_len: usize) -> Strings<R> {
+    Strings {
+        src,
+        min_len: min_len.max(1),
+        buffer: Scratch::new(),
+        pos: 0,
+        len: 0,
+        offset: 0,
+        run_start: 0,
+        run: String::new(),
+        done: false,
+    }
+}
+
+impl<R: Read> Strings<R> {
+    #[inline]
+    /// Consumes this `Strings`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.src
+    }
+
+    /// Takes the current run if it is long enough to be yielded, and starts a new one.
+    fn take_run(&mut self) -> Option<(u64, String)> {
+        let run = mem::take(&mut self.run);
+        let start = self.run_start;
+        if run.chars().count() >= self.min_len {
+            Some((start, run))
+        } else {
+            None
+        }
+    }
+}
+
+impl<R: Read> Iterator for Strings<R> {
+    type Item = io::Result<(u64, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if self.pos == self.len {
+                match read_checked(&mut self.src, &mut self.buffer) {
+                    Ok(0) => {
+                        self.done = true;
+                        return self.take_run().map(Ok);
+                    }
+                    Ok(len) => {
+                        self.pos = 0;
+                        self.len = len;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                }
+                continue;
+            }
+            let byte = self.buffer[self.pos];
+            self.pos += 1;
+            self.offset += 1;
+            if is_printable(byte) {
+                if self.run.is_empty() {
+                    self.run_start = self.offset - 1;
+                }
+                self.run.push(char::from(byte));
+            } else if let Some(run) = self.take_run() {
+                return Some(Ok(run));
+            }
+        }
+        None
+    }
+}
+
+impl<R: Read> LineReader<R> {
+    #[inline]
+    /// Creates a new `LineReader` with no limit on the length of a line.
+    pub fn new(src: R) -> Self {
+        Self {
+            src: BufReader::new(src),
+            max_len: None,
+            line: 0,
+            done: false,
+        }
+    }
+
+    #[inline]
+    /// Creates a new `LineReader` which fails on any line longer than `max_len` bytes, not
+    /// counting the line ending.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::text::LineReader;
+    ///
+    /// let mut lines = LineReader::with_max_len(&b"short\nmuch too long\n"[..], 8);
+    /// assert_eq!(lines.next().unwrap().unwrap(), "short");
+    /// assert_eq!(lines.next().unwrap().unwrap_err().line(), 2);
+    /// assert!(lines.next().is_none());
+    /// ```
+    pub fn with_max_len(src: R, max_len: usize) -> Self {
+        Self {
+            max_len: Some(max_len),
+            ..Self::new(src)
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the number of lines read so far.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    #[inline]
+    /// Consumes this `LineReader`, returning the underlying reader.
+    ///
+    /// Any data that was buffered but not yet returned as a line is lost.
+    pub fn into_inner(self) -> R {
+        self.src.into_inner()
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut buf = Vec::new();
+        let len = match self.max_len {
+            // Allow for a CRLF line ending, so that a line of exactly `max_len` bytes fits.
+            Some(max_len) => {
+                let limit = u64::try_from(max_len).unwrap_or(u64::MAX).saturating_add(2);
+                (&mut self.src).take(limit).read_until(b'\n', &mut buf)?
+            }
+            None => self.src.read_until(b'\n', &mut buf)?,
+        };
+        if len == 0 {
+            return Ok(None);
+        }
+        if buf.last() == Some(&b'\n') {
+            let _ = buf.pop();
+            if buf.last() == Some(&b'\r') {
+                let _ = buf.pop();
+            }
+        }
+        if let Some(max_len) = self.max_len {
+            if buf.len() > max_len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line is longer than {max_len} bytes"),
+                ));
+            }
+        }
+        String::from_utf8(buf)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl<R: Read> Iterator for LineReader<R> {
+    type Item = Result<String, LineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        self.line += 1;
+        match self.read_line() {
+            Ok(Some(line)) => Some(Ok(line)),
+            Ok(None) => {
+                self.done = true;
+                self.line -= 1;
+                None
+            }
+            Err(inner) => {
+                self.done = true;
+                Some(Err(LineError {
+                    line: self.line,
+                    inner,
+                }))
+            }
+        }
+    }
+}
+
+impl<R: Read + Debug> Debug for LineReader<R> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LineReader")
+            .field("src", self.src.get_ref())
+            .field("max_len", &self.max_len)
+            .field("line", &self.line)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LineError {
+    #[inline]
+    #[must_use]
+    /// Returns the number of the line on which the error occurred, starting from 1.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    #[inline]
+    #[must_use]
+    /// Consumes this `LineError`, returning the underlying I/O error.
+    pub fn into_inner(self) -> io::Error {
+        self.inner
+    }
+}
+
+impl Display for LineError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.inner)
+    }
+}
+
+impl error::Error for LineError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+impl From<LineError> for io::Error {
+    #[inline]
+    /// Converts a `LineError` into an `io::Error` of the same kind, whose message includes the
+    /// line number.
+    fn from(e: LineError) -> Self {
+        io::Error::new(e.inner.kind(), e)
+    }
+}
+
+impl<R: Read + Debug> Debug for Strings<R> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Strings")
+            .field("src", &self.src)
+            .field("min_len", &self.min_len)
+            .field("offset", &self.offset)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Converts decoded data to a `String`, failing with `Error::InvalidText` if it is not UTF-8.
+pub(crate) fn to_string(data: Vec<u8>) -> Result<String, Error> {
+    String::from_utf8(data).map_err(|e| Error::InvalidText {
+        valid_up_to: e.utf8_error().valid_up_to(),
+    })
+}
+
+/// Converts decoded data to a `String`, replacing invalid UTF-8 with `U+FFFD`.
+pub(crate) fn to_string_lossy(data: Vec<u8>) -> String {
+    String::from_utf8(data).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+fn is_printable(byte: u8) -> bool {
+    matches!(byte, b'\t' | 0x20..=0x7E | 0xA0..=0xFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_strings, LineReader};
+    use crate::cursor::BUFFER_LEN;
+    use std::io::{self, Cursor, Read};
+
+    fn strings<R: Read>(src: R, min_len: usize) -> Vec<(u64, String)> {
+        extract_strings(src, min_len)
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn extract_runs() {
+        let data = b"ab\x00Hello\tworld\x01\x02caf\xe9\x7fend";
+        assert_eq!(
+            strings(&data[..], 3),
+            [
+                (3, "Hello\tworld".to_owned()),
+                (16, "caf\u{e9}".to_owned()),
+                (21, "end".to_owned()),
+            ]
+        );
+        assert_eq!(strings(&data[..], 0)[0], (0, "ab".to_owned()));
+        assert!(strings(&b""[..], 1).is_empty());
+    }
+
+    #[test]
+    fn extract_run_across_chunks() {
+        let mut data = vec![0; BUFFER_LEN - 3];
+        data.extend_from_slice(b"straddle\x00");
+        data.resize(BUFFER_LEN * 2, 0);
+        data.extend_from_slice(b"tail");
+        assert_eq!(
+            strings(Cursor::new(data), 4),
+            [
+                ((BUFFER_LEN - 3) as u64, "straddle".to_owned()),
+                ((BUFFER_LEN * 2) as u64, "tail".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_reports_errors() {
+        struct Failing;
+
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("failed"))
+            }
+        }
+
+        let mut iter = extract_strings(Failing, 4);
+        assert!(matches!(iter.next(), Some(Err(_))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn read_lines_mixed_endings() {
+        let mut lines = LineReader::new(&b"one\r\ntwo\n\nthree\r"[..]);
+        assert_eq!(lines.next().unwrap().unwrap(), "one");
+        assert_eq!(lines.next().unwrap().unwrap(), "two");
+        assert_eq!(lines.next().unwrap().unwrap(), "");
+        assert_eq!(lines.next().unwrap().unwrap(), "three\r");
+        assert!(lines.next().is_none());
+        assert_eq!(lines.line(), 4);
+    }
+
+    #[test]
+    fn read_lines_max_len() {
+        let lines: Vec<_> = LineReader::with_max_len(&b"1234\r\n1234\n"[..], 4)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(lines, ["1234", "1234"]);
+        let mut lines = LineReader::with_max_len(&b"1234\n12345\n"[..], 4);
+        assert!(lines.next().unwrap().is_ok());
+        let e = lines.next().unwrap().unwrap_err();
+        assert_eq!(e.line(), 2);
+        assert_eq!(e.into_inner().kind(), io::ErrorKind::InvalidData);
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn read_lines_invalid_utf8() {
+        let mut lines = LineReader::new(&b"ok\n\xff\n"[..]);
+        assert!(lines.next().unwrap().is_ok());
+        let e = lines.next().unwrap().unwrap_err();
+        assert!(e.to_string().starts_with("line 2: "));
+        assert_eq!(io::Error::from(e).kind(), io::ErrorKind::InvalidData);
+    }
+}
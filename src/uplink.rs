@@ -0,0 +1,210 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Helpers for the data stored in decoded Uplink files.
+//!
+//! Uplink saves and other Redshirt-protected game files are written by Introversion's serializer,
+//! so once decoded by `v1::Reader` or `v2::Reader`, they are a sequence of little-endian integers,
+//! floats, single-byte fields and length-prefixed strings. The `io` module reads and writes
+//! strings from any `Read` or `Write` implementation. The `ReadPrimitives` and `WritePrimitives`
+//! extension traits read and write every kind of field, and `read_darray`, `read_llist` and
+//! `read_btree` read the serializer's three container types. Together, these are enough to walk
+//! the structure of a whole save.
+//!
+//! Each reader has a writer counterpart, and data read from a file and written back unchanged is
+//! identical to the original, with the exceptions below.
+//!
+//! `probe_save_version` and `probe_path` read the version tag at the start of a save, so that
+//! saves from incompatible game versions can be recognised without reading them further. `Options`
+//! loads, edits and saves the game's `options` file, and `validate_users_dir` checks every save in
+//! a player's `users` directory.
+//!
+//! # Re-saving files
+//!
+//! A few details of the game's own output are not obvious from the values read:
+//!
+//! * The game writes a null string with a length of `-1`, and an empty string with a length of 1,
+//!   for its NUL terminator. `read_string` reads both as an empty string, which `write_string`
+//!   writes in the second form. Use `read_optional_string` and `write_optional_string` for fields
+//!   that may be null.
+//! * A string ends at its first NUL byte. Bytes after it, which the game never writes, are lost.
+//! * The game writes every slot a `DArray` has allocated, used or not, and allocates several slots
+//!   at a time. The unused slots are kept by `read_darray`, so a `DArray` read from a file is
+//!   written back unchanged; for one built from scratch, `ContainerOptions::darray_step` adds
+//!   the same padding.
+//! * The shape of a `BTree` depends on the order in which its entries were inserted. `read_btree`
+//!   returns entries in the order they are stored, from which `write_btree` rebuilds the same
+//!   shape; new entries should be appended, rather than the entries sorted.
+//!
+//! # Offsets
+//!
+//! Errors returned by `ReadPrimitives` record the offset of the field being read, taken from the
+//! reader's `Seek` implementation. Seeking this crate's readers uses positions in the decoded data,
+//! so when reading through a `v1::Reader` or `v2::Reader`, the offset is the one a hex editor
+//! shows for the decoded file, not the position in the encoded file.
+
+mod containers;
+pub mod io;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+mod options;
+mod primitives;
+mod probe;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+mod users;
+
+pub use containers::{
+    read_btree, read_darray, read_llist, write_btree, write_darray, write_llist, ContainerOptions,
+    MAX_BTREE_DEPTH, MAX_CONTAINER_LEN,
+};
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+pub use options::{OptionEntry, Options};
+pub use primitives::{ReadPrimitives, WritePrimitives};
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+pub use probe::probe_path;
+pub use probe::{probe_save_version, SAVE_VERSION_LEN};
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+pub use users::{validate_users_dir, FileKind, FileReport};
+
+use std::{
+    error,
+    fmt::{self, Display, Formatter},
+};
+
+/// The longest string, in bytes including its NUL terminator, that `io::read_string` accepts.
+///
+/// A corrupt length prefix would otherwise cause gigabytes to be allocated.
+pub const MAX_STRING_LEN: usize = 20_000;
+
+#[derive(Debug)]
+/// Represents errors that may occur when reading or writing Uplink data.
+pub struct UplinkError {
+    kind: UplinkErrorKind,
+    offset: Option<u64>,
+}
+
+#[derive(Debug)]
+/// The cause of an `UplinkError`.
+pub enum UplinkErrorKind {
+    /// An I/O error occurred.
+    Io(std::io::Error),
+    /// A length prefix is negative (other than the `-1` sentinel for a null string), or exceeds
+    /// `MAX_STRING_LEN`.
+    BadLength(i64),
+    /// The count at the start of a `DArray` or `LList` is negative, or exceeds the configured
+    /// limit.
+    BadCount(i64),
+    /// A `DArray` slot has an index other than its own position or `-1`.
+    BadIndex(i64),
+    /// A `BTree` node lies deeper than the configured limit.
+    TooDeep(usize),
+    /// A boolean field holds a value other than 0 or 1.
+    BadBool(u8),
+    /// A string or character field cannot be written, since it contains a character outside
+    /// Latin-1, or a string contains NUL.
+    Unencodable(char),
+}
+
+impl UplinkError {
+    #[inline]
+    pub(crate) const fn at(mut self, offset: Option<u64>) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the cause of the error.
+    pub const fn kind(&self) -> &UplinkErrorKind {
+        &self.kind
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the offset of the field that could not be read, if known.
+    ///
+    /// This is only known for errors returned by `ReadPrimitives`, and only if the reader could
+    /// report its position.
+    pub const fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    #[inline]
+    #[must_use]
+    /// Consumes this `UplinkError`, returning its cause.
+    pub fn into_kind(self) -> UplinkErrorKind {
+        self.kind
+    }
+}
+
+impl From<UplinkErrorKind> for UplinkError {
+    #[inline]
+    /// Converts an `UplinkErrorKind` into an `UplinkError` with no offset.
+    fn from(kind: UplinkErrorKind) -> Self {
+        Self { kind, offset: None }
+    }
+}
+
+impl Display for UplinkErrorKind {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            UplinkErrorKind::Io(inner) => Display::fmt(inner, f),
+            UplinkErrorKind::BadLength(len) => write!(f, "bad string length {len}"),
+            UplinkErrorKind::BadCount(len) => write!(f, "bad container length {len}"),
+            UplinkErrorKind::BadIndex(index) => write!(f, "bad slot index {index}"),
+            UplinkErrorKind::TooDeep(depth) => write!(f, "tree node at depth {depth}"),
+            UplinkErrorKind::BadBool(b) => write!(f, "bad boolean {b:#04x}"),
+            UplinkErrorKind::Unencodable(c) => write!(f, "unencodable character {c:?}"),
+        }
+    }
+}
+
+impl Display for UplinkError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "offset {offset:#x}: {}", self.kind),
+            None => Display::fmt(&self.kind, f),
+        }
+    }
+}
+
+impl error::Error for UplinkError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.kind {
+            UplinkErrorKind::Io(inner) => Some(inner),
+            _ => None,
+        }
+    }
+}
+
+impl From<UplinkError> for std::io::Error {
+    #[inline]
+    /// Converts an `UplinkError` into an `io::Error`.
+    ///
+    /// An I/O error with no offset is unwrapped. Otherwise, the result has the kind of the
+    /// underlying I/O error, `InvalidInput` for `UplinkErrorKind::Unencodable`, or `InvalidData`,
+    /// and its message includes the offset.
+    fn from(e: UplinkError) -> Self {
+        match e {
+            UplinkError {
+                kind: UplinkErrorKind::Io(inner),
+                offset: None,
+            } => inner,
+            UplinkError {
+                kind: UplinkErrorKind::Io(ref inner),
+                ..
+            } => Self::new(inner.kind(), e),
+            UplinkError {
+                kind: UplinkErrorKind::Unencodable(_),
+                ..
+            } => Self::new(std::io::ErrorKind::InvalidInput, e),
+            _ => Self::new(std::io::ErrorKind::InvalidData, e),
+        }
+    }
+}
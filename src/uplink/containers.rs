@@ -0,0 +1,821 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use super::{
+    primitives::{boolean, bytes, field},
+    ReadPrimitives, UplinkError, UplinkErrorKind, WritePrimitives,
+};
+use std::{
+    convert::TryFrom,
+    io::{Read, Seek, Write},
+};
+
+/// The default limit on the number of slots or elements in a container.
+pub const MAX_CONTAINER_LEN: usize = 1 << 16;
+
+/// The default limit on the depth of a `BTree`.
+///
+/// `BTree`s are not balanced, so one built from keys inserted in order is as deep as it is long.
+pub const MAX_BTREE_DEPTH: usize = 4096;
+
+/// The index written in place of an unused `DArray` slot.
+const UNUSED_SLOT: i32 = -1;
+
+#[derive(Clone, Copy, Debug)]
+/// Options which can be used to configure how `DArray`, `LList` and `BTree` containers are read
+/// and written.
+pub struct ContainerOptions {
+    max_len: usize,
+    max_depth: usize,
+    darray_step: usize,
+}
+
+impl ContainerOptions {
+    #[inline]
+    #[must_use]
+    /// Creates a new set of options with the default configuration.
+    ///
+    /// By default, containers with more than `MAX_CONTAINER_LEN` slots or elements, and `BTree`s
+    /// deeper than `MAX_BTREE_DEPTH`, are rejected, and `DArray`s are written with exactly the
+    /// slots given.
+    pub const fn new() -> Self {
+        Self {
+            max_len: MAX_CONTAINER_LEN,
+            max_depth: MAX_BTREE_DEPTH,
+            darray_step: 1,
+        }
+    }
+
+    #[inline]
+    /// Sets the largest number of slots or elements a container may have.
+    ///
+    /// A corrupt count would otherwise cause a huge allocation, or a long series of reads that all
+    /// fail. Space for every slot of a `DArray` is allocated up front. Writing a larger container
+    /// also fails, since it could not be read back with the same options.
+    pub fn max_len(&mut self, max_len: usize) -> &mut Self {
+        self.max_len = max_len;
+        self
+    }
+
+    #[inline]
+    /// Sets the largest depth a `BTree` may have, where a `BTree` with only a root node has a
+    /// depth of 1.
+    ///
+    /// `BTree`s are read without recursion, so this limit exists only to reject corrupt data early.
+    /// Writing a deeper `BTree` also fails.
+    pub fn max_depth(&mut self, max_depth: usize) -> &mut Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    #[inline]
+    /// Sets the multiple to which the number of slots in a written `DArray` is rounded up, by
+    /// appending unused slots.
+    ///
+    /// The game writes every slot a `DArray` has allocated, and a `DArray` grows several slots at
+    /// a time, so a `DArray` built from scratch can be padded to match. A `DArray` that was read
+    /// from a file already includes its unused slots, and needs no padding. A step of 0 or 1
+    /// disables padding, which is the default.
+    pub fn darray_step(&mut self, step: usize) -> &mut Self {
+        self.darray_step = step;
+        self
+    }
+
+    #[inline]
+    /// Reads a `DArray`, using the current set of options.
+    ///
+    /// A `DArray` is written as an `int` giving its number of slots, followed by each slot in
+    /// order. A used slot is written as its index, counting from 0, followed by its element; an
+    /// unused slot is written as an index of `-1` with no element. `read_elem` is called once for
+    /// each used slot, and may itself return `None`, for example for a null string. The result
+    /// has one entry per slot, in which unused slots are `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, `read_elem` fails, the number of slots is negative
+    /// or exceeds the limit, or a slot has an index other than its own or `-1`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::uplink::{ContainerOptions, ReadPrimitives};
+    /// use std::io::Cursor;
+    ///
+    /// let data = b"\x02\x00\x00\x00\xff\xff\xff\xff\x01\x00\x00\x00\x07\x00\x00\x00";
+    /// let slots = ContainerOptions::new()
+    ///     .max_len(8)
+    ///     .read_darray(&mut Cursor::new(&data[..]), |src| src.read_i32_le().map(Some))
+    ///     .unwrap();
+    /// assert_eq!(slots, [None, Some(7)]);
+    /// ```
+    pub fn read_darray<R, T, F>(
+        &self,
+        src: &mut R,
+        mut read_elem: F,
+    ) -> Result<Vec<Option<T>>, UplinkError>
+    where
+        R: Read + Seek + ?Sized,
+        F: FnMut(&mut R) -> Result<Option<T>, UplinkError>,
+    {
+        let len = self.read_len(src)?;
+        let mut slots = Vec::with_capacity(len);
+        for slot in 0..len {
+            let used = field(src, |src| {
+                let index = i32::from_le_bytes(bytes(src)?);
+                if index == UNUSED_SLOT {
+                    Ok(false)
+                } else if usize::try_from(index).ok() == Some(slot) {
+                    Ok(true)
+                } else {
+                    Err(UplinkErrorKind::BadIndex(index.into()).into())
+                }
+            })?;
+            slots.push(if used { read_elem(src)? } else { None });
+        }
+        Ok(slots)
+    }
+
+    #[inline]
+    /// Reads an `LList`, using the current set of options.
+    ///
+    /// An `LList` is written as an `int` giving its number of elements, followed by each element
+    /// in order, with no indices. `read_elem` is called once for each element.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, `read_elem` fails, or the number of elements is
+    /// negative or exceeds the limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::uplink::{ContainerOptions, ReadPrimitives};
+    /// use std::io::Cursor;
+    ///
+    /// let data = b"\x02\x00\x00\x00\x03\x00\x00\x00ab\x00\x01\x00\x00\x00\x00";
+    /// let elems = ContainerOptions::new()
+    ///     .read_llist(&mut Cursor::new(&data[..]), |src| src.read_string())
+    ///     .unwrap();
+    /// assert_eq!(elems, ["ab", ""]);
+    /// ```
+    pub fn read_llist<R, T, F>(&self, src: &mut R, mut read_elem: F) -> Result<Vec<T>, UplinkError>
+    where
+        R: Read + Seek + ?Sized,
+        F: FnMut(&mut R) -> Result<T, UplinkError>,
+    {
+        let len = self.read_len(src)?;
+        (0..len).map(|_| read_elem(src)).collect()
+    }
+
+    #[inline]
+    /// Reads a `BTree` keyed by strings, using the current set of options.
+    ///
+    /// A `BTree` is written node by node, starting from the root. Each node is written as a
+    /// `bool` stating whether it is present. A present node is followed by its key as a
+    /// length-prefixed string, its value, its left subtree and its right subtree, each subtree
+    /// written in the same way; an absent node has nothing following it. `read_value` is called
+    /// once for each present node.
+    ///
+    /// The result holds every entry in the order it is stored, which is not key order. Inserting
+    /// the entries into an empty `BTree` in this order rebuilds the same tree, so `write_btree`
+    /// can write them back unchanged; sort them by key if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, `read_value` fails, a node's presence flag or key
+    /// is invalid, or the `BTree` has more entries or is deeper than the limits allow.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::uplink::{ContainerOptions, ReadPrimitives};
+    /// use std::io::Cursor;
+    ///
+    /// let data = b"\x01\x02\x00\x00\x00b\x00\x02\x00\x00\x00\
+    ///              \x01\x02\x00\x00\x00a\x00\x01\x00\x00\x00\x00\x00\
+    ///              \x00";
+    /// let entries = ContainerOptions::new()
+    ///     .read_btree(&mut Cursor::new(&data[..]), ReadPrimitives::read_i32_le)
+    ///     .unwrap();
+    /// assert_eq!(entries, [("b".to_owned(), 2), ("a".to_owned(), 1)]);
+    /// ```
+    pub fn read_btree<R, T, F>(
+        &self,
+        src: &mut R,
+        mut read_value: F,
+    ) -> Result<Vec<(String, T)>, UplinkError>
+    where
+        R: Read + Seek + ?Sized,
+        F: FnMut(&mut R) -> Result<T, UplinkError>,
+    {
+        let mut entries = Vec::new();
+        // The depths of the nodes still to be read, with the next node on top.
+        let mut pending = vec![1];
+        while let Some(depth) = pending.pop() {
+            if self.read_presence(src, depth, entries.len())? {
+                let key = src.read_string()?;
+                let value = read_value(src)?;
+                entries.push((key, value));
+                pending.extend_from_slice(&[depth + 1, depth + 1]);
+            }
+        }
+        Ok(entries)
+    }
+
+    #[inline]
+    /// Writes a `DArray`, using the current set of options.
+    ///
+    /// This writes the layout read by `read_darray`, with one slot for each entry in `slots`
+    /// followed by any padding (see `darray_step`). `write_elem` is called once for each used
+    /// slot.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, `write_elem` fails, or the number of slots exceeds
+    /// the limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::uplink::{ContainerOptions, WritePrimitives};
+    ///
+    /// let mut data = Vec::new();
+    /// ContainerOptions::new()
+    ///     .darray_step(4)
+    ///     .write_darray(&mut data, &[None, Some(7)], |dst, &n| dst.write_i32_le(n))
+    ///     .unwrap();
+    /// assert_eq!(data.len(), 4 + 4 + 8 + 4 + 4);
+    /// ```
+    pub fn write_darray<W, T, F>(
+        &self,
+        dst: &mut W,
+        slots: &[Option<T>],
+        mut write_elem: F,
+    ) -> Result<(), UplinkError>
+    where
+        W: Write + ?Sized,
+        F: FnMut(&mut W, &T) -> Result<(), UplinkError>,
+    {
+        let step = self.darray_step.max(1);
+        let padding = (step - slots.len() % step) % step;
+        self.write_len(dst, slots.len().saturating_add(padding))?;
+        // The length fits in an `i32`, so every index does too.
+        for (index, slot) in (0..).zip(slots) {
+            match slot {
+                Some(elem) => {
+                    dst.write_i32_le(index)?;
+                    write_elem(dst, elem)?;
+                }
+                None => dst.write_i32_le(UNUSED_SLOT)?,
+            }
+        }
+        (0..padding).try_for_each(|_| dst.write_i32_le(UNUSED_SLOT))
+    }
+
+    #[inline]
+    /// Writes an `LList`, using the current set of options.
+    ///
+    /// This writes the layout read by `read_llist`. `write_elem` is called once for each element.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, `write_elem` fails, or the number of elements
+    /// exceeds the limit.
+    pub fn write_llist<W, T, F>(
+        &self,
+        dst: &mut W,
+        elems: &[T],
+        mut write_elem: F,
+    ) -> Result<(), UplinkError>
+    where
+        W: Write + ?Sized,
+        F: FnMut(&mut W, &T) -> Result<(), UplinkError>,
+    {
+        self.write_len(dst, elems.len())?;
+        elems.iter().try_for_each(|elem| write_elem(dst, elem))
+    }
+
+    #[inline]
+    /// Writes a `BTree` keyed by strings, using the current set of options.
+    ///
+    /// The entries are inserted into an empty `BTree` in the order given, as the game does when
+    /// adding them: an entry whose key is less than a node's goes into its left subtree, and any
+    /// other entry into its right subtree. The tree is then written in the layout read by
+    /// `read_btree`. Entries read by `read_btree` are therefore written back unchanged, and
+    /// entries appended to them are added as the game would add them. `write_value` is called once
+    /// for each entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, `write_value` fails, a key cannot be written, or
+    /// the `BTree` has more entries or is deeper than the limits allow.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::uplink::{ContainerOptions, ReadPrimitives, WritePrimitives};
+    /// use std::io::Cursor;
+    ///
+    /// let entries = [("b", 2), ("a", 1), ("c", 3)];
+    /// let mut data = Vec::new();
+    /// ContainerOptions::new()
+    ///     .write_btree(&mut data, &entries, |dst, &n| dst.write_i32_le(n))
+    ///     .unwrap();
+    /// let read = ContainerOptions::new()
+    ///     .read_btree(&mut Cursor::new(data), ReadPrimitives::read_i32_le)
+    ///     .unwrap();
+    /// assert_eq!(read, [("b".to_owned(), 2), ("a".to_owned(), 1), ("c".to_owned(), 3)]);
+    /// ```
+    pub fn write_btree<W, K, T, F>(
+        &self,
+        dst: &mut W,
+        entries: &[(K, T)],
+        mut write_value: F,
+    ) -> Result<(), UplinkError>
+    where
+        W: Write + ?Sized,
+        K: AsRef<str>,
+        F: FnMut(&mut W, &T) -> Result<(), UplinkError>,
+    {
+        if entries.len() > self.max_len {
+            let count = i64::try_from(entries.len()).unwrap_or(i64::MAX);
+            return Err(UplinkErrorKind::BadCount(count).into());
+        }
+        if !entries.is_empty() && self.max_depth == 0 {
+            return Err(UplinkErrorKind::TooDeep(1).into());
+        }
+        // The left and right children of each entry.
+        let mut children = vec![[None; 2]; entries.len()];
+        for (index, (key, _)) in entries.iter().enumerate().skip(1) {
+            let side = |parent: usize| usize::from(key.as_ref() >= entries[parent].0.as_ref());
+            let mut parent = 0;
+            let mut depth = 2;
+            while let Some(child) = children[parent][side(parent)] {
+                parent = child;
+                depth += 1;
+            }
+            if depth > self.max_depth {
+                return Err(UplinkErrorKind::TooDeep(depth).into());
+            }
+            children[parent][side(parent)] = Some(index);
+        }
+        // The nodes still to be written, with the next node on top.
+        let mut pending = vec![Some(0).filter(|_| !entries.is_empty())];
+        while let Some(node) = pending.pop() {
+            match node {
+                Some(index) => {
+                    let (key, value) = &entries[index];
+                    dst.write_bool(true)?;
+                    dst.write_string(key.as_ref())?;
+                    write_value(dst, value)?;
+                    let [left, right] = children[index];
+                    pending.extend_from_slice(&[right, left]);
+                }
+                None => dst.write_bool(false)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the flag stating whether a `BTree` node is present, checking that a present node lies
+    /// within the limits.
+    fn read_presence<R: Read + Seek + ?Sized>(
+        self,
+        src: &mut R,
+        depth: usize,
+        nodes: usize,
+    ) -> Result<bool, UplinkError> {
+        field(src, |src| {
+            if !boolean(src)? {
+                Ok(false)
+            } else if depth > self.max_depth {
+                Err(UplinkErrorKind::TooDeep(depth).into())
+            } else if nodes >= self.max_len {
+                let count = i64::try_from(nodes + 1).unwrap_or(i64::MAX);
+                Err(UplinkErrorKind::BadCount(count).into())
+            } else {
+                Ok(true)
+            }
+        })
+    }
+
+    /// Writes the count at the start of a container.
+    fn write_len<W: Write + ?Sized>(self, dst: &mut W, len: usize) -> Result<(), UplinkError> {
+        let count = i32::try_from(len)
+            .ok()
+            .filter(|_| len <= self.max_len)
+            .ok_or_else(|| UplinkErrorKind::BadCount(i64::try_from(len).unwrap_or(i64::MAX)))?;
+        dst.write_i32_le(count)
+    }
+
+    /// Reads the count at the start of a container.
+    fn read_len<R: Read + Seek + ?Sized>(self, src: &mut R) -> Result<usize, UplinkError> {
+        field(src, |src| {
+            let len = i32::from_le_bytes(bytes(src)?);
+            usize::try_from(len)
+                .ok()
+                .filter(|&len| len <= self.max_len)
+                .ok_or_else(|| UplinkErrorKind::BadCount(len.into()).into())
+        })
+    }
+}
+
+impl Default for ContainerOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline]
+/// Reads a `DArray`.
+///
+/// This is equivalent to `ContainerOptions::new().read_darray(src, read_elem)`.
+///
+/// # Errors
+///
+/// Returns an `Err` in the same cases as `ContainerOptions::read_darray`.
+pub fn read_darray<R, T, F>(src: &mut R, read_elem: F) -> Result<Vec<Option<T>>, UplinkError>
+where
+    R: Read + Seek + ?Sized,
+    F: FnMut(&mut R) -> Result<Option<T>, UplinkError>,
+{
+    ContainerOptions::new().read_darray(src, read_elem)
+}
+
+#[inline]
+/// Reads an `LList`.
+///
+/// This is equivalent to `ContainerOptions::new().read_llist(src, read_elem)`.
+///
+/// # Errors
+///
+/// Returns an `Err` in the same cases as `ContainerOptions::read_llist`.
+pub fn read_llist<R, T, F>(src: &mut R, read_elem: F) -> Result<Vec<T>, UplinkError>
+where
+    R: Read + Seek + ?Sized,
+    F: FnMut(&mut R) -> Result<T, UplinkError>,
+{
+    ContainerOptions::new().read_llist(src, read_elem)
+}
+
+#[inline]
+/// Reads a `BTree` keyed by strings.
+///
+/// This is equivalent to `ContainerOptions::new().read_btree(src, read_value)`.
+///
+/// # Errors
+///
+/// Returns an `Err` in the same cases as `ContainerOptions::read_btree`.
+pub fn read_btree<R, T, F>(src: &mut R, read_value: F) -> Result<Vec<(String, T)>, UplinkError>
+where
+    R: Read + Seek + ?Sized,
+    F: FnMut(&mut R) -> Result<T, UplinkError>,
+{
+    ContainerOptions::new().read_btree(src, read_value)
+}
+
+#[inline]
+/// Writes a `DArray`.
+///
+/// This is equivalent to `ContainerOptions::new().write_darray(dst, slots, write_elem)`.
+///
+/// # Errors
+///
+/// Returns an `Err` in the same cases as `ContainerOptions::write_darray`.
+pub fn write_darray<W, T, F>(
+    dst: &mut W,
+    slots: &[Option<T>],
+    write_elem: F,
+) -> Result<(), UplinkError>
+where
+    W: Write + ?Sized,
+    F: FnMut(&mut W, &T) -> Result<(), UplinkError>,
+{
+    ContainerOptions::new().write_darray(dst, slots, write_elem)
+}
+
+#[inline]
+/// Writes an `LList`.
+///
+/// This is equivalent to `ContainerOptions::new().write_llist(dst, elems, write_elem)`.
+///
+/// # Errors
+///
+/// Returns an `Err` in the same cases as `ContainerOptions::write_llist`.
+pub fn write_llist<W, T, F>(dst: &mut W, elems: &[T], write_elem: F) -> Result<(), UplinkError>
+where
+    W: Write + ?Sized,
+    F: FnMut(&mut W, &T) -> Result<(), UplinkError>,
+{
+    ContainerOptions::new().write_llist(dst, elems, write_elem)
+}
+
+#[inline]
+/// Writes a `BTree` keyed by strings.
+///
+/// This is equivalent to `ContainerOptions::new().write_btree(dst, entries, write_value)`.
+///
+/// # Errors
+///
+/// Returns an `Err` in the same cases as `ContainerOptions::write_btree`.
+pub fn write_btree<W, K, T, F>(
+    dst: &mut W,
+    entries: &[(K, T)],
+    write_value: F,
+) -> Result<(), UplinkError>
+where
+    W: Write + ?Sized,
+    K: AsRef<str>,
+    F: FnMut(&mut W, &T) -> Result<(), UplinkError>,
+{
+    ContainerOptions::new().write_btree(dst, entries, write_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        read_btree, read_darray, read_llist, write_btree, write_darray, write_llist,
+        ContainerOptions,
+    };
+    use crate::uplink::{ReadPrimitives, UplinkErrorKind, WritePrimitives};
+    use std::{convert::TryFrom, io::Cursor};
+
+    /// A `BTree` written as nested `(key, value, left, right)` tuples.
+    enum Tree {
+        Empty,
+        Node(&'static str, i32, Box<Tree>, Box<Tree>),
+    }
+
+    fn write_tree(dst: &mut Vec<u8>, tree: &Tree) {
+        match tree {
+            Tree::Empty => dst.write_bool(false).unwrap(),
+            Tree::Node(key, value, left, right) => {
+                dst.write_bool(true).unwrap();
+                dst.write_string(key).unwrap();
+                dst.write_i32_le(*value).unwrap();
+                write_tree(dst, left);
+                write_tree(dst, right);
+            }
+        }
+    }
+
+    fn node(key: &'static str, value: i32, left: Tree, right: Tree) -> Tree {
+        Tree::Node(key, value, Box::new(left), Box::new(right))
+    }
+
+    /// A `BTree` of `len` nodes, each the right child of the one before.
+    fn chain(len: usize) -> Cursor<Vec<u8>> {
+        let mut data = Vec::new();
+        for n in 0..len {
+            data.write_bool(true).unwrap();
+            data.write_string("k").unwrap();
+            data.write_i32_le(i32::try_from(n).unwrap()).unwrap();
+            data.write_bool(false).unwrap();
+        }
+        data.write_bool(false).unwrap();
+        Cursor::new(data)
+    }
+
+    fn ints(fields: &[i32]) -> Cursor<Vec<u8>> {
+        let mut data = Vec::new();
+        for &n in fields {
+            data.write_i32_le(n).unwrap();
+        }
+        Cursor::new(data)
+    }
+
+    #[test]
+    fn darray() {
+        let mut src = ints(&[4, 0, 10, -1, -1, 3, 13, 99]);
+        let slots = read_darray(&mut src, |src| src.read_i32_le().map(Some)).unwrap();
+        assert_eq!(slots, [Some(10), None, None, Some(13)]);
+        assert_eq!(src.read_i32_le().unwrap(), 99);
+    }
+
+    #[test]
+    fn darray_null_elements() {
+        let mut src = ints(&[2, 0, 0, 1, 5]);
+        let slots = read_darray(&mut src, |src| {
+            src.read_i32_le().map(|n| Some(n).filter(|&n| n != 0))
+        })
+        .unwrap();
+        assert_eq!(slots, [None, Some(5)]);
+    }
+
+    #[test]
+    fn darray_empty() {
+        let slots = read_darray(&mut ints(&[0]), |_| -> Result<Option<()>, _> {
+            unreachable!()
+        })
+        .unwrap();
+        assert!(slots.is_empty());
+    }
+
+    #[test]
+    fn darray_bad_index() {
+        let cases = [
+            (&[2, 1, 7][..], 1, 4),
+            (&[2, 0, 7, 2, 8], 2, 12),
+            (&[1, -2], -2, 4),
+        ];
+        for (fields, bad, offset) in cases {
+            let e = read_darray(&mut ints(fields), |src| src.read_i32_le().map(Some)).unwrap_err();
+            assert!(
+                matches!(e.kind(), UplinkErrorKind::BadIndex(index) if *index == bad),
+                "{:?}",
+                e
+            );
+            assert_eq!(e.offset(), Some(offset));
+        }
+    }
+
+    #[test]
+    fn llist() {
+        let mut src = ints(&[3, 1, 2, 3, 99]);
+        assert_eq!(
+            read_llist(&mut src, ReadPrimitives::read_i32_le).unwrap(),
+            [1, 2, 3]
+        );
+        assert_eq!(src.read_i32_le().unwrap(), 99);
+    }
+
+    #[test]
+    fn limits() {
+        let mut options = ContainerOptions::new();
+        let _ = options.max_len(2);
+        assert_eq!(
+            options
+                .read_llist(&mut ints(&[2, 1, 2]), ReadPrimitives::read_i32_le)
+                .unwrap(),
+            [1, 2]
+        );
+        for fields in [&[3, 1, 2, 3][..], &[-1]] {
+            let e = options
+                .read_llist(&mut ints(fields), ReadPrimitives::read_i32_le)
+                .unwrap_err();
+            assert!(matches!(e.kind(), UplinkErrorKind::BadCount(_)), "{:?}", e);
+            assert_eq!(e.offset(), Some(0));
+            let e = options
+                .read_darray(&mut ints(fields), |src| src.read_i32_le().map(Some))
+                .unwrap_err();
+            assert!(matches!(e.kind(), UplinkErrorKind::BadCount(_)), "{:?}", e);
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let e = read_llist(&mut ints(&[3, 1]), ReadPrimitives::read_i32_le).unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::Io(_)), "{:?}", e);
+        assert_eq!(e.offset(), Some(8));
+    }
+
+    #[test]
+    fn btree() {
+        let tree = node(
+            "m",
+            1,
+            node("c", 2, Tree::Empty, node("f", 3, Tree::Empty, Tree::Empty)),
+            node("t", 4, node("p", 5, Tree::Empty, Tree::Empty), Tree::Empty),
+        );
+        let mut data = Vec::new();
+        write_tree(&mut data, &tree);
+        data.write_i32_le(99).unwrap();
+        let mut src = Cursor::new(data);
+        let entries = read_btree(&mut src, ReadPrimitives::read_i32_le).unwrap();
+        let keys = entries.iter().map(|(key, _)| &key[..]).collect::<Vec<_>>();
+        let values = entries.iter().map(|&(_, value)| value).collect::<Vec<_>>();
+        assert_eq!(keys, ["m", "c", "f", "t", "p"]);
+        assert_eq!(values, [1, 2, 3, 4, 5]);
+        assert_eq!(src.read_i32_le().unwrap(), 99);
+    }
+
+    #[test]
+    fn btree_empty() {
+        let mut data = Vec::new();
+        write_tree(&mut data, &Tree::Empty);
+        let entries = read_btree(&mut Cursor::new(data), ReadPrimitives::read_i32_le).unwrap();
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn btree_deep() {
+        let mut options = ContainerOptions::new();
+        let _ = options.max_depth(100_000).max_len(100_000);
+        let entries = options
+            .read_btree(&mut chain(100_000), ReadPrimitives::read_i32_le)
+            .unwrap();
+        assert_eq!(entries.len(), 100_000);
+        assert_eq!(entries[99_999].1, 99_999);
+    }
+
+    #[test]
+    fn btree_limits() {
+        let mut options = ContainerOptions::new();
+        let _ = options.max_depth(3);
+        assert_eq!(
+            options
+                .read_btree(&mut chain(3), ReadPrimitives::read_i32_le)
+                .unwrap()
+                .len(),
+            3
+        );
+        let e = options
+            .read_btree(&mut chain(4), ReadPrimitives::read_i32_le)
+            .unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::TooDeep(4)), "{:?}", e);
+        assert_eq!(e.offset(), Some(3 * 12));
+        let _ = options.max_depth(10).max_len(2);
+        let e = options
+            .read_btree(&mut chain(3), ReadPrimitives::read_i32_le)
+            .unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::BadCount(3)), "{:?}", e);
+    }
+
+    #[test]
+    fn btree_truncated() {
+        let mut data = chain(2).into_inner();
+        data.truncate(data.len() - 2);
+        let e = read_btree(&mut Cursor::new(data), ReadPrimitives::read_i32_le).unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::Io(_)), "{:?}", e);
+    }
+
+    #[test]
+    fn write_containers() {
+        let mut data = Vec::new();
+        write_darray(&mut data, &[Some(10), None, Some(13)], |dst, &n| {
+            dst.write_i32_le(n)
+        })
+        .unwrap();
+        write_llist(&mut data, &[1, 2], |dst, &n| dst.write_i32_le(n)).unwrap();
+        assert_eq!(data, ints(&[3, 0, 10, -1, 2, 13, 2, 1, 2]).into_inner());
+    }
+
+    #[test]
+    fn darray_step() {
+        let mut options = ContainerOptions::new();
+        for (step, slots) in [(0, 2), (1, 2), (2, 2), (3, 3), (4, 4)] {
+            let mut data = Vec::new();
+            options
+                .darray_step(step)
+                .write_darray(&mut data, &[None, Some(5)], |dst, &n| dst.write_i32_le(n))
+                .unwrap();
+            let read = read_darray(&mut Cursor::new(data), |src| src.read_i32_le().map(Some));
+            let mut expected = vec![None, Some(5)];
+            expected.resize(slots, None);
+            assert_eq!(read.unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn write_btree_shape() {
+        let entries = [("m", 1), ("c", 2), ("f", 3), ("t", 4), ("p", 5), ("m", 6)];
+        let mut data = Vec::new();
+        write_btree(&mut data, &entries, |dst, &n| dst.write_i32_le(n)).unwrap();
+        let tree = node(
+            "m",
+            1,
+            node("c", 2, Tree::Empty, node("f", 3, Tree::Empty, Tree::Empty)),
+            node(
+                "t",
+                4,
+                node("p", 5, node("m", 6, Tree::Empty, Tree::Empty), Tree::Empty),
+                Tree::Empty,
+            ),
+        );
+        let mut expected = Vec::new();
+        write_tree(&mut expected, &tree);
+        assert_eq!(data, expected);
+        let mut data = Vec::new();
+        write_btree::<_, &str, i32, _>(&mut data, &[], |dst, &n| dst.write_i32_le(n)).unwrap();
+        assert_eq!(data, [0]);
+    }
+
+    #[test]
+    fn write_limits() {
+        let mut options = ContainerOptions::new();
+        let _ = options.max_len(2).max_depth(2);
+        let write = |dst: &mut Vec<u8>, &n: &i32| dst.write_i32_le(n);
+        let e = options
+            .write_llist(&mut Vec::new(), &[1, 2, 3], write)
+            .unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::BadCount(3)), "{:?}", e);
+        let e = options
+            .darray_step(3)
+            .write_darray(&mut Vec::new(), &[Some(1)], write)
+            .unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::BadCount(3)), "{:?}", e);
+        let e = options
+            .max_len(3)
+            .write_btree(&mut Vec::new(), &[("a", 1), ("b", 2), ("c", 3)], write)
+            .unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::TooDeep(3)), "{:?}", e);
+        assert!(options
+            .write_btree(&mut Vec::new(), &[("b", 1), ("a", 2), ("c", 3)], write)
+            .is_ok());
+    }
+}
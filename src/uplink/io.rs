@@ -0,0 +1,232 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Reading and writing values in the format of Introversion's serializer.
+//!
+//! A string is stored as a little-endian `i32` length, followed by that many bytes: the string
+//! itself and a NUL terminator. A null string is stored as a length of `-1` with no bytes
+//! following it. Strings are Latin-1, so each byte is one character.
+
+use super::{UplinkError, UplinkErrorKind, MAX_STRING_LEN};
+use crate::cursor;
+use std::{
+    convert::TryFrom,
+    io::{Read, Write},
+};
+
+/// The length prefix of a null string.
+const NULL_LEN: i32 = -1;
+
+#[inline]
+/// Reads a length-prefixed string.
+///
+/// A null string is read as an empty string; use `read_optional_string` to tell the two apart. The
+/// string ends at the first NUL byte, as it would when read by the game; any bytes after it are
+/// discarded.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, or the length prefix is negative (other than `-1`) or
+/// greater than `MAX_STRING_LEN`.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::uplink::io::read_string;
+///
+/// let data = b"\x06\x00\x00\x00Agent\x00";
+/// assert_eq!(read_string(&mut &data[..]).unwrap(), "Agent");
+/// ```
+pub fn read_string<R: Read>(src: R) -> Result<String, UplinkError> {
+    read_optional_string(src).map(Option::unwrap_or_default)
+}
+
+#[inline]
+/// Reads a length-prefixed string that may be null.
+///
+/// This is identical to `read_string`, except that a null string is read as `None`.
+///
+/// # Errors
+///
+/// Returns an `Err` in the same cases as `read_string`.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::uplink::io::read_optional_string;
+///
+/// let data = b"\xff\xff\xff\xff\x01\x00\x00\x00\x00";
+/// let mut src = &data[..];
+/// assert_eq!(read_optional_string(&mut src).unwrap(), None);
+/// assert_eq!(read_optional_string(&mut src).unwrap().as_deref(), Some(""));
+/// ```
+pub fn read_optional_string<R: Read>(mut src: R) -> Result<Option<String>, UplinkError> {
+    let mut len_buf = array!(4);
+    cursor::read_exact(&mut src, &mut len_buf).map_err(UplinkErrorKind::Io)?;
+    let len = i32::from_le_bytes(len_buf);
+    if len == NULL_LEN {
+        return Ok(None);
+    }
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|&len| len <= MAX_STRING_LEN)
+        .ok_or(UplinkErrorKind::BadLength(len.into()))?;
+    let mut buf = vec![0; len];
+    cursor::read_exact(&mut src, &mut buf).map_err(UplinkErrorKind::Io)?;
+    Ok(Some(
+        buf.into_iter()
+            .take_while(|&b| b != 0)
+            .map(char::from)
+            .collect(),
+    ))
+}
+
+#[inline]
+/// Writes a length-prefixed, NUL-terminated string.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, the string contains NUL or a character outside
+/// Latin-1, or the string is too long to be read back by `read_string`.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::uplink::io::write_string;
+///
+/// let mut data = Vec::new();
+/// write_string(&mut data, "Agent").unwrap();
+/// assert_eq!(data, b"\x06\x00\x00\x00Agent\x00");
+/// ```
+pub fn write_string<W: Write>(mut dst: W, s: &str) -> Result<(), UplinkError> {
+    let mut buf = s
+        .chars()
+        .map(|c| match u8::try_from(c) {
+            Ok(b) if b != 0 => Ok(b),
+            _ => Err(UplinkErrorKind::Unencodable(c)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    buf.push(0);
+    let len = i32::try_from(buf.len())
+        .ok()
+        .filter(|_| buf.len() <= MAX_STRING_LEN)
+        .ok_or_else(|| UplinkErrorKind::BadLength(i64::try_from(buf.len()).unwrap_or(i64::MAX)))?;
+    cursor::write_all(&mut dst, &len.to_le_bytes())
+        .and_then(|()| cursor::write_all(&mut dst, &buf))
+        .map_err(|e| UplinkErrorKind::Io(e).into())
+}
+
+#[inline]
+/// Writes a length-prefixed string that may be null.
+///
+/// `None` is written as a null string, and `Some` as `write_string` would write it.
+///
+/// # Errors
+///
+/// Returns an `Err` in the same cases as `write_string`.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::uplink::io::write_optional_string;
+///
+/// let mut data = Vec::new();
+/// write_optional_string(&mut data, None).unwrap();
+/// assert_eq!(data, b"\xff\xff\xff\xff");
+/// ```
+pub fn write_optional_string<W: Write>(mut dst: W, s: Option<&str>) -> Result<(), UplinkError> {
+    match s {
+        Some(s) => write_string(dst, s),
+        None => cursor::write_all(&mut dst, &NULL_LEN.to_le_bytes())
+            .map_err(|e| UplinkErrorKind::Io(e).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_optional_string, read_string, write_optional_string, write_string};
+    use crate::uplink::{UplinkErrorKind, MAX_STRING_LEN};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn round_trip() {
+        for s in ["", "Agent", "caf\u{e9} \u{ff}"] {
+            let mut data = Vec::new();
+            write_string(&mut data, s).unwrap();
+            assert_eq!(data.len(), 4 + s.chars().count() + 1);
+            assert_eq!(read_string(&data[..]).unwrap(), s);
+        }
+    }
+
+    #[test]
+    fn latin1() {
+        let data = b"\x05\x00\x00\x00caf\xe9\x00";
+        assert_eq!(read_string(&data[..]).unwrap(), "caf\u{e9}");
+    }
+
+    #[test]
+    fn null_string() {
+        let mut data = &b"\xff\xff\xff\xffrest"[..];
+        assert_eq!(read_string(&mut data).unwrap(), "");
+        assert_eq!(data, b"rest");
+    }
+
+    #[test]
+    fn stops_at_nul() {
+        let mut data = &b"\x06\x00\x00\x00ab\x00cd\x00rest"[..];
+        assert_eq!(read_string(&mut data).unwrap(), "ab");
+        assert_eq!(data, b"rest");
+    }
+
+    #[test]
+    fn bad_lengths() {
+        for data in [
+            &b"\xfe\xff\xff\xff"[..],
+            b"\x00\x00\x00\x40",
+            b"\x21\x4e\x00\x00",
+        ] {
+            let e = read_string(data).unwrap_err();
+            assert!(matches!(e.kind(), UplinkErrorKind::BadLength(_)), "{:?}", e);
+        }
+        let mut data = u32::try_from(MAX_STRING_LEN)
+            .unwrap()
+            .to_le_bytes()
+            .to_vec();
+        data.resize(4 + MAX_STRING_LEN, b'a');
+        assert_eq!(read_string(&data[..]).unwrap().len(), MAX_STRING_LEN);
+    }
+
+    #[test]
+    fn truncated() {
+        let e = read_string(&b"\x06\x00\x00\x00Age"[..]).unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::Io(_)), "{:?}", e);
+    }
+
+    #[test]
+    fn unencodable() {
+        for (s, c) in [("\u{100}", '\u{100}'), ("a\0b", '\0')] {
+            let e = write_string(Vec::new(), s).unwrap_err();
+            assert!(
+                matches!(e.kind(), UplinkErrorKind::Unencodable(e) if *e == c),
+                "{:?}",
+                e
+            );
+        }
+        let e = write_string(Vec::new(), &"a".repeat(MAX_STRING_LEN)).unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::BadLength(_)), "{:?}", e);
+    }
+
+    #[test]
+    fn optional_strings() {
+        for s in [None, Some(""), Some("Agent")] {
+            let mut data = Vec::new();
+            write_optional_string(&mut data, s).unwrap();
+            assert_eq!(read_optional_string(&data[..]).unwrap().as_deref(), s);
+            assert_eq!(read_string(&data[..]).unwrap(), s.unwrap_or_default());
+        }
+    }
+}
@@ -0,0 +1,350 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use super::{
+    containers::{read_btree, write_btree},
+    probe::parse_tag,
+    ReadPrimitives, UplinkError, WritePrimitives, SAVE_VERSION_LEN,
+};
+use crate::{
+    convert::{decode, replace},
+    cursor,
+    error::Error,
+    v2,
+    version::Version,
+};
+use std::{
+    fs,
+    io::{self, Cursor, Read, Seek, Write},
+    path::Path,
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The contents of Uplink's `options` file.
+///
+/// The decoded file begins with a version tag, as a save does, followed by a `BTree` of options
+/// keyed by name. Each option is written as its tooltip, two `bool` fields stating whether it is a
+/// yes/no option and whether it is shown in the game's options screen, its integer value, and its
+/// text value as a string that is null for options without one. Any data after the `BTree` is
+/// kept as it is.
+///
+/// Options are kept in the order they are stored, and options that are added are appended, so a
+/// file that is loaded and saved again without changes is written back exactly as it was, apart
+/// from being encoded with Redshirt 2. Options this crate knows nothing about are kept as they
+/// are.
+pub struct Options {
+    version: [u8; SAVE_VERSION_LEN],
+    entries: Vec<OptionEntry>,
+    trailing: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A single option in an `Options` file.
+pub struct OptionEntry {
+    /// The name of the option, such as `graphics_screenwidth`.
+    pub name: String,
+    /// The description shown for the option in the game's options screen.
+    pub tooltip: String,
+    /// Whether the option is a yes/no option, whose value is 0 or 1.
+    pub yes_or_no: bool,
+    /// Whether the option is shown in the game's options screen.
+    pub visible: bool,
+    /// The integer value of the option.
+    pub value: i32,
+    /// The text value of the option, if it has one.
+    pub text: Option<String>,
+}
+
+impl Options {
+    #[inline]
+    /// Loads an `options` file encoded with Redshirt 1 or Redshirt 2.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, the file is not a valid Redshirt file, or its
+    /// decoded contents are malformed. In the last case, the `Error::Io` can be downcast to an
+    /// `UplinkError` describing the problem.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::uplink::Options;
+    ///
+    /// let options = Options::load("options").unwrap();
+    /// println!("screen width: {:?}", options.get_int("graphics_screenwidth"));
+    /// ```
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let data = fs::read(path).map_err(Error::Io)?;
+        let version = Version::detect(&data).ok_or(Error::BadHeader)?;
+        Self::read_from(Cursor::new(decode(version, &data)?))
+    }
+
+    #[inline]
+    /// Reads the decoded contents of an `options` file from `src`, up to the end of the stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or the data is malformed, as for `load`.
+    pub fn read_from<R: Read + Seek>(mut src: R) -> Result<Self, Error> {
+        let mut version = array!(SAVE_VERSION_LEN);
+        cursor::read_exact(&mut src, &mut version).map_err(Error::Io)?;
+        let entries = read_btree(&mut src, |src| {
+            Ok(OptionEntry {
+                name: String::new(),
+                tooltip: src.read_string()?,
+                yes_or_no: src.read_bool()?,
+                visible: src.read_bool()?,
+                value: src.read_i32_le()?,
+                text: src.read_optional_string()?,
+            })
+        })
+        .map_err(uplink_error)?
+        .into_iter()
+        .map(|(name, entry)| OptionEntry { name, ..entry })
+        .collect();
+        let mut trailing = Vec::new();
+        let _ = src.read_to_end(&mut trailing).map_err(Error::Io)?;
+        Ok(Self {
+            version,
+            entries,
+            trailing,
+        })
+    }
+
+    #[inline]
+    /// Encodes these options with Redshirt 2 and saves them to `path`.
+    ///
+    /// The file is replaced atomically: the data is written to a temporary file in the same
+    /// directory, which is then renamed over `path`, so the original is never left partly
+    /// written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or an option cannot be written, as for
+    /// `write_to`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::uplink::Options;
+    ///
+    /// let mut options = Options::load("options").unwrap();
+    /// options.set_int("graphics_screenwidth", 1024);
+    /// options.save("options").unwrap();
+    /// ```
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut data = Vec::new();
+        self.write_to(&mut data)?;
+        replace(path.as_ref(), &v2::encode(&data))
+    }
+
+    #[inline]
+    /// Writes the decoded contents of an `options` file to `dst`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or a name, tooltip or text value contains a
+    /// character that cannot be written. In the latter case, the `Error::Io` can be downcast to an
+    /// `UplinkError`.
+    pub fn write_to<W: Write>(&self, mut dst: W) -> Result<(), Error> {
+        cursor::write_all(&mut dst, &self.version).map_err(Error::Io)?;
+        let entries = self
+            .entries
+            .iter()
+            .map(|entry| (&entry.name[..], entry))
+            .collect::<Vec<_>>();
+        write_btree(&mut dst, &entries, |dst, entry| {
+            dst.write_string(&entry.tooltip)?;
+            dst.write_bool(entry.yes_or_no)?;
+            dst.write_bool(entry.visible)?;
+            dst.write_i32_le(entry.value)?;
+            dst.write_optional_string(entry.text.as_deref())
+        })
+        .map_err(uplink_error)?;
+        cursor::write_all(&mut dst, &self.trailing).map_err(Error::Io)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the version tag at the start of the file, such as `SAV62`, if it is valid.
+    pub fn version(&self) -> Option<String> {
+        parse_tag(&self.version)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns every option, in the order they are stored.
+    pub fn entries(&self) -> &[OptionEntry] {
+        &self.entries
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the option with the given name, if there is one.
+    pub fn get(&self, name: &str) -> Option<&OptionEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the integer value of the option with the given name, if there is one.
+    pub fn get_int(&self, name: &str) -> Option<i32> {
+        self.get(name).map(|entry| entry.value)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the text value of the option with the given name, if there is one and it has a
+    /// text value.
+    pub fn get_string(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(|entry| entry.text.as_deref())
+    }
+
+    #[inline]
+    /// Sets the integer value of the option with the given name, adding a hidden option with no
+    /// tooltip if there is none.
+    pub fn set_int(&mut self, name: &str, value: i32) {
+        self.entry(name).value = value;
+    }
+
+    #[inline]
+    /// Sets the text value of the option with the given name, adding a hidden option with no
+    /// tooltip if there is none.
+    pub fn set_string(&mut self, name: &str, text: &str) {
+        self.entry(name).text = Some(text.to_owned());
+    }
+
+    /// Returns the option with the given name, appending it if there is none.
+    fn entry(&mut self, name: &str) -> &mut OptionEntry {
+        if let Some(index) = self.entries.iter().position(|entry| entry.name == name) {
+            return &mut self.entries[index];
+        }
+        self.entries.push(OptionEntry {
+            name: name.to_owned(),
+            tooltip: String::new(),
+            yes_or_no: false,
+            visible: false,
+            value: 0,
+            text: None,
+        });
+        self.entries.last_mut().unwrap()
+    }
+}
+
+/// Converts an `UplinkError` into an `Error`, from which it can be recovered by downcasting.
+fn uplink_error(e: UplinkError) -> Error {
+    Error::Io(io::Error::from(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Options;
+    use crate::{
+        error::Error,
+        uplink::{write_btree, UplinkError, WritePrimitives},
+        v1, v2,
+    };
+    use std::{env, fs, io::Cursor, process};
+
+    /// Writes the decoded contents of an `options` file with the given options, each given as its
+    /// name, integer value and text value.
+    fn options_data(entries: &[(&str, (i32, Option<&str>))]) -> Vec<u8> {
+        let mut data = b"SAV62\0".to_vec();
+        write_btree(&mut data, entries, |dst, &(value, text)| {
+            dst.write_string("tip")?;
+            dst.write_bool(false)?;
+            dst.write_bool(true)?;
+            dst.write_i32_le(value)?;
+            dst.write_optional_string(text)
+        })
+        .unwrap();
+        data
+    }
+
+    #[test]
+    fn read_write() {
+        let mut data = options_data(&[
+            ("graphics_screenwidth", (800, None)),
+            ("game_debugstart", (0, None)),
+            ("theme", (0, Some("graphics"))),
+            ("zz_future_option", (7, Some("kept"))),
+        ]);
+        data.extend_from_slice(b"trailing");
+        let options = Options::read_from(Cursor::new(&data)).unwrap();
+        assert_eq!(options.version().as_deref(), Some("SAV62"));
+        assert_eq!(options.get_int("graphics_screenwidth"), Some(800));
+        assert_eq!(options.get_string("graphics_screenwidth"), None);
+        assert_eq!(options.get_string("theme"), Some("graphics"));
+        assert_eq!(options.get_int("missing"), None);
+        let names = options
+            .entries()
+            .iter()
+            .map(|e| &e.name[..])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "graphics_screenwidth",
+                "game_debugstart",
+                "theme",
+                "zz_future_option"
+            ]
+        );
+        let mut rewritten = Vec::new();
+        options.write_to(&mut rewritten).unwrap();
+        assert_eq!(rewritten, data);
+    }
+
+    #[test]
+    fn set() {
+        let data = options_data(&[("graphics_screenwidth", (800, None))]);
+        let mut options = Options::read_from(Cursor::new(data)).unwrap();
+        options.set_int("graphics_screenwidth", 1024);
+        options.set_string("theme", "hacker");
+        options.set_int("theme", 3);
+        assert_eq!(options.entries().len(), 2);
+        let mut rewritten = Vec::new();
+        options.write_to(&mut rewritten).unwrap();
+        let reread = Options::read_from(Cursor::new(rewritten)).unwrap();
+        assert_eq!(reread, options);
+        assert_eq!(reread.get_int("graphics_screenwidth"), Some(1024));
+        assert_eq!(reread.get_string("theme"), Some("hacker"));
+        assert_eq!(reread.get_int("theme"), Some(3));
+    }
+
+    #[test]
+    fn malformed() {
+        let mut data = options_data(&[("a", (1, None))]);
+        data[6] = 2;
+        let e = Options::read_from(Cursor::new(data)).unwrap_err();
+        let inner = match &e {
+            Error::Io(inner) => inner
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<UplinkError>()),
+            _ => None,
+        };
+        assert_eq!(inner.and_then(UplinkError::offset), Some(6), "{e:?}");
+        let mut options = Options::read_from(Cursor::new(options_data(&[]))).unwrap();
+        options.set_string("a", "\u{100}");
+        assert!(matches!(options.write_to(Vec::new()), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn load_save() {
+        let data = options_data(&[("graphics_screenwidth", (800, None))]);
+        let path = env::temp_dir().join(format!("redshirt-options-{}", process::id()));
+        fs::write(&path, v1::encode(&data)).unwrap();
+        let mut options = Options::load(&path).unwrap();
+        options.set_int("graphics_screenwidth", 640);
+        options.save(&path).unwrap();
+        let saved = fs::read(&path).unwrap();
+        let reloaded = Options::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(v2::verify(&saved[..]).is_ok());
+        assert_eq!(reloaded, options);
+    }
+}
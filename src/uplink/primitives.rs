@@ -0,0 +1,260 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use super::{io as uplink_io, UplinkError, UplinkErrorKind};
+use crate::cursor;
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Seek, Write},
+};
+
+/// Counts the bytes read through a reader, so that the offset of a field can be found from the
+/// position of the reader after a failed read.
+pub(super) struct Counted<'a, R: ?Sized> {
+    src: &'a mut R,
+    count: u64,
+}
+
+impl<R: Read + ?Sized> Read for Counted<'_, R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = cursor::read_checked(self.src, buf)?;
+        self.count += len as u64;
+        Ok(len)
+    }
+}
+
+/// Reads a field, recording its offset in any error that occurs.
+pub(super) fn field<R, T, F>(src: &mut R, read: F) -> Result<T, UplinkError>
+where
+    R: Read + Seek + ?Sized,
+    F: FnOnce(&mut Counted<'_, R>) -> Result<T, UplinkError>,
+{
+    let mut counted = Counted { src, count: 0 };
+    read(&mut counted).map_err(|e| {
+        let offset = counted
+            .src
+            .stream_position()
+            .ok()
+            .and_then(|pos| pos.checked_sub(counted.count));
+        e.at(offset)
+    })
+}
+
+/// Reads a fixed-size field.
+pub(super) fn bytes<R: Read + ?Sized, const N: usize>(src: &mut R) -> Result<[u8; N], UplinkError> {
+    let mut buf = [0; N];
+    cursor::read_exact(src, &mut buf)
+        .map(|()| buf)
+        .map_err(|e| UplinkErrorKind::Io(e).into())
+}
+
+/// Reads a one-byte `bool` field.
+pub(super) fn boolean<R: Read + ?Sized>(src: &mut R) -> Result<bool, UplinkError> {
+    match bytes(src)? {
+        [0] => Ok(false),
+        [1] => Ok(true),
+        [b] => Err(UplinkErrorKind::BadBool(b).into()),
+    }
+}
+
+/// Reads the fields found in decoded Uplink files.
+///
+/// This is implemented for every `Read + Seek` type. The offset of a field that cannot be read is
+/// recorded in the error; see the module documentation.
+pub trait ReadPrimitives: Read + Seek {
+    #[inline]
+    /// Reads a little-endian `int` field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs.
+    fn read_i32_le(&mut self) -> Result<i32, UplinkError> {
+        field(self, |src| bytes(src).map(i32::from_le_bytes))
+    }
+
+    #[inline]
+    /// Reads a little-endian `float` field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs.
+    fn read_f32_le(&mut self) -> Result<f32, UplinkError> {
+        field(self, |src| bytes(src).map(f32::from_le_bytes))
+    }
+
+    #[inline]
+    /// Reads a one-byte `bool` field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or the field holds a value other than 0 or 1.
+    fn read_bool(&mut self) -> Result<bool, UplinkError> {
+        field(self, |src| boolean(src))
+    }
+
+    #[inline]
+    /// Reads a one-byte `char` field, as a Latin-1 character.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs.
+    fn read_char(&mut self) -> Result<char, UplinkError> {
+        field(self, |src| bytes(src).map(|[b]| char::from(b)))
+    }
+
+    #[inline]
+    /// Reads a length-prefixed string, as `io::read_string` does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` in the same cases as `io::read_string`.
+    fn read_string(&mut self) -> Result<String, UplinkError> {
+        field(self, |src| uplink_io::read_string(src))
+    }
+
+    #[inline]
+    /// Reads a length-prefixed string that may be null, as `io::read_optional_string` does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` in the same cases as `io::read_optional_string`.
+    fn read_optional_string(&mut self) -> Result<Option<String>, UplinkError> {
+        field(self, |src| uplink_io::read_optional_string(src))
+    }
+}
+
+impl<R: Read + Seek + ?Sized> ReadPrimitives for R {}
+
+/// Writes the fields found in decoded Uplink files.
+///
+/// This is implemented for every `Write` type. Errors do not record an offset, since
+/// `v2::Writer` and many other output streams do not support seeking.
+pub trait WritePrimitives: Write {
+    #[inline]
+    /// Writes a little-endian `int` field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs.
+    fn write_i32_le(&mut self, n: i32) -> Result<(), UplinkError> {
+        cursor::write_all(self, &n.to_le_bytes()).map_err(|e| UplinkErrorKind::Io(e).into())
+    }
+
+    #[inline]
+    /// Writes a little-endian `float` field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs.
+    fn write_f32_le(&mut self, n: f32) -> Result<(), UplinkError> {
+        cursor::write_all(self, &n.to_le_bytes()).map_err(|e| UplinkErrorKind::Io(e).into())
+    }
+
+    #[inline]
+    /// Writes a one-byte `bool` field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs.
+    fn write_bool(&mut self, b: bool) -> Result<(), UplinkError> {
+        cursor::write_all(self, &[u8::from(b)]).map_err(|e| UplinkErrorKind::Io(e).into())
+    }
+
+    #[inline]
+    /// Writes a one-byte `char` field, as a Latin-1 character.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or `c` lies outside Latin-1.
+    fn write_char(&mut self, c: char) -> Result<(), UplinkError> {
+        let b = u8::try_from(c).map_err(|_| UplinkErrorKind::Unencodable(c))?;
+        cursor::write_all(self, &[b]).map_err(|e| UplinkErrorKind::Io(e).into())
+    }
+
+    #[inline]
+    /// Writes a length-prefixed, NUL-terminated string, as `io::write_string` does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` in the same cases as `io::write_string`.
+    fn write_string(&mut self, s: &str) -> Result<(), UplinkError> {
+        uplink_io::write_string(self, s)
+    }
+
+    #[inline]
+    /// Writes a length-prefixed string that may be null, as `io::write_optional_string` does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` in the same cases as `io::write_optional_string`.
+    fn write_optional_string(&mut self, s: Option<&str>) -> Result<(), UplinkError> {
+        uplink_io::write_optional_string(self, s)
+    }
+}
+
+impl<W: Write + ?Sized> WritePrimitives for W {}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReadPrimitives, WritePrimitives};
+    use crate::uplink::UplinkErrorKind;
+    use std::io::Cursor;
+
+    #[test]
+    fn round_trip() {
+        let mut data = Vec::new();
+        data.write_i32_le(-2).unwrap();
+        data.write_f32_le(1.5).unwrap();
+        data.write_bool(true).unwrap();
+        data.write_char('\u{e9}').unwrap();
+        data.write_string("Agent").unwrap();
+        assert_eq!(data.len(), 4 + 4 + 1 + 1 + 10);
+        let mut src = Cursor::new(data);
+        assert_eq!(src.read_i32_le().unwrap(), -2);
+        assert!((src.read_f32_le().unwrap() - 1.5).abs() < f32::EPSILON);
+        assert!(src.read_bool().unwrap());
+        assert_eq!(src.read_char().unwrap(), '\u{e9}');
+        assert_eq!(src.read_string().unwrap(), "Agent");
+    }
+
+    #[test]
+    fn offsets() {
+        let mut src = Cursor::new(b"\x01\x00\x00\x00\x02\xff\xff\xff\x7f\x00".to_vec());
+        assert_eq!(src.read_i32_le().unwrap(), 1);
+        let e = src.read_bool().unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::BadBool(2)), "{:?}", e);
+        assert_eq!(e.offset(), Some(4));
+        let e = src.read_string().unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::BadLength(_)), "{:?}", e);
+        assert_eq!(e.offset(), Some(5));
+        assert_eq!(e.to_string(), "offset 0x5: bad string length 2147483647");
+        let e = src.read_i32_le().unwrap_err();
+        assert!(matches!(e.kind(), UplinkErrorKind::Io(_)), "{:?}", e);
+        assert_eq!(e.offset(), Some(9));
+    }
+
+    #[cfg(feature = "redshirt2")]
+    #[test]
+    fn logical_offsets() {
+        let encoded = crate::v2::encode(b"\x01\x00\x00\x00\x02");
+        let mut src = crate::v2::Reader::new(Cursor::new(encoded)).unwrap();
+        assert_eq!(src.read_i32_le().unwrap(), 1);
+        assert_eq!(src.read_bool().unwrap_err().offset(), Some(4));
+    }
+
+    #[test]
+    fn unencodable() {
+        let e = Vec::new().write_char('\u{100}').unwrap_err();
+        assert!(
+            matches!(e.kind(), UplinkErrorKind::Unencodable('\u{100}')),
+            "{:?}",
+            e
+        );
+        assert_eq!(e.offset(), None);
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{cursor::read_up_to, error::Error};
+use std::io::Read;
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+use {
+    crate::{v1, v2, version::Version},
+    std::{
+        fs::File,
+        io::{Seek, SeekFrom},
+        path::Path,
+    },
+};
+
+/// The length of a save version tag, including its NUL terminator.
+pub const SAVE_VERSION_LEN: usize = 6;
+
+/// The prefix of every save version tag.
+const SAVE_VERSION_PREFIX: &[u8] = b"SAV";
+
+#[inline]
+/// Reads the version tag at the start of a decoded Uplink save, such as `SAV62`.
+///
+/// A save begins with its version tag, written as `SAV` and a version number, followed by a NUL
+/// byte, in `SAVE_VERSION_LEN` bytes. This reads exactly that many bytes from `src`, or fewer if
+/// the stream ends first, and returns `None` if they do not hold a version tag.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::{uplink, v2};
+///
+/// let encoded = v2::encode(b"SAV62\0...");
+/// let reader = v2::Reader::new(std::io::Cursor::new(encoded)).unwrap();
+/// assert_eq!(uplink::probe_save_version(reader).unwrap().as_deref(), Some("SAV62"));
+/// ```
+pub fn probe_save_version<R: Read>(mut src: R) -> Result<Option<String>, Error> {
+    let mut tag = array!(SAVE_VERSION_LEN);
+    let len = read_up_to(&mut src, &mut tag).map_err(Error::Io)?;
+    Ok(parse_tag(&tag[..len]))
+}
+
+#[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+#[inline]
+/// Reads the version tag at the start of a Redshirt-encoded Uplink save on disk.
+///
+/// This detects whether the file uses Redshirt 1 or Redshirt 2, then reads the tag as
+/// `probe_save_version` does. Only the header and the first `SAVE_VERSION_LEN` bytes of data are
+/// read, so the SHA-1 hash of a Redshirt 2 file is not verified, and files that were never
+/// finalised are accepted; use `v2::verify` to check them.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, or the file does not begin with a valid Redshirt 1 or
+/// Redshirt 2 header.
+///
+/// # Examples
+///
+/// ```no_run
+/// match redshirt::uplink::probe_path("agent.usr").unwrap().as_deref() {
+///     Some("SAV62") => println!("compatible"),
+///     Some(version) => println!("incompatible save version {version}"),
+///     None => println!("not an Uplink save"),
+/// }
+/// ```
+pub fn probe_path<P: AsRef<Path>>(path: P) -> Result<Option<String>, Error> {
+    let mut file = File::open(path).map_err(Error::Io)?;
+    let mut marker = array!(v1::MARKER_LEN);
+    let len = read_up_to(&mut file, &mut marker).map_err(Error::Io)?;
+    let _ = file.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
+    match Version::detect(&marker[..len]) {
+        Some(Version::V1) => probe_save_version(v1::Reader::new(file)?),
+        Some(Version::V2) => probe_save_version(
+            v2::ReaderOptions::new()
+                .accept_unfinalized(true)
+                .verification(v2::Verification::Skip)
+                .from_reader(file)?,
+        ),
+        None => Err(Error::BadHeader),
+    }
+}
+
+/// Extracts the version from a tag, if it is one.
+pub(super) fn parse_tag(tag: &[u8]) -> Option<String> {
+    let version = tag.strip_prefix(SAVE_VERSION_PREFIX)?;
+    let digits = version.iter().take_while(|b| b.is_ascii_digit()).count();
+    match version.get(digits) {
+        Some(0) if digits > 0 => {
+            let len = SAVE_VERSION_PREFIX.len() + digits;
+            Some(tag[..len].iter().map(|&b| char::from(b)).collect())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{probe_save_version, SAVE_VERSION_LEN};
+    use std::io::Read;
+
+    #[test]
+    fn tags() {
+        for (data, tag) in [
+            (&b"SAV62\0rest"[..], Some("SAV62")),
+            (b"SAV56\0", Some("SAV56")),
+            (b"SAV7\0\0", Some("SAV7")),
+            (b"SAV62", None),
+            (b"SAV\0\0\0", None),
+            (b"SAVxy\0", None),
+            (b"sav62\0", None),
+            (b"", None),
+        ] {
+            assert_eq!(probe_save_version(data).unwrap().as_deref(), tag);
+        }
+    }
+
+    #[test]
+    fn consumption() {
+        let mut data = &b"SAV62\0rest"[..];
+        let _ = probe_save_version(&mut data).unwrap();
+        assert_eq!(data, b"rest");
+        let mut data = &b"\x01\x00\x00\x00\x02\x00\x00\x00"[..];
+        assert_eq!(probe_save_version(&mut data).unwrap(), None);
+        let mut rest = Vec::new();
+        let _ = data.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest.len(), 8 - SAVE_VERSION_LEN);
+    }
+
+    #[cfg(all(feature = "redshirt1", feature = "redshirt2"))]
+    #[test]
+    fn probe_path() {
+        use crate::{v1, v2};
+        use std::{env, fs, process};
+
+        let dir = env::temp_dir().join(format!("redshirt-probe-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let v1_path = dir.join("v1.usr");
+        let v2_path = dir.join("v2.usr");
+        let plain_path = dir.join("plain.usr");
+        fs::write(&v1_path, v1::encode(b"SAV56\0data")).unwrap();
+        let mut encoded = v2::encode(b"SAV62\0data");
+        // Corrupt the data after the tag; the hash is not checked.
+        *encoded.last_mut().unwrap() ^= 1;
+        fs::write(&v2_path, encoded).unwrap();
+        fs::write(&plain_path, b"SAV62\0data").unwrap();
+        let v1_tag = super::probe_path(&v1_path).unwrap();
+        let v2_tag = super::probe_path(&v2_path).unwrap();
+        let plain = super::probe_path(&plain_path);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(v1_tag.as_deref(), Some("SAV56"));
+        assert_eq!(v2_tag.as_deref(), Some("SAV62"));
+        assert!(matches!(plain, Err(crate::Error::BadHeader)), "{:?}", plain);
+    }
+}
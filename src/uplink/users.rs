@@ -0,0 +1,192 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use super::probe::probe_path;
+use crate::{
+    error::Error,
+    inspect::{inspect, Info},
+};
+use std::{ffi::OsString, fs, path::Path};
+
+/// The extension of a save file.
+const SAVE_SUFFIX: &str = ".usr";
+
+/// The extension of a save file left behind by an interrupted save.
+const TEMP_SUFFIX: &str = ".usr.tmp";
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// The kind of file described by a `FileReport`.
+pub enum FileKind {
+    /// A save file, ending in `.usr`.
+    Save,
+    /// A temporary file, ending in `.usr.tmp`, left behind when the game was interrupted while
+    /// saving. The save of the same name may be missing or out of date.
+    InterruptedSave,
+}
+
+#[derive(Debug)]
+/// The result of checking one file in an Uplink `users` directory, as returned by
+/// `validate_users_dir`.
+pub struct FileReport {
+    /// The name of the file.
+    pub name: OsString,
+    /// The kind of file.
+    pub kind: FileKind,
+    /// A description of the file, or `None` if its header could not be read.
+    ///
+    /// This gives the Redshirt version, the length of the encoded data, and for Redshirt 2 files,
+    /// whether the SHA-1 hash was verified.
+    pub info: Option<Info>,
+    /// The version tag at the start of the decoded save, such as `SAV62`, if one was found.
+    pub save_version: Option<String>,
+    /// The error that prevented the file from being fully checked, if any.
+    pub error: Option<Error>,
+}
+
+impl FileReport {
+    #[inline]
+    #[must_use]
+    /// Returns whether the file is a save with a valid header, a verified SHA-1 hash if it is a
+    /// Redshirt 2 file, and a save version tag.
+    pub fn is_valid(&self) -> bool {
+        self.kind == FileKind::Save
+            && self.error.is_none()
+            && self.save_version.is_some()
+            && self.info.is_some_and(|info| info.verified != Some(false))
+    }
+}
+
+#[inline]
+/// Checks every save in an Uplink `users` directory.
+///
+/// A report is produced for each file whose name ends in `.usr` or `.usr.tmp`, in name order.
+/// Other files and subdirectories are ignored. A problem with one file is recorded in its report,
+/// and does not stop the others from being checked.
+///
+/// Each file is read once in full, to verify its SHA-1 hash if it is a Redshirt 2 file, and its
+/// first few bytes are then read again to find its save version tag.
+///
+/// # Errors
+///
+/// Returns an `Err` only if the directory itself cannot be read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::uplink::{validate_users_dir, FileKind};
+///
+/// for report in validate_users_dir("users").unwrap() {
+///     if report.kind == FileKind::InterruptedSave {
+///         println!("{:?}: left behind by an interrupted save", report.name);
+///     } else if !report.is_valid() {
+///         println!("{:?}: damaged ({:?})", report.name, report.error);
+///     }
+/// }
+/// ```
+pub fn validate_users_dir<P: AsRef<Path>>(path: P) -> Result<Vec<FileReport>, Error> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path).map_err(Error::Io)? {
+        let entry = entry.map_err(Error::Io)?;
+        let name = entry.file_name();
+        let kind = match name.to_str() {
+            Some(s) if s.ends_with(TEMP_SUFFIX) => FileKind::InterruptedSave,
+            Some(s) if s.ends_with(SAVE_SUFFIX) => FileKind::Save,
+            _ => continue,
+        };
+        if entry
+            .file_type()
+            .map_or(true, |file_type| !file_type.is_dir())
+        {
+            files.push((name, kind, entry.path()));
+        }
+    }
+    files.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    Ok(files
+        .into_iter()
+        .map(|(name, kind, path)| check(name, kind, &path))
+        .collect())
+}
+
+/// Checks a single file.
+fn check(name: OsString, kind: FileKind, path: &Path) -> FileReport {
+    let mut report = FileReport {
+        name,
+        kind,
+        info: None,
+        save_version: None,
+        error: None,
+    };
+    match inspect(path, true).and_then(|info| {
+        report.info = Some(info);
+        probe_path(path)
+    }) {
+        Ok(save_version) => report.save_version = save_version,
+        Err(e) => report.error = Some(e),
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_users_dir, FileKind};
+    use crate::{error::Error, v1, v2, version::Version};
+    use std::{env, fs, process};
+
+    #[test]
+    fn users_dir() {
+        let dir = env::temp_dir().join(format!("redshirt-users-{}", process::id()));
+        fs::create_dir_all(dir.join("subdir.usr")).unwrap();
+        fs::write(dir.join("agent.usr"), v2::encode(b"SAV62\0data")).unwrap();
+        fs::write(dir.join("agent.usr.tmp"), v2::encode(b"SAV62\0da")).unwrap();
+        fs::write(dir.join("old.usr"), v1::encode(b"SAV56\0data")).unwrap();
+        let mut corrupt = v2::encode(b"SAV62\0data");
+        *corrupt.last_mut().unwrap() ^= 1;
+        fs::write(dir.join("corrupt.usr"), corrupt).unwrap();
+        fs::write(dir.join("plain.usr"), b"SAV62\0data").unwrap();
+        fs::write(dir.join("notes.txt"), b"").unwrap();
+        let reports = validate_users_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names = reports
+            .iter()
+            .map(|r| r.name.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "agent.usr",
+                "agent.usr.tmp",
+                "corrupt.usr",
+                "old.usr",
+                "plain.usr"
+            ]
+        );
+        let [agent, temp, corrupt, old, plain] = &reports[..] else {
+            unreachable!();
+        };
+        assert!(agent.is_valid());
+        assert_eq!(agent.info.map(|info| info.verified), Some(Some(true)));
+        assert_eq!(agent.info.map(|info| info.payload_len), Some(10));
+        assert_eq!(agent.save_version.as_deref(), Some("SAV62"));
+        assert_eq!(temp.kind, FileKind::InterruptedSave);
+        assert!(!temp.is_valid());
+        assert!(!corrupt.is_valid());
+        assert_eq!(corrupt.info.map(|info| info.verified), Some(Some(false)));
+        assert_eq!(corrupt.save_version.as_deref(), Some("SAV62"));
+        assert!(old.is_valid());
+        assert_eq!(old.info.map(|info| info.version), Some(Version::V1));
+        assert!(!plain.is_valid());
+        assert!(plain.info.is_none());
+        assert!(matches!(plain.error, Some(Error::BadHeader)));
+    }
+
+    #[test]
+    fn missing_dir() {
+        let dir = env::temp_dir().join(format!("redshirt-users-missing-{}", process::id()));
+        assert!(matches!(validate_users_dir(dir), Err(Error::Io(_))));
+    }
+}
@@ -8,147 +8,1355 @@
 //! Redshirt 1 utilities.
 //!
 //! This module provides `Reader` and `Writer` types for reading and writing Redshirt 1-encoded
-//! data, respectively.
+//! data, respectively. For data that is already in memory, `decode` and `encode` work directly on
+//! byte slices. `MultiWriter` writes the same encoded data to two output streams at once.
 
-use crate::{cursor::Cursor, error::Error};
-use std::io::{self, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "fadvise")]
+use crate::advice;
+#[cfg(all(feature = "mmap", unix))]
+use crate::mmap::Mapping;
+use crate::{
+    boxed::ReadSeek,
+    buffered::BufferedReader,
+    cursor::{self, Cursor, ReadFn, Staging, BUFFER_LEN},
+    error::{Error, Sink, SinkError},
+    forward::ForwardOnly,
+    resize::Resize,
+    tee::Tee,
+    text::{self, LineReader},
+    xor_bytes, xor_payload,
+};
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+#[cfg(feature = "flate2")]
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+#[cfg(redshirt_unstable)]
+use std::io::BorrowedCursor;
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroizing;
 
-const MARKER: [u8; MARKER_LEN] = *b"REDSHIRT\x00";
-const MARKER_LEN: usize = 9;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_core;
+#[cfg(feature = "async-std")]
+pub mod async_std;
+#[cfg(feature = "tokio-codec")]
+pub(crate) mod codec;
+#[cfg(feature = "futures-io")]
+pub mod futures_io;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+pub(crate) const MARKER: [u8; MARKER_LEN] = *b"REDSHIRT\x00";
+pub(crate) const MARKER_LEN: usize = 9;
 
 #[derive(Debug)]
 /// Reads Redshirt 1-protected data from an input stream.
 pub struct Reader<R>(Cursor<R>);
 
-#[derive(Debug)]
-/// Writes Redshirt 1-protected data to an output stream.
-pub struct Writer<W>(Cursor<W>);
+#[derive(Debug)]
+/// Writes Redshirt 1-protected data to an output stream.
+///
+/// Data is encoded in a heap buffer whose length is chosen at runtime if `BUF` is `0`, as it is by
+/// default, or otherwise in a buffer of `BUF` bytes held inline, without allocating.
+pub struct Writer<W, const BUF: usize = 0>(Cursor<W, BUF>);
+
+#[derive(Debug)]
+/// Writes Redshirt 1-protected data to two output streams, encoding it only once.
+///
+/// Writing stops at the first error on either stream. The error names the stream on which it
+/// occurred, and every later write fails, as the streams may no longer hold the same data.
+pub struct MultiWriter<A, B>(Tee<A, B>, Staging);
+
+#[cfg(all(feature = "mmap", unix))]
+#[derive(Debug)]
+/// Writes Redshirt 1-protected data of a known length into a file mapped into memory, as created
+/// by `Writer::create_mmap`.
+///
+/// Data is encoded directly into the mapping, without a write call per buffer. Writes that would
+/// exceed the length the file was created with fail without writing anything. If the writer is
+/// dropped without `finish` being called, the file keeps that length, and any part of it that was
+/// not written decodes as `0x80` bytes.
+pub struct MmapWriter {
+    map: Mapping,
+}
+
+/// A `Reader` over a boxed stream, for storing readers of different stream types together.
+pub type BoxedReader = Reader<Box<dyn ReadSeek + Send>>;
+
+/// A `Writer` over a boxed stream, for storing writers of different stream types together.
+pub type BoxedWriter = Writer<Box<dyn Write + Send>>;
+
+impl<R: Read> Reader<R> {
+    #[inline]
+    /// Creates a new reader from an existing input stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or the underlying reader produces an invalid
+    /// Redshirt 1 header.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::Reader;
+    /// use std::fs::OpenOptions;
+    ///
+    /// let file = OpenOptions::new().read(true).open("data.dat").unwrap();
+    /// let reader = Reader::new(file).unwrap();
+    /// ```
+    pub fn new(mut src: R) -> Result<Self, Error> {
+        let mut marker_buf = array!(MARKER_LEN);
+        cursor::read_exact(&mut src, &mut marker_buf)
+            .map_err(Error::Io)
+            .and_then(|()| {
+                if marker_buf == MARKER {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        version = 1,
+                        header_len = MARKER_LEN,
+                        "reading Redshirt 1 stream",
+                    );
+                    Ok(Self(Cursor::new(src)))
+                } else {
+                    Err(Error::BadHeader)
+                }
+            })
+    }
+
+    #[inline]
+    #[must_use]
+    /// Creates a reader that decodes data from the current position of an input stream, without
+    /// reading or validating a Redshirt 1 header.
+    ///
+    /// This is for encoded data whose header is stored elsewhere, or has already been read. Seeking
+    /// is relative to the position of the input stream when the reader is created.
+    ///
+    /// Nothing about the input is checked, so data that is not Redshirt 1-encoded is silently
+    /// decoded into garbage. Headerless data like this is not recognised by `Version::detect`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Reader};
+    /// use std::io::{Cursor, Read, Seek, SeekFrom};
+    ///
+    /// let mut src = Cursor::new(v1::encode(b"foobar"));
+    /// src.set_position(9);
+    /// let mut reader = Reader::from_raw(src);
+    /// assert_eq!(reader.seek(SeekFrom::Start(3)).unwrap(), 3);
+    /// let mut rest = String::new();
+    /// let _ = reader.read_to_string(&mut rest).unwrap();
+    /// assert_eq!(rest, "bar");
+    /// ```
+    pub const fn from_raw(src: R) -> Self {
+        Self(Cursor::new(src))
+    }
+
+    #[inline]
+    /// Unwraps a `Reader`, returning its underlying reader.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::Reader;
+    /// use std::fs::OpenOptions;
+    ///
+    /// let file = OpenOptions::new().read(true).open("data.dat").unwrap();
+    /// let reader = Reader::new(file).unwrap();
+    /// let inner = reader.into_inner();
+    /// ```
+    pub fn into_inner(self) -> R {
+        self.0.into_inner()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns a reference to the underlying reader.
+    pub const fn get_ref(&self) -> &R {
+        self.0.get_ref()
+    }
+
+    #[inline]
+    /// Returns a mutable reference to the underlying reader.
+    ///
+    /// Reading from or seeking the underlying reader directly leaves the `Reader` with a stale
+    /// position, so that later reads decode data from the wrong place without any error. Any such
+    /// use must be followed by a call to `Reader::resync`.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.0.get_mut()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the number of bytes of decoded data read so far, not counting the header.
+    ///
+    /// Unlike `Reader::position`, this is not affected by seeking.
+    pub const fn bytes_read(&self) -> u64 {
+        self.0.transferred()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the current position in the decoded data, without requiring the underlying reader
+    /// to implement `Seek`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Reader};
+    /// use std::io::Read;
+    ///
+    /// let encoded = v1::encode(b"foobar");
+    /// let mut reader = Reader::new(&encoded[..]).unwrap();
+    /// let mut buf = [0; 3];
+    /// reader.read_exact(&mut buf).unwrap();
+    /// assert_eq!(reader.position(), 3);
+    /// assert_eq!(reader.bytes_read(), 3);
+    /// ```
+    pub const fn position(&self) -> u64 {
+        self.0.offset()
+    }
+
+    #[inline]
+    /// Decodes the rest of the stream and writes it to `dst`, returning the number of bytes
+    /// written.
+    ///
+    /// Data is decoded in place in chunks of 16 KiB, twice the size used by `std::io::copy`, so
+    /// this should be preferred to it. Interrupted reads and writes are retried.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if reading from the underlying reader or writing to `dst` fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Reader};
+    ///
+    /// let encoded = v1::encode(b"foo");
+    /// let mut decoded = Vec::new();
+    /// let len = Reader::new(&encoded[..]).unwrap().pipe_to(&mut decoded).unwrap();
+    /// assert_eq!((len, &decoded[..]), (3, &b"foo"[..]));
+    /// ```
+    pub fn pipe_to<W: Write + ?Sized>(&mut self, dst: &mut W) -> Result<u64, Error> {
+        cursor::pump(self, dst).map_err(Error::from_io)
+    }
+
+    #[inline]
+    /// Wraps this `Reader` in a `BufferedReader` with a buffer of `capacity` bytes of decoded
+    /// data.
+    ///
+    /// Seeking within the buffered data does not discard it, so this is better suited to many
+    /// small reads and seeks than wrapping the underlying reader in a `std::io::BufReader`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Reader};
+    /// use std::io::{BufRead, Cursor};
+    ///
+    /// let encoded = v1::encode(b"first\nsecond\n");
+    /// let reader = Reader::new(Cursor::new(encoded)).unwrap().buffered(4096);
+    /// assert_eq!(reader.lines().count(), 2);
+    /// ```
+    pub fn buffered(self, capacity: usize) -> BufferedReader<Self> {
+        BufferedReader::with_capacity(capacity, self)
+    }
+
+    #[inline]
+    /// Wraps this `Reader` in a `ForwardOnly`, which implements `Seek` by reading and discarding
+    /// decoded data, for streams that cannot seek.
+    ///
+    /// Seeks forwards from the current position are supported; others fail with an `io::Error` of
+    /// kind `Unsupported`. Positions are those of the decoded data, as returned by
+    /// `Reader::position`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Reader};
+    /// use std::io::{Read, Seek, SeekFrom};
+    ///
+    /// let encoded = v1::encode(b"foobar");
+    /// let mut reader = Reader::new(&encoded[..]).unwrap().forward_only();
+    /// assert_eq!(reader.seek(SeekFrom::Start(4)).unwrap(), 4);
+    /// let mut rest = String::new();
+    /// let _ = reader.read_to_string(&mut rest).unwrap();
+    /// assert_eq!(rest, "ar");
+    /// ```
+    pub fn forward_only(self) -> ForwardOnly<Self> {
+        let position = self.position();
+        ForwardOnly::at(self, position)
+    }
+
+    #[inline]
+    /// Skips over the next `n` bytes of decoded data, returning the number of bytes skipped, which
+    /// is less than `n` only if the end of the stream was reached.
+    ///
+    /// The skipped bytes are read from the underlying reader, but not decoded. If the underlying
+    /// reader supports seeking, `Seek::seek` can be used instead to avoid reading them at all.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if reading from the underlying reader fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Reader};
+    /// use std::io::Read;
+    ///
+    /// let encoded = v1::encode(b"foobar");
+    /// let mut reader = Reader::new(&encoded[..]).unwrap();
+    /// assert_eq!(reader.skip_bytes(3).unwrap(), 3);
+    /// let mut rest = String::new();
+    /// let _ = reader.read_to_string(&mut rest).unwrap();
+    /// assert_eq!(rest, "bar");
+    /// ```
+    pub fn skip_bytes(&mut self, n: u64) -> io::Result<u64> {
+        cursor::skip(n, |buf| self.0.read_encoded(buf))
+    }
+
+    #[inline]
+    /// Fills `buf` with the decoded data that follows the current position, without advancing it,
+    /// returning the number of bytes peeked, which is less than `buf.len()` only if the end of the
+    /// stream was reached.
+    ///
+    /// The peeked bytes are held by the `Reader` and returned again by later reads, so this works
+    /// even if the underlying reader cannot seek, as when sniffing the format of the decoded data.
+    /// Seeking the `Reader` discards them. Any held bytes are lost by `Reader::into_inner`, and
+    /// the underlying reader is positioned after them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if reading from the underlying reader fails, in which case any bytes read
+    /// before the failure are still held.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Reader};
+    /// use std::io::Read;
+    ///
+    /// let encoded = v1::encode(b"SAV62 data");
+    /// let mut reader = Reader::new(&encoded[..]).unwrap();
+    /// let mut magic = [0; 5];
+    /// assert_eq!(reader.peek(&mut magic).unwrap(), 5);
+    /// assert_eq!(&magic, b"SAV62");
+    /// let mut decoded = String::new();
+    /// let _ = reader.read_to_string(&mut decoded).unwrap();
+    /// assert_eq!(decoded, "SAV62 data");
+    /// ```
+    pub fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let peeked = self.0.peek_encoded(buf.len())?;
+        let len = peeked.len();
+        buf[..len].copy_from_slice(peeked);
+        xor_bytes(&mut buf[..len]);
+        Ok(len)
+    }
+
+    #[cfg(feature = "flate2")]
+    #[inline]
+    #[must_use]
+    /// Wraps this `Reader` in a `GzDecoder`, for streams whose decoded data is a gzip member.
+    ///
+    /// Invalid compressed data is reported by reads from the `GzDecoder`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{
+    ///     v1::{Reader, Writer},
+    ///     Compression,
+    /// };
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut encoder = Writer::new(Vec::new()).unwrap().with_gz(Compression::best());
+    /// encoder.write_all(b"foo").unwrap();
+    /// let encoded = encoder.finish().unwrap().into_inner();
+    /// let mut decoded = String::new();
+    /// let mut decoder = Reader::new(&encoded[..]).unwrap().with_gz();
+    /// let _ = decoder.read_to_string(&mut decoded).unwrap();
+    /// assert_eq!(decoded, "foo");
+    /// ```
+    pub fn with_gz(self) -> GzDecoder<Self> {
+        GzDecoder::new(self)
+    }
+}
+
+impl Reader<File> {
+    #[cfg(feature = "flate2")]
+    #[inline]
+    /// Opens the Redshirt 1 file at `path`, and wraps it in a `GzDecoder`, for files whose decoded
+    /// data is a gzip member.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the file cannot be opened, or does not begin with a valid Redshirt 1
+    /// header. Invalid compressed data is reported by reads from the `GzDecoder`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::Reader;
+    /// use std::io::Read;
+    ///
+    /// let mut decoded = Vec::new();
+    /// let _ = Reader::open_gz("data.dat.gz").unwrap().read_to_end(&mut decoded).unwrap();
+    /// ```
+    pub fn open_gz<P: AsRef<Path>>(path: P) -> Result<GzDecoder<Self>, Error> {
+        File::open(path)
+            .map_err(Error::Io)
+            .and_then(Self::new)
+            .map(Self::with_gz)
+    }
+
+    #[inline]
+    /// Creates a new `Reader` for the same file, at the same position in the decoded data.
+    ///
+    /// The two readers track their positions separately, but share the underlying file offset.
+    /// Before reading from one reader after using the other, call `seek(SeekFrom::Current(0))` on
+    /// it to restore its position.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the file handle cannot be duplicated, or its position cannot be read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::Reader;
+    /// use std::{
+    ///     fs::File,
+    ///     io::{Read, Seek, SeekFrom},
+    /// };
+    ///
+    /// let mut reader = Reader::new(File::open("data.dat").unwrap()).unwrap();
+    /// let mut other = reader.try_clone().unwrap();
+    /// let mut byte = [0];
+    /// other.read_exact(&mut byte).unwrap();
+    /// let _ = reader.seek(SeekFrom::Current(0)).unwrap();
+    /// reader.read_exact(&mut byte).unwrap();
+    /// ```
+    pub fn try_clone(&mut self) -> io::Result<Self> {
+        self.0.try_clone().map(Self)
+    }
+
+    #[cfg(feature = "fadvise")]
+    #[inline]
+    /// Tells the operating system that the file will be read sequentially, so that it reads ahead
+    /// more aggressively.
+    ///
+    /// This uses `posix_fadvise` on Android, FreeBSD and Linux, and does nothing on other
+    /// platforms. The hint covers the whole file, and does not change what is read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the operating system rejects the hint.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::Reader;
+    /// use std::fs::File;
+    ///
+    /// let reader = Reader::new(File::open("data.dat").unwrap()).unwrap();
+    /// reader.advise_sequential().unwrap();
+    /// ```
+    pub fn advise_sequential(&self) -> io::Result<()> {
+        advice::sequential(self.0.get_ref())
+    }
+
+    #[cfg(feature = "fadvise")]
+    #[inline]
+    /// Tells the operating system that the cached contents of the file will not be needed again,
+    /// so that they can be evicted from the page cache, for example once a file has been read in
+    /// a long batch run.
+    ///
+    /// This uses `posix_fadvise` on Android, FreeBSD and Linux, and does nothing on other
+    /// platforms. Data that has already been read is unaffected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the operating system rejects the hint.
+    pub fn advise_dontneed(&self) -> io::Result<()> {
+        advice::dont_need(self.0.get_ref())
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Reader<io::Cursor<Bytes>> {
+    #[inline]
+    /// Creates a new reader over a shared buffer of encoded data.
+    ///
+    /// Cloning a `Bytes` only increments a reference count, so any number of readers can decode the
+    /// same buffer, each at its own position, without copying it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if `data` does not begin with a valid Redshirt 1 header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bytes::Bytes;
+    /// use redshirt::v1::{self, Reader};
+    /// use std::io::{Read, Seek, SeekFrom};
+    ///
+    /// let encoded = Bytes::from(v1::encode(b"Hello world!"));
+    /// let mut reader = Reader::from_bytes(encoded.clone()).unwrap();
+    /// let mut other = Reader::from_bytes(encoded).unwrap();
+    /// let _ = other.seek(SeekFrom::Start(6)).unwrap();
+    /// let mut decoded = String::new();
+    /// let _ = other.read_to_string(&mut decoded).unwrap();
+    /// assert_eq!(decoded, "world!");
+    /// decoded.clear();
+    /// let _ = reader.read_to_string(&mut decoded).unwrap();
+    /// assert_eq!(decoded, "Hello world!");
+    /// ```
+    pub fn from_bytes(data: Bytes) -> Result<Self, Error> {
+        Self::new(io::Cursor::new(data))
+    }
+}
+
+impl BoxedReader {
+    #[inline]
+    /// Boxes an input stream and creates a new reader from it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or the underlying reader produces an invalid
+    /// Redshirt 1 header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, BoxedReader};
+    /// use std::io::{Cursor, Read};
+    ///
+    /// let encoded = v1::encode(b"Hello world!");
+    /// let mut readers = vec![
+    ///     BoxedReader::from_boxed(Cursor::new(encoded.clone())).unwrap(),
+    ///     BoxedReader::from_boxed(Cursor::new(encoded.into_boxed_slice())).unwrap(),
+    /// ];
+    /// for reader in &mut readers {
+    ///     let mut decoded = String::new();
+    ///     let _ = reader.read_to_string(&mut decoded).unwrap();
+    ///     assert_eq!(decoded, "Hello world!");
+    /// }
+    /// ```
+    pub fn from_boxed<R: Read + Seek + Send + 'static>(src: R) -> Result<Self, Error> {
+        Self::new(Box::new(src))
+    }
+}
+
+impl<R: Seek> Reader<R> {
+    #[inline]
+    /// Re-establishes the position in the decoded data from the position of the underlying
+    /// reader, after the underlying reader has been moved through `Reader::get_mut`.
+    ///
+    /// The position of the start of the data in the underlying reader is recorded by the first
+    /// seek, so a `Reader` must have been seeked at least once, for example by calling
+    /// `stream_position`, before its underlying reader is moved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, the `Reader` has never been seeked, or the
+    /// underlying reader is positioned before the start of the data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Reader};
+    /// use std::io::{Cursor, Read, Seek};
+    ///
+    /// let mut reader = Reader::new(Cursor::new(v1::encode(b"foobar"))).unwrap();
+    /// let _ = reader.stream_position().unwrap();
+    /// reader.get_mut().set_position(12);
+    /// reader.resync().unwrap();
+    /// let mut rest = String::new();
+    /// let _ = reader.read_to_string(&mut rest).unwrap();
+    /// assert_eq!(rest, "bar");
+    /// ```
+    pub fn resync(&mut self) -> io::Result<()> {
+        self.0.resync()
+    }
+}
+
+impl<R: Read> Read for Reader<R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+
+    #[cfg(redshirt_unstable)]
+    #[inline]
+    fn read_buf(&mut self, cursor: BorrowedCursor<'_>) -> io::Result<()> {
+        self.0.read_buf(cursor)
+    }
+}
+
+impl<R: Seek> Seek for Reader<R> {
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+impl<W: Write> Writer<W> {
+    #[inline]
+    /// Wraps an existing output stream and writes a valid Redshirt 1 header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the Redshirt 1 header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::Writer;
+    ///
+    /// let mut data = [u8::default(); 10];
+    /// let writer = Writer::new(&mut data[..]).unwrap();
+    /// ```
+    pub fn new(dst: W) -> Result<Self, Error> {
+        Self::with_buffer_len(dst, BUFFER_LEN)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Wraps an existing output stream without writing a Redshirt 1 header, so that only encoded
+    /// data is written.
+    ///
+    /// This is for encoded data whose header is stored elsewhere, or has already been written.
+    /// Seeking is relative to the position of the output stream when the writer is created. The
+    /// output alone is not a Redshirt 1 stream, and is not recognised by `Version::detect`,
+    /// `v1::decode` or `Reader::new`; read it with `Reader::from_raw` instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Writer};
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::from_raw(Vec::new());
+    /// writer.write_all(b"foobar").unwrap();
+    /// assert_eq!(writer.into_inner(), &v1::encode(b"foobar")[9..]);
+    /// ```
+    pub const fn from_raw(dst: W) -> Self {
+        Self(Cursor::new(dst))
+    }
+
+    #[inline]
+    /// Wraps an existing output stream and writes a valid Redshirt 1 header, encoding data in a
+    /// buffer of `len` bytes instead of the default 16 KiB. A value of `0` is treated as `1`.
+    ///
+    /// The buffer is allocated on the heap by the first write and reused by every later one. Each
+    /// call to `write` encodes and writes at most `len` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the Redshirt 1 header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Writer};
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::with_buffer_len(Vec::new(), 4).unwrap();
+    /// assert_eq!(writer.write(b"foobar").unwrap(), 4);
+    /// writer.write_all(b"ar").unwrap();
+    /// assert_eq!(writer.into_inner(), v1::encode(b"foobar"));
+    /// ```
+    pub fn with_buffer_len(mut dst: W, len: usize) -> Result<Self, Error> {
+        cursor::write_all(&mut dst, &MARKER)
+            .map(|()| Self(Cursor::with_buffer_len(dst, len)))
+            .map_err(Error::Io)
+    }
+}
+
+impl<W: Write, const BUF: usize> Writer<W, BUF> {
+    #[inline]
+    /// Wraps an existing output stream and writes a valid Redshirt 1 header, encoding data in an
+    /// inline buffer of `BUF` bytes. If `BUF` is `0`, this is the same as `Writer::new`.
+    ///
+    /// Each call to `write` encodes and writes at most `BUF` bytes. As the buffer is part of the
+    /// `Writer`, a large `BUF` makes the `Writer` itself large.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the Redshirt 1 header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Writer};
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::<_, 4>::with_inline_buffer(Vec::new()).unwrap();
+    /// assert_eq!(writer.write(b"foobar").unwrap(), 4);
+    /// writer.write_all(b"ar").unwrap();
+    /// assert_eq!(writer.into_inner(), v1::encode(b"foobar"));
+    /// ```
+    pub fn with_inline_buffer(mut dst: W) -> Result<Self, Error> {
+        cursor::write_all(&mut dst, &MARKER)
+            .map(|()| Self(Cursor::with_inline_buffer(dst)))
+            .map_err(Error::Io)
+    }
+
+    #[inline]
+    /// Unwraps a `Writer`, returning its underlying writer.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::Writer;
+    ///
+    /// let mut data = [u8::default(); 10];
+    /// let writer = Writer::new(&mut data[..]).unwrap();
+    /// let inner = writer.into_inner();
+    /// ```
+    pub fn into_inner(self) -> W {
+        self.0.into_inner()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns a reference to the underlying writer.
+    pub const fn get_ref(&self) -> &W {
+        self.0.get_ref()
+    }
+
+    #[inline]
+    /// Returns a mutable reference to the underlying writer.
+    ///
+    /// Writing to or seeking the underlying writer directly leaves the `Writer` with a stale
+    /// position, which is then reported by `Writer::position` and used by later seeks. Any such
+    /// use must be followed by a call to `Writer::resync`.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.0.get_mut()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the number of bytes of data written so far, not counting the header.
+    ///
+    /// Unlike `Writer::position`, this is not affected by seeking.
+    pub const fn bytes_written(&self) -> u64 {
+        self.0.transferred()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the current position in the data written, without requiring the underlying writer
+    /// to implement `Seek`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::Writer;
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::new(Vec::new()).unwrap();
+    /// writer.write_all(b"foo").unwrap();
+    /// assert_eq!(writer.position(), 3);
+    /// assert_eq!(writer.bytes_written(), 3);
+    /// ```
+    pub const fn position(&self) -> u64 {
+        self.0.offset()
+    }
+
+    #[inline]
+    /// Encodes everything read from `src` and writes it to the underlying writer, returning the
+    /// number of bytes read.
+    ///
+    /// Data is read from `src` directly into the buffer in which it is encoded, so this copies it
+    /// once less than `std::io::copy`, and should be preferred to it. Interrupted reads and writes
+    /// are retried.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if reading from `src` or writing to the underlying writer fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v1::{self, Writer};
+    ///
+    /// let mut writer = Writer::new(Vec::new()).unwrap();
+    /// assert_eq!(writer.write_reader(&mut &b"foo"[..]).unwrap(), 3);
+    /// assert_eq!(writer.into_inner(), v1::encode(b"foo"));
+    /// ```
+    pub fn write_reader<R: Read + ?Sized>(&mut self, src: &mut R) -> Result<u64, Error> {
+        cursor::pump_with(|| {
+            self.0
+                .write_from(src, usize::MAX, true)
+                .map(|chunk| chunk.len())
+        })
+        .map_err(Error::Io)
+    }
+
+    #[cfg(feature = "flate2")]
+    #[inline]
+    #[must_use]
+    /// Wraps this `Writer` in a `GzEncoder` that compresses data at the given `level` before it is
+    /// encoded.
+    ///
+    /// Call `GzEncoder::finish` to write the end of the gzip member and get this `Writer` back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{v1::Writer, Compression};
+    /// use std::io::Write;
+    ///
+    /// let mut encoder = Writer::new(Vec::new()).unwrap().with_gz(Compression::fast());
+    /// encoder.write_all(b"foo").unwrap();
+    /// let encoded = encoder.finish().unwrap().into_inner();
+    /// assert!(encoded.starts_with(b"REDSHIRT\x00"));
+    /// ```
+    pub fn with_gz(self, level: Compression) -> GzEncoder<Self> {
+        GzEncoder::new(self, level)
+    }
+}
+
+#[cfg(feature = "flate2")]
+impl Writer<File> {
+    #[inline]
+    /// Creates the Redshirt 1 file at `path`, truncating it if it exists, and wraps it in a
+    /// `GzEncoder` that compresses data at the given `level` before it is encoded.
+    ///
+    /// Call `GzEncoder::finish` to write the end of the gzip member.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the file cannot be created, or the header cannot be written.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::{v1::Writer, Compression};
+    /// use std::io::Write;
+    ///
+    /// let mut encoder = Writer::create_gz("data.dat.gz", Compression::default()).unwrap();
+    /// encoder.write_all(b"foo").unwrap();
+    /// let _ = encoder.finish().unwrap();
+    /// ```
+    pub fn create_gz<P: AsRef<Path>>(
+        path: P,
+        level: Compression,
+    ) -> Result<GzEncoder<Self>, Error> {
+        File::create(path)
+            .map_err(Error::Io)
+            .and_then(Self::new)
+            .map(|writer| writer.with_gz(level))
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl Writer<File> {
+    #[inline]
+    /// Creates the Redshirt 1 file at `path`, truncating it if it exists, sizes it to hold
+    /// `payload_len` bytes of data, and maps it into memory to encode data into.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the file cannot be created, resized or mapped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::Writer;
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::create_mmap("data.dat", 6).unwrap();
+    /// writer.write_all(b"foobar").unwrap();
+    /// let _ = writer.finish().unwrap();
+    /// ```
+    pub fn create_mmap<P: AsRef<Path>>(path: P, payload_len: u64) -> Result<MmapWriter, Error> {
+        Mapping::create(path.as_ref(), &MARKER, payload_len).map(|map| MmapWriter { map })
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl MmapWriter {
+    #[inline]
+    #[must_use]
+    /// Returns the number of bytes of data written so far, not counting the header.
+    pub fn position(&self) -> u64 {
+        self.map.position()
+    }
+
+    #[inline]
+    /// Writes the mapped data back to the file and unmaps it, returning the file.
+    ///
+    /// If less data was written than the file was created to hold, the file is truncated to the
+    /// end of the data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing back the data or truncating the file fails.
+    pub fn finish(self) -> Result<File, Error> {
+        self.map.finish().map_err(Error::Io)
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl Write for MmapWriter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        xor_bytes(self.map.append(buf)?);
+        Ok(buf.len())
+    }
 
-impl<R: Read> Reader<R> {
     #[inline]
-    /// Creates a new reader from an existing input stream.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl BoxedWriter {
+    #[inline]
+    /// Boxes an output stream and creates a new writer from it.
     ///
     /// # Errors
     ///
-    /// Returns an `Err` if an I/O error occurs, or the underlying reader produces an invalid
-    /// Redshirt 1 header.
+    /// Returns an `Err` if writing the Redshirt 1 header fails.
     ///
     /// # Examples
     ///
-    /// ```no_run
-    /// use redshirt::v1::Reader;
-    /// use std::fs::OpenOptions;
+    /// ```
+    /// use redshirt::v1::BoxedWriter;
+    /// use std::io::{self, Write};
     ///
-    /// let file = OpenOptions::new().read(true).open("data.dat").unwrap();
-    /// let reader = Reader::new(file).unwrap();
+    /// let mut writer = BoxedWriter::from_boxed(io::sink()).unwrap();
+    /// writer.write_all(b"Hello world!").unwrap();
     /// ```
-    pub fn new(mut src: R) -> Result<Self, Error> {
-        let mut marker_buf = array!(MARKER_LEN);
-        src.read_exact(&mut marker_buf)
-            .map_err(Error::Io)
-            .and_then(|_| {
-                if marker_buf == MARKER {
-                    Ok(Self(Cursor::new(src)))
-                } else {
-                    Err(Error::BadHeader)
-                }
-            })
+    pub fn from_boxed<W: Write + Send + 'static>(dst: W) -> Result<Self, Error> {
+        Self::new(Box::new(dst))
     }
+}
 
+impl<W: Seek, const BUF: usize> Writer<W, BUF> {
     #[inline]
-    /// Unwraps a `Reader`, returning its underlying reader.
+    /// Re-establishes the position in the data from the position of the underlying writer, after
+    /// the underlying writer has been moved through `Writer::get_mut`.
+    ///
+    /// The position of the start of the data in the underlying writer is recorded by the first
+    /// seek, so a `Writer` must have been seeked at least once, for example by calling
+    /// `stream_position`, before its underlying writer is moved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, the `Writer` has never been seeked, or the
+    /// underlying writer is positioned before the start of the data.
+    pub fn resync(&mut self) -> io::Result<()> {
+        self.0.resync()
+    }
+}
+
+impl<W: Write + Seek + Resize, const BUF: usize> Writer<W, BUF> {
+    #[inline]
+    /// Truncates or extends the data to `len` bytes, not counting the header.
+    ///
+    /// Bytes added by extending the data are encoded zeroes, so they decode as zeroes rather than
+    /// as `0x80`. If the position is past the new end of the data, it is moved to the end;
+    /// otherwise it is unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or the new length of the underlying writer would
+    /// overflow a `u64`.
     ///
     /// # Examples
     ///
-    /// ```no_run
-    /// use redshirt::v1::Reader;
-    /// use std::fs::OpenOptions;
+    /// ```
+    /// use redshirt::v1::{self, Writer};
+    /// use std::io::{Cursor, Write};
     ///
-    /// let file = OpenOptions::new().read(true).open("data.dat").unwrap();
-    /// let reader = Reader::new(file).unwrap();
-    /// let inner = reader.into_inner();
+    /// let mut writer = Writer::new(Cursor::new(Vec::new())).unwrap();
+    /// writer.write_all(b"foobar").unwrap();
+    /// writer.set_payload_len(3).unwrap();
+    /// assert_eq!(writer.position(), 3);
+    /// writer.set_payload_len(5).unwrap();
+    /// let encoded = writer.into_inner().into_inner();
+    /// assert_eq!(v1::decode(&encoded).unwrap(), b"foo\0\0");
     /// ```
-    pub fn into_inner(self) -> R {
-        self.0.into_inner()
+    pub fn set_payload_len(&mut self, len: u64) -> io::Result<()> {
+        self.0.set_len(len)
     }
 }
 
-impl<R: Read> Read for Reader<R> {
+impl<W: Write, const BUF: usize> Write for Writer<W, BUF> {
     #[inline]
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.0.read(buf)
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
     }
 }
 
-impl<R: Seek> Seek for Reader<R> {
+impl<W: Seek, const BUF: usize> Seek for Writer<W, BUF> {
     #[inline]
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         self.0.seek(pos)
     }
 }
 
-impl<W: Write> Writer<W> {
+impl<A: Write, B: Write> MultiWriter<A, B> {
     #[inline]
-    /// Wraps an existing output stream and writes a valid Redshirt 1 header.
+    /// Wraps two existing output streams and writes a valid Redshirt 1 header to each.
     ///
     /// # Errors
     ///
-    /// Returns an `Err` if writing the Redshirt 1 header fails.
+    /// Returns an `Err` if writing either header fails.
     ///
     /// # Examples
     ///
-    /// ```no_run
-    /// use redshirt::v1::Writer;
+    /// ```
+    /// use redshirt::v1::MultiWriter;
+    /// use std::io::Write;
     ///
-    /// let mut data = [u8::default(); 10];
-    /// let writer = Writer::new(&mut data[..]).unwrap();
+    /// let mut writer = MultiWriter::new(Vec::new(), Vec::new()).unwrap();
+    /// writer.write_all(b"foo").unwrap();
+    /// let (primary, secondary) = writer.into_inner();
+    /// assert_eq!(primary, secondary);
     /// ```
-    pub fn new(mut dst: W) -> Result<Self, Error> {
-        dst.write_all(&MARKER)
-            .map(|_| Self(Cursor::new(dst)))
-            .map_err(Error::Io)
+    pub fn new(primary: A, secondary: B) -> Result<Self, SinkError> {
+        Tee::new(primary, secondary, &MARKER).map(|tee| Self(tee, Staging::new(BUFFER_LEN)))
     }
+}
 
+impl<A, B> MultiWriter<A, B> {
     #[inline]
-    /// Unwraps a `Writer`, returning its underlying writer.
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// use redshirt::v1::Writer;
-    ///
-    /// let mut data = [u8::default(); 10];
-    /// let writer = Writer::new(&mut data[..]).unwrap();
-    /// let inner = writer.into_inner();
-    /// ```
-    pub fn into_inner(self) -> W {
+    #[must_use]
+    /// Returns the output stream on which a write failed, if any.
+    pub const fn failed(&self) -> Option<Sink> {
+        self.0.failed()
+    }
+
+    #[inline]
+    /// Unwraps a `MultiWriter`, returning its underlying writers.
+    pub fn into_inner(self) -> (A, B) {
         self.0.into_inner()
     }
 }
 
-impl<W: Write> Write for Writer<W> {
+impl<A: Write, B: Write> Write for MultiWriter<A, B> {
     #[inline]
+    /// Encodes a chunk of `buf` and writes it to both output streams.
+    ///
+    /// If writing to either stream fails, the returned `io::Error` wraps a `SinkError`.
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.0.write(buf)
+        let encoded = self.1.encode(buf);
+        self.0
+            .write_all(&encoded)
+            .map(|()| encoded.len())
+            .map_err(io::Error::from)
     }
 
     #[inline]
     fn flush(&mut self) -> io::Result<()> {
-        self.0.flush()
+        self.0.flush().map_err(io::Error::from)
     }
 }
 
-impl<W: Seek> Seek for Writer<W> {
-    #[inline]
-    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        self.0.seek(pos)
+#[inline]
+/// Decodes an in-memory Redshirt 1 stream.
+///
+/// # Errors
+///
+/// Returns an `Err` if `encoded` does not begin with a valid Redshirt 1 header.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v1;
+///
+/// let decoded = v1::decode(b"REDSHIRT\x00\xE6\xEF\xEF").unwrap();
+/// assert_eq!(decoded, b"foo");
+/// ```
+pub fn decode(encoded: &[u8]) -> Result<Vec<u8>, Error> {
+    if encoded.len() >= MARKER_LEN && encoded[..MARKER_LEN] == MARKER {
+        let mut decoded = encoded[MARKER_LEN..].to_vec();
+        xor_payload(&mut decoded);
+        Ok(decoded)
+    } else {
+        Err(Error::BadHeader)
+    }
+}
+
+#[inline]
+#[must_use]
+/// Encodes `data` as an in-memory Redshirt 1 stream.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v1;
+///
+/// let encoded = v1::encode(b"foo");
+/// assert_eq!(encoded, b"REDSHIRT\x00\xE6\xEF\xEF");
+/// ```
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(MARKER_LEN + data.len());
+    encoded.extend_from_slice(&MARKER);
+    encoded.extend_from_slice(data);
+    xor_payload(&mut encoded[MARKER_LEN..]);
+    encoded
+}
+
+#[inline]
+/// Copies the rest of the data from `src` to `dst` without decoding and re-encoding it, returning
+/// the number of bytes copied.
+///
+/// The encoded data is moved from the underlying reader of `src` to the underlying writer of `dst`
+/// as-is, so this copies at the speed of the streams themselves. Peeked data is copied too.
+/// Interrupted reads and writes are retried.
+///
+/// # Errors
+///
+/// Returns an `Err` if reading from `src` or writing to `dst` fails.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v1::{self, Reader, Writer};
+///
+/// let encoded = v1::encode(b"foobar");
+/// let mut src = Reader::new(&encoded[..]).unwrap();
+/// let mut dst = Writer::new(Vec::new()).unwrap();
+/// assert_eq!(v1::copy_raw(&mut src, &mut dst).unwrap(), 6);
+/// assert_eq!(dst.into_inner(), encoded);
+/// ```
+pub fn copy_raw<R: Read, W: Write, const BUF: usize>(
+    src: &mut Reader<R>,
+    dst: &mut Writer<W, BUF>,
+) -> Result<u64, Error> {
+    let mut encoded = ReadFn(|buf: &mut [u8]| src.0.read_encoded(buf));
+    cursor::pump_with(|| {
+        dst.0
+            .write_from(&mut encoded, usize::MAX, false)
+            .map(|chunk| chunk.len())
+    })
+    .map_err(Error::from_io)
+}
+
+#[inline]
+/// Decodes a Redshirt 1 stream as UTF-8 text.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, `src` produces an invalid Redshirt 1 header, or the
+/// decoded data is not valid UTF-8, in which case the error is `Error::InvalidText`.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v1;
+///
+/// let encoded = v1::encode_str(Vec::new(), "caf\u{e9}").unwrap();
+/// assert_eq!(v1::decode_to_string(&encoded[..]).unwrap(), "caf\u{e9}");
+/// ```
+pub fn decode_to_string<R: Read>(src: R) -> Result<String, Error> {
+    read_all(src).and_then(text::to_string)
+}
+
+#[inline]
+/// Decodes a Redshirt 1 stream as UTF-8 text, replacing any invalid UTF-8 with `U+FFFD`.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, or `src` produces an invalid Redshirt 1 header.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v1;
+///
+/// let encoded = v1::encode(b"caf\xe9");
+/// assert_eq!(v1::decode_to_string_lossy(&encoded[..]).unwrap(), "caf\u{fffd}");
+/// ```
+pub fn decode_to_string_lossy<R: Read>(src: R) -> Result<String, Error> {
+    read_all(src).map(text::to_string_lossy)
+}
+
+#[inline]
+/// Decodes a Redshirt 1 stream into memory, unless the decoded data is longer than `max_len`
+/// bytes.
+///
+/// Since a Redshirt 1 stream has no length or hash to check up front, the limit is enforced as
+/// data is read, and reading stops as soon as it is exceeded. The buffer is grown with
+/// `Vec::try_reserve`, so a failure to allocate memory is returned as an I/O error of kind
+/// `OutOfMemory` instead of aborting the process.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, `src` produces an invalid Redshirt 1 header, or the
+/// decoded data is longer than `max_len`, in which case the error is `Error::PayloadTooLarge`.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::{v1, Error};
+///
+/// let encoded = v1::encode(b"foobar");
+/// assert_eq!(v1::decode_to_vec_limited(&encoded[..], 6).unwrap(), b"foobar");
+/// assert!(matches!(
+///     v1::decode_to_vec_limited(&encoded[..], 4),
+///     Err(Error::PayloadTooLarge { limit: 4, .. })
+/// ));
+/// ```
+pub fn decode_to_vec_limited<R: Read>(src: R, max_len: u64) -> Result<Vec<u8>, Error> {
+    let decoded = cursor::read_capped(Reader::new(src)?, 0, max_len).map_err(Error::Io)?;
+    let len = cursor::len_u64(decoded.len());
+    if len > max_len {
+        Err(Error::PayloadTooLarge {
+            limit: max_len,
+            attempted: len,
+        })
+    } else {
+        Ok(decoded)
     }
 }
 
+#[inline]
+/// Decodes the first `n` bytes of a Redshirt 1 stream, or all of them if there are fewer, reading
+/// only the header and those bytes.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, or `src` produces an invalid Redshirt 1 header.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v1;
+///
+/// let encoded = v1::encode(b"foobar");
+/// assert_eq!(v1::decode_prefix(&encoded[..], 3).unwrap(), b"foo");
+/// assert_eq!(v1::decode_prefix(&encoded[..], 10).unwrap(), b"foobar");
+/// ```
+pub fn decode_prefix<R: Read>(src: R, n: usize) -> Result<Vec<u8>, Error> {
+    let mut decoded = Vec::new();
+    let _ = Reader::new(src)?
+        .take(cursor::len_u64(n))
+        .read_to_end(&mut decoded)
+        .map_err(Error::Io)?;
+    Ok(decoded)
+}
+
+#[cfg(feature = "zeroize")]
+#[inline]
+/// Decodes a Redshirt 1 stream into a buffer that is overwritten with zeroes when dropped.
+///
+/// No copy of the decoded data is left in memory freed while decoding. See the
+/// [crate-level documentation](../index.html#scrubbing-decoded-data) for what is and isn't
+/// covered.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, or `src` produces an invalid Redshirt 1 header.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v1;
+///
+/// let decoded = v1::decode_to_vec_zeroizing(&v1::encode(b"hunter2")[..]).unwrap();
+/// assert_eq!(&decoded[..], b"hunter2");
+/// ```
+pub fn decode_to_vec_zeroizing<R: Read>(src: R) -> Result<Zeroizing<Vec<u8>>, Error> {
+    cursor::read_zeroizing(Reader::new(src)?).map_err(Error::Io)
+}
+
+#[inline]
+/// Writes `text` to `dst` as a Redshirt 1 stream, returning `dst`.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v1;
+///
+/// let encoded = v1::encode_str(Vec::new(), "foo").unwrap();
+/// assert_eq!(encoded, v1::encode(b"foo"));
+/// ```
+pub fn encode_str<W: Write>(dst: W, text: &str) -> Result<W, Error> {
+    let mut writer = Writer::new(dst)?;
+    writer.write_all(text.as_bytes()).map_err(Error::Io)?;
+    Ok(writer.into_inner())
+}
+
+fn read_all<R: Read>(src: R) -> Result<Vec<u8>, Error> {
+    let mut decoded = Vec::new();
+    let _ = Reader::new(src)?
+        .read_to_end(&mut decoded)
+        .map_err(Error::Io)?;
+    Ok(decoded)
+}
+
+#[inline]
+/// Opens the Redshirt 1 file at `path`, returning an iterator over the lines of its decoded data.
+///
+/// See `text::LineReader` for how lines are read.
+///
+/// # Errors
+///
+/// Returns an `Err` if the file cannot be opened, or does not begin with a valid Redshirt 1
+/// header.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::v1;
+///
+/// for line in v1::read_lines("data/world.dat").unwrap() {
+///     println!("{}", line.unwrap());
+/// }
+/// ```
+pub fn read_lines<P: AsRef<Path>>(path: P) -> Result<LineReader<Reader<File>>, Error> {
+    File::open(path)
+        .map_err(Error::Io)
+        .and_then(Reader::new)
+        .map(LineReader::new)
+}
+
 #[cfg(test)]
+// Lints that newer versions of Clippy raise on these tests, which predate them.
+#[allow(
+    clippy::cast_possible_wrap,
+    clippy::legacy_numeric_constants,
+    clippy::seek_from_current,
+    clippy::should_panic_without_expect
+)]
 mod tests {
-    use super::{Reader, Writer, MARKER_LEN};
-    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+    use super::{
+        decode, decode_prefix, decode_to_string, decode_to_string_lossy, decode_to_vec_limited,
+        encode, encode_str, read_lines, MultiWriter, Reader, Writer, MARKER_LEN,
+    };
+    use crate::{
+        cursor::{Overclaim, Trickle, BUFFER_LEN},
+        error::{Error, Sink, SinkError},
+    };
+    use std::{
+        env,
+        fs::{self, File},
+        io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write},
+        process,
+    };
 
     const MSG_DEC: &[u8] = b"Hello world!";
     const MSG_ENC: &[u8] = b"REDSHIRT\x00\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
@@ -233,6 +1441,168 @@ mod tests {
         let _ = reader.seek(SeekFrom::Current(-1)).unwrap();
     }
 
+    #[test]
+    fn reader_counters() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        let mut buffer = array!(MSG_LEN);
+        reader.read_exact(&mut buffer[..4]).unwrap();
+        assert_eq!((reader.position(), reader.bytes_read()), (4, 4));
+        assert_eq!(reader.seek(SeekFrom::Start(2)).unwrap(), 2);
+        assert_eq!((reader.position(), reader.bytes_read()), (2, 4));
+        reader.read_exact(&mut buffer[..6]).unwrap();
+        assert_eq!((reader.position(), reader.bytes_read()), (8, 10));
+        let _ = reader.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!((reader.position(), reader.bytes_read()), (MSG_LEN_U64, 14));
+        assert!(format!("{reader:?}").contains("transferred: 14"));
+    }
+
+    #[test]
+    fn reader_peek() {
+        let mut reader = Reader::new(Trickle::new(MSG_ENC)).unwrap();
+        let mut peeked = array!(MSG_LEN);
+        assert_eq!(reader.peek(&mut peeked[..5]).unwrap(), 5);
+        assert_eq!(&peeked[..5], &MSG_DEC[..5]);
+        assert_eq!(reader.peek(&mut peeked[..8]).unwrap(), 8);
+        assert_eq!(&peeked[..8], &MSG_DEC[..8]);
+        assert_eq!((reader.position(), reader.bytes_read()), (0, 0));
+        let mut buffer = array!(MSG_LEN);
+        reader.read_exact(&mut buffer[..3]).unwrap();
+        assert_eq!(&buffer[..3], &MSG_DEC[..3]);
+        assert_eq!(reader.peek(&mut peeked).unwrap(), MSG_LEN - 3);
+        assert_eq!(&peeked[..MSG_LEN - 3], &MSG_DEC[3..]);
+        let mut rest = Vec::new();
+        let _ = reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &MSG_DEC[3..]);
+        assert_eq!(
+            (reader.position(), reader.bytes_read()),
+            (MSG_LEN_U64, MSG_LEN_U64)
+        );
+        assert_eq!(reader.peek(&mut peeked).unwrap(), 0);
+    }
+
+    #[test]
+    fn reader_peek_seek() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        let mut peeked = array!(4);
+        assert_eq!(reader.peek(&mut peeked).unwrap(), 4);
+        assert_eq!(reader.stream_position().unwrap(), 0);
+        let mut buffer = array!(4);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, &MSG_DEC[..4]);
+        assert_eq!(reader.peek(&mut peeked).unwrap(), 4);
+        assert_eq!(reader.seek(SeekFrom::Current(2)).unwrap(), 6);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, &MSG_DEC[6..10]);
+        assert_eq!(reader.peek(&mut peeked[..2]).unwrap(), 2);
+        assert_eq!(reader.seek(SeekFrom::Start(1)).unwrap(), 1);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, &MSG_DEC[1..5]);
+    }
+
+    #[test]
+    fn copy_raw() {
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let encoded = encode(&data);
+        let mut src = Reader::new(Trickle::new(&encoded[..])).unwrap();
+        let mut buffer = array!(4);
+        src.read_exact(&mut buffer).unwrap();
+        assert_eq!(src.peek(&mut buffer).unwrap(), 4);
+        let mut dst = Writer::new(Vec::new()).unwrap();
+        dst.write_all(b"foo").unwrap();
+        assert_eq!(
+            super::copy_raw(&mut src, &mut dst).unwrap(),
+            data.len() as u64 - 4
+        );
+        assert_eq!(
+            (src.position(), dst.position()),
+            (data.len() as u64, data.len() as u64 - 1)
+        );
+        let mut expected = b"foo".to_vec();
+        expected.extend_from_slice(&data[4..]);
+        assert_eq!(decode(&dst.into_inner()).unwrap(), expected);
+    }
+
+    #[test]
+    fn reader_from_raw() {
+        let mut src = Cursor::new(MSG_ENC);
+        src.set_position(MARKER_LEN as u64);
+        let mut reader = Reader::from_raw(src);
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+        assert_eq!(reader.seek(SeekFrom::Start(6)).unwrap(), 6);
+        assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), MSG_LEN_U64 - 2);
+        assert!(reader.seek(SeekFrom::Current(-11)).is_err());
+        assert_eq!(
+            reader.get_ref().position(),
+            MARKER_LEN as u64 + MSG_LEN_U64 - 2
+        );
+    }
+
+    #[test]
+    fn writer_from_raw() {
+        let mut dst = Cursor::new(MSG_ENC[..MARKER_LEN].to_vec());
+        dst.set_position(MARKER_LEN as u64);
+        let mut writer = Writer::from_raw(dst);
+        writer.write_all(&MSG_DEC[..8]).unwrap();
+        assert_eq!(writer.seek(SeekFrom::Start(4)).unwrap(), 4);
+        writer.write_all(&MSG_DEC[4..]).unwrap();
+        assert_eq!(writer.into_inner().into_inner(), MSG_ENC);
+    }
+
+    #[test]
+    fn reader_resync() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        assert_eq!(
+            reader.resync().unwrap_err().kind(),
+            ErrorKind::Other,
+            "the start of the data is not known before the first seek",
+        );
+        assert_eq!(reader.stream_position().unwrap(), 0);
+        reader.get_mut().set_position(MARKER_LEN as u64 + 6);
+        reader.resync().unwrap();
+        assert_eq!(reader.position(), 6);
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, &MSG_DEC[6..]);
+        reader.get_mut().set_position(MARKER_LEN as u64 - 1);
+        assert_eq!(reader.resync().unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(reader.get_ref().position(), MARKER_LEN as u64 - 1);
+    }
+
+    #[test]
+    fn writer_resync() {
+        let mut buffer = array!(MARKER_LEN + MSG_LEN);
+        let mut writer = Writer::new(Cursor::new(&mut buffer[..])).unwrap();
+        writer.write_all(&MSG_DEC[..4]).unwrap();
+        assert_eq!(writer.stream_position().unwrap(), 4);
+        writer.get_mut().set_position(MARKER_LEN as u64 + 8);
+        writer.resync().unwrap();
+        writer.write_all(&MSG_DEC[8..]).unwrap();
+        assert_eq!(writer.seek(SeekFrom::Start(4)).unwrap(), 4);
+        writer.write_all(&MSG_DEC[4..8]).unwrap();
+        assert_eq!(writer.position(), 8);
+        let _ = writer.into_inner();
+        assert_eq!(buffer, MSG_ENC);
+    }
+
+    #[test]
+    fn writer_counters() {
+        let mut buffer = array!(MARKER_LEN + MSG_LEN);
+        let mut writer = Writer::new(Cursor::new(&mut buffer[..])).unwrap();
+        writer.write_all(&MSG_DEC[..8]).unwrap();
+        assert_eq!((writer.position(), writer.bytes_written()), (8, 8));
+        assert_eq!(writer.seek(SeekFrom::Start(4)).unwrap(), 4);
+        writer.write_all(&MSG_DEC[4..]).unwrap();
+        assert_eq!(
+            (writer.position(), writer.bytes_written()),
+            (MSG_LEN_U64, 16)
+        );
+        assert!(format!("{writer:?}").contains("transferred: 16"));
+        let _ = writer.into_inner();
+        assert_eq!(buffer, MSG_ENC);
+    }
+
     #[test]
     fn writer_write() {
         let mut buffer = array!(MARKER_LEN + MSG_LEN);
@@ -248,6 +1618,46 @@ mod tests {
         assert_eq!(buffer, MSG_ENC);
     }
 
+    #[cfg(redshirt_unstable)]
+    #[test]
+    fn reader_read_buf() {
+        use std::{io::BorrowedBuf, mem::MaybeUninit};
+
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let encoded = encode(&data);
+        let mut reader = Reader::new(&encoded[..]).unwrap();
+        let mut storage = [MaybeUninit::uninit(); 7];
+        let mut buf = BorrowedBuf::from(&mut storage[..]);
+        reader.read_buf(buf.unfilled()).unwrap();
+        assert_eq!(buf.filled(), &data[..7]);
+        assert_eq!(reader.position(), 7);
+        let mut rest = Vec::new();
+        assert_eq!(reader.read_to_end(&mut rest).unwrap(), data.len() - 7);
+        assert_eq!(rest, &data[7..]);
+        let mut decoded = Vec::new();
+        let _ = Reader::new(Trickle::new(&encoded[..]))
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn writer_inline_buffer() {
+        let data: Vec<u8> = (0..=255).cycle().take(50).collect();
+        let mut writer = Writer::<_, 7>::with_inline_buffer(Cursor::new(Vec::new())).unwrap();
+        assert_eq!(writer.write(&data).unwrap(), 7);
+        writer.write_all(&data[7..]).unwrap();
+        assert_eq!(writer.seek(SeekFrom::Start(5)).unwrap(), 5);
+        writer.write_all(&data[5..20]).unwrap();
+        assert_eq!(writer.seek(SeekFrom::End(0)).unwrap(), 50);
+        let mut src = Trickle::new(&data[..]);
+        assert_eq!(writer.write_reader(&mut src).unwrap(), 50);
+        assert_eq!(writer.bytes_written(), 115);
+        let encoded = writer.into_inner().into_inner();
+        assert_eq!(encoded, encode(&[&data[..], &data[..]].concat()));
+    }
+
     #[test]
     fn writer_seek_start() {
         let mut buffer = array!(MARKER_LEN + MSG_LEN);
@@ -303,7 +1713,7 @@ mod tests {
     #[should_panic]
     fn writer_seek_positive_overflow() {
         let mut buffer = array!(MARKER_LEN + MSG_LEN);
-        let mut writer = Reader::new(Cursor::new(&mut buffer[..])).unwrap();
+        let mut writer = Writer::new(Cursor::new(&mut buffer[..])).unwrap();
         let _ = writer.seek(SeekFrom::Start(u64::max_value())).unwrap();
     }
 
@@ -311,7 +1721,337 @@ mod tests {
     #[should_panic]
     fn writer_seek_negative_overflow() {
         let mut buffer = array!(MARKER_LEN + MSG_LEN);
-        let mut writer = Reader::new(Cursor::new(&mut buffer[..])).unwrap();
+        let mut writer = Writer::new(Cursor::new(&mut buffer[..])).unwrap();
         let _ = writer.seek(SeekFrom::Current(-1)).unwrap();
     }
+
+    #[test]
+    fn writer_set_payload_len_shrink() {
+        let mut writer = Writer::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        assert_eq!(writer.seek(SeekFrom::Start(2)).unwrap(), 2);
+        writer.set_payload_len(5).unwrap();
+        assert_eq!(writer.position(), 2);
+        assert_eq!(writer.stream_position().unwrap(), 2);
+        assert_eq!(writer.seek(SeekFrom::End(0)).unwrap(), 5);
+        let encoded = writer.into_inner().into_inner();
+        assert_eq!(encoded, MSG_ENC[..MARKER_LEN + 5]);
+    }
+
+    #[test]
+    fn writer_set_payload_len_grow() {
+        let mut writer = Writer::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_all(&MSG_DEC[..4]).unwrap();
+        writer.set_payload_len(BUFFER_LEN as u64 + 4).unwrap();
+        assert_eq!(writer.position(), 4);
+        writer.write_all(&MSG_DEC[4..]).unwrap();
+        let decoded = decode(&writer.into_inner().into_inner()).unwrap();
+        assert_eq!(decoded.len(), BUFFER_LEN + 4);
+        assert_eq!(decoded[..MSG_LEN], *MSG_DEC);
+        assert!(decoded[MSG_LEN..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn writer_set_payload_len_below_position() {
+        let path = env::temp_dir().join(format!("redshirt-v1-set-len-{}", process::id()));
+        let mut writer = Writer::new(File::create(&path).unwrap()).unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        writer.set_payload_len(5).unwrap();
+        assert_eq!(writer.position(), 5);
+        assert_eq!(writer.stream_position().unwrap(), 5);
+        writer.write_all(b"!").unwrap();
+        drop(writer);
+        assert_eq!(decode(&fs::read(&path).unwrap()).unwrap(), b"Hello!");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn decode_prefix_partial() {
+        let mut src = MSG_ENC;
+        assert_eq!(decode_prefix(&mut src, 5).unwrap(), MSG_DEC[..5]);
+        assert_eq!(src.len(), MSG_LEN - 5);
+        assert_eq!(decode_prefix(Trickle::new(MSG_ENC), 100).unwrap(), MSG_DEC);
+        assert!(decode_prefix(MSG_ENC, 0).unwrap().is_empty());
+        assert!(matches!(
+            decode_prefix(&MSG_ENC[1..], 5),
+            Err(Error::BadHeader)
+        ));
+    }
+
+    #[cfg(all(feature = "mmap", unix))]
+    #[test]
+    fn mmap_writer() {
+        let path = env::temp_dir().join(format!("redshirt-v1-mmap-{}", process::id()));
+        let mut writer = Writer::create_mmap(&path, MSG_LEN_U64).unwrap();
+        writer.write_all(&MSG_DEC[..5]).unwrap();
+        writer.write_all(&MSG_DEC[5..]).unwrap();
+        assert_eq!(writer.position(), MSG_LEN_U64);
+        let e = writer.write(b"!").unwrap_err();
+        assert!(matches!(
+            Error::from_io(e),
+            Error::PayloadTooLarge {
+                limit: MSG_LEN_U64,
+                attempted: 13
+            }
+        ));
+        let _ = writer.finish().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), MSG_ENC);
+
+        let mut writer = Writer::create_mmap(&path, MSG_LEN_U64).unwrap();
+        writer.write_all(b"Hello").unwrap();
+        assert!(writer.write_all(b" there!!").is_err());
+        assert_eq!(writer.position(), 5);
+        let _ = writer.finish().unwrap();
+        assert_eq!(decode(&fs::read(&path).unwrap()).unwrap(), b"Hello");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn gz_round_trip() {
+        let path = env::temp_dir().join(format!("redshirt-v1-gz-{}", process::id()));
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let mut encoder = Writer::create_gz(&path, flate2::Compression::best()).unwrap();
+        encoder.write_all(&data).unwrap();
+        let _ = encoder.finish().unwrap();
+        assert!(fs::metadata(&path).unwrap().len() < data.len() as u64);
+        let mut decoded = Vec::new();
+        let _ = Reader::open_gz(&path)
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn gz_external() {
+        // Produced by `printf 'Hello world!' | gzip -9n`.
+        const GZ_MEMBER: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xf3\x48\xcd\xc9\xc9\x57\x28\xcf\x2f\xca\x49\x51\x04\x00\x95\x19\x85\x1b\x0c\x00\x00\x00";
+        let encoded = encode(GZ_MEMBER);
+        let mut decoded = Vec::new();
+        let _ = Reader::new(&encoded[..])
+            .unwrap()
+            .with_gz()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, MSG_DEC);
+        let encoded = encode(&GZ_MEMBER[..GZ_MEMBER.len() - 4]);
+        assert!(Reader::new(&encoded[..])
+            .unwrap()
+            .with_gz()
+            .read_to_end(&mut Vec::new())
+            .is_err());
+    }
+
+    #[test]
+    fn decode_limited() {
+        assert_eq!(
+            decode_to_vec_limited(MSG_ENC, MSG_LEN_U64).unwrap(),
+            MSG_DEC
+        );
+        assert!(matches!(
+            decode_to_vec_limited(MSG_ENC, 4),
+            Err(Error::PayloadTooLarge {
+                limit: 4,
+                attempted: 5,
+            })
+        ));
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let encoded = encode(&data);
+        assert_eq!(
+            decode_to_vec_limited(Trickle::new(&encoded[..]), u64::MAX).unwrap(),
+            data
+        );
+        assert!(matches!(
+            decode_to_vec_limited(&encoded[..], BUFFER_LEN as u64),
+            Err(Error::PayloadTooLarge { limit, attempted })
+                if limit == BUFFER_LEN as u64 && attempted == limit + 1
+        ));
+        assert!(matches!(
+            decode_to_vec_limited(&MSG_ENC[1..], u64::MAX),
+            Err(Error::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn decode_slice() {
+        assert_eq!(decode(MSG_ENC).unwrap(), MSG_DEC);
+    }
+
+    #[test]
+    fn decode_slice_bad_header() {
+        assert!(matches!(decode(MSG_DEC), Err(Error::BadHeader)));
+        assert!(matches!(decode(&MSG_ENC[..4]), Err(Error::BadHeader)));
+    }
+
+    #[test]
+    fn encode_slice() {
+        assert_eq!(encode(MSG_DEC), MSG_ENC);
+    }
+
+    #[test]
+    fn string_round_trip_across_chunks() {
+        // Place a two-byte character across the buffer boundary, measured from either the start
+        // of the stream or the start of the data.
+        for prefix in [BUFFER_LEN - 1, BUFFER_LEN - 1 - MARKER_LEN] {
+            let text = format!("{}\u{e9}z", "a".repeat(prefix));
+            let encoded = encode_str(Vec::new(), &text).unwrap();
+            assert_eq!(decode_to_string(&encoded[..]).unwrap(), text);
+            assert_eq!(decode_to_string_lossy(&encoded[..]).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn string_invalid_utf8() {
+        let mut data = vec![b'a'; BUFFER_LEN - 1];
+        data.extend_from_slice(b"\xc3z");
+        let encoded = encode(&data);
+        assert!(matches!(
+            decode_to_string(&encoded[..]),
+            Err(Error::InvalidText { valid_up_to }) if valid_up_to == BUFFER_LEN - 1
+        ));
+        let lossy = decode_to_string_lossy(&encoded[..]).unwrap();
+        assert!(lossy.ends_with("a\u{fffd}z"));
+    }
+
+    #[test]
+    fn read_lines_file() {
+        let path = env::temp_dir().join(format!("redshirt-v1-lines-{}", process::id()));
+        fs::write(&path, encode(b"first\r\nsecond\n")).unwrap();
+        let lines: Vec<_> = read_lines(&path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(lines, ["first", "second"]);
+        fs::write(&path, MSG_DEC).unwrap();
+        assert!(matches!(read_lines(&path), Err(Error::BadHeader)));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn multi_writer_write() {
+        let data = vec![0x42; BUFFER_LEN + 1];
+        let mut writer = MultiWriter::new(Vec::new(), Cursor::new(Vec::new())).unwrap();
+        writer.write_all(&data).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.failed(), None);
+        let (primary, secondary) = writer.into_inner();
+        assert_eq!(primary, encode(&data));
+        assert_eq!(secondary.into_inner(), primary);
+    }
+
+    #[test]
+    fn multi_writer_sink_error() {
+        let mut buffer = [u8::default(); MARKER_LEN + 4];
+        let mut writer = MultiWriter::new(Vec::new(), &mut buffer[..]).unwrap();
+        let e = writer.write_all(MSG_DEC).unwrap_err();
+        let e = e.get_ref().unwrap().downcast_ref::<SinkError>().unwrap();
+        assert_eq!(e.sink(), Sink::Secondary);
+        assert_eq!(writer.failed(), Some(Sink::Secondary));
+        assert!(writer.write_all(b"more").is_err());
+        assert!(MultiWriter::new(&mut [u8::default(); 4][..], Vec::new()).is_err());
+    }
+
+    #[test]
+    fn reader_skip_bytes() {
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let encoded = encode(&data);
+        let mut reader = Reader::new(Trickle::new(&encoded[..])).unwrap();
+        assert_eq!(
+            reader.skip_bytes(BUFFER_LEN as u64 + 1).unwrap(),
+            BUFFER_LEN as u64 + 1
+        );
+        let mut byte = [0];
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], data[BUFFER_LEN + 1]);
+        assert_eq!(reader.skip_bytes(u64::MAX).unwrap(), BUFFER_LEN as u64 + 3);
+        assert_eq!(reader.skip_bytes(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn reader_try_clone() {
+        let path = env::temp_dir().join(format!("redshirt-v1-clone-{}", process::id()));
+        fs::write(&path, MSG_ENC).unwrap();
+        let mut reader = Reader::new(File::open(&path).unwrap()).unwrap();
+        let mut buffer = array!(MSG_LEN / 2);
+        reader.read_exact(&mut buffer).unwrap();
+        let mut other = reader.try_clone().unwrap();
+        other.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC[MSG_LEN / 2..]);
+        assert_eq!(reader.stream_position().unwrap(), MSG_LEN_U64 / 2);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC[MSG_LEN / 2..]);
+        assert_eq!(other.seek(SeekFrom::Current(-2)).unwrap(), MSG_LEN_U64 - 2);
+        other.read_exact(&mut buffer[..2]).unwrap();
+        assert_eq!(buffer[..2], MSG_DEC[MSG_LEN - 2..]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn pump_short_transfers() {
+        let data = vec![0x42; BUFFER_LEN + 5];
+        let mut writer = Writer::new(Trickle::new(Vec::new())).unwrap();
+        let len = writer.write_reader(&mut Trickle::new(&data[..])).unwrap();
+        assert_eq!(len, data.len() as u64);
+        let encoded = writer.into_inner().into_inner();
+        assert_eq!(encoded, encode(&data));
+        let mut decoded = Trickle::new(Vec::new());
+        let mut reader = Reader::new(Trickle::new(&encoded[..])).unwrap();
+        assert_eq!(reader.pipe_to(&mut decoded).unwrap(), data.len() as u64);
+        assert_eq!(decoded.into_inner(), data);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn reader_from_bytes() {
+        let encoded = bytes::Bytes::from_static(MSG_ENC);
+        let mut reader = Reader::from_bytes(encoded.clone()).unwrap();
+        let mut other = Reader::from_bytes(encoded.clone()).unwrap();
+        let mut buffer = array!(MSG_LEN);
+        assert_eq!(other.seek(SeekFrom::Start(6)).unwrap(), 6);
+        other.read_exact(&mut buffer[6..]).unwrap();
+        reader.read_exact(&mut buffer[..6]).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+        assert_eq!(reader.into_inner().into_inner(), encoded);
+        assert!(matches!(
+            Reader::from_bytes(encoded.slice(1..)),
+            Err(Error::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn overclaiming_streams() {
+        let e = Reader::new(Overclaim::new(MSG_ENC, 0)).unwrap_err();
+        assert!(matches!(e, Error::Io(e) if e.kind() == ErrorKind::Other));
+        let mut reader = Reader::new(Overclaim::new(MSG_ENC, MARKER_LEN)).unwrap();
+        let mut buffer = array!(MSG_LEN);
+        let e = reader.read(&mut buffer).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Other);
+        assert_eq!(reader.bytes_read(), 0);
+        assert!(Writer::new(Overclaim::new(Vec::new(), 0)).is_err());
+        let mut writer = Writer::new(Overclaim::new(Vec::new(), MARKER_LEN)).unwrap();
+        let e = writer.write_all(MSG_DEC).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Other);
+        assert_eq!(writer.bytes_written(), 0);
+        let mut decoded = Overclaim::new(Vec::new(), 0);
+        let mut reader = Reader::new(MSG_ENC).unwrap();
+        let e = reader.pipe_to(&mut decoded).unwrap_err();
+        assert!(matches!(e, Error::Io(e) if e.kind() == ErrorKind::Other));
+    }
+
+    #[cfg(feature = "fadvise")]
+    #[test]
+    fn advise_file() {
+        let path = env::temp_dir().join(format!("redshirt-v1-advise-{}", process::id()));
+        fs::write(&path, MSG_ENC).unwrap();
+        let mut reader = Reader::new(File::open(&path).unwrap()).unwrap();
+        reader.advise_sequential().unwrap();
+        let mut decoded = Vec::new();
+        let _ = reader.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, MSG_DEC);
+        reader.advise_dontneed().unwrap();
+        fs::remove_file(path).unwrap();
+    }
 }
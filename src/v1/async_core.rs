@@ -0,0 +1,39 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Runtime-agnostic helpers for the asynchronous Redshirt 1 types.
+//!
+//! Redshirt 1 has no state beyond the header, so each runtime-specific module only needs these to
+//! construct an `AsyncCursor` over its adapted I/O object.
+
+use super::{MARKER, MARKER_LEN};
+use crate::{
+    async_cursor::AsyncCursor,
+    async_io::{self, RawRead, RawWrite},
+    error::Error,
+};
+
+#[inline]
+pub(crate) async fn read_header<R: RawRead>(mut src: R) -> Result<AsyncCursor<R>, Error> {
+    let mut marker_buf = array!(MARKER_LEN);
+    async_io::read_exact(&mut src, &mut marker_buf)
+        .await
+        .map_err(Error::Io)?;
+    if marker_buf == MARKER {
+        Ok(AsyncCursor::new(src))
+    } else {
+        Err(Error::BadHeader)
+    }
+}
+
+#[inline]
+pub(crate) async fn write_header<W: RawWrite>(mut dst: W) -> Result<AsyncCursor<W>, Error> {
+    async_io::write_all(&mut dst, &MARKER)
+        .await
+        .map(|()| AsyncCursor::new(dst))
+        .map_err(Error::Io)
+}
@@ -0,0 +1,27 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Asynchronous Redshirt 1 utilities for use with [async-std].
+//!
+//! async-std's I/O types implement the `futures::io` traits, so this module re-exports the types
+//! from `v1::futures_io`. They accept `async_std::fs::File` and other async-std streams directly:
+//!
+//! ```no_run
+//! use async_std::{fs::File, prelude::*};
+//! use redshirt::v1::async_std::Reader;
+//!
+//! # async_std::task::block_on(async {
+//! let file = File::open("data.dat").await.unwrap();
+//! let mut reader = Reader::new(file).await.unwrap();
+//! let mut contents = Vec::new();
+//! let _ = reader.read_to_end(&mut contents).await.unwrap();
+//! # });
+//! ```
+//!
+//! [async-std]: https://async.rs
+
+pub use super::futures_io::{Reader, Writer};
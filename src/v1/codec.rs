@@ -0,0 +1,164 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use super::{MARKER, MARKER_LEN};
+use crate::{error::Error, xor_bytes};
+use bytes::{Buf, Bytes, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// Encodes and decodes a Redshirt 1 stream as part of a framed I/O pipeline.
+pub struct V1Codec {
+    header_written: bool,
+    header_read: bool,
+}
+
+impl V1Codec {
+    #[inline]
+    #[must_use]
+    /// Creates a new `V1Codec`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::codec::V1Codec;
+    /// use tokio::fs::File;
+    /// use tokio_util::codec::FramedRead;
+    ///
+    /// # async fn example() {
+    /// let file = File::open("data.dat").await.unwrap();
+    /// let frames = FramedRead::new(file, V1Codec::new());
+    /// # }
+    /// ```
+    pub const fn new() -> Self {
+        Self {
+            header_written: false,
+            header_read: false,
+        }
+    }
+}
+
+impl Default for V1Codec {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Encoder<Bytes> for V1Codec {
+    type Error = io::Error;
+
+    #[inline]
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+        if !self.header_written {
+            dst.extend_from_slice(&MARKER);
+            self.header_written = true;
+        }
+        let start = dst.len();
+        dst.extend_from_slice(&item);
+        xor_bytes(&mut dst[start..]);
+        Ok(())
+    }
+}
+
+impl Decoder for V1Codec {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    #[inline]
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Bytes>> {
+        if !self.header_read {
+            let len = src.len().min(MARKER_LEN);
+            if src[..len] != MARKER[..len] {
+                return Err(Error::BadHeader.into());
+            } else if len < MARKER_LEN {
+                return Ok(None);
+            }
+            src.advance(MARKER_LEN);
+            self.header_read = true;
+        }
+        if src.is_empty() {
+            return Ok(None);
+        }
+        let mut frame = src.split();
+        xor_bytes(&mut frame);
+        Ok(Some(frame.freeze()))
+    }
+
+    #[inline]
+    fn decode_eof(&mut self, src: &mut BytesMut) -> io::Result<Option<Bytes>> {
+        match self.decode(src)? {
+            None if !self.header_read => Err(io::ErrorKind::UnexpectedEof.into()),
+            frame => Ok(frame),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::V1Codec;
+    use crate::error::Error;
+    use bytes::{Bytes, BytesMut};
+    use futures::{SinkExt, TryStreamExt};
+    use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+    use tokio_util::codec::{Decoder, FramedRead, FramedWrite};
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+    const MSG_ENC: &[u8] = b"REDSHIRT\x00\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+
+    #[tokio::test]
+    async fn framed_read() {
+        let (mut tx, rx) = io::duplex(5);
+        let send = tokio::spawn(async move {
+            for chunk in MSG_ENC.chunks(3) {
+                tx.write_all(chunk).await.unwrap();
+            }
+        });
+        let frames = FramedRead::new(rx, V1Codec::new())
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        send.await.unwrap();
+        assert_eq!(frames.concat(), MSG_DEC);
+    }
+
+    #[tokio::test]
+    async fn framed_write() {
+        let (tx, mut rx) = io::duplex(7);
+        let recv = tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            let _ = rx.read_to_end(&mut buffer).await.unwrap();
+            buffer
+        });
+        let mut frames = FramedWrite::new(tx, V1Codec::new());
+        for chunk in MSG_DEC.chunks(5) {
+            frames.send(Bytes::copy_from_slice(chunk)).await.unwrap();
+        }
+        drop(frames);
+        assert_eq!(recv.await.unwrap(), MSG_ENC);
+    }
+
+    #[test]
+    fn decode_bad_header() {
+        let mut src = BytesMut::from(&b"REDSHRT"[..]);
+        let e = V1Codec::new().decode(&mut src).unwrap_err();
+        match e.into_inner().unwrap().downcast::<Error>().map(|e| *e) {
+            Ok(Error::BadHeader) => (),
+            other => panic!("expected `Error::BadHeader`, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decode_eof_truncated_header() {
+        let mut src = BytesMut::from(&MSG_ENC[..4]);
+        let mut codec = V1Codec::new();
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        let e = codec.decode_eof(&mut src).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Asynchronous Redshirt 1 utilities for use with the [`futures-io`] traits.
+//!
+//! This module provides `Reader` and `Writer` types, which are the counterparts of
+//! `v1::tokio::Reader` and `v1::tokio::Writer` for runtimes built on `futures::io`, such as smol
+//! and async-std.
+//!
+//! [`futures-io`]: https://docs.rs/futures-io
+
+use super::async_core::{read_header, write_header};
+use crate::{
+    async_cursor::AsyncCursor,
+    async_io::{FuturesIo, RawRead, RawWrite},
+    error::Error,
+};
+use futures_io::{AsyncRead, AsyncSeek, AsyncWrite};
+use std::{
+    io::{self, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[derive(Debug)]
+/// Asynchronously reads Redshirt 1-protected data from an input stream.
+pub struct Reader<R>(AsyncCursor<FuturesIo<R>>);
+
+#[derive(Debug)]
+/// Asynchronously writes Redshirt 1-protected data to an output stream.
+pub struct Writer<W>(AsyncCursor<FuturesIo<W>>);
+
+impl<R: AsyncRead + Unpin> Reader<R> {
+    #[inline]
+    /// Creates a new reader from an existing input stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or the underlying reader produces an invalid
+    /// Redshirt 1 header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures::io::Cursor;
+    /// use redshirt::v1::futures_io::Reader;
+    ///
+    /// # futures::executor::block_on(async {
+    /// let reader = Reader::new(Cursor::new(b"REDSHIRT\x00")).await.unwrap();
+    /// # });
+    /// ```
+    pub async fn new(src: R) -> Result<Self, Error> {
+        read_header(FuturesIo::new(src)).await.map(Self)
+    }
+}
+
+impl<R> Reader<R> {
+    #[inline]
+    /// Unwraps a `Reader`, returning its underlying reader.
+    pub fn into_inner(self) -> R {
+        self.0.into_inner().into_inner()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Reader<R> {
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_read(cx, buf)
+    }
+}
+
+impl<R: AsyncSeek + Unpin> AsyncSeek for Reader<R> {
+    #[inline]
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        self.get_mut().0.poll_seek(cx, pos)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Writer<W> {
+    #[inline]
+    /// Wraps an existing output stream and writes a valid Redshirt 1 header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the Redshirt 1 header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures::io::Cursor;
+    /// use redshirt::v1::futures_io::Writer;
+    ///
+    /// # futures::executor::block_on(async {
+    /// let writer = Writer::new(Cursor::new(Vec::new())).await.unwrap();
+    /// # });
+    /// ```
+    pub async fn new(dst: W) -> Result<Self, Error> {
+        write_header(FuturesIo::new(dst)).await.map(Self)
+    }
+}
+
+impl<W> Writer<W> {
+    #[inline]
+    /// Unwraps a `Writer`, returning its underlying writer.
+    pub fn into_inner(self) -> W {
+        self.0.into_inner().into_inner()
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Writer<W> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_close(cx)
+    }
+}
+
+impl<W: AsyncSeek + Unpin> AsyncSeek for Writer<W> {
+    #[inline]
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        self.get_mut().0.poll_seek(cx, pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Reader, Writer};
+    use futures::{
+        executor,
+        io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, Cursor},
+    };
+    use std::{
+        io::{self, ErrorKind, SeekFrom},
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+    const MSG_ENC: &[u8] = b"REDSHIRT\x00\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+    const MSG_LEN: usize = 12;
+    const MSG_LEN_U64: u64 = MSG_LEN as u64;
+    const MSG_LEN_I64: i64 = 12;
+
+    #[derive(Debug)]
+    /// A stream that claims to transfer one byte more than each buffer holds.
+    struct Overclaim;
+
+    impl AsyncRead for Overclaim {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(Ok(buf.len() + 1))
+        }
+    }
+
+    impl AsyncWrite for Overclaim {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(Ok(buf.len() + 1))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn reader_read() {
+        executor::block_on(async {
+            let mut reader = Reader::new(Cursor::new(MSG_ENC)).await.unwrap();
+            let mut buffer = Vec::new();
+            let _ = reader.read_to_end(&mut buffer).await.unwrap();
+            assert_eq!(buffer, MSG_DEC);
+        });
+    }
+
+    #[test]
+    fn reader_seek() {
+        executor::block_on(async {
+            let mut reader = Reader::new(Cursor::new(MSG_ENC)).await.unwrap();
+            let mut buffer = array!(MSG_LEN);
+            let (left, right) = buffer.split_at_mut(MSG_LEN / 2);
+            assert_eq!(
+                reader
+                    .seek(SeekFrom::Current(MSG_LEN_I64 / 2))
+                    .await
+                    .unwrap(),
+                MSG_LEN_U64 / 2
+            );
+            reader.read_exact(right).await.unwrap();
+            assert_eq!(reader.seek(SeekFrom::Start(0)).await.unwrap(), 0);
+            reader.read_exact(left).await.unwrap();
+            assert_eq!(buffer, MSG_DEC);
+            assert!(reader.seek(SeekFrom::End(-MSG_LEN_I64 - 1)).await.is_err());
+            assert_eq!(reader.seek(SeekFrom::End(0)).await.unwrap(), MSG_LEN_U64);
+        });
+    }
+
+    #[test]
+    fn writer_write() {
+        executor::block_on(async {
+            let mut writer = Writer::new(Cursor::new(Vec::new())).await.unwrap();
+            writer.write_all(MSG_DEC).await.unwrap();
+            writer.close().await.unwrap();
+            assert_eq!(writer.into_inner().into_inner(), MSG_ENC);
+        });
+    }
+
+    #[test]
+    fn overclaiming_streams() {
+        executor::block_on(async {
+            let e = Reader::new(Overclaim).await.unwrap_err();
+            assert!(matches!(e, crate::Error::Io(e) if e.kind() == ErrorKind::Other));
+            assert!(Writer::new(Overclaim).await.is_err());
+        });
+    }
+}
@@ -0,0 +1,422 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Asynchronous Redshirt 1 utilities for use with [Tokio].
+//!
+//! This module provides `Reader` and `Writer` types, which are the asynchronous counterparts of
+//! `v1::Reader` and `v1::Writer`, along with `ByteStream`, which adapts a `Reader` into a stream of
+//! decoded chunks. `BlockingReader` runs a synchronous reader on a blocking thread instead.
+//!
+//! [Tokio]: https://tokio.rs
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::blocking::BlockingReader;
+pub use crate::byte_stream::ByteStream;
+
+use super::async_core::{read_header, write_header};
+use crate::{
+    async_cursor::AsyncCursor,
+    async_io::{poll_read_buf, RawSeek, RawWrite, TokioIo},
+    error::Error,
+};
+use std::{
+    io::{self, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+#[derive(Debug)]
+/// Asynchronously reads Redshirt 1-protected data from an input stream.
+pub struct Reader<R>(AsyncCursor<TokioIo<R>>);
+
+#[derive(Debug)]
+/// Asynchronously writes Redshirt 1-protected data to an output stream.
+pub struct Writer<W>(AsyncCursor<TokioIo<W>>);
+
+impl<R: AsyncRead + Unpin> Reader<R> {
+    #[inline]
+    /// Creates a new reader from an existing input stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or the underlying reader produces an invalid
+    /// Redshirt 1 header.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::tokio::Reader;
+    /// use tokio::fs::File;
+    ///
+    /// # async fn example() {
+    /// let file = File::open("data.dat").await.unwrap();
+    /// let reader = Reader::new(file).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn new(src: R) -> Result<Self, Error> {
+        read_header(TokioIo::new(src)).await.map(Self)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Reader<File> {
+    #[inline]
+    /// Opens the file at `path` for reading and creates a new reader from it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the file cannot be opened, or under the same conditions as
+    /// `Reader::new`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::tokio::Reader;
+    ///
+    /// # async fn example() {
+    /// let reader = Reader::open("data.dat").await.unwrap();
+    /// # }
+    /// ```
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path).await.map_err(Error::Io)?;
+        Self::new(file).await
+    }
+}
+
+impl<R> Reader<R> {
+    #[inline]
+    /// Unwraps a `Reader`, returning its underlying reader.
+    pub fn into_inner(self) -> R {
+        self.0.into_inner().into_inner()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Converts a `Reader` into a stream of decoded data, such as for use as an HTTP response
+    /// body.
+    ///
+    /// Each chunk yielded by the stream is exactly `chunk_size` bytes long, except for the final
+    /// chunk, which may be shorter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::tokio::Reader;
+    /// use tokio::fs::File;
+    ///
+    /// # async fn example() {
+    /// let file = File::open("data.dat").await.unwrap();
+    /// let stream = Reader::new(file).await.unwrap().into_byte_stream(8192);
+    /// # }
+    /// ```
+    pub fn into_byte_stream(self, chunk_size: usize) -> ByteStream<Self> {
+        ByteStream::new(self, chunk_size)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Reader<R> {
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        poll_read_buf(&mut self.get_mut().0, cx, buf)
+    }
+}
+
+impl<R: AsyncSeek + Unpin> AsyncSeek for Reader<R> {
+    #[inline]
+    fn start_seek(self: Pin<&mut Self>, pos: SeekFrom) -> io::Result<()> {
+        self.get_mut().0.start_seek(pos)
+    }
+
+    #[inline]
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        self.get_mut().0.poll_complete(cx)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Writer<W> {
+    #[inline]
+    /// Wraps an existing output stream and writes a valid Redshirt 1 header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the Redshirt 1 header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::tokio::Writer;
+    /// use tokio::fs::File;
+    ///
+    /// # async fn example() {
+    /// let file = File::create("data.dat").await.unwrap();
+    /// let writer = Writer::new(file).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn new(dst: W) -> Result<Self, Error> {
+        write_header(TokioIo::new(dst)).await.map(Self)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Writer<File> {
+    #[inline]
+    /// Creates or truncates the file at `path` and creates a new writer for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the file cannot be created, or under the same conditions as
+    /// `Writer::new`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v1::tokio::Writer;
+    ///
+    /// # async fn example() {
+    /// let writer = Writer::create("data.dat").await.unwrap();
+    /// # }
+    /// ```
+    pub async fn create<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::create(path).await.map_err(Error::Io)?;
+        Self::new(file).await
+    }
+}
+
+impl<W> Writer<W> {
+    #[inline]
+    /// Unwraps a `Writer`, returning its underlying writer.
+    pub fn into_inner(self) -> W {
+        self.0.into_inner().into_inner()
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Writer<W> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_close(cx)
+    }
+}
+
+impl<W: AsyncSeek + Unpin> AsyncSeek for Writer<W> {
+    #[inline]
+    fn start_seek(self: Pin<&mut Self>, pos: SeekFrom) -> io::Result<()> {
+        self.get_mut().0.start_seek(pos)
+    }
+
+    #[inline]
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        self.get_mut().0.poll_complete(cx)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[inline]
+/// Reads and decodes the entire contents of the file at `path`.
+///
+/// # Errors
+///
+/// Returns an `Err` if the file cannot be opened or read, or under the same conditions as
+/// `Reader::new`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::v1::tokio;
+///
+/// # async fn example() {
+/// let data = tokio::decode_file("data.dat").await.unwrap();
+/// # }
+/// ```
+pub async fn decode_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, Error> {
+    let mut reader = Reader::open(path).await?;
+    let mut data = Vec::new();
+    let _ = reader.read_to_end(&mut data).await.map_err(Error::Io)?;
+    Ok(data)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[inline]
+/// Encodes `data` and writes it to the file at `path`, replacing its contents if it exists.
+///
+/// The file is flushed before the returned future resolves.
+///
+/// # Errors
+///
+/// Returns an `Err` if the file cannot be created or written.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::v1::tokio;
+///
+/// # async fn example() {
+/// tokio::encode_file("data.dat", b"foobar").await.unwrap();
+/// # }
+/// ```
+pub async fn encode_file<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<(), Error> {
+    let mut writer = Writer::create(path).await?;
+    writer.write_all(data).await.map_err(Error::Io)?;
+    writer.flush().await.map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Reader, Writer};
+    use crate::v1;
+    use futures::TryStreamExt;
+    use std::io::{Cursor, Read, SeekFrom};
+    use tokio::io::{self, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+    const MSG_ENC: &[u8] = b"REDSHIRT\x00\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+    const MSG_LEN: usize = 12;
+    const MSG_LEN_U64: u64 = MSG_LEN as u64;
+    const MSG_LEN_I64: i64 = 12;
+
+    #[tokio::test]
+    async fn reader_read() {
+        let (mut tx, rx) = io::duplex(4);
+        let send = tokio::spawn(async move { tx.write_all(MSG_ENC).await.unwrap() });
+        let mut reader = Reader::new(rx).await.unwrap();
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).await.unwrap();
+        send.await.unwrap();
+        let mut expected = Vec::new();
+        let _ = v1::Reader::new(MSG_ENC)
+            .unwrap()
+            .read_to_end(&mut expected)
+            .unwrap();
+        assert_eq!(buffer, expected);
+        assert_eq!(buffer, MSG_DEC);
+    }
+
+    #[tokio::test]
+    async fn reader_seek() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).await.unwrap();
+        let mut buffer = array!(MSG_LEN);
+        let (left, right) = buffer.split_at_mut(MSG_LEN / 2);
+        assert_eq!(
+            reader
+                .seek(SeekFrom::Current(MSG_LEN_I64 / 2))
+                .await
+                .unwrap(),
+            MSG_LEN_U64 / 2
+        );
+        let _ = reader.read_exact(right).await.unwrap();
+        assert_eq!(reader.seek(SeekFrom::Start(0)).await.unwrap(), 0);
+        let _ = reader.read_exact(left).await.unwrap();
+        assert_eq!(buffer, MSG_DEC);
+        assert_eq!(reader.seek(SeekFrom::End(-MSG_LEN_I64)).await.unwrap(), 0);
+        assert_eq!(reader.seek(SeekFrom::End(0)).await.unwrap(), MSG_LEN_U64);
+    }
+
+    #[tokio::test]
+    async fn reader_seek_overflow() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).await.unwrap();
+        assert!(reader.seek(SeekFrom::Start(u64::MAX)).await.is_err());
+        assert!(reader.seek(SeekFrom::Current(-1)).await.is_err());
+        assert!(reader.seek(SeekFrom::End(-MSG_LEN_I64 - 1)).await.is_err());
+        assert_eq!(reader.stream_position().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn writer_write() {
+        let (tx, mut rx) = io::duplex(4);
+        let recv = tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            let _ = rx.read_to_end(&mut buffer).await.unwrap();
+            buffer
+        });
+        let mut writer = Writer::new(tx).await.unwrap();
+        writer.write_all(MSG_DEC).await.unwrap();
+        writer.shutdown().await.unwrap();
+        drop(writer);
+        assert_eq!(recv.await.unwrap(), MSG_ENC);
+    }
+
+    #[tokio::test]
+    async fn writer_seek() {
+        let mut buffer = Cursor::new(Vec::new());
+        let mut writer = Writer::new(&mut buffer).await.unwrap();
+        let (left, right) = MSG_DEC.split_at(MSG_LEN / 2);
+        writer.write_all(left).await.unwrap();
+        writer.write_all(left).await.unwrap();
+        assert_eq!(
+            writer
+                .seek(SeekFrom::Current(-MSG_LEN_I64 / 2))
+                .await
+                .unwrap(),
+            MSG_LEN_U64 / 2
+        );
+        writer.write_all(right).await.unwrap();
+        assert_eq!(buffer.into_inner(), MSG_ENC);
+    }
+
+    #[tokio::test]
+    async fn reader_byte_stream() {
+        let (mut tx, rx) = io::duplex(4);
+        let send = tokio::spawn(async move { tx.write_all(MSG_ENC).await.unwrap() });
+        let chunks = Reader::new(rx)
+            .await
+            .unwrap()
+            .into_byte_stream(5)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        send.await.unwrap();
+        assert_eq!(
+            chunks.iter().map(bytes::Bytes::len).collect::<Vec<_>>(),
+            [5, 5, 2]
+        );
+        assert_eq!(chunks.concat(), MSG_DEC);
+    }
+
+    #[tokio::test]
+    async fn file_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "redshirt-v1-tokio-{}-file_round_trip",
+            std::process::id()
+        ));
+        super::encode_file(&path, MSG_DEC).await.unwrap();
+        let encoded = std::fs::read(&path).unwrap();
+        let decoded = super::decode_file(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(encoded, MSG_ENC);
+        assert_eq!(decoded, MSG_DEC);
+    }
+}
@@ -8,43 +8,373 @@
 //! Redshirt 2 utilities.
 //!
 //! This module provides `Reader` and `Writer` types for reading and writing Redshirt 2-encoded
-//! data, respectively.
+//! data, respectively. `MultiWriter` writes the same encoded data to two output streams at once,
+//! and `HashingReader` decodes a stream of encoded data while computing its SHA-1 hash.
+//! `PrecomputedWriter` writes a stream whose hash is already known, without seeking, and
+//! `SyncWriter` shares a `Writer` between threads.
+//!
+//! # Lazy verification
+//!
+//! By default, `Reader` verifies the SHA-1 hash of the encoded data before returning, which
+//! requires reading the entire stream up front. Using `Verification::Lazy`, the hash is instead
+//! computed as the data is read, and checked once the end of the stream is reached.
+//!
+//! Since `Read::read` can only return an `io::Error`, a mismatch is reported as an `io::Error` of
+//! kind `InvalidData`, in place of the `Ok(0)` that would otherwise signal the end of the stream.
+//! The original `Error::BadChecksum` can be recovered by downcasting; every subsequent call to
+//! `read` returns the same error.
+//!
+//! Encoded data that is seeked past without being read is still covered: once the end of the
+//! stream is reached, it is read from the underlying reader and hashed before the hash is checked.
+//! This requires the reader to have been created from a reader known to implement `Seek`; one
+//! created by `ReaderOptions::from_stream` instead reports that the hash cannot be verified, as an
+//! `io::Error` of kind `InvalidData` returned by that and every subsequent call to `read`.
+//!
+//! ```no_run
+//! use redshirt::{
+//!     v2::{ReaderOptions, Verification},
+//!     Error,
+//! };
+//! use std::{fs::OpenOptions, io};
+//!
+//! fn main() {
+//!     let file = OpenOptions::new().read(true).open("User.usr").unwrap();
+//!     let mut reader = ReaderOptions::new()
+//!         .verification(Verification::Lazy)
+//!         .from_stream(file)
+//!         .unwrap();
+//!     let mut decoded = Vec::new();
+//!     if let Err(e) = io::copy(&mut reader, &mut decoded) {
+//!         match e.get_ref().and_then(|inner| inner.downcast_ref::<Error>()) {
+//!             Some(Error::BadChecksum { expected, actual }) => {
+//!                 eprintln!("checksum mismatch: expected {}, found {}", expected, actual)
+//!             }
+//!             _ => eprintln!("I/O error: {}", e),
+//!         }
+//!     }
+//! }
+//! ```
 
-use crate::{cursor::Cursor, error::Error};
-use ring::digest::{Context, SHA1_FOR_LEGACY_USE_ONLY as SHA1, SHA1_OUTPUT_LEN};
+#[cfg(feature = "fadvise")]
+use crate::advice;
+#[cfg(all(feature = "mmap", unix))]
+use crate::mmap::Mapping;
+#[cfg(redshirt_unstable)]
+use crate::read_buf;
+use crate::{
+    boxed::{ReadSeek, WriteSeek},
+    buffered::BufferedReader,
+    cancel::{self, CancelToken},
+    cursor::{self, overflow_error, Cursor, ReadFn, Scratch, Staging, BUFFER_LEN},
+    digest::{Digest, DIGEST_LEN},
+    error::{Error, Sink, SinkError},
+    forward::ForwardOnly,
+    prealloc,
+    sha1::Sha1,
+    tee::Tee,
+    text::{self, LineReader},
+    xor_bytes, xor_payload,
+};
+#[cfg(feature = "bytes")]
+use bytes::Buf;
+#[cfg(feature = "flate2")]
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+#[cfg(redshirt_unstable)]
+use std::io::BorrowedCursor;
+#[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+use std::path::PathBuf;
 use std::{
+    convert::TryFrom,
     fmt::{self, Debug, Formatter},
-    io::{self, Read, Seek, SeekFrom, Write},
+    fs::File,
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
     mem,
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
 };
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroizing;
+
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_core;
+#[cfg(feature = "async-std")]
+pub mod async_std;
+#[cfg(feature = "tokio-codec")]
+pub(crate) mod codec;
+#[cfg(feature = "futures-io")]
+pub mod futures_io;
+#[cfg(feature = "tokio")]
+pub mod tokio;
 
-const MARKER: [u8; MARKER_LEN] = *b"REDSHRT2\x00";
+pub(crate) const MARKER: [u8; MARKER_LEN] = *b"REDSHRT2\x00";
 const MARKER_LEN: usize = 9;
-const HEADER_LEN: usize = MARKER_LEN + SHA1_OUTPUT_LEN;
+pub(crate) const HEADER_LEN: usize = MARKER_LEN + DIGEST_LEN;
 
 #[derive(Debug)]
 /// Reads Redshirt 2-protected data from an input stream.
-pub struct Reader<R>(Cursor<R>);
+pub struct Reader<R> {
+    src: Cursor<R>,
+    /// The SHA-1 hash in the header, or `None` if the reader was created without one.
+    header: Option<Digest>,
+    verifier: Option<LazyVerifier>,
+    /// The maximum length of the decoded data, set by `ReaderOptions::max_payload_len`.
+    limit: u64,
+    /// Whether seeks before the start of the data are clamped, set by
+    /// `ReaderOptions::clamp_seeks`.
+    clamp_seeks: bool,
+    /// The result of verification, if the reader was created by `Reader::new_tolerant`.
+    report: Option<ChecksumReport>,
+    /// Hashes encoded data that was seeked past without being read, for lazy verification, or
+    /// `None` if the underlying reader was not known to implement `Seek` when the reader was
+    /// created.
+    hash_skipped: Option<fn(&mut Self, u64) -> io::Result<()>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+/// Options which can be used to configure how a Redshirt 2 stream is read.
+pub struct ReaderOptions {
+    accept_unfinalized: bool,
+    verification: Verification,
+    max_payload_len: u64,
+    clamp_seeks: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// Specifies when, if ever, a `Reader` verifies the SHA-1 hash of the encoded data.
+pub enum Verification {
+    /// The hash is verified when the `Reader` is created, before any data is decoded.
+    Eager,
+    /// The hash is computed as data is read, and verified once the end of the stream is reached.
+    ///
+    /// See the [module-level documentation](index.html#lazy-verification) for how a mismatch is
+    /// reported.
+    Lazy,
+    /// The hash is never verified.
+    Skip,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// Specifies whether `decode_prefix` verifies the SHA-1 hash of the encoded data.
+pub enum PrefixVerify {
+    /// The rest of the stream is read after the prefix, and the hash of the whole stream is
+    /// verified before the prefix is returned.
+    Full,
+    /// Only the header and the prefix are read, and the hash is never verified.
+    ///
+    /// The prefix may be corrupt, and a stream whose header contains an all-zero hash is not
+    /// rejected.
+    Skip,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+/// The start of the decoded data of a Redshirt 2 stream, as returned by `decode_prefix`.
+pub struct Prefix {
+    /// The decoded bytes, which are fewer than requested only if the data is shorter.
+    pub data: Vec<u8>,
+    /// Whether the SHA-1 hash of the stream was verified.
+    ///
+    /// If this is `false`, nothing was checked for corruption, and `data` should not be trusted.
+    pub verified: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// The result of verifying a Redshirt 2 stream opened by `Reader::new_tolerant`.
+pub struct ChecksumReport {
+    /// The SHA-1 hash stored in the header.
+    pub expected: Digest,
+    /// The SHA-1 hash of the encoded data.
+    pub actual: Digest,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// The result of recovering the data of a damaged Redshirt 2 stream with `salvage`.
+pub struct SalvageReport {
+    /// The number of decoded bytes written to the output.
+    pub recovered: u64,
+    /// The SHA-1 hash stored in the header, or `None` if the stream ends before it.
+    pub stored: Option<Digest>,
+    /// The SHA-1 hash of the encoded data that was recovered.
+    pub actual: Digest,
+}
+
+#[derive(Clone, Copy, Debug)]
+/// Options which can be used to configure how a Redshirt 2 stream is written by a `Writer`.
+pub struct WriterOptions {
+    checkpoint_on_flush: bool,
+    checkpoint_every: Option<u64>,
+    buffer_len: usize,
+    coalesce_writes: bool,
+    max_payload_len: u64,
+    preallocate: u64,
+}
+
+#[derive(Clone, Copy, Debug)]
+/// Options which can be used to configure how a Redshirt 2 stream is copied by `copy_verified`.
+pub struct CopyOptions {
+    buffered: bool,
+}
+
+#[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+#[derive(Clone, Debug)]
+/// Options which can be used to configure how a `BufferedWriter` spills encoded data to disk.
+///
+/// A `BufferedWriter` normally holds all encoded data in memory until it is finished. With these
+/// options, once more than `memory_limit` bytes are buffered, they are moved to an anonymous
+/// temporary file in `temp_dir`, and streamed back to the output stream by `finish`. Memory use is
+/// then bounded by `memory_limit`, at the cost of writing every spilled byte twice: once to the
+/// temporary file, and once more to the output stream.
+///
+/// The temporary file has no name, and is removed by the operating system as soon as it is
+/// closed, including when the `BufferedWriter` is dropped or `finish` fails. Note that spilled
+/// data is already encoded, but is otherwise stored on disk as-is, and that the temporary file is
+/// accessed with blocking I/O, even by the asynchronous writers.
+pub struct SpillOptions {
+    memory_limit: usize,
+    temp_dir: Option<PathBuf>,
+}
 
 /// Writes Redshirt 2-protected data to an output stream.
-pub struct Writer<W: Seek + Write> {
-    dst: Option<Cursor<W>>,
+///
+/// The SHA-1 hash is written by seeking back to the header once all data has been written. The
+/// output stream is flushed before seeking and after writing the hash, so buffered streams whose
+/// `seek` does not flush pending data are still written correctly. To buffer the output, use
+/// `Writer::buffered`.
+///
+/// Data is encoded in a heap buffer whose length is chosen at runtime if `BUF` is `0`, as it is by
+/// default, or otherwise in a buffer of `BUF` bytes held inline, without allocating.
+pub struct Writer<W: Seek + Write, const BUF: usize = 0> {
+    dst: Option<Cursor<W, BUF>>,
+    checksum: ChecksumBuilder,
+    options: WriterOptions,
+    /// The number of bytes written since the SHA-1 hash was last written into the header.
+    since_checkpoint: u64,
+    /// Whether a checkpoint is due, but could not be written.
+    checkpoint_pending: bool,
+    finalize_on_drop: bool,
+    /// The length the output file was extended to by `WriterOptions::preallocate`, and the
+    /// function that truncates it back to the end of the data.
+    preallocated: Option<(u64, SetLen<W>)>,
+}
+
+/// Sets the length of a file, as `File::set_len` does.
+type SetLen<W> = fn(&W, u64) -> io::Result<()>;
+
+#[derive(Debug)]
+/// Writes Redshirt 2-protected data to two output streams, encoding and hashing it only once.
+///
+/// Writing stops at the first error on either stream. The error names the stream on which it
+/// occurred, and every later write fails, as the streams may no longer hold the same data.
+///
+/// Unlike `Writer`, a `MultiWriter` must be finalised with an explicit `finish` call. If it is
+/// dropped instead, both headers are left with an all-zero SHA-1 hash.
+pub struct MultiWriter<A, B> {
+    tee: Tee<A, B>,
+    offset: u64,
+    checksum: ChecksumBuilder,
+    staging: Staging,
+}
+
+#[cfg(all(feature = "mmap", unix))]
+#[derive(Debug)]
+/// Writes Redshirt 2-protected data of a known length into a file mapped into memory, as created
+/// by `Writer::create_mmap`.
+///
+/// Data is encoded directly into the mapping, without a write call per buffer, and is hashed once,
+/// by `finish`, which then writes the SHA-1 hash into the header. Writes that would exceed the
+/// length the file was created with fail without writing anything.
+///
+/// Unlike `Writer`, an `MmapWriter` must be finalised with an explicit `finish` call. If it is
+/// dropped instead, the header is left with an all-zero SHA-1 hash, marking the file as
+/// unfinalised.
+pub struct MmapWriter {
+    map: Mapping,
+}
+
+#[derive(Debug)]
+/// Writes Redshirt 2-protected data whose SHA-1 hash is already known to an output stream.
+///
+/// The complete header is written up front, so unlike `Writer`, the output stream does not need
+/// to support seeking. The hash can optionally be verified once all data has been written.
+pub struct PrecomputedWriter<W> {
+    dst: W,
+    expected: Digest,
+    checksum: Option<ChecksumBuilder>,
+    staging: Staging,
+}
+
+#[derive(Debug)]
+/// Decodes a stream of encoded data without a header, computing the SHA-1 hash of the encoded
+/// bytes as they are read.
+///
+/// Since Redshirt 1 and Redshirt 2 encode data identically, this can decode the data following
+/// either header, producing the hash that a Redshirt 2 header for the same data would contain.
+///
+/// Seeking to any position other than the end of the data read so far invalidates the hash, and
+/// `finish` then returns an error.
+pub struct HashingReader<R> {
+    src: Cursor<R>,
+    checksum: ChecksumBuilder,
+    valid: bool,
+}
+
+#[derive(Clone, Debug)]
+/// Checks encoded data against the SHA-1 hash from a Redshirt 2 header, as the data arrives.
+///
+/// This is for encoded data that is not available through a `Read` implementation, such as data
+/// received in pieces from a network protocol. Pass it every byte following the header, in order.
+pub struct Verifier {
+    expected: Digest,
     checksum: ChecksumBuilder,
 }
 
+#[derive(Debug)]
+/// A `Writer` that can be written to from several threads at once through a shared reference.
+///
+/// Each write through `&SyncWriter` is applied in full while holding a lock, so the data from one
+/// call to `write`, `write_all`, `write_vectored` or `write_fmt` is never interleaved with data
+/// from another thread, even if the `Writer` splits it into several chunks. Data from separate
+/// calls may be interleaved, so each record should be written with a single call.
+///
+/// If a thread panics while writing, the lock is recovered by the next caller, and the record
+/// being written by that thread may be incomplete.
+pub struct SyncWriter<W: Seek + Write> {
+    inner: Mutex<Writer<W>>,
+}
+
+/// A `Reader` over a boxed stream, for storing readers of different stream types together.
+pub type BoxedReader = Reader<Box<dyn ReadSeek + Send>>;
+
+/// A `Writer` over a boxed stream, for storing writers of different stream types together.
+pub type BoxedWriter = Writer<Box<dyn WriteSeek + Send>>;
+
 #[derive(Clone)]
-struct ChecksumBuilder(Context);
+struct ChecksumBuilder(Sha1);
+
+#[derive(Clone, Debug)]
+struct LazyVerifier {
+    /// The hash to check against, or `None` if the hash is only computed.
+    expected: Option<Digest>,
+    checksum: ChecksumBuilder,
+    hashed: u64,
+    actual: Option<Digest>,
+    /// Whether the end of the stream was reached after seeking past bytes that were never hashed.
+    skipped: bool,
+}
 
 impl<R: Read + Seek> Reader<R> {
     #[inline]
     /// Creates a new reader from an input stream.
     ///
+    /// This is equivalent to `ReaderOptions::new().from_reader(src)`.
+    ///
     /// # Errors
     ///
     /// Returns an `Err` if any of the following occurs:
     ///
     /// * An I/O error occurs;
     /// * The underlying reader produces an invalid Redshirt 2 header;
+    /// * The SHA-1 hash in the header is all zeroes, indicating that the stream was never
+    ///   finalised;
     /// * The SHA-1 hash in the header does not match that of the encoded data.
     ///
     /// # Examples
@@ -56,47 +386,252 @@ impl<R: Read + Seek> Reader<R> {
     /// let file = OpenOptions::new().read(true).open("User.usr").unwrap();
     /// let reader = Reader::new(file).unwrap();
     /// ```
-    pub fn new(mut src: R) -> Result<Self, Error> {
-        let mut header_buf = array!(HEADER_LEN);
-        src.read_exact(&mut header_buf)
+    pub fn new(src: R) -> Result<Self, Error> {
+        ReaderOptions::new().from_reader(src)
+    }
+
+    #[inline]
+    /// Creates a reader that decodes data from the current position of an input stream, without
+    /// reading a Redshirt 2 header.
+    ///
+    /// This is for encoded data whose header is stored elsewhere, or has already been read. If
+    /// `digest` is `Some`, the SHA-1 hash of the encoded data is verified against it before this
+    /// returns, as by `Reader::new`. If it is `None`, the hash is not verified, but is computed as
+    /// the data is read; see `Reader::digest_computed`. Seeking is relative to the position of the
+    /// input stream when the reader is created.
+    ///
+    /// Headerless data like this is not recognised by `Version::detect` or `v2::verify`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or `digest` does not match the SHA-1 hash of the
+    /// encoded data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Reader};
+    /// use std::io::{Cursor, Read};
+    ///
+    /// let encoded = v2::encode(b"foobar");
+    /// let digest = v2::verify(&encoded[..]).unwrap();
+    /// let mut src = Cursor::new(encoded);
+    /// src.set_position(29);
+    /// let mut reader = Reader::from_raw(src, Some(digest)).unwrap();
+    /// let mut decoded = String::new();
+    /// let _ = reader.read_to_string(&mut decoded).unwrap();
+    /// assert_eq!(decoded, "foobar");
+    /// ```
+    pub fn from_raw(mut src: R, digest: Option<Digest>) -> Result<Self, Error> {
+        let base = src.stream_position().map_err(Error::Io)?;
+        let verifier = if let Some(expected) = digest {
+            let actual = hash_payload(&mut src).map_err(Error::Io)?;
+            if actual != expected {
+                return Err(checksum_mismatch(expected, actual));
+            }
+            let _ = src.seek(SeekFrom::Start(base)).map_err(Error::Io)?;
+            None
+        } else {
+            Some(LazyVerifier::new(None))
+        };
+        Ok(Self::with_verifier(Cursor::new(src).pinned_at(base), None, verifier).rewindable())
+    }
+
+    #[inline]
+    /// Creates a new reader from an input stream, verifying the SHA-1 hash of the encoded data
+    /// but returning the reader even if it does not match.
+    ///
+    /// This is for recovering data from corrupt streams, which often decode to mostly intact
+    /// data. The hash is checked before this returns, as by `Reader::new`, and the result is
+    /// returned alongside the reader; it can also be retrieved later with
+    /// `Reader::checksum_report`. A header whose hash is all zeroes is reported as a mismatch
+    /// rather than rejected. Decoded data from a reader whose report does not match should not be
+    /// trusted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, or the underlying reader produces an invalid
+    /// Redshirt 2 header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Reader};
+    /// use std::io::{Cursor, Read};
+    ///
+    /// let mut encoded = v2::encode(b"Hello world!");
+    /// *encoded.last_mut().unwrap() ^= 1;
+    /// let (mut reader, report) = Reader::new_tolerant(Cursor::new(encoded)).unwrap();
+    /// assert!(!report.is_match());
+    /// let mut decoded = Vec::new();
+    /// let _ = reader.read_to_end(&mut decoded).unwrap();
+    /// assert_eq!(&decoded[..11], b"Hello world");
+    /// ```
+    pub fn new_tolerant(mut src: R) -> Result<(Self, ChecksumReport), Error> {
+        let expected = read_header(&mut src)?;
+        let base = src.stream_position().map_err(Error::Io)?;
+        let actual = hash_payload(&mut src).map_err(Error::Io)?;
+        let base = src.seek(SeekFrom::Start(base)).map_err(Error::Io)?;
+        let report = ChecksumReport { expected, actual };
+        #[cfg(feature = "tracing")]
+        if !report.is_match() {
+            trace_mismatch(expected, actual);
+        }
+        let mut reader =
+            Self::with_verifier(Cursor::new(src).pinned_at(base), Some(expected), None);
+        reader.report = Some(report);
+        Ok((reader, report))
+    }
+
+    #[inline]
+    /// Reads the header and hashes the encoded data again, returning whether they still match the
+    /// header read when the reader was created.
+    ///
+    /// This detects whether the input has been modified by something else since the reader was
+    /// created, such as another program overwriting a file. The underlying reader is used
+    /// directly, and is returned to the current position afterwards, even if an error occurs.
+    ///
+    /// If the hash in the header has changed, it replaces the one recorded by the reader, so later
+    /// calls compare against it instead. A reader that verifies the hash lazily also starts
+    /// checking reads against it, returning `Error::BadChecksum` if it does not match the encoded
+    /// data. Other readers continue to decode whatever data is now present, so the result of this
+    /// method is the only indication that it has changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any of the following occurs:
+    ///
+    /// * An I/O error occurs;
+    /// * The header is no longer a valid Redshirt 2 header;
+    /// * The reader was created by `Reader::from_raw`, `Reader::from_raw_stream`, or otherwise
+    ///   without a header to read again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Reader};
+    /// use std::io::{Cursor, Read, Write};
+    ///
+    /// let mut reader = Reader::new(Cursor::new(v2::encode(b"foobar"))).unwrap();
+    /// let mut buf = [0; 3];
+    /// reader.read_exact(&mut buf).unwrap();
+    /// assert!(reader.reverify().unwrap());
+    /// *reader.get_mut().get_mut() = v2::encode(b"foobaz");
+    /// assert!(!reader.reverify().unwrap());
+    /// reader.read_exact(&mut buf).unwrap();
+    /// assert_eq!(&buf, b"baz");
+    /// ```
+    pub fn reverify(&mut self) -> Result<bool, Error> {
+        let recorded = self.header.ok_or_else(|| {
+            Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the reader was created without a header",
+            ))
+        })?;
+        let base = self.src.pin_base().map_err(Error::Io)?;
+        let (start, position) = base
+            .checked_sub(cursor::len_u64(HEADER_LEN))
+            .zip(base.checked_add(self.src.offset()))
+            .ok_or_else(|| Error::Io(overflow_error()))?;
+        let src = self.src.get_mut();
+        let hashed = src
+            .seek(SeekFrom::Start(start))
             .map_err(Error::Io)
-            .and_then(|_| {
-                if header_buf[..MARKER_LEN] == MARKER {
-                    let mut buffer = array!(16384);
-                    let mut checksum = ChecksumBuilder::new();
-                    loop {
-                        match src.read(&mut buffer) {
-                            Ok(len) => {
-                                if len == 0 {
-                                    break;
-                                } else {
-                                    checksum.update(&buffer[..len]);
-                                }
-                            }
-                            Err(e) => {
-                                if e.kind() == io::ErrorKind::Interrupted {
-                                    continue;
-                                } else {
-                                    return Err(Error::Io(e));
-                                }
-                            }
-                        }
-                    }
-                    let digest_a = &header_buf[MARKER_LEN..];
-                    let digest_b = checksum.finish();
-                    if digest_a == digest_b {
-                        src.seek(SeekFrom::Start(HEADER_LEN as u64))
-                            .map(|_| Self(Cursor::new(src)))
-                            .map_err(Error::Io)
-                    } else {
-                        Err(Error::BadChecksum)
+            .and_then(|_| read_header(src))
+            .and_then(|header| {
+                hash_payload(src)
+                    .map(|actual| (header, actual))
+                    .map_err(Error::Io)
+            });
+        let restored = src.seek(SeekFrom::Start(position));
+        self.src.clear_peeked();
+        let (header, actual) = hashed?;
+        let _ = restored.map_err(Error::Io)?;
+        self.header = Some(header);
+        if let Some(verifier) = self.verifier.as_mut() {
+            verifier.complete(header, actual);
+        }
+        Ok(header == recorded && actual == header)
+    }
+
+    #[inline]
+    /// Lets the lazy verifier hash encoded data that was seeked past without being read, by
+    /// reading it from the underlying reader once the end of the stream is reached.
+    fn rewindable(mut self) -> Self {
+        self.hash_skipped = Some(Self::hash_skipped);
+        self
+    }
+
+    /// Hashes the encoded data from the end of the run hashed by the lazy verifier up to `end`,
+    /// reading it from the underlying reader, which is left at `end`.
+    fn hash_skipped(&mut self, end: u64) -> io::Result<()> {
+        let base = self.src.pin_base()?;
+        let Some((verifier, start)) = self
+            .verifier
+            .as_mut()
+            .and_then(|verifier| verifier.hashed().map(|start| (verifier, start)))
+        else {
+            return Ok(());
+        };
+        let (from, to) = base
+            .checked_add(start)
+            .zip(base.checked_add(end))
+            .ok_or_else(overflow_error)?;
+        let src = self.src.get_mut();
+        let hashed = src.seek(SeekFrom::Start(from)).and_then(|_| {
+            let mut skipped = (&mut *src).take(end.saturating_sub(start));
+            let mut buffer = Scratch::new();
+            let mut offset = start;
+            loop {
+                match cursor::read_checked(&mut skipped, &mut buffer) {
+                    Ok(0) => break Ok(()),
+                    Ok(len) => {
+                        verifier.update(offset, &buffer[..len], false)?;
+                        offset = cursor::add_len(offset, len)?;
                     }
-                } else {
-                    Err(Error::BadHeader)
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(e) => break Err(e),
                 }
-            })
+            }
+        });
+        let restored = src.seek(SeekFrom::Start(to));
+        self.src.clear_peeked();
+        hashed?;
+        restored.map(|_| ())
+    }
+}
+
+impl BoxedReader {
+    #[inline]
+    /// Boxes an input stream and creates a new reader from it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` in the same cases as `Reader::new`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, BoxedReader};
+    /// use std::io::{Cursor, Read};
+    ///
+    /// let encoded = v2::encode(b"Hello world!");
+    /// let mut readers = vec![
+    ///     BoxedReader::from_boxed(Cursor::new(encoded.clone())).unwrap(),
+    ///     BoxedReader::from_boxed(Cursor::new(encoded.into_boxed_slice())).unwrap(),
+    /// ];
+    /// for reader in &mut readers {
+    ///     let mut decoded = String::new();
+    ///     let _ = reader.read_to_string(&mut decoded).unwrap();
+    ///     assert_eq!(decoded, "Hello world!");
+    /// }
+    /// ```
+    pub fn from_boxed<R: Read + Seek + Send + 'static>(src: R) -> Result<Self, Error> {
+        Self::new(Box::new(src))
     }
+}
 
+impl<R> Reader<R> {
     #[inline]
     /// Unwraps a `Reader`, returning its underlying reader.
     ///
@@ -111,269 +646,5198 @@ impl<R: Read + Seek> Reader<R> {
     /// let inner = reader.into_inner();
     /// ```
     pub fn into_inner(self) -> R {
-        self.0.into_inner()
+        self.src.into_inner()
     }
-}
 
-impl<R: Read> Read for Reader<R> {
     #[inline]
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.0.read(buf)
+    #[must_use]
+    /// Returns a reference to the underlying reader.
+    pub const fn get_ref(&self) -> &R {
+        self.src.get_ref()
     }
-}
 
-impl<R: Seek> Seek for Reader<R> {
     #[inline]
-    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        self.0.seek(pos)
+    /// Returns a mutable reference to the underlying reader.
+    ///
+    /// Reading from or seeking the underlying reader directly leaves the `Reader` with a stale
+    /// position, so that later reads decode data from the wrong place without any error. Any such
+    /// use must be followed by a call to `Reader::resync`.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.src.get_mut()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the number of bytes of decoded data read so far, not counting the header.
+    ///
+    /// Unlike `Reader::position`, this is not affected by seeking.
+    pub const fn bytes_read(&self) -> u64 {
+        self.src.transferred()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the current position in the decoded data, without requiring the underlying reader
+    /// to implement `Seek`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Reader};
+    /// use std::io::{Cursor, Read};
+    ///
+    /// let mut reader = Reader::new(Cursor::new(v2::encode(b"foobar"))).unwrap();
+    /// let mut buf = [0; 3];
+    /// reader.read_exact(&mut buf).unwrap();
+    /// assert_eq!(reader.position(), 3);
+    /// assert_eq!(reader.bytes_read(), 3);
+    /// ```
+    pub const fn position(&self) -> u64 {
+        self.src.offset()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the SHA-1 hash of the encoded data, as computed while reading it.
+    ///
+    /// The hash is only computed by readers that verify it lazily, and by those created by
+    /// `Reader::from_raw` or `Reader::from_raw_stream` without a hash to verify. It is available
+    /// once the end of the stream has been reached. Bytes of encoded data that were seeked past
+    /// without being read are hashed then, unless the reader was created by
+    /// `Reader::from_raw_stream` or `ReaderOptions::from_stream`, in which case they prevent the
+    /// hash from being computed.
+    pub fn digest_computed(&self) -> Option<Digest> {
+        self.verifier.as_ref().and_then(|verifier| verifier.actual)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the result of verifying the SHA-1 hash when the reader was created, or `None` if it
+    /// was not created by `Reader::new_tolerant`.
+    ///
+    /// The report is not updated by `Reader::reverify`.
+    pub const fn checksum_report(&self) -> Option<ChecksumReport> {
+        self.report
+    }
+
+    #[inline]
+    const fn with_verifier(
+        src: Cursor<R>,
+        header: Option<Digest>,
+        verifier: Option<LazyVerifier>,
+    ) -> Self {
+        Self {
+            src,
+            header,
+            verifier,
+            limit: u64::MAX,
+            clamp_seeks: false,
+            report: None,
+            hash_skipped: None,
+        }
+    }
+
+    #[inline]
+    /// Applies the options that affect a reader after it has been created.
+    const fn configured(mut self, options: ReaderOptions) -> Self {
+        self.limit = options.max_payload_len;
+        self.clamp_seeks = options.clamp_seeks;
+        self
     }
 }
 
-impl<W: Seek + Write> Writer<W> {
+impl Reader<File> {
     #[inline]
-    /// Wraps an existing output stream and writes a Redshirt 2 header that is valid, but contains
-    /// an invalid SHA-1 hash.
+    /// Creates a new `Reader` for the same file, at the same position in the decoded data.
+    ///
+    /// The SHA-1 hash is not verified again; if it is being verified lazily, the new reader
+    /// continues from the same state. The two readers track their positions separately, but share
+    /// the underlying file offset. Before reading from one reader after using the other, call
+    /// `seek(SeekFrom::Current(0))` on it to restore its position.
     ///
     /// # Errors
     ///
-    /// Returns an `Err` if writing the header fails.
+    /// Returns an `Err` if the file handle cannot be duplicated, or its position cannot be read.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use redshirt::v2::Writer;
-    /// use std::io::Cursor;
+    /// use redshirt::v2::Reader;
+    /// use std::{
+    ///     fs::File,
+    ///     io::{Read, Seek, SeekFrom},
+    /// };
     ///
-    /// let mut data = [u8::default(); 30];
-    /// let writer = Writer::new(Cursor::new(&mut data[..])).unwrap();
+    /// let mut reader = Reader::new(File::open("User.usr").unwrap()).unwrap();
+    /// let mut other = reader.try_clone().unwrap();
+    /// let mut byte = [0];
+    /// other.read_exact(&mut byte).unwrap();
+    /// let _ = reader.seek(SeekFrom::Current(0)).unwrap();
+    /// reader.read_exact(&mut byte).unwrap();
     /// ```
-    pub fn new(mut dst: W) -> Result<Self, Error> {
-        let mut dummy_header = array!(HEADER_LEN);
-        dummy_header[..MARKER_LEN].copy_from_slice(&MARKER);
-        dst.write_all(&dummy_header)
-            .map(|_| Self {
-                dst: Some(Cursor::new(dst)),
-                checksum: ChecksumBuilder::new(),
-            })
+    pub fn try_clone(&mut self) -> io::Result<Self> {
+        Ok(Self {
+            src: self.src.try_clone()?,
+            header: self.header,
+            verifier: self.verifier.clone(),
+            limit: self.limit,
+            clamp_seeks: self.clamp_seeks,
+            report: self.report,
+            hash_skipped: self.hash_skipped,
+        })
+    }
+
+    #[cfg(feature = "flate2")]
+    #[inline]
+    /// Opens the Redshirt 2 file at `path`, verifying its SHA-1 hash, and wraps it in a
+    /// `GzDecoder`, for files whose decoded data is a gzip member.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` under the same conditions as `Reader::new`, or if the file cannot be
+    /// opened. Invalid compressed data is reported by reads from the `GzDecoder`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::Reader;
+    /// use std::io::Read;
+    ///
+    /// let mut decoded = Vec::new();
+    /// let _ = Reader::open_gz("User.usr.gz").unwrap().read_to_end(&mut decoded).unwrap();
+    /// ```
+    pub fn open_gz<P: AsRef<Path>>(path: P) -> Result<GzDecoder<Self>, Error> {
+        File::open(path)
             .map_err(Error::Io)
+            .and_then(Self::new)
+            .map(Self::with_gz)
     }
 
+    #[cfg(feature = "fadvise")]
     #[inline]
-    /// Writes out the SHA-1 hash of all previously encoded data, then unwraps the `Writer`.
+    /// Tells the operating system that the file will be read sequentially, so that it reads ahead
+    /// more aggressively.
     ///
-    /// If a `Writer` is dropped without calling this method, the SHA-1 hash is written out, but the
-    /// destructor will panic if an error occurs. Calling this method ensures that any such errors
-    /// are safely handled.
+    /// This uses `posix_fadvise` on Android, FreeBSD and Linux, and does nothing on other
+    /// platforms. The hint covers the whole file, and does not change what is read.
     ///
     /// # Errors
     ///
-    /// Returns an `Err` if writing the SHA-1 hash fails.
+    /// Returns an `Err` if the operating system rejects the hint.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use redshirt::v2::Writer;
-    /// use std::io::Cursor;
+    /// use redshirt::v2::Reader;
+    /// use std::fs::File;
     ///
-    /// let mut data = [u8::default(); 30];
-    /// let writer = Writer::new(Cursor::new(&mut data[..])).unwrap();
-    /// let inner = writer.into_inner().unwrap();
+    /// let reader = Reader::new(File::open("data.dat").unwrap()).unwrap();
+    /// reader.advise_sequential().unwrap();
     /// ```
-    pub fn into_inner(mut self) -> Result<W, Error> {
-        self.write_digest().map(Option::unwrap)
+    pub fn advise_sequential(&self) -> io::Result<()> {
+        advice::sequential(self.src.get_ref())
     }
 
+    #[cfg(feature = "fadvise")]
     #[inline]
-    fn write_digest(&mut self) -> Result<Option<W>, Error> {
-        if let Some(mut dst) = self.dst.take() {
-            let offset = dst.offset();
-            dst.inner_mut()
-                .seek(SeekFrom::Start(MARKER_LEN as u64))
-                .and_then(|_| {
-                    let digest = self.checksum.clone().finish();
-                    let res = dst.inner_mut().write_all(&digest);
-                    let _ = dst.seek(SeekFrom::Start(offset)).unwrap();
-                    res
-                })
-                .map(|_| Some(dst.into_inner()))
-                .map_err(Error::Io)
-        } else {
-            Ok(None)
-        }
+    /// Tells the operating system that the cached contents of the file will not be needed again,
+    /// so that they can be evicted from the page cache, for example once a file has been read in
+    /// a long batch run.
+    ///
+    /// This uses `posix_fadvise` on Android, FreeBSD and Linux, and does nothing on other
+    /// platforms. Data that has already been read is unaffected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the operating system rejects the hint.
+    pub fn advise_dontneed(&self) -> io::Result<()> {
+        advice::dont_need(self.src.get_ref())
     }
 }
 
-impl<W: Debug + Seek + Write> Debug for Writer<W> {
+impl<'a> Reader<io::Cursor<&'a [u8]>> {
     #[inline]
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let digest = self.checksum.clone().finish();
-        f.debug_struct("Writer")
-            .field("dst", &self.dst)
-            .field("digest", &digest)
-            .finish()
+    /// Creates a new reader from an in-memory Redshirt 2 stream.
+    ///
+    /// Unlike `Reader::new`, this hashes `data` directly rather than reading it in chunks and
+    /// seeking back to the start of the encoded data. The returned reader reads from `data`
+    /// without copying it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any of the following occurs:
+    ///
+    /// * `data` does not begin with a valid Redshirt 2 header;
+    /// * The SHA-1 hash in the header is all zeroes, indicating that the stream was never
+    ///   finalised;
+    /// * The SHA-1 hash in the header does not match that of the encoded data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Reader};
+    /// use std::io::{Read, Seek, SeekFrom};
+    ///
+    /// let encoded = v2::encode(b"Hello world!");
+    /// let mut reader = Reader::from_slice(&encoded).unwrap();
+    /// let _ = reader.seek(SeekFrom::Start(6)).unwrap();
+    /// let mut decoded = String::new();
+    /// let _ = reader.read_to_string(&mut decoded).unwrap();
+    /// assert_eq!(decoded, "world!");
+    /// ```
+    pub fn from_slice(data: &'a [u8]) -> Result<Self, Error> {
+        verified_payload(data).map(|(digest, _)| Self::at_payload(io::Cursor::new(data), digest))
     }
 }
 
-impl<W: Seek + Write> Write for Writer<W> {
+impl Reader<io::Cursor<Vec<u8>>> {
     #[inline]
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.dst.as_mut().unwrap().write_chunk(buf).map(|chunk| {
-            self.checksum.update(&chunk);
-            chunk.len()
-        })
+    /// Creates a new reader from an in-memory Redshirt 2 stream, taking ownership of it.
+    ///
+    /// As with `Reader::from_slice`, `data` is hashed directly, and is not copied.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` in the same cases as `Reader::from_slice`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Reader};
+    /// use std::io::Read;
+    ///
+    /// let mut reader = Reader::from_vec(v2::encode(b"Hello world!")).unwrap();
+    /// let mut decoded = String::new();
+    /// let _ = reader.read_to_string(&mut decoded).unwrap();
+    /// assert_eq!(decoded, "Hello world!");
+    /// ```
+    pub fn from_vec(data: Vec<u8>) -> Result<Self, Error> {
+        let (digest, _) = verified_payload(&data)?;
+        Ok(Self::at_payload(io::Cursor::new(data), digest))
     }
+}
 
-    #[inline]
-    fn flush(&mut self) -> io::Result<()> {
-        self.dst.as_mut().unwrap().flush()
+impl<T: AsRef<[u8]>> Reader<io::Cursor<T>> {
+    /// Creates a reader for a verified in-memory stream, positioned at the start of its encoded
+    /// data.
+    fn at_payload(mut src: io::Cursor<T>, digest: Digest) -> Self {
+        src.set_position(HEADER_LEN as u64);
+        Self::with_verifier(
+            Cursor::new(src).pinned_at(HEADER_LEN as u64),
+            Some(digest),
+            None,
+        )
     }
 }
 
-impl<W: Seek + Write> Drop for Writer<W> {
+impl<R: Read> Reader<R> {
     #[inline]
-    /// When a `Writer` is dropped, this causes the SHA-1 hash of all previously encoded data to be
-    /// written into the header.
+    #[must_use]
+    /// Creates a reader that decodes data from the current position of an input stream that
+    /// cannot be rewound, without reading a Redshirt 2 header.
     ///
-    /// In general, you should use `Writer::into_inner` instead of relying on implict `drop` calls.
+    /// This is the counterpart of `Reader::from_raw` for streams that cannot be rewound. If
+    /// `digest` is `Some`, the SHA-1 hash of the encoded data is verified lazily, as with
+    /// `Verification::Lazy`; otherwise, it is only computed.
     ///
-    /// # Panics
+    /// # Examples
     ///
-    /// Panics if writing the SHA-1 hash fails for any reason. To catch these errors, use
-    /// `Writer::into_inner` instead of relying on implicit `drop` calls.
-    fn drop(&mut self) {
-        let _ = self.write_digest().unwrap();
+    /// ```
+    /// use redshirt::v2::{self, Reader};
+    /// use std::io::Read;
+    ///
+    /// let encoded = v2::encode(b"foobar");
+    /// let mut reader = Reader::from_raw_stream(&encoded[29..], None);
+    /// let mut decoded = String::new();
+    /// let _ = reader.read_to_string(&mut decoded).unwrap();
+    /// assert_eq!(decoded, "foobar");
+    /// assert_eq!(reader.digest_computed(), Some(v2::verify(&encoded[..]).unwrap()));
+    /// ```
+    pub fn from_raw_stream(src: R, digest: Option<Digest>) -> Self {
+        Self::with_verifier(Cursor::new(src), None, Some(LazyVerifier::new(digest)))
     }
-}
+
+    #[inline]
+    /// Decodes the rest of the stream and writes it to `dst`, returning the number of bytes
+    /// written.
+    ///
+    /// Data is decoded in place in chunks of 16 KiB, twice the size used by `std::io::copy`, so
+    /// this should be preferred to it. Interrupted reads and writes are retried.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if reading from the underlying reader or writing to `dst` fails. If the
+    /// hash is verified lazily and does not match, the error is `Error::BadChecksum`, and all of
+    /// the decoded data has already been written to `dst`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Reader};
+    /// use std::io::Cursor;
+    ///
+    /// let encoded = v2::encode(b"foo");
+    /// let mut decoded = Vec::new();
+    /// let mut reader = Reader::new(Cursor::new(encoded)).unwrap();
+    /// assert_eq!(reader.pipe_to(&mut decoded).unwrap(), 3);
+    /// assert_eq!(decoded, b"foo");
+    /// ```
+    pub fn pipe_to<W: Write + ?Sized>(&mut self, dst: &mut W) -> Result<u64, Error> {
+        cursor::pump(self, dst).map_err(Error::from_io)
+    }
+
+    #[inline]
+    /// Wraps this `Reader` in a `BufferedReader` with a buffer of `capacity` bytes of decoded
+    /// data.
+    ///
+    /// Seeking within the buffered data does not discard it, so this is better suited to many
+    /// small reads and seeks than wrapping the underlying reader in a `std::io::BufReader`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Reader};
+    /// use std::io::{BufRead, Cursor};
+    ///
+    /// let encoded = v2::encode(b"first\nsecond\n");
+    /// let reader = Reader::new(Cursor::new(encoded)).unwrap().buffered(4096);
+    /// assert_eq!(reader.lines().count(), 2);
+    /// ```
+    pub fn buffered(self, capacity: usize) -> BufferedReader<Self> {
+        BufferedReader::with_capacity(capacity, self)
+    }
+
+    #[inline]
+    /// Wraps this `Reader` in a `ForwardOnly`, which implements `Seek` by reading and discarding
+    /// decoded data, for streams that cannot seek.
+    ///
+    /// Seeks forwards from the current position are supported; others fail with an `io::Error` of
+    /// kind `Unsupported`. If the hash is verified lazily, skipped data is still hashed. Positions
+    /// are those of the decoded data, as returned by `Reader::position`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, ReaderOptions};
+    /// use std::io::{Read, Seek, SeekFrom};
+    ///
+    /// let encoded = v2::encode(b"foobar");
+    /// let mut reader = ReaderOptions::new()
+    ///     .from_stream(&encoded[..])
+    ///     .unwrap()
+    ///     .forward_only();
+    /// assert_eq!(reader.seek(SeekFrom::Start(4)).unwrap(), 4);
+    /// let mut rest = String::new();
+    /// let _ = reader.read_to_string(&mut rest).unwrap();
+    /// assert_eq!(rest, "ar");
+    /// ```
+    pub fn forward_only(self) -> ForwardOnly<Self> {
+        let position = self.position();
+        ForwardOnly::at(self, position)
+    }
+
+    #[inline]
+    /// Skips over the next `n` bytes of decoded data, returning the number of bytes skipped, which
+    /// is less than `n` only if the end of the stream was reached.
+    ///
+    /// The skipped bytes are read from the underlying reader, but not decoded. If the hash is
+    /// verified lazily, the skipped bytes are still hashed, so verification is unaffected. With
+    /// any other verification policy, `Seek::seek` can be used instead to avoid reading them at
+    /// all.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if reading from the underlying reader fails, or the hash is verified lazily
+    /// and the end of the stream is reached with a mismatched hash.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, ReaderOptions, Verification};
+    /// use std::io::Read;
+    ///
+    /// let encoded = v2::encode(b"foobar");
+    /// let mut reader = ReaderOptions::new()
+    ///     .verification(Verification::Lazy)
+    ///     .from_stream(&encoded[..])
+    ///     .unwrap();
+    /// assert_eq!(reader.skip_bytes(3).unwrap(), 3);
+    /// let mut rest = String::new();
+    /// let _ = reader.read_to_string(&mut rest).unwrap();
+    /// assert_eq!(rest, "bar");
+    /// ```
+    pub fn skip_bytes(&mut self, n: u64) -> io::Result<u64> {
+        cursor::skip(n, |buf| self.read_verified(buf))
+    }
+
+    #[inline]
+    /// Fills `buf` with the decoded data that follows the current position, without advancing it,
+    /// returning the number of bytes peeked, which is less than `buf.len()` only if the end of the
+    /// stream, or the limit set by `ReaderOptions::max_payload_len`, was reached.
+    ///
+    /// The peeked bytes are held by the `Reader` and returned again by later reads, so this works
+    /// even if the underlying reader cannot seek, as when sniffing the format of the decoded data.
+    /// If the hash is verified lazily, the bytes are hashed once, when they are read rather than
+    /// when they are peeked, and reaching the end of the stream while peeking does not verify it.
+    /// Seeking the `Reader` discards them. Any held bytes are lost by `Reader::into_inner`, and
+    /// the underlying reader is positioned after them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if reading from the underlying reader fails, in which case any bytes read
+    /// before the failure are still held, or if lazy verification has already failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, ReaderOptions, Verification};
+    /// use std::io::Read;
+    ///
+    /// let encoded = v2::encode(b"SAV62 data");
+    /// let mut reader = ReaderOptions::new()
+    ///     .verification(Verification::Lazy)
+    ///     .from_stream(&encoded[..])
+    ///     .unwrap();
+    /// let mut magic = [0; 5];
+    /// assert_eq!(reader.peek(&mut magic).unwrap(), 5);
+    /// assert_eq!(&magic, b"SAV62");
+    /// let mut decoded = String::new();
+    /// let _ = reader.read_to_string(&mut decoded).unwrap();
+    /// assert_eq!(decoded, "SAV62 data");
+    /// ```
+    pub fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(verifier) = self.verifier.as_ref() {
+            verifier.check()?;
+        }
+        let left = self.limit.saturating_sub(self.src.offset());
+        let wanted = usize::try_from(left).map_or(buf.len(), |left| left.min(buf.len()));
+        let peeked = self.src.peek_encoded(wanted)?;
+        let len = peeked.len();
+        buf[..len].copy_from_slice(peeked);
+        xor_bytes(&mut buf[..len]);
+        Ok(len)
+    }
+
+    #[cfg(feature = "flate2")]
+    #[inline]
+    #[must_use]
+    /// Wraps this `Reader` in a `GzDecoder`, for streams whose decoded data is a gzip member.
+    ///
+    /// The SHA-1 hash covers the compressed data, so it is verified as usual, and invalid
+    /// compressed data in a stream with a valid hash is reported by reads from the `GzDecoder`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{
+    ///     v2::{Reader, Writer},
+    ///     Compression,
+    /// };
+    /// use std::io::{Cursor, Read, Write};
+    ///
+    /// let mut encoder = Writer::new(Cursor::new(Vec::new()))
+    ///     .unwrap()
+    ///     .with_gz(Compression::best());
+    /// encoder.write_all(b"foo").unwrap();
+    /// let encoded = encoder.finish().unwrap().into_inner().unwrap().into_inner();
+    /// let mut decoded = String::new();
+    /// let mut decoder = Reader::from_slice(&encoded).unwrap().with_gz();
+    /// let _ = decoder.read_to_string(&mut decoded).unwrap();
+    /// assert_eq!(decoded, "foo");
+    /// ```
+    pub fn with_gz(self) -> GzDecoder<Self> {
+        GzDecoder::new(self)
+    }
+
+    /// Reads encoded data into `buf` without decoding it, updating the lazy verifier if present.
+    fn read_verified(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(verifier) = self.verifier.as_ref() {
+            verifier.check()?;
+        }
+        let offset = self.src.offset();
+        let wanted = clamp_read(self.limit, offset, buf.len())?;
+        let len = self.src.read_encoded(&mut buf[..wanted])?;
+        check_read(self.limit, offset, len)?;
+        let eof = len == 0 && !buf.is_empty();
+        if eof {
+            self.catch_up(offset)?;
+        }
+        if let Some(verifier) = self.verifier.as_mut() {
+            verifier.update(offset, &buf[..len], eof)?;
+        }
+        Ok(len)
+    }
+
+    /// Hashes the encoded data that was seeked past without being read, once the end of the
+    /// stream has been reached at `end`, if the underlying reader can be rewound to it.
+    fn catch_up(&mut self, end: u64) -> io::Result<()> {
+        let skipped = self
+            .verifier
+            .as_ref()
+            .and_then(LazyVerifier::hashed)
+            .is_some_and(|hashed| hashed < end);
+        match self.hash_skipped {
+            Some(hash_skipped) if skipped => hash_skipped(self, end),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<R: Seek> Reader<R> {
+    #[inline]
+    /// Re-establishes the position in the decoded data from the position of the underlying
+    /// reader, after the underlying reader has been moved through `Reader::get_mut`.
+    ///
+    /// The position of the start of the data in the underlying reader is recorded when the
+    /// `Reader` is created, except by `ReaderOptions::from_stream`, in which case it is recorded
+    /// by the first seek instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if an I/O error occurs, the position of the start of the data is not
+    /// known, or the underlying reader is positioned before the start of the data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Reader};
+    /// use std::io::{Cursor, Read};
+    ///
+    /// let mut reader = Reader::new(Cursor::new(v2::encode(b"foobar"))).unwrap();
+    /// reader.get_mut().set_position(32);
+    /// reader.resync().unwrap();
+    /// let mut rest = String::new();
+    /// let _ = reader.read_to_string(&mut rest).unwrap();
+    /// assert_eq!(rest, "bar");
+    /// ```
+    pub fn resync(&mut self) -> io::Result<()> {
+        self.src.resync()
+    }
+}
+
+impl<R: Read> Read for Reader<R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.read_verified(buf)?;
+        xor_bytes(&mut buf[..len]);
+        Ok(len)
+    }
+
+    #[cfg(redshirt_unstable)]
+    #[inline]
+    fn read_buf(&mut self, cursor: BorrowedCursor<'_>) -> io::Result<()> {
+        let offset = self.src.offset();
+        let skipped = self
+            .verifier
+            .as_ref()
+            .and_then(LazyVerifier::hashed)
+            .is_some_and(|hashed| hashed < offset);
+        if self.limit != u64::MAX || skipped {
+            // Only `read` can keep the read from going past the limit, or hash skipped data.
+            return read_buf::fill_init(cursor, |buf| self.read(buf));
+        }
+        if let Some(verifier) = self.verifier.as_ref() {
+            verifier.check()?;
+        }
+        let empty = cursor.capacity() == 0;
+        let verifier = self.verifier.as_mut();
+        self.src.read_encoded_buf(cursor, |filled| {
+            if let Some(verifier) = verifier {
+                verifier.update(offset, filled, filled.is_empty() && !empty)?;
+            }
+            xor_bytes(filled);
+            Ok(())
+        })
+    }
+}
+
+impl<R: Seek> Seek for Reader<R> {
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        if self.clamp_seeks {
+            self.src.seek_clamped(pos)
+        } else {
+            self.src.seek(pos)
+        }
+    }
+}
+
+impl ReaderOptions {
+    #[inline]
+    #[must_use]
+    /// Creates a new set of options with the default configuration.
+    ///
+    /// By default, every stream is verified against the SHA-1 hash stored in its header, and
+    /// streams whose header contains an all-zero hash are rejected.
+    pub const fn new() -> Self {
+        Self {
+            accept_unfinalized: false,
+            verification: Verification::Eager,
+            max_payload_len: u64::MAX,
+            clamp_seeks: false,
+        }
+    }
+
+    #[inline]
+    /// Sets whether streams with an all-zero SHA-1 hash in their header are accepted.
+    ///
+    /// A `Writer` that is interrupted before it can finalise its output leaves an all-zero hash in
+    /// the header. If this option is enabled, such streams are read without verification instead
+    /// of being rejected with `Error::Unfinalized`.
+    pub fn accept_unfinalized(&mut self, accept: bool) -> &mut Self {
+        self.accept_unfinalized = accept;
+        self
+    }
+
+    #[inline]
+    /// Sets when the SHA-1 hash of the encoded data is verified.
+    ///
+    /// The default is `Verification::Eager`.
+    pub fn verification(&mut self, verification: Verification) -> &mut Self {
+        self.verification = verification;
+        self
+    }
+
+    #[inline]
+    /// Sets the maximum length of the decoded data, in bytes, not counting the header.
+    ///
+    /// Reading past the limit fails with an `io::Error` wrapping `Error::PayloadTooLarge`, so that
+    /// `Read::read_to_end` cannot be made to allocate without bound by an oversized stream. For a
+    /// stream opened with `from_reader`, or with `with_options` on an asynchronous reader, the
+    /// length is also checked up front, and an oversized stream is rejected with
+    /// `Error::PayloadTooLarge` before any of it is hashed or decoded.
+    ///
+    /// The default is `u64::MAX`, which imposes no limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{v2::{self, ReaderOptions}, Error};
+    /// use std::io::Cursor;
+    ///
+    /// let encoded = Cursor::new(v2::encode(b"foobar"));
+    /// let res = ReaderOptions::new().max_payload_len(4).from_reader(encoded);
+    /// assert!(matches!(
+    ///     res,
+    ///     Err(Error::PayloadTooLarge { limit: 4, attempted: 6 })
+    /// ));
+    /// ```
+    pub fn max_payload_len(&mut self, bytes: u64) -> &mut Self {
+        self.max_payload_len = bytes;
+        self
+    }
+
+    #[inline]
+    /// Sets whether seeking a reader to a position before the start of the decoded data moves it
+    /// to the start instead of failing.
+    ///
+    /// By default, such a seek fails with an `io::Error` of kind `InvalidInput`, and the reader's
+    /// position is unchanged. With this option enabled, the reader is moved to the start of the
+    /// decoded data, and the seek returns `Ok(0)`. Seeks past the largest representable position
+    /// still fail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, ReaderOptions};
+    /// use std::io::{Cursor, Seek, SeekFrom};
+    ///
+    /// let encoded = Cursor::new(v2::encode(b"foobar"));
+    /// let mut reader = ReaderOptions::new()
+    ///     .clamp_seeks(true)
+    ///     .from_reader(encoded)
+    ///     .unwrap();
+    /// assert_eq!(reader.seek(SeekFrom::End(-100)).unwrap(), 0);
+    /// assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), 4);
+    /// ```
+    pub fn clamp_seeks(&mut self, clamp: bool) -> &mut Self {
+        self.clamp_seeks = clamp;
+        self
+    }
+
+    #[inline]
+    /// Creates a new reader from an input stream, using the current set of options.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any of the following occurs:
+    ///
+    /// * An I/O error occurs;
+    /// * The underlying reader produces an invalid Redshirt 2 header;
+    /// * The SHA-1 hash in the header is all zeroes, and unfinalised streams are not accepted;
+    /// * The SHA-1 hash in the header does not match that of the encoded data;
+    /// * The encoded data is longer than `max_payload_len` allows, in which case the error is
+    ///   `Error::PayloadTooLarge`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::ReaderOptions;
+    /// use std::fs::OpenOptions;
+    ///
+    /// let file = OpenOptions::new().read(true).open("User.usr").unwrap();
+    /// let reader = ReaderOptions::new()
+    ///     .accept_unfinalized(true)
+    ///     .from_reader(file)
+    ///     .unwrap();
+    /// ```
+    pub fn from_reader<R: Read + Seek>(&self, src: R) -> Result<Reader<R>, Error> {
+        self.open_seekable(src, None)
+    }
+
+    #[inline]
+    /// Creates a new reader from an input stream as `from_reader` does, stopping with
+    /// `Error::Cancelled` if `cancel` is cancelled while the SHA-1 hash is being verified.
+    ///
+    /// The token is only checked by `Verification::Eager`, before each chunk of up to 16 KiB is
+    /// hashed. The reader returned does not keep the token.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` in the same cases as `from_reader`, or `Error::Cancelled` if `cancel` is
+    /// cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{v2::{self, ReaderOptions}, CancelToken, Error};
+    /// use std::io::Cursor;
+    ///
+    /// let token = CancelToken::new();
+    /// token.cancel();
+    /// let encoded = Cursor::new(v2::encode(b"foo"));
+    /// assert!(matches!(
+    ///     ReaderOptions::new().from_reader_cancellable(encoded, &token),
+    ///     Err(Error::Cancelled)
+    /// ));
+    /// ```
+    pub fn from_reader_cancellable<R: Read + Seek>(
+        &self,
+        src: R,
+        cancel: &CancelToken,
+    ) -> Result<Reader<R>, Error> {
+        self.open_seekable(src, Some(cancel))
+    }
+
+    #[inline]
+    fn open_seekable<R: Read + Seek>(
+        &self,
+        mut src: R,
+        cancel: Option<&CancelToken>,
+    ) -> Result<Reader<R>, Error> {
+        recorded(self.verification == Verification::Eager, |payload_len| {
+            let expected = read_header(&mut src)?;
+            #[cfg(feature = "tracing")]
+            self.trace_open(expected);
+            self.check_len(&mut src)?;
+            if self.verification != Verification::Eager || expected.is_zero() {
+                let verifier = self.verifier(expected)?;
+                return Cursor::new_seekable(src)
+                    .map(|src| {
+                        Reader::with_verifier(src, Some(expected), verifier)
+                            .rewindable()
+                            .configured(*self)
+                    })
+                    .map_err(Error::Io);
+            }
+            let base = src.stream_position().map_err(Error::Io)?;
+            let (actual, len) = {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::debug_span!("verify", %expected).entered();
+                hash_payload_counted(&mut src, cancel).map_err(Error::from_io)?
+            };
+            *payload_len = Some(len);
+            if actual == expected {
+                src.seek(SeekFrom::Start(base))
+                    .map(|base| {
+                        Reader::with_verifier(
+                            Cursor::new(src).pinned_at(base),
+                            Some(expected),
+                            None,
+                        )
+                        .configured(*self)
+                    })
+                    .map_err(Error::Io)
+            } else {
+                Err(checksum_mismatch(expected, actual))
+            }
+        })
+    }
+
+    #[inline]
+    /// Creates a new reader from an input stream that cannot be rewound, using the current set of
+    /// options.
+    ///
+    /// Since the hash of the encoded data can only be computed by reading the entire stream,
+    /// `Verification::Eager` is treated as `Verification::Lazy`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any of the following occurs:
+    ///
+    /// * An I/O error occurs;
+    /// * The underlying reader produces an invalid Redshirt 2 header;
+    /// * The SHA-1 hash in the header is all zeroes, unfinalised streams are not accepted, and
+    ///   verification is not disabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::ReaderOptions;
+    /// use std::io;
+    ///
+    /// let stdin = io::stdin();
+    /// let reader = ReaderOptions::new().from_stream(stdin.lock()).unwrap();
+    /// ```
+    pub fn from_stream<R: Read>(&self, mut src: R) -> Result<Reader<R>, Error> {
+        let expected = read_header(&mut src)?;
+        self.open_stream(src, expected)
+    }
+
+    #[cfg(feature = "redshirt1")]
+    #[inline]
+    /// Creates a new reader as `from_stream` does, from an input stream whose marker has already
+    /// been read and checked.
+    pub(crate) fn open_after_marker<R: Read>(&self, mut src: R) -> Result<Reader<R>, Error> {
+        let mut digest = array!(DIGEST_LEN);
+        cursor::read_exact(&mut src, &mut digest).map_err(Error::Io)?;
+        self.open_stream(src, Digest::from_bytes(digest))
+    }
+
+    #[inline]
+    fn open_stream<R: Read>(&self, src: R, expected: Digest) -> Result<Reader<R>, Error> {
+        #[cfg(feature = "tracing")]
+        self.trace_open(expected);
+        self.verifier(expected).map(|verifier| {
+            Reader::with_verifier(Cursor::new(src), Some(expected), verifier).configured(*self)
+        })
+    }
+
+    #[inline]
+    /// Fails with `Error::PayloadTooLarge` if more than `max_payload_len` bytes follow the current
+    /// position of `src`, leaving `src` where it was. Nothing is checked if there is no limit.
+    fn check_len<R: Seek>(self, src: &mut R) -> Result<(), Error> {
+        if self.max_payload_len == u64::MAX {
+            return Ok(());
+        }
+        let base = src.stream_position().map_err(Error::Io)?;
+        let end = src.seek(SeekFrom::End(0)).map_err(Error::Io)?;
+        let _ = src.seek(SeekFrom::Start(base)).map_err(Error::Io)?;
+        check_payload_len(self.max_payload_len, end.saturating_sub(base))
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace_open(self, digest: Digest) {
+        tracing::debug!(
+            version = 2,
+            header_len = HEADER_LEN,
+            %digest,
+            verification = ?self.verification,
+            "reading Redshirt 2 stream",
+        );
+    }
+
+    #[inline]
+    fn verifier(self, expected: Digest) -> Result<Option<LazyVerifier>, Error> {
+        if self.verification == Verification::Skip {
+            Ok(None)
+        } else if !expected.is_zero() {
+            Ok(Some(LazyVerifier::new(Some(expected))))
+        } else if self.accept_unfinalized {
+            Ok(None)
+        } else {
+            Err(Error::Unfinalized)
+        }
+    }
+}
+
+impl Default for ReaderOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WriterOptions {
+    #[inline]
+    #[must_use]
+    /// Creates a new set of options with the default configuration.
+    ///
+    /// By default, the SHA-1 hash is only written into the header when the `Writer` is unwrapped
+    /// or dropped, data is encoded in chunks of up to 16 KiB, and every write is passed on to the
+    /// underlying writer straight away.
+    pub const fn new() -> Self {
+        Self {
+            checkpoint_on_flush: false,
+            checkpoint_every: None,
+            buffer_len: BUFFER_LEN,
+            coalesce_writes: false,
+            max_payload_len: u64::MAX,
+            preallocate: 0,
+        }
+    }
+
+    #[inline]
+    /// Sets whether the SHA-1 hash of all data written so far is written into the header whenever
+    /// the `Writer` is flushed.
+    ///
+    /// If this option is enabled, the output is a valid Redshirt 2 stream up to the point of the
+    /// last flush, so if the program stops before the `Writer` is finalised, the output can be
+    /// truncated to that point and read. Each flush then costs two extra seeks, a 20-byte write
+    /// and a flush of the underlying writer.
+    pub fn checkpoint_on_flush(&mut self, checkpoint: bool) -> &mut Self {
+        self.checkpoint_on_flush = checkpoint;
+        self
+    }
+
+    #[inline]
+    /// Sets how many bytes of data may be written before the SHA-1 hash of all data written so far
+    /// is written into the header. A value of `0` disables periodic checkpoints.
+    ///
+    /// If this option is enabled, the output is a valid Redshirt 2 stream up to a point at most
+    /// `bytes` bytes before the end of the data written, without the caller needing to flush the
+    /// `Writer`. Writes are split so that a checkpoint falls exactly every `bytes` bytes. If a
+    /// checkpoint fails, the error is returned by the next write instead, as the data preceding
+    /// it has already been written.
+    pub fn checkpoint_every(&mut self, bytes: u64) -> &mut Self {
+        self.checkpoint_every = if bytes == 0 { None } else { Some(bytes) };
+        self
+    }
+
+    #[inline]
+    /// Sets the size in bytes of the buffer in which the `Writer` encodes data before writing it.
+    /// A value of `0` is treated as `1`.
+    ///
+    /// The buffer is allocated on the heap by the first write and reused by every later one. Each
+    /// call to `write` encodes and writes at most this many bytes, so a larger buffer means fewer
+    /// writes to the underlying stream when writing large amounts of data at once.
+    pub fn buffer_len(&mut self, len: usize) -> &mut Self {
+        self.buffer_len = len;
+        self
+    }
+
+    #[inline]
+    /// Sets whether writes smaller than the `Writer`'s buffer are held in the buffer, and written
+    /// to the underlying writer together, instead of one by one.
+    ///
+    /// Many small writes then cost a single write to the underlying writer, and a single update of
+    /// the SHA-1 hash, per buffer filled. Writes at least as large as the buffer are not held, but
+    /// any data already held is written first, so the output is unchanged.
+    ///
+    /// Held data is written when the buffer cannot fit the next write, and by `flush`, by a
+    /// checkpoint, by `write_reader`, and when the SHA-1 hash is written by `into_inner` or `drop`.
+    /// An error writing it is returned by whichever of these triggered the write, and the data
+    /// that was not written is kept, to be written by the next of them. Unlike `Writer::buffered`,
+    /// this also saves updating the SHA-1 hash once per write.
+    pub fn coalesce_writes(&mut self, coalesce: bool) -> &mut Self {
+        self.coalesce_writes = coalesce;
+        self
+    }
+
+    #[inline]
+    /// Sets the maximum length in bytes of the data written, not counting the header. By default,
+    /// the length is not limited.
+    ///
+    /// A write that would exceed the limit writes as much of its data as fits, and returns the
+    /// number of bytes written as usual; once the limit is reached, writing more fails with an
+    /// `io::Error` wrapping `Error::PayloadTooLarge`, and nothing is written. `write_all` therefore
+    /// writes exactly `bytes` bytes of an overlong buffer before failing. `Writer::write_reader`
+    /// fails in the same way if `src` has more data once the limit is reached, discarding the
+    /// byte it read to find out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{v2::WriterOptions, Error};
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut writer = WriterOptions::new()
+    ///     .max_payload_len(4)
+    ///     .from_writer(Cursor::new(Vec::new()))
+    ///     .unwrap();
+    /// let e = writer.write_all(b"foobar").unwrap_err();
+    /// assert!(matches!(
+    ///     e.get_ref().and_then(|inner| inner.downcast_ref::<Error>()),
+    ///     Some(Error::PayloadTooLarge { limit: 4, attempted: 6 })
+    /// ));
+    /// assert_eq!(writer.bytes_written(), 4);
+    /// ```
+    pub fn max_payload_len(&mut self, bytes: u64) -> &mut Self {
+        self.max_payload_len = bytes;
+        self
+    }
+
+    #[inline]
+    /// Sets how many bytes of data to reserve space for when writing to a file. A value of `0`, the
+    /// default, reserves no space.
+    ///
+    /// `WriterOptions::from_file` extends the file to fit the header and `bytes` bytes of data
+    /// before anything is written. If less data than that is written, the file is truncated back
+    /// to the end of the data before the SHA-1 hash is written into the header. The file is never
+    /// shortened to reserve space, and other output streams ignore this option.
+    ///
+    /// With the `fallocate` feature on Linux, the space is allocated on disk with `fallocate`, so
+    /// that the file system can lay the file out in one go and writing it cannot run out of space.
+    /// Otherwise, or if the file system does not support `fallocate`, the file is only extended
+    /// with `File::set_len`, which on most file systems creates a sparse file without reserving
+    /// any space.
+    ///
+    /// Until the `Writer` is finalised, the file holds zeroes after the data written so far, so a
+    /// checkpoint is only readable once the file is truncated to the end of the data it covers.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::WriterOptions;
+    /// use std::{fs::File, io::Write};
+    ///
+    /// let file = File::create("archive.dat").unwrap();
+    /// let mut writer = WriterOptions::new()
+    ///     .preallocate(2 << 30)
+    ///     .from_file(file)
+    ///     .unwrap();
+    /// writer.write_all(b"foo").unwrap();
+    /// let file = writer.into_inner().unwrap();
+    /// assert_eq!(file.metadata().unwrap().len(), 32);
+    /// ```
+    pub fn preallocate(&mut self, bytes: u64) -> &mut Self {
+        self.preallocate = bytes;
+        self
+    }
+
+    #[inline]
+    /// Wraps an existing output stream in a `Writer` with these options, and writes a Redshirt 2
+    /// header that is valid, but contains an invalid SHA-1 hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::WriterOptions;
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut writer = WriterOptions::new()
+    ///     .checkpoint_on_flush(true)
+    ///     .from_writer(Cursor::new(Vec::new()))
+    ///     .unwrap();
+    /// writer.write_all(b"foo").unwrap();
+    /// writer.flush().unwrap();
+    /// ```
+    pub fn from_writer<W: Seek + Write>(&self, dst: W) -> Result<Writer<W>, Error> {
+        self.wrap(dst, |dst| Cursor::with_buffer_len(dst, self.buffer_len))
+    }
+
+    #[inline]
+    /// Wraps a file in a `Writer` with these options, as by `WriterOptions::from_writer`, first
+    /// reserving space for the header and data if `WriterOptions::preallocate` was used.
+    ///
+    /// The header is written at the current position of `file`, and the space is reserved from
+    /// there.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the position or length of `file` cannot be read, `file` cannot be
+    /// extended, or writing the header fails.
+    pub fn from_file(&self, mut file: File) -> Result<Writer<File>, Error> {
+        let mut preallocated = None;
+        if self.preallocate > 0 {
+            let reserved = file
+                .stream_position()
+                .and_then(|start| {
+                    start
+                        .checked_add(cursor::len_u64(HEADER_LEN))
+                        .and_then(|n| n.checked_add(self.preallocate))
+                        .ok_or_else(overflow_error)
+                })
+                .map_err(Error::Io)?;
+            if file.metadata().map_err(Error::Io)?.len() < reserved {
+                prealloc::reserve(&file, reserved).map_err(Error::Io)?;
+                let set_len: SetLen<File> = File::set_len;
+                preallocated = Some((reserved, set_len));
+            }
+        }
+        let mut writer = self.from_writer(file)?;
+        writer.preallocated = preallocated;
+        Ok(writer)
+    }
+
+    #[inline]
+    /// Wraps an existing output stream in a `Writer` with these options, encoding data in an
+    /// inline buffer of `BUF` bytes instead of one of the length set by `buffer_len`, and writes a
+    /// Redshirt 2 header that is valid, but contains an invalid SHA-1 hash.
+    ///
+    /// If `BUF` is `0`, this is the same as `from_writer` with the default buffer length.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Writer, WriterOptions};
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut writer: Writer<_, 4> = WriterOptions::new()
+    ///     .from_writer_inline(Cursor::new(Vec::new()))
+    ///     .unwrap();
+    /// assert_eq!(writer.write(b"foobar").unwrap(), 4);
+    /// writer.write_all(b"ar").unwrap();
+    /// let inner = writer.into_inner().unwrap();
+    /// assert_eq!(inner.into_inner(), v2::encode(b"foobar"));
+    /// ```
+    pub fn from_writer_inline<W: Seek + Write, const BUF: usize>(
+        &self,
+        dst: W,
+    ) -> Result<Writer<W, BUF>, Error> {
+        self.wrap(dst, Cursor::with_inline_buffer)
+    }
+
+    /// Writes a Redshirt 2 header to `dst`, then wraps it in a `Writer` using `cursor` to create
+    /// the `Cursor` through which data is encoded.
+    fn wrap<W: Seek + Write, const BUF: usize, F: FnOnce(W) -> Cursor<W, BUF>>(
+        &self,
+        mut dst: W,
+        cursor: F,
+    ) -> Result<Writer<W, BUF>, Error> {
+        let mut dummy_header = array!(HEADER_LEN);
+        dummy_header[..MARKER_LEN].copy_from_slice(&MARKER);
+        cursor::write_all(&mut dst, &dummy_header)
+            .map(|()| Writer {
+                dst: Some(cursor(dst)),
+                checksum: ChecksumBuilder::new(),
+                options: *self,
+                since_checkpoint: 0,
+                checkpoint_pending: false,
+                finalize_on_drop: true,
+                preallocated: None,
+            })
+            .map_err(Error::Io)
+    }
+}
+
+impl Default for WriterOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CopyOptions {
+    #[inline]
+    #[must_use]
+    /// Creates a new set of options with the default configuration.
+    ///
+    /// By default, data is written to the output stream as soon as it is read.
+    pub const fn new() -> Self {
+        Self { buffered: false }
+    }
+
+    #[inline]
+    /// Sets whether the encoded data is held in memory until its SHA-1 hash has been verified.
+    ///
+    /// If this option is enabled, nothing is written to the output stream unless the hash is
+    /// valid, at the cost of buffering the entire stream.
+    pub fn buffered(&mut self, buffered: bool) -> &mut Self {
+        self.buffered = buffered;
+        self
+    }
+
+    #[inline]
+    /// Copies a Redshirt 2 stream from `src` to `dst` without decoding it, verifying its SHA-1 hash
+    /// and returning it.
+    ///
+    /// The header and encoded data are copied verbatim. Unless the `buffered` option is enabled,
+    /// the encoded data has already been written to `dst` by the time a hash mismatch is detected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any of the following occurs:
+    ///
+    /// * An I/O error occurs;
+    /// * The stream contains an invalid Redshirt 2 header;
+    /// * The SHA-1 hash in the header is all zeroes, indicating that the stream was never
+    ///   finalised;
+    /// * The SHA-1 hash in the header does not match that of the encoded data.
+    ///
+    /// Nothing is written to `dst` if the header is invalid or unfinalised.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2;
+    ///
+    /// let mut encoded = v2::encode(b"foo");
+    /// let last = encoded.len() - 1;
+    /// encoded[last] ^= 0xFF;
+    /// let mut copy = Vec::new();
+    /// assert!(v2::CopyOptions::new()
+    ///     .buffered(true)
+    ///     .copy(&encoded[..], &mut copy)
+    ///     .is_err());
+    /// assert!(copy.is_empty());
+    /// ```
+    pub fn copy<R: Read, W: Write>(&self, src: R, dst: W) -> Result<Digest, Error> {
+        self.copy_with(src, dst, None)
+    }
+
+    #[inline]
+    /// Copies a Redshirt 2 stream as `copy` does, stopping with `Error::Cancelled` if `cancel` is
+    /// cancelled before the whole stream has been copied.
+    ///
+    /// The token is checked before each chunk of up to 16 KiB is read. Data already written to
+    /// `dst` when the copy is cancelled is left there, unless the `buffered` option is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` in the same cases as `copy`, or `Error::Cancelled` if `cancel` is
+    /// cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{v2, CancelToken};
+    ///
+    /// let token = CancelToken::new();
+    /// let encoded = v2::encode(b"foo");
+    /// let mut copy = Vec::new();
+    /// let _ = v2::CopyOptions::new()
+    ///     .copy_cancellable(&encoded[..], &mut copy, &token)
+    ///     .unwrap();
+    /// assert_eq!(copy, encoded);
+    /// ```
+    pub fn copy_cancellable<R: Read, W: Write>(
+        &self,
+        src: R,
+        dst: W,
+        cancel: &CancelToken,
+    ) -> Result<Digest, Error> {
+        self.copy_with(src, dst, Some(cancel))
+    }
+
+    #[inline]
+    fn copy_with<R: Read, W: Write>(
+        self,
+        mut src: R,
+        mut dst: W,
+        cancel: Option<&CancelToken>,
+    ) -> Result<Digest, Error> {
+        recorded(true, |payload_len| {
+            let mut header_buf = array!(HEADER_LEN);
+            cursor::read_exact(&mut src, &mut header_buf).map_err(Error::Io)?;
+            let expected = parse_header(&header_buf)?;
+            if expected.is_zero() {
+                return Err(Error::Unfinalized);
+            }
+            let mut held = Vec::new();
+            if !self.buffered {
+                cursor::write_all(&mut dst, &header_buf).map_err(Error::Io)?;
+            }
+            let mut buffer = Scratch::new();
+            let mut checksum = ChecksumBuilder::new();
+            let mut copied = 0;
+            loop {
+                cancel::check(cancel)?;
+                let len = match cursor::read_checked(&mut src, &mut buffer) {
+                    Ok(0) => break,
+                    Ok(len) => len,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(Error::Io(e)),
+                };
+                checksum.update(&buffer[..len]);
+                copied = cursor::add_len(copied, len).map_err(Error::Io)?;
+                if self.buffered {
+                    held.extend_from_slice(&buffer[..len]);
+                } else {
+                    cursor::write_all(&mut dst, &buffer[..len]).map_err(Error::Io)?;
+                }
+            }
+            let actual = checksum.finish();
+            *payload_len = Some(copied);
+            if actual != expected {
+                return Err(checksum_mismatch(expected, actual));
+            }
+            if self.buffered {
+                cursor::write_all(&mut dst, &header_buf)
+                    .and_then(|()| cursor::write_all(&mut dst, &held))
+                    .map_err(Error::Io)?;
+            }
+            dst.flush().map(|()| actual).map_err(Error::Io)
+        })
+    }
+}
+
+impl Default for CopyOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+impl SpillOptions {
+    #[inline]
+    #[must_use]
+    /// Creates a new set of options with the default configuration.
+    ///
+    /// By default, up to 64 MiB of encoded data is held in memory, and the temporary file is
+    /// created in the directory returned by `std::env::temp_dir`.
+    pub const fn new() -> Self {
+        Self {
+            memory_limit: 64 * 1024 * 1024,
+            temp_dir: None,
+        }
+    }
+
+    #[inline]
+    /// Sets the maximum number of bytes of encoded data held in memory.
+    ///
+    /// A limit of 0 is treated as 1.
+    pub fn memory_limit(&mut self, bytes: usize) -> &mut Self {
+        self.memory_limit = bytes.max(1);
+        self
+    }
+
+    #[inline]
+    /// Sets the directory in which the temporary file is created.
+    pub fn temp_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.temp_dir = Some(dir.into());
+        self
+    }
+
+    #[cfg(any(feature = "tokio", feature = "futures-io"))]
+    #[inline]
+    pub(crate) const fn limit(&self) -> usize {
+        self.memory_limit
+    }
+
+    #[cfg(any(feature = "tokio", feature = "futures-io"))]
+    #[inline]
+    pub(crate) fn create_file(&self) -> io::Result<File> {
+        self.temp_dir
+            .as_ref()
+            .map_or_else(tempfile::tempfile, tempfile::tempfile_in)
+    }
+}
+
+#[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+impl Default for SpillOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Verification {
+    #[inline]
+    fn default() -> Self {
+        Verification::Eager
+    }
+}
+
+impl Default for PrefixVerify {
+    #[inline]
+    fn default() -> Self {
+        PrefixVerify::Full
+    }
+}
+
+impl ChecksumReport {
+    #[inline]
+    #[must_use]
+    /// Returns whether the hash in the header matches that of the encoded data.
+    pub fn is_match(&self) -> bool {
+        self.expected == self.actual
+    }
+
+    #[inline]
+    /// Converts the report into a `Result`, returning the hash of the encoded data if it matches
+    /// the header.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BadChecksum` if the hashes do not match.
+    pub fn into_result(self) -> Result<Digest, Error> {
+        if self.is_match() {
+            Ok(self.actual)
+        } else {
+            Err(Error::BadChecksum {
+                expected: self.expected,
+                actual: self.actual,
+            })
+        }
+    }
+}
+
+impl SalvageReport {
+    #[inline]
+    #[must_use]
+    /// Returns whether the hash in the header is all zeroes, indicating that the stream was never
+    /// finalised.
+    pub fn is_unfinalized(&self) -> bool {
+        self.stored.is_some_and(|stored| stored.is_zero())
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns whether the header contains a finalised hash that does not match the recovered data,
+    /// as it would if the stream were truncated or corrupt.
+    pub fn is_mismatch(&self) -> bool {
+        self.stored
+            .is_some_and(|stored| !stored.is_zero() && stored != self.actual)
+    }
+}
+
+#[inline]
+/// Verifies a Redshirt 2 stream without decoding it, returning the SHA-1 hash of the encoded data.
+///
+/// # Errors
+///
+/// Returns an `Err` if any of the following occurs:
+///
+/// * An I/O error occurs;
+/// * The stream contains an invalid Redshirt 2 header;
+/// * The SHA-1 hash in the header is all zeroes, indicating that the stream was never finalised;
+/// * The SHA-1 hash in the header does not match that of the encoded data.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::v2;
+/// use std::fs::OpenOptions;
+///
+/// let file = OpenOptions::new().read(true).open("User.usr").unwrap();
+/// let digest = v2::verify(file).unwrap();
+/// ```
+pub fn verify<R: Read>(src: R) -> Result<Digest, Error> {
+    verify_with(src, None)
+}
+
+#[inline]
+/// Verifies a Redshirt 2 stream as `verify` does, stopping with `Error::Cancelled` if `cancel` is
+/// cancelled before the whole stream has been hashed.
+///
+/// The token is checked before each chunk of up to 16 KiB is read.
+///
+/// # Errors
+///
+/// Returns an `Err` in the same cases as `verify`, or `Error::Cancelled` if `cancel` is cancelled.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::{v2, CancelToken};
+///
+/// let token = CancelToken::new();
+/// let encoded = v2::encode(b"foo");
+/// assert!(v2::verify_cancellable(&encoded[..], &token).is_ok());
+/// ```
+pub fn verify_cancellable<R: Read>(src: R, cancel: &CancelToken) -> Result<Digest, Error> {
+    verify_with(src, Some(cancel))
+}
+
+#[inline]
+pub(crate) fn verify_with<R: Read>(
+    mut src: R,
+    cancel: Option<&CancelToken>,
+) -> Result<Digest, Error> {
+    recorded(true, |payload_len| {
+        let expected = read_header(&mut src)?;
+        if expected.is_zero() {
+            return Err(Error::Unfinalized);
+        }
+        let (actual, len) = hash_payload_counted(&mut src, cancel).map_err(Error::from_io)?;
+        *payload_len = Some(len);
+        if actual == expected {
+            Ok(actual)
+        } else {
+            Err(checksum_mismatch(expected, actual))
+        }
+    })
+}
+
+#[inline]
+/// Copies a Redshirt 2 stream from `src` to `dst` without decoding it, verifying its SHA-1 hash
+/// and returning it.
+///
+/// This is equivalent to `CopyOptions::new().copy(src, dst)`. The encoded data is written as it is
+/// read, so `dst` receives a full copy of a corrupt stream before `Error::BadChecksum` is
+/// returned; use `CopyOptions::buffered` to avoid this.
+///
+/// # Errors
+///
+/// See `CopyOptions::copy`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::v2;
+/// use std::fs::File;
+///
+/// let src = File::open("User.usr").unwrap();
+/// let dst = File::create("User.usr.bak").unwrap();
+/// let digest = v2::copy_verified(src, dst).unwrap();
+/// println!("copied, SHA-1 {digest}");
+/// ```
+pub fn copy_verified<R: Read, W: Write>(src: R, dst: W) -> Result<Digest, Error> {
+    CopyOptions::new().copy(src, dst)
+}
+
+#[inline]
+/// Copies the rest of the data from `src` to `dst` without decoding and re-encoding it, returning
+/// the number of bytes copied.
+///
+/// The encoded data is moved from the underlying reader of `src` to the underlying writer of `dst`
+/// unchanged, without being transformed in either direction, and is hashed only for `dst`, as by
+/// `Writer::write`. This is the fastest way to copy data between Redshirt 2 streams, or part of one
+/// into another. `src` verifies the data as it would if it were read normally, so if it verifies
+/// lazily and its hash does not match, `Error::BadChecksum` is returned once all of the data has
+/// been copied.
+///
+/// Peeked data is copied too. Checkpoints and `WriterOptions::max_payload_len` apply to `dst` as
+/// they do for `Writer::write_reader`, and the SHA-1 hash is not written until `dst` is finished.
+///
+/// # Errors
+///
+/// Returns an `Err` if reading from `src` or writing to `dst` fails, if `src` verifies lazily and
+/// its hash does not match, or `Error::PayloadTooLarge` if either limit is exceeded.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v2::{self, Reader, Writer};
+/// use std::io::Cursor;
+///
+/// let mut src = Reader::new(Cursor::new(v2::encode(b"foobar"))).unwrap();
+/// let mut dst = Writer::new(Cursor::new(Vec::new())).unwrap();
+/// assert_eq!(v2::copy_raw(&mut src, &mut dst).unwrap(), 6);
+/// let encoded = dst.into_inner().unwrap().into_inner();
+/// assert_eq!(encoded, v2::encode(b"foobar"));
+/// ```
+pub fn copy_raw<R: Read, W: Seek + Write, const BUF: usize>(
+    src: &mut Reader<R>,
+    dst: &mut Writer<W, BUF>,
+) -> Result<u64, Error> {
+    let mut encoded = ReadFn(|buf: &mut [u8]| src.read_verified(buf));
+    cursor::pump_with(|| dst.pipe_chunk(&mut encoded, false)).map_err(Error::from_io)
+}
+
+#[inline]
+/// Decodes as much of a damaged Redshirt 2 stream as is present, writing it to `dst` and
+/// returning a report of what was recovered.
+///
+/// The SHA-1 hash in the header is read but not enforced, so a stream that was truncated, for
+/// example by a full disk, or never finalised yields all of its remaining data. A stream that ends
+/// within its header is reported as having nothing to recover. Use `SalvageReport::is_mismatch`
+/// and `SalvageReport::is_unfinalized` to find out whether the data is known to be incomplete or
+/// corrupt.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, or `Error::BadHeader` if `src` does not begin with a
+/// Redshirt 2 marker, or as much of one as it contains. The hash never causes an error.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v2;
+///
+/// let encoded = v2::encode(b"Hello world!");
+/// let mut decoded = Vec::new();
+/// let report = v2::salvage(&encoded[..encoded.len() - 7], &mut decoded).unwrap();
+/// assert_eq!(decoded, b"Hello");
+/// assert_eq!(report.recovered, 5);
+/// assert!(report.is_mismatch());
+/// ```
+pub fn salvage<R: Read, W: Write>(mut src: R, mut dst: W) -> Result<SalvageReport, Error> {
+    let mut header_buf = array!(HEADER_LEN);
+    let header_len = cursor::read_up_to(&mut src, &mut header_buf).map_err(Error::Io)?;
+    let marker_len = header_len.min(MARKER_LEN);
+    if header_buf[..marker_len] != MARKER[..marker_len] {
+        return Err(Error::BadHeader);
+    }
+    let mut checksum = ChecksumBuilder::new();
+    let mut recovered = 0;
+    let stored = if header_len == HEADER_LEN {
+        Some(parse_header(&header_buf)?)
+    } else {
+        None
+    };
+    if stored.is_some() {
+        let mut buffer = Scratch::new();
+        loop {
+            let len = match cursor::read_checked(&mut src, &mut buffer) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::Io(e)),
+            };
+            checksum.update(&buffer[..len]);
+            xor_bytes(&mut buffer[..len]);
+            cursor::write_all(&mut dst, &buffer[..len]).map_err(Error::Io)?;
+            recovered = cursor::add_len(recovered, len).map_err(Error::Io)?;
+        }
+    }
+    dst.flush().map_err(Error::Io)?;
+    Ok(SalvageReport {
+        recovered,
+        stored,
+        actual: checksum.finish(),
+    })
+}
+
+#[inline]
+/// Recovers the data of the damaged Redshirt 2 file at `src` into a new file at `dst`, as
+/// `salvage` does.
+///
+/// `dst` is created, or truncated if it exists, before `src` is read.
+///
+/// # Errors
+///
+/// Returns an `Err` if either file cannot be opened, or `salvage` fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::v2;
+///
+/// let report = v2::salvage_path("User.usr", "User.dat").unwrap();
+/// println!("recovered {} bytes", report.recovered);
+/// ```
+pub fn salvage_path<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+) -> Result<SalvageReport, Error> {
+    let src = File::open(src).map_err(Error::Io)?;
+    let dst = File::create(dst).map_err(Error::Io)?;
+    salvage(src, dst)
+}
+
+#[inline]
+/// Decodes an in-memory Redshirt 2 stream, verifying its SHA-1 hash.
+///
+/// # Errors
+///
+/// Returns an `Err` if any of the following occurs:
+///
+/// * `encoded` does not begin with a valid Redshirt 2 header;
+/// * The SHA-1 hash in the header is all zeroes, indicating that the stream was never finalised;
+/// * The SHA-1 hash in the header does not match that of the encoded data.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v2;
+///
+/// let encoded = v2::encode(b"foo");
+/// assert_eq!(v2::decode(&encoded).unwrap(), b"foo");
+/// ```
+pub fn decode(encoded: &[u8]) -> Result<Vec<u8>, Error> {
+    verified_payload(encoded).map(|(_, payload)| {
+        let mut decoded = payload.to_vec();
+        xor_payload(&mut decoded);
+        decoded
+    })
+}
+
+#[inline]
+#[must_use]
+/// Encodes `data` as an in-memory Redshirt 2 stream.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v2;
+///
+/// let encoded = v2::encode(b"foo");
+/// assert!(encoded.starts_with(b"REDSHRT2\x00"));
+/// ```
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(HEADER_LEN + data.len());
+    encoded.resize(HEADER_LEN, u8::default());
+    encoded.extend_from_slice(data);
+    encode_in_place(&mut encoded);
+    encoded
+}
+
+#[inline]
+/// Decodes a Redshirt 2 stream as UTF-8 text, verifying its SHA-1 hash.
+///
+/// # Errors
+///
+/// Returns an `Err` if any of the following occurs:
+///
+/// * An I/O error occurs;
+/// * The stream contains an invalid Redshirt 2 header;
+/// * The SHA-1 hash in the header is all zeroes, indicating that the stream was never finalised;
+/// * The SHA-1 hash in the header does not match that of the encoded data;
+/// * The decoded data is not valid UTF-8, in which case the error is `Error::InvalidText`.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v2;
+/// use std::io::Cursor;
+///
+/// let encoded = v2::encode_str(Cursor::new(Vec::new()), "caf\u{e9}").unwrap();
+/// let decoded = v2::decode_to_string(Cursor::new(encoded.into_inner())).unwrap();
+/// assert_eq!(decoded, "caf\u{e9}");
+/// ```
+pub fn decode_to_string<R: Read + Seek>(src: R) -> Result<String, Error> {
+    read_all(src).and_then(text::to_string)
+}
+
+#[inline]
+/// Decodes a Redshirt 2 stream as UTF-8 text, verifying its SHA-1 hash and replacing any invalid
+/// UTF-8 with `U+FFFD`.
+///
+/// # Errors
+///
+/// Returns an `Err` if any of the following occurs:
+///
+/// * An I/O error occurs;
+/// * The stream contains an invalid Redshirt 2 header;
+/// * The SHA-1 hash in the header is all zeroes, indicating that the stream was never finalised;
+/// * The SHA-1 hash in the header does not match that of the encoded data.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v2;
+/// use std::io::Cursor;
+///
+/// let encoded = v2::encode(b"caf\xe9");
+/// assert_eq!(v2::decode_to_string_lossy(Cursor::new(encoded)).unwrap(), "caf\u{fffd}");
+/// ```
+pub fn decode_to_string_lossy<R: Read + Seek>(src: R) -> Result<String, Error> {
+    read_all(src).map(text::to_string_lossy)
+}
+
+#[inline]
+/// Decodes a Redshirt 2 stream into memory, verifying its SHA-1 hash, unless the decoded data is
+/// longer than `max_len` bytes.
+///
+/// The length of the data is checked before any of it is hashed or decoded, and the limit is
+/// enforced again as data is read, in case the stream grows in the meantime. Memory for the
+/// decoded data is reserved with `Vec::try_reserve`, so a failure to allocate it is returned as an
+/// I/O error of kind `OutOfMemory` instead of aborting the process.
+///
+/// # Errors
+///
+/// Returns an `Err` if any of the following occurs:
+///
+/// * An I/O error occurs, or memory for the decoded data cannot be allocated;
+/// * The stream contains an invalid Redshirt 2 header;
+/// * The SHA-1 hash in the header is all zeroes, indicating that the stream was never finalised;
+/// * The SHA-1 hash in the header does not match that of the encoded data;
+/// * The decoded data is longer than `max_len`, in which case the error is
+///   `Error::PayloadTooLarge`.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::{v2, Error};
+/// use std::io::Cursor;
+///
+/// let encoded = v2::encode(b"foobar");
+/// let decoded = v2::decode_to_vec_limited(Cursor::new(&encoded), 6).unwrap();
+/// assert_eq!(decoded, b"foobar");
+/// assert!(matches!(
+///     v2::decode_to_vec_limited(Cursor::new(&encoded), 4),
+///     Err(Error::PayloadTooLarge { limit: 4, attempted: 6 })
+/// ));
+/// ```
+pub fn decode_to_vec_limited<R: Read + Seek>(src: R, max_len: u64) -> Result<Vec<u8>, Error> {
+    let mut reader = ReaderOptions::new()
+        .max_payload_len(max_len)
+        .from_reader(src)?;
+    let len = reader.seek(SeekFrom::End(0)).map_err(Error::Io)?;
+    let _ = reader.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
+    cursor::read_capped(reader, len, max_len).map_err(Error::from_io)
+}
+
+#[inline]
+/// Decodes the first `n` bytes of a Redshirt 2 stream, or all of them if there are fewer.
+///
+/// With `PrefixVerify::Skip`, only the header and the first `n` bytes of encoded data are read,
+/// so the cost does not depend on the length of the stream, but the prefix is unverified and may
+/// be corrupt. With `PrefixVerify::Full`, the rest of the stream is read and hashed after the
+/// prefix, as by `decode`. Either way, `Prefix::verified` records whether the hash was verified.
+///
+/// `src` does not need to support seeking.
+///
+/// # Errors
+///
+/// Returns an `Err` if any of the following occurs:
+///
+/// * An I/O error occurs;
+/// * The stream contains an invalid Redshirt 2 header;
+/// * The hash is verified, and the SHA-1 hash in the header is all zeroes, or does not match that
+///   of the encoded data.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v2::{self, PrefixVerify};
+///
+/// let encoded = v2::encode(b"foobar");
+/// let prefix = v2::decode_prefix(&encoded[..], 3, PrefixVerify::Skip).unwrap();
+/// assert_eq!(prefix.data, b"foo");
+/// assert!(!prefix.verified);
+/// let prefix = v2::decode_prefix(&encoded[..], 3, PrefixVerify::Full).unwrap();
+/// assert_eq!(prefix.data, b"foo");
+/// assert!(prefix.verified);
+/// ```
+pub fn decode_prefix<R: Read>(src: R, n: usize, verify: PrefixVerify) -> Result<Prefix, Error> {
+    let verified = verify == PrefixVerify::Full;
+    let mut reader = ReaderOptions::new()
+        .verification(if verified {
+            Verification::Lazy
+        } else {
+            Verification::Skip
+        })
+        .from_stream(src)?;
+    let mut data = Vec::new();
+    let _ = reader
+        .by_ref()
+        .take(cursor::len_u64(n))
+        .read_to_end(&mut data)
+        .map_err(Error::from_io)?;
+    if verified {
+        let _ = reader.skip_bytes(u64::MAX).map_err(Error::from_io)?;
+    }
+    Ok(Prefix { data, verified })
+}
+
+#[cfg(feature = "zeroize")]
+#[inline]
+/// Decodes a Redshirt 2 stream into a buffer that is overwritten with zeroes when dropped.
+///
+/// No copy of the decoded data is left in memory freed while decoding. See the
+/// [crate-level documentation](../index.html#scrubbing-decoded-data) for what is and isn't
+/// covered.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs, or `src` produces an invalid Redshirt 2 header, or the SHA-1 hash is invalid.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v2;
+/// use std::io::Cursor;
+///
+/// let decoded = v2::decode_to_vec_zeroizing(Cursor::new(v2::encode(b"hunter2"))).unwrap();
+/// assert_eq!(&decoded[..], b"hunter2");
+/// ```
+pub fn decode_to_vec_zeroizing<R: Read + Seek>(src: R) -> Result<Zeroizing<Vec<u8>>, Error> {
+    cursor::read_zeroizing(Reader::new(src)?).map_err(Error::Io)
+}
+
+#[inline]
+/// Writes `text` to `dst` as a Redshirt 2 stream, finalising its SHA-1 hash, and returns `dst`.
+///
+/// # Errors
+///
+/// Returns an `Err` if an I/O error occurs.
+///
+/// # Examples
+///
+/// ```
+/// use redshirt::v2;
+/// use std::io::Cursor;
+///
+/// let encoded = v2::encode_str(Cursor::new(Vec::new()), "foo").unwrap();
+/// assert_eq!(encoded.into_inner(), v2::encode(b"foo"));
+/// ```
+pub fn encode_str<W: Seek + Write>(dst: W, text: &str) -> Result<W, Error> {
+    let mut writer = Writer::new(dst)?;
+    writer.write_all(text.as_bytes()).map_err(Error::Io)?;
+    writer.into_inner()
+}
+
+fn read_all<R: Read + Seek>(src: R) -> Result<Vec<u8>, Error> {
+    let mut decoded = Vec::new();
+    let _ = Reader::new(src)?
+        .read_to_end(&mut decoded)
+        .map_err(Error::Io)?;
+    Ok(decoded)
+}
+
+#[inline]
+/// Opens the Redshirt 2 file at `path`, verifying its SHA-1 hash, and returns an iterator over the
+/// lines of its decoded data.
+///
+/// See `text::LineReader` for how lines are read. To read a file without verifying its hash, pass
+/// a `Reader` created with `ReaderOptions` to `text::LineReader::new` instead.
+///
+/// # Errors
+///
+/// Returns an `Err` if any of the following occurs:
+///
+/// * The file cannot be opened or read;
+/// * The file contains an invalid Redshirt 2 header;
+/// * The SHA-1 hash in the header is all zeroes, indicating that the file was never finalised;
+/// * The SHA-1 hash in the header does not match that of the encoded data.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::v2;
+///
+/// for line in v2::read_lines("User.usr").unwrap() {
+///     println!("{}", line.unwrap());
+/// }
+/// ```
+pub fn read_lines<P: AsRef<Path>>(path: P) -> Result<LineReader<Reader<File>>, Error> {
+    File::open(path)
+        .map_err(Error::Io)
+        .and_then(Reader::new)
+        .map(LineReader::new)
+}
+
+#[inline]
+fn read_header<R: Read>(src: &mut R) -> Result<Digest, Error> {
+    let mut header_buf = array!(HEADER_LEN);
+    cursor::read_exact(src, &mut header_buf)
+        .map_err(Error::Io)
+        .and_then(|()| parse_header(&header_buf))
+}
+
+/// Checks the header and SHA-1 hash of an in-memory Redshirt 2 stream, returning its hash and
+/// encoded data.
+fn verified_payload(encoded: &[u8]) -> Result<(Digest, &[u8]), Error> {
+    let header_buf = encoded
+        .get(..HEADER_LEN)
+        .and_then(|header| <&[u8; HEADER_LEN]>::try_from(header).ok())
+        .ok_or(Error::BadHeader)?;
+    let expected = parse_header(header_buf)?;
+    if expected.is_zero() {
+        return Err(Error::Unfinalized);
+    }
+    let payload = &encoded[HEADER_LEN..];
+    let mut checksum = ChecksumBuilder::new();
+    checksum.update(payload);
+    let actual = checksum.finish();
+    if actual == expected {
+        Ok((actual, payload))
+    } else {
+        Err(checksum_mismatch(expected, actual))
+    }
+}
+
+#[inline]
+/// Encodes an in-memory payload in place, writing the header into the first `HEADER_LEN` bytes of
+/// `encoded`, which must already be reserved.
+pub(crate) fn encode_in_place(encoded: &mut [u8]) {
+    let (header, payload) = encoded.split_at_mut(HEADER_LEN);
+    header[..MARKER_LEN].copy_from_slice(&MARKER);
+    xor_payload(payload);
+    let mut checksum = ChecksumBuilder::new();
+    checksum.update(payload);
+    header[MARKER_LEN..].copy_from_slice(checksum.finish().as_ref());
+}
+
+#[inline]
+/// Returns an `Error::BadChecksum`, emitting a `tracing` event if the feature is enabled.
+pub(crate) fn checksum_mismatch(expected: Digest, actual: Digest) -> Error {
+    #[cfg(feature = "tracing")]
+    trace_mismatch(expected, actual);
+    Error::BadChecksum { expected, actual }
+}
+
+#[cfg(feature = "tracing")]
+fn trace_mismatch(expected: Digest, actual: Digest) {
+    tracing::warn!(%expected, %actual, "SHA-1 hash mismatch");
+}
+
+#[inline]
+/// Fails with `Error::PayloadTooLarge` if `len` bytes of data exceed `limit`.
+pub(crate) fn check_payload_len(limit: u64, len: u64) -> Result<(), Error> {
+    if len > limit {
+        Err(Error::PayloadTooLarge {
+            limit,
+            attempted: len,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[inline]
+/// Returns how much of a `len`-byte read at `offset` in the decoded data to attempt without
+/// exceeding `limit`.
+///
+/// At the limit, a single byte is read to find out whether the data goes on, for `check_read` to
+/// reject. Past the limit, nothing is read.
+pub(crate) fn clamp_read(limit: u64, offset: u64, len: usize) -> io::Result<usize> {
+    match limit.checked_sub(offset) {
+        Some(0) => Ok(len.min(1)),
+        Some(left) => Ok(usize::try_from(left).map_or(len, |left| len.min(left))),
+        None => check_read(limit, offset, 0).map(|()| 0),
+    }
+}
+
+#[inline]
+/// Fails with an `io::Error` wrapping `Error::PayloadTooLarge` if `len` bytes read at `offset` in
+/// the decoded data end past `limit`.
+pub(crate) fn check_read(limit: u64, offset: u64, len: usize) -> io::Result<()> {
+    check_payload_len(limit, offset.saturating_add(cursor::len_u64(len))).map_err(io::Error::from)
+}
+
+#[inline]
+fn parse_header(header_buf: &[u8; HEADER_LEN]) -> Result<Digest, Error> {
+    if header_buf[..MARKER_LEN] == MARKER {
+        let mut digest = array!(DIGEST_LEN);
+        digest.copy_from_slice(&header_buf[MARKER_LEN..]);
+        Ok(Digest::from_bytes(digest))
+    } else {
+        Err(Error::BadHeader)
+    }
+}
+
+#[inline]
+pub(crate) fn hash_payload<R: Read>(src: &mut R) -> io::Result<Digest> {
+    hash_payload_cancellable(src, None)
+}
+
+#[inline]
+/// Hashes the rest of `src` as `hash_payload` does, failing with an `io::Error` wrapping
+/// `Error::Cancelled` before the next chunk is read once `cancel` has been cancelled.
+pub(crate) fn hash_payload_cancellable<R: Read>(
+    src: &mut R,
+    cancel: Option<&CancelToken>,
+) -> io::Result<Digest> {
+    hash_payload_counted(src, cancel).map(|(digest, _)| digest)
+}
+
+#[inline]
+/// Hashes the rest of `src` as `hash_payload_cancellable` does, also returning the number of bytes
+/// hashed.
+fn hash_payload_counted<R: Read>(
+    src: &mut R,
+    cancel: Option<&CancelToken>,
+) -> io::Result<(Digest, u64)> {
+    let mut buffer = Scratch::new();
+    let mut checksum = ChecksumBuilder::new();
+    let mut hashed = 0;
+    loop {
+        cancel::check_io(cancel)?;
+        match cursor::read_checked(src, &mut buffer) {
+            Ok(0) => break Ok((checksum.finish(), hashed)),
+            Ok(len) => {
+                checksum.update(&buffer[..len]);
+                hashed = cursor::add_len(hashed, len)?;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => break Err(e),
+        }
+    }
+}
+
+#[inline]
+/// Runs a verification that may hash a stream, recording its outcome if the `metrics` feature is
+/// enabled and `enabled` is `true`.
+///
+/// `f` sets the length of the encoded data once it has been hashed. A verification that succeeds
+/// without hashing anything, such as that of an accepted unfinalised stream, is not recorded.
+fn recorded<T, F>(enabled: bool, f: F) -> Result<T, Error>
+where
+    F: FnOnce(&mut Option<u64>) -> Result<T, Error>,
+{
+    #[cfg(feature = "metrics")]
+    let timer = enabled.then(crate::metrics::Verification::start);
+    #[cfg(not(feature = "metrics"))]
+    let _ = enabled;
+    let mut payload_len = None;
+    let res = f(&mut payload_len);
+    #[cfg(feature = "metrics")]
+    if let Some(timer) = timer.filter(|_| res.is_err() || payload_len.is_some()) {
+        timer.finish(&res, payload_len);
+    }
+    res
+}
+
+impl<W: Seek + Write> Writer<W> {
+    #[inline]
+    /// Wraps an existing output stream and writes a Redshirt 2 header that is valid, but contains
+    /// an invalid SHA-1 hash.
+    ///
+    /// The header is written at the current position of `dst`, which need not be its start, and
+    /// the hash is later written back into that header. Several streams can therefore be written
+    /// one after another into a larger file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::Writer;
+    /// use std::io::Cursor;
+    ///
+    /// let mut data = [u8::default(); 30];
+    /// let writer = Writer::new(Cursor::new(&mut data[..])).unwrap();
+    /// ```
+    pub fn new(dst: W) -> Result<Self, Error> {
+        WriterOptions::new().from_writer(dst)
+    }
+}
+
+impl<W: Seek + Write, const BUF: usize> Writer<W, BUF> {
+    #[inline]
+    /// Wraps an existing output stream and writes a Redshirt 2 header that is valid, but contains
+    /// an invalid SHA-1 hash, encoding data in an inline buffer of `BUF` bytes.
+    ///
+    /// Each call to `write` encodes and writes at most `BUF` bytes. As the buffer is part of the
+    /// `Writer`, a large `BUF` makes the `Writer` itself large. If `BUF` is `0`, this is the same
+    /// as `Writer::new`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Writer};
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut writer = Writer::<_, 4096>::with_inline_buffer(Cursor::new(Vec::new())).unwrap();
+    /// writer.write_all(b"foo").unwrap();
+    /// let inner = writer.into_inner().unwrap();
+    /// assert_eq!(inner.into_inner(), v2::encode(b"foo"));
+    /// ```
+    pub fn with_inline_buffer(dst: W) -> Result<Self, Error> {
+        WriterOptions::new().from_writer_inline(dst)
+    }
+
+    #[inline]
+    /// Writes out the SHA-1 hash of all previously encoded data, then unwraps the `Writer`.
+    ///
+    /// If a `Writer` is dropped without calling this method, the SHA-1 hash is written out, but the
+    /// destructor will panic if an error occurs. Calling this method ensures that any such errors
+    /// are safely handled.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if writing the SHA-1 hash fails, including if seeking back to the end of
+    /// the data afterwards fails. In the latter case, the hash has been written and the stream is
+    /// complete, but the underlying writer is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::Writer;
+    /// use std::io::Cursor;
+    ///
+    /// let mut data = [u8::default(); 30];
+    /// let writer = Writer::new(Cursor::new(&mut data[..])).unwrap();
+    /// let inner = writer.into_inner().unwrap();
+    /// ```
+    pub fn into_inner(mut self) -> Result<W, Error> {
+        self.write_digest("into_inner").map(Option::unwrap)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the number of bytes of data written so far, not counting the header.
+    ///
+    /// As a `Writer` cannot seek, this is always equal to `Writer::position`.
+    pub fn bytes_written(&self) -> u64 {
+        self.dst.as_ref().map_or(0, Cursor::transferred)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the SHA-1 hash of the encoded data written so far, which is the hash the header
+    /// would contain if the `Writer` were finalised now.
+    ///
+    /// This copies and finalises the hash state, so it is not free; the `Debug` output omits it
+    /// for this reason. Neither the header nor the hash that is eventually written is affected, so
+    /// this can be called any number of times, for example together with `Writer::bytes_written`
+    /// at record boundaries, so that a prefix of the encoded data can later be checked with a
+    /// `Verifier`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Verifier, Writer};
+    /// use std::{
+    ///     convert::TryFrom,
+    ///     io::{Cursor, Write},
+    /// };
+    ///
+    /// let mut writer = Writer::new(Cursor::new(Vec::new())).unwrap();
+    /// writer.write_all(b"foo").unwrap();
+    /// let (offset, partial) = (writer.bytes_written(), writer.digest_so_far());
+    /// writer.write_all(b"bar").unwrap();
+    /// let digest = writer.digest_so_far();
+    /// let encoded = writer.into_inner().unwrap().into_inner();
+    /// assert_eq!(v2::verify(&encoded[..]).unwrap(), digest);
+    /// let mut verifier = Verifier::new(partial);
+    /// verifier.update(&encoded[29..][..usize::try_from(offset).unwrap()]);
+    /// assert_eq!(verifier.finish().unwrap(), partial);
+    /// ```
+    pub fn digest_so_far(&self) -> Digest {
+        let mut checksum = self.checksum.clone();
+        if let Some(dst) = self.dst.as_ref() {
+            checksum.update(dst.pending());
+        }
+        checksum.finish()
+    }
+
+    #[inline]
+    /// Writes the SHA-1 hash of all data written so far into the header, after writing any data
+    /// held by `WriterOptions::coalesce_writes`.
+    ///
+    /// The output is then a valid Redshirt 2 stream up to this point, as if the `Writer` had been
+    /// created with `WriterOptions::checkpoint_on_flush` and flushed. Unlike `flush`, the
+    /// underlying writer is not flushed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if writing the held data or the SHA-1 hash fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Writer};
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut data = Vec::new();
+    /// let mut writer = Writer::new(Cursor::new(&mut data)).unwrap();
+    /// writer.set_finalize_on_drop(false);
+    /// writer.write_all(b"foo").unwrap();
+    /// writer.checkpoint().unwrap();
+    /// drop(writer);
+    /// assert_eq!(data, v2::encode(b"foo"));
+    /// ```
+    pub fn checkpoint(&mut self) -> Result<(), Error> {
+        self.write_checkpoint().map_err(Error::from_io)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the current position in the data written, not counting the header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::Writer;
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut writer = Writer::new(Cursor::new(Vec::new())).unwrap();
+    /// writer.write_all(b"foo").unwrap();
+    /// assert_eq!(writer.position(), 3);
+    /// assert_eq!(writer.bytes_written(), 3);
+    /// ```
+    pub fn position(&self) -> u64 {
+        self.dst.as_ref().map_or(0, Cursor::offset)
+    }
+
+    #[inline]
+    /// Sets whether the SHA-1 hash is written into the header when the `Writer` is dropped. This
+    /// is enabled by default.
+    ///
+    /// If this is disabled, dropping the `Writer` leaves the header as it was after the last
+    /// checkpoint, or with an all-zero SHA-1 hash if there was none, which is useful if the hash
+    /// will be written later by other means. `Writer::into_inner` always writes the hash.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{v2::Writer, Error};
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut data = Vec::new();
+    /// let mut writer = Writer::new(Cursor::new(&mut data)).unwrap();
+    /// writer.set_finalize_on_drop(false);
+    /// writer.write_all(b"foo").unwrap();
+    /// drop(writer);
+    /// assert!(matches!(
+    ///     redshirt::v2::decode(&data),
+    ///     Err(Error::Unfinalized)
+    /// ));
+    /// ```
+    pub fn set_finalize_on_drop(&mut self, enabled: bool) {
+        self.finalize_on_drop = enabled;
+    }
+
+    #[inline]
+    #[must_use]
+    /// Buffers the encoded data written by this `Writer` in memory, writing it to the underlying
+    /// writer in large chunks.
+    ///
+    /// This is the supported way to buffer a `Writer`'s output. The buffer is flushed before the
+    /// SHA-1 hash is written, and the hash is flushed before `into_inner` returns, so the data and
+    /// hash always reach the underlying writer in the right places.
+    ///
+    /// `into_inner` then returns a `BufWriter`, whose own `into_inner` method returns the original
+    /// writer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Writer};
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut writer = Writer::new(Cursor::new(Vec::new())).unwrap().buffered();
+    /// for byte in b"foo" {
+    ///     writer.write_all(&[*byte]).unwrap();
+    /// }
+    /// let inner = writer.into_inner().unwrap().into_inner().unwrap();
+    /// assert_eq!(inner.into_inner(), v2::encode(b"foo"));
+    /// ```
+    pub fn buffered(mut self) -> Writer<BufWriter<W>, BUF> {
+        if let (Some(dst), Some((reserved, set_len))) =
+            (self.dst.as_mut(), self.preallocated.take())
+        {
+            // The `BufWriter` cannot truncate the file when it is finalised, so release the space
+            // now. If this fails, the file keeps its length until the data reaches its end.
+            let _ = truncate_preallocated(dst.get_mut(), reserved, set_len);
+        }
+        let dst = self.dst.take().map(|dst| dst.map_inner(BufWriter::new));
+        Writer {
+            dst,
+            checksum: self.checksum.clone(),
+            options: self.options,
+            since_checkpoint: self.since_checkpoint,
+            checkpoint_pending: self.checkpoint_pending,
+            finalize_on_drop: self.finalize_on_drop,
+            preallocated: None,
+        }
+    }
+
+    #[inline]
+    /// Encodes everything read from `src` and writes it to the underlying writer, returning the
+    /// number of bytes read.
+    ///
+    /// Data is read from `src` directly into the buffer in which it is encoded and hashed, so this
+    /// copies it once less than `std::io::copy`, and should be preferred to it. Checkpoints are
+    /// written as by `write`. Interrupted reads and writes are retried. The SHA-1 hash is not
+    /// written; call `Writer::into_inner` once all data has been written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if reading from `src` or writing to the underlying writer fails, or
+    /// `Error::PayloadTooLarge` if `src` has more data than `WriterOptions::max_payload_len`
+    /// allows.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Writer};
+    /// use std::io::Cursor;
+    ///
+    /// let mut writer = Writer::new(Cursor::new(Vec::new())).unwrap();
+    /// assert_eq!(writer.write_reader(&mut &b"foo"[..]).unwrap(), 3);
+    /// let encoded = writer.into_inner().unwrap().into_inner();
+    /// assert_eq!(encoded, v2::encode(b"foo"));
+    /// ```
+    pub fn write_reader<R: Read + ?Sized>(&mut self, src: &mut R) -> Result<u64, Error> {
+        cursor::pump_with(|| self.pipe_chunk(src, true)).map_err(Error::from_io)
+    }
+
+    #[cfg(feature = "flate2")]
+    #[inline]
+    #[must_use]
+    /// Wraps this `Writer` in a `GzEncoder` that compresses data at the given `level` before it is
+    /// encoded.
+    ///
+    /// The SHA-1 hash in the header covers the compressed data as stored in the stream, not the
+    /// data written to the `GzEncoder`. The layers must be finished from the outside in: first call
+    /// `GzEncoder::finish`, which writes the end of the gzip member and returns this `Writer`, then
+    /// `Writer::into_inner`, which writes the hash of everything the encoder wrote. If the
+    /// `GzEncoder` is dropped instead, both layers are still finished in this order, unless
+    /// `Writer::set_finalize_on_drop` disabled it, but any error is lost.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{v2::Writer, Compression};
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut encoder = Writer::new(Cursor::new(Vec::new()))
+    ///     .unwrap()
+    ///     .with_gz(Compression::fast());
+    /// encoder.write_all(b"foo").unwrap();
+    /// let writer = encoder.finish().unwrap();
+    /// let encoded = writer.into_inner().unwrap().into_inner();
+    /// assert!(encoded.starts_with(b"REDSHRT2\x00"));
+    /// ```
+    pub fn with_gz(self, level: Compression) -> GzEncoder<Self> {
+        GzEncoder::new(self, level)
+    }
+
+    /// Reads, encodes if `encode` is set, and writes one chunk of `src` for `write_reader` or
+    /// `copy_raw`, returning its length.
+    fn pipe_chunk<R: Read + ?Sized>(&mut self, src: &mut R, encode: bool) -> io::Result<usize> {
+        if self.checkpoint_pending {
+            self.write_checkpoint()?;
+        }
+        self.write_pending()?;
+        let limit = self.chunk_limit().min(self.payload_limit(0)?);
+        if limit == 0 {
+            // Only the payload limit can be zero here; find out whether `src` has more data.
+            let mut byte = [u8::default()];
+            return loop {
+                match cursor::read_checked(src, &mut byte) {
+                    Ok(0) => break Ok(0),
+                    Ok(len) => break self.payload_limit(len),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(e) => break Err(e),
+                }
+            };
+        }
+        let len = {
+            let chunk = self.dst.as_mut().unwrap().write_from(src, limit, encode)?;
+            self.checksum.update(&chunk);
+            chunk.len()
+        };
+        self.wrote(len);
+        Ok(len)
+    }
+
+    /// Returns the most data that can be written before the next checkpoint is due.
+    fn chunk_limit(&self) -> usize {
+        self.options.checkpoint_every.map_or(usize::MAX, |every| {
+            usize::try_from(every - self.since_checkpoint).unwrap_or(usize::MAX)
+        })
+    }
+
+    /// Returns the most data that can be written without exceeding the payload limit, failing if
+    /// that is none but `wanted` bytes are to be written.
+    fn payload_limit(&self, wanted: usize) -> io::Result<usize> {
+        let limit = self.options.max_payload_len;
+        let written = self.bytes_written();
+        match limit.saturating_sub(written) {
+            0 if wanted > 0 => Err(io::Error::from(Error::PayloadTooLarge {
+                limit,
+                attempted: written.saturating_add(cursor::len_u64(wanted)),
+            })),
+            remaining => Ok(usize::try_from(remaining).unwrap_or(usize::MAX)),
+        }
+    }
+
+    /// Holds `buf` in the buffer to be written with later writes, returning whether it did, if
+    /// coalescing is enabled and `buf` is smaller than the buffer. Otherwise, or if the buffer
+    /// cannot fit `buf`, the data already held is written first.
+    fn coalesce(&mut self, buf: &[u8]) -> io::Result<bool> {
+        let dst = self.dst.as_mut().unwrap();
+        let small = self.options.coalesce_writes && buf.len() < dst.buffer_len();
+        if small && dst.coalesce(buf)? {
+            return Ok(true);
+        }
+        self.write_pending()?;
+        Ok(small && self.dst.as_mut().unwrap().coalesce(buf)?)
+    }
+
+    /// Writes the data held back by `coalesce`, hashing it as the underlying writer accepts it.
+    fn write_pending(&mut self) -> io::Result<()> {
+        let checksum = &mut self.checksum;
+        self.dst.as_mut().map_or(Ok(()), |dst| {
+            dst.write_pending(|written| checksum.update(written))
+        })
+    }
+
+    /// Records that `len` bytes of data were written, writing a checkpoint if one is due.
+    fn wrote(&mut self, len: usize) {
+        self.since_checkpoint += cursor::len_u64(len);
+        if matches!(self.options.checkpoint_every, Some(every) if self.since_checkpoint >= every) {
+            self.checkpoint_pending = true;
+            // The data has been written, so a failed checkpoint is retried by the next write.
+            let _ = self.write_checkpoint();
+        }
+    }
+
+    /// Writes the SHA-1 hash of all data written so far into the header, leaving the underlying
+    /// writer positioned at the end of the data.
+    fn write_checkpoint(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        let dst = self.dst.as_mut().unwrap();
+        let digest = self.checksum.clone().finish();
+        let offset = dst.offset();
+        write_header_digest(dst.get_mut(), offset, &digest)?;
+        self.since_checkpoint = 0;
+        self.checkpoint_pending = false;
+        Ok(())
+    }
+
+    #[inline]
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    /// Writes the SHA-1 hash into the header and unwraps the underlying writer, unless this was
+    /// already done. `trigger` names the caller, for `tracing` events.
+    fn write_digest(&mut self, trigger: &'static str) -> Result<Option<W>, Error> {
+        self.write_pending().map_err(Error::Io)?;
+        let Some(dst) = self.dst.take() else {
+            return Ok(None);
+        };
+        let offset = dst.offset();
+        let mut inner = dst.into_inner();
+        if let Some((reserved, set_len)) = self.preallocated.take() {
+            truncate_preallocated(&mut inner, reserved, set_len).map_err(Error::Io)?;
+        }
+        let digest = self.checksum.clone().finish();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            payload_len = offset,
+            %digest,
+            trigger,
+            "finalising Redshirt 2 stream",
+        );
+        write_header_digest(&mut inner, offset, &digest)
+            .map(|()| Some(inner))
+            .map_err(Error::Io)
+    }
+}
+
+#[cfg(feature = "flate2")]
+impl Writer<File> {
+    #[inline]
+    /// Creates the Redshirt 2 file at `path`, truncating it if it exists, and wraps it in a
+    /// `GzEncoder` that compresses data at the given `level` before it is encoded.
+    ///
+    /// See `Writer::with_gz` for what the SHA-1 hash covers, and how to finish the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the file cannot be created, or the header cannot be written.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::{v2::Writer, Compression};
+    /// use std::io::Write;
+    ///
+    /// let mut encoder = Writer::create_gz("User.usr.gz", Compression::default()).unwrap();
+    /// encoder.write_all(b"foo").unwrap();
+    /// let _ = encoder.finish().unwrap().into_inner().unwrap();
+    /// ```
+    pub fn create_gz<P: AsRef<Path>>(
+        path: P,
+        level: Compression,
+    ) -> Result<GzEncoder<Self>, Error> {
+        File::create(path)
+            .map_err(Error::Io)
+            .and_then(Self::new)
+            .map(|writer| writer.with_gz(level))
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl Writer<File> {
+    #[inline]
+    /// Creates the Redshirt 2 file at `path`, truncating it if it exists, sizes it to hold
+    /// `payload_len` bytes of data, and maps it into memory to encode data into.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the file cannot be created, resized or mapped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::Writer;
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::create_mmap("User.usr", 6).unwrap();
+    /// writer.write_all(b"foobar").unwrap();
+    /// let _ = writer.finish().unwrap();
+    /// ```
+    pub fn create_mmap<P: AsRef<Path>>(path: P, payload_len: u64) -> Result<MmapWriter, Error> {
+        let mut header = array!(HEADER_LEN);
+        header[..MARKER_LEN].copy_from_slice(&MARKER);
+        Mapping::create(path.as_ref(), &header, payload_len).map(|map| MmapWriter { map })
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl MmapWriter {
+    #[inline]
+    #[must_use]
+    /// Returns the number of bytes of data written so far, not counting the header.
+    pub fn position(&self) -> u64 {
+        self.map.position()
+    }
+
+    #[inline]
+    /// Hashes the mapped data and writes the SHA-1 hash into the header, then writes the mapping
+    /// back to the file and unmaps it, returning the file.
+    ///
+    /// If less data was written than the file was created to hold, the file is truncated to the
+    /// end of the data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing back the data or truncating the file fails.
+    pub fn finish(mut self) -> Result<File, Error> {
+        let (header, payload) = self.map.split_mut();
+        let mut checksum = ChecksumBuilder::new();
+        checksum.update(payload);
+        header[MARKER_LEN..].copy_from_slice(checksum.finish().as_ref());
+        self.map.finish().map_err(Error::Io)
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl Write for MmapWriter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        xor_bytes(self.map.append(buf)?);
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl BoxedWriter {
+    #[inline]
+    /// Boxes an output stream and creates a new writer from it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, BoxedWriter};
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut writer = BoxedWriter::from_boxed(Cursor::new(Vec::new())).unwrap();
+    /// writer.write_all(b"Hello world!").unwrap();
+    /// let _ = writer.into_inner().unwrap();
+    /// ```
+    pub fn from_boxed<W: Write + Seek + Send + 'static>(dst: W) -> Result<Self, Error> {
+        Self::new(Box::new(dst))
+    }
+}
+
+impl<W: Debug + Seek + Write, const BUF: usize> Debug for Writer<W, BUF> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Writer")
+            .field("dst", &self.dst)
+            .field("bytes_written", &self.bytes_written())
+            .field("digest", &format_args!("<pending>"))
+            .field("options", &self.options)
+            .field("finalize_on_drop", &self.finalize_on_drop)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Seek + Write, const BUF: usize> Write for Writer<W, BUF> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.checkpoint_pending {
+            self.write_checkpoint()?;
+        }
+        let limit = self.chunk_limit().min(self.payload_limit(buf.len())?);
+        let buf = &buf[..buf.len().min(limit)];
+        let len = if self.coalesce(buf)? {
+            buf.len()
+        } else {
+            let chunk = self.dst.as_mut().unwrap().write_chunk(buf)?;
+            self.checksum.update(&chunk);
+            chunk.len()
+        };
+        self.wrote(len);
+        Ok(len)
+    }
+
+    #[inline]
+    /// Flushes the underlying writer, after writing any data held by
+    /// `WriterOptions::coalesce_writes`.
+    ///
+    /// If the `Writer` was created with `WriterOptions::checkpoint_on_flush`, the SHA-1 hash of all
+    /// data written so far is first written into the header.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.options.checkpoint_on_flush {
+            self.write_checkpoint()
+        } else {
+            self.write_pending()?;
+            self.dst.as_mut().unwrap().flush()
+        }
+    }
+}
+
+impl<W: Seek + Write, const BUF: usize> Drop for Writer<W, BUF> {
+    #[inline]
+    /// When a `Writer` is dropped, this causes the SHA-1 hash of all previously encoded data to be
+    /// written into the header.
+    ///
+    /// In general, you should use `Writer::into_inner` instead of relying on implict `drop` calls.
+    ///
+    /// # Panics
+    ///
+    /// Panics if writing the SHA-1 hash fails for any reason. To catch these errors, use
+    /// `Writer::into_inner` instead of relying on implicit `drop` calls.
+    ///
+    /// Does nothing if `Writer::set_finalize_on_drop` was used to disable this.
+    fn drop(&mut self) {
+        if !self.finalize_on_drop {
+            return;
+        }
+        let _ = self.write_digest("drop").unwrap();
+    }
+}
+
+impl<W: Write> PrecomputedWriter<W> {
+    #[inline]
+    /// Wraps an existing output stream and writes a Redshirt 2 header containing `digest`, the
+    /// SHA-1 hash of the encoded data that will be written.
+    ///
+    /// If `verify` is `true`, the encoded data is hashed as it is written, and `finish` checks the
+    /// result against `digest`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the header fails, or `digest` is all zeroes, which would mark
+    /// the stream as unfinalised.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, PrecomputedWriter};
+    /// use std::io::Write;
+    ///
+    /// let encoded = v2::encode(b"foo");
+    /// let digest = v2::verify(&encoded[..]).unwrap();
+    /// let mut writer = PrecomputedWriter::new(Vec::new(), digest, true).unwrap();
+    /// writer.write_all(b"foo").unwrap();
+    /// assert_eq!(writer.finish().unwrap(), encoded);
+    /// ```
+    pub fn new(mut dst: W, digest: Digest, verify: bool) -> Result<Self, Error> {
+        if digest.is_zero() {
+            return Err(Error::Unfinalized);
+        }
+        let mut header = array!(HEADER_LEN);
+        header[..MARKER_LEN].copy_from_slice(&MARKER);
+        header[MARKER_LEN..].copy_from_slice(digest.as_ref());
+        cursor::write_all(&mut dst, &header).map_err(Error::Io)?;
+        Ok(Self {
+            dst,
+            expected: digest,
+            checksum: verify.then(ChecksumBuilder::new),
+            staging: Staging::new(BUFFER_LEN),
+        })
+    }
+
+    #[inline]
+    /// Unwraps a `PrecomputedWriter`, returning its underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if verification is enabled, and the SHA-1 hash of the encoded data does not
+    /// match the one written to the header. The data has already been written by this point, so
+    /// the output should be discarded.
+    pub fn finish(self) -> Result<W, Error> {
+        match self.checksum.map(ChecksumBuilder::finish) {
+            Some(actual) if actual != self.expected => {
+                Err(checksum_mismatch(self.expected, actual))
+            }
+            _ => Ok(self.dst),
+        }
+    }
+}
+
+impl<W: Write> Write for PrecomputedWriter<W> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let encoded = self.staging.encode(buf);
+        let len = cursor::write_checked(&mut self.dst, &encoded)?;
+        if let Some(checksum) = self.checksum.as_mut() {
+            checksum.update(&encoded[..len]);
+        }
+        Ok(len)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.dst.flush()
+    }
+}
+
+impl<W: Seek + Write> SyncWriter<W> {
+    #[inline]
+    #[must_use]
+    /// Wraps a `Writer` so that it can be shared between threads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, SyncWriter, Writer};
+    /// use std::{
+    ///     io::{Cursor, Write},
+    ///     thread,
+    /// };
+    ///
+    /// let writer = SyncWriter::new(Writer::new(Cursor::new(Vec::new())).unwrap());
+    /// thread::scope(|scope| {
+    ///     for _ in 0..2 {
+    ///         let _ = scope.spawn(|| (&writer).write_all(b"foo").unwrap());
+    ///     }
+    /// });
+    /// let encoded = writer.finish().unwrap().into_inner();
+    /// assert_eq!(v2::decode(&encoded).unwrap(), b"foofoo");
+    /// ```
+    pub fn new(writer: Writer<W>) -> Self {
+        Self {
+            inner: Mutex::new(writer),
+        }
+    }
+
+    #[inline]
+    /// Writes the SHA-1 hash of all data written so far into the header, as by
+    /// `Writer::checkpoint`.
+    ///
+    /// Writes from other threads wait until the checkpoint has been written, so the output is a
+    /// valid Redshirt 2 stream containing every record written before this call.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if writing the SHA-1 hash fails.
+    pub fn checkpoint(&self) -> Result<(), Error> {
+        self.lock().checkpoint()
+    }
+
+    #[inline]
+    /// Writes out the SHA-1 hash of all data written, then unwraps the underlying writer, as by
+    /// `Writer::into_inner`.
+    ///
+    /// This takes the `SyncWriter` by value, so every thread must have finished with it first.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if writing the SHA-1 hash fails.
+    pub fn finish(self) -> Result<W, Error> {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .into_inner()
+    }
+
+    /// Locks the `Writer`, recovering it if a thread panicked while holding the lock.
+    fn lock(&self) -> MutexGuard<'_, Writer<W>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<W: Seek + Write> Write for &SyncWriter<W> {
+    #[inline]
+    /// Writes all of `buf` while holding the lock, so that it is never interleaved with data from
+    /// another thread.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let mut writer = self.lock();
+        let mut len = 0;
+        for buf in bufs {
+            writer.write_all(buf)?;
+            len += buf.len();
+        }
+        Ok(len)
+    }
+
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.lock().write_all(buf)
+    }
+
+    #[inline]
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> io::Result<()> {
+        self.lock().write_fmt(args)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+}
+
+impl<R: Read> HashingReader<R> {
+    #[inline]
+    /// Creates a new `HashingReader` from a stream of encoded data.
+    ///
+    /// `src` should be positioned immediately after the Redshirt header.
+    ///
+    /// # Examples
+    ///
+    /// Decoding a Redshirt 1 stream while computing its Redshirt 2 hash:
+    ///
+    /// ```
+    /// use redshirt::{v1, v2::{self, HashingReader}};
+    /// use std::io::Read;
+    ///
+    /// let encoded = v1::encode(b"foo");
+    /// let mut reader = HashingReader::new(&encoded[9..]);
+    /// let mut decoded = Vec::new();
+    /// let _ = reader.read_to_end(&mut decoded).unwrap();
+    /// let (_, digest) = reader.finish().unwrap();
+    /// assert_eq!(decoded, b"foo");
+    /// assert_eq!(v2::verify(&v2::encode(b"foo")[..]).unwrap(), digest);
+    /// ```
+    pub fn new(src: R) -> Self {
+        Self {
+            src: Cursor::new(src),
+            checksum: ChecksumBuilder::new(),
+            valid: true,
+        }
+    }
+}
+
+impl<R> HashingReader<R> {
+    #[inline]
+    #[must_use]
+    /// Returns whether the hash still covers every encoded byte read so far, which is no longer
+    /// the case once the reader has been seeked away from the end of the bytes hashed.
+    pub const fn is_hash_valid(&self) -> bool {
+        self.valid
+    }
+
+    #[inline]
+    /// Unwraps a `HashingReader`, returning its underlying reader and discarding the hash.
+    ///
+    /// Unlike `finish`, this succeeds even if the hash was invalidated by seeking.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{v1, v2::HashingReader};
+    /// use std::io::{Cursor, Seek, SeekFrom};
+    ///
+    /// let encoded = v1::encode(b"foo");
+    /// let mut reader = HashingReader::new(Cursor::new(&encoded[9..]));
+    /// let _ = reader.seek(SeekFrom::Start(2)).unwrap();
+    /// assert!(!reader.is_hash_valid());
+    /// assert_eq!(reader.into_inner().position(), 2);
+    /// ```
+    pub fn into_inner(self) -> R {
+        self.src.into_inner()
+    }
+
+    #[inline]
+    /// Unwraps a `HashingReader`, returning its underlying reader and the SHA-1 hash of the
+    /// encoded bytes read so far.
+    ///
+    /// To hash the entire stream, read it to the end before calling this method.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the hash was invalidated by seeking, in which case the underlying reader
+    /// is dropped. To keep it, check `is_hash_valid` first, and call `into_inner` instead if the
+    /// hash is no longer valid.
+    pub fn finish(self) -> Result<(R, Digest), Error> {
+        if self.valid {
+            Ok((self.src.into_inner(), self.checksum.finish()))
+        } else {
+            Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "SHA-1 hash invalidated by seeking",
+            )))
+        }
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.src.read_encoded(buf)?;
+        if self.valid {
+            self.checksum.update(&buf[..len]);
+        }
+        xor_bytes(&mut buf[..len]);
+        Ok(len)
+    }
+}
+
+impl<R: Seek> Seek for HashingReader<R> {
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let hashed = self.src.offset();
+        self.src.seek(pos).inspect(|&offset| {
+            if offset != hashed {
+                self.valid = false;
+            }
+        })
+    }
+}
+
+impl Verifier {
+    #[inline]
+    #[must_use]
+    /// Creates a new `Verifier` that checks encoded data against `expected`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, Verifier};
+    ///
+    /// let encoded = v2::encode(b"Hello world!");
+    /// let expected = v2::verify(&encoded[..]).unwrap();
+    /// let mut verifier = Verifier::new(expected);
+    /// for chunk in encoded[29..].chunks(5) {
+    ///     verifier.update(chunk);
+    /// }
+    /// assert_eq!(verifier.finish().unwrap(), expected);
+    /// ```
+    pub fn new(expected: Digest) -> Self {
+        Self {
+            expected,
+            checksum: ChecksumBuilder::new(),
+        }
+    }
+
+    #[inline]
+    /// Adds the next piece of encoded data to the hash.
+    pub fn update(&mut self, encoded: &[u8]) {
+        self.checksum.update(encoded);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[inline]
+    /// Adds all of the encoded data remaining in `encoded` to the hash, one chunk at a time,
+    /// consuming it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bytes::{Buf, Bytes};
+    /// use redshirt::v2::{self, Verifier};
+    ///
+    /// let encoded = Bytes::from(v2::encode(b"Hello world!"));
+    /// let expected = v2::verify(&encoded[..]).unwrap();
+    /// let mut verifier = Verifier::new(expected);
+    /// let mut payload = encoded.slice(29..35).chain(encoded.slice(35..));
+    /// verifier.update_buf(&mut payload);
+    /// assert!(!payload.has_remaining());
+    /// assert_eq!(verifier.finish().unwrap(), expected);
+    /// ```
+    pub fn update_buf<B: Buf + ?Sized>(&mut self, encoded: &mut B) {
+        while encoded.has_remaining() {
+            let chunk = encoded.chunk();
+            let len = chunk.len();
+            self.checksum.update(chunk);
+            encoded.advance(len);
+        }
+    }
+
+    #[inline]
+    /// Consumes this `Verifier`, returning the SHA-1 hash of the encoded data if it matches the
+    /// expected hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Unfinalized` if the expected hash is all zeroes, or `Error::BadChecksum` if
+    /// the hash of the encoded data does not match it.
+    pub fn finish(self) -> Result<Digest, Error> {
+        if self.expected.is_zero() {
+            return Err(Error::Unfinalized);
+        }
+        let actual = self.checksum.finish();
+        if actual == self.expected {
+            Ok(actual)
+        } else {
+            Err(checksum_mismatch(self.expected, actual))
+        }
+    }
+}
+
+impl<A: Write, B: Write> MultiWriter<A, B> {
+    #[inline]
+    /// Wraps two existing output streams and writes a Redshirt 2 header that is valid, but contains
+    /// an invalid SHA-1 hash, to each.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing either header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::v2::{self, MultiWriter};
+    /// use std::io::{Cursor, Write};
+    ///
+    /// let mut writer = MultiWriter::new(Cursor::new(Vec::new()), Cursor::new(Vec::new())).unwrap();
+    /// writer.write_all(b"foo").unwrap();
+    /// let (primary, secondary, digest) = writer.finish().unwrap();
+    /// assert_eq!(primary.get_ref(), secondary.get_ref());
+    /// assert_eq!(v2::verify(&primary.get_ref()[..]).unwrap(), digest);
+    /// ```
+    pub fn new(primary: A, secondary: B) -> Result<Self, SinkError> {
+        let mut dummy_header = array!(HEADER_LEN);
+        dummy_header[..MARKER_LEN].copy_from_slice(&MARKER);
+        Tee::new(primary, secondary, &dummy_header).map(|tee| Self {
+            tee,
+            offset: 0,
+            checksum: ChecksumBuilder::new(),
+            staging: Staging::new(BUFFER_LEN),
+        })
+    }
+}
+
+impl<A: Seek + Write, B: Seek + Write> MultiWriter<A, B> {
+    #[inline]
+    /// Writes the SHA-1 hash of all previously encoded data into both headers, then unwraps the
+    /// `MultiWriter`, returning its underlying writers and the hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing either hash fails, or a previous write failed. The hash is
+    /// written to the primary stream first, and to the secondary stream only if that succeeds,
+    /// including seeking the primary stream back to the end of the data.
+    pub fn finish(self) -> Result<(A, B, Digest), SinkError> {
+        self.tee.check()?;
+        let digest = self.checksum.finish();
+        let (mut primary, mut secondary) = self.tee.into_inner();
+        write_header_digest(&mut primary, self.offset, &digest)
+            .map_err(|e| SinkError::new(Sink::Primary, e))?;
+        write_header_digest(&mut secondary, self.offset, &digest)
+            .map_err(|e| SinkError::new(Sink::Secondary, e))?;
+        Ok((primary, secondary, digest))
+    }
+}
+
+impl<A, B> MultiWriter<A, B> {
+    #[inline]
+    #[must_use]
+    /// Returns the output stream on which a write failed, if any.
+    pub const fn failed(&self) -> Option<Sink> {
+        self.tee.failed()
+    }
+}
+
+impl<A: Write, B: Write> Write for MultiWriter<A, B> {
+    #[inline]
+    /// Encodes a chunk of `buf` and writes it to both output streams.
+    ///
+    /// If writing to either stream fails, the returned `io::Error` wraps a `SinkError`.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let encoded = self.staging.encode(buf);
+        self.tee.write_all(&encoded).map_err(io::Error::from)?;
+        self.checksum.update(&encoded);
+        self.offset = cursor::add_len(self.offset, encoded.len())?;
+        Ok(encoded.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.tee.flush().map_err(io::Error::from)
+    }
+}
+
+/// Truncates a file extended to `reserved` bytes by `WriterOptions::preallocate` back to its
+/// current position, which is the end of the data written to it.
+fn truncate_preallocated<W: Seek + Write>(
+    dst: &mut W,
+    reserved: u64,
+    set_len: SetLen<W>,
+) -> io::Result<()> {
+    dst.flush()?;
+    let end = dst.stream_position()?;
+    if end < reserved {
+        set_len(dst, end)
+    } else {
+        Ok(())
+    }
+}
+
+/// Writes `digest` into the header of a Redshirt 2 stream whose `len` bytes of encoded data end
+/// at the current position of `dst`, leaving `dst` at that position.
+///
+/// `dst` is flushed before seeking and after writing the hash, so that buffered data is never
+/// written at the wrong position, even if seeking `dst` does not flush it.
+///
+/// Once `dst` has been positioned at the hash, seeking back to the end of the data is attempted
+/// even if writing the hash fails. If only that seek fails, the hash has been written, so the
+/// stream is complete, but the position of `dst` is unspecified.
+fn write_header_digest<W: Seek + Write>(dst: &mut W, len: u64, digest: &Digest) -> io::Result<()> {
+    dst.flush()?;
+    let end = dst.stream_position()?;
+    let start = len
+        .checked_add(cursor::len_u64(DIGEST_LEN))
+        .and_then(|n| end.checked_sub(n))
+        .ok_or_else(overflow_error)?;
+    let _ = dst.seek(SeekFrom::Start(start))?;
+    let written = cursor::write_all(dst, digest.as_ref()).and_then(|()| dst.flush());
+    let restored = dst.seek(SeekFrom::Start(end));
+    written?;
+    restored.map(|_| ())
+}
 
 impl ChecksumBuilder {
     pub(self) fn new() -> Self {
-        Self(Context::new(&SHA1))
+        Self(Sha1::new())
+    }
+
+    pub(self) fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub(self) fn finish(self) -> Digest {
+        let mut out = self.0.finish();
+        for chunk in out.chunks_exact_mut(mem::size_of::<u32>()) {
+            chunk.reverse();
+        }
+        Digest::from_bytes(out)
+    }
+}
+
+impl Debug for ChecksumBuilder {
+    /// Formats the hash as pending, as computing it would mean copying and finalising the state.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Checksum")
+            .field(&format_args!("<pending>"))
+            .finish()
+    }
+}
+
+impl Default for ChecksumBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LazyVerifier {
+    #[inline]
+    pub(self) fn new(expected: Option<Digest>) -> Self {
+        Self {
+            expected,
+            checksum: ChecksumBuilder::new(),
+            hashed: 0,
+            actual: None,
+            skipped: false,
+        }
+    }
+
+    #[inline]
+    /// Records the result of hashing all of the encoded data by other means.
+    pub(self) fn complete(&mut self, expected: Digest, actual: Digest) {
+        self.expected = Some(expected);
+        self.actual = Some(actual);
+        self.skipped = false;
+    }
+
+    #[inline]
+    /// Returns the offset up to which the encoded data has been hashed, or `None` if the hash has
+    /// already been computed.
+    pub(self) fn hashed(&self) -> Option<u64> {
+        self.actual.map_or(Some(self.hashed), |_| None)
+    }
+
+    #[inline]
+    pub(self) fn check(&self) -> io::Result<()> {
+        match (self.expected, self.actual) {
+            (Some(expected), Some(actual)) if actual != expected => {
+                Err(io::Error::from(Error::BadChecksum { expected, actual }))
+            }
+            (Some(_), None) if self.skipped => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the SHA-1 hash cannot be verified, as encoded data was seeked past without being \
+                 read",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    #[inline]
+    /// Hashes the encoded bytes read from `offset`, and checks the hash if the end of the stream
+    /// was reached.
+    ///
+    /// Only bytes that extend the contiguous run of hashed data starting at offset zero are
+    /// hashed, so bytes that are read more than once (e.g. after seeking backwards) are hashed
+    /// exactly once. If the end of the stream is reached after seeking past bytes that were never
+    /// hashed, the hash cannot be checked, and an error saying so is returned from then on.
+    pub(self) fn update(&mut self, offset: u64, encoded: &[u8], eof: bool) -> io::Result<()> {
+        if self.actual.is_none() {
+            let end = cursor::add_len(offset, encoded.len())?;
+            if offset <= self.hashed && self.hashed < end {
+                let start = usize::try_from(self.hashed - offset).unwrap();
+                self.checksum.update(&encoded[start..]);
+                self.hashed = end;
+            } else if eof && offset > self.hashed {
+                self.skipped = true;
+            } else if eof && offset == self.hashed {
+                let actual = mem::take(&mut self.checksum).finish();
+                #[cfg(feature = "tracing")]
+                if let Some(expected) = self.expected.filter(|&e| e != actual) {
+                    trace_mismatch(expected, actual);
+                }
+                self.actual = Some(actual);
+            }
+        }
+        self.check()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        copy_verified, decode, decode_prefix, decode_to_string, decode_to_string_lossy,
+        decode_to_vec_limited, encode, encode_str, read_lines, salvage, salvage_path, verify,
+        CopyOptions, HashingReader, MultiWriter, PrecomputedWriter, PrefixVerify, Reader,
+        ReaderOptions, SyncWriter, Verification, Verifier, Writer, WriterOptions, HEADER_LEN,
+        MARKER_LEN,
+    };
+    use crate::{
+        cursor::{Overclaim, Trickle, BUFFER_LEN},
+        digest::{Digest, DIGEST_LEN},
+        error::{Error, Sink, SinkError},
+    };
+    use std::{
+        convert::TryFrom,
+        env,
+        fs::{self, File},
+        io::{self, Cursor, Read, Seek, SeekFrom, Write},
+        process, thread,
+    };
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+    const MSG_ENC: &[u8] = b"REDSHRT2\x00\x34\x54\x26\x2B\x4A\xBF\x29\x1D\x0B\x8E\x60\xD9\xA1\x76\xE1\x14\x7D\xDF\x05\xD4\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+    const MSG_UNFINALIZED: &[u8] = b"REDSHRT2\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+    const MSG_LEN: usize = 12;
+    const MSG_LEN_U64: u64 = MSG_LEN as u64;
+    const MSG_LEN_I64: i64 = 12;
+
+    #[test]
+    fn reader_read() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        let mut buffer = array!(MSG_LEN);
+        let (left, right) = buffer.split_at_mut(MSG_LEN / 2);
+        assert_eq!(
+            reader.seek(SeekFrom::Current(MSG_LEN_I64 / 2)).unwrap(),
+            MSG_LEN_U64 / 2
+        );
+        reader.read_exact(right).unwrap();
+        assert_eq!(reader.seek(SeekFrom::Current(-MSG_LEN_I64)).unwrap(), 0);
+        reader.read_exact(left).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+    }
+
+    #[test]
+    fn reader_seek_start() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+        assert_eq!(
+            reader.seek(SeekFrom::Start(MSG_LEN_U64)).unwrap(),
+            MSG_LEN_U64
+        );
+        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+        assert_eq!(
+            reader.seek(SeekFrom::Start(MSG_LEN_U64 / 2)).unwrap(),
+            MSG_LEN_U64 / 2
+        );
+        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+    }
+
+    #[test]
+    fn reader_seek_current() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        assert_eq!(reader.stream_position().unwrap(), 0);
+        assert_eq!(
+            reader.seek(SeekFrom::Current(MSG_LEN_I64)).unwrap(),
+            MSG_LEN_U64
+        );
+        assert_eq!(reader.seek(SeekFrom::Current(-MSG_LEN_I64)).unwrap(), 0);
+        assert_eq!(
+            reader.seek(SeekFrom::Current(MSG_LEN_I64 / 2)).unwrap(),
+            MSG_LEN_U64 / 2
+        );
+        assert_eq!(
+            reader.seek(SeekFrom::Current(-(MSG_LEN_I64 / 2))).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn reader_seek_end() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        assert_eq!(reader.seek(SeekFrom::End(-MSG_LEN_I64)).unwrap(), 0);
+        assert_eq!(reader.seek(SeekFrom::End(0)).unwrap(), MSG_LEN_U64);
+        assert_eq!(reader.seek(SeekFrom::End(-MSG_LEN_I64)).unwrap(), 0);
+        assert_eq!(
+            reader.seek(SeekFrom::End(-MSG_LEN_I64 / 2)).unwrap(),
+            MSG_LEN_U64 / 2
+        );
+        assert_eq!(reader.seek(SeekFrom::End(-MSG_LEN_I64)).unwrap(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid seek")]
+    fn reader_seek_positive_overflow() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        let _ = reader.seek(SeekFrom::Start(u64::MAX)).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid seek")]
+    fn reader_seek_negative_overflow() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        let _ = reader.seek(SeekFrom::Current(-1)).unwrap();
+    }
+
+    #[test]
+    fn reader_seek_negative_clamped() {
+        let mut reader = ReaderOptions::new()
+            .clamp_seeks(true)
+            .from_reader(Cursor::new(MSG_ENC))
+            .unwrap();
+        assert_eq!(reader.seek(SeekFrom::Current(-1)).unwrap(), 0);
+        assert_eq!(reader.seek(SeekFrom::Start(5)).unwrap(), 5);
+        assert_eq!(reader.seek(SeekFrom::Current(-2)).unwrap(), 3);
+        assert_eq!(reader.seek(SeekFrom::Current(i64::MIN)).unwrap(), 0);
+        assert_eq!(reader.seek(SeekFrom::End(-4)).unwrap(), MSG_LEN_U64 - 4);
+        assert_eq!(reader.seek(SeekFrom::End(-100)).unwrap(), 0);
+        assert_eq!(reader.position(), 0);
+        assert_eq!(reader.get_ref().position(), HEADER_LEN as u64);
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+        assert!(reader.seek(SeekFrom::Start(u64::MAX)).is_err());
+        assert_eq!(reader.position(), MSG_LEN_U64);
+    }
+
+    #[test]
+    fn writer_write() {
+        let mut buffer = array!(HEADER_LEN + MSG_LEN);
+        {
+            let mut writer = Writer::new(Cursor::new(&mut buffer[..])).unwrap();
+            writer.write_all(MSG_DEC).unwrap();
+            let _ = writer.into_inner().unwrap();
+        }
+        assert_eq!(&buffer[..], MSG_ENC);
+    }
+
+    #[test]
+    fn writer_write_long_at_offset() {
+        let data = vec![0x5A; BUFFER_LEN + MSG_LEN];
+        let mut dst = Cursor::new(b"prefix".to_vec());
+        let _ = dst.seek(SeekFrom::End(0)).unwrap();
+        let mut writer = Writer::new(dst).unwrap();
+        writer.write_all(&data).unwrap();
+        let dst = writer.into_inner().unwrap();
+        assert_eq!(dst.position(), dst.get_ref().len() as u64);
+        let encoded = dst.into_inner();
+        assert_eq!(&encoded[..6], b"prefix");
+        assert_eq!(&encoded[6..], &encode(&data)[..]);
+    }
+
+    #[test]
+    fn reader_counters() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        assert_eq!((reader.position(), reader.bytes_read()), (0, 0));
+        let mut buffer = array!(MSG_LEN);
+        reader.read_exact(&mut buffer[..4]).unwrap();
+        assert_eq!(reader.seek(SeekFrom::Current(-3)).unwrap(), 1);
+        assert_eq!((reader.position(), reader.bytes_read()), (1, 4));
+        let _ = reader.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!((reader.position(), reader.bytes_read()), (MSG_LEN_U64, 15));
+        assert!(format!("{reader:?}").contains("transferred: 15"));
+    }
+
+    #[test]
+    fn writer_back_to_back() {
+        let mut dst = Cursor::new(b"pack".to_vec());
+        dst.set_position(4);
+        for msg in [MSG_DEC, b"foobar"] {
+            let mut writer = Writer::new(&mut dst).unwrap();
+            writer.write_all(msg).unwrap();
+            let _ = writer.into_inner().unwrap();
+        }
+        let pack = dst.into_inner();
+        let first = 4 + HEADER_LEN + MSG_LEN;
+        assert_eq!(&pack[..4], b"pack");
+        assert_eq!(&pack[4..first], MSG_ENC);
+        assert_eq!(&pack[first..], &encode(b"foobar")[..]);
+        assert_eq!(decode(&pack[4..first]).unwrap(), MSG_DEC);
+        let mut src = Cursor::new(&pack[..]);
+        src.set_position(first as u64);
+        let mut buffer = Vec::new();
+        let _ = Reader::new(src).unwrap().read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, b"foobar");
+    }
+
+    #[test]
+    fn reader_from_raw() {
+        let payload = &MSG_ENC[HEADER_LEN..];
+        let digest = verify(MSG_ENC).unwrap();
+        let mut reader = Reader::from_raw(Cursor::new(payload), Some(digest)).unwrap();
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+        assert_eq!(reader.digest_computed(), None);
+        let mut src = Cursor::new(MSG_ENC);
+        src.set_position(HEADER_LEN as u64);
+        let mut reader = Reader::from_raw(src, None).unwrap();
+        assert_eq!(reader.seek(SeekFrom::End(-6)).unwrap(), 6);
+        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+        buffer.clear();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+        assert_eq!(reader.digest_computed(), Some(digest));
+        let mut bad = digest.to_bytes();
+        bad[0] ^= 1;
+        assert!(matches!(
+            Reader::from_raw(Cursor::new(payload), Some(Digest::from_bytes(bad))),
+            Err(Error::BadChecksum { expected, actual }) if actual == digest && expected != digest
+        ));
+    }
+
+    #[test]
+    fn reader_from_raw_stream() {
+        let payload = &MSG_ENC[HEADER_LEN..];
+        let digest = verify(MSG_ENC).unwrap();
+        let mut reader = Reader::from_raw_stream(Trickle::new(payload), Some(digest));
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+        assert_eq!(reader.digest_computed(), Some(digest));
+        let mut bad = digest.to_bytes();
+        bad[0] ^= 1;
+        let mut reader = Reader::from_raw_stream(payload, Some(Digest::from_bytes(bad)));
+        let e = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(
+            e.get_ref().and_then(|inner| inner.downcast_ref::<Error>()),
+            Some(Error::BadChecksum { .. })
+        ));
+    }
+
+    #[test]
+    fn writer_max_payload_len() {
+        let too_large = |e: &io::Error| match e.get_ref().and_then(|e| e.downcast_ref::<Error>()) {
+            Some(&Error::PayloadTooLarge { limit, attempted }) => (limit, attempted),
+            _ => panic!("expected `Error::PayloadTooLarge`, got {:?}", e),
+        };
+        for coalesce in [false, true] {
+            let mut writer = WriterOptions::new()
+                .max_payload_len(4)
+                .coalesce_writes(coalesce)
+                .from_writer(Cursor::new(Vec::new()))
+                .unwrap();
+            assert_eq!(writer.write(b"foo").unwrap(), 3);
+            assert_eq!(writer.write(b"bar").unwrap(), 1);
+            assert_eq!(too_large(&writer.write(b"ar").unwrap_err()), (4, 6));
+            assert_eq!(writer.write(b"").unwrap(), 0);
+            assert_eq!(writer.bytes_written(), 4);
+            let encoded = writer.into_inner().unwrap().into_inner();
+            assert_eq!(encoded, encode(b"foob"));
+        }
+        let mut writer = WriterOptions::new()
+            .max_payload_len(MSG_LEN_U64)
+            .from_writer(Cursor::new(Vec::new()))
+            .unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        assert_eq!(writer.into_inner().unwrap().into_inner(), MSG_ENC);
+        let mut writer = WriterOptions::new()
+            .max_payload_len(0)
+            .from_writer(Cursor::new(Vec::new()))
+            .unwrap();
+        assert_eq!(
+            too_large(&writer.write(MSG_DEC).unwrap_err()),
+            (0, MSG_LEN_U64)
+        );
+        assert_eq!(writer.into_inner().unwrap().into_inner(), encode(b""));
+    }
+
+    #[test]
+    fn reader_max_payload_len() {
+        for verification in [Verification::Eager, Verification::Lazy, Verification::Skip] {
+            let mut options = ReaderOptions::new();
+            let _ = options.verification(verification).max_payload_len(4);
+            assert!(matches!(
+                options.from_reader(Cursor::new(MSG_ENC)),
+                Err(Error::PayloadTooLarge {
+                    limit: 4,
+                    attempted: MSG_LEN_U64
+                })
+            ));
+            let _ = options.max_payload_len(MSG_LEN_U64);
+            let mut decoded = Vec::new();
+            let mut reader = options.from_reader(Cursor::new(MSG_ENC)).unwrap();
+            assert_eq!(reader.read_to_end(&mut decoded).unwrap(), MSG_LEN);
+            assert_eq!(decoded, MSG_DEC);
+        }
+
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let encoded = encode(&data);
+        let mut reader = ReaderOptions::new()
+            .max_payload_len(BUFFER_LEN as u64)
+            .from_stream(Trickle::new(&encoded[..]))
+            .unwrap();
+        let mut decoded = Vec::new();
+        let e = reader.read_to_end(&mut decoded).unwrap_err();
+        assert!(matches!(
+            e.get_ref().and_then(|e| e.downcast_ref::<Error>()),
+            Some(&Error::PayloadTooLarge { limit, attempted })
+                if limit == BUFFER_LEN as u64 && attempted == limit + 1
+        ));
+        assert_eq!(decoded, data[..BUFFER_LEN]);
+        assert!(reader.read(&mut [0; 4]).is_err());
+
+        let mut reader = ReaderOptions::new()
+            .max_payload_len(MSG_LEN_U64)
+            .from_stream(Trickle::new(MSG_ENC))
+            .unwrap();
+        let mut decoded = Vec::new();
+        assert_eq!(reader.read_to_end(&mut decoded).unwrap(), MSG_LEN);
+        assert_eq!(decoded, MSG_DEC);
+    }
+
+    #[cfg(all(feature = "mmap", unix))]
+    #[test]
+    fn mmap_writer() {
+        let path = env::temp_dir().join(format!("redshirt-v2-mmap-{}", process::id()));
+        let mut writer = Writer::create_mmap(&path, MSG_LEN_U64).unwrap();
+        writer.write_all(&MSG_DEC[..5]).unwrap();
+        writer.write_all(&MSG_DEC[5..]).unwrap();
+        assert_eq!(writer.position(), MSG_LEN_U64);
+        let e = writer.write(b"!").unwrap_err();
+        assert!(matches!(
+            Error::from_io(e),
+            Error::PayloadTooLarge {
+                limit: MSG_LEN_U64,
+                attempted: 13
+            }
+        ));
+        let _ = writer.finish().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), MSG_ENC);
+
+        let mut writer = Writer::create_mmap(&path, MSG_LEN_U64).unwrap();
+        writer.write_all(b"Hello").unwrap();
+        assert!(writer.write_all(b" there!!").is_err());
+        let _ = writer.finish().unwrap();
+        assert_eq!(decode(&fs::read(&path).unwrap()).unwrap(), b"Hello");
+
+        let mut writer = Writer::create_mmap(&path, MSG_LEN_U64).unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        drop(writer);
+        let encoded = fs::read(&path).unwrap();
+        assert_eq!(encoded, MSG_UNFINALIZED);
+        assert!(matches!(decode(&encoded), Err(Error::Unfinalized)));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn gz_round_trip() {
+        let path = env::temp_dir().join(format!("redshirt-v2-gz-{}", process::id()));
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let mut encoder = Writer::create_gz(&path, flate2::Compression::best()).unwrap();
+        encoder.write_all(&data).unwrap();
+        let _ = encoder.finish().unwrap().into_inner().unwrap();
+        assert!(fs::metadata(&path).unwrap().len() < data.len() as u64);
+        let stored = decode(&fs::read(&path).unwrap()).unwrap();
+        assert!(stored.starts_with(b"\x1f\x8b"));
+        let mut decoded = Vec::new();
+        let _ = Reader::open_gz(&path)
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn gz_external() {
+        // Produced by `printf 'Hello world!' | gzip -9n`.
+        const GZ_MEMBER: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xf3\x48\xcd\xc9\xc9\x57\x28\xcf\x2f\xca\x49\x51\x04\x00\x95\x19\x85\x1b\x0c\x00\x00\x00";
+        let encoded = encode(GZ_MEMBER);
+        let mut decoded = Vec::new();
+        let _ = Reader::from_slice(&encoded)
+            .unwrap()
+            .with_gz()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, MSG_DEC);
+        let encoded = encode(&GZ_MEMBER[..GZ_MEMBER.len() - 4]);
+        assert!(Reader::from_slice(&encoded)
+            .unwrap()
+            .with_gz()
+            .read_to_end(&mut Vec::new())
+            .is_err());
+    }
+
+    #[test]
+    fn decode_limited() {
+        assert_eq!(
+            decode_to_vec_limited(Cursor::new(MSG_ENC), MSG_LEN_U64).unwrap(),
+            MSG_DEC
+        );
+        assert!(matches!(
+            decode_to_vec_limited(Cursor::new(MSG_ENC), MSG_LEN_U64 - 1),
+            Err(Error::PayloadTooLarge {
+                limit,
+                attempted: MSG_LEN_U64,
+            }) if limit == MSG_LEN_U64 - 1
+        ));
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let encoded = encode(&data);
+        assert_eq!(
+            decode_to_vec_limited(Trickle::new(Cursor::new(&encoded)), u64::MAX).unwrap(),
+            data
+        );
+        let mut corrupt = encoded;
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            decode_to_vec_limited(Cursor::new(corrupt), u64::MAX),
+            Err(Error::BadChecksum { .. })
+        ));
+    }
+
+    #[test]
+    fn decode_prefix_skip() {
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let mut encoded = encode(&data);
+        *encoded.last_mut().unwrap() ^= 1;
+        let mut src = &encoded[..];
+        let prefix = decode_prefix(&mut src, 5, PrefixVerify::Skip).unwrap();
+        assert_eq!(prefix.data, data[..5]);
+        assert!(!prefix.verified);
+        assert_eq!(src.len(), encoded.len() - HEADER_LEN - 5);
+        let prefix = decode_prefix(MSG_UNFINALIZED, MSG_LEN + 1, PrefixVerify::Skip).unwrap();
+        assert_eq!(prefix.data, MSG_DEC);
+        assert!(!prefix.verified);
+        assert!(matches!(
+            decode_prefix(&MSG_ENC[1..], 5, PrefixVerify::Skip),
+            Err(Error::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn decode_prefix_full() {
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let mut encoded = encode(&data);
+        for n in [0, 5, data.len(), data.len() + 1] {
+            let prefix = decode_prefix(Trickle::new(&encoded[..]), n, PrefixVerify::Full).unwrap();
+            assert_eq!(prefix.data, data[..n.min(data.len())]);
+            assert!(prefix.verified);
+        }
+        *encoded.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            decode_prefix(&encoded[..], 5, PrefixVerify::Full),
+            Err(Error::BadChecksum { .. })
+        ));
+        assert!(matches!(
+            decode_prefix(MSG_UNFINALIZED, 5, PrefixVerify::Full),
+            Err(Error::Unfinalized)
+        ));
+    }
+
+    #[test]
+    fn writer_max_payload_len_write_reader() {
+        let mut options = WriterOptions::new();
+        let _ = options.max_payload_len(4);
+        let mut writer = options.from_writer(Cursor::new(Vec::new())).unwrap();
+        assert_eq!(
+            writer
+                .write_reader(&mut Trickle::new(&b"foob"[..]))
+                .unwrap(),
+            4
+        );
+        assert_eq!(writer.into_inner().unwrap().into_inner(), encode(b"foob"));
+        let mut writer = options.from_writer(Cursor::new(Vec::new())).unwrap();
+        let mut src = &b"foobar"[..];
+        assert!(matches!(
+            writer.write_reader(&mut src),
+            Err(Error::PayloadTooLarge {
+                limit: 4,
+                attempted: 5
+            })
+        ));
+        assert_eq!(src, b"r");
+        assert_eq!(writer.into_inner().unwrap().into_inner(), encode(b"foob"));
+    }
+
+    #[test]
+    fn writer_digest_so_far() {
+        let mut writer = Writer::new(Trickle::new(Cursor::new(Vec::new()))).unwrap();
+        let mut checkpoints = Vec::new();
+        for chunk in MSG_DEC.chunks(5) {
+            writer.write_all(chunk).unwrap();
+            let digest = writer.digest_so_far();
+            assert_eq!(writer.digest_so_far(), digest);
+            checkpoints.push((writer.bytes_written(), digest));
+        }
+        let dst = writer.into_inner().unwrap().into_inner().into_inner();
+        assert_eq!(dst, MSG_ENC);
+        assert_eq!(
+            checkpoints.last(),
+            Some(&(MSG_LEN_U64, verify(MSG_ENC).unwrap()))
+        );
+        for (offset, digest) in checkpoints {
+            let end = HEADER_LEN + usize::try_from(offset).unwrap();
+            let mut verifier = Verifier::new(digest);
+            verifier.update(&MSG_ENC[HEADER_LEN..end]);
+            assert_eq!(verifier.finish().unwrap(), digest);
+        }
+    }
+
+    #[test]
+    fn reader_reverify() {
+        let mut options = ReaderOptions::new();
+        let _ = options.verification(Verification::Lazy);
+        let mut reader = options.from_reader(Cursor::new(MSG_ENC.to_vec())).unwrap();
+        let mut buffer = array!(MSG_LEN);
+        reader.read_exact(&mut buffer[..4]).unwrap();
+        assert!(reader.reverify().unwrap());
+        assert_eq!(reader.position(), 4);
+        reader.get_mut().get_mut()[HEADER_LEN + 6] ^= 1;
+        assert!(!reader.reverify().unwrap());
+        assert_eq!(reader.position(), 4);
+        let e = reader.read(&mut buffer[4..]).unwrap_err();
+        assert!(matches!(
+            e.get_ref().and_then(|inner| inner.downcast_ref::<Error>()),
+            Some(Error::BadChecksum { .. })
+        ));
+        reader.get_mut().get_mut()[0] ^= 1;
+        assert!(matches!(reader.reverify(), Err(Error::BadHeader)));
+        assert_eq!(reader.get_ref().position(), HEADER_LEN as u64 + 4);
+        let mut reader = Reader::from_raw(Cursor::new(&MSG_ENC[HEADER_LEN..]), None).unwrap();
+        assert!(matches!(reader.reverify(), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn reader_reverify_rewritten() {
+        let path = env::temp_dir().join(format!(
+            "redshirt-v2-{}-reader_reverify_rewritten",
+            process::id()
+        ));
+        fs::write(&path, MSG_ENC).unwrap();
+        let mut reader = Reader::new(File::open(&path).unwrap()).unwrap();
+        let mut buffer = array!(MSG_LEN);
+        reader.read_exact(&mut buffer[..6]).unwrap();
+        fs::write(&path, encode(b"Hello there!")).unwrap();
+        assert!(!reader.reverify().unwrap());
+        assert!(reader.reverify().unwrap());
+        reader.read_exact(&mut buffer[6..]).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(&buffer, b"Hello there!");
+    }
+
+    #[test]
+    fn reader_resync() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        let mut buffer = array!(MSG_LEN);
+        reader.read_exact(&mut buffer[..4]).unwrap();
+        reader.get_mut().set_position(HEADER_LEN as u64 + 2);
+        reader.resync().unwrap();
+        assert_eq!(reader.position(), 2);
+        reader.read_exact(&mut buffer[..4]).unwrap();
+        assert_eq!(&buffer[..4], &MSG_DEC[2..6]);
+        reader.get_mut().set_position(0);
+        assert_eq!(
+            reader.resync().unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        let mut reader = ReaderOptions::new()
+            .from_stream(Cursor::new(MSG_ENC))
+            .unwrap();
+        assert_eq!(reader.resync().unwrap_err().kind(), io::ErrorKind::Other);
+        assert_eq!(reader.stream_position().unwrap(), 0);
+        reader.get_mut().set_position(HEADER_LEN as u64 + 6);
+        reader.resync().unwrap();
+        assert_eq!(reader.position(), 6);
+    }
+
+    #[test]
+    fn writer_counters() {
+        let mut writer = Writer::new(Trickle::new(Cursor::new(Vec::new()))).unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        assert_eq!(
+            (writer.position(), writer.bytes_written()),
+            (MSG_LEN_U64, MSG_LEN_U64)
+        );
+        assert!(format!("{writer:?}").contains("transferred: 12"));
+    }
+
+    #[test]
+    fn reader_unfinalized() {
+        match Reader::new(Cursor::new(MSG_UNFINALIZED)) {
+            Err(Error::Unfinalized) => (),
+            other => panic!("expected `Error::Unfinalized`, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reader_accept_unfinalized() {
+        let mut reader = ReaderOptions::new()
+            .accept_unfinalized(true)
+            .from_reader(Cursor::new(MSG_UNFINALIZED))
+            .unwrap();
+        let mut buffer = array!(MSG_LEN);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+    }
+
+    #[test]
+    fn reader_bad_checksum() {
+        let mut data = MSG_ENC.to_vec();
+        data[MARKER_LEN] ^= 1;
+        match Reader::new(Cursor::new(&data[..])) {
+            Err(Error::BadChecksum { .. }) => (),
+            other => panic!("expected `Error::BadChecksum`, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reader_tolerant() {
+        let (mut reader, report) = Reader::new_tolerant(Cursor::new(MSG_ENC)).unwrap();
+        assert!(report.is_match());
+        assert_eq!(reader.checksum_report(), Some(report));
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+
+        let mut data = MSG_ENC.to_vec();
+        data[HEADER_LEN + 11] ^= 1;
+        let (mut reader, report) = Reader::new_tolerant(Cursor::new(&data[..])).unwrap();
+        assert!(!report.is_match());
+        assert_eq!(report.expected, verify(MSG_ENC).unwrap());
+        assert!(matches!(
+            report.into_result(),
+            Err(Error::BadChecksum { expected, actual })
+                if expected == report.expected && actual == report.actual
+        ));
+        buffer.clear();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer[..11], MSG_DEC[..11]);
+        assert_ne!(buffer, MSG_DEC);
+        assert_eq!(reader.checksum_report(), Some(report));
+        assert_eq!(
+            Reader::new(Cursor::new(MSG_ENC)).unwrap().checksum_report(),
+            None
+        );
+
+        let mut unfinalized = MSG_ENC.to_vec();
+        unfinalized[MARKER_LEN..HEADER_LEN].fill(0);
+        let (_, report) = Reader::new_tolerant(Cursor::new(unfinalized)).unwrap();
+        assert!(report.expected.is_zero() && !report.is_match());
+
+        let mut bad_marker = MSG_ENC.to_vec();
+        bad_marker[0] ^= 1;
+        assert!(matches!(
+            Reader::new_tolerant(Cursor::new(bad_marker)),
+            Err(Error::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn verify_ok() {
+        let digest = verify(MSG_ENC).unwrap();
+        assert_eq!(digest.as_ref(), &MSG_ENC[MARKER_LEN..HEADER_LEN]);
+    }
+
+    #[test]
+    fn verify_unfinalized() {
+        match verify(MSG_UNFINALIZED) {
+            Err(Error::Unfinalized) => (),
+            other => panic!("expected `Error::Unfinalized`, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn copy_verified_ok() {
+        let data = vec![0x42; BUFFER_LEN * 2 + 1];
+        let encoded = encode(&data);
+        let mut copy = Vec::new();
+        let digest = copy_verified(&encoded[..], &mut copy).unwrap();
+        assert_eq!(copy, encoded);
+        assert_eq!(digest.as_ref(), &encoded[MARKER_LEN..HEADER_LEN]);
+        copy.clear();
+        let buffered = CopyOptions::new()
+            .buffered(true)
+            .copy(&encoded[..], &mut copy)
+            .unwrap();
+        assert_eq!(buffered, digest);
+        assert_eq!(copy, encoded);
+    }
+
+    #[test]
+    fn copy_verified_bad_checksum() {
+        let mut data = MSG_ENC.to_vec();
+        data[HEADER_LEN] ^= 1;
+        let mut copy = Vec::new();
+        assert!(matches!(
+            copy_verified(&data[..], &mut copy),
+            Err(Error::BadChecksum { .. })
+        ));
+        assert_eq!(copy, data);
+        copy.clear();
+        assert!(matches!(
+            CopyOptions::new().buffered(true).copy(&data[..], &mut copy),
+            Err(Error::BadChecksum { .. })
+        ));
+        assert!(copy.is_empty());
+        assert!(matches!(
+            copy_verified(MSG_UNFINALIZED, &mut copy),
+            Err(Error::Unfinalized)
+        ));
+        assert!(matches!(
+            copy_verified(&[0; HEADER_LEN][..], &mut copy),
+            Err(Error::BadHeader)
+        ));
+        assert!(copy.is_empty());
+    }
+
+    #[test]
+    fn multi_writer_finish() {
+        let data = vec![0x42; BUFFER_LEN * 2 + 1];
+        let mut secondary = Cursor::new(b"prefix".to_vec());
+        secondary.set_position(6);
+        let mut writer = MultiWriter::new(Cursor::new(Vec::new()), secondary).unwrap();
+        writer.write_all(&data).unwrap();
+        let (primary, secondary, digest) = writer.finish().unwrap();
+        let primary = primary.into_inner();
+        let secondary = secondary.into_inner();
+        assert_eq!(primary, encode(&data));
+        assert_eq!(&secondary[..6], b"prefix");
+        assert_eq!(&secondary[6..], &primary[..]);
+        assert_eq!(verify(&primary[..]).unwrap(), digest);
+        assert_eq!(verify(&secondary[6..]).unwrap(), digest);
+    }
+
+    #[test]
+    fn multi_writer_sink_error() {
+        let mut buffer = [u8::default(); HEADER_LEN + 4];
+        let mut writer =
+            MultiWriter::new(Cursor::new(&mut buffer[..]), Cursor::new(Vec::new())).unwrap();
+        let e = writer.write_all(MSG_DEC).unwrap_err();
+        let e = e.get_ref().unwrap().downcast_ref::<SinkError>().unwrap();
+        assert_eq!(e.sink(), Sink::Primary);
+        assert_eq!(writer.failed(), Some(Sink::Primary));
+        assert_eq!(writer.finish().unwrap_err().sink(), Sink::Primary);
+    }
+
+    #[test]
+    fn hashing_reader_read() {
+        let data = vec![0x42; BUFFER_LEN * 2 + 1];
+        let encoded = encode(&data);
+        let mut reader = HashingReader::new(Cursor::new(&encoded[HEADER_LEN..]));
+        assert_eq!(reader.stream_position().unwrap(), 0);
+        let mut decoded = Vec::new();
+        let _ = reader.read_to_end(&mut decoded).unwrap();
+        let (_, digest) = reader.finish().unwrap();
+        assert_eq!(decoded, data);
+        assert_eq!(digest.as_ref(), &encoded[MARKER_LEN..HEADER_LEN]);
+    }
+
+    #[test]
+    fn hashing_reader_seek_invalidates() {
+        let mut reader = HashingReader::new(Cursor::new(&MSG_ENC[HEADER_LEN..]));
+        let mut buffer = array!(MSG_LEN);
+        assert_eq!(reader.seek(SeekFrom::Start(2)).unwrap(), 2);
+        reader.read_exact(&mut buffer[2..]).unwrap();
+        assert_eq!(&buffer[2..], &MSG_DEC[2..]);
+        assert!(!reader.is_hash_valid());
+        assert!(matches!(reader.finish(), Err(Error::Io(_))));
+
+        let mut reader = HashingReader::new(Cursor::new(&MSG_ENC[HEADER_LEN..]));
+        reader.read_exact(&mut buffer[..2]).unwrap();
+        assert_eq!(reader.stream_position().unwrap(), 2);
+        assert!(reader.is_hash_valid());
+        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+        assert!(!reader.is_hash_valid());
+        assert_eq!(reader.into_inner().position(), 0);
+    }
+
+    #[test]
+    fn reader_skip_bytes_lazy() {
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let encoded = encode(&data);
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(Trickle::new(&encoded[..]))
+            .unwrap();
+        assert_eq!(
+            reader.skip_bytes(BUFFER_LEN as u64 + 1).unwrap(),
+            BUFFER_LEN as u64 + 1
+        );
+        let mut byte = [0];
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], data[BUFFER_LEN + 1]);
+        assert_eq!(reader.skip_bytes(u64::MAX).unwrap(), BUFFER_LEN as u64 + 3);
+        assert_eq!(reader.read_to_end(&mut Vec::new()).unwrap(), 0);
+
+        let mut corrupt = encoded;
+        *corrupt.last_mut().unwrap() ^= 1;
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(&corrupt[..])
+            .unwrap();
+        let e = reader.skip_bytes(u64::MAX).unwrap_err();
+        assert!(matches!(Error::from_io(e), Error::BadChecksum { .. }));
+    }
+
+    #[test]
+    fn reader_peek_lazy() {
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN + 5).collect();
+        let encoded = encode(&data);
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(Trickle::new(&encoded[..]))
+            .unwrap();
+        let mut peeked = vec![0; BUFFER_LEN * 2];
+        assert_eq!(reader.peek(&mut peeked[..7]).unwrap(), 7);
+        assert_eq!(&peeked[..7], &data[..7]);
+        let mut buffer = array!(3);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, &data[..3]);
+        assert_eq!(reader.peek(&mut peeked).unwrap(), data.len() - 3);
+        assert_eq!(&peeked[..data.len() - 3], &data[3..]);
+        assert_eq!(reader.digest_computed(), None);
+        let mut rest = Vec::new();
+        let _ = reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &data[3..]);
+        assert_eq!(
+            reader.digest_computed(),
+            Some(verify(&encoded[..]).unwrap())
+        );
+
+        let mut corrupt = encoded;
+        *corrupt.last_mut().unwrap() ^= 1;
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(&corrupt[..])
+            .unwrap();
+        assert_eq!(reader.peek(&mut peeked).unwrap(), data.len());
+        let e = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(Error::from_io(e), Error::BadChecksum { .. }));
+        let e = reader.peek(&mut peeked).unwrap_err();
+        assert!(matches!(Error::from_io(e), Error::BadChecksum { .. }));
+    }
+
+    #[test]
+    fn reader_peek_seek_lazy() {
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_reader(Cursor::new(MSG_ENC))
+            .unwrap();
+        let mut buffer = array!(MSG_LEN);
+        assert_eq!(reader.peek(&mut buffer[..8]).unwrap(), 8);
+        assert_eq!(reader.seek(SeekFrom::Current(2)).unwrap(), 2);
+        reader.read_exact(&mut buffer[..4]).unwrap();
+        assert_eq!(&buffer[..4], &MSG_DEC[2..6]);
+        assert_eq!(reader.peek(&mut buffer[..4]).unwrap(), 4);
+        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+        assert_eq!(reader.read(&mut buffer).unwrap(), 0);
+        assert_eq!(reader.digest_computed(), Some(verify(MSG_ENC).unwrap()));
+    }
+
+    #[test]
+    fn reader_peek_limit() {
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .max_payload_len(4)
+            .from_stream(MSG_ENC)
+            .unwrap();
+        let mut buffer = array!(MSG_LEN);
+        assert_eq!(reader.peek(&mut buffer).unwrap(), 4);
+        assert_eq!(&buffer[..4], &MSG_DEC[..4]);
+        reader.read_exact(&mut buffer[..4]).unwrap();
+        assert_eq!(reader.peek(&mut buffer).unwrap(), 0);
+        let e = reader.read(&mut buffer).unwrap_err();
+        assert!(matches!(
+            Error::from_io(e),
+            Error::PayloadTooLarge { limit: 4, .. }
+        ));
     }
 
-    pub(self) fn update(&mut self, data: &[u8]) {
-        self.0.update(data);
+    #[test]
+    fn copy_raw() {
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let encoded = encode(&data);
+        let mut src = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(Trickle::new(&encoded[..]))
+            .unwrap();
+        let mut buffer = array!(4);
+        src.read_exact(&mut buffer).unwrap();
+        assert_eq!(src.peek(&mut buffer).unwrap(), 4);
+        let mut dst = WriterOptions::new()
+            .checkpoint_every(BUFFER_LEN as u64 / 3)
+            .from_writer(Cursor::new(Vec::new()))
+            .unwrap();
+        dst.write_all(b"foo").unwrap();
+        assert_eq!(
+            super::copy_raw(&mut src, &mut dst).unwrap(),
+            data.len() as u64 - 4
+        );
+        assert_eq!(src.digest_computed(), Some(verify(&encoded[..]).unwrap()));
+        let copied = dst.into_inner().unwrap().into_inner();
+        let mut expected = b"foo".to_vec();
+        expected.extend_from_slice(&data[4..]);
+        assert_eq!(copied, encode(&expected));
+
+        let mut corrupt = encoded;
+        *corrupt.last_mut().unwrap() ^= 1;
+        let mut src = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(&corrupt[..])
+            .unwrap();
+        let mut dst = Writer::new(Cursor::new(Vec::new())).unwrap();
+        assert!(matches!(
+            super::copy_raw(&mut src, &mut dst),
+            Err(Error::BadChecksum { .. })
+        ));
+        assert_eq!(dst.position(), data.len() as u64);
+
+        let mut src = Reader::new(Cursor::new(MSG_ENC)).unwrap();
+        let mut dst = WriterOptions::new()
+            .max_payload_len(4)
+            .from_writer(Cursor::new(Vec::new()))
+            .unwrap();
+        assert!(matches!(
+            super::copy_raw(&mut src, &mut dst),
+            Err(Error::PayloadTooLarge { limit: 4, .. })
+        ));
+        assert_eq!(src.position(), 5);
     }
 
-    pub(self) fn finish(self) -> [u8; SHA1_OUTPUT_LEN] {
-        let digest = self.0.finish();
-        let mut out = array!(SHA1_OUTPUT_LEN);
-        out.copy_from_slice(digest.as_ref());
-        for chunk in out.chunks_exact_mut(mem::size_of::<u32>()) {
-            chunk.reverse();
+    #[test]
+    fn salvage_damaged() {
+        let expected = verify(MSG_ENC).unwrap();
+        let mut decoded = Vec::new();
+        let report = salvage(Trickle::new(MSG_ENC), &mut decoded).unwrap();
+        assert_eq!(decoded, MSG_DEC);
+        assert_eq!(report.recovered, MSG_LEN as u64);
+        assert_eq!(report.stored, Some(expected));
+        assert_eq!(report.actual, expected);
+        assert!(!report.is_mismatch() && !report.is_unfinalized());
+
+        decoded.clear();
+        let report = salvage(&MSG_ENC[..HEADER_LEN + 5], &mut decoded).unwrap();
+        assert_eq!(decoded, &MSG_DEC[..5]);
+        assert_eq!(report.actual, verify(&encode(&MSG_DEC[..5])[..]).unwrap());
+        assert!(report.is_mismatch() && !report.is_unfinalized());
+
+        decoded.clear();
+        let report = salvage(MSG_UNFINALIZED, &mut decoded).unwrap();
+        assert_eq!(decoded, MSG_DEC);
+        assert_eq!(report.actual, expected);
+        assert!(report.is_unfinalized() && !report.is_mismatch());
+
+        for len in [0, 4, MARKER_LEN, HEADER_LEN - 1, HEADER_LEN] {
+            decoded.clear();
+            let report = salvage(&MSG_ENC[..len], &mut decoded).unwrap();
+            assert!(decoded.is_empty());
+            assert_eq!(report.recovered, 0);
+            assert_eq!(report.stored.is_some(), len == HEADER_LEN);
+            assert_eq!(report.actual, verify(&encode(b"")[..]).unwrap());
         }
-        out
+        assert!(matches!(
+            salvage(&b"REDSHRT1"[..], &mut decoded),
+            Err(Error::BadHeader)
+        ));
+        let mut full = [0; 4];
+        assert!(matches!(
+            salvage(MSG_ENC, &mut full[..]),
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::WriteZero
+        ));
     }
-}
 
-impl Debug for ChecksumBuilder {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let digest = self.0.clone().finish();
-        f.debug_tuple("Checksum").field(&digest).finish()
+    #[test]
+    fn salvage_files() {
+        let src = env::temp_dir().join(format!("redshirt-v2-salvage-src-{}", process::id()));
+        let dst = env::temp_dir().join(format!("redshirt-v2-salvage-dst-{}", process::id()));
+        fs::write(&src, &MSG_ENC[..MSG_ENC.len() - 1]).unwrap();
+        let report = salvage_path(&src, &dst).unwrap();
+        assert_eq!(fs::read(&dst).unwrap(), &MSG_DEC[..MSG_LEN - 1]);
+        assert_eq!(report.recovered, MSG_LEN as u64 - 1);
+        assert!(report.is_mismatch());
+        fs::remove_file(src).unwrap();
+        fs::remove_file(dst).unwrap();
     }
-}
 
-impl Default for ChecksumBuilder {
-    fn default() -> Self {
-        Self::new()
+    #[test]
+    fn reader_pins_base() {
+        let mut data = b"junk".to_vec();
+        data.extend_from_slice(MSG_ENC);
+        let mut src = Cursor::new(data);
+        src.set_position(4);
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_reader(src)
+            .unwrap();
+        reader.src.get_mut().set_position(0);
+        assert_eq!(reader.seek(SeekFrom::Start(6)).unwrap(), 6);
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, &MSG_DEC[6..]);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::{Reader, Writer, HEADER_LEN};
-    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+    #[test]
+    fn reader_try_clone() {
+        let path = env::temp_dir().join(format!("redshirt-v2-clone-{}", process::id()));
+        fs::write(&path, MSG_ENC).unwrap();
+        let mut reader = Reader::new(File::open(&path).unwrap()).unwrap();
+        let mut buffer = array!(MSG_LEN / 2);
+        reader.read_exact(&mut buffer).unwrap();
+        let mut other = reader.try_clone().unwrap();
+        other.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC[MSG_LEN / 2..]);
+        assert_eq!(reader.stream_position().unwrap(), MSG_LEN_U64 / 2);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC[MSG_LEN / 2..]);
+        assert_eq!(other.seek(SeekFrom::Start(0)).unwrap(), 0);
+        other.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC[..MSG_LEN / 2]);
+        fs::remove_file(path).unwrap();
+    }
 
-    const MSG_DEC: &[u8] = b"Hello world!";
-    const MSG_ENC: &[u8] = b"REDSHRT2\x00\x34\x54\x26\x2B\x4A\xBF\x29\x1D\x0B\x8E\x60\xD9\xA1\x76\xE1\x14\x7D\xDF\x05\xD4\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
-    const MSG_LEN: usize = 12;
-    const MSG_LEN_U64: u64 = MSG_LEN as u64;
-    const MSG_LEN_I64: i64 = MSG_LEN as i64;
+    /// A buffered stream that only writes pending data when flushed, even when seeking.
+    struct Deferred {
+        inner: Cursor<Vec<u8>>,
+        pending: Vec<u8>,
+    }
+
+    impl Write for Deferred {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.pending.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.write_all(&self.pending)?;
+            self.pending.clear();
+            Ok(())
+        }
+    }
+
+    impl Seek for Deferred {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
 
     #[test]
-    fn reader_read() {
-        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
-        let mut buffer = array!(MSG_LEN);
-        let (left, right) = buffer.split_at_mut(MSG_LEN / 2);
-        assert_eq!(
-            reader.seek(SeekFrom::Current(MSG_LEN_I64 / 2)).unwrap(),
-            MSG_LEN_U64 / 2
-        );
-        reader.read_exact(right).unwrap();
-        assert_eq!(reader.seek(SeekFrom::Current(-MSG_LEN_I64)).unwrap(), 0);
-        reader.read_exact(left).unwrap();
-        assert_eq!(buffer, MSG_DEC);
+    fn writer_flushes_before_digest() {
+        let data = vec![0x42; BUFFER_LEN + 5];
+        let dst = Deferred {
+            inner: Cursor::new(Vec::new()),
+            pending: Vec::new(),
+        };
+        let mut writer = Writer::new(dst).unwrap();
+        writer.write_all(&data).unwrap();
+        let dst = writer.into_inner().unwrap();
+        assert!(dst.pending.is_empty());
+        assert_eq!(dst.inner.into_inner(), encode(&data));
+    }
+
+    #[derive(Debug)]
+    /// A stream that cannot seek back past the Redshirt 2 header once the hash has been written.
+    struct NoRestore<'a>(Cursor<&'a mut Vec<u8>>);
+
+    impl Write for NoRestore<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for NoRestore<'_> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            match pos {
+                SeekFrom::Start(n) if n > MARKER_LEN as u64 => Err(io::Error::other("no restore")),
+                _ => self.0.seek(pos),
+            }
+        }
     }
 
     #[test]
-    fn reader_seek_start() {
-        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
-        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+    fn writer_restore_fails() {
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new(NoRestore(Cursor::new(&mut buffer))).unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        match writer.into_inner() {
+            Err(Error::Io(e)) => assert_eq!(e.to_string(), "no restore"),
+            _ => panic!("expected the restore seek to fail"),
+        }
+        assert_eq!(buffer, MSG_ENC);
+    }
+
+    #[test]
+    fn multi_writer_restore_fails() {
+        let mut buffer = Vec::new();
+        let primary = NoRestore(Cursor::new(&mut buffer));
+        let mut writer = MultiWriter::new(primary, Cursor::new(Vec::new())).unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        assert_eq!(writer.finish().unwrap_err().sink(), Sink::Primary);
+        assert_eq!(buffer, MSG_ENC);
+    }
+
+    #[test]
+    fn writer_buffered() {
+        let mut writer = Writer::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_all(&MSG_DEC[..2]).unwrap();
+        let mut writer = writer.buffered();
+        for byte in &MSG_DEC[2..] {
+            writer.write_all(&[*byte]).unwrap();
+        }
+        let inner = writer.into_inner().unwrap().into_inner().unwrap();
+        assert_eq!(inner.into_inner(), MSG_ENC);
+    }
+
+    #[test]
+    fn writer_checkpoint_on_flush() {
+        let mut writer = WriterOptions::new()
+            .checkpoint_on_flush(true)
+            .from_writer(Cursor::new(Vec::new()))
+            .unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        writer.flush().unwrap();
+        writer
+            .write_all(b" More data that never reaches the disk.")
+            .unwrap();
+        let mut data = writer.dst.take().unwrap().into_inner().into_inner();
+        data.truncate(MSG_ENC.len());
+        assert_eq!(data, MSG_ENC);
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let mut decoded = Vec::new();
+        let _ = reader.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, MSG_DEC);
+    }
+
+    #[test]
+    fn writer_no_checkpoint_on_flush() {
+        let mut writer = Writer::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        writer.flush().unwrap();
+        let data = writer.dst.take().unwrap().into_inner().into_inner();
+        assert!(matches!(
+            Reader::new(Cursor::new(data)),
+            Err(Error::Unfinalized)
+        ));
+    }
+
+    #[test]
+    fn writer_checkpoint_every() {
+        let data: Vec<u8> = (0..10).collect();
+        let mut writer = WriterOptions::new()
+            .checkpoint_every(4)
+            .from_writer(Trickle::new(Cursor::new(Vec::new())))
+            .unwrap();
+        writer.write_all(&data).unwrap();
+        assert_eq!(writer.since_checkpoint, 2);
+        let mut encoded = writer
+            .dst
+            .take()
+            .unwrap()
+            .into_inner()
+            .into_inner()
+            .into_inner();
+        encoded.truncate(HEADER_LEN + 8);
+        assert_eq!(encoded, encode(&data[..8]));
+        let mut writer = WriterOptions::new()
+            .checkpoint_every(4)
+            .from_writer(Cursor::new(Vec::new()))
+            .unwrap();
+        assert_eq!(writer.write(&data).unwrap(), 4);
+        assert_eq!(writer.since_checkpoint, 0);
+        writer.write_all(&data[4..]).unwrap();
+        let encoded = writer.into_inner().unwrap().into_inner();
+        assert_eq!(encoded, encode(&data));
+    }
+
+    #[test]
+    fn writer_buffer_len() {
+        let data: Vec<u8> = (0..10).collect();
+        let mut writer = WriterOptions::new()
+            .buffer_len(3)
+            .from_writer(Cursor::new(Vec::new()))
+            .unwrap();
+        assert_eq!(writer.write(&data).unwrap(), 3);
+        writer.write_all(&data[3..]).unwrap();
+        let encoded = writer.into_inner().unwrap().into_inner();
+        assert_eq!(encoded, encode(&data));
+    }
+
+    #[test]
+    fn writer_inline_buffer() {
+        let data: Vec<u8> = (0..=255).cycle().take(50).collect();
+        let mut writer: Writer<_, 7> = WriterOptions::new()
+            .checkpoint_every(10)
+            .from_writer_inline(Trickle::new(Cursor::new(Vec::new())))
+            .unwrap();
+        writer.write_all(&data).unwrap();
         assert_eq!(
-            reader.seek(SeekFrom::Start(MSG_LEN_U64)).unwrap(),
-            MSG_LEN_U64
+            writer.write_reader(&mut Trickle::new(&data[..])).unwrap(),
+            50
         );
-        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+        assert_eq!(writer.since_checkpoint, 0);
+        let encoded = writer.into_inner().unwrap().into_inner().into_inner();
+        assert_eq!(encoded, encode(&[&data[..], &data[..]].concat()));
+        let mut writer = Writer::<_, 7>::with_inline_buffer(Cursor::new(Vec::new())).unwrap();
+        assert_eq!(writer.write(&data).unwrap(), 7);
+        writer.write_all(&data[7..]).unwrap();
+        assert_eq!(writer.into_inner().unwrap().into_inner(), encode(&data));
+    }
+
+    /// Counts the writes made to a stream, and fails the write after `fail_after` bytes, once.
+    struct Counting {
+        inner: Cursor<Vec<u8>>,
+        writes: usize,
+        fail_after: Option<usize>,
+    }
+
+    impl Counting {
+        fn new() -> Self {
+            Self {
+                inner: Cursor::new(Vec::new()),
+                writes: 0,
+                fail_after: None,
+            }
+        }
+    }
+
+    impl Write for Counting {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            let buf = match self.fail_after {
+                Some(0) => {
+                    self.fail_after = None;
+                    return Err(io::Error::other("full"));
+                }
+                Some(left) => {
+                    let len = buf.len().min(left);
+                    self.fail_after = Some(left - len);
+                    &buf[..len]
+                }
+                None => buf,
+            };
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for Counting {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn writer_coalesce_writes() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let mut writer = WriterOptions::new()
+            .buffer_len(64)
+            .coalesce_writes(true)
+            .from_writer(Counting::new())
+            .unwrap();
+        for chunk in data.chunks(8) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(writer.bytes_written(), 1000);
+        assert_eq!(writer.dst.as_ref().unwrap().pending().len(), 40);
+        let digest = writer.digest_so_far();
+        // Held data is written before a write too large to hold.
+        writer.write_all(&data[..100]).unwrap();
+        let dst = writer.into_inner().unwrap();
+        // The header, 15 full buffers, the 40 bytes held, 64 bytes written straight through, the
+        // remaining 36 bytes held, and the hash.
+        assert_eq!(dst.writes, 1 + 15 + 1 + 1 + 1 + 1);
+        let encoded = dst.inner.into_inner();
+        assert_eq!(encoded, encode(&[&data[..], &data[..100]].concat()));
+        assert_eq!(verify(&encode(&data)[..]).unwrap(), digest);
+    }
+
+    #[test]
+    fn writer_coalesce_writes_failure() {
+        let data: Vec<u8> = (0..=255).cycle().take(100).collect();
+        let mut dst = Counting::new();
+        dst.fail_after = Some(HEADER_LEN + 5);
+        let mut writer = WriterOptions::new()
+            .buffer_len(64)
+            .checkpoint_every(30)
+            .coalesce_writes(true)
+            .from_writer(Trickle::new(dst))
+            .unwrap();
+        writer.write_all(&data[..20]).unwrap();
+        assert_eq!(writer.flush().unwrap_err().to_string(), "full");
+        assert_eq!(writer.dst.as_ref().unwrap().pending().len(), 15);
+        writer.flush().unwrap();
+        writer.write_all(&data[20..]).unwrap();
+        let encoded = writer.into_inner().unwrap().into_inner().inner.into_inner();
+        assert_eq!(encoded, encode(&data));
+    }
+
+    #[test]
+    fn writer_finalize_on_drop() {
+        let mut data = Vec::new();
+        let mut writer = Writer::new(Cursor::new(&mut data)).unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        assert!(format!("{writer:?}").contains("finalize_on_drop: true"));
+        drop(writer);
+        assert_eq!(data, MSG_ENC);
+    }
+
+    #[test]
+    fn writer_no_finalize_on_drop() {
+        let mut data = Vec::new();
+        let mut writer = Writer::new(Cursor::new(&mut data)).unwrap();
+        writer.set_finalize_on_drop(false);
+        writer.write_all(MSG_DEC).unwrap();
+        assert!(format!("{writer:?}").contains("finalize_on_drop: false"));
+        assert!(format!("{writer:?}").contains("bytes_written: 12, digest: <pending>, options: "));
         assert_eq!(
-            reader.seek(SeekFrom::Start(MSG_LEN_U64 / 2)).unwrap(),
-            MSG_LEN_U64 / 2
+            format!("{:?}", Verifier::new(Digest::from_bytes([1; DIGEST_LEN]))),
+            "Verifier { expected: Digest([1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1]), \
+             checksum: Checksum(<pending>) }"
         );
+        drop(writer);
+        assert_eq!(&data[HEADER_LEN..], &MSG_ENC[HEADER_LEN..]);
+        assert!(matches!(decode(&data), Err(Error::Unfinalized)));
+    }
+
+    #[test]
+    fn pump_short_transfers() {
+        let data = vec![0x42; BUFFER_LEN + 5];
+        let mut writer = Writer::new(Trickle::new(Cursor::new(Vec::new()))).unwrap();
+        let len = writer.write_reader(&mut Trickle::new(&data[..])).unwrap();
+        assert_eq!(len, data.len() as u64);
+        let encoded = writer.into_inner().unwrap().into_inner().into_inner();
+        assert_eq!(encoded, encode(&data));
+        let mut decoded = Trickle::new(Vec::new());
+        let mut reader = Reader::new(Trickle::new(Cursor::new(&encoded[..]))).unwrap();
+        assert_eq!(reader.pipe_to(&mut decoded).unwrap(), data.len() as u64);
+        assert_eq!(decoded.into_inner(), data);
+    }
+
+    #[test]
+    fn write_reader_matches_io_copy() {
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 7).collect();
+        let encode_with = |pipe: bool| {
+            let mut writer = WriterOptions::new()
+                .checkpoint_every(1000)
+                .from_writer(Cursor::new(Vec::new()))
+                .unwrap();
+            let len = if pipe {
+                writer.write_reader(&mut &data[..]).unwrap()
+            } else {
+                io::copy(&mut &data[..], &mut writer).unwrap()
+            };
+            assert_eq!(len, data.len() as u64);
+            writer.set_finalize_on_drop(false);
+            let since_checkpoint = writer.since_checkpoint;
+            let encoded = writer.dst.take().unwrap().into_inner().into_inner();
+            (encoded, since_checkpoint)
+        };
+        assert_eq!(encode_with(true), encode_with(false));
+    }
+
+    #[test]
+    fn pipe_to_lazy_bad_checksum() {
+        let mut data = MSG_ENC.to_vec();
+        data[HEADER_LEN] ^= 1;
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(&data[..])
+            .unwrap();
+        let mut decoded = Vec::new();
+        assert!(matches!(
+            reader.pipe_to(&mut decoded),
+            Err(Error::BadChecksum { .. })
+        ));
+        assert_eq!(decoded.len(), MSG_LEN);
+    }
+
+    #[test]
+    fn precomputed_writer_write() {
+        let data = vec![0x42; BUFFER_LEN + 5];
+        let encoded = encode(&data);
+        let digest = verify(&encoded[..]).unwrap();
+        let mut writer = PrecomputedWriter::new(Trickle::new(Vec::new()), digest, true).unwrap();
+        writer.write_all(&data).unwrap();
+        assert_eq!(writer.finish().unwrap().into_inner(), encoded);
+    }
+
+    #[test]
+    fn precomputed_writer_mismatch() {
+        let digest = verify(MSG_ENC).unwrap();
+        let mut writer = PrecomputedWriter::new(Vec::new(), digest, true).unwrap();
+        writer.write_all(b"Hello world?").unwrap();
+        assert!(matches!(writer.finish(), Err(Error::BadChecksum { .. })));
+        let mut writer = PrecomputedWriter::new(Vec::new(), digest, false).unwrap();
+        writer.write_all(b"Hello world?").unwrap();
+        assert!(matches!(
+            decode(&writer.finish().unwrap()),
+            Err(Error::BadChecksum { .. })
+        ));
+        let zero = Digest::from_bytes([0; DIGEST_LEN]);
+        assert!(matches!(
+            PrecomputedWriter::new(Vec::new(), zero, false),
+            Err(Error::Unfinalized)
+        ));
+    }
+
+    #[test]
+    fn reader_lazy_copy() {
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(MSG_ENC)
+            .unwrap();
+        let mut buffer = Vec::new();
+        assert_eq!(io::copy(&mut reader, &mut buffer).unwrap(), MSG_LEN_U64);
+        assert_eq!(buffer, MSG_DEC);
+    }
+
+    #[cfg(redshirt_unstable)]
+    #[test]
+    fn reader_read_buf() {
+        use std::{io::BorrowedBuf, mem::MaybeUninit};
+
+        let data: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN * 2 + 5).collect();
+        let encoded = encode(&data);
+        for verification in [Verification::Eager, Verification::Lazy] {
+            let mut reader = ReaderOptions::new()
+                .verification(verification)
+                .from_stream(Trickle::new(&encoded[..]))
+                .unwrap();
+            let mut storage = [MaybeUninit::uninit(); 7];
+            let mut buf = BorrowedBuf::from(&mut storage[..]);
+            while buf.len() == 0 {
+                match reader.read_buf(buf.unfilled()) {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    res => res.unwrap(),
+                }
+            }
+            assert_eq!(buf.filled(), &data[..buf.len()]);
+            let mut decoded = buf.filled().to_vec();
+            let _ = reader.read_to_end(&mut decoded).unwrap();
+            assert_eq!(decoded, data);
+        }
+        let mut corrupted = encoded;
+        corrupted[HEADER_LEN] ^= 1;
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(&corrupted[..])
+            .unwrap();
+        let e = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reader_lazy_copy_bad_checksum() {
+        let mut data = MSG_ENC.to_vec();
+        data[HEADER_LEN] ^= 1;
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(&data[..])
+            .unwrap();
+        let e = io::copy(&mut reader, &mut io::sink()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        match e.get_ref().and_then(|inner| inner.downcast_ref::<Error>()) {
+            Some(Error::BadChecksum { expected, .. }) => {
+                assert_eq!(expected.as_ref(), &MSG_ENC[MARKER_LEN..HEADER_LEN]);
+            }
+            other => panic!("expected `Error::BadChecksum`, got {:?}", other),
+        }
+        let mut buffer = array!(MSG_LEN);
+        for _ in 0..2 {
+            let e = reader.read(&mut buffer).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn reader_lazy_seek() {
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_reader(Cursor::new(MSG_ENC))
+            .unwrap();
+        let mut buffer = array!(MSG_LEN);
+        reader.read_exact(&mut buffer[..MSG_LEN / 2]).unwrap();
         assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+        assert_eq!(reader.read(&mut buffer).unwrap(), 0);
     }
 
     #[test]
-    fn reader_seek_current() {
-        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
-        assert_eq!(reader.seek(SeekFrom::Current(0)).unwrap(), 0);
-        assert_eq!(
-            reader.seek(SeekFrom::Current(MSG_LEN_I64)).unwrap(),
-            MSG_LEN_U64
-        );
-        assert_eq!(reader.seek(SeekFrom::Current(-MSG_LEN_I64)).unwrap(), 0);
-        assert_eq!(
-            reader.seek(SeekFrom::Current(MSG_LEN_I64 / 2)).unwrap(),
-            MSG_LEN_U64 / 2
-        );
-        assert_eq!(
-            reader.seek(SeekFrom::Current(-(MSG_LEN_I64 / 2))).unwrap(),
-            0
-        );
+    fn reader_lazy_seek_past_unread() {
+        let mut data = MSG_ENC.to_vec();
+        *data.last_mut().unwrap() ^= 1;
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_reader(Cursor::new(&data[..]))
+            .unwrap();
+        assert_eq!(reader.seek(SeekFrom::Start(6)).unwrap(), 6);
+        let mut buffer = Vec::new();
+        let e = reader.read_to_end(&mut buffer).unwrap_err();
+        assert!(matches!(
+            e.get_ref().and_then(|inner| inner.downcast_ref::<Error>()),
+            Some(Error::BadChecksum { .. })
+        ));
+        assert_eq!(reader.read(&mut [0; 1]).unwrap_err().kind(), e.kind());
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_reader(Cursor::new(MSG_ENC))
+            .unwrap();
+        let _ = reader.seek(SeekFrom::Start(6)).unwrap();
+        buffer.clear();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, &MSG_DEC[6..]);
+        assert_eq!(reader.digest_computed(), Some(verify(MSG_ENC).unwrap()));
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
     }
 
     #[test]
-    fn reader_seek_end() {
-        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
-        assert_eq!(reader.seek(SeekFrom::End(-MSG_LEN_I64)).unwrap(), 0);
-        assert_eq!(reader.seek(SeekFrom::End(0)).unwrap(), MSG_LEN_U64);
+    fn reader_lazy_copy_after_seek() {
+        let mut data = MSG_ENC.to_vec();
+        *data.last_mut().unwrap() ^= 1;
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_reader(Cursor::new(&data[..]))
+            .unwrap();
+        let mut buffer = array!(2);
+        reader.read_exact(&mut buffer).unwrap();
+        let _ = reader.seek(SeekFrom::Current(4)).unwrap();
+        let e = io::copy(&mut reader, &mut io::sink()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            e.get_ref().and_then(|inner| inner.downcast_ref::<Error>()),
+            Some(Error::BadChecksum { .. })
+        ));
+        assert_eq!(reader.position(), MSG_LEN_U64);
+    }
+
+    #[test]
+    fn reader_lazy_stream_seek_past_unread() {
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(Cursor::new(MSG_ENC))
+            .unwrap();
+        let _ = reader.seek(SeekFrom::Start(6)).unwrap();
+        let e = io::copy(&mut reader, &mut io::sink()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.get_ref().unwrap().downcast_ref::<Error>().is_none());
+        for _ in 0..2 {
+            assert_eq!(
+                reader.read(&mut [0; 1]).unwrap_err().kind(),
+                io::ErrorKind::InvalidData
+            );
+        }
+        assert_eq!(reader.digest_computed(), None);
+    }
+
+    #[test]
+    fn reader_lazy_unfinalized() {
+        let res = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_stream(MSG_UNFINALIZED);
+        match res {
+            Err(Error::Unfinalized) => (),
+            other => panic!("expected `Error::Unfinalized`, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reader_skip_bad_checksum() {
+        let mut data = MSG_ENC.to_vec();
+        data[MARKER_LEN] ^= 1;
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Skip)
+            .from_reader(Cursor::new(&data[..]))
+            .unwrap();
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+    }
+
+    #[test]
+    fn reader_from_slice() {
+        let mut reader = Reader::from_slice(MSG_ENC).unwrap();
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
         assert_eq!(reader.seek(SeekFrom::End(-MSG_LEN_I64)).unwrap(), 0);
+        assert_eq!(reader.seek(SeekFrom::Current(6)).unwrap(), 6);
+        buffer.clear();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, &MSG_DEC[6..]);
+        assert!(reader.seek(SeekFrom::Current(-MSG_LEN_I64 - 1)).is_err());
+        assert_eq!(reader.position(), MSG_LEN_U64);
+    }
+
+    #[test]
+    fn reader_from_vec() {
+        let mut reader = Reader::from_vec(MSG_ENC.to_vec()).unwrap();
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(buffer, MSG_DEC);
+        assert_eq!(reader.into_inner().into_inner(), MSG_ENC);
+        let mut encoded = MSG_ENC.to_vec();
+        encoded[HEADER_LEN] ^= 0xFF;
+        assert!(matches!(
+            Reader::from_vec(encoded),
+            Err(Error::BadChecksum { .. })
+        ));
+        assert!(matches!(
+            Reader::from_slice(MSG_UNFINALIZED),
+            Err(Error::Unfinalized)
+        ));
+        assert!(matches!(
+            Reader::from_slice(&MSG_ENC[..HEADER_LEN - 1]),
+            Err(Error::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn decode_slice() {
+        assert_eq!(decode(MSG_ENC).unwrap(), MSG_DEC);
+    }
+
+    #[test]
+    fn decode_slice_bad_header() {
+        assert!(matches!(decode(MSG_DEC), Err(Error::BadHeader)));
+        assert!(matches!(
+            decode(&MSG_ENC[..HEADER_LEN - 1]),
+            Err(Error::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn decode_slice_unfinalized() {
+        assert!(matches!(decode(MSG_UNFINALIZED), Err(Error::Unfinalized)));
+    }
+
+    #[test]
+    fn decode_slice_bad_checksum() {
+        let mut encoded = MSG_ENC.to_vec();
+        encoded[HEADER_LEN] ^= 0xFF;
+        assert!(matches!(decode(&encoded), Err(Error::BadChecksum { .. })));
+    }
+
+    #[test]
+    fn encode_slice() {
+        assert_eq!(encode(MSG_DEC), MSG_ENC);
+    }
+
+    #[test]
+    fn string_round_trip_across_chunks() {
+        // Place a two-byte character across the buffer boundary, measured from either the start
+        // of the stream or the start of the data.
+        for prefix in [BUFFER_LEN - 1, BUFFER_LEN - 1 - HEADER_LEN] {
+            let text = format!("{}\u{e9}z", "a".repeat(prefix));
+            let encoded = encode_str(Cursor::new(Vec::new()), &text)
+                .unwrap()
+                .into_inner();
+            assert_eq!(decode_to_string(Cursor::new(&encoded)).unwrap(), text);
+            assert_eq!(decode_to_string_lossy(Cursor::new(&encoded)).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn string_invalid_utf8() {
+        let mut data = vec![b'a'; BUFFER_LEN - 1];
+        data.extend_from_slice(b"\xc3z");
+        let encoded = encode(&data);
+        assert!(matches!(
+            decode_to_string(Cursor::new(&encoded)),
+            Err(Error::InvalidText { valid_up_to }) if valid_up_to == BUFFER_LEN - 1
+        ));
+        let lossy = decode_to_string_lossy(Cursor::new(&encoded)).unwrap();
+        assert!(lossy.ends_with("a\u{fffd}z"));
+    }
+
+    #[test]
+    fn read_lines_file() {
+        let path = env::temp_dir().join(format!("redshirt-v2-lines-{}", process::id()));
+        fs::write(&path, encode(b"first\r\nsecond\n")).unwrap();
+        let lines: Vec<_> = read_lines(&path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(lines, ["first", "second"]);
+        let mut corrupt = encode(MSG_DEC);
+        *corrupt.last_mut().unwrap() ^= 0xFF;
+        fs::write(&path, corrupt).unwrap();
+        assert!(matches!(read_lines(&path), Err(Error::BadChecksum { .. })));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn verifier() {
+        let expected = verify(MSG_ENC).unwrap();
+        let mut verifier = Verifier::new(expected);
+        for chunk in MSG_ENC[HEADER_LEN..].chunks(5) {
+            verifier.update(chunk);
+        }
+        assert_eq!(verifier.finish().unwrap(), expected);
+        let mut verifier = Verifier::new(expected);
+        verifier.update(&MSG_ENC[HEADER_LEN + 1..]);
+        assert!(matches!(
+            verifier.finish(),
+            Err(Error::BadChecksum { expected: e, .. }) if e == expected
+        ));
+        let verifier = Verifier::new(Digest::default());
+        assert!(matches!(verifier.finish(), Err(Error::Unfinalized)));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn verifier_update_buf() {
+        use bytes::{Buf, BytesMut};
+
+        let expected = verify(MSG_ENC).unwrap();
+        let mut verifier = Verifier::new(expected);
+        let mut segments = BytesMut::from(&MSG_ENC[HEADER_LEN..HEADER_LEN + 2])
+            .chain(BytesMut::from(&MSG_ENC[HEADER_LEN + 2..HEADER_LEN + 7]))
+            .chain(BytesMut::from(&MSG_ENC[HEADER_LEN + 7..]));
+        verifier.update_buf(&mut segments);
+        assert!(!segments.has_remaining());
+        assert_eq!(verifier.finish().unwrap(), expected);
+    }
+
+    fn is_overclaim(e: &Error) -> bool {
+        matches!(e, Error::Io(e) if e.kind() == io::ErrorKind::Other)
+    }
+
+    #[test]
+    fn overclaiming_readers() {
+        let e = Reader::new(Overclaim::new(Cursor::new(MSG_ENC), 0)).unwrap_err();
+        assert!(is_overclaim(&e), "{:?}", e);
+        let e = Reader::new(Overclaim::new(Cursor::new(MSG_ENC), HEADER_LEN)).unwrap_err();
+        assert!(is_overclaim(&e), "{:?}", e);
+        let mut reader = ReaderOptions::new()
+            .verification(Verification::Lazy)
+            .from_reader(Overclaim::new(Cursor::new(MSG_ENC), HEADER_LEN))
+            .unwrap();
+        let mut buffer = array!(MSG_LEN);
         assert_eq!(
-            reader.seek(SeekFrom::End(-MSG_LEN_I64 / 2)).unwrap(),
-            MSG_LEN_U64 / 2
+            reader.read(&mut buffer).unwrap_err().kind(),
+            io::ErrorKind::Other
         );
-        assert_eq!(reader.seek(SeekFrom::End(-MSG_LEN_I64)).unwrap(), 0);
+        assert_eq!(reader.bytes_read(), 0);
+        let e = verify(Overclaim::new(MSG_ENC, HEADER_LEN)).unwrap_err();
+        assert!(is_overclaim(&e), "{:?}", e);
+        let e = CopyOptions::new()
+            .copy(Overclaim::new(MSG_ENC, HEADER_LEN), Vec::new())
+            .unwrap_err();
+        assert!(is_overclaim(&e), "{:?}", e);
     }
 
     #[test]
-    #[should_panic]
-    fn reader_seek_positive_overflow() {
-        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
-        let _ = reader.seek(SeekFrom::Start(u64::max_value())).unwrap();
+    fn overclaiming_writers() {
+        assert!(Writer::new(Overclaim::new(Cursor::new(Vec::new()), 0)).is_err());
+        let mut writer = Writer::new(Overclaim::new(Cursor::new(Vec::new()), HEADER_LEN)).unwrap();
+        let e = writer.write_all(MSG_DEC).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::Other);
+        assert_eq!(writer.bytes_written(), 0);
+        writer.set_finalize_on_drop(false);
+        let digest = verify(MSG_ENC).unwrap();
+        let dst = Overclaim::new(Vec::new(), HEADER_LEN);
+        let mut writer = PrecomputedWriter::new(dst, digest, true).unwrap();
+        let e = writer.write_all(MSG_DEC).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::Other);
+        let e = CopyOptions::new()
+            .copy(MSG_ENC, Overclaim::new(Vec::new(), 0))
+            .unwrap_err();
+        assert!(is_overclaim(&e), "{:?}", e);
     }
 
     #[test]
-    #[should_panic]
-    fn reader_seek_negative_overflow() {
-        let mut reader = Reader::new(Cursor::new(MSG_ENC)).unwrap();
-        let _ = reader.seek(SeekFrom::Current(-1)).unwrap();
+    fn sync_writer_records_intact() {
+        const THREADS: usize = 8;
+        const RECORDS: usize = 200;
+        // A tiny buffer makes the `Writer` split every record into several chunks.
+        let writer = WriterOptions::new()
+            .buffer_len(4)
+            .from_writer(Cursor::new(Vec::new()))
+            .unwrap();
+        let writer = SyncWriter::new(writer);
+        thread::scope(|scope| {
+            for t in 0..THREADS {
+                let writer = &writer;
+                let _ = scope.spawn(move || {
+                    let mut handle = writer;
+                    for i in 0..RECORDS {
+                        if i % 2 == 0 {
+                            writeln!(handle, "thread {t} record {i} {}", "x".repeat(i)).unwrap();
+                        } else {
+                            let record = format!("thread {t} record {i} {}\n", "x".repeat(i));
+                            handle.write_all(record.as_bytes()).unwrap();
+                        }
+                        if i % 50 == 0 {
+                            writer.checkpoint().unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        let encoded = writer.finish().unwrap().into_inner();
+        let decoded = String::from_utf8(decode(&encoded).unwrap()).unwrap();
+        let mut records = decoded.lines().collect::<Vec<_>>();
+        let mut expected = (0..THREADS)
+            .flat_map(|t| {
+                (0..RECORDS).map(move |i| format!("thread {t} record {i} {}", "x".repeat(i)))
+            })
+            .collect::<Vec<_>>();
+        records.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(records, expected);
     }
 
     #[test]
-    fn writer_write() {
-        let mut buffer = array!(HEADER_LEN + MSG_LEN);
-        {
-            let mut writer = Writer::new(Cursor::new(&mut buffer[..])).unwrap();
-            writer.write_all(MSG_DEC).unwrap();
-            let _ = writer.into_inner().unwrap();
-        }
-        assert_eq!(&buffer[..], &MSG_ENC[..]);
+    fn preallocate_file() {
+        let path = env::temp_dir().join(format!("redshirt-v2-prealloc-{}", process::id()));
+        let mut options = WriterOptions::new();
+        let _ = options.preallocate(4096);
+        let mut writer = options.from_file(File::create(&path).unwrap()).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 29 + 4096);
+        writer.write_all(MSG_DEC).unwrap();
+        let file = writer.into_inner().unwrap();
+        assert_eq!(file.metadata().unwrap().len(), 29 + 12);
+        assert_eq!(fs::read(&path).unwrap(), MSG_ENC);
+        // Writing more than was reserved, and finalising on drop, both leave the file intact.
+        let _ = options.preallocate(4);
+        let mut writer = options.from_file(File::create(&path).unwrap()).unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        drop(writer);
+        assert_eq!(fs::read(&path).unwrap(), MSG_ENC);
+        let _ = options.preallocate(4096);
+        let writer = options.from_file(File::create(&path).unwrap()).unwrap();
+        let mut writer = writer.buffered();
+        writer.write_all(MSG_DEC).unwrap();
+        let _ = writer.into_inner().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), MSG_ENC);
+        let mut writer = options.from_writer(Cursor::new(Vec::new())).unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        assert_eq!(writer.into_inner().unwrap().into_inner(), MSG_ENC);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "fadvise")]
+    #[test]
+    fn advise_file() {
+        let path = env::temp_dir().join(format!("redshirt-v2-advise-{}", process::id()));
+        fs::write(&path, MSG_ENC).unwrap();
+        let mut reader = Reader::new(File::open(&path).unwrap()).unwrap();
+        reader.advise_sequential().unwrap();
+        let mut decoded = Vec::new();
+        let _ = reader.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, MSG_DEC);
+        reader.advise_dontneed().unwrap();
+        fs::remove_file(path).unwrap();
     }
 }
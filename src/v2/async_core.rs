@@ -0,0 +1,445 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Runtime-agnostic implementations of the asynchronous Redshirt 2 types.
+//!
+//! Each runtime-specific module wraps these in its own public types, adapting the underlying I/O
+//! object with one of the adapters in `async_io`.
+
+#[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+use super::SpillOptions;
+use super::{
+    check_payload_len, check_read, checksum_mismatch, clamp_read, parse_header, ChecksumBuilder,
+    LazyVerifier, ReaderOptions, Verification, HEADER_LEN, MARKER, MARKER_LEN,
+};
+use crate::{
+    async_cursor::AsyncCursor,
+    async_io::{self, RawRead, RawSeek, RawWrite},
+    cursor::{self, overflow_error, BUFFER_LEN},
+    digest::{Digest, DIGEST_LEN},
+    error::Error,
+    xor_bytes,
+};
+#[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+use std::{
+    fs::File,
+    io::{Read, Seek, Write},
+};
+use std::{
+    io::{self, SeekFrom},
+    task::{ready, Context, Poll},
+};
+
+#[derive(Debug)]
+pub(crate) struct ReaderCore<R> {
+    src: AsyncCursor<R>,
+    verifier: Option<LazyVerifier>,
+    limit: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct WriterCore<W> {
+    dst: AsyncCursor<W>,
+    checksum: ChecksumBuilder,
+}
+
+#[derive(Debug)]
+pub(crate) struct BufferedWriterCore<W> {
+    dst: W,
+    payload: Vec<u8>,
+    checksum: ChecksumBuilder,
+    #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+    spill: Option<Spill>,
+    #[cfg(feature = "metrics")]
+    buffered: crate::metrics::Buffered,
+}
+
+#[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+struct Spill {
+    options: SpillOptions,
+    file: Option<File>,
+    // The number of bytes successfully moved to `file`. Anything past this is left over from a
+    // failed spill, and is overwritten by the next one.
+    len: u64,
+}
+
+impl<R: RawRead + RawSeek> ReaderCore<R> {
+    #[inline]
+    pub(crate) async fn with_options(mut src: R, options: &ReaderOptions) -> Result<Self, Error> {
+        let expected = read_header(&mut src).await?;
+        #[cfg(feature = "tracing")]
+        options.trace_open(expected);
+        check_len(&mut src, options.max_payload_len).await?;
+        if options.verification != Verification::Eager || expected.is_zero() {
+            return options
+                .verifier(expected)
+                .map(|verifier| Self::with_verifier(src, verifier, options.max_payload_len));
+        }
+        let base = async_io::seek(&mut src, SeekFrom::Current(0))
+            .await
+            .map_err(Error::Io)?;
+        let actual = hash_payload(&mut src).await.map_err(Error::Io)?;
+        if actual == expected {
+            async_io::seek(&mut src, SeekFrom::Start(base))
+                .await
+                .map(|_| Self::with_verifier(src, None, options.max_payload_len))
+                .map_err(Error::Io)
+        } else {
+            Err(checksum_mismatch(expected, actual))
+        }
+    }
+}
+
+impl<R: RawRead> ReaderCore<R> {
+    #[inline]
+    pub(crate) async fn from_stream(mut src: R, options: &ReaderOptions) -> Result<Self, Error> {
+        let expected = read_header(&mut src).await?;
+        options
+            .verifier(expected)
+            .map(|verifier| Self::with_verifier(src, verifier, options.max_payload_len))
+    }
+}
+
+impl<R> ReaderCore<R> {
+    #[inline]
+    pub(crate) fn into_inner(self) -> R {
+        self.src.into_inner()
+    }
+
+    #[inline]
+    const fn with_verifier(src: R, verifier: Option<LazyVerifier>, limit: u64) -> Self {
+        Self {
+            src: AsyncCursor::new(src),
+            verifier,
+            limit,
+        }
+    }
+}
+
+impl<R: RawRead> RawRead for ReaderCore<R> {
+    #[inline]
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if let Some(verifier) = self.verifier.as_ref() {
+            verifier.check()?;
+        }
+        let offset = self.src.offset();
+        let wanted = clamp_read(self.limit, offset, buf.len())?;
+        let buf = &mut buf[..wanted];
+        let len = ready!(self.src.poll_read_encoded(cx, buf))?;
+        check_read(self.limit, offset, len)?;
+        let eof = len == 0 && !buf.is_empty();
+        let new = &mut buf[..len];
+        if let Some(verifier) = self.verifier.as_mut() {
+            verifier.update(offset, new, eof)?;
+        }
+        xor_bytes(new);
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<R: RawSeek> ReaderCore<R> {
+    #[cfg(feature = "futures-io")]
+    #[inline]
+    pub(crate) fn poll_seek(
+        &mut self,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        self.src.poll_seek(cx, pos)
+    }
+}
+
+impl<R: RawSeek> RawSeek for ReaderCore<R> {
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.src.poll_ready(cx)
+    }
+
+    #[inline]
+    fn start_seek(&mut self, pos: SeekFrom) -> io::Result<()> {
+        self.src.start_seek(pos)
+    }
+
+    #[inline]
+    fn poll_complete(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        self.src.poll_complete(cx)
+    }
+}
+
+impl<W: RawWrite + RawSeek> WriterCore<W> {
+    #[inline]
+    pub(crate) async fn new(mut dst: W) -> Result<Self, Error> {
+        let mut dummy_header = array!(HEADER_LEN);
+        dummy_header[..MARKER_LEN].copy_from_slice(&MARKER);
+        async_io::write_all(&mut dst, &dummy_header)
+            .await
+            .map(|()| Self {
+                dst: AsyncCursor::new(dst),
+                checksum: ChecksumBuilder::new(),
+            })
+            .map_err(Error::Io)
+    }
+
+    #[inline]
+    pub(crate) async fn finish(self) -> Result<(W, Digest), Error> {
+        let digest = self.checksum.finish();
+        let len = self.dst.offset();
+        let mut dst = self.dst.into_inner();
+        async_io::flush(&mut dst).await.map_err(Error::Io)?;
+        let end = async_io::seek(&mut dst, SeekFrom::Current(0))
+            .await
+            .map_err(Error::Io)?;
+        // The header is found relative to the end of the data, so that streams which did not start
+        // at offset 0 of the output are finalised correctly.
+        let start = len
+            .checked_add(cursor::len_u64(DIGEST_LEN))
+            .and_then(|n| end.checked_sub(n))
+            .ok_or_else(overflow_error)
+            .map_err(Error::Io)?;
+        let _ = async_io::seek(&mut dst, SeekFrom::Start(start))
+            .await
+            .map_err(Error::Io)?;
+        async_io::write_all(&mut dst, digest.as_ref())
+            .await
+            .map_err(Error::Io)?;
+        let _ = async_io::seek(&mut dst, SeekFrom::Start(end))
+            .await
+            .map_err(Error::Io)?;
+        async_io::flush(&mut dst)
+            .await
+            .map(|()| (dst, digest))
+            .map_err(Error::Io)
+    }
+}
+
+impl<W: RawWrite> RawWrite for WriterCore<W> {
+    #[inline]
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let checksum = &mut self.checksum;
+        self.dst
+            .poll_write_with(cx, buf, |chunk| checksum.update(chunk))
+    }
+
+    #[inline]
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.dst.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.dst.poll_close(cx)
+    }
+}
+
+impl<W: RawWrite> BufferedWriterCore<W> {
+    #[inline]
+    pub(crate) fn new(dst: W) -> Self {
+        Self {
+            dst,
+            payload: Vec::new(),
+            checksum: ChecksumBuilder::new(),
+            #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+            spill: None,
+            #[cfg(feature = "metrics")]
+            buffered: crate::metrics::Buffered::default(),
+        }
+    }
+
+    #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+    #[inline]
+    pub(crate) fn with_spill(dst: W, options: &SpillOptions) -> Self {
+        Self {
+            spill: Some(Spill {
+                options: options.clone(),
+                file: None,
+                len: 0,
+            }),
+            ..Self::new(dst)
+        }
+    }
+
+    #[inline]
+    pub(crate) async fn finish(self) -> Result<(W, Digest), Error> {
+        let digest = self.checksum.finish();
+        let mut dst = self.dst;
+        let mut header = array!(HEADER_LEN);
+        header[..MARKER_LEN].copy_from_slice(&MARKER);
+        header[MARKER_LEN..].copy_from_slice(digest.as_ref());
+        async_io::write_all(&mut dst, &header)
+            .await
+            .map_err(Error::Io)?;
+        #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+        if let Some(spill) = self.spill {
+            spill.copy_to(&mut dst).await.map_err(Error::Io)?;
+        }
+        async_io::write_all(&mut dst, &self.payload)
+            .await
+            .map_err(Error::Io)?;
+        async_io::flush(&mut dst)
+            .await
+            .map(|()| (dst, digest))
+            .map_err(Error::Io)
+    }
+}
+
+impl<W> RawWrite for BufferedWriterCore<W> {
+    #[inline]
+    fn poll_write(&mut self, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+        let buf = match self.spill.as_mut() {
+            Some(spill) => {
+                let limit = spill.options.limit();
+                if self.payload.len() >= limit && !buf.is_empty() {
+                    spill.push(&self.payload)?;
+                    #[cfg(feature = "metrics")]
+                    self.buffered.sub(self.payload.len());
+                    self.payload.clear();
+                }
+                &buf[..buf.len().min(limit - self.payload.len())]
+            }
+            None => buf,
+        };
+        let start = self.payload.len();
+        self.payload.extend_from_slice(buf);
+        #[cfg(feature = "metrics")]
+        self.buffered.add(buf.len());
+        let encoded = &mut self.payload[start..];
+        xor_bytes(encoded);
+        self.checksum.update(encoded);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    #[inline]
+    fn poll_flush(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    #[inline]
+    fn poll_close(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+impl Spill {
+    #[inline]
+    fn push(&mut self, encoded: &[u8]) -> io::Result<()> {
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => self.file.insert(self.options.create_file()?),
+        };
+        let len = cursor::add_len(self.len, encoded.len())?;
+        let _ = file.seek(SeekFrom::Start(self.len))?;
+        file.write_all(encoded)?;
+        self.len = len;
+        Ok(())
+    }
+
+    #[inline]
+    async fn copy_to<W: RawWrite>(self, dst: &mut W) -> io::Result<()> {
+        let Some(mut file) = self.file else {
+            return Ok(());
+        };
+        file.rewind()?;
+        let mut src = file.take(self.len);
+        // Kept on the heap so that the buffer doesn't bloat the size of the returned future.
+        let mut buffer = vec![u8::default(); BUFFER_LEN];
+        loop {
+            match src.read(&mut buffer) {
+                Ok(0) if src.limit() == 0 => break Ok(()),
+                Ok(0) => break Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(len) => async_io::write_all(dst, &buffer[..len]).await?,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => break Err(e),
+            }
+        }
+    }
+}
+
+#[inline]
+pub(crate) async fn copy_verified<R: RawRead, W: RawWrite>(
+    mut src: R,
+    mut dst: W,
+) -> Result<Digest, Error> {
+    let expected = read_header(&mut src).await?;
+    if expected.is_zero() {
+        return Err(Error::Unfinalized);
+    }
+    let mut header = array!(HEADER_LEN);
+    header[..MARKER_LEN].copy_from_slice(&MARKER);
+    header[MARKER_LEN..].copy_from_slice(expected.as_ref());
+    async_io::write_all(&mut dst, &header)
+        .await
+        .map_err(Error::Io)?;
+    // Kept on the heap so that the buffer doesn't bloat the size of the returned future.
+    let mut buffer = vec![u8::default(); BUFFER_LEN];
+    let mut checksum = ChecksumBuilder::new();
+    loop {
+        let len = match async_io::read(&mut src, &mut buffer).await {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::Io(e)),
+        };
+        checksum.update(&buffer[..len]);
+        async_io::write_all(&mut dst, &buffer[..len])
+            .await
+            .map_err(Error::Io)?;
+    }
+    let actual = checksum.finish();
+    if actual != expected {
+        return Err(checksum_mismatch(expected, actual));
+    }
+    async_io::flush(&mut dst)
+        .await
+        .map(|()| actual)
+        .map_err(Error::Io)
+}
+
+#[inline]
+async fn read_header<R: RawRead>(src: &mut R) -> Result<Digest, Error> {
+    let mut header_buf = array!(HEADER_LEN);
+    async_io::read_exact(src, &mut header_buf)
+        .await
+        .map_err(Error::Io)?;
+    parse_header(&header_buf)
+}
+
+#[inline]
+/// Fails with `Error::PayloadTooLarge` if more than `limit` bytes follow the current position of
+/// `src`, leaving `src` where it was. Nothing is checked if there is no limit.
+async fn check_len<R: RawSeek>(src: &mut R, limit: u64) -> Result<(), Error> {
+    if limit == u64::MAX {
+        return Ok(());
+    }
+    let base = async_io::seek(src, SeekFrom::Current(0))
+        .await
+        .map_err(Error::Io)?;
+    let end = async_io::seek(src, SeekFrom::End(0))
+        .await
+        .map_err(Error::Io)?;
+    let _ = async_io::seek(src, SeekFrom::Start(base))
+        .await
+        .map_err(Error::Io)?;
+    check_payload_len(limit, end.saturating_sub(base))
+}
+
+#[inline]
+async fn hash_payload<R: RawRead>(src: &mut R) -> io::Result<Digest> {
+    // Kept on the heap so that the buffer doesn't bloat the size of the returned future.
+    let mut buffer = vec![u8::default(); BUFFER_LEN];
+    let mut checksum = ChecksumBuilder::new();
+    loop {
+        match async_io::read(src, &mut buffer).await {
+            Ok(0) => break Ok(checksum.finish()),
+            Ok(len) => checksum.update(&buffer[..len]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => break Err(e),
+        }
+    }
+}
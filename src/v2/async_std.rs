@@ -0,0 +1,28 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Asynchronous Redshirt 2 utilities for use with [async-std].
+//!
+//! async-std's I/O types implement the `futures::io` traits, so this module re-exports the types
+//! and functions from `v2::futures_io`. They accept `async_std::fs::File` and other async-std streams directly,
+//! and the writers must be finalised with `finish` in the same way:
+//!
+//! ```no_run
+//! use async_std::{fs::File, prelude::*};
+//! use redshirt::v2::async_std::Writer;
+//!
+//! # async_std::task::block_on(async {
+//! let file = File::create("User.usr").await.unwrap();
+//! let mut writer = Writer::new(file).await.unwrap();
+//! writer.write_all(b"foobar").await.unwrap();
+//! let (file, digest) = writer.finish().await.unwrap();
+//! # });
+//! ```
+//!
+//! [async-std]: https://async.rs
+
+pub use super::futures_io::{copy_verified, BufferedWriter, Reader, Writer};
@@ -0,0 +1,251 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use super::{
+    parse_header, ChecksumBuilder, LazyVerifier, ReaderOptions, HEADER_LEN, MARKER, MARKER_LEN,
+};
+use crate::{cursor, error::Error, xor_bytes};
+use bytes::{Buf, Bytes, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+#[derive(Debug)]
+/// Encodes and decodes a Redshirt 2 stream as part of a framed I/O pipeline.
+///
+/// Since the header of a Redshirt 2 stream contains the SHA-1 hash of the entire payload, the
+/// encoder only accepts a single item, which is written out in full along with the header. Any
+/// further items are rejected with an error of kind `InvalidInput`.
+///
+/// The decoder cannot rewind the stream, so `Verification::Eager` is treated as
+/// `Verification::Lazy`: decoded data is yielded as it arrives, and the hash is checked once the
+/// end of the stream is reached.
+pub struct V2Codec {
+    options: ReaderOptions,
+    header_written: bool,
+    header_read: bool,
+    verifier: Option<LazyVerifier>,
+    offset: u64,
+}
+
+impl V2Codec {
+    #[inline]
+    #[must_use]
+    /// Creates a new `V2Codec`.
+    ///
+    /// This is equivalent to `V2Codec::with_options(&ReaderOptions::new())`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::codec::V2Codec;
+    /// use tokio::fs::File;
+    /// use tokio_util::codec::FramedRead;
+    ///
+    /// # async fn example() {
+    /// let file = File::open("User.usr").await.unwrap();
+    /// let frames = FramedRead::new(file, V2Codec::new());
+    /// # }
+    /// ```
+    pub fn new() -> Self {
+        Self::with_options(&ReaderOptions::new())
+    }
+
+    #[inline]
+    #[must_use]
+    /// Creates a new `V2Codec`, using the given set of options when decoding.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::{codec::V2Codec, v2::ReaderOptions};
+    ///
+    /// let codec = V2Codec::with_options(ReaderOptions::new().accept_unfinalized(true));
+    /// ```
+    pub fn with_options(options: &ReaderOptions) -> Self {
+        Self {
+            options: *options,
+            header_written: false,
+            header_read: false,
+            verifier: None,
+            offset: 0,
+        }
+    }
+}
+
+impl Default for V2Codec {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Encoder<Bytes> for V2Codec {
+    type Error = io::Error;
+
+    #[inline]
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+        if self.header_written {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a Redshirt 2 stream can only be encoded from a single item",
+            ));
+        }
+        let start = dst.len();
+        dst.reserve(HEADER_LEN + item.len());
+        dst.extend_from_slice(&MARKER);
+        dst.extend_from_slice(&[0; HEADER_LEN - MARKER_LEN]);
+        dst.extend_from_slice(&item);
+        let (header, payload) = dst[start..].split_at_mut(HEADER_LEN);
+        xor_bytes(payload);
+        let mut checksum = ChecksumBuilder::new();
+        checksum.update(payload);
+        header[MARKER_LEN..].copy_from_slice(checksum.finish().as_ref());
+        self.header_written = true;
+        Ok(())
+    }
+}
+
+impl Decoder for V2Codec {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    #[inline]
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Bytes>> {
+        self.decode_frame(src, false)
+    }
+
+    #[inline]
+    fn decode_eof(&mut self, src: &mut BytesMut) -> io::Result<Option<Bytes>> {
+        self.decode_frame(src, true)
+    }
+}
+
+impl V2Codec {
+    #[inline]
+    fn decode_frame(&mut self, src: &mut BytesMut, eof: bool) -> io::Result<Option<Bytes>> {
+        if !self.header_read {
+            let len = src.len().min(MARKER_LEN);
+            if src[..len] != MARKER[..len] {
+                return Err(Error::BadHeader.into());
+            } else if src.len() < HEADER_LEN {
+                return if eof {
+                    Err(io::ErrorKind::UnexpectedEof.into())
+                } else {
+                    Ok(None)
+                };
+            }
+            let mut header_buf = array!(HEADER_LEN);
+            header_buf.copy_from_slice(&src[..HEADER_LEN]);
+            src.advance(HEADER_LEN);
+            self.verifier = parse_header(&header_buf).and_then(|d| self.options.verifier(d))?;
+            self.header_read = true;
+        }
+        let mut frame = src.split();
+        if let Some(verifier) = self.verifier.as_mut() {
+            verifier.update(self.offset, &frame, eof && frame.is_empty())?;
+        }
+        if frame.is_empty() {
+            return Ok(None);
+        }
+        self.offset = cursor::add_len(self.offset, frame.len())?;
+        xor_bytes(&mut frame);
+        Ok(Some(frame.freeze()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::V2Codec;
+    use crate::{error::Error, v2::ReaderOptions};
+    use bytes::{Bytes, BytesMut};
+    use futures::{SinkExt, StreamExt, TryStreamExt};
+    use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+    use tokio_util::codec::{Encoder, FramedRead, FramedWrite};
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+    const MSG_ENC: &[u8] = b"REDSHRT2\x00\x34\x54\x26\x2B\x4A\xBF\x29\x1D\x0B\x8E\x60\xD9\xA1\x76\xE1\x14\x7D\xDF\x05\xD4\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+
+    async fn framed_read(encoded: Vec<u8>, options: &ReaderOptions) -> io::Result<Vec<Bytes>> {
+        let (mut tx, rx) = io::duplex(5);
+        let send = tokio::spawn(async move {
+            for chunk in encoded.chunks(3) {
+                tx.write_all(chunk).await.unwrap();
+            }
+        });
+        let frames = FramedRead::new(rx, V2Codec::with_options(options))
+            .try_collect::<Vec<_>>()
+            .await;
+        send.await.unwrap();
+        frames
+    }
+
+    #[tokio::test]
+    async fn framed_read_chunked() {
+        let frames = framed_read(MSG_ENC.to_vec(), &ReaderOptions::new())
+            .await
+            .unwrap();
+        assert!(frames.len() > 1);
+        assert_eq!(frames.concat(), MSG_DEC);
+    }
+
+    #[tokio::test]
+    async fn framed_read_bad_checksum() {
+        let mut encoded = MSG_ENC.to_vec();
+        *encoded.last_mut().unwrap() ^= 1;
+        let e = framed_read(encoded, &ReaderOptions::new())
+            .await
+            .unwrap_err();
+        match e.into_inner().unwrap().downcast::<Error>().map(|e| *e) {
+            Ok(Error::BadChecksum { .. }) => (),
+            other => panic!("expected `Error::BadChecksum`, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn framed_read_truncated_header() {
+        let e = framed_read(MSG_ENC[..20].to_vec(), &ReaderOptions::new())
+            .await
+            .unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn framed_read_bad_header() {
+        let mut frames = FramedRead::new(&b"REDSHIRT\x00"[..], V2Codec::new());
+        let e = frames.next().await.unwrap().unwrap_err();
+        match e.into_inner().unwrap().downcast::<Error>().map(|e| *e) {
+            Ok(Error::BadHeader) => (),
+            other => panic!("expected `Error::BadHeader`, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn framed_write() {
+        let (tx, mut rx) = io::duplex(7);
+        let recv = tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            let _ = rx.read_to_end(&mut buffer).await.unwrap();
+            buffer
+        });
+        let mut frames = FramedWrite::new(tx, V2Codec::new());
+        frames.send(Bytes::from_static(MSG_DEC)).await.unwrap();
+        drop(frames);
+        assert_eq!(recv.await.unwrap(), MSG_ENC);
+    }
+
+    #[test]
+    fn encode_twice() {
+        let mut codec = V2Codec::new();
+        let mut dst = BytesMut::new();
+        codec.encode(Bytes::from_static(MSG_DEC), &mut dst).unwrap();
+        let e = codec
+            .encode(Bytes::from_static(MSG_DEC), &mut dst)
+            .unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(dst, MSG_ENC);
+    }
+}
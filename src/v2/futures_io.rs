@@ -0,0 +1,377 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Asynchronous Redshirt 2 utilities for use with the [`futures-io`] traits.
+//!
+//! This module provides `Reader`, `Writer` and `BufferedWriter` types, which are the counterparts
+//! of the types in `v2::tokio` for runtimes built on `futures::io`, such as smol and async-std.
+//! They behave identically, including the need to call `finish` on the writers; see the `v2::tokio`
+//! documentation for details.
+//!
+//! [`futures-io`]: https://docs.rs/futures-io
+
+#[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+use super::SpillOptions;
+use super::{
+    async_core::{self, BufferedWriterCore, ReaderCore, WriterCore},
+    ReaderOptions,
+};
+use crate::{
+    async_io::{FuturesIo, RawRead, RawWrite},
+    digest::Digest,
+    error::Error,
+};
+use futures_io::{AsyncRead, AsyncSeek, AsyncWrite};
+use std::{
+    io::{self, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[derive(Debug)]
+/// Asynchronously reads Redshirt 2-protected data from an input stream.
+pub struct Reader<R>(ReaderCore<FuturesIo<R>>);
+
+#[derive(Debug)]
+/// Asynchronously writes Redshirt 2-protected data to an output stream.
+///
+/// The SHA-1 hash of the encoded data is written into the header by `Writer::finish`, which
+/// requires the output stream to support seeking. For output streams that cannot be rewound, use
+/// `BufferedWriter` instead.
+pub struct Writer<W>(WriterCore<FuturesIo<W>>);
+
+#[derive(Debug)]
+/// Asynchronously writes Redshirt 2-protected data to an output stream that cannot be rewound.
+///
+/// Since the header precedes the encoded data, a `BufferedWriter` holds all encoded data in memory
+/// until `BufferedWriter::finish` is called, at which point the header and encoded data are
+/// written to the output stream in order.
+pub struct BufferedWriter<W>(BufferedWriterCore<FuturesIo<W>>);
+
+impl<R: AsyncRead + AsyncSeek + Unpin> Reader<R> {
+    #[inline]
+    /// Creates a new reader from an input stream.
+    ///
+    /// This is equivalent to `Reader::with_options(src, &ReaderOptions::new())`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` under the same conditions as `v2::tokio::Reader::new`.
+    pub async fn new(src: R) -> Result<Self, Error> {
+        Self::with_options(src, &ReaderOptions::new()).await
+    }
+
+    #[inline]
+    /// Creates a new reader from an input stream, using the given set of options.
+    ///
+    /// If the options specify `Verification::Eager`, the entire stream is read and hashed before
+    /// this method returns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` under the same conditions as `ReaderOptions::from_reader`.
+    pub async fn with_options(src: R, options: &ReaderOptions) -> Result<Self, Error> {
+        ReaderCore::with_options(FuturesIo::new(src), options)
+            .await
+            .map(Self)
+    }
+}
+
+impl<R: AsyncRead + Unpin> Reader<R> {
+    #[inline]
+    /// Creates a new reader from an input stream that cannot be rewound, using the given set of
+    /// options.
+    ///
+    /// Since the hash of the encoded data can only be computed by reading the entire stream,
+    /// `Verification::Eager` is treated as `Verification::Lazy`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` under the same conditions as `ReaderOptions::from_stream`.
+    pub async fn from_stream(src: R, options: &ReaderOptions) -> Result<Self, Error> {
+        ReaderCore::from_stream(FuturesIo::new(src), options)
+            .await
+            .map(Self)
+    }
+}
+
+impl<R> Reader<R> {
+    #[inline]
+    /// Unwraps a `Reader`, returning its underlying reader.
+    pub fn into_inner(self) -> R {
+        self.0.into_inner().into_inner()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Reader<R> {
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_read(cx, buf)
+    }
+}
+
+impl<R: AsyncSeek + Unpin> AsyncSeek for Reader<R> {
+    #[inline]
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        self.get_mut().0.poll_seek(cx, pos)
+    }
+}
+
+impl<W: AsyncWrite + AsyncSeek + Unpin> Writer<W> {
+    #[inline]
+    /// Wraps an existing output stream and writes a Redshirt 2 header that is valid, but contains
+    /// an all-zero SHA-1 hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the header fails.
+    pub async fn new(dst: W) -> Result<Self, Error> {
+        WriterCore::new(FuturesIo::new(dst)).await.map(Self)
+    }
+
+    #[inline]
+    /// Writes the SHA-1 hash of all previously encoded data into the header, then unwraps the
+    /// `Writer`, returning its underlying writer and the SHA-1 hash.
+    ///
+    /// The underlying writer is flushed both before and after the hash is written, and is left
+    /// positioned at the end of the encoded data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if flushing the underlying writer, seeking within it, or writing the SHA-1
+    /// hash fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures::io::{AsyncWriteExt, Cursor};
+    /// use redshirt::v2::futures_io::Writer;
+    ///
+    /// # futures::executor::block_on(async {
+    /// let mut writer = Writer::new(Cursor::new(Vec::new())).await.unwrap();
+    /// writer.write_all(b"foobar").await.unwrap();
+    /// let (dst, digest) = writer.finish().await.unwrap();
+    /// # });
+    /// ```
+    pub async fn finish(self) -> Result<(W, Digest), Error> {
+        self.0
+            .finish()
+            .await
+            .map(|(dst, digest)| (dst.into_inner(), digest))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Writer<W> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_close(cx)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> BufferedWriter<W> {
+    #[inline]
+    /// Wraps an existing output stream. Nothing is written until `BufferedWriter::finish` is
+    /// called.
+    pub fn new(dst: W) -> Self {
+        Self(BufferedWriterCore::new(FuturesIo::new(dst)))
+    }
+
+    #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+    #[inline]
+    /// Wraps an existing output stream, spilling encoded data to a temporary file once more than
+    /// the configured memory limit is buffered. Nothing is written to the output stream until
+    /// `BufferedWriter::finish` is called.
+    ///
+    /// See `SpillOptions` for the costs of spilling. Writes may accept fewer bytes than given, so
+    /// that no more than the memory limit is buffered at once; a write that needs to spill returns
+    /// an `Err` if the temporary file cannot be created or written, in which case no data is lost.
+    pub fn with_spill(dst: W, options: &SpillOptions) -> Self {
+        Self(BufferedWriterCore::with_spill(FuturesIo::new(dst), options))
+    }
+
+    #[inline]
+    /// Writes a Redshirt 2 header containing the SHA-1 hash of all previously encoded data,
+    /// followed by the encoded data itself, then unwraps the `BufferedWriter`, returning its
+    /// underlying writer and the SHA-1 hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing to or flushing the underlying writer fails.
+    pub async fn finish(self) -> Result<(W, Digest), Error> {
+        self.0
+            .finish()
+            .await
+            .map(|(dst, digest)| (dst.into_inner(), digest))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for BufferedWriter<W> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_close(cx)
+    }
+}
+
+#[inline]
+/// Copies a Redshirt 2 stream from `src` to `dst` without decoding it, verifying its SHA-1 hash
+/// and returning it.
+///
+/// This is the asynchronous counterpart of `v2::copy_verified`. `src` need not be seekable, so
+/// this suits streams received over a network: the header and encoded data are written to `dst`
+/// verbatim as they arrive, and the hash is checked once `src` reaches its end. If it does not
+/// match, `Error::BadChecksum` is returned, and `dst` already holds the corrupt copy, which it is
+/// up to the caller to discard. Dropping the returned future cancels the copy, likewise leaving
+/// whatever was copied so far in `dst`.
+///
+/// # Errors
+///
+/// Returns an `Err` if any of the following occurs:
+///
+/// * An I/O error occurs;
+/// * The stream contains an invalid Redshirt 2 header;
+/// * The SHA-1 hash in the header is all zeroes, indicating that the stream was never finalised;
+/// * The SHA-1 hash in the header does not match that of the encoded data.
+///
+/// Nothing is written to `dst` if the header is invalid or unfinalised.
+pub async fn copy_verified<R, W>(src: R, dst: W) -> Result<Digest, Error>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    async_core::copy_verified(FuturesIo::new(src), FuturesIo::new(dst)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferedWriter, Reader, Writer};
+    #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+    use crate::v2::SpillOptions;
+    use crate::{
+        error::Error,
+        v2::{ReaderOptions, Verification},
+    };
+    use futures::{
+        executor,
+        io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, Cursor},
+    };
+    use std::io::SeekFrom;
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+    const MSG_ENC: &[u8] = b"REDSHRT2\x00\x34\x54\x26\x2B\x4A\xBF\x29\x1D\x0B\x8E\x60\xD9\xA1\x76\xE1\x14\x7D\xDF\x05\xD4\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+
+    #[test]
+    fn reader_read() {
+        executor::block_on(async {
+            let mut reader = Reader::new(Cursor::new(MSG_ENC)).await.unwrap();
+            let mut buffer = Vec::new();
+            let _ = reader.read_to_end(&mut buffer).await.unwrap();
+            assert_eq!(buffer, MSG_DEC);
+            assert_eq!(reader.seek(SeekFrom::Start(6)).await.unwrap(), 6);
+            buffer.clear();
+            let _ = reader.read_to_end(&mut buffer).await.unwrap();
+            assert_eq!(buffer, &MSG_DEC[6..]);
+        });
+    }
+
+    #[test]
+    fn reader_lazy_bad_checksum() {
+        executor::block_on(async {
+            let mut data = MSG_ENC.to_vec();
+            *data.last_mut().unwrap() ^= 1;
+            let mut options = ReaderOptions::new();
+            let _ = options.verification(Verification::Lazy);
+            let mut reader = Reader::from_stream(Cursor::new(data), &options)
+                .await
+                .unwrap();
+            let mut buffer = Vec::new();
+            let e = reader.read_to_end(&mut buffer).await.unwrap_err();
+            match e.into_inner().unwrap().downcast::<Error>().map(|e| *e) {
+                Ok(Error::BadChecksum { .. }) => (),
+                other => panic!("expected `Error::BadChecksum`, got {:?}", other),
+            }
+        });
+    }
+
+    #[test]
+    fn writer_write() {
+        executor::block_on(async {
+            let mut writer = Writer::new(Cursor::new(Vec::new())).await.unwrap();
+            writer.write_all(MSG_DEC).await.unwrap();
+            let (dst, _) = writer.finish().await.unwrap();
+            assert_eq!(dst.into_inner(), MSG_ENC);
+        });
+    }
+
+    #[test]
+    fn copy_verified() {
+        executor::block_on(async {
+            let mut dst = Vec::new();
+            let digest = super::copy_verified(MSG_ENC, &mut dst).await.unwrap();
+            assert_eq!(dst, MSG_ENC);
+            assert_eq!(digest.as_ref(), &MSG_ENC[9..29]);
+        });
+    }
+
+    #[test]
+    fn buffered_writer_write() {
+        executor::block_on(async {
+            let mut writer = BufferedWriter::new(Cursor::new(Vec::new()));
+            writer.write_all(MSG_DEC).await.unwrap();
+            let (dst, _) = writer.finish().await.unwrap();
+            assert_eq!(dst.into_inner(), MSG_ENC);
+        });
+    }
+
+    #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+    #[test]
+    fn buffered_writer_spill() {
+        executor::block_on(async {
+            let mut options = SpillOptions::new();
+            let _ = options.memory_limit(5);
+            let mut writer = BufferedWriter::with_spill(Cursor::new(Vec::new()), &options);
+            writer.write_all(MSG_DEC).await.unwrap();
+            let (dst, _) = writer.finish().await.unwrap();
+            assert_eq!(dst.into_inner(), MSG_ENC);
+        });
+    }
+}
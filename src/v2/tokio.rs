@@ -0,0 +1,841 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Asynchronous Redshirt 2 utilities for use with [Tokio].
+//!
+//! This module provides `Reader` and `Writer` types, which are the asynchronous counterparts of
+//! `v2::Reader` and `v2::Writer`, as well as a `BufferedWriter` type for output streams that
+//! cannot be rewound. `ByteStream` adapts a `Reader` into a stream of decoded chunks.
+//!
+//! `BlockingReader`, `decode_file_blocking` and `encode_file_blocking` instead run the synchronous
+//! types on Tokio's blocking threads, for callers who would rather not depend on the asynchronous
+//! implementations.
+//!
+//! # Finalising output
+//!
+//! There's no way to run asynchronous code when a value is dropped, so unlike `v2::Writer`, the
+//! asynchronous writers never write out the SHA-1 hash by themselves. Instead, you must call
+//! `Writer::finish` or `BufferedWriter::finish` once all data has been written:
+//!
+//! ```no_run
+//! use redshirt::v2::tokio::Writer;
+//! use tokio::{fs::File, io::AsyncWriteExt};
+//!
+//! # async fn example() {
+//! let file = File::create("User.usr").await.unwrap();
+//! let mut writer = Writer::new(file).await.unwrap();
+//! writer.write_all(b"foobar").await.unwrap();
+//! let (file, digest) = writer.finish().await.unwrap();
+//! # }
+//! ```
+//!
+//! A writer that is dropped without being finished does nothing; if it was a `Writer`, the header
+//! of the output is left with an all-zero hash, which `Reader` reports as `Error::Unfinalized`.
+//!
+//! [Tokio]: https://tokio.rs
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::blocking::BlockingReader;
+#[cfg(all(feature = "redshirt1", not(target_arch = "wasm32")))]
+pub use crate::blocking::{decode_file_blocking, encode_file_blocking};
+pub use crate::byte_stream::ByteStream;
+
+#[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+use super::SpillOptions;
+use super::{
+    async_core::{self, BufferedWriterCore, ReaderCore, WriterCore},
+    ReaderOptions,
+};
+use crate::{
+    async_io::{poll_read_buf, RawSeek, RawWrite, TokioIo},
+    digest::Digest,
+    error::Error,
+};
+use std::{
+    io::{self, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+#[derive(Debug)]
+/// Asynchronously reads Redshirt 2-protected data from an input stream.
+pub struct Reader<R>(ReaderCore<TokioIo<R>>);
+
+#[derive(Debug)]
+/// Asynchronously writes Redshirt 2-protected data to an output stream.
+///
+/// The SHA-1 hash of the encoded data is written into the header by `Writer::finish`, which
+/// requires the output stream to support seeking. For output streams that cannot be rewound, use
+/// `BufferedWriter` instead.
+pub struct Writer<W>(WriterCore<TokioIo<W>>);
+
+#[derive(Debug)]
+/// Asynchronously writes Redshirt 2-protected data to an output stream that cannot be rewound.
+///
+/// Since the header precedes the encoded data, a `BufferedWriter` holds all encoded data in memory
+/// until `BufferedWriter::finish` is called, at which point the header and encoded data are
+/// written to the output stream in order.
+pub struct BufferedWriter<W>(BufferedWriterCore<TokioIo<W>>);
+
+impl<R: AsyncRead + AsyncSeek + Unpin> Reader<R> {
+    #[inline]
+    /// Creates a new reader from an input stream.
+    ///
+    /// This is equivalent to `Reader::with_options(src, &ReaderOptions::new())`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any of the following occurs:
+    ///
+    /// * An I/O error occurs;
+    /// * The underlying reader produces an invalid Redshirt 2 header;
+    /// * The SHA-1 hash in the header is all zeroes, indicating that the stream was never
+    ///   finalised;
+    /// * The SHA-1 hash in the header does not match that of the encoded data.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::tokio::Reader;
+    /// use tokio::fs::File;
+    ///
+    /// # async fn example() {
+    /// let file = File::open("User.usr").await.unwrap();
+    /// let reader = Reader::new(file).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn new(src: R) -> Result<Self, Error> {
+        Self::with_options(src, &ReaderOptions::new()).await
+    }
+
+    #[inline]
+    /// Creates a new reader from an input stream, using the given set of options.
+    ///
+    /// If the options specify `Verification::Eager`, the entire stream is read and hashed before
+    /// this method returns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` under the same conditions as `ReaderOptions::from_reader`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::{tokio::Reader, ReaderOptions, Verification};
+    /// use tokio::fs::File;
+    ///
+    /// # async fn example() {
+    /// let file = File::open("User.usr").await.unwrap();
+    /// let mut options = ReaderOptions::new();
+    /// let _ = options.verification(Verification::Lazy);
+    /// let reader = Reader::with_options(file, &options).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn with_options(src: R, options: &ReaderOptions) -> Result<Self, Error> {
+        ReaderCore::with_options(TokioIo::new(src), options)
+            .await
+            .map(Self)
+    }
+}
+
+impl<R: AsyncRead + Unpin> Reader<R> {
+    #[inline]
+    /// Creates a new reader from an input stream that cannot be rewound, such as a socket, using
+    /// the given set of options.
+    ///
+    /// Since the hash of the encoded data can only be computed by reading the entire stream,
+    /// `Verification::Eager` is treated as `Verification::Lazy`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` under the same conditions as `ReaderOptions::from_stream`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::{tokio::Reader, ReaderOptions};
+    /// use tokio::io;
+    ///
+    /// # async fn example() {
+    /// let reader = Reader::from_stream(io::stdin(), &ReaderOptions::new())
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn from_stream(src: R, options: &ReaderOptions) -> Result<Self, Error> {
+        ReaderCore::from_stream(TokioIo::new(src), options)
+            .await
+            .map(Self)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Reader<File> {
+    #[inline]
+    /// Opens the file at `path` for reading and creates a new reader from it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the file cannot be opened, or under the same conditions as
+    /// `Reader::new`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::tokio::Reader;
+    ///
+    /// # async fn example() {
+    /// let reader = Reader::open("User.usr").await.unwrap();
+    /// # }
+    /// ```
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path).await.map_err(Error::Io)?;
+        Self::new(file).await
+    }
+}
+
+impl<R> Reader<R> {
+    #[inline]
+    /// Unwraps a `Reader`, returning its underlying reader.
+    pub fn into_inner(self) -> R {
+        self.0.into_inner().into_inner()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Converts a `Reader` into a stream of decoded data, such as for use as an HTTP response
+    /// body.
+    ///
+    /// Each chunk yielded by the stream is exactly `chunk_size` bytes long, except for the final
+    /// chunk, which may be shorter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::tokio::Reader;
+    /// use tokio::fs::File;
+    ///
+    /// # async fn example() {
+    /// let file = File::open("User.usr").await.unwrap();
+    /// let stream = Reader::new(file).await.unwrap().into_byte_stream(8192);
+    /// # }
+    /// ```
+    pub fn into_byte_stream(self, chunk_size: usize) -> ByteStream<Self> {
+        ByteStream::new(self, chunk_size)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Reader<R> {
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        poll_read_buf(&mut self.get_mut().0, cx, buf)
+    }
+}
+
+impl<R: AsyncSeek + Unpin> AsyncSeek for Reader<R> {
+    #[inline]
+    fn start_seek(self: Pin<&mut Self>, pos: SeekFrom) -> io::Result<()> {
+        self.get_mut().0.start_seek(pos)
+    }
+
+    #[inline]
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        self.get_mut().0.poll_complete(cx)
+    }
+}
+
+impl<W: AsyncWrite + AsyncSeek + Unpin> Writer<W> {
+    #[inline]
+    /// Wraps an existing output stream and writes a Redshirt 2 header that is valid, but contains
+    /// an all-zero SHA-1 hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing the header fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::tokio::Writer;
+    /// use tokio::fs::File;
+    ///
+    /// # async fn example() {
+    /// let file = File::create("User.usr").await.unwrap();
+    /// let writer = Writer::new(file).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn new(dst: W) -> Result<Self, Error> {
+        WriterCore::new(TokioIo::new(dst)).await.map(Self)
+    }
+
+    #[inline]
+    /// Writes the SHA-1 hash of all previously encoded data into the header, then unwraps the
+    /// `Writer`, returning its underlying writer and the SHA-1 hash.
+    ///
+    /// The underlying writer is flushed both before and after the hash is written, and is left
+    /// positioned at the end of the encoded data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if flushing the underlying writer, seeking within it, or writing the SHA-1
+    /// hash fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::tokio::Writer;
+    /// use tokio::fs::File;
+    ///
+    /// # async fn example() {
+    /// let file = File::create("User.usr").await.unwrap();
+    /// let writer = Writer::new(file).await.unwrap();
+    /// let (file, digest) = writer.finish().await.unwrap();
+    /// # }
+    /// ```
+    pub async fn finish(self) -> Result<(W, Digest), Error> {
+        self.0
+            .finish()
+            .await
+            .map(|(dst, digest)| (dst.into_inner(), digest))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Writer<File> {
+    #[inline]
+    /// Creates or truncates the file at `path` and creates a new writer for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the file cannot be created, or under the same conditions as
+    /// `Writer::new`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::tokio::Writer;
+    ///
+    /// # async fn example() {
+    /// let writer = Writer::create("User.usr").await.unwrap();
+    /// # }
+    /// ```
+    pub async fn create<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::create(path).await.map_err(Error::Io)?;
+        Self::new(file).await
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Writer<W> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_close(cx)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> BufferedWriter<W> {
+    #[inline]
+    /// Wraps an existing output stream. Nothing is written until `BufferedWriter::finish` is
+    /// called.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::tokio::BufferedWriter;
+    /// use tokio::io;
+    ///
+    /// let writer = BufferedWriter::new(io::stdout());
+    /// ```
+    pub fn new(dst: W) -> Self {
+        Self(BufferedWriterCore::new(TokioIo::new(dst)))
+    }
+
+    #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+    #[inline]
+    /// Wraps an existing output stream, spilling encoded data to a temporary file once more than
+    /// the configured memory limit is buffered. Nothing is written to the output stream until
+    /// `BufferedWriter::finish` is called.
+    ///
+    /// See `SpillOptions` for the costs of spilling. Writes may accept fewer bytes than given, so
+    /// that no more than the memory limit is buffered at once; a write that needs to spill returns
+    /// an `Err` if the temporary file cannot be created or written, in which case no data is lost.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::{tokio::BufferedWriter, SpillOptions};
+    /// use tokio::io;
+    ///
+    /// let mut options = SpillOptions::new();
+    /// let writer = BufferedWriter::with_spill(io::stdout(), options.memory_limit(1024 * 1024));
+    /// ```
+    pub fn with_spill(dst: W, options: &SpillOptions) -> Self {
+        Self(BufferedWriterCore::with_spill(TokioIo::new(dst), options))
+    }
+
+    #[inline]
+    /// Writes a Redshirt 2 header containing the SHA-1 hash of all previously encoded data,
+    /// followed by the encoded data itself, then unwraps the `BufferedWriter`, returning its
+    /// underlying writer and the SHA-1 hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if writing to or flushing the underlying writer fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use redshirt::v2::tokio::BufferedWriter;
+    /// use tokio::io::{self, AsyncWriteExt};
+    ///
+    /// # async fn example() {
+    /// let mut writer = BufferedWriter::new(io::stdout());
+    /// writer.write_all(b"foobar").await.unwrap();
+    /// let (stdout, digest) = writer.finish().await.unwrap();
+    /// # }
+    /// ```
+    pub async fn finish(self) -> Result<(W, Digest), Error> {
+        self.0
+            .finish()
+            .await
+            .map(|(dst, digest)| (dst.into_inner(), digest))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for BufferedWriter<W> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_close(cx)
+    }
+}
+
+#[inline]
+/// Copies a Redshirt 2 stream from `src` to `dst` without decoding it, verifying its SHA-1 hash
+/// and returning it.
+///
+/// This is the asynchronous counterpart of `v2::copy_verified`. `src` need not be seekable, so
+/// this suits streams received over a network: the header and encoded data are written to `dst`
+/// verbatim as they arrive, and the hash is checked once `src` reaches its end. If it does not
+/// match, `Error::BadChecksum` is returned, and `dst` already holds the corrupt copy, which it is
+/// up to the caller to discard. Dropping the returned future cancels the copy, likewise leaving
+/// whatever was copied so far in `dst`.
+///
+/// # Errors
+///
+/// Returns an `Err` if any of the following occurs:
+///
+/// * An I/O error occurs;
+/// * The stream contains an invalid Redshirt 2 header;
+/// * The SHA-1 hash in the header is all zeroes, indicating that the stream was never finalised;
+/// * The SHA-1 hash in the header does not match that of the encoded data.
+///
+/// Nothing is written to `dst` if the header is invalid or unfinalised.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::v2::tokio;
+///
+/// # async fn example(download: impl ::tokio::io::AsyncRead + Unpin) {
+/// let file = ::tokio::fs::File::create("User.usr.part").await.unwrap();
+/// match tokio::copy_verified(download, file).await {
+///     Ok(_) => ::tokio::fs::rename("User.usr.part", "User.usr").await.unwrap(),
+///     Err(_) => ::tokio::fs::remove_file("User.usr.part").await.unwrap(),
+/// }
+/// # }
+/// ```
+pub async fn copy_verified<R, W>(src: R, dst: W) -> Result<Digest, Error>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    async_core::copy_verified(TokioIo::new(src), TokioIo::new(dst)).await
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[inline]
+/// Reads and decodes the entire contents of the file at `path`.
+///
+/// # Errors
+///
+/// Returns an `Err` if the file cannot be opened or read, or under the same conditions as
+/// `Reader::new`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::v2::tokio;
+///
+/// # async fn example() {
+/// let data = tokio::decode_file("User.usr").await.unwrap();
+/// # }
+/// ```
+pub async fn decode_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, Error> {
+    let mut reader = Reader::open(path).await?;
+    let mut data = Vec::new();
+    let _ = reader.read_to_end(&mut data).await.map_err(Error::Io)?;
+    Ok(data)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[inline]
+/// Encodes `data` and writes it to the file at `path`, replacing its contents if it exists.
+///
+/// The SHA-1 hash is written into the header and the file is flushed before the returned future
+/// resolves. Returns the SHA-1 hash.
+///
+/// # Errors
+///
+/// Returns an `Err` if the file cannot be created or written.
+///
+/// # Examples
+///
+/// ```no_run
+/// use redshirt::v2::tokio;
+///
+/// # async fn example() {
+/// tokio::encode_file("User.usr", b"foobar").await.unwrap();
+/// # }
+/// ```
+pub async fn encode_file<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<Digest, Error> {
+    let mut writer = Writer::create(path).await?;
+    writer.write_all(data).await.map_err(Error::Io)?;
+    writer.finish().await.map(|(_, digest)| digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferedWriter, Reader, Writer};
+    #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+    use crate::v2::SpillOptions;
+    use crate::{
+        error::Error,
+        v2::{self, ReaderOptions, Verification, HEADER_LEN, MARKER_LEN},
+    };
+    use futures::{StreamExt, TryStreamExt};
+    use std::{
+        convert::TryFrom,
+        io::{Cursor, Read, SeekFrom},
+    };
+    use tokio::io::{self, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+    const MSG_DEC: &[u8] = b"Hello world!";
+    const MSG_ENC: &[u8] = b"REDSHRT2\x00\x34\x54\x26\x2B\x4A\xBF\x29\x1D\x0B\x8E\x60\xD9\xA1\x76\xE1\x14\x7D\xDF\x05\xD4\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+    const MSG_LEN: usize = 12;
+    const MSG_LEN_U64: u64 = MSG_LEN as u64;
+
+    #[tokio::test]
+    async fn reader_read() {
+        let mut reader = Reader::new(Cursor::new(MSG_ENC)).await.unwrap();
+        let mut buffer = Vec::new();
+        assert_eq!(reader.read_to_end(&mut buffer).await.unwrap(), MSG_LEN);
+        assert_eq!(buffer, MSG_DEC);
+        assert_eq!(
+            reader.seek(SeekFrom::Start(MSG_LEN_U64 / 2)).await.unwrap(),
+            MSG_LEN_U64 / 2
+        );
+    }
+
+    #[tokio::test]
+    async fn reader_max_payload_len() {
+        let mut options = ReaderOptions::new();
+        let _ = options.max_payload_len(4);
+        assert!(matches!(
+            Reader::with_options(Cursor::new(MSG_ENC), &options).await,
+            Err(Error::PayloadTooLarge {
+                limit: 4,
+                attempted: MSG_LEN_U64
+            })
+        ));
+        let mut reader = Reader::from_stream(MSG_ENC, &options).await.unwrap();
+        let mut buffer = Vec::new();
+        let e = reader.read_to_end(&mut buffer).await.unwrap_err();
+        assert!(matches!(
+            e.get_ref().and_then(|e| e.downcast_ref::<Error>()),
+            Some(&Error::PayloadTooLarge {
+                limit: 4,
+                attempted: 5
+            })
+        ));
+        assert_eq!(buffer, &MSG_DEC[..4]);
+        let _ = options.max_payload_len(MSG_LEN_U64);
+        let mut reader = Reader::with_options(Cursor::new(MSG_ENC), &options)
+            .await
+            .unwrap();
+        let mut buffer = Vec::new();
+        assert_eq!(reader.read_to_end(&mut buffer).await.unwrap(), MSG_LEN);
+        assert_eq!(buffer, MSG_DEC);
+    }
+
+    #[tokio::test]
+    async fn reader_bad_checksum() {
+        let mut data = MSG_ENC.to_vec();
+        data[HEADER_LEN] ^= 1;
+        match Reader::new(Cursor::new(data)).await {
+            Err(Error::BadChecksum { .. }) => (),
+            other => panic!("expected `Error::BadChecksum`, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn reader_stream_chunked() {
+        let (mut tx, rx) = io::duplex(3);
+        let send = tokio::spawn(async move {
+            for chunk in MSG_ENC.chunks(3) {
+                tx.write_all(chunk).await.unwrap();
+            }
+        });
+        let mut options = ReaderOptions::new();
+        let _ = options.verification(Verification::Lazy);
+        let mut reader = Reader::from_stream(rx, &options).await.unwrap();
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).await.unwrap();
+        send.await.unwrap();
+        assert_eq!(buffer, MSG_DEC);
+    }
+
+    #[tokio::test]
+    async fn reader_stream_bad_checksum() {
+        let mut data = MSG_ENC.to_vec();
+        data[HEADER_LEN] ^= 1;
+        let mut reader = Reader::from_stream(&data[..], &ReaderOptions::new())
+            .await
+            .unwrap();
+        let mut buffer = Vec::new();
+        let e = reader.read_to_end(&mut buffer).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        let e = reader.read_to_end(&mut buffer).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn writer_write() {
+        let mut writer = Writer::new(Cursor::new(Vec::new())).await.unwrap();
+        writer.write_all(MSG_DEC).await.unwrap();
+        let (dst, digest) = writer.finish().await.unwrap();
+        assert_eq!(dst.position(), u64::try_from(MSG_ENC.len()).unwrap());
+        assert_eq!(dst.get_ref(), MSG_ENC);
+        assert_eq!(digest.as_ref(), &MSG_ENC[MARKER_LEN..HEADER_LEN]);
+    }
+
+    #[tokio::test]
+    async fn writer_round_trip() {
+        let data = (0..40000_u32)
+            .map(|n| u8::try_from(n % 251).unwrap())
+            .collect::<Vec<_>>();
+        let mut writer = Writer::new(Cursor::new(Vec::new())).await.unwrap();
+        writer.write_all(&data).await.unwrap();
+        let (dst, digest) = writer.finish().await.unwrap();
+        let encoded = dst.into_inner();
+        assert_eq!(v2::verify(&encoded[..]).unwrap(), digest);
+        let mut decoded = Vec::new();
+        let _ = v2::Reader::new(Cursor::new(encoded))
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[tokio::test]
+    async fn writer_unfinished() {
+        let mut dst = Cursor::new(Vec::new());
+        {
+            let mut writer = Writer::new(&mut dst).await.unwrap();
+            writer.write_all(MSG_DEC).await.unwrap();
+        }
+        match v2::Reader::new(Cursor::new(dst.into_inner())) {
+            Err(Error::Unfinalized) => (),
+            other => panic!("expected `Error::Unfinalized`, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn writer_back_to_back() {
+        let mut dst = Cursor::new(b"pack".to_vec());
+        dst.set_position(4);
+        for msg in [MSG_DEC, b"foobar"] {
+            let mut writer = Writer::new(&mut dst).await.unwrap();
+            writer.write_all(msg).await.unwrap();
+            let _ = writer.finish().await.unwrap();
+        }
+        let pack = dst.into_inner();
+        let first = 4 + HEADER_LEN + MSG_LEN;
+        assert_eq!(&pack[..4], b"pack");
+        assert_eq!(&pack[4..first], MSG_ENC);
+        assert_eq!(&pack[first..], &v2::encode(b"foobar")[..]);
+        let mut src = Cursor::new(&pack[..]);
+        src.set_position(first as u64);
+        let mut buffer = Vec::new();
+        let mut reader = Reader::new(src).await.unwrap();
+        let _ = reader.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, b"foobar");
+        assert_eq!(reader.seek(SeekFrom::Start(3)).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn buffered_writer_write() {
+        let (tx, mut rx) = io::duplex(3);
+        let recv = tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            let _ = rx.read_to_end(&mut buffer).await.unwrap();
+            buffer
+        });
+        let mut writer = BufferedWriter::new(tx);
+        writer.write_all(MSG_DEC).await.unwrap();
+        let (tx, digest) = writer.finish().await.unwrap();
+        drop(tx);
+        assert_eq!(recv.await.unwrap(), MSG_ENC);
+        assert_eq!(digest.as_ref(), &MSG_ENC[MARKER_LEN..HEADER_LEN]);
+    }
+
+    #[cfg(all(feature = "tempfile", not(target_arch = "wasm32")))]
+    #[tokio::test]
+    async fn buffered_writer_spill() {
+        let dir = std::env::temp_dir().join(format!(
+            "redshirt-v2-tokio-{}-buffered_writer_spill",
+            std::process::id()
+        ));
+        std::fs::create_dir(&dir).unwrap();
+        let mut options = SpillOptions::new();
+        let _ = options.memory_limit(5).temp_dir(&dir);
+        let data = MSG_DEC.repeat(100);
+        let mut writer = BufferedWriter::with_spill(Cursor::new(Vec::new()), &options);
+        writer.write_all(&data).await.unwrap();
+        let (dst, _) = writer.finish().await.unwrap();
+        assert_eq!(dst.into_inner(), v2::encode(&data));
+        let (tx, rx) = io::duplex(3);
+        drop(rx);
+        let mut writer = BufferedWriter::with_spill(tx, &options);
+        writer.write_all(&data).await.unwrap();
+        assert!(writer.finish().await.is_err());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+        let mut writer = BufferedWriter::with_spill(Cursor::new(Vec::new()), &options);
+        writer.write_all(&MSG_DEC[..5]).await.unwrap();
+        assert!(writer.write_all(&MSG_DEC[5..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn copy_verified() {
+        let (mut tx, rx) = io::duplex(5);
+        let send = tokio::spawn(async move {
+            tx.write_all(MSG_ENC).await.unwrap();
+        });
+        let mut dst = Vec::new();
+        let digest = super::copy_verified(rx, &mut dst).await.unwrap();
+        send.await.unwrap();
+        assert_eq!(dst, MSG_ENC);
+        assert_eq!(digest.as_ref(), &MSG_ENC[MARKER_LEN..HEADER_LEN]);
+        let mut data = MSG_ENC.to_vec();
+        data[HEADER_LEN] ^= 1;
+        dst.clear();
+        match super::copy_verified(&data[..], &mut dst).await {
+            Err(Error::BadChecksum { .. }) => (),
+            other => panic!("expected `Error::BadChecksum`, got {:?}", other),
+        }
+        assert_eq!(dst, data);
+        dst.clear();
+        assert!(super::copy_verified(&MSG_ENC[1..], &mut dst).await.is_err());
+        assert!(dst.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reader_byte_stream() {
+        let chunks = Reader::new(Cursor::new(MSG_ENC))
+            .await
+            .unwrap()
+            .into_byte_stream(4)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.len() == 4));
+        assert_eq!(chunks.concat(), MSG_DEC);
+    }
+
+    #[tokio::test]
+    async fn reader_byte_stream_bad_checksum() {
+        let mut data = MSG_ENC.to_vec();
+        data[HEADER_LEN] ^= 1;
+        let mut stream = Reader::from_stream(&data[..], &ReaderOptions::new())
+            .await
+            .unwrap()
+            .into_byte_stream(5);
+        let mut results = Vec::new();
+        while let Some(res) = stream.next().await {
+            results.push(res);
+        }
+        assert_eq!(results.len(), 3);
+        assert!(results[..2].iter().all(Result::is_ok));
+        match results[2].as_ref().unwrap_err().get_ref() {
+            Some(e) => assert!(matches!(
+                e.downcast_ref::<Error>(),
+                Some(Error::BadChecksum { .. })
+            )),
+            None => panic!("expected `Error::BadChecksum`"),
+        }
+    }
+
+    #[tokio::test]
+    async fn file_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "redshirt-v2-tokio-{}-file_round_trip",
+            std::process::id()
+        ));
+        let digest = super::encode_file(&path, MSG_DEC).await.unwrap();
+        let encoded = std::fs::read(&path).unwrap();
+        let decoded = super::decode_file(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(encoded, MSG_ENC);
+        assert_eq!(decoded, MSG_DEC);
+        assert_eq!(digest.as_ref(), &MSG_ENC[MARKER_LEN..HEADER_LEN]);
+    }
+}
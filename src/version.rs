@@ -0,0 +1,74 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::{v1, v2};
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+/// A Redshirt encoding scheme.
+pub enum Version {
+    /// Redshirt 1.
+    V1,
+    /// Redshirt 2.
+    V2,
+}
+
+impl Version {
+    #[inline]
+    #[must_use]
+    /// Identifies the encoding scheme of `data` from the marker at its start.
+    ///
+    /// Only the marker is checked; the rest of the header, and for Redshirt 2 the SHA-1 hash, are
+    /// not validated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use redshirt::Version;
+    ///
+    /// assert_eq!(Version::detect(b"REDSHRT2\x00"), Some(Version::V2));
+    /// assert_eq!(Version::detect(b"Hello world!"), None);
+    /// ```
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&v1::MARKER) {
+            Some(Version::V1)
+        } else if data.starts_with(&v2::MARKER) {
+            Some(Version::V2)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Version {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Version::V1 => "Redshirt 1",
+            Version::V2 => "Redshirt 2",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Version;
+
+    #[test]
+    fn detect() {
+        assert_eq!(Version::detect(b"REDSHIRT\x00\xC8"), Some(Version::V1));
+        assert_eq!(Version::detect(b"REDSHRT2\x00\x00"), Some(Version::V2));
+        assert_eq!(Version::detect(b"REDSHIRT"), None);
+        assert_eq!(Version::detect(b""), None);
+    }
+}
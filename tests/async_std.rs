@@ -0,0 +1,93 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use async_std::{fs::File, prelude::*, task};
+use redshirt::{v1, v2};
+use std::{env, fs, path::PathBuf};
+
+const MSG_DEC: &[u8] = b"Hello world!";
+const MSG_ENC_V1: &[u8] = b"REDSHIRT\x00\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+const MSG_ENC_V2: &[u8] = b"REDSHRT2\x00\x34\x54\x26\x2B\x4A\xBF\x29\x1D\x0B\x8E\x60\xD9\xA1\x76\xE1\x14\x7D\xDF\x05\xD4\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("redshirt-async-std-{}-{name}", std::process::id()))
+}
+
+#[test]
+fn v1_reader() {
+    let path = temp_path("v1_reader");
+    fs::write(&path, MSG_ENC_V1).unwrap();
+    let buffer = task::block_on(async {
+        let file = File::open(&path).await.unwrap();
+        let mut reader = v1::async_std::Reader::new(file).await.unwrap();
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).await.unwrap();
+        buffer
+    });
+    fs::remove_file(&path).unwrap();
+    assert_eq!(buffer, MSG_DEC);
+}
+
+#[test]
+fn v1_writer() {
+    let path = temp_path("v1_writer");
+    task::block_on(async {
+        let file = File::create(&path).await.unwrap();
+        let mut writer = v1::async_std::Writer::new(file).await.unwrap();
+        writer.write_all(MSG_DEC).await.unwrap();
+        writer.flush().await.unwrap();
+    });
+    let contents = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(contents, MSG_ENC_V1);
+}
+
+#[test]
+fn v2_reader() {
+    let path = temp_path("v2_reader");
+    fs::write(&path, MSG_ENC_V2).unwrap();
+    let buffer = task::block_on(async {
+        let file = File::open(&path).await.unwrap();
+        let mut reader = v2::async_std::Reader::new(file).await.unwrap();
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer).await.unwrap();
+        buffer
+    });
+    fs::remove_file(&path).unwrap();
+    assert_eq!(buffer, MSG_DEC);
+}
+
+#[test]
+fn v2_writer() {
+    let path = temp_path("v2_writer");
+    task::block_on(async {
+        let file = File::create(&path).await.unwrap();
+        let mut writer = v2::async_std::Writer::new(file).await.unwrap();
+        writer.write_all(MSG_DEC).await.unwrap();
+        let (file, digest) = writer.finish().await.unwrap();
+        file.sync_all().await.unwrap();
+        assert_eq!(digest.as_ref(), &MSG_ENC_V2[9..29]);
+    });
+    let contents = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(contents, MSG_ENC_V2);
+}
+
+#[test]
+fn v2_buffered_writer() {
+    let path = temp_path("v2_buffered_writer");
+    task::block_on(async {
+        let file = File::create(&path).await.unwrap();
+        let mut writer = v2::async_std::BufferedWriter::new(file);
+        writer.write_all(MSG_DEC).await.unwrap();
+        let (file, _) = writer.finish().await.unwrap();
+        file.sync_all().await.unwrap();
+    });
+    let contents = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(contents, MSG_ENC_V2);
+}
@@ -0,0 +1,101 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Checks the `Send` and `Sync` guarantees described in the crate documentation. These tests
+//! fail to compile, rather than fail at run time, if a guarantee is broken.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use redshirt::{v1, v2, BufferedReader, Digest, Error, Info, Version};
+use std::{
+    env,
+    fs::{self, File},
+    io::{Cursor, Read, Write},
+    process, thread,
+};
+
+fn assert_send<T: Send>() {}
+
+fn assert_sync<T: Sync>() {}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn value_types() {
+    assert_send_sync::<Error>();
+    assert_send_sync::<Digest>();
+    assert_send_sync::<Info>();
+    assert_send_sync::<Version>();
+    assert_send_sync::<v2::Verifier>();
+}
+
+#[test]
+fn file_streams() {
+    assert_send_sync::<v1::Reader<File>>();
+    assert_send_sync::<v1::Writer<File>>();
+    assert_send_sync::<v1::MultiWriter<File, File>>();
+    assert_send_sync::<v2::Reader<File>>();
+    assert_send_sync::<v2::Writer<File>>();
+    assert_send_sync::<v2::MultiWriter<File, File>>();
+    assert_send_sync::<v2::PrecomputedWriter<File>>();
+    assert_send_sync::<v2::SyncWriter<File>>();
+    assert_send_sync::<v2::HashingReader<File>>();
+    assert_send_sync::<BufferedReader<File>>();
+}
+
+#[cfg(all(feature = "mmap", unix))]
+#[test]
+fn mmap_writers() {
+    assert_send_sync::<v1::MmapWriter>();
+    assert_send_sync::<v2::MmapWriter>();
+}
+
+#[test]
+fn boxed_streams() {
+    assert_send::<v1::BoxedReader>();
+    assert_send::<v1::BoxedWriter>();
+    assert_send::<v2::BoxedReader>();
+    assert_send::<v2::BoxedWriter>();
+    assert_sync::<v1::Reader<Box<dyn redshirt::ReadSeek + Send + Sync>>>();
+    assert_sync::<v2::Writer<Box<dyn redshirt::WriteSeek + Send + Sync>>>();
+}
+
+#[test]
+fn boxed_readers_cross_threads() {
+    let readers = vec![
+        v1::BoxedReader::from_boxed(Cursor::new(v1::encode(b"Hello world!"))).unwrap(),
+        v1::BoxedReader::from_boxed(Cursor::new(&b"REDSHIRT\0\xc8\xe5\xec\xec\xef"[..])).unwrap(),
+    ];
+    let decoded = thread::spawn(move || {
+        readers
+            .into_iter()
+            .map(|mut reader| {
+                let mut decoded = String::new();
+                let _ = reader.read_to_string(&mut decoded).unwrap();
+                decoded
+            })
+            .collect::<Vec<_>>()
+    })
+    .join()
+    .unwrap();
+    assert_eq!(decoded, ["Hello world!", "Hello"]);
+}
+
+#[test]
+fn boxed_writers_cross_threads() {
+    let path = env::temp_dir().join(format!("redshirt-auto-traits-{}", process::id()));
+    let mut writer = v2::BoxedWriter::from_boxed(File::create(&path).unwrap()).unwrap();
+    writer.write_all(b"Hello world!").unwrap();
+    thread::spawn(move || writer.into_inner().unwrap())
+        .join()
+        .unwrap();
+    let mut reader = v2::BoxedReader::from_boxed(File::open(&path).unwrap()).unwrap();
+    let mut decoded = String::new();
+    let _ = reader.read_to_string(&mut decoded).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(decoded, "Hello world!");
+}
@@ -0,0 +1,42 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Builds `tests/capi/smoke.c` against the `cdylib` produced alongside this test, and runs it.
+
+#![cfg(unix)]
+
+use std::{env, path::PathBuf, process::Command};
+
+#[test]
+fn c_smoke_test() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let deps_dir = env::current_exe().unwrap().parent().unwrap().to_owned();
+    let out_dir = env::temp_dir().join(format!("redshirt-capi-{}", std::process::id()));
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let exe = out_dir.join("smoke");
+    let compiler = env::var_os("CC").unwrap_or_else(|| "cc".into());
+    let status = Command::new(compiler)
+        .arg(manifest_dir.join("tests/capi/smoke.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(&deps_dir)
+        .arg("-lredshirt")
+        .arg("-o")
+        .arg(&exe)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "failed to compile the C smoke test");
+    let status = Command::new(&exe)
+        .arg(out_dir.join("scratch.usr"))
+        .env("LD_LIBRARY_PATH", &deps_dir)
+        .env("DYLD_LIBRARY_PATH", &deps_dir)
+        .status()
+        .unwrap();
+    std::fs::remove_dir_all(&out_dir).unwrap();
+    assert!(status.success(), "the C smoke test failed");
+}
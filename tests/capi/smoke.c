@@ -0,0 +1,55 @@
+/*
+ * Copyright (c) 2019 FaultyRAM
+ *
+ * Licensed under the Apache License, Version 2.0
+ * <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+ * modified, or distributed except according to those terms.
+ */
+
+#include <redshirt.h>
+#include <stdio.h>
+#include <string.h>
+
+#define CHECK(expr)                                                                     \
+    do {                                                                                \
+        if (!(expr)) {                                                                  \
+            const char *message = redshirt_last_error_message();                        \
+            fprintf(stderr, "%s:%d: check failed: %s (%s)\n", __FILE__, __LINE__, #expr, \
+                    message ? message : "no error");                                    \
+            return 1;                                                                   \
+        }                                                                               \
+    } while (0)
+
+static int round_trip(const char *path, int version) {
+    static const char message[] = "Hello world!";
+    uint8_t *buf = NULL;
+    size_t len = 0;
+    CHECK(redshirt_encode_file(path, (const uint8_t *)message, strlen(message), version) ==
+          REDSHIRT_OK);
+    CHECK(redshirt_decode_file(path, &buf, &len) == REDSHIRT_OK);
+    CHECK(len == strlen(message) && memcmp(buf, message, len) == 0);
+    redshirt_free_buffer(buf, len);
+    return 0;
+}
+
+int main(int argc, char **argv) {
+    static const uint8_t expected[REDSHIRT_DIGEST_LEN] = {
+        0x34, 0x54, 0x26, 0x2B, 0x4A, 0xBF, 0x29, 0x1D, 0x0B, 0x8E,
+        0x60, 0xD9, 0xA1, 0x76, 0xE1, 0x14, 0x7D, 0xDF, 0x05, 0xD4,
+    };
+    uint8_t digest[REDSHIRT_DIGEST_LEN];
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <scratch file>\n", argv[0]);
+        return 2;
+    }
+    CHECK(redshirt_last_error_message() == NULL);
+    CHECK(round_trip(argv[1], 1) == 0);
+    CHECK(redshirt_verify(argv[1], digest) != REDSHIRT_OK);
+    CHECK(redshirt_last_error_message() != NULL);
+    CHECK(round_trip(argv[1], 2) == 0);
+    CHECK(redshirt_verify(argv[1], digest) == REDSHIRT_OK);
+    CHECK(memcmp(digest, expected, sizeof(expected)) == 0);
+    CHECK(redshirt_encode_file(argv[1], NULL, 0, 3) == REDSHIRT_ERROR_INVALID_ARGUMENT);
+    return 0;
+}
@@ -0,0 +1,502 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Drives the `redshirt` binary against the fixtures in `tests/fixtures`.
+
+use assert_cmd::Command;
+use redshirt::v2;
+use std::{env, fs, path::PathBuf, process};
+
+const HELLO: &[u8] = b"Hello world!";
+const HELLO_V1: &str = "tests/fixtures/hello.v1.dat";
+const HELLO_V2: &str = "tests/fixtures/hello.v2.dat";
+
+fn redshirt() -> Command {
+    Command::cargo_bin("redshirt").unwrap()
+}
+
+fn stderr(command: &mut Command) -> String {
+    let output = command.output().unwrap();
+    String::from_utf8(output.stderr).unwrap()
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("redshirt-cli-{}-{name}", process::id()))
+}
+
+/// Creates a directory tree containing both fixtures and a plain text file.
+fn temp_tree(name: &str) -> PathBuf {
+    let root = temp_path(name);
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("users/agent")).unwrap();
+    let _ = fs::copy(HELLO_V1, root.join("users/one.dat")).unwrap();
+    let _ = fs::copy(HELLO_V2, root.join("users/agent/two.usr")).unwrap();
+    fs::write(root.join("users/readme.txt"), HELLO).unwrap();
+    root
+}
+
+#[test]
+fn decode_v1_to_stdout() {
+    let _ = redshirt()
+        .args(["decode", HELLO_V1, "-"])
+        .assert()
+        .success()
+        .stdout(HELLO);
+}
+
+#[test]
+fn decode_v2_to_stdout() {
+    let _ = redshirt()
+        .args(["decode", HELLO_V2, "-"])
+        .assert()
+        .success()
+        .stdout(HELLO);
+}
+
+#[test]
+fn encode_v1_from_stdin() {
+    let _ = redshirt()
+        .args(["encode", "-", "-", "--v1"])
+        .write_stdin(HELLO)
+        .assert()
+        .success()
+        .stdout(fs::read(HELLO_V1).unwrap());
+}
+
+#[test]
+fn encode_v2_from_stdin() {
+    let _ = redshirt()
+        .args(["encode", "--v2", "-", "-"])
+        .write_stdin(HELLO)
+        .assert()
+        .success()
+        .stdout(fs::read(HELLO_V2).unwrap());
+}
+
+#[test]
+fn decode_v2_from_stdin() {
+    let _ = redshirt()
+        .args(["decode", "-", "-"])
+        .write_stdin(fs::read(HELLO_V2).unwrap())
+        .assert()
+        .success()
+        .stdout(HELLO);
+}
+
+#[test]
+fn decode_bad_checksum_from_stdin() {
+    let mut encoded = fs::read(HELLO_V2).unwrap();
+    let last = encoded.len() - 1;
+    encoded[last] ^= 1;
+    let mut command = redshirt();
+    let _ = command.args(["decode", "-", "-"]).write_stdin(encoded);
+    let _ = command.assert().code(1);
+    assert!(stderr(&mut command).contains("<stdin>: bad checksum"));
+}
+
+#[test]
+fn encode_decode_files() {
+    let encoded = temp_path("encoded.dat");
+    let decoded = temp_path("decoded.dat");
+    let _ = redshirt()
+        .arg("encode")
+        .arg(HELLO_V1)
+        .arg(&encoded)
+        .arg("--v2")
+        .assert()
+        .success();
+    let _ = redshirt()
+        .arg("decode")
+        .arg(&encoded)
+        .arg(&decoded)
+        .assert()
+        .success();
+    assert_eq!(fs::read(&decoded).unwrap(), fs::read(HELLO_V1).unwrap());
+    fs::remove_file(encoded).unwrap();
+    fs::remove_file(decoded).unwrap();
+}
+
+#[test]
+fn verify_ok() {
+    let _ = redshirt().args(["verify", HELLO_V1]).assert().success();
+    let _ = redshirt()
+        .args(["verify", HELLO_V2])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{HELLO_V2}: OK (Redshirt 2, SHA-1 3454262b4abf291d0b8e60d9a176e1147ddf05d4)\n"
+        ));
+}
+
+#[test]
+fn verify_bad_checksum() {
+    let mut encoded = fs::read(HELLO_V2).unwrap();
+    *encoded.last_mut().unwrap() ^= 0xFF;
+    let mut command = redshirt();
+    let _ = command.args(["verify", "-"]).write_stdin(encoded);
+    let _ = command.assert().code(1);
+    let message = stderr(&mut command);
+    assert!(message.contains("bad checksum (expected 3454262b"));
+}
+
+#[test]
+fn verify_bad_header() {
+    let mut command = redshirt();
+    let _ = command.args(["verify", "-"]).write_stdin(HELLO);
+    let _ = command.assert().code(1);
+    let message = stderr(&mut command);
+    assert!(message.contains("bad header (found 48 65 6c 6c 6f 20 77 6f 72,"));
+}
+
+#[test]
+fn decode_bad_header() {
+    let _ = redshirt()
+        .args(["decode", "-", "-"])
+        .write_stdin(HELLO)
+        .assert()
+        .code(1)
+        .stdout("");
+}
+
+#[test]
+fn missing_file() {
+    let mut command = redshirt();
+    let _ = command.args(["decode", "tests/fixtures/missing.dat", "-"]);
+    let _ = command.assert().code(1);
+    assert!(stderr(&mut command).starts_with("error: tests/fixtures/missing.dat: "));
+}
+
+#[test]
+fn usage_errors() {
+    let _ = redshirt().assert().code(2);
+    let _ = redshirt().args(["encode", "-", "-"]).assert().code(2);
+    let _ = redshirt().args(["decode", "-"]).assert().code(2);
+    let _ = redshirt().args(["verify", "-", "-"]).assert().code(2);
+    let _ = redshirt().args(["frobnicate"]).assert().code(2);
+    let _ = redshirt().args(["verify", "--v3", "-"]).assert().code(2);
+    let _ = redshirt()
+        .args(["verify", "--strict", "-"])
+        .assert()
+        .code(2);
+    let _ = redshirt()
+        .args(["encode", "-r", "--strict", "--v1", "a", "b"])
+        .assert()
+        .code(2);
+    let _ = redshirt().args(["verify", "--json", "-"]).assert().code(2);
+    let _ = redshirt()
+        .args(["manifest", "update", "a", "b"])
+        .assert()
+        .code(2);
+    let _ = redshirt().args(["info", "--rehash", "-"]).assert().code(2);
+    let _ = redshirt()
+        .args(["decode", "-n", "4", "-", "-"])
+        .assert()
+        .code(2);
+    let _ = redshirt()
+        .args(["strings", "-n", "four", "-"])
+        .assert()
+        .code(2);
+    let _ = redshirt().arg("--help").assert().success();
+}
+
+#[test]
+fn decode_recursive() {
+    let root = temp_tree("decode-recursive");
+    let mut command = redshirt();
+    let _ = command
+        .args(["decode", "--recursive"])
+        .arg(root.join("users"))
+        .arg(root.join("out"));
+    let output = command.output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("two.usr: decoded (Redshirt 2)"));
+    assert!(stdout.contains("readme.txt: skipped"));
+    assert!(stdout.ends_with("2 ok, 0 failed, 1 skipped\n"));
+    assert_eq!(fs::read(root.join("out/one.dat")).unwrap(), HELLO);
+    assert_eq!(fs::read(root.join("out/agent/two.usr")).unwrap(), HELLO);
+    assert!(!root.join("out/readme.txt").exists());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn encode_recursive() {
+    let root = temp_tree("encode-recursive");
+    let mut command = redshirt();
+    let _ = command
+        .args(["encode", "-r", "--v2"])
+        .arg(root.join("users"))
+        .arg(root.join("out"));
+    let output = command.output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("readme.txt: encoded (Redshirt 2)"));
+    assert!(stdout.ends_with("3 ok, 0 failed, 0 skipped\n"));
+    assert_eq!(
+        fs::read(root.join("out/readme.txt")).unwrap(),
+        fs::read(HELLO_V2).unwrap()
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn verify_recursive_failures() {
+    let root = temp_tree("verify-recursive");
+    let mut corrupt = fs::read(HELLO_V2).unwrap();
+    *corrupt.last_mut().unwrap() ^= 0xFF;
+    fs::write(root.join("users/agent/two.usr"), corrupt).unwrap();
+    let mut command = redshirt();
+    let _ = command.args(["verify", "-r"]).arg(root.join("users"));
+    let output = command.output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with("1 ok, 1 failed, 1 skipped\n"));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("two.usr: bad checksum"));
+    let mut command = redshirt();
+    let _ = command
+        .args(["verify", "-r", "--fail-fast"])
+        .arg(root.join("users"));
+    let stdout = String::from_utf8(command.output().unwrap().stdout).unwrap();
+    assert!(stdout.ends_with("0 ok, 1 failed, 0 skipped\n"));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn verify_recursive_strict() {
+    let root = temp_tree("verify-strict");
+    let mut command = redshirt();
+    let _ = command
+        .args(["verify", "--recursive", "--strict"])
+        .arg(root.join("users"));
+    let output = command.output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with("2 ok, 1 failed, 0 skipped\n"));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn info_v2() {
+    let _ = redshirt()
+        .args(["info", HELLO_V2])
+        .assert()
+        .success()
+        .stdout(
+            "version      v2\n\
+         payload_len  12\n\
+         digest       3454262b4abf291d0b8e60d9a176e1147ddf05d4\n\
+         checksum_ok  true\n",
+        );
+}
+
+#[test]
+fn info_json() {
+    let _ = redshirt()
+        .args(["info", "--json", HELLO_V1])
+        .assert()
+        .success()
+        .stdout(
+            "{ \"version\": \"v1\", \"payload_len\": 12, \"digest\": null, \"checksum_ok\": null }\n",
+        );
+    let _ = redshirt()
+        .args(["info", "--json", "--no-verify", "-"])
+        .write_stdin(fs::read(HELLO_V2).unwrap())
+        .assert()
+        .success()
+        .stdout(
+            "{ \"version\": \"v2\", \"payload_len\": 12, \
+             \"digest\": \"3454262b4abf291d0b8e60d9a176e1147ddf05d4\", \"checksum_ok\": null }\n",
+        );
+}
+
+#[test]
+fn info_exit_codes() {
+    let mut corrupt = fs::read(HELLO_V2).unwrap();
+    *corrupt.last_mut().unwrap() ^= 0xFF;
+    let _ = redshirt()
+        .args(["info", "--json", "-"])
+        .write_stdin(corrupt.clone())
+        .assert()
+        .code(4)
+        .stdout(
+            "{ \"version\": \"v2\", \"payload_len\": 12, \
+             \"digest\": \"3454262b4abf291d0b8e60d9a176e1147ddf05d4\", \"checksum_ok\": false }\n",
+        );
+    let _ = redshirt()
+        .args(["info", "--no-verify", "-"])
+        .write_stdin(corrupt)
+        .assert()
+        .success();
+    let _ = redshirt()
+        .args(["info", "-"])
+        .write_stdin(HELLO)
+        .assert()
+        .code(3)
+        .stdout("");
+    let _ = redshirt()
+        .args(["info", "tests/fixtures/missing.dat"])
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn manifest_create_check() {
+    let root = temp_tree("manifest");
+    let manifest = root.join("users.manifest");
+    let _ = redshirt()
+        .args(["manifest", "create"])
+        .arg(root.join("users"))
+        .arg(&manifest)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&manifest).unwrap(),
+        "# redshirt manifest\n\
+         3454262b4abf291d0b8e60d9a176e1147ddf05d4 v2 12 agent/two.usr\n\
+         3454262b4abf291d0b8e60d9a176e1147ddf05d4 v1 12 one.dat\n"
+    );
+    let _ = redshirt()
+        .args(["manifest", "check"])
+        .arg(root.join("users"))
+        .arg(&manifest)
+        .assert()
+        .success()
+        .stdout("0 missing, 0 added, 0 modified\n");
+    let mut corrupt = fs::read(HELLO_V2).unwrap();
+    *corrupt.last_mut().unwrap() ^= 0xFF;
+    fs::write(root.join("users/agent/two.usr"), corrupt).unwrap();
+    fs::remove_file(root.join("users/one.dat")).unwrap();
+    let mut command = redshirt();
+    let _ = command
+        .args(["manifest", "check"])
+        .arg(root.join("users"))
+        .arg(&manifest);
+    let output = command.output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with("1 missing, 0 added, 0 modified\n"));
+    let mut command = redshirt();
+    let _ = command
+        .args(["manifest", "check", "--rehash"])
+        .arg(root.join("users"))
+        .arg(&manifest);
+    let stdout = String::from_utf8(command.output().unwrap().stdout).unwrap();
+    assert!(stdout.contains("two.usr: modified (expected SHA-1 3454262b"));
+    assert!(stdout.ends_with("1 missing, 0 added, 1 modified\n"));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn strings() {
+    let _ = redshirt()
+        .args(["strings", HELLO_V1])
+        .assert()
+        .success()
+        .stdout("00000000  Hello world!\n");
+    let mut corrupt = v2::encode(b"\x00\x01agent\x00xyz\x02Redshirt");
+    *corrupt.last_mut().unwrap() ^= 0xFF;
+    let _ = redshirt()
+        .args(["strings", "--min-len", "5", "-"])
+        .write_stdin(corrupt)
+        .assert()
+        .success()
+        .stdout("00000002  agent\n0000000c  Redshir\n");
+    let _ = redshirt()
+        .args(["strings", "-"])
+        .write_stdin(HELLO)
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn salvage() {
+    let encoded = fs::read(HELLO_V2).unwrap();
+    let mut command = redshirt();
+    let _ = command
+        .args(["salvage", "-", "-"])
+        .write_stdin(&encoded[..encoded.len() - 7]);
+    let _ = command.assert().success().stdout("Hello");
+    let message = stderr(&mut command);
+    assert!(message.starts_with(
+        "<stdin>: recovered 5 bytes; the SHA-1 hash does not match (expected 3454262b"
+    ));
+    let decoded = temp_path("salvaged");
+    let mut command = redshirt();
+    let _ = command.arg("salvage").arg(HELLO_V2).arg(&decoded);
+    let _ = command.assert().success();
+    assert_eq!(
+        stderr(&mut command),
+        format!("{HELLO_V2}: recovered 12 bytes; the SHA-1 hash matches\n")
+    );
+    assert_eq!(fs::read(&decoded).unwrap(), HELLO);
+    fs::remove_file(decoded).unwrap();
+    let _ = redshirt()
+        .args(["salvage", "-", "-"])
+        .write_stdin(&encoded[..4])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("<stdin>: recovered 0 bytes; the file ends within its header\n");
+    let _ = redshirt().args(["salvage", HELLO_V1, "-"]).assert().code(1);
+}
+
+#[test]
+fn scan() {
+    let mut dump = b"junk".to_vec();
+    dump.extend_from_slice(&fs::read(HELLO_V2).unwrap());
+    dump.extend_from_slice(&fs::read(HELLO_V1).unwrap());
+    dump.extend_from_slice(&v2::encode(HELLO)[..20]);
+    let _ = redshirt()
+        .args(["scan", "-"])
+        .write_stdin(dump.clone())
+        .assert()
+        .success()
+        .stdout("00000004  v2  ok\n0000002d  v1\n00000042  v2  bad checksum\n");
+    let _ = redshirt()
+        .args(["scan", "--no-verify", "-"])
+        .write_stdin(dump)
+        .assert()
+        .success()
+        .stdout("00000004  v2\n0000002d  v1\n00000042  v2\n");
+    let _ = redshirt()
+        .args(["scan", "-"])
+        .write_stdin(HELLO)
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn diff() {
+    let _ = redshirt()
+        .args(["diff", HELLO_V1, HELLO_V2])
+        .assert()
+        .success()
+        .stdout("");
+    let _ = redshirt()
+        .args(["diff", HELLO_V1, "-"])
+        .write_stdin(v2::encode(b"Hello there!"))
+        .assert()
+        .code(1)
+        .stdout(format!("{HELLO_V1} and <stdin> differ at offset 6\n"));
+    let _ = redshirt()
+        .args(["diff", "-", HELLO_V2])
+        .write_stdin(v2::encode(b"Hello"))
+        .assert()
+        .code(1)
+        .stdout(format!(
+            "<stdin> and {HELLO_V2} differ in length (5 and 12 bytes)\n"
+        ));
+    let _ = redshirt()
+        .args(["diff", HELLO_V1, "-"])
+        .write_stdin(HELLO)
+        .assert()
+        .code(1);
+    let _ = redshirt().args(["diff", "-", "-"]).assert().code(2);
+}
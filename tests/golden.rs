@@ -0,0 +1,273 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Checks the crate against the golden files in `tests/fixtures/uplink`, which are laid out like
+//! files written by Uplink: an `options` file and a small save, both encoded with Redshirt 2, and
+//! a `debug.log` encoded with Redshirt 1. Each `.decoded` file holds the expected decoded
+//! contents.
+//!
+//! Game data is not redistributed, so the fixtures are synthesised by the generators below, which
+//! build each structure byte by byte without using this crate. `generated_match_fixtures` checks
+//! that the committed files are exactly what the generators produce; to rebuild them, run
+//! `cargo test --test golden -- --ignored`.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use redshirt::{v1, v2, Version};
+use std::{
+    convert::TryFrom,
+    fs,
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// A golden file: its name in `tests/fixtures/uplink`, its encoding scheme, and a generator for
+/// its decoded contents.
+struct Fixture {
+    name: &'static str,
+    version: Version,
+    generate: fn() -> Vec<u8>,
+}
+
+const FIXTURES: [Fixture; 3] = [
+    Fixture {
+        name: "options",
+        version: Version::V2,
+        generate: options,
+    },
+    Fixture {
+        name: "agent.usr",
+        version: Version::V2,
+        generate: save,
+    },
+    Fixture {
+        name: "debug.log",
+        version: Version::V1,
+        generate: debug_log,
+    },
+];
+
+impl Fixture {
+    fn path(&self) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/uplink")
+            .join(self.name)
+    }
+
+    fn encoded(&self) -> Vec<u8> {
+        fs::read(self.path()).unwrap()
+    }
+
+    fn decoded(&self) -> Vec<u8> {
+        fs::read(self.path().with_file_name(format!("{}.decoded", self.name))).unwrap()
+    }
+}
+
+/// Builds decoded Uplink data field by field, in the game's little-endian layout.
+#[derive(Default)]
+struct Layout(Vec<u8>);
+
+impl Layout {
+    fn bytes(mut self, bytes: &[u8]) -> Self {
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn int(self, n: i32) -> Self {
+        self.bytes(&n.to_le_bytes())
+    }
+
+    fn bool(self, b: bool) -> Self {
+        self.bytes(&[u8::from(b)])
+    }
+
+    /// A string, prefixed with its length including the null terminator.
+    fn string(self, s: &str) -> Self {
+        self.int(i32::try_from(s.len() + 1).unwrap())
+            .bytes(s.as_bytes())
+            .bytes(b"\0")
+    }
+
+    /// A string that is null, which is written as a length of -1.
+    fn null_string(self) -> Self {
+        self.int(-1)
+    }
+}
+
+/// An `options` file: the version tag, then a `BTree` of options keyed by name. The names are in
+/// ascending order, so each node is the right child of the one before, and is written in
+/// pre-order with a marker for every missing child.
+fn options() -> Vec<u8> {
+    let entries = [
+        (
+            "game_debugstart",
+            "Start the game in debug mode",
+            true,
+            false,
+            0,
+            None,
+        ),
+        (
+            "graphics_fullscreen",
+            "Run the game full screen",
+            true,
+            true,
+            1,
+            None,
+        ),
+        (
+            "graphics_screenwidth",
+            "Width of the screen",
+            false,
+            true,
+            1024,
+            None,
+        ),
+        (
+            "theme_name",
+            "Interface theme",
+            false,
+            false,
+            0,
+            Some("graphics"),
+        ),
+    ];
+    let mut layout = Layout::default().bytes(b"SAV62\0");
+    for (name, tooltip, yes_or_no, visible, value, text) in entries {
+        layout = layout
+            .bool(true)
+            .string(name)
+            .string(tooltip)
+            .bool(yes_or_no)
+            .bool(visible)
+            .int(value);
+        layout = match text {
+            Some(text) => layout.string(text),
+            None => layout.null_string(),
+        };
+        // Each node has no left child.
+        layout = layout.bool(false);
+    }
+    // The last node has no right child.
+    layout.bool(false).0
+}
+
+/// The start of a save: the version tag, the agent's handle and password, and a few of the
+/// integer fields that follow them.
+fn save() -> Vec<u8> {
+    Layout::default()
+        .bytes(b"SAV62\0")
+        .string("agent")
+        .string("rosebud")
+        .int(1_000)
+        .int(2)
+        .bool(true)
+        .int(0)
+        .0
+}
+
+/// A short `debug.log`, which is plain text.
+fn debug_log() -> Vec<u8> {
+    b"Uplink version 1.55\r\n\
+      Loading application data\r\n\
+      Loading options from users/options\r\n\
+      Loading user agent\r\n\
+      Saving user agent\r\n"
+        .to_vec()
+}
+
+#[test]
+fn generated_match_fixtures() {
+    for fixture in &FIXTURES {
+        assert_eq!(fixture.decoded(), (fixture.generate)(), "{}", fixture.name);
+    }
+}
+
+#[test]
+fn decode_byte_for_byte() {
+    for fixture in &FIXTURES {
+        let encoded = fixture.encoded();
+        assert_eq!(
+            Version::detect(&encoded),
+            Some(fixture.version),
+            "{}",
+            fixture.name
+        );
+        let decoded = match fixture.version {
+            Version::V1 => v1::decode(&encoded),
+            Version::V2 => v2::decode(&encoded),
+        }
+        .unwrap();
+        assert_eq!(decoded, fixture.decoded(), "{}", fixture.name);
+    }
+}
+
+#[test]
+fn reencode_identically() {
+    for fixture in &FIXTURES {
+        let decoded = fixture.decoded();
+        let encoded = match fixture.version {
+            Version::V1 => v1::encode(&decoded),
+            Version::V2 => v2::encode(&decoded),
+        };
+        assert_eq!(encoded, fixture.encoded(), "{}", fixture.name);
+    }
+}
+
+#[test]
+fn v2_writer_output_is_readable() {
+    for fixture in &FIXTURES {
+        let decoded = fixture.decoded();
+        let mut writer = v2::Writer::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_all(&decoded).unwrap();
+        let encoded = writer.into_inner().unwrap().into_inner();
+        if fixture.version == Version::V2 {
+            assert_eq!(encoded, fixture.encoded(), "{}", fixture.name);
+        }
+        let mut reader = v2::Reader::new(Cursor::new(encoded)).unwrap();
+        let mut read = Vec::new();
+        let _ = reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, decoded, "{}", fixture.name);
+    }
+}
+
+#[cfg(feature = "uplink")]
+#[test]
+fn uplink_structures() {
+    use redshirt::uplink::{probe_save_version, Options};
+
+    let options = Options::read_from(Cursor::new(FIXTURES[0].decoded())).unwrap();
+    assert_eq!(options.version().as_deref(), Some("SAV62"));
+    assert_eq!(options.get_int("graphics_screenwidth"), Some(1024));
+    assert_eq!(options.get_string("theme_name"), Some("graphics"));
+    let mut rewritten = Vec::new();
+    options.write_to(&mut rewritten).unwrap();
+    assert_eq!(rewritten, FIXTURES[0].decoded());
+    let version = probe_save_version(&FIXTURES[1].decoded()[..]).unwrap();
+    assert_eq!(version.as_deref(), Some("SAV62"));
+}
+
+#[test]
+#[ignore = "rewrites the golden files"]
+fn regenerate() {
+    for fixture in &FIXTURES {
+        let decoded = (fixture.generate)();
+        let encoded = match fixture.version {
+            Version::V1 => v1::encode(&decoded),
+            Version::V2 => v2::encode(&decoded),
+        };
+        fs::create_dir_all(fixture.path().parent().unwrap()).unwrap();
+        fs::write(fixture.path(), encoded).unwrap();
+        fs::write(
+            fixture
+                .path()
+                .with_file_name(format!("{}.decoded", fixture.name)),
+            decoded,
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,66 @@
+# Copyright (c) 2019 FaultyRAM
+#
+# Licensed under the Apache License, Version 2.0
+# <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+# <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+# modified, or distributed except according to those terms.
+
+# Run with `maturin develop && python -m pytest tests/python`.
+
+import pytest
+
+import redshirt
+
+DATA = b"Hello, world!"
+
+
+@pytest.mark.parametrize("version", [1, 2])
+def test_round_trip(version):
+    encoded = redshirt.encode(DATA, version)
+    assert redshirt.decode(encoded) == DATA
+    assert redshirt.decode(encoded, version) == DATA
+
+
+def test_encode_v1():
+    assert redshirt.encode(DATA, 1) == b"REDSHIRT\0" + bytes(b ^ 0x80 for b in DATA)
+
+
+def test_encode_bad_version():
+    with pytest.raises(ValueError):
+        redshirt.encode(DATA, 3)
+
+
+def test_decode_bad_header():
+    with pytest.raises(ValueError):
+        redshirt.decode(b"NOTREDSHIRT" + DATA)
+
+
+def test_decode_bad_checksum():
+    encoded = bytearray(redshirt.encode(DATA, 2))
+    encoded[-1] ^= 0xFF
+    with pytest.raises(ValueError):
+        redshirt.decode(bytes(encoded))
+
+
+@pytest.mark.parametrize("version", [1, 2])
+def test_file_round_trip(tmp_path, version):
+    path = tmp_path / "data.dat"
+    redshirt.encode_file(path, DATA, version)
+    assert redshirt.decode_file(path) == DATA
+    assert redshirt.decode_file(str(path), version) == DATA
+
+
+def test_verify(tmp_path):
+    path = tmp_path / "data.dat"
+    redshirt.encode_file(path, DATA, 2)
+    digest = redshirt.verify(path)
+    assert len(digest) == 40
+    assert digest == redshirt.verify(str(path))
+    int(digest, 16)
+
+
+def test_missing_file(tmp_path):
+    with pytest.raises(OSError):
+        redshirt.decode_file(tmp_path / "missing.dat")
+    with pytest.raises(OSError):
+        redshirt.verify(tmp_path / "missing.dat")
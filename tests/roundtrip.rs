@@ -0,0 +1,218 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use proptest::{
+    collection::vec, num::u64, prop_assert_eq, prop_oneof, proptest, strategy::Strategy,
+    test_runner::Config,
+};
+use redshirt::{roundtrip_identity, v1, v2};
+use std::{
+    fs,
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+};
+
+const MAX_LEN: usize = 1 << 20;
+const V2_HEADER_LEN: usize = 29;
+
+/// Payloads up to 1 MiB, weighted towards lengths around the 16 KiB internal buffer.
+///
+/// Generating large payloads byte by byte is slow, so each payload is filled from a seeded
+/// xorshift generator; shrinking reduces its length.
+fn payload() -> impl Strategy<Value = Vec<u8>> {
+    let len = prop_oneof![0..64usize, 16 * 1024 - 64..16 * 1024 + 64usize, 0..MAX_LEN];
+    (len, u64::ANY).prop_map(|(len, seed)| {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state.to_le_bytes()[0]
+            })
+            .collect()
+    })
+}
+
+/// Non-zero chunk lengths, used cyclically.
+fn chunks() -> impl Strategy<Value = Vec<usize>> {
+    vec(prop_oneof![1..64usize, 1..64 * 1024usize], 1..16)
+}
+
+/// Splits `data` at the lengths in `chunks`, cycling through them until `data` is exhausted.
+fn split<'a>(data: &'a [u8], chunks: &'a [usize]) -> impl Iterator<Item = &'a [u8]> {
+    let mut rest = data;
+    let mut lens = chunks.iter().cycle();
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let (chunk, tail) = rest.split_at((*lens.next()?).min(rest.len()));
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+fn read_chunked<R: Read>(mut reader: R, chunks: &[usize]) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    for &len in chunks.iter().cycle() {
+        let start = decoded.len();
+        decoded.resize(start + len, 0);
+        let n = reader.read(&mut decoded[start..]).unwrap();
+        decoded.truncate(start + n);
+        if n == 0 {
+            return decoded;
+        }
+    }
+    unreachable!()
+}
+
+fn write_v1(payload: &[u8], chunks: &[usize]) -> Vec<u8> {
+    let mut writer = v1::Writer::new(Vec::new()).unwrap();
+    for chunk in split(payload, chunks) {
+        writer.write_all(chunk).unwrap();
+    }
+    writer.into_inner()
+}
+
+fn write_v2(payload: &[u8], chunks: &[usize]) -> Vec<u8> {
+    let mut writer = v2::Writer::new(Cursor::new(Vec::new())).unwrap();
+    for chunk in split(payload, chunks) {
+        writer.write_all(chunk).unwrap();
+    }
+    writer.into_inner().unwrap().into_inner()
+}
+
+/// A seek to a valid offset within a payload of the given length, expressed in any of the three
+/// ways `SeekFrom` allows, followed by the number of bytes to read.
+#[derive(Clone, Copy, Debug)]
+struct SeekOp {
+    from: u8,
+    offset: usize,
+    read: usize,
+}
+
+fn seek_ops() -> impl Strategy<Value = Vec<SeekOp>> {
+    vec(
+        (0..3u8, 0..=MAX_LEN, 0..32 * 1024usize).prop_map(|(from, offset, read)| SeekOp {
+            from,
+            offset,
+            read,
+        }),
+        1..16,
+    )
+}
+
+fn check_seeks<R: Read + Seek>(
+    mut reader: R,
+    payload: &[u8],
+    ops: &[SeekOp],
+) -> Result<(), proptest::test_runner::TestCaseError> {
+    let mut pos = 0;
+    for op in ops {
+        let target = op.offset % (payload.len() + 1);
+        let from = match op.from {
+            0 => SeekFrom::Start(target as u64),
+            1 => SeekFrom::Current(target as i64 - pos as i64),
+            _ => SeekFrom::End(target as i64 - payload.len() as i64),
+        };
+        prop_assert_eq!(reader.seek(from).unwrap(), target as u64);
+        let end = (target + op.read).min(payload.len());
+        let mut buffer = vec![0; end - target];
+        reader.read_exact(&mut buffer).unwrap();
+        prop_assert_eq!(&buffer[..], &payload[target..end]);
+        pos = end;
+    }
+    Ok(())
+}
+
+#[test]
+fn fixtures_reencode_identically() {
+    let mut count = 0;
+    for entry in fs::read_dir("tests/fixtures").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "dat") {
+            let encoded = fs::read(&path).unwrap();
+            assert!(roundtrip_identity(&encoded).unwrap(), "{}", path.display());
+            count += 1;
+        }
+    }
+    assert!(count >= 3);
+}
+
+proptest! {
+    #![proptest_config(Config::with_cases(32))]
+
+    #[test]
+    fn v1_reencode_identity(payload in payload()) {
+        // Every byte string after a Redshirt 1 marker is a valid encoded payload.
+        let mut encoded = v1::encode(&[]);
+        encoded.extend_from_slice(&payload);
+        prop_assert_eq!(v1::encode(&v1::decode(&encoded).unwrap()), &encoded[..]);
+        prop_assert_eq!(roundtrip_identity(&encoded).unwrap(), true);
+    }
+
+    #[test]
+    fn v2_reencode_identity(payload in payload(), chunks in chunks()) {
+        let encoded = write_v2(&payload, &chunks);
+        prop_assert_eq!(v2::encode(&v2::decode(&encoded).unwrap()), &encoded[..]);
+        prop_assert_eq!(roundtrip_identity(&encoded).unwrap(), true);
+    }
+
+    #[test]
+    fn v1_encode_decode(payload in payload()) {
+        prop_assert_eq!(v1::decode(&v1::encode(&payload)).unwrap(), payload);
+    }
+
+    #[test]
+    fn v2_encode_decode(payload in payload()) {
+        prop_assert_eq!(v2::decode(&v2::encode(&payload)).unwrap(), payload);
+    }
+
+    #[test]
+    fn v1_split_writes(payload in payload(), chunks in chunks()) {
+        prop_assert_eq!(write_v1(&payload, &chunks), v1::encode(&payload));
+    }
+
+    #[test]
+    fn v2_split_writes(payload in payload(), chunks in chunks()) {
+        prop_assert_eq!(write_v2(&payload, &chunks), v2::encode(&payload));
+    }
+
+    #[test]
+    fn v2_digest_invariant(payload in payload(), a in chunks(), b in chunks()) {
+        let a = write_v2(&payload, &a);
+        let b = write_v2(&payload, &b);
+        prop_assert_eq!(&a[..V2_HEADER_LEN], &b[..V2_HEADER_LEN]);
+        prop_assert_eq!(v2::verify(&a[..]).unwrap(), v2::verify(&b[..]).unwrap());
+    }
+
+    #[test]
+    fn v1_chunked_reads(payload in payload(), chunks in chunks()) {
+        let reader = v1::Reader::new(Cursor::new(v1::encode(&payload))).unwrap();
+        prop_assert_eq!(read_chunked(reader, &chunks), payload);
+    }
+
+    #[test]
+    fn v2_chunked_reads(payload in payload(), chunks in chunks()) {
+        let reader = v2::Reader::new(Cursor::new(v2::encode(&payload))).unwrap();
+        prop_assert_eq!(read_chunked(reader, &chunks), payload);
+    }
+
+    #[test]
+    fn v1_seeks(payload in payload(), ops in seek_ops()) {
+        let reader = v1::Reader::new(Cursor::new(v1::encode(&payload))).unwrap();
+        check_seeks(reader, &payload, &ops)?;
+    }
+
+    #[test]
+    fn v2_seeks(payload in payload(), ops in seek_ops()) {
+        let reader = v2::Reader::new(Cursor::new(v2::encode(&payload))).unwrap();
+        check_seeks(reader, &payload, &ops)?;
+    }
+}
@@ -0,0 +1,74 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use redshirt::{inspect_reader, v1, Digest, Info, Version};
+use std::io::Cursor;
+
+const MSG_ENC_V2: &[u8] = b"REDSHRT2\x00\x34\x54\x26\x2B\x4A\xBF\x29\x1D\x0B\x8E\x60\xD9\xA1\x76\xE1\x14\x7D\xDF\x05\xD4\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+const MSG_DIGEST: &str = "3454262b4abf291d0b8e60d9a176e1147ddf05d4";
+
+#[test]
+fn version() {
+    assert_eq!(serde_json::to_string(&Version::V1).unwrap(), "\"v1\"");
+    assert_eq!(serde_json::to_string(&Version::V2).unwrap(), "\"v2\"");
+    let version: Version = serde_json::from_str("\"v2\"").unwrap();
+    assert_eq!(version, Version::V2);
+    assert!(serde_json::from_str::<Version>("\"V2\"").is_err());
+}
+
+#[test]
+fn digest() {
+    let digest = inspect_reader(Cursor::new(MSG_ENC_V2), false)
+        .unwrap()
+        .stored_digest
+        .unwrap();
+    let json = serde_json::to_string(&digest).unwrap();
+    assert_eq!(json, format!("\"{}\"", MSG_DIGEST));
+    assert_eq!(serde_json::from_str::<Digest>(&json).unwrap(), digest);
+    let upper = format!("\"{}\"", MSG_DIGEST.to_uppercase());
+    assert_eq!(serde_json::from_str::<Digest>(&upper).unwrap(), digest);
+}
+
+#[test]
+fn digest_malformed() {
+    for hex in [
+        "",
+        "3454262b4abf291d0b8e60d9a176e1147ddf05d",
+        "3454262b4abf291d0b8e60d9a176e1147ddf05d4a",
+        "3454262b4abf291d0b8e60d9a176e1147ddf05dg",
+        "+454262b4abf291d0b8e60d9a176e1147ddf05d4",
+        " 454262b4abf291d0b8e60d9a176e1147ddf05d4",
+    ] {
+        let json = format!("\"{}\"", hex);
+        assert!(serde_json::from_str::<Digest>(&json).is_err(), "{}", hex);
+    }
+    assert!(serde_json::from_str::<Digest>("[52, 84]").is_err());
+    assert!(serde_json::from_str::<Digest>("null").is_err());
+}
+
+#[test]
+fn info() {
+    let v2 = inspect_reader(Cursor::new(MSG_ENC_V2), true).unwrap();
+    let json = serde_json::to_string(&v2).unwrap();
+    assert_eq!(
+        json,
+        format!(
+            "{{\"version\":\"v2\",\"header_len\":29,\"payload_len\":12,\"stored_digest\":\"{}\",\"verified\":true}}",
+            MSG_DIGEST
+        )
+    );
+    assert_eq!(serde_json::from_str::<Info>(&json).unwrap(), v2);
+    let v1 = inspect_reader(Cursor::new(v1::encode(b"foo")), true).unwrap();
+    let json = serde_json::to_string(&v1).unwrap();
+    assert_eq!(
+        json,
+        "{\"version\":\"v1\",\"header_len\":9,\"payload_len\":3,\"stored_digest\":null,\"verified\":null}"
+    );
+    assert_eq!(serde_json::from_str::<Info>(&json).unwrap(), v1);
+}
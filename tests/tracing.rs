@@ -0,0 +1,137 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use redshirt::{v1, v2};
+use std::{
+    fmt::{self, Write as _},
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    sync::{Arc, Mutex},
+};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+
+const MSG_DEC: &[u8] = b"Hello world!";
+const MSG_ENC_V1: &[u8] = b"REDSHIRT\x00\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+const MSG_ENC_V2: &[u8] = b"REDSHRT2\x00\x34\x54\x26\x2B\x4A\xBF\x29\x1D\x0B\x8E\x60\xD9\xA1\x76\xE1\x14\x7D\xDF\x05\xD4\xC8\xE5\xEC\xEC\xEF\xA0\xF7\xEF\xF2\xEC\xE4\xA1";
+
+/// Records every span and event as a line of `name=value` pairs.
+struct Capture(Arc<Mutex<Vec<String>>>);
+
+struct Line(String);
+
+impl Visit for Line {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let _ = write!(self.0, " {}={value:?}", field.name());
+    }
+}
+
+impl Subscriber for Capture {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+        let mut line = Line(format!("span {}", span.metadata().name()));
+        span.record(&mut line);
+        self.0.lock().unwrap().push(line.0);
+        span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut line = Line(format!("event {}", event.metadata().level()));
+        event.record(&mut line);
+        self.0.lock().unwrap().push(line.0);
+    }
+
+    fn enter(&self, _: &span::Id) {}
+
+    fn exit(&self, _: &span::Id) {}
+}
+
+fn capture<F: FnOnce()>(f: F) -> Vec<String> {
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    tracing::subscriber::with_default(Capture(Arc::clone(&lines)), f);
+    let lines = lines.lock().unwrap();
+    lines.clone()
+}
+
+fn find<'a>(lines: &'a [String], message: &str) -> &'a str {
+    lines
+        .iter()
+        .find(|line| line.contains(message))
+        .unwrap_or_else(|| panic!("no event containing {:?} in {:#?}", message, lines))
+}
+
+#[test]
+fn reader_events() {
+    let lines = capture(|| {
+        let _ = v1::Reader::new(MSG_ENC_V1).unwrap();
+        let _ = v2::Reader::new(Cursor::new(MSG_ENC_V2)).unwrap();
+    });
+    let v1 = find(&lines, "reading Redshirt 1 stream");
+    assert!(v1.contains("version=1 header_len=9"));
+    let v2 = find(&lines, "reading Redshirt 2 stream");
+    assert!(v2.contains("version=2 header_len=29"));
+    assert!(v2.contains("verification=Eager"));
+    assert!(find(&lines, "span verify").contains("expected="));
+}
+
+#[test]
+fn checksum_mismatch_events() {
+    let mut corrupt = MSG_ENC_V2.to_vec();
+    *corrupt.last_mut().unwrap() ^= 0xFF;
+    let lines = capture(|| {
+        assert!(v2::Reader::new(Cursor::new(&corrupt)).is_err());
+        let mut reader = v2::ReaderOptions::new()
+            .verification(v2::Verification::Lazy)
+            .from_reader(Cursor::new(&corrupt))
+            .unwrap();
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    });
+    let mismatches: Vec<_> = lines
+        .iter()
+        .filter(|line| line.contains("SHA-1 hash mismatch"))
+        .collect();
+    assert_eq!(mismatches.len(), 2);
+    for mismatch in mismatches {
+        assert!(mismatch.starts_with("event WARN"));
+        assert!(mismatch.contains(" expected=") && mismatch.contains(" actual="));
+    }
+}
+
+#[test]
+fn writer_events() {
+    let lines = capture(|| {
+        let mut writer = v2::Writer::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_all(MSG_DEC).unwrap();
+        let _ = writer.into_inner().unwrap();
+        let mut writer = v2::Writer::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_all(b"foo").unwrap();
+    });
+    let finish = find(&lines, "trigger=\"into_inner\"");
+    assert!(finish.contains("payload_len=12"));
+    let drop = find(&lines, "trigger=\"drop\"");
+    assert!(drop.contains("payload_len=3"));
+}
+
+#[test]
+fn seek_error_events() {
+    let lines = capture(|| {
+        let mut reader = v1::Reader::new(Cursor::new(MSG_ENC_V1)).unwrap();
+        let mut buffer = [0; 4];
+        reader.read_exact(&mut buffer).unwrap();
+        assert!(reader.seek(SeekFrom::Current(-5)).is_err());
+    });
+    let seek = find(&lines, "seek failed");
+    assert!(seek.contains("offset=4 underlying=13"));
+}
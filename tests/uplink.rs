@@ -0,0 +1,150 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Walks `tests/fixtures/world.v2.dat`, a small synthetic file laid out like the location index of
+//! an Uplink `world.dat`: a `BTree` of locations keyed by IP address, followed by an `LList` of
+//! strings and a `DArray` of integers. Also checks that containers written back after reading are
+//! byte-for-byte identical.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use proptest::{
+    collection::vec, option, prop_assert_eq, proptest, strategy::Strategy, test_runner::Config,
+};
+use redshirt::{
+    uplink::{
+        read_btree, read_darray, read_llist, write_btree, write_darray, write_llist,
+        ReadPrimitives, UplinkError, WritePrimitives,
+    },
+    v2,
+};
+use std::io::{Cursor, Read};
+
+const WORLD: &[u8] = include_bytes!("fixtures/world.v2.dat");
+
+#[derive(Debug, PartialEq)]
+struct Location {
+    x: i32,
+    y: i32,
+    listed: bool,
+}
+
+fn read_location<R: ReadPrimitives>(src: &mut R) -> Result<Location, UplinkError> {
+    Ok(Location {
+        x: src.read_i32_le()?,
+        y: src.read_i32_le()?,
+        listed: src.read_bool()?,
+    })
+}
+
+fn write_location<W: WritePrimitives>(dst: &mut W, location: &Location) -> Result<(), UplinkError> {
+    dst.write_i32_le(location.x)?;
+    dst.write_i32_le(location.y)?;
+    dst.write_bool(location.listed)
+}
+
+fn location(ip: &str, x: i32, y: i32, listed: bool) -> (String, Location) {
+    (ip.to_owned(), Location { x, y, listed })
+}
+
+#[test]
+fn walk_world() {
+    let mut src = v2::Reader::new(Cursor::new(WORLD)).unwrap();
+    let locations = read_btree(&mut src, read_location).unwrap();
+    assert_eq!(
+        locations,
+        [
+            location("234.773.0.666", 120, 45, true),
+            location("128.185.0.4", 30, 60, true),
+            location("173.22.180.14", 88, 12, false),
+            location("458.615.48.651", 200, 150, true),
+        ]
+    );
+    let names = read_llist(&mut src, ReadPrimitives::read_string).unwrap();
+    assert_eq!(names, ["Uplink Corporation", "Uplink Test Machine", ""]);
+    let slots = read_darray(&mut src, |src| src.read_i32_le().map(Some)).unwrap();
+    assert_eq!(slots, [Some(7), None, Some(9)]);
+    assert_eq!(src.read(&mut [0]).unwrap(), 0);
+}
+
+#[test]
+fn corrupt_world() {
+    let mut data = v2::decode(WORLD).unwrap();
+    // The value of the root node ends with its `listed` flag at offset 27; the left subtree's
+    // presence flag follows it.
+    data[28] = 2;
+    let mut src = v2::Reader::new(Cursor::new(v2::encode(&data))).unwrap();
+    let e = read_btree(&mut src, read_location).unwrap_err();
+    assert_eq!(e.offset(), Some(28));
+    assert_eq!(e.to_string(), "offset 0x1c: bad boolean 0x02");
+}
+
+#[test]
+fn rewrite_world() {
+    let decoded = v2::decode(WORLD).unwrap();
+    let mut src = Cursor::new(&decoded[..]);
+    let locations = read_btree(&mut src, read_location).unwrap();
+    let names = read_llist(&mut src, ReadPrimitives::read_optional_string).unwrap();
+    let slots = read_darray(&mut src, |src| src.read_i32_le().map(Some)).unwrap();
+    let mut data = Vec::new();
+    write_btree(&mut data, &locations, write_location).unwrap();
+    write_llist(&mut data, &names, |dst, name| {
+        dst.write_optional_string(name.as_deref())
+    })
+    .unwrap();
+    write_darray(&mut data, &slots, |dst, &n| dst.write_i32_le(n)).unwrap();
+    assert_eq!(data, decoded);
+}
+
+/// Latin-1 keys from a small alphabet, so that trees contain duplicate keys.
+fn keys() -> impl Strategy<Value = Vec<String>> {
+    vec("[a-d\u{e0}-\u{e3}]{0,3}", 0..64)
+}
+
+proptest! {
+    #![proptest_config(Config::with_cases(64))]
+
+    #[test]
+    fn darray_round_trip(slots in vec(option::of(option::of("[ -~\u{a0}-\u{ff}]{0,8}")), 0..64)) {
+        let write = |dst: &mut Vec<u8>, s: &Option<String>| dst.write_optional_string(s.as_deref());
+        let mut data = Vec::new();
+        write_darray(&mut data, &slots, write).unwrap();
+        let read = read_darray(&mut Cursor::new(&data[..]), |src| {
+            src.read_optional_string().map(Some)
+        })
+        .unwrap();
+        prop_assert_eq!(&read, &slots);
+        let mut rewritten = Vec::new();
+        write_darray(&mut rewritten, &read, write).unwrap();
+        prop_assert_eq!(rewritten, data);
+    }
+
+    #[test]
+    fn llist_round_trip(elems in vec(0..i32::MAX, 0..64)) {
+        let mut data = Vec::new();
+        write_llist(&mut data, &elems, |dst, &n| dst.write_i32_le(n)).unwrap();
+        let read = read_llist(&mut Cursor::new(&data[..]), ReadPrimitives::read_i32_le).unwrap();
+        prop_assert_eq!(&read, &elems);
+        let mut rewritten = Vec::new();
+        write_llist(&mut rewritten, &read, |dst, &n| dst.write_i32_le(n)).unwrap();
+        prop_assert_eq!(rewritten, data);
+    }
+
+    #[test]
+    fn btree_round_trip(keys in keys()) {
+        let entries = keys.into_iter().zip(0..).collect::<Vec<_>>();
+        let mut data = Vec::new();
+        write_btree(&mut data, &entries, |dst, &n| dst.write_i32_le(n)).unwrap();
+        let read = read_btree(&mut Cursor::new(&data[..]), ReadPrimitives::read_i32_le).unwrap();
+        let mut sorted = read.iter().map(|(_, n)| *n).collect::<Vec<_>>();
+        sorted.sort_unstable();
+        prop_assert_eq!(sorted, (0..entries.len() as i32).collect::<Vec<_>>());
+        let mut rewritten = Vec::new();
+        write_btree(&mut rewritten, &read, |dst, &n| dst.write_i32_le(n)).unwrap();
+        prop_assert_eq!(rewritten, data);
+    }
+}
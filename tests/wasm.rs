@@ -0,0 +1,63 @@
+// Copyright (c) 2019 FaultyRAM
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Runs the slice-based API in a headless browser.
+//!
+//! With `wasm-bindgen-cli` and a WebDriver installed, run these tests with:
+//!
+//! ```text
+//! cargo test --target wasm32-unknown-unknown --no-default-features \
+//!     --features redshirt1,redshirt2 --test wasm
+//! ```
+
+#![cfg(target_arch = "wasm32")]
+
+use redshirt::{v1, v2, Error};
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const HELLO: &[u8] = b"Hello world!";
+const HELLO_V1: &[u8] = include_bytes!("fixtures/hello.v1.dat");
+const HELLO_V2: &[u8] = include_bytes!("fixtures/hello.v2.dat");
+
+#[wasm_bindgen_test]
+fn decode_v1() {
+    assert_eq!(v1::decode(HELLO_V1).unwrap(), HELLO);
+}
+
+#[wasm_bindgen_test]
+fn encode_v1() {
+    assert_eq!(v1::encode(HELLO), HELLO_V1);
+}
+
+#[wasm_bindgen_test]
+fn decode_v2() {
+    assert_eq!(v2::decode(HELLO_V2).unwrap(), HELLO);
+}
+
+#[wasm_bindgen_test]
+fn encode_v2() {
+    assert_eq!(v2::encode(HELLO), HELLO_V2);
+}
+
+#[wasm_bindgen_test]
+fn verify_v2() {
+    let digest = v2::verify(HELLO_V2).unwrap();
+    assert_eq!(digest.as_ref(), &HELLO_V2[9..29]);
+}
+
+#[wasm_bindgen_test]
+fn verify_v2_bad_checksum() {
+    let mut encoded = HELLO_V2.to_vec();
+    let last = encoded.len() - 1;
+    encoded[last] ^= 0xFF;
+    assert!(matches!(
+        v2::verify(&encoded[..]),
+        Err(Error::BadChecksum { .. })
+    ));
+}